@@ -1,4 +1,5 @@
 use crate::commands::Commands;
+use crate::output::OutputFormat;
 
 use clap::{builder::styling::AnsiColor, Parser};
 
@@ -8,6 +9,31 @@ use clap::{builder::styling::AnsiColor, Parser};
 pub struct Cli {
     #[clap(subcommand)]
     pub command: Commands,
+    // Not global: `file append` and `file insert` have their own `--format`
+    #[clap(
+        long,
+        value_enum,
+        default_value_t = OutputFormat::Human,
+        help = "Output format, must precede the command"
+    )]
+    pub format: OutputFormat,
+    #[clap(
+        long,
+        global = true,
+        action = clap::ArgAction::SetTrue,
+        help = "Print output as JSON, same as --format json"
+    )]
+    pub json: bool,
+}
+
+impl Cli {
+    pub fn output_format(&self) -> OutputFormat {
+        if self.json {
+            OutputFormat::Json
+        } else {
+            self.format
+        }
+    }
 }
 
 pub fn styles() -> clap::builder::Styles {
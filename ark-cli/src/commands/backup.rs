@@ -1,21 +1,73 @@
 use std::io::Write;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+
+use fs_storage::{INDEX_PATH, PREVIEWS_STORAGE_FOLDER};
+use serde::{Deserialize, Serialize};
+use walkdir::WalkDir;
 
 use crate::{
     create_dir_all, dir, discover_roots, home_dir, storages_exists, timestamp,
     AppError, CopyOptions, File, ARK_BACKUPS_PATH, ARK_FOLDER,
-    ROOTS_CFG_FILENAME,
+    RESOURCE_ID_KIND, ROOTS_CFG_FILENAME,
 };
 
+/// Name of the manifest entry, always the first one in an archive
+pub const MANIFEST_FILE: &str = "manifest.json";
+/// Version of the archive layout
+pub const ARCHIVE_FORMAT: u32 = 1;
+
+/// Describes the content of a backup archive
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Manifest {
+    pub format: u32,
+    /// Version of ark-cli which created the archive
+    pub ark_version: String,
+    /// Hash function used for resource ids in the archived storages
+    pub hash: String,
+    /// Creation time, in seconds since UNIX epoch
+    pub created: u64,
+}
+
 #[derive(Clone, Debug, clap::Args)]
 #[clap(name = "backup", about = "Backup the ark managed folder")]
 pub struct Backup {
-    #[clap(value_parser, help = "Path to the root directory")]
+    #[clap(
+        value_parser,
+        help = "Root directory to archive when --output is given, \
+                roots config otherwise"
+    )]
     roots_cfg: Option<PathBuf>,
+    #[clap(
+        short,
+        long,
+        help = "Archive user data of the root's .ark folder into this \
+                file (tar.zst)"
+    )]
+    output: Option<PathBuf>,
+    #[clap(
+        long,
+        action = clap::ArgAction::SetTrue,
+        help = "Include cached previews and thumbnails in the archive"
+    )]
+    include_cache: bool,
+    #[clap(
+        long,
+        action = clap::ArgAction::SetTrue,
+        help = "Include the resource index in the archive"
+    )]
+    include_index: bool,
 }
 
 impl Backup {
     pub fn run(&self) -> Result<(), AppError> {
+        if let Some(output) = &self.output {
+            let root = match &self.roots_cfg {
+                Some(root) => root.clone(),
+                None => std::env::current_dir()?,
+            };
+            return self.archive(&root, output);
+        }
+
         let timestamp = timestamp().as_secs();
         let backup_dir = home_dir()
             .ok_or(AppError::HomeDirNotFound)?
@@ -86,4 +138,87 @@ impl Backup {
 
         Ok(())
     }
+
+    /// Write the `.ark` folder of `root` into a zstd-compressed tarball.
+    ///
+    /// Caches and the index can be regenerated, so they are skipped
+    /// unless requested explicitly.
+    fn archive(&self, root: &Path, output: &Path) -> Result<(), AppError> {
+        let ark_dir = root.join(ARK_FOLDER);
+        if !ark_dir.is_dir() {
+            return Err(AppError::StorageNotFound(format!(
+                "{} doesn't contain any storages",
+                root.display()
+            )));
+        }
+
+        let cache_dir = Path::new(PREVIEWS_STORAGE_FOLDER)
+            .parent()
+            .map(|cache| ark_dir.join(cache));
+        let index_path = ark_dir.join(INDEX_PATH);
+
+        let backup_error = |e: std::io::Error| {
+            AppError::BackupCreationError(format!(
+                "Couldn't write {}: {}",
+                output.display(),
+                e
+            ))
+        };
+
+        let encoder = zstd::Encoder::new(File::create(output)?, 0)
+            .map_err(backup_error)?;
+        let mut builder = tar::Builder::new(encoder);
+
+        let manifest = Manifest {
+            format: ARCHIVE_FORMAT,
+            ark_version: env!("CARGO_PKG_VERSION").to_owned(),
+            hash: RESOURCE_ID_KIND.to_owned(),
+            created: timestamp().as_secs(),
+        };
+        let manifest = serde_json::to_vec_pretty(&manifest)?;
+        let mut header = tar::Header::new_gnu();
+        header.set_size(manifest.len() as u64);
+        header.set_mode(0o644);
+        header.set_mtime(timestamp().as_secs());
+        header.set_cksum();
+        builder
+            .append_data(&mut header, MANIFEST_FILE, manifest.as_slice())
+            .map_err(backup_error)?;
+
+        let entries = WalkDir::new(&ark_dir)
+            .min_depth(1)
+            .into_iter()
+            .filter_entry(|entry| {
+                let path = entry.path();
+                (self.include_cache || Some(path) != cache_dir.as_deref())
+                    && (self.include_index || path != index_path)
+            });
+
+        let mut archived = 0;
+        for entry in entries {
+            let entry = entry.map_err(std::io::Error::from)?;
+            if !entry.file_type().is_file() {
+                continue;
+            }
+
+            let name = entry.path().strip_prefix(root).map_err(|_| {
+                AppError::BackupCreationError(format!(
+                    "{} is outside of the root",
+                    entry.path().display()
+                ))
+            })?;
+            builder
+                .append_path_with_name(entry.path(), name)
+                .map_err(backup_error)?;
+            archived += 1;
+        }
+
+        builder
+            .into_inner()
+            .and_then(|encoder| encoder.finish())
+            .map_err(backup_error)?;
+
+        println!("Archived {} files into {}", archived, output.display());
+        Ok(())
+    }
 }
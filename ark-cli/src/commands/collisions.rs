@@ -1,6 +1,13 @@
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 
-use crate::{monitor_index, AppError};
+use data_resource::ResourceId as _;
+use dev_hash::Blake3;
+use fs_index::ResourceIndex;
+use serde::Serialize;
+
+use crate::output::{print_records, CollisionGroup, OutputFormat};
+use crate::{provide_root, AppError, ResourceId};
 
 #[derive(Clone, Debug, clap::Args)]
 #[clap(
@@ -12,8 +19,132 @@ pub struct Collisions {
     root_dir: Option<PathBuf>,
 }
 
+/// Paths sharing the same resource id
+#[derive(Debug, Serialize)]
+pub struct IdGroup {
+    pub id: ResourceId,
+    /// Paths split by their actual content: paths inside one
+    /// set are true duplicates, while several sets mean a hash collision
+    pub contents: Vec<Vec<PathBuf>>,
+}
+
+impl IdGroup {
+    pub fn is_collision(&self) -> bool {
+        self.contents.len() > 1
+    }
+}
+
+/// Group all indexed paths which share an id.
+///
+/// Content is compared with a stronger hash to distinguish
+/// duplicated files from different files with colliding ids.
+pub fn id_groups(
+    index: &ResourceIndex<ResourceId>,
+) -> Result<Vec<IdGroup>, AppError> {
+    let mut by_id: HashMap<&ResourceId, Vec<&Path>> = HashMap::new();
+    for (path, entry) in index.path2id.iter() {
+        by_id
+            .entry(&entry.id)
+            .or_default()
+            .push(path.as_ref());
+    }
+
+    let mut groups = Vec::new();
+    for (id, mut paths) in by_id {
+        if paths.len() < 2 {
+            continue;
+        }
+        paths.sort();
+
+        let mut contents: Vec<(Blake3, Vec<PathBuf>)> = Vec::new();
+        for path in paths {
+            let hash = Blake3::from_path(path)?;
+            match contents
+                .iter_mut()
+                .find(|(other, _)| *other == hash)
+            {
+                Some((_, same)) => same.push(path.to_path_buf()),
+                None => contents.push((hash, vec![path.to_path_buf()])),
+            }
+        }
+
+        groups.push(IdGroup {
+            id: id.clone(),
+            contents: contents
+                .into_iter()
+                .map(|(_, paths)| paths)
+                .collect(),
+        });
+    }
+    groups.sort_by(|a, b| a.id.cmp(&b.id));
+
+    Ok(groups)
+}
+
+pub fn relative(root: &Path, path: &Path) -> PathBuf {
+    path.strip_prefix(root)
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|_| path.to_path_buf())
+}
+
 impl Collisions {
-    pub fn run(&self) -> Result<(), AppError> {
-        monitor_index(&self.root_dir, None)
+    pub fn run(&self, format: OutputFormat) -> Result<(), AppError> {
+        let root = provide_root(&self.root_dir)?.canonicalize()?;
+        let index = ResourceIndex::<ResourceId>::provide(&root)?;
+
+        let mut groups = id_groups(&index)?;
+        for group in groups.iter_mut() {
+            for paths in group.contents.iter_mut() {
+                for path in paths.iter_mut() {
+                    *path = relative(&root, path);
+                }
+            }
+        }
+
+        if !format.is_human() {
+            let records: Vec<CollisionGroup> = groups
+                .into_iter()
+                .map(|group| CollisionGroup {
+                    id: group.id.to_string(),
+                    collision: group.is_collision(),
+                    contents: group.contents,
+                })
+                .collect();
+            return print_records(format, &records);
+        }
+
+        if groups.is_empty() {
+            println!("No paths share an id");
+        }
+        for group in groups {
+            let duplicates = group
+                .contents
+                .iter()
+                .filter(|paths| paths.len() > 1)
+                .map(|paths| paths.len())
+                .sum::<usize>();
+            println!(
+                "Id {}: {} duplicates{}",
+                group.id,
+                duplicates,
+                if group.is_collision() {
+                    ", hash collision"
+                } else {
+                    ""
+                }
+            );
+            for (i, paths) in group.contents.iter().enumerate() {
+                let mark = if paths.len() > 1 {
+                    "duplicate"
+                } else {
+                    "collision"
+                };
+                for path in paths {
+                    println!("\t[{}] {: <10} {}", i + 1, mark, path.display());
+                }
+            }
+        }
+
+        Ok(())
     }
 }
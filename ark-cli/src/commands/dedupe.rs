@@ -0,0 +1,169 @@
+use std::io::{BufRead, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use fs_index::ResourceIndex;
+
+use crate::{delete_file, provide_root, AppError, ResourceId};
+
+use super::collisions::{id_groups, relative};
+
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+pub enum Keep {
+    /// Keep the most recently modified copy
+    Newest,
+    /// Keep the least recently modified copy
+    Oldest,
+    /// Keep the first copy in path order
+    First,
+}
+
+#[derive(Clone, Debug, clap::Args)]
+#[clap(
+    name = "dedupe",
+    about = "Remove duplicated copies of resources (dry run by default)"
+)]
+pub struct Dedupe {
+    #[clap(value_parser, help = "Path to the root directory")]
+    root_dir: Option<PathBuf>,
+    #[clap(
+        long,
+        value_enum,
+        help = "Which copy to keep, asked interactively if omitted"
+    )]
+    keep: Option<Keep>,
+    #[clap(
+        long,
+        action = clap::ArgAction::SetTrue,
+        help = "Actually delete the duplicates"
+    )]
+    apply: bool,
+    #[clap(
+        long,
+        action = clap::ArgAction::SetTrue,
+        help = "Move duplicates to the trash instead of deleting them"
+    )]
+    trash: bool,
+}
+
+impl Dedupe {
+    pub fn run(&self) -> Result<(), AppError> {
+        if self.trash && !cfg!(feature = "trash") {
+            return Err(AppError::UnsupportedOption(
+                "--trash requires ark-cli built with the `trash` feature"
+                    .to_owned(),
+            ));
+        }
+        if self.keep.is_none() && !std::io::stdin().is_terminal() {
+            return Err(AppError::UnsupportedOption(
+                "--keep is required when not running interactively".to_owned(),
+            ));
+        }
+
+        let root = provide_root(&self.root_dir)?.canonicalize()?;
+        let mut index = ResourceIndex::<ResourceId>::provide(&root)?;
+
+        let mut reclaimed = 0;
+        let mut removed = 0;
+        for group in id_groups(&index)? {
+            // Tags and properties are attached to the resource id, which
+            // is shared by all the duplicates, so the kept copy inherits
+            // them and nothing needs to be relocated.
+            for paths in group
+                .contents
+                .iter()
+                .filter(|paths| paths.len() > 1)
+            {
+                let Some(kept) = self.choose(&root, &group.id, paths)? else {
+                    continue;
+                };
+
+                for path in paths.iter().filter(|path| **path != kept) {
+                    let size = std::fs::metadata(path)?.len();
+                    if self.apply {
+                        delete_file(path, self.trash)?;
+                        println!("Removed {}", relative(&root, path).display());
+                    } else {
+                        println!(
+                            "Would remove {} (keeping {})",
+                            relative(&root, path).display(),
+                            relative(&root, &kept).display()
+                        );
+                    }
+                    reclaimed += size;
+                    removed += 1;
+                }
+            }
+        }
+
+        if self.apply {
+            if removed > 0 {
+                index.update_all()?;
+                index.store()?;
+            }
+            println!(
+                "Removed {} files, reclaimed {} bytes",
+                removed, reclaimed
+            );
+        } else {
+            println!(
+                "Would remove {} files, reclaiming {} bytes. \
+                 Use --apply to delete them",
+                removed, reclaimed
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Pick the copy to keep, `None` means the group should be left as is
+    fn choose(
+        &self,
+        root: &Path,
+        id: &ResourceId,
+        paths: &[PathBuf],
+    ) -> Result<Option<PathBuf>, AppError> {
+        let modified = |path: &PathBuf| {
+            std::fs::metadata(path)
+                .and_then(|metadata| metadata.modified())
+                .unwrap_or(SystemTime::UNIX_EPOCH)
+        };
+
+        let kept = match self.keep {
+            Some(Keep::Newest) => paths.iter().max_by_key(|p| modified(p)),
+            Some(Keep::Oldest) => paths.iter().min_by_key(|p| modified(p)),
+            Some(Keep::First) => paths.iter().min(),
+            None => return prompt(root, id, paths),
+        };
+        Ok(kept.cloned())
+    }
+}
+
+fn prompt(
+    root: &Path,
+    id: &ResourceId,
+    paths: &[PathBuf],
+) -> Result<Option<PathBuf>, AppError> {
+    println!("Duplicates of {}:", id);
+    for (i, path) in paths.iter().enumerate() {
+        println!("\t[{}] {}", i + 1, relative(root, path).display());
+    }
+    print!("Copy to keep (empty to skip): ");
+    std::io::stdout().flush()?;
+
+    let mut answer = String::new();
+    std::io::stdin().lock().read_line(&mut answer)?;
+    let answer = answer.trim();
+    if answer.is_empty() {
+        return Ok(None);
+    }
+
+    answer
+        .parse::<usize>()
+        .ok()
+        .and_then(|i| i.checked_sub(1))
+        .and_then(|i| paths.get(i))
+        .cloned()
+        .map(Some)
+        .ok_or(AppError::InvalidEntryOption)
+}
@@ -0,0 +1,68 @@
+use std::path::PathBuf;
+
+use fs_index::ResourceIndex;
+use serde_json::Value;
+
+use crate::commands::collisions::relative;
+use crate::{metadata, provide_root, resolve_resource, AppError, ResourceId};
+
+#[derive(Clone, Debug, clap::Args)]
+#[clap(name = "info", about = "Print a resource and its metadata")]
+pub struct Info {
+    #[clap(short, long, help = "Root directory of the ark managed folder")]
+    root_dir: Option<PathBuf>,
+    #[clap(help = "Path or id of the resource")]
+    resource: String,
+}
+
+impl Info {
+    pub fn run(&self) -> Result<(), AppError> {
+        let root = provide_root(&self.root_dir)?.canonicalize()?;
+        let index = ResourceIndex::<ResourceId>::provide(&root)?;
+
+        let id = resolve_resource(&index, &self.resource)?;
+        let mut paths: Vec<PathBuf> = index
+            .path2id
+            .iter()
+            .filter(|(_, entry)| entry.id == id)
+            .map(|(path, _)| path.clone().into_path_buf())
+            .collect();
+        paths.sort();
+        let size = std::fs::metadata(&paths[0])?.len();
+
+        println!("id:         {}", id);
+        for path in &paths {
+            println!("path:       {}", relative(&root, path).display());
+        }
+        println!("size:       {} bytes", size);
+
+        let tags = metadata::read_tags(&root, &id)?;
+        let score = metadata::read_score(&root, &id)?;
+        let none = || "-".to_owned();
+        println!(
+            "tags:       {}",
+            tags.map_or_else(none, |tags| tags.to_string())
+        );
+        println!(
+            "score:      {}",
+            score.map_or_else(none, |score| score.to_string())
+        );
+        println!(
+            "properties: {}",
+            metadata::read_properties(&root, &id)?
+                .map_or_else(none, |properties| summary(&properties))
+        );
+
+        Ok(())
+    }
+}
+
+/// Names of the properties, the value itself if it isn't an object
+fn summary(properties: &Value) -> String {
+    match properties {
+        Value::Object(map) => {
+            map.keys().cloned().collect::<Vec<_>>().join(", ")
+        }
+        value => value.to_string(),
+    }
+}
@@ -1,8 +1,11 @@
 use clap::Subcommand;
 
 mod append;
+mod info;
 mod insert;
 mod read;
+mod replace;
+mod rm;
 mod utils;
 
 /// Available commands for the `file` subcommand
@@ -11,6 +14,9 @@ pub enum File {
     Append(append::Append),
     Insert(insert::Insert),
     Read(read::Read),
+    Rm(rm::Rm),
+    Replace(replace::Replace),
+    Info(info::Info),
 }
 
 pub use utils::{file_append, file_insert, format_file, format_line};
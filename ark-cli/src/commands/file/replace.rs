@@ -0,0 +1,73 @@
+use std::path::PathBuf;
+
+use canonical_path::CanonicalPathBuf;
+use data_resource::ResourceId as _;
+use fs_index::ResourceIndex;
+
+use crate::commands::collisions::relative;
+use crate::{metadata, provide_root, AppError, ResourceId};
+
+#[derive(Clone, Debug, clap::Args)]
+#[clap(
+    name = "replace",
+    about = "Replace the content of a resource, keeping its metadata"
+)]
+pub struct Replace {
+    #[clap(short, long, help = "Root directory of the ark managed folder")]
+    root_dir: Option<PathBuf>,
+    #[clap(help = "Path of the resource")]
+    path: PathBuf,
+    #[clap(help = "File to copy the new content from")]
+    new_content: PathBuf,
+}
+
+impl Replace {
+    pub fn run(&self) -> Result<(), AppError> {
+        let root = provide_root(&self.root_dir)?.canonicalize()?;
+        let path = CanonicalPathBuf::canonicalize(&self.path)?;
+        let mut index = ResourceIndex::<ResourceId>::provide(&root)?;
+
+        let old_id = index
+            .path2id
+            .get(&path)
+            .map(|entry| entry.id.clone())
+            .ok_or_else(|| {
+                AppError::IndexError(format!(
+                    "{} is not indexed",
+                    self.path.display()
+                ))
+            })?;
+        let display = relative(&root, path.as_ref());
+
+        let new_id = ResourceId::from_path(&self.new_content)?;
+        if new_id == old_id {
+            println!("{} already has this content", display.display());
+            return Ok(());
+        }
+
+        std::fs::copy(&self.new_content, &path)?;
+        index.update_one(&path, old_id.clone())?;
+        index.store()?;
+        println!("Replaced {}: {} -> {}", display.display(), old_id, new_id);
+
+        // Duplicates of the old content keep their metadata
+        let keep_old = index.id2path.contains_key(&old_id);
+        let carried = metadata::relocate(&root, &old_id, &new_id, keep_old)?;
+        if carried.is_empty() {
+            println!("No metadata to carry over");
+        } else {
+            println!(
+                "{} {} to {}",
+                if keep_old {
+                    "Copied"
+                } else {
+                    "Moved"
+                },
+                carried.join(", "),
+                new_id
+            );
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,99 @@
+use std::io::{BufRead, IsTerminal, Write};
+use std::path::PathBuf;
+
+use canonical_path::CanonicalPathBuf;
+use fs_index::ResourceIndex;
+
+use crate::commands::collisions::relative;
+use crate::{delete_file, metadata, provide_root, AppError, ResourceId};
+
+#[derive(Clone, Debug, clap::Args)]
+#[clap(name = "rm", about = "Remove a resource from the folder and the index")]
+pub struct Rm {
+    #[clap(short, long, help = "Root directory of the ark managed folder")]
+    root_dir: Option<PathBuf>,
+    #[clap(help = "Path of the resource")]
+    path: PathBuf,
+    #[clap(
+        long,
+        action = clap::ArgAction::SetTrue,
+        help = "Keep tags, scores and properties of the resource"
+    )]
+    keep_meta: bool,
+    #[clap(
+        short,
+        long,
+        action = clap::ArgAction::SetTrue,
+        help = "Remove tags, scores and properties without asking"
+    )]
+    yes: bool,
+    #[clap(
+        long,
+        action = clap::ArgAction::SetTrue,
+        help = "Move the file to the trash instead of deleting it"
+    )]
+    trash: bool,
+}
+
+impl Rm {
+    pub fn run(&self) -> Result<(), AppError> {
+        let root = provide_root(&self.root_dir)?.canonicalize()?;
+        let path = CanonicalPathBuf::canonicalize(&self.path)?;
+        let mut index = ResourceIndex::<ResourceId>::provide(&root)?;
+
+        let id = index
+            .path2id
+            .get(&path)
+            .map(|entry| entry.id.clone())
+            .ok_or_else(|| {
+                AppError::IndexError(format!(
+                    "{} is not indexed",
+                    self.path.display()
+                ))
+            })?;
+
+        delete_file(path.as_ref(), self.trash)?;
+        index.update_all()?;
+        index.store()?;
+        println!(
+            "Removed {} ({})",
+            relative(&root, path.as_ref()).display(),
+            id
+        );
+
+        // Duplicates share the id and its metadata
+        if let Some(other) = index.id2path.get(&id) {
+            println!(
+                "Kept metadata of {}, still used by {}",
+                id,
+                relative(&root, other.as_ref()).display()
+            );
+            return Ok(());
+        }
+
+        let attached = metadata::attached(&root, &id)?;
+        if attached.is_empty() {
+            return Ok(());
+        }
+
+        let remove = !self.keep_meta
+            && (self.yes || (std::io::stdin().is_terminal() && confirm()?));
+        if remove {
+            let removed = metadata::remove(&root, &id)?;
+            println!("Removed {} of {}", removed.join(", "), id);
+        } else {
+            println!("Kept {} of {}", attached.join(", "), id);
+        }
+
+        Ok(())
+    }
+}
+
+fn confirm() -> Result<bool, AppError> {
+    print!("Remove tags, scores and properties of the resource? [y/N] ");
+    std::io::stdout().flush()?;
+
+    let mut answer = String::new();
+    std::io::stdin().lock().read_line(&mut answer)?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
@@ -122,18 +122,12 @@ pub fn format_file(file: &AtomicFile) -> Option<String> {
         return None;
     }
 
-    let mut split = current
-        .path
-        .file_name()
-        .expect("Not a file")
-        .to_str()
-        .unwrap()
-        .split('_');
-
-    let name = split.next().unwrap();
-
-    let machine = split.next().unwrap();
-    let machine = &machine[..machine.len() - 2];
+    let mut split = current.path.file_name()?.to_str()?.split('_');
+
+    let name = split.next()?;
+
+    let machine = split.next()?;
+    let machine = machine.get(..machine.len().checked_sub(2)?)?;
 
     Some(format_line(
         current.version,
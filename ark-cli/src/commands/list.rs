@@ -1,6 +1,7 @@
 use std::io::Read;
 use std::path::PathBuf;
 
+use crate::output::{print_records, ListEntry, OutputFormat};
 use crate::{
     provide_index, provide_root, read_storage_value, AppError, DateTime,
     EntryOutput, File, Sort, StorageEntry, Utc,
@@ -64,20 +65,21 @@ impl List {
         }
     }
 
-    pub fn run(&self) -> Result<(), AppError> {
+    pub fn run(&self, format: OutputFormat) -> Result<(), AppError> {
         let root = provide_root(&self.root_dir)?;
         let entry_output = self.entry()?;
 
-        let mut storage_entries: Vec<StorageEntry> = provide_index(&root)
-            .map_err(|_| {
-                AppError::IndexError("Could not provide index".to_owned())
-            })?
-            .read()
-            .map_err(|_| {
-                AppError::IndexError("Could not read index".to_owned())
-            })?
-            .path2id
-            .iter()
+        let index = provide_index(&root).map_err(|_| {
+            AppError::IndexError("Could not provide index".to_owned())
+        })?;
+        let index = index.read().map_err(|_| {
+            AppError::IndexError("Could not read index".to_owned())
+        })?;
+        let mut resources: Vec<_> = index.path2id.iter().collect();
+        resources.sort_by(|a, b| a.0.cmp(b.0));
+
+        let mut storage_entries: Vec<StorageEntry> = resources
+            .into_iter()
             .filter_map(|(path, resource)| {
                 let tags = if self.tags {
                     Some(
@@ -111,16 +113,14 @@ impl List {
                     None
                 };
 
-                let datetime = if self.modified {
-                    let format = "%b %e %H:%M %Y";
-                    Some(
-                        DateTime::<Utc>::from(resource.modified)
-                            .format(format)
-                            .to_string(),
-                    )
+                let modified = if self.modified {
+                    Some(DateTime::<Utc>::from(resource.modified))
                 } else {
                     None
                 };
+                let datetime = modified.map(|modified| {
+                    modified.format("%b %e %H:%M %Y").to_string()
+                });
 
                 let (path, resource, content) = match entry_output {
                     EntryOutput::Both => (
@@ -161,6 +161,7 @@ impl List {
                     tags,
                     scores,
                     datetime,
+                    modified,
                 })
             })
             .collect::<Vec<_>>();
@@ -186,6 +187,26 @@ impl List {
             });
         }
 
+        if !format.is_human() {
+            let root = root.canonicalize()?;
+            let records: Vec<ListEntry> = storage_entries
+                .into_iter()
+                .map(|entry| ListEntry {
+                    id: entry.resource.map(|id| id.to_string()),
+                    path: entry.path.map(|path| {
+                        path.strip_prefix(&root)
+                            .map(|path| path.to_path_buf())
+                            .unwrap_or(path)
+                    }),
+                    link: entry.content,
+                    tags: entry.tags,
+                    score: entry.scores,
+                    modified: entry.modified,
+                })
+                .collect();
+            return print_records(format, &records);
+        }
+
         let no_tags = "NO_TAGS";
         let no_scores = "NO_SCORE";
 
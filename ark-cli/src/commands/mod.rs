@@ -2,22 +2,34 @@ use clap::Subcommand;
 
 mod backup;
 mod collisions;
+mod dedupe;
 pub mod file;
 pub mod link;
 mod list;
 mod monitor;
 mod render;
+mod restore;
+mod schema;
+pub mod scores;
+pub mod stats;
 pub mod storage;
+mod verify;
+mod watch;
 
 pub use file::{file_append, file_insert, format_file, format_line};
 
 #[derive(Debug, Subcommand)]
 pub enum Commands {
     Backup(backup::Backup),
+    Restore(restore::Restore),
     Collisions(collisions::Collisions),
+    Dedupe(dedupe::Dedupe),
     Monitor(monitor::Monitor),
     Render(render::Render),
     List(list::List),
+    Watch(watch::Watch),
+    Verify(verify::Verify),
+    Schema(schema::Schema),
     #[command(about = "Manage links")]
     Link {
         #[clap(subcommand)]
@@ -28,6 +40,16 @@ pub enum Commands {
         #[clap(subcommand)]
         subcommand: file::File,
     },
+    #[command(about = "Print and set scores")]
+    Scores {
+        #[clap(subcommand)]
+        subcommand: scores::Scores,
+    },
+    #[command(about = "Print usage statistics")]
+    Stats {
+        #[clap(subcommand)]
+        subcommand: stats::Stats,
+    },
     #[command(about = "Manage storage")]
     Storage {
         #[clap(subcommand)]
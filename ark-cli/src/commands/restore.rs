@@ -0,0 +1,256 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use fs_atomic_versions::atomic::{modify, AtomicFile};
+use fs_storage::base_storage::BaseStorage;
+use fs_storage::file_storage::FileStorage;
+use serde_json::Value;
+use tempdir::TempDir;
+
+use crate::{
+    commands::backup::{Manifest, ARCHIVE_FORMAT, MANIFEST_FILE},
+    dir,
+    models::storage_value::StorageValue,
+    AppError, CopyOptions, File, ARK_FOLDER, RESOURCE_ID_KIND,
+};
+
+#[derive(Clone, Debug, clap::Args)]
+#[clap(
+    name = "restore",
+    about = "Restore the ark managed folder from a backup archive"
+)]
+pub struct Restore {
+    #[clap(value_parser, help = "Path to the root directory")]
+    root_dir: PathBuf,
+    #[clap(value_parser, help = "Archive created by `backup --output`")]
+    archive: PathBuf,
+    #[clap(
+        long,
+        action = clap::ArgAction::SetTrue,
+        help = "Restore even if the archive uses another hash function"
+    )]
+    force: bool,
+}
+
+#[derive(Default)]
+struct RestoreStats {
+    copied: usize,
+    merged: usize,
+    skipped: usize,
+}
+
+impl Restore {
+    pub fn run(&self) -> Result<(), AppError> {
+        let restore_error = |e: std::io::Error| {
+            AppError::FileOperationError(format!(
+                "Couldn't read archive {}: {}",
+                self.archive.display(),
+                e
+            ))
+        };
+
+        let unpacked = TempDir::new("ark-restore")?;
+        let decoder = zstd::Decoder::new(File::open(&self.archive)?)
+            .map_err(restore_error)?;
+        tar::Archive::new(decoder)
+            .unpack(unpacked.path())
+            .map_err(restore_error)?;
+
+        let manifest =
+            fs::read(unpacked.path().join(MANIFEST_FILE)).map_err(|_| {
+                AppError::FileOperationError(format!(
+                    "{} is not an ark backup: manifest is missing",
+                    self.archive.display()
+                ))
+            })?;
+        let manifest: Manifest = serde_json::from_slice(&manifest)?;
+        self.validate(&manifest)?;
+
+        let mut stats = RestoreStats::default();
+        let source = unpacked.path().join(ARK_FOLDER);
+        if source.is_dir() {
+            restore_dir(&source, &self.root_dir.join(ARK_FOLDER), &mut stats)?;
+        }
+
+        println!(
+            "Restored from backup made by ark-cli {}: {} copied, {} merged, \
+             {} skipped",
+            manifest.ark_version, stats.copied, stats.merged, stats.skipped
+        );
+        Ok(())
+    }
+
+    fn validate(&self, manifest: &Manifest) -> Result<(), AppError> {
+        if manifest.format > ARCHIVE_FORMAT {
+            return Err(AppError::FileOperationError(format!(
+                "Archive format {} is not supported, please update ark-cli",
+                manifest.format
+            )));
+        }
+
+        if manifest.hash != RESOURCE_ID_KIND {
+            if !self.force {
+                return Err(AppError::FileOperationError(format!(
+                    "Archive uses {} resource ids, but {} is expected. \
+                     Use --force to restore anyway",
+                    manifest.hash, RESOURCE_ID_KIND
+                )));
+            }
+            eprintln!(
+                "Restoring {} resource ids into {} storages",
+                manifest.hash, RESOURCE_ID_KIND
+            );
+        }
+
+        Ok(())
+    }
+}
+
+/// Restore `source` into `target`, merging with data already present
+fn restore_dir(
+    source: &Path,
+    target: &Path,
+    stats: &mut RestoreStats,
+) -> Result<(), AppError> {
+    if !target.exists() {
+        // Nothing to merge with, keep the whole version history
+        let mut options = CopyOptions::new();
+        options.copy_inside = true;
+        dir::copy(source, target, &options)
+            .map_err(|e| AppError::FileOperationError(e.to_string()))?;
+        stats.copied += 1;
+        return Ok(());
+    }
+
+    if is_atomic_folder(source) {
+        merge_atomic_folder(source, target)?;
+        stats.merged += 1;
+        return Ok(());
+    }
+
+    for entry in fs::read_dir(source)? {
+        let entry = entry?;
+        let target = target.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            restore_dir(&entry.path(), &target, stats)?;
+        } else {
+            restore_file(&entry.path(), &target, stats)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn restore_file(
+    source: &Path,
+    target: &Path,
+    stats: &mut RestoreStats,
+) -> Result<(), AppError> {
+    if !target.exists() {
+        fs::copy(source, target)?;
+        stats.copied += 1;
+        return Ok(());
+    }
+
+    if fs::read(source)? == fs::read(target)? {
+        return Ok(());
+    }
+
+    // Storages written by `FileStorage` are merged entry by entry
+    let label = target.display().to_string();
+    let storages =
+        FileStorage::<String, StorageValue>::new(label.clone(), source)
+            .and_then(|archived| {
+                FileStorage::<String, StorageValue>::new(label, target)
+                    .map(|current| (archived, current))
+            });
+    match storages {
+        Ok((archived, mut current)) => {
+            current.merge_from(&archived)?;
+            current.write_fs()?;
+            stats.merged += 1;
+        }
+        Err(_) => {
+            eprintln!(
+                "Skipping {}: it differs from the archived copy",
+                target.display()
+            );
+            stats.skipped += 1;
+        }
+    }
+
+    Ok(())
+}
+
+/// Atomic folders contain only versioned files named
+/// `<folder name>_<app id>.<version>`
+fn is_atomic_folder(path: &Path) -> bool {
+    let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+        return false;
+    };
+    let prefix = format!("{}_", name);
+
+    let Ok(entries) = fs::read_dir(path) else {
+        return false;
+    };
+    let mut versions = 0;
+    for entry in entries.flatten() {
+        let file_name = entry.file_name();
+        let is_version = file_name
+            .to_str()
+            .filter(|file_name| file_name.starts_with(&prefix))
+            .and_then(|file_name| file_name.rsplit_once('.'))
+            .map(|(_, version)| version.parse::<usize>().is_ok())
+            .unwrap_or(false);
+        if !is_version {
+            return false;
+        }
+        versions += 1;
+    }
+    versions > 0
+}
+
+/// Write a new version of the target folder, combining its latest
+/// content with the latest archived one
+fn merge_atomic_folder(source: &Path, target: &Path) -> Result<(), AppError> {
+    let archived = AtomicFile::new(source)?.load()?.read_content()?;
+    if archived.is_empty() {
+        return Ok(());
+    }
+
+    let target = AtomicFile::new(target)?;
+    modify(&target, |current| merge_content(current, &archived))?;
+    Ok(())
+}
+
+/// JSON documents are merged as in `fs-properties`,
+/// anything else is treated as a set of lines.
+fn merge_content(current: &[u8], archived: &[u8]) -> Vec<u8> {
+    if current.is_empty() {
+        return archived.to_vec();
+    }
+
+    if let (Ok(current), Ok(archived)) = (
+        serde_json::from_slice::<Value>(current),
+        serde_json::from_slice::<Value>(archived),
+    ) {
+        if let Ok(merged) =
+            serde_json::to_vec(&data_json::merge(current, archived))
+        {
+            return merged;
+        }
+    }
+
+    let current = String::from_utf8_lossy(current);
+    let archived = String::from_utf8_lossy(archived);
+    let mut lines: Vec<&str> = current.lines().collect();
+    for line in archived.lines() {
+        if !lines.contains(&line) {
+            lines.push(line);
+        }
+    }
+
+    let mut merged = lines.join("\n");
+    merged.push('\n');
+    merged.into_bytes()
+}
@@ -0,0 +1,53 @@
+use schemars::schema_for;
+
+use crate::output::{
+    CollisionGroup, ListEntry, StorageRecord, StorageSyncReport, VerifyFinding,
+    WatchEvent,
+};
+use crate::AppError;
+
+#[derive(Clone, Debug, clap::Args)]
+#[clap(
+    name = "schema",
+    about = "Print the JSON Schema of the records printed by a command \
+             with --format json or ndjson"
+)]
+pub struct Schema {
+    #[clap(value_enum, help = "Command to describe")]
+    command: SchemaCommand,
+}
+
+#[derive(Clone, Copy, Debug, clap::ValueEnum)]
+enum SchemaCommand {
+    List,
+    Watch,
+    Collisions,
+    StorageList,
+    StorageGet,
+    StorageSet,
+    StorageSync,
+    Verify,
+    ScoresTop,
+    StatsRecent,
+    StatsReport,
+}
+
+impl Schema {
+    pub fn run(&self) -> Result<(), AppError> {
+        let schema = match self.command {
+            SchemaCommand::List => schema_for!(ListEntry),
+            SchemaCommand::Watch => schema_for!(WatchEvent),
+            SchemaCommand::Collisions => schema_for!(CollisionGroup),
+            SchemaCommand::StorageList
+            | SchemaCommand::StorageGet
+            | SchemaCommand::StorageSet => schema_for!(StorageRecord),
+            SchemaCommand::StorageSync => schema_for!(StorageSyncReport),
+            SchemaCommand::Verify => schema_for!(VerifyFinding),
+            SchemaCommand::ScoresTop => schema_for!(ScoreEntry),
+            SchemaCommand::StatsRecent => schema_for!(UsageEntry),
+            SchemaCommand::StatsReport => schema_for!(StatsReport),
+        };
+        println!("{}", serde_json::to_string_pretty(&schema)?);
+        Ok(())
+    }
+}
@@ -0,0 +1,31 @@
+use std::path::Path;
+
+use clap::Subcommand;
+use fs_scores::ScoreStorage;
+
+use crate::output::OutputFormat;
+use crate::{AppError, ResourceId};
+
+mod set;
+mod top;
+
+/// Available commands for the `scores` subcommand
+#[derive(Subcommand, Debug)]
+pub enum Scores {
+    Top(top::Top),
+    Set(set::Set),
+}
+
+impl Scores {
+    pub fn run(&self, format: OutputFormat) -> Result<(), AppError> {
+        match self {
+            Scores::Top(top) => top.run(format),
+            Scores::Set(set) => set.run(format),
+        }
+    }
+}
+
+/// Scores storage of `root`, created on the first write
+fn open_scores(root: &Path) -> Result<ScoreStorage<ResourceId>, AppError> {
+    Ok(ScoreStorage::new(root)?)
+}
@@ -0,0 +1,48 @@
+use std::path::PathBuf;
+
+use fs_index::ResourceIndex;
+
+use crate::commands::collisions::relative;
+use crate::output::{print_record, OutputFormat, ScoreEntry};
+use crate::{provide_root, resolve_resource, AppError, ResourceId};
+
+use super::open_scores;
+
+#[derive(Clone, Debug, clap::Args)]
+#[clap(name = "set", about = "Set the score of a resource")]
+pub struct Set {
+    #[clap(short, long, help = "Root directory of the ark managed folder")]
+    root_dir: Option<PathBuf>,
+    #[clap(help = "Path or id of the resource")]
+    resource: String,
+    #[clap(
+        allow_negative_numbers = true,
+        help = "New score, 0 removes the score"
+    )]
+    score: i64,
+}
+
+impl Set {
+    pub fn run(&self, format: OutputFormat) -> Result<(), AppError> {
+        let root = provide_root(&self.root_dir)?.canonicalize()?;
+        let index = ResourceIndex::<ResourceId>::provide(&root)?;
+        let id = resolve_resource(&index, &self.resource)?;
+
+        // Same as the apps: resources without score have score 0
+        open_scores(&root)?.set_score(id.clone(), self.score)?;
+
+        let path = index
+            .id2path
+            .get(&id)
+            .map(|path| relative(&root, path.as_ref()));
+        if !format.is_human() {
+            return print_record(&ScoreEntry {
+                id: id.to_string(),
+                path,
+                score: self.score,
+            });
+        }
+        println!("{} = {}", id, self.score);
+        Ok(())
+    }
+}
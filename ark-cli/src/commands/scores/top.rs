@@ -0,0 +1,66 @@
+use std::path::PathBuf;
+
+use fs_index::ResourceIndex;
+
+use crate::commands::collisions::relative;
+use crate::output::{print_records, OutputFormat, ScoreEntry};
+use crate::{provide_root, AppError, ResourceId};
+
+use super::open_scores;
+
+#[derive(Clone, Debug, clap::Args)]
+#[clap(name = "top", about = "Print the resources with the highest scores")]
+pub struct Top {
+    #[clap(short, long, help = "Root directory of the ark managed folder")]
+    root_dir: Option<PathBuf>,
+    #[clap(short, default_value_t = 20, help = "Number of resources to print")]
+    n: usize,
+}
+
+impl Top {
+    pub fn run(&self, format: OutputFormat) -> Result<(), AppError> {
+        let root = provide_root(&self.root_dir)?.canonicalize()?;
+        let scores = open_scores(&root)?;
+        let index = ResourceIndex::<ResourceId>::provide(&root)?;
+
+        // Highest scores first, ties in id order
+        let entries: Vec<ScoreEntry> = scores
+            .top_n(self.n)
+            .into_iter()
+            .map(|(id, score)| ScoreEntry {
+                id: id.to_string(),
+                path: index
+                    .id2path
+                    .get(&id)
+                    .map(|path| relative(&root, path.as_ref())),
+                score,
+            })
+            .collect();
+
+        if entries.is_empty() {
+            eprintln!(
+                "No scores yet, set one with `ark-cli scores set \
+                 <PATH_OR_ID> <SCORE>`"
+            );
+        }
+        if !format.is_human() {
+            return print_records(format, &entries);
+        }
+
+        if !entries.is_empty() {
+            println!("{:>6}  {:<12}  PATH", "SCORE", "ID");
+        }
+        for entry in entries {
+            println!(
+                "{:>6}  {:<12}  {}",
+                entry.score,
+                entry.id,
+                entry.path.map_or_else(
+                    || "-".to_owned(),
+                    |path| path.display().to_string()
+                )
+            );
+        }
+        Ok(())
+    }
+}
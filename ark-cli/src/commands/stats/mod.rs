@@ -0,0 +1,77 @@
+use std::path::Path;
+
+use chrono::{DateTime, TimeZone, Utc};
+use clap::Subcommand;
+use fs_index::ResourceIndex;
+use fs_stats::ResourceStats;
+
+use crate::commands::collisions::relative;
+use crate::output::{OutputFormat, UsageEntry};
+use crate::{AppError, ResourceId};
+
+mod recent;
+mod report;
+
+/// Available commands for the `stats` subcommand
+#[derive(Subcommand, Debug)]
+pub enum Stats {
+    Recent(recent::Recent),
+    Report(report::Report),
+}
+
+impl Stats {
+    pub fn run(&self, format: OutputFormat) -> Result<(), AppError> {
+        match self {
+            Stats::Recent(recent) => recent.run(format),
+            Stats::Report(report) => report.run(format),
+        }
+    }
+}
+
+const NO_STATS_HINT: &str =
+    "No stats yet, they are recorded by the apps when resources are opened";
+
+fn datetime(millis: u64) -> DateTime<Utc> {
+    Utc.timestamp_millis_opt(millis as i64)
+        .single()
+        .unwrap_or_default()
+}
+
+/// Usage of every resource opened at least once
+fn usage_entries(
+    root: &Path,
+    index: &ResourceIndex<ResourceId>,
+    stats: &[(ResourceId, ResourceStats)],
+) -> Vec<UsageEntry> {
+    stats
+        .iter()
+        .filter_map(|(id, stats)| {
+            Some(UsageEntry {
+                id: id.to_string(),
+                path: index
+                    .id2path
+                    .get(id)
+                    .map(|path| relative(root, path.as_ref())),
+                opens: stats.open_count(),
+                last_opened: datetime(stats.last_opened()?),
+            })
+        })
+        .collect()
+}
+
+fn print_usage(entries: &[UsageEntry]) {
+    if !entries.is_empty() {
+        println!("{:<16}  {:>5}  PATH", "LAST OPENED", "OPENS");
+    }
+    for entry in entries {
+        println!(
+            "{:<16}  {:>5}  {}",
+            entry.last_opened.format("%Y-%m-%d %H:%M"),
+            entry.opens,
+            entry.path.as_ref().map_or_else(
+                || entry.id.clone(),
+                |path| path.display().to_string()
+            )
+        );
+    }
+}
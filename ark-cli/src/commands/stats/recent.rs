@@ -0,0 +1,37 @@
+use std::path::PathBuf;
+
+use fs_index::ResourceIndex;
+use fs_stats::recently_used;
+
+use crate::output::{print_records, OutputFormat};
+use crate::{provide_root, AppError, ResourceId};
+
+use super::{print_usage, usage_entries, NO_STATS_HINT};
+
+#[derive(Clone, Debug, clap::Args)]
+#[clap(name = "recent", about = "Print the recently opened resources")]
+pub struct Recent {
+    #[clap(short, long, help = "Root directory of the ark managed folder")]
+    root_dir: Option<PathBuf>,
+    #[clap(short, default_value_t = 20, help = "Number of resources to print")]
+    n: usize,
+}
+
+impl Recent {
+    pub fn run(&self, format: OutputFormat) -> Result<(), AppError> {
+        let root = provide_root(&self.root_dir)?.canonicalize()?;
+        let stats = recently_used(&root, self.n)?;
+        let index = ResourceIndex::<ResourceId>::provide(&root)?;
+
+        let entries = usage_entries(&root, &index, &stats);
+
+        if entries.is_empty() {
+            eprintln!("{}", NO_STATS_HINT);
+        }
+        if !format.is_human() {
+            return print_records(format, &entries);
+        }
+        print_usage(&entries);
+        Ok(())
+    }
+}
@@ -0,0 +1,90 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use chrono::{Duration, Utc};
+use fs_index::ResourceIndex;
+use fs_stats::load_all_stats;
+
+use crate::output::{print_record, DailyOpens, OutputFormat, StatsReport};
+use crate::{provide_root, AppError, ResourceId};
+
+use super::{datetime, print_usage, usage_entries, NO_STATS_HINT};
+
+/// Number of days covered by the report, today included
+const REPORT_DAYS: i64 = 30;
+
+#[derive(Clone, Debug, clap::Args)]
+#[clap(
+    name = "report",
+    about = "Print the opens per day of the last month \
+             and the most used resources"
+)]
+pub struct Report {
+    #[clap(short, long, help = "Root directory of the ark managed folder")]
+    root_dir: Option<PathBuf>,
+    #[clap(
+        short,
+        default_value_t = 10,
+        help = "Number of most used resources to print"
+    )]
+    n: usize,
+}
+
+impl Report {
+    pub fn run(&self, format: OutputFormat) -> Result<(), AppError> {
+        let root = provide_root(&self.root_dir)?.canonicalize()?;
+        let stats = load_all_stats(&root)?;
+        let index = ResourceIndex::<ResourceId>::provide(&root)?;
+
+        // Only the latest opens of every resource are kept with their time
+        let mut per_day = HashMap::new();
+        for (_, resource) in &stats {
+            for opened in resource.recent_opens() {
+                *per_day
+                    .entry(datetime(*opened).date_naive())
+                    .or_insert(0) += 1;
+            }
+        }
+        let today = Utc::now().date_naive();
+        let days: Vec<DailyOpens> = (0..REPORT_DAYS)
+            .rev()
+            .map(|ago| {
+                let date = today - Duration::days(ago);
+                DailyOpens {
+                    date,
+                    opens: per_day.get(&date).copied().unwrap_or(0),
+                }
+            })
+            .collect();
+
+        let mut most_used = usage_entries(&root, &index, &stats);
+        most_used.sort_by(|a, b| {
+            b.opens
+                .cmp(&a.opens)
+                .then(b.last_opened.cmp(&a.last_opened))
+                .then(a.id.cmp(&b.id))
+        });
+        most_used.truncate(self.n);
+
+        if most_used.is_empty() {
+            eprintln!("{}", NO_STATS_HINT);
+        }
+        if !format.is_human() {
+            return print_record(&StatsReport { days, most_used });
+        }
+
+        println!("Opens per day");
+        for day in &days {
+            println!(
+                "{}  {:>4}  {}",
+                day.date,
+                day.opens,
+                "#".repeat(day.opens.min(50))
+            );
+        }
+        println!();
+        println!("Most used");
+        print_usage(&most_used);
+        Ok(())
+    }
+}
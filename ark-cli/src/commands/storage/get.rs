@@ -0,0 +1,41 @@
+use std::path::PathBuf;
+
+use crate::output::{print_record, OutputFormat, StorageRecord};
+use crate::AppError;
+
+use super::open_file_storage;
+
+#[derive(Clone, Debug, clap::Args)]
+#[clap(name = "get", about = "Print the value of a key in a storage")]
+pub struct Get {
+    #[clap(short, long, help = "Root directory of the ark managed folder")]
+    root_dir: Option<PathBuf>,
+    #[clap(help = "Storage name or path")]
+    storage: String,
+    #[clap(help = "Key of the entry")]
+    key: String,
+}
+
+impl Get {
+    pub fn run(&self, format: OutputFormat) -> Result<(), AppError> {
+        let storage = open_file_storage(&self.root_dir, &self.storage)?;
+
+        let value = storage.as_ref().get(&self.key).ok_or_else(|| {
+            AppError::StorageNotFound(format!(
+                "Key {} not found in {}",
+                self.key, self.storage
+            ))
+        })?;
+
+        if !format.is_human() {
+            print_record(&StorageRecord {
+                key: self.key.clone(),
+                value: Some(value.0.clone()),
+            })?;
+        } else {
+            println!("{}", value);
+        }
+
+        Ok(())
+    }
+}
@@ -1,12 +1,18 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
+use crate::output::{print_record, print_records, OutputFormat, StorageRecord};
 use crate::{
     models::storage::Storage, models::storage::StorageType, translate_storage,
     AppError,
 };
 
+use super::load_file_storage;
+
 #[derive(Clone, Debug, clap::Args)]
-#[clap(name = "list", about = "List resources in a storage")]
+#[clap(
+    name = "list",
+    about = "List resources in a storage, or entries of a file storage"
+)]
 pub struct List {
     #[clap(value_parser, help = "Root directory of the ark managed folder")]
     root_dir: Option<PathBuf>,
@@ -19,7 +25,7 @@ pub struct List {
 }
 
 impl List {
-    pub fn run(&self) -> Result<(), AppError> {
+    pub fn run(&self, format: OutputFormat) -> Result<(), AppError> {
         let storage =
             self.storage
                 .as_ref()
@@ -33,6 +39,15 @@ impl List {
             translate_storage(&self.root_dir, storage)
                 .ok_or(AppError::StorageNotFound(storage.to_owned()))?;
 
+        if file_path.is_file() {
+            return list_file_storage(storage, &file_path, format);
+        }
+        if !format.is_human() && versions {
+            return Err(AppError::UnsupportedOption(
+                "--versions is only supported with --format human".to_owned(),
+            ));
+        }
+
         let storage_type = storage_type.unwrap_or(match self.kind {
             Some(t) => t,
             None => StorageType::File,
@@ -42,10 +57,51 @@ impl List {
 
         storage.load()?;
 
-        let output = storage.list(versions)?;
-
-        println!("{}", output);
+        if !format.is_human() {
+            let records: Vec<StorageRecord> = storage
+                .ids()
+                .iter()
+                .map(|id| StorageRecord {
+                    key: id.to_string(),
+                    value: None,
+                })
+                .collect();
+            print_records(format, &records)?;
+        } else {
+            let output = storage.list(versions)?;
+            println!("{}", output);
+        }
 
         Ok(())
     }
 }
+
+fn list_file_storage(
+    label: &str,
+    path: &Path,
+    format: OutputFormat,
+) -> Result<(), AppError> {
+    let storage = load_file_storage(label, path)?;
+    let entries = storage.as_ref();
+
+    // entries are printed as a single object keyed by key with
+    // `--format json`, as records with `--format ndjson`
+    if format == OutputFormat::Json {
+        print_record(entries)?;
+    } else if !format.is_human() {
+        let records: Vec<StorageRecord> = entries
+            .iter()
+            .map(|(key, value)| StorageRecord {
+                key: key.clone(),
+                value: Some(value.0.clone()),
+            })
+            .collect();
+        print_records(format, &records)?;
+    } else {
+        for (key, value) in entries {
+            println!("{: <16} {}", key, value);
+        }
+    }
+
+    Ok(())
+}
@@ -1,9 +1,63 @@
+use std::path::{Path, PathBuf};
+
 use clap::Subcommand;
+use fs_storage::file_storage::FileStorage;
+
+use crate::output::OutputFormat;
+use crate::{models::storage_value::StorageValue, translate_storage, AppError};
 
+mod get;
 mod list;
+mod set;
+mod sync;
 
 /// Available commands for the `storage` subcommand
 #[derive(Subcommand, Debug)]
 pub enum Storage {
     List(list::List),
+    Get(get::Get),
+    Set(set::Set),
+    Sync(sync::Sync),
+}
+
+impl Storage {
+    pub fn run(&self, format: OutputFormat) -> Result<(), AppError> {
+        match self {
+            Storage::List(list) => list.run(format),
+            Storage::Get(get) => get.run(format),
+            Storage::Set(set) => set.run(format),
+            Storage::Sync(sync) => sync.run(format),
+        }
+    }
+}
+
+/// `FileStorage` with values of any type, used for inspection
+pub type AnyFileStorage = FileStorage<String, StorageValue>;
+
+/// Resolve a storage name or path and open it as a `FileStorage`.
+///
+/// Both the legacy plaintext format (version 2) and the JSON format
+/// (version 3) are supported.
+fn open_file_storage(
+    root_dir: &Option<PathBuf>,
+    storage: &str,
+) -> Result<AnyFileStorage, AppError> {
+    let (path, _) = translate_storage(root_dir, storage)
+        .ok_or(AppError::StorageNotFound(storage.to_owned()))?;
+
+    load_file_storage(storage, &path)
+}
+
+fn load_file_storage(
+    label: &str,
+    path: &Path,
+) -> Result<AnyFileStorage, AppError> {
+    if path.is_dir() {
+        return Err(AppError::StorageCreationError(format!(
+            "{} is a folder, not a file storage",
+            path.display()
+        )));
+    }
+
+    Ok(FileStorage::new(label.to_owned(), path)?)
 }
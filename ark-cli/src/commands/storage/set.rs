@@ -0,0 +1,46 @@
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use fs_storage::base_storage::BaseStorage;
+
+use crate::output::{print_record, OutputFormat, StorageRecord};
+use crate::{models::storage_value::StorageValue, AppError};
+
+use super::open_file_storage;
+
+#[derive(Clone, Debug, clap::Args)]
+#[clap(name = "set", about = "Set the value of a key in a storage")]
+pub struct Set {
+    #[clap(short, long, help = "Root directory of the ark managed folder")]
+    root_dir: Option<PathBuf>,
+    #[clap(help = "Storage name or path")]
+    storage: String,
+    #[clap(help = "Key of the entry")]
+    key: String,
+    #[clap(help = "Value of the entry, parsed as JSON if possible")]
+    value: String,
+}
+
+impl Set {
+    pub fn run(&self, format: OutputFormat) -> Result<(), AppError> {
+        let mut storage = open_file_storage(&self.root_dir, &self.storage)?;
+
+        // Parsing never fails, invalid JSON is stored as a string
+        let value = StorageValue::from_str(&self.value)
+            .unwrap_or_else(|never| match never {});
+
+        storage.set(self.key.clone(), value.clone());
+        storage.write_fs()?;
+
+        if !format.is_human() {
+            print_record(&StorageRecord {
+                key: self.key.clone(),
+                value: Some(value.0),
+            })?;
+        } else {
+            println!("{} = {}", self.key, value);
+        }
+
+        Ok(())
+    }
+}
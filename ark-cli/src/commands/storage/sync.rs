@@ -0,0 +1,65 @@
+use std::path::PathBuf;
+
+use fs_storage::base_storage::{BaseStorage, SyncStatus};
+
+use crate::output::{print_record, OutputFormat, StorageSyncReport};
+use crate::AppError;
+
+use super::{load_file_storage, open_file_storage};
+
+#[derive(Clone, Debug, clap::Args)]
+#[clap(
+    name = "sync",
+    about = "Merge another copy of a storage into it and print the sync status"
+)]
+pub struct Sync {
+    #[clap(short, long, help = "Root directory of the ark managed folder")]
+    root_dir: Option<PathBuf>,
+    #[clap(help = "Storage name or path")]
+    storage: String,
+    #[clap(help = "Other copy of the storage, e.g. from another device")]
+    from: PathBuf,
+}
+
+impl Sync {
+    pub fn run(&self, format: OutputFormat) -> Result<(), AppError> {
+        let mut storage = open_file_storage(&self.root_dir, &self.storage)?;
+        if !self.from.is_file() {
+            return Err(AppError::StorageNotFound(
+                self.from.display().to_string(),
+            ));
+        }
+        let other =
+            load_file_storage(&self.from.display().to_string(), &self.from)?;
+
+        let before = storage.as_ref().clone();
+        storage.merge_from(&other)?;
+        let merged = storage
+            .as_ref()
+            .iter()
+            .filter(|(key, value)| before.get(*key) != Some(*value))
+            .count();
+
+        // the storage is only written if the other copy changed it
+        let status = if merged == 0 {
+            SyncStatus::InSync
+        } else {
+            let status = storage.sync_status()?;
+            storage.sync()?;
+            status
+        };
+
+        if !format.is_human() {
+            print_record(&StorageSyncReport {
+                storage: self.storage.clone(),
+                status: status.to_string(),
+                entries: storage.as_ref().len(),
+                merged,
+            })?;
+        } else {
+            println!("{}: {}, {} merged", self.storage, status, merged);
+        }
+
+        Ok(())
+    }
+}
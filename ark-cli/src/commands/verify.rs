@@ -0,0 +1,199 @@
+use std::io::{BufRead, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+
+use fs_index::verify::{repair, verify, Finding, VerifyMode};
+use fs_index::ResourceIndex;
+
+use crate::metadata;
+use crate::output::{print_records, OutputFormat, VerifyFinding};
+use crate::{provide_root, AppError, ResourceId};
+
+#[derive(Clone, Debug, clap::Args)]
+#[clap(
+    name = "verify",
+    about = "Check the stored index against the files \
+             of the ark managed folder"
+)]
+pub struct Verify {
+    #[clap(value_parser, help = "Path to the root directory")]
+    root_dir: Option<PathBuf>,
+    #[clap(
+        long,
+        action = clap::ArgAction::SetTrue,
+        conflicts_with = "sample",
+        help = "Re-hash every file instead of only the modified ones"
+    )]
+    full: bool,
+    #[clap(
+        long,
+        value_parser = parse_fraction,
+        help = "Also re-hash this share of the files, e.g. 5% or 0.05"
+    )]
+    sample: Option<f64>,
+    #[clap(
+        long,
+        action = clap::ArgAction::SetTrue,
+        help = "Repair the index, moving tags, scores and properties \
+                of modified files to their new ids"
+    )]
+    fix: bool,
+    #[clap(
+        short,
+        long,
+        action = clap::ArgAction::SetTrue,
+        help = "Drop entries of missing files without asking"
+    )]
+    yes: bool,
+}
+
+fn parse_fraction(s: &str) -> Result<f64, String> {
+    let fraction = match s.strip_suffix('%') {
+        Some(percent) => percent.trim().parse::<f64>().map(|p| p / 100.0),
+        None => s.parse::<f64>(),
+    }
+    .map_err(|e| e.to_string())?;
+
+    if (0.0..=1.0).contains(&fraction) {
+        Ok(fraction)
+    } else {
+        Err("must be between 0% and 100%".to_owned())
+    }
+}
+
+impl Verify {
+    pub fn run(&self, format: OutputFormat) -> Result<(), AppError> {
+        let root = provide_root(&self.root_dir)?.canonicalize()?;
+        let mode = match (self.full, self.sample) {
+            (true, _) => VerifyMode::Full,
+            (false, Some(fraction)) => VerifyMode::Sample(fraction),
+            (false, None) => VerifyMode::Quick,
+        };
+
+        let findings = verify::<ResourceId, _>(&root, mode)?;
+
+        let fixed = if self.fix && !findings.is_empty() {
+            let fixes = self.select_fixes(&findings, format)?;
+            repair(&root, &fixes)?;
+            relocate_metadata(&root, &fixes)?;
+            fixes
+        } else {
+            vec![]
+        };
+
+        if !format.is_human() {
+            let records: Vec<VerifyFinding> = findings
+                .iter()
+                .map(|finding| VerifyFinding::new(finding, &fixed))
+                .collect();
+            print_records(format, &records)?;
+        } else {
+            for finding in &findings {
+                print_finding(finding, fixed.contains(finding));
+            }
+            println!(
+                "{} problems found, {} fixed",
+                findings.len(),
+                fixed.len()
+            );
+        }
+
+        let unfixed = findings.len() - fixed.len();
+        if unfixed > 0 {
+            return Err(AppError::VerificationFailed(unfixed));
+        }
+        Ok(())
+    }
+
+    /// Dropping entries loses the tags of the missing files,
+    /// so it must be confirmed unless `--yes` is given
+    fn select_fixes(
+        &self,
+        findings: &[Finding<ResourceId>],
+        format: OutputFormat,
+    ) -> Result<Vec<Finding<ResourceId>>, AppError> {
+        let missing = findings
+            .iter()
+            .filter(|finding| matches!(finding, Finding::Missing { .. }))
+            .count();
+
+        let drop_missing = missing == 0
+            || self.yes
+            || (format.is_human()
+                && std::io::stdin().is_terminal()
+                && confirm(missing)?);
+
+        Ok(findings
+            .iter()
+            .filter(|finding| {
+                drop_missing || !matches!(finding, Finding::Missing { .. })
+            })
+            .cloned()
+            .collect())
+    }
+}
+
+fn confirm(missing: usize) -> Result<bool, AppError> {
+    print!(
+        "Drop {} entries of missing files from the index? [y/N] ",
+        missing
+    );
+    std::io::stdout().flush()?;
+
+    let mut answer = String::new();
+    std::io::stdin().lock().read_line(&mut answer)?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
+
+fn print_finding(finding: &Finding<ResourceId>, fixed: bool) {
+    let line = match finding {
+        Finding::Missing { path, id } => {
+            format!("missing        {} {}", id, path.display())
+        }
+        Finding::MetadataDrift { path, id, .. } => {
+            format!("metadata drift {} {}", id, path.display())
+        }
+        Finding::IdMismatch {
+            path,
+            stored,
+            actual,
+            ..
+        } => format!(
+            "id mismatch    {} -> {} {}",
+            stored,
+            actual,
+            path.display()
+        ),
+    };
+    if fixed {
+        println!("{} (fixed)", line);
+    } else {
+        println!("{}", line);
+    }
+}
+
+/// Tags, scores and properties must follow resources whose content
+/// changed. They are copied if another file still has the old id.
+fn relocate_metadata(
+    root: &Path,
+    fixed: &[Finding<ResourceId>],
+) -> Result<(), AppError> {
+    let moves: Vec<(&ResourceId, &ResourceId)> = fixed
+        .iter()
+        .filter_map(|finding| match finding {
+            Finding::IdMismatch { stored, actual, .. } => {
+                Some((stored, actual))
+            }
+            _ => None,
+        })
+        .collect();
+    if moves.is_empty() {
+        return Ok(());
+    }
+
+    let index = ResourceIndex::<ResourceId>::load(root)?;
+    for (old, new) in moves {
+        let keep_old = index.id2path.contains_key(old);
+        metadata::relocate(root, old, new, keep_old)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,248 @@
+use std::collections::BTreeSet;
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use fs_index::{IndexEvent, ResourceIndex};
+use fs_properties::PROPERTIES_STORAGE_FOLDER;
+use fs_storage::{ARK_FOLDER, SCORE_STORAGE_FILE, TAG_STORAGE_FILE};
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use tokio::sync::mpsc::{self, UnboundedSender};
+
+use crate::output::{print_record, OutputFormat, WatchEvent};
+use crate::{provide_root, AppError, ResourceId};
+
+/// Time the index waits for more changes before it is stored
+const STORE_DEBOUNCE: Duration = Duration::from_secs(1);
+
+#[derive(Clone, Debug, clap::Args)]
+#[clap(name = "watch", about = "Print changes of the ark managed folder")]
+pub struct Watch {
+    #[clap(value_parser, help = "Path to the root directory")]
+    root_dir: Option<PathBuf>,
+    #[clap(
+        long,
+        action = clap::ArgAction::SetTrue,
+        help = "Also watch tags, scores and properties storages"
+    )]
+    storages: bool,
+}
+
+impl WatchEvent {
+    fn print(
+        &self,
+        format: OutputFormat,
+        colored: bool,
+    ) -> Result<(), AppError> {
+        // Events are streamed, so JSON output is always one per line
+        if !format.is_human() {
+            return print_record(self);
+        }
+
+        let (color, line) = match self {
+            WatchEvent::Added { id, path } => {
+                ("32", format!("added    {} {}", id, path.display()))
+            }
+            WatchEvent::Removed { id, path } => {
+                ("31", format!("removed  {} {}", id, path.display()))
+            }
+            WatchEvent::Modified { old_id, id, path } => (
+                "33",
+                format!("modified {} -> {} {}", old_id, id, path.display()),
+            ),
+            WatchEvent::Moved { id, from, to } => (
+                "36",
+                format!(
+                    "moved    {} {} -> {}",
+                    id,
+                    from.display(),
+                    to.display()
+                ),
+            ),
+            WatchEvent::Storage { storage, path } => {
+                ("35", format!("storage  {} {}", storage, path.display()))
+            }
+        };
+
+        if colored {
+            println!("\x1b[{}m{}\x1b[0m", color, line);
+        } else {
+            println!("{}", line);
+        }
+        Ok(())
+    }
+}
+
+/// Change reported by the index or by the watcher of the storages
+enum Change {
+    Index(IndexEvent<ResourceId>),
+    Storages(notify::Result<Event>),
+}
+
+impl Watch {
+    pub async fn run(&self, format: OutputFormat) -> Result<(), AppError> {
+        let root = provide_root(&self.root_dir)?.canonicalize()?;
+        let colored = format.is_human() && std::io::stdout().is_terminal();
+
+        let mut index = ResourceIndex::<ResourceId>::provide(&root)?;
+        if format.is_human() {
+            println!(
+                "Watching {} ({} resources), press Ctrl-C to stop",
+                root.display(),
+                index.size()
+            );
+        }
+        index.set_auto_store(Some(STORE_DEBOUNCE));
+        let index_events = index.subscribe();
+        let (watcher, _updates) = index.watch()?;
+
+        let (changes_tx, mut changes) = mpsc::unbounded_channel();
+        {
+            let changes_tx = changes_tx.clone();
+            // ends once the index is dropped
+            std::thread::spawn(move || {
+                for event in index_events {
+                    if changes_tx.send(Change::Index(event)).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        let storages = watched_storages(&root);
+        let _storages_watcher = if self.storages {
+            Some(watch_storages(&root, changes_tx)?)
+        } else {
+            None
+        };
+
+        loop {
+            let change = tokio::select! {
+                _ = tokio::signal::ctrl_c() => break,
+                change = changes.recv() => change,
+            };
+            let Some(change) = change else {
+                break;
+            };
+            // several writes of a storage are reported at once
+            let mut received = vec![change];
+            while let Ok(change) = changes.try_recv() {
+                received.push(change);
+            }
+            let mut storage_events = Vec::new();
+            for change in received {
+                match change {
+                    Change::Index(event) => {
+                        index_event(&root, event).print(format, colored)?
+                    }
+                    Change::Storages(event) => storage_events.push(event),
+                }
+            }
+            for (path, storage) in changed_storages(&storages, storage_events) {
+                WatchEvent::Storage {
+                    storage: storage.to_owned(),
+                    path: relative(&root, path),
+                }
+                .print(format, colored)?;
+            }
+        }
+
+        let mut index = watcher.stop();
+        index.flush()?;
+        if format.is_human() {
+            println!("Index stored, bye");
+        }
+        Ok(())
+    }
+}
+
+/// Turn an event of the index into a user-facing event
+fn index_event(root: &Path, event: IndexEvent<ResourceId>) -> WatchEvent {
+    match event {
+        IndexEvent::Added { id, path } => WatchEvent::Added {
+            id: id.to_string(),
+            path: relative(root, path),
+        },
+        IndexEvent::Removed { id, path } => WatchEvent::Removed {
+            id: id.to_string(),
+            path: relative(root, path),
+        },
+        IndexEvent::Modified {
+            path,
+            old_id,
+            new_id,
+        } => WatchEvent::Modified {
+            old_id: old_id.to_string(),
+            id: new_id.to_string(),
+            path: relative(root, path),
+        },
+        IndexEvent::Moved { id, from, to } => WatchEvent::Moved {
+            id: id.to_string(),
+            from: relative(root, from),
+            to: relative(root, to),
+        },
+    }
+}
+
+fn relative<P: AsRef<Path>>(root: &Path, path: P) -> PathBuf {
+    let path = path.as_ref();
+    path.strip_prefix(root)
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|_| path.to_path_buf())
+}
+
+fn watched_storages(root: &Path) -> Vec<(&'static str, PathBuf)> {
+    let ark = root.join(ARK_FOLDER);
+    vec![
+        ("tags", ark.join(TAG_STORAGE_FILE)),
+        ("scores", ark.join(SCORE_STORAGE_FILE)),
+        ("properties", ark.join(PROPERTIES_STORAGE_FOLDER)),
+    ]
+}
+
+/// Watch the `.ark` folder, where the storages are kept
+fn watch_storages(
+    root: &Path,
+    changes: UnboundedSender<Change>,
+) -> Result<RecommendedWatcher, AppError> {
+    let ark = root.join(ARK_FOLDER);
+    std::fs::create_dir_all(&ark)?;
+    let watch_error =
+        |err: notify::Error| AppError::IndexError(err.to_string());
+    let mut watcher = notify::recommended_watcher(move |event| {
+        let _ = changes.send(Change::Storages(event));
+    })
+    .map_err(watch_error)?;
+    watcher
+        .watch(&ark, RecursiveMode::Recursive)
+        .map_err(watch_error)?;
+    Ok(watcher)
+}
+
+/// Files of the storages changed by `events`, sorted
+fn changed_storages(
+    storages: &[(&'static str, PathBuf)],
+    events: Vec<notify::Result<Event>>,
+) -> BTreeSet<(PathBuf, &'static str)> {
+    let mut changed = BTreeSet::new();
+    for event in events {
+        let event = match event {
+            Ok(event) => event,
+            Err(err) => {
+                log::warn!("Failed to watch storages: {}", err);
+                continue;
+            }
+        };
+        if matches!(event.kind, EventKind::Access(_)) {
+            continue;
+        }
+        for path in event.paths {
+            let storage = storages
+                .iter()
+                .find(|(_, storage)| path.starts_with(storage));
+            if let Some((name, _)) = storage {
+                changed.insert((path, *name));
+            }
+        }
+    }
+    changed
+}
@@ -46,12 +46,21 @@ pub enum AppError {
     #[error("Invalid entry option")]
     InvalidEntryOption,
 
+    #[error("Unsupported option: {0}")]
+    UnsupportedOption(String),
+
+    #[error("{0} problems found and not fixed")]
+    VerificationFailed(usize),
+
     #[error(transparent)]
     IoError(#[from] io::Error),
 
     #[error(transparent)]
     ArklibError(#[from] ArklibError),
 
+    #[error(transparent)]
+    JsonError(#[from] serde_json::Error),
+
     #[error(transparent)]
     InlineJsonParseError(#[from] InlineJsonParseError),
 }
@@ -9,6 +9,8 @@ use data_pdf::{render_preview_page, PDFQuality};
 //
 // We define it globally here so that it can be easily changed.
 pub(crate) use dev_hash::Crc32 as ResourceId;
+// Name of the hash function behind `ResourceId`, recorded in backups
+pub(crate) const RESOURCE_ID_KIND: &str = "crc32";
 
 use fs_atomic_versions::app_id;
 use fs_storage::ARK_FOLDER;
@@ -26,7 +28,7 @@ use fs_extra::dir::{self, CopyOptions};
 use home::home_dir;
 
 use crate::cli::Cli;
-use crate::commands::file::File::{Append, Insert, Read};
+use crate::commands::file::File::{Append, Info, Insert, Read, Replace, Rm};
 use crate::commands::link::Link::{Create, Load};
 use crate::commands::Commands::Link;
 use crate::commands::Commands::Storage;
@@ -36,17 +38,21 @@ use crate::models::Format;
 use crate::models::Sort;
 
 use crate::error::AppError;
+use crate::output::OutputFormat;
 
 use util::{
-    discover_roots, monitor_index, provide_root, read_storage_value,
-    storages_exists, timestamp, translate_storage,
+    delete_file, discover_roots, monitor_index, provide_root,
+    read_storage_value, resolve_resource, storages_exists, timestamp,
+    translate_storage,
 };
 
 mod cli;
 mod commands;
 mod error;
 mod index_registrar;
+mod metadata;
 mod models;
+mod output;
 mod util;
 
 const ARK_CONFIG: &str = ".config/ark";
@@ -60,29 +66,71 @@ struct StorageEntry {
     tags: Option<Vec<String>>,
     scores: Option<u32>,
     datetime: Option<String>,
+    modified: Option<DateTime<Utc>>,
 }
 
-async fn run() -> Result<()> {
-    let matches = Cli::command().get_matches();
-    let cli = Cli::from_arg_matches(&matches)?;
+/// Fail if machine-readable output was requested from a command
+/// which doesn't support it
+fn human_only(format: OutputFormat, command: &str) -> Result<(), AppError> {
+    if format.is_human() {
+        Ok(())
+    } else {
+        Err(AppError::UnsupportedOption(format!(
+            "`{}` only supports human-readable output",
+            command
+        )))
+    }
+}
+
+async fn run(cli: Cli) -> Result<()> {
+    let format = cli.output_format();
     match cli.command {
-        Backup(backup) => backup.run()?,
-        Collisions(collisions) => collisions.run()?,
-        Monitor(monitor) => monitor.run()?,
-        Render(render) => render.run()?,
-        List(list) => list.run()?,
-        Link { subcommand } => match subcommand {
-            Create(create) => create.run().await?,
-            Load(load) => load.run()?,
-        },
-        crate::commands::Commands::File { subcommand } => match subcommand {
-            Append(append) => append.run()?,
-            Insert(insert) => insert.run()?,
-            Read(read) => read.run()?,
-        },
-        Storage { subcommand } => match subcommand {
-            crate::commands::storage::Storage::List(list) => list.run()?,
-        },
+        Backup(backup) => {
+            human_only(format, "backup")?;
+            backup.run()?
+        }
+        Restore(restore) => {
+            human_only(format, "restore")?;
+            restore.run()?
+        }
+        Collisions(collisions) => collisions.run(format)?,
+        Dedupe(dedupe) => {
+            human_only(format, "dedupe")?;
+            dedupe.run()?
+        }
+        Monitor(monitor) => {
+            human_only(format, "monitor")?;
+            monitor.run()?
+        }
+        Render(render) => {
+            human_only(format, "render")?;
+            render.run()?
+        }
+        List(list) => list.run(format)?,
+        Watch(watch) => watch.run(format).await?,
+        Verify(verify) => verify.run(format)?,
+        Schema(schema) => schema.run()?,
+        Link { subcommand } => {
+            human_only(format, "link")?;
+            match subcommand {
+                Create(create) => create.run().await?,
+                Load(load) => load.run()?,
+            }
+        }
+        crate::commands::Commands::File { subcommand } => {
+            human_only(format, "file")?;
+            match subcommand {
+                Append(append) => append.run()?,
+                Insert(insert) => insert.run()?,
+                Read(read) => read.run()?,
+                Rm(rm) => rm.run()?,
+                Replace(replace) => replace.run()?,
+                Info(info) => info.run()?,
+            }
+        }
+        Storage { subcommand } => subcommand.run(format)?,
+        Scores { subcommand } => subcommand.run(format)?,
+        Stats { subcommand } => subcommand.run(format)?,
     };
 
     Ok(())
@@ -94,6 +142,9 @@ async fn main() -> anyhow::Result<()> {
         env_logger::Env::default().default_filter_or("info"),
     );
 
+    let matches = Cli::command().get_matches();
+    let cli = Cli::from_arg_matches(&matches)?;
+
     let app_id_dir = home_dir().ok_or(AppError::HomeDirNotFound)?;
     let ark_dir = app_id_dir.join(".ark");
     if !ark_dir.exists() {
@@ -101,13 +152,16 @@ async fn main() -> anyhow::Result<()> {
             .map_err(|e| AppError::ArkDirectoryCreationError(e.to_string()))?;
     }
 
-    println!("Loading app id at {}...", ark_dir.display());
+    // Keep stdout clean for machine consumption
+    if cli.output_format().is_human() {
+        println!("Loading app id at {}...", ark_dir.display());
+    }
     let _ = app_id::load(ark_dir)
         .map_err(|e| AppError::AppIdLoadError(e.to_string()))?;
 
     // Having a separate function for the main logic allows for easier
     // error handling and testing.
-    if let Err(err) = run().await {
+    if let Err(err) = run(cli).await {
         eprintln!("Error: {:#}", err);
         std::process::exit(1);
     }
@@ -0,0 +1,212 @@
+//! Tags, scores and properties attached to resource ids.
+//!
+//! Ids are content-based, so the metadata must be moved to the new id when
+//! the content of a resource changes, and can be dropped with the resource.
+
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use fs_properties::{
+    load_raw_properties, store_properties, PROPERTIES_STORAGE_FOLDER,
+};
+use fs_scores::Score;
+use fs_storage::base_storage::BaseStorage;
+use fs_storage::file_storage::FileStorage;
+use fs_storage::monoid::Monoid;
+use fs_storage::tag_set::{TagSet, TagSetWithTombstones};
+use fs_storage::{ARK_FOLDER, SCORE_STORAGE_FILE, TAG_STORAGE_FILE};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+
+use crate::models::storage_value::StorageValue;
+use crate::{AppError, ResourceId};
+
+const TAGS: &str = "tags";
+const SCORES: &str = "scores";
+
+/// Storages keeping a single value per id, with their names
+const FILE_STORAGES: [(&str, &str); 2] =
+    [(TAGS, TAG_STORAGE_FILE), (SCORES, SCORE_STORAGE_FILE)];
+
+const PROPERTIES: &str = "properties";
+
+fn open_storage<V>(
+    root: &Path,
+    file: &str,
+) -> Result<Option<FileStorage<String, V>>, AppError>
+where
+    V: Clone + Serialize + DeserializeOwned + FromStr + Monoid<V>,
+{
+    let path = root.join(ARK_FOLDER).join(file);
+    if !path.is_file() {
+        return Ok(None);
+    }
+    Ok(Some(FileStorage::new(path.display().to_string(), &path)?))
+}
+
+fn properties_folder(root: &Path, id: &ResourceId) -> PathBuf {
+    root.join(ARK_FOLDER)
+        .join(PROPERTIES_STORAGE_FOLDER)
+        .join(id.to_string())
+}
+
+fn read_value<V>(
+    root: &Path,
+    file: &str,
+    id: &ResourceId,
+) -> Result<Option<V>, AppError>
+where
+    V: Clone + Serialize + DeserializeOwned + FromStr + Monoid<V>,
+{
+    Ok(open_storage::<V>(root, file)?
+        .and_then(|storage| storage.as_ref().get(&id.to_string()).cloned()))
+}
+
+/// Tags of `id`, without the ones removed
+pub fn read_tags(
+    root: &Path,
+    id: &ResourceId,
+) -> Result<Option<TagSet>, AppError> {
+    Ok(
+        read_value::<TagSetWithTombstones>(root, TAG_STORAGE_FILE, id)?
+            .map(|tags| tags.tags()),
+    )
+}
+
+pub fn read_score(
+    root: &Path,
+    id: &ResourceId,
+) -> Result<Option<i64>, AppError> {
+    Ok(read_value::<Score>(root, SCORE_STORAGE_FILE, id)?
+        .map(|score| score.value))
+}
+
+pub fn read_properties(
+    root: &Path,
+    id: &ResourceId,
+) -> Result<Option<Value>, AppError> {
+    if !properties_folder(root, id).exists() {
+        return Ok(None);
+    }
+    let raw = load_raw_properties(root, id.clone())?;
+    Ok(Some(serde_json::from_slice(&raw)?))
+}
+
+/// Move the metadata of `old` to `new`, merging it with the metadata
+/// `new` may already have: tags are united, tags removed from either id
+/// staying removed, and the latest score is kept. With `keep_old`,
+/// the metadata is copied, e.g. when another file still has the old id.
+///
+/// Returns the names of the metadata carried over.
+pub fn relocate(
+    root: &Path,
+    old: &ResourceId,
+    new: &ResourceId,
+    keep_old: bool,
+) -> Result<Vec<&'static str>, AppError> {
+    let mut carried = Vec::new();
+
+    let tags = relocate_value::<TagSetWithTombstones>(
+        root,
+        TAG_STORAGE_FILE,
+        old,
+        new,
+        keep_old,
+    )?;
+    if tags {
+        carried.push(TAGS);
+    }
+    if relocate_value::<Score>(root, SCORE_STORAGE_FILE, old, new, keep_old)? {
+        carried.push(SCORES);
+    }
+
+    if let Some(properties) = read_properties(root, old)? {
+        store_properties(root, new.clone(), &properties)?;
+        if !keep_old {
+            std::fs::remove_dir_all(properties_folder(root, old))?;
+        }
+        carried.push(PROPERTIES);
+    }
+
+    Ok(carried)
+}
+
+/// Combine the value of `old` into the value of `new` in the storage,
+/// returning whether `old` had a value
+fn relocate_value<V>(
+    root: &Path,
+    file: &str,
+    old: &ResourceId,
+    new: &ResourceId,
+    keep_old: bool,
+) -> Result<bool, AppError>
+where
+    V: Clone + Serialize + DeserializeOwned + FromStr + Monoid<V>,
+{
+    let Some(mut storage) = open_storage::<V>(root, file)? else {
+        return Ok(false);
+    };
+    let Some(value) = storage.as_ref().get(&old.to_string()).cloned() else {
+        return Ok(false);
+    };
+
+    let value = match storage.as_ref().get(&new.to_string()) {
+        Some(existing) => V::combine(existing, &value),
+        None => value,
+    };
+    storage.set(new.to_string(), value);
+    if !keep_old {
+        storage.remove(&old.to_string())?;
+    }
+    storage.write_fs()?;
+    Ok(true)
+}
+
+/// Drop all the metadata of `id`.
+///
+/// Returns the names of the metadata removed.
+pub fn remove(
+    root: &Path,
+    id: &ResourceId,
+) -> Result<Vec<&'static str>, AppError> {
+    let mut removed = Vec::new();
+
+    for (name, file) in FILE_STORAGES {
+        let Some(mut storage) = open_storage::<StorageValue>(root, file)?
+        else {
+            continue;
+        };
+        if storage.as_ref().contains_key(&id.to_string()) {
+            storage.remove(&id.to_string())?;
+            storage.write_fs()?;
+            removed.push(name);
+        }
+    }
+
+    let folder = properties_folder(root, id);
+    if folder.exists() {
+        std::fs::remove_dir_all(folder)?;
+        removed.push(PROPERTIES);
+    }
+
+    Ok(removed)
+}
+
+/// Names of the metadata attached to `id`
+pub fn attached(
+    root: &Path,
+    id: &ResourceId,
+) -> Result<Vec<&'static str>, AppError> {
+    let mut attached = Vec::new();
+    for (name, file) in FILE_STORAGES {
+        if let Some(storage) = open_storage::<StorageValue>(root, file)? {
+            if storage.as_ref().contains_key(&id.to_string()) {
+                attached.push(name);
+            }
+        }
+    }
+    if properties_folder(root, id).exists() {
+        attached.push(PROPERTIES);
+    }
+    Ok(attached)
+}
@@ -1,4 +1,5 @@
 pub mod storage;
+pub mod storage_value;
 
 use clap::Parser;
 
@@ -302,6 +302,11 @@ impl Storage {
         }
     }
 
+    /// Ids of the resources loaded from the storage
+    pub fn ids(&self) -> &[ResourceId] {
+        &self.files
+    }
+
     pub fn list(&self, versions: bool) -> Result<String, AppError> {
         let mut output = String::new();
 
@@ -0,0 +1,69 @@
+use std::convert::Infallible;
+use std::fmt::Display;
+use std::str::FromStr;
+
+use fs_storage::monoid::Monoid;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Value of a `FileStorage` entry, when the type of the values
+/// stored is not known in advance.
+///
+/// Legacy version 2 values which aren't valid JSON are kept as strings.
+///
+/// When merging two values, the highest of two numbers is kept and arrays
+/// are merged. Other values, strings included, are replaced by the value
+/// merged in, as the latest write.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct StorageValue(pub Value);
+
+impl FromStr for StorageValue {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(StorageValue(
+            serde_json::from_str(s)
+                .unwrap_or_else(|_| Value::String(s.to_owned())),
+        ))
+    }
+}
+
+impl Display for StorageValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.0 {
+            Value::String(s) => write!(f, "{}", s),
+            value => write!(f, "{}", value),
+        }
+    }
+}
+
+impl Monoid<StorageValue> for StorageValue {
+    fn neutral() -> StorageValue {
+        StorageValue(Value::Null)
+    }
+
+    fn combine(a: &StorageValue, b: &StorageValue) -> StorageValue {
+        let value = match (&a.0, &b.0) {
+            (Value::Null, other) | (other, Value::Null) => other.clone(),
+            (Value::Number(x), Value::Number(y)) => {
+                if x.as_f64() > y.as_f64() {
+                    a.0.clone()
+                } else {
+                    b.0.clone()
+                }
+            }
+            (Value::Array(x), Value::Array(y)) => {
+                let mut union = x.clone();
+                for value in y {
+                    if !union.contains(value) {
+                        union.push(value.clone());
+                    }
+                }
+                Value::Array(union)
+            }
+            _ => b.0.clone(),
+        };
+        StorageValue(value)
+    }
+}
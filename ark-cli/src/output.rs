@@ -0,0 +1,214 @@
+//! Machine-readable output of the commands.
+//!
+//! With `--format json` a command prints a single JSON array of records,
+//! with `--format ndjson` it prints one record per line. Commands acting
+//! on a single item (e.g. `storage get`) and `watch` always print one
+//! record per line.
+//!
+//! The record types below are the schema of the output: fields may be
+//! added, but existing fields are never renamed or removed.
+//! `ark-cli schema <command>` prints the JSON Schema of a record.
+
+use std::path::PathBuf;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use fs_index::verify::Finding;
+use schemars::JsonSchema;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::{AppError, ResourceId};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+    /// Text meant to be read by humans, may change between versions
+    #[default]
+    Human,
+    /// A single JSON document
+    Json,
+    /// One JSON document per line
+    Ndjson,
+}
+
+impl OutputFormat {
+    pub fn is_human(self) -> bool {
+        self == OutputFormat::Human
+    }
+}
+
+/// Print `records` as a JSON array or as one record per line
+pub fn print_records<T: Serialize>(
+    format: OutputFormat,
+    records: &[T],
+) -> Result<(), AppError> {
+    if format == OutputFormat::Ndjson {
+        for record in records {
+            print_record(record)?;
+        }
+    } else {
+        println!("{}", serde_json::to_string(records)?);
+    }
+    Ok(())
+}
+
+/// Print a single record on its own line
+pub fn print_record<T: Serialize>(record: &T) -> Result<(), AppError> {
+    println!("{}", serde_json::to_string(record)?);
+    Ok(())
+}
+
+/// Resource printed by `list`.
+///
+/// Fields are present only if requested with the matching flag,
+/// `id` being the default.
+#[derive(Debug, Default, Serialize, JsonSchema)]
+pub struct ListEntry {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    /// Path relative to the root
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<PathBuf>,
+    /// URL stored in a link resource
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub link: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tags: Option<Vec<String>>,
+    /// Score of the resource, 0 if it has none
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub score: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub modified: Option<DateTime<Utc>>,
+}
+
+/// Change printed by `watch`, paths are relative to the root
+#[derive(Debug, Serialize, JsonSchema)]
+#[serde(tag = "event", rename_all = "lowercase")]
+pub enum WatchEvent {
+    Added {
+        id: String,
+        path: PathBuf,
+    },
+    Removed {
+        id: String,
+        path: PathBuf,
+    },
+    Modified {
+        old_id: String,
+        id: String,
+        path: PathBuf,
+    },
+    Moved {
+        id: String,
+        from: PathBuf,
+        to: PathBuf,
+    },
+    Storage {
+        storage: String,
+        path: PathBuf,
+    },
+}
+
+/// Paths sharing an id, printed by `collisions`
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct CollisionGroup {
+    pub id: String,
+    /// Whether files with different content share the id
+    pub collision: bool,
+    /// Paths relative to the root, split by their actual content:
+    /// paths inside one list are true duplicates
+    pub contents: Vec<Vec<PathBuf>>,
+}
+
+/// Entry printed by `storage list`, `storage get` and `storage set`.
+///
+/// Folder storages only list their keys, without values.
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct StorageRecord {
+    pub key: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<Value>,
+}
+
+/// Result of `storage sync`
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct StorageSyncReport {
+    pub storage: String,
+    /// Sync status before syncing
+    pub status: String,
+    /// Number of entries after syncing
+    pub entries: usize,
+    /// Number of entries added or changed by the other copy
+    pub merged: usize,
+}
+
+/// Problem found by `verify`
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct VerifyFinding {
+    /// One of `missing`, `metadata_drift` or `id_mismatch`
+    pub kind: String,
+    /// Path relative to the root
+    pub path: PathBuf,
+    /// Id stored in the index
+    pub id: String,
+    /// Id of the current content, for `id_mismatch`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub actual_id: Option<String>,
+    /// Whether `--fix` repaired the entry
+    pub fixed: bool,
+}
+
+impl VerifyFinding {
+    pub fn new(
+        finding: &Finding<ResourceId>,
+        fixed: &[Finding<ResourceId>],
+    ) -> Self {
+        let actual_id = match finding {
+            Finding::IdMismatch { actual, .. } => Some(actual.to_string()),
+            _ => None,
+        };
+        VerifyFinding {
+            kind: finding.kind().to_owned(),
+            path: finding.path().to_path_buf(),
+            id: finding.stored_id().to_string(),
+            actual_id,
+            fixed: fixed.contains(finding),
+        }
+    }
+}
+
+/// Entry printed by `scores top`
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct ScoreEntry {
+    pub id: String,
+    /// Path relative to the root, absent if the resource isn't indexed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<PathBuf>,
+    pub score: i64,
+}
+
+/// Entry printed by `stats recent`, and in the `stats report`
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct UsageEntry {
+    pub id: String,
+    /// Path relative to the root, absent if the resource isn't indexed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<PathBuf>,
+    pub opens: u64,
+    pub last_opened: DateTime<Utc>,
+}
+
+/// Opens of all resources during a day
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct DailyOpens {
+    pub date: NaiveDate,
+    pub opens: usize,
+}
+
+/// Aggregate usage printed by `stats report`
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct StatsReport {
+    /// Every day of the last month, oldest first
+    pub days: Vec<DailyOpens>,
+    /// Resources opened the most, most used first
+    pub most_used: Vec<UsageEntry>,
+}
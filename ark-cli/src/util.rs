@@ -1,4 +1,5 @@
 use crate::ResourceId;
+use canonical_path::CanonicalPathBuf;
 use fs_index::index::ResourceIndex;
 use fs_metadata::METADATA_STORAGE_FOLDER;
 use fs_properties::PROPERTIES_STORAGE_FOLDER;
@@ -174,7 +175,8 @@ pub fn translate_storage(
     storage: &str,
 ) -> Option<(PathBuf, Option<StorageType>)> {
     if let Ok(path) = PathBuf::from_str(storage) {
-        if path.exists() && path.is_dir() {
+        // Custom storage: either an atomic folder or a `FileStorage` file
+        if path.exists() {
             return Some((path, None));
         }
     }
@@ -258,3 +260,45 @@ pub fn read_storage_value(
         ResourceId::from_str(id).map_err(|_| AppError::InvalidEntryOption)?;
     storage.read(resource_id)
 }
+
+/// Delete a file, or move it to the trash with `trash`,
+/// which requires the `trash` feature
+pub fn delete_file(path: &Path, trash: bool) -> Result<(), AppError> {
+    if trash {
+        #[cfg(feature = "trash")]
+        return trash::delete(path)
+            .map_err(|e| AppError::FileOperationError(e.to_string()));
+        #[cfg(not(feature = "trash"))]
+        return Err(AppError::UnsupportedOption(
+            "--trash requires ark-cli built with the `trash` feature"
+                .to_owned(),
+        ));
+    }
+
+    Ok(std::fs::remove_file(path)?)
+}
+
+/// Id of the resource given by `resource`: the id itself if some indexed
+/// resource has it, a path otherwise
+pub fn resolve_resource(
+    index: &ResourceIndex<ResourceId>,
+    resource: &str,
+) -> Result<ResourceId, AppError> {
+    if let Ok(id) = ResourceId::from_str(resource) {
+        if index.id2path.contains_key(&id) {
+            return Ok(id);
+        }
+    }
+
+    let path = CanonicalPathBuf::canonicalize(resource)?;
+    index
+        .path2id
+        .get(&path)
+        .map(|entry| entry.id.clone())
+        .ok_or_else(|| {
+            AppError::IndexError(format!(
+                "{} is not an indexed path or id",
+                resource
+            ))
+        })
+}
@@ -0,0 +1,126 @@
+use std::fs::{self, File};
+use std::path::Path;
+
+use predicates::str::contains;
+use serde_json::{json, Value};
+use tempdir::TempDir;
+
+mod common;
+use common::ark_cli;
+
+fn populate(root: &Path) {
+    let ark = root.join(".ark");
+    fs::create_dir_all(ark.join("user/properties/1234")).unwrap();
+    fs::write(
+        ark.join("user/properties/1234/1234_device.1"),
+        r#"{"title":"old"}"#,
+    )
+    .unwrap();
+    fs::write(
+        ark.join("user/properties/1234/1234_device.2"),
+        r#"{"title":"lena"}"#,
+    )
+    .unwrap();
+    fs::write(
+        ark.join("user/custom.json"),
+        r#"{"version":3,"entries":{"a":1}}"#,
+    )
+    .unwrap();
+    fs::create_dir_all(ark.join("cache/previews")).unwrap();
+    fs::write(ark.join("cache/previews/1234"), "preview").unwrap();
+    fs::write(ark.join("index"), "").unwrap();
+}
+
+#[test]
+fn backup_and_restore_roundtrip() {
+    let home = TempDir::new("ark-cli-home").unwrap();
+    let source = TempDir::new("ark-cli-source").unwrap();
+    let target = TempDir::new("ark-cli-target").unwrap();
+    let archive = home.path().join("backup.tar.zst");
+    populate(source.path());
+
+    ark_cli(home.path())
+        .arg("backup")
+        .arg(source.path())
+        .arg("-o")
+        .arg(&archive)
+        .assert()
+        .success();
+
+    // The target already has some data which must be kept
+    let target_ark = target.path().join(".ark");
+    fs::create_dir_all(target_ark.join("user")).unwrap();
+    fs::write(
+        target_ark.join("user/custom.json"),
+        r#"{"version":3,"entries":{"b":2}}"#,
+    )
+    .unwrap();
+
+    ark_cli(home.path())
+        .arg("restore")
+        .arg(target.path())
+        .arg(&archive)
+        .assert()
+        .success();
+
+    // Version history is preserved
+    for version in 1..=2 {
+        let name = format!("user/properties/1234/1234_device.{}", version);
+        assert_eq!(
+            fs::read(source.path().join(".ark").join(&name)).unwrap(),
+            fs::read(target_ark.join(&name)).unwrap()
+        );
+    }
+    // Caches and the index are not archived by default
+    assert!(!target_ark.join("cache").exists());
+    assert!(!target_ark.join("index").exists());
+
+    let custom: Value = serde_json::from_slice(
+        &fs::read(target_ark.join("user/custom.json")).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(custom["entries"], json!({ "a": 1, "b": 2 }));
+}
+
+#[test]
+fn restore_rejects_mismatched_hash_kind() {
+    let home = TempDir::new("ark-cli-home").unwrap();
+    let target = TempDir::new("ark-cli-target").unwrap();
+    let archive = home.path().join("backup.tar.zst");
+
+    let manifest = serde_json::to_vec(&json!({
+        "format": 1,
+        "ark_version": "0.1.0",
+        "hash": "blake3",
+        "created": 0,
+    }))
+    .unwrap();
+    let encoder = zstd::Encoder::new(File::create(&archive).unwrap(), 0)
+        .unwrap()
+        .auto_finish();
+    let mut builder = tar::Builder::new(encoder);
+    let mut header = tar::Header::new_gnu();
+    header.set_size(manifest.len() as u64);
+    header.set_mode(0o644);
+    header.set_cksum();
+    builder
+        .append_data(&mut header, "manifest.json", manifest.as_slice())
+        .unwrap();
+    builder.into_inner().unwrap();
+
+    ark_cli(home.path())
+        .arg("restore")
+        .arg(target.path())
+        .arg(&archive)
+        .assert()
+        .failure()
+        .stderr(contains("--force"));
+
+    ark_cli(home.path())
+        .arg("restore")
+        .arg(target.path())
+        .arg(&archive)
+        .arg("--force")
+        .assert()
+        .success();
+}
@@ -0,0 +1,49 @@
+//! Helpers shared by the integration tests, every test file only uses
+//! some of them.
+#![allow(dead_code)]
+
+use std::fs;
+use std::path::Path;
+
+use assert_cmd::Command;
+use serde_json::{json, Value};
+use tempdir::TempDir;
+
+/// Ids of the files containing "a", "b" and "c"
+pub const ID_A: &str = "3904355907";
+pub const ID_B: &str = "1908338681";
+pub const ID_C: &str = "112844655";
+
+/// `ark-cli` with `home` as home directory, so that the id of the app
+/// and the settings of the user are kept apart from the real ones
+pub fn ark_cli(home: &Path) -> Command {
+    let mut cmd = Command::cargo_bin("ark-cli").unwrap();
+    cmd.env("HOME", home);
+    cmd
+}
+
+/// Root with a file for every `(name, content)`
+pub fn fixture_root(files: &[(&str, &str)]) -> TempDir {
+    let root = TempDir::new("ark-cli-root").unwrap();
+    for (name, content) in files {
+        fs::write(root.path().join(name), content).unwrap();
+    }
+    root
+}
+
+/// Write the tags storage of `root`, with the tags of every id
+pub fn write_tags(root: &Path, entries: Value) {
+    let user = root.join(".ark").join("user");
+    fs::create_dir_all(&user).unwrap();
+    fs::write(
+        user.join("tags"),
+        json!({ "version": 3, "entries": entries }).to_string(),
+    )
+    .unwrap();
+}
+
+/// Tags of every id in the tags storage of `root`
+pub fn read_tags(root: &Path) -> Value {
+    let tags = fs::read_to_string(root.join(".ark/user/tags")).unwrap();
+    serde_json::from_str::<Value>(&tags).unwrap()["entries"].clone()
+}
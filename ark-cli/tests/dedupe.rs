@@ -0,0 +1,78 @@
+use std::fs::{self, File};
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+use predicates::str::contains;
+use tempdir::TempDir;
+
+mod common;
+use common::ark_cli;
+
+/// `a.txt` and `b.txt` are duplicates, `a.txt` being older
+fn fixtures(root: &Path) {
+    fs::write(root.join("a.txt"), "duplicated content").unwrap();
+    fs::write(root.join("b.txt"), "duplicated content").unwrap();
+    fs::write(root.join("c.txt"), "unique content").unwrap();
+
+    let old = SystemTime::now() - Duration::from_secs(3600);
+    File::options()
+        .write(true)
+        .open(root.join("a.txt"))
+        .unwrap()
+        .set_modified(old)
+        .unwrap();
+}
+
+#[test]
+fn collisions_lists_duplicates() {
+    let home = TempDir::new("ark-cli-home").unwrap();
+    let root = TempDir::new("ark-cli-root").unwrap();
+    fixtures(root.path());
+
+    ark_cli(home.path())
+        .arg("collisions")
+        .arg(root.path())
+        .assert()
+        .success()
+        .stdout(contains("duplicate  a.txt"))
+        .stdout(contains("duplicate  b.txt"));
+}
+
+#[test]
+fn dedupe_is_dry_run_by_default() {
+    let home = TempDir::new("ark-cli-home").unwrap();
+    let root = TempDir::new("ark-cli-root").unwrap();
+    fixtures(root.path());
+
+    ark_cli(home.path())
+        .arg("dedupe")
+        .arg(root.path())
+        .args(["--keep", "oldest"])
+        .assert()
+        .success()
+        .stdout(contains("Would remove b.txt"))
+        .stdout(contains("18 bytes"));
+
+    assert!(root.path().join("a.txt").exists());
+    assert!(root.path().join("b.txt").exists());
+    assert!(root.path().join("c.txt").exists());
+}
+
+#[test]
+fn dedupe_apply_keeps_oldest() {
+    let home = TempDir::new("ark-cli-home").unwrap();
+    let root = TempDir::new("ark-cli-root").unwrap();
+    fixtures(root.path());
+
+    ark_cli(home.path())
+        .arg("dedupe")
+        .arg(root.path())
+        .args(["--keep", "oldest", "--apply"])
+        .assert()
+        .success()
+        .stdout(contains("Removed b.txt"));
+
+    assert!(root.path().join("a.txt").exists());
+    assert!(!root.path().join("b.txt").exists());
+    assert!(root.path().join("c.txt").exists());
+}
@@ -0,0 +1,125 @@
+use std::fs;
+
+use predicates::str::contains;
+use serde_json::json;
+use tempdir::TempDir;
+
+mod common;
+use common::{ark_cli, fixture_root, read_tags, write_tags, ID_A, ID_C};
+
+/// Root with `a.txt` tagged as "todo"
+fn tagged_root() -> TempDir {
+    let root = fixture_root(&[("a.txt", "a"), ("b.txt", "b")]);
+    write_tags(root.path(), json!({ ID_A: "todo" }));
+    root
+}
+
+#[test]
+fn replace_carries_tags_to_new_id() {
+    let home = TempDir::new("ark-cli-home").unwrap();
+    let root = tagged_root();
+    let new_content = home.path().join("new.txt");
+    fs::write(&new_content, "c").unwrap();
+
+    ark_cli(home.path())
+        .args(["file", "replace", "--root-dir"])
+        .arg(root.path())
+        .arg(root.path().join("a.txt"))
+        .arg(&new_content)
+        .assert()
+        .success()
+        .stdout(contains(format!("Moved tags to {}", ID_C)));
+
+    assert_eq!(fs::read_to_string(root.path().join("a.txt")).unwrap(), "c");
+    assert_eq!(
+        read_tags(root.path()),
+        json!({ ID_C: { "added": { "todo": 0 } } })
+    );
+
+    ark_cli(home.path())
+        .args(["file", "info", "--root-dir"])
+        .arg(root.path())
+        .arg(ID_C)
+        .assert()
+        .success()
+        .stdout(contains("path:       a.txt"))
+        .stdout(contains("tags:       todo"));
+}
+
+#[test]
+fn replace_merges_tags_of_both_ids() {
+    let home = TempDir::new("ark-cli-home").unwrap();
+    let root = tagged_root();
+    let new_content = home.path().join("new.txt");
+    fs::write(&new_content, "c").unwrap();
+    let entries = json!({
+        ID_A: "todo,urgent",
+        ID_C: { "added": { "done": 5, "todo": 1 }, "removed": { "todo": 3 } },
+    });
+    write_tags(root.path(), entries);
+
+    ark_cli(home.path())
+        .args(["file", "replace", "--root-dir"])
+        .arg(root.path())
+        .arg(root.path().join("a.txt"))
+        .arg(&new_content)
+        .assert()
+        .success()
+        .stdout(contains(format!("Moved tags to {}", ID_C)));
+
+    // "todo" was removed from the new id after it was added to the old one
+    let merged = json!({
+        "added": { "done": 5, "todo": 1, "urgent": 0 },
+        "removed": { "todo": 3 },
+    });
+    assert_eq!(read_tags(root.path()), json!({ ID_C: merged }));
+
+    ark_cli(home.path())
+        .args(["file", "info", "--root-dir"])
+        .arg(root.path())
+        .arg(ID_C)
+        .assert()
+        .success()
+        .stdout(contains("tags:       done,urgent"));
+}
+
+#[test]
+fn rm_removes_metadata() {
+    let home = TempDir::new("ark-cli-home").unwrap();
+    let root = tagged_root();
+
+    ark_cli(home.path())
+        .args(["file", "rm", "--yes", "--root-dir"])
+        .arg(root.path())
+        .arg(root.path().join("a.txt"))
+        .assert()
+        .success()
+        .stdout(contains(format!("Removed tags of {}", ID_A)));
+
+    assert!(!root.path().join("a.txt").exists());
+    assert_eq!(read_tags(root.path()), json!({}));
+}
+
+#[test]
+fn rm_keeps_metadata() {
+    let home = TempDir::new("ark-cli-home").unwrap();
+    let root = tagged_root();
+
+    ark_cli(home.path())
+        .args(["file", "rm", "--keep-meta", "--yes", "--root-dir"])
+        .arg(root.path())
+        .arg(root.path().join("a.txt"))
+        .assert()
+        .success()
+        .stdout(contains(format!("Kept tags of {}", ID_A)));
+
+    assert!(!root.path().join("a.txt").exists());
+    assert_eq!(read_tags(root.path()), json!({ ID_A: "todo" }));
+
+    ark_cli(home.path())
+        .args(["file", "info", "--root-dir"])
+        .arg(root.path())
+        .arg(ID_A)
+        .assert()
+        .failure();
+}
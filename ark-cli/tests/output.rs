@@ -0,0 +1,129 @@
+use std::fs;
+use std::path::Path;
+
+use jsonschema::JSONSchema;
+use serde_json::Value;
+use tempdir::TempDir;
+
+mod common;
+use common::{ark_cli, fixture_root};
+
+/// Two identical files and a different one
+const FILES: [(&str, &str); 3] =
+    [("a.txt", "a"), ("b.txt", "b"), ("c.txt", "a")];
+
+fn expected(name: &str) -> Value {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures/output")
+        .join(name);
+    serde_json::from_str(&fs::read_to_string(path).unwrap()).unwrap()
+}
+
+fn run(home: &Path, args: &[&str]) -> String {
+    let output = ark_cli(home).args(args).output().unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).unwrap()
+}
+
+/// Check the JSON and NDJSON outputs against the snapshot and the schema
+fn check_output(home: &Path, args: &[&str], schema: &str, snapshot: &str) {
+    let json: Value = serde_json::from_str(&run(
+        home,
+        &[&["--format", "json"], args].concat(),
+    ))
+    .unwrap();
+    assert_eq!(json, expected(snapshot));
+
+    let ndjson: Vec<Value> =
+        run(home, &[&["--format", "ndjson"], args].concat())
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+    assert_eq!(Value::Array(ndjson), json);
+
+    let schema: Value =
+        serde_json::from_str(&run(home, &["schema", schema])).unwrap();
+    let schema = JSONSchema::compile(&schema).unwrap();
+    for record in json.as_array().unwrap() {
+        assert!(schema.is_valid(record), "{} doesn't match", record);
+    }
+}
+
+#[test]
+fn list_output() {
+    let home = TempDir::new("ark-cli-home").unwrap();
+    let root = fixture_root(&FILES);
+    let root = root.path().to_str().unwrap();
+
+    check_output(
+        home.path(),
+        &["list", root, "--id", "--path"],
+        "list",
+        "list.json",
+    );
+}
+
+#[test]
+fn collisions_output() {
+    let home = TempDir::new("ark-cli-home").unwrap();
+    let root = fixture_root(&FILES);
+    let root = root.path().to_str().unwrap();
+
+    check_output(
+        home.path(),
+        &["collisions", root],
+        "collisions",
+        "collisions.json",
+    );
+}
+
+#[test]
+fn storage_list_output() {
+    let home = TempDir::new("ark-cli-home").unwrap();
+    let storage = home.path().join("custom.json");
+    fs::write(&storage, r#"{"version":3,"entries":{"a":1,"b":["x","y"]}}"#)
+        .unwrap();
+
+    let args = ["storage", "list", storage.to_str().unwrap()];
+
+    // a single object with `--format json`, keyed by key
+    let json: Value = serde_json::from_str(&run(
+        home.path(),
+        &[&["--format", "json"][..], &args[..]].concat(),
+    ))
+    .unwrap();
+    assert_eq!(json, expected("storage-list.json"));
+
+    let schema: Value =
+        serde_json::from_str(&run(home.path(), &["schema", "storage-list"]))
+            .unwrap();
+    let schema = JSONSchema::compile(&schema).unwrap();
+    let ndjson = run(
+        home.path(),
+        &[&["--format", "ndjson"][..], &args[..]].concat(),
+    );
+    let mut entries = serde_json::Map::new();
+    for line in ndjson.lines() {
+        let record: Value = serde_json::from_str(line).unwrap();
+        assert!(schema.is_valid(&record), "{} doesn't match", record);
+        let key = record["key"].as_str().unwrap().to_owned();
+        entries.insert(key, record["value"].clone());
+    }
+    assert_eq!(Value::Object(entries), json);
+}
+
+#[test]
+fn unsupported_format_fails() {
+    let home = TempDir::new("ark-cli-home").unwrap();
+    let root = fixture_root(&FILES);
+
+    ark_cli(home.path())
+        .args(["--format", "json", "backup"])
+        .arg(root.path())
+        .assert()
+        .failure();
+}
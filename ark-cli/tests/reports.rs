@@ -0,0 +1,158 @@
+use std::fs;
+use std::path::Path;
+
+use chrono::Utc;
+use fs_atomic_versions::app_id;
+use fs_atomic_versions::atomic::{modify_json, AtomicFile};
+use fs_storage::base_storage::BaseStorage;
+use fs_storage::file_storage::FileStorage;
+use fs_storage::now_millis;
+use predicates::str::contains;
+use serde_json::{json, Value};
+use tempdir::TempDir;
+
+mod common;
+use common::{ark_cli, fixture_root, ID_A, ID_B, ID_C};
+
+const DAY: u64 = 24 * 60 * 60 * 1000;
+
+const FILES: [(&str, &str); 3] =
+    [("a.txt", "a"), ("b.txt", "b"), ("c.txt", "c")];
+
+fn seed_scores(root: &Path, scores: &[(&str, i32)]) {
+    let path = root.join(".ark/user/scores");
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    let mut storage =
+        FileStorage::<String, i32>::new("scores".to_owned(), &path).unwrap();
+    for (id, score) in scores {
+        storage.set(id.to_string(), *score);
+    }
+    storage.write_fs().unwrap();
+}
+
+fn seed_opens(home: &Path, root: &Path, id: &str, opens: &[u64]) {
+    app_id::load(home).unwrap();
+    let file = AtomicFile::new(root.join(".ark/stats").join(id)).unwrap();
+    modify_json(&file, |stats: &mut Option<Value>| {
+        *stats = Some(json!({ "opens": opens }));
+    })
+    .unwrap();
+}
+
+fn json_output(home: &Path, root: &Path, args: &[&str]) -> Value {
+    let output = ark_cli(home)
+        .args(["--format", "json"])
+        .args(args)
+        .arg("--root-dir")
+        .arg(root)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    serde_json::from_slice(&output.stdout).unwrap()
+}
+
+#[test]
+fn scores_top_orders_by_score() {
+    let home = TempDir::new("ark-cli-home").unwrap();
+    let root = fixture_root(&FILES);
+    seed_scores(root.path(), &[(ID_A, 5), (ID_B, 15), (ID_C, -1)]);
+
+    let top =
+        json_output(home.path(), root.path(), &["scores", "top", "-n", "2"]);
+    assert_eq!(
+        top,
+        json!([
+            { "id": ID_B, "path": "b.txt", "score": 15 },
+            { "id": ID_A, "path": "a.txt", "score": 5 },
+        ])
+    );
+
+    ark_cli(home.path())
+        .args(["scores", "set", "--root-dir"])
+        .arg(root.path())
+        .arg(root.path().join("c.txt"))
+        .arg("10")
+        .assert()
+        .success();
+
+    ark_cli(home.path())
+        .args(["scores", "top", "--root-dir"])
+        .arg(root.path())
+        .assert()
+        .success()
+        .stdout(contains(format!(
+            "    15  {}    b.txt\n    10  {}     c.txt\n     5  {}    a.txt\n",
+            ID_B, ID_C, ID_A
+        )));
+}
+
+#[test]
+fn missing_storages_are_empty() {
+    let home = TempDir::new("ark-cli-home").unwrap();
+    let root = fixture_root(&FILES);
+
+    for command in [["scores", "top"], ["stats", "recent"]] {
+        ark_cli(home.path())
+            .args(["--format", "json"])
+            .args(command)
+            .arg("--root-dir")
+            .arg(root.path())
+            .assert()
+            .success()
+            .stdout("[]\n")
+            .stderr(contains("No "));
+    }
+}
+
+#[test]
+fn stats_recent_and_report() {
+    let home = TempDir::new("ark-cli-home").unwrap();
+    let root = fixture_root(&FILES);
+    let now = now_millis();
+    seed_opens(
+        home.path(),
+        root.path(),
+        ID_A,
+        &[now - 2 * DAY, now - DAY, now],
+    );
+    seed_opens(home.path(), root.path(), ID_B, &[now - 40 * DAY]);
+    seed_opens(home.path(), root.path(), ID_C, &[now - DAY, now - 3 * DAY]);
+
+    let recent = json_output(home.path(), root.path(), &["stats", "recent"]);
+    let order: Vec<&str> = recent
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|entry| entry["path"].as_str().unwrap())
+        .collect();
+    assert_eq!(order, ["a.txt", "c.txt", "b.txt"]);
+    assert_eq!(recent[0]["opens"], 3);
+
+    let report = json_output(home.path(), root.path(), &["stats", "report"]);
+    let days = report["days"].as_array().unwrap();
+    assert_eq!(days.len(), 30);
+    assert_eq!(
+        days.last().unwrap()["date"],
+        Utc::now().date_naive().to_string()
+    );
+    assert_eq!(days.last().unwrap()["opens"], 1);
+    let opens: u64 = days
+        .iter()
+        .map(|day| day["opens"].as_u64().unwrap())
+        .sum();
+    // The open of b.txt is older than a month
+    assert_eq!(opens, 5);
+
+    let most_used: Vec<(&str, u64)> = report["most_used"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|entry| {
+            (
+                entry["id"].as_str().unwrap(),
+                entry["opens"].as_u64().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(most_used, [(ID_A, 3), (ID_C, 2), (ID_B, 1)]);
+}
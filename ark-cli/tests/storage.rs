@@ -0,0 +1,106 @@
+use std::fs;
+use std::path::Path;
+
+use predicates::prelude::*;
+use predicates::str::contains;
+use serde_json::{json, Value};
+use tempdir::TempDir;
+
+mod common;
+use common::ark_cli;
+
+fn storage_file(root: &Path) -> String {
+    let path = root.join(".ark").join("user").join("custom.json");
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    path.display().to_string()
+}
+
+#[test]
+fn set_then_get_and_list() {
+    let dir = TempDir::new("ark-cli").unwrap();
+    let storage = storage_file(dir.path());
+    fs::write(&storage, r#"{"version":3,"entries":{"a":1}}"#).unwrap();
+
+    ark_cli(dir.path())
+        .args(["storage", "set", &storage, "b", "[\"x\",\"y\"]"])
+        .assert()
+        .success();
+
+    let output = ark_cli(dir.path())
+        .args(["--json", "storage", "list", &storage])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let entries: Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(entries, json!({ "a": 1, "b": ["x", "y"] }));
+
+    ark_cli(dir.path())
+        .args(["storage", "get", &storage, "a"])
+        .assert()
+        .success()
+        .stdout(contains("1"));
+
+    ark_cli(dir.path())
+        .args(["storage", "get", &storage, "missing"])
+        .assert()
+        .failure();
+}
+
+#[test]
+fn list_legacy_storage() {
+    let dir = TempDir::new("ark-cli").unwrap();
+    let storage = storage_file(dir.path());
+    fs::write(&storage, "version: 2\nkey1:1\nkey2:tag1,tag2\n").unwrap();
+
+    let output = ark_cli(dir.path())
+        .args(["storage", "list", &storage, "--json"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let entries: Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(entries, json!({ "key1": 1, "key2": "tag1,tag2" }));
+}
+
+#[test]
+fn sync_merges_other_copy() {
+    let dir = TempDir::new("ark-cli").unwrap();
+    let storage = storage_file(dir.path());
+    fs::write(&storage, r#"{"version":3,"entries":{"a":"x","n":1}}"#).unwrap();
+    let other = dir.path().join("other.json");
+    fs::write(
+        &other,
+        r#"{"version":3,"entries":{"a":"y","n":3,"t":["p"]}}"#,
+    )
+    .unwrap();
+    let other = other.display().to_string();
+
+    let sync = || {
+        let output = ark_cli(dir.path())
+            .args(["--json", "storage", "sync", &storage, &other])
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        serde_json::from_slice::<Value>(&output.stdout).unwrap()
+    };
+    let status = sync();
+    assert_eq!(status["status"], "StorageStale");
+    assert_eq!(status["entries"], 3);
+    assert_eq!(status["merged"], 3);
+
+    // strings aren't concatenated, the value merged in wins
+    ark_cli(dir.path())
+        .args(["--json", "storage", "get", &storage, "a"])
+        .assert()
+        .success()
+        .stdout(contains("\"y\""))
+        .stdout(contains("xy").not());
+
+    let status = sync();
+    assert_eq!(status["status"], "InSync");
+    assert_eq!(status["merged"], 0);
+
+    ark_cli(dir.path())
+        .args(["storage", "sync", &storage, "missing.json"])
+        .assert()
+        .failure();
+}
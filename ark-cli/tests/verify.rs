@@ -0,0 +1,129 @@
+use std::fs::{self, File};
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+use dev_hash::Crc32;
+use fs_index::ResourceIndex;
+use serde_json::{json, Value};
+use tempdir::TempDir;
+
+mod common;
+use common::{ark_cli, fixture_root, read_tags, write_tags, ID_A, ID_C};
+
+/// Indexed root with `a.txt` tagged as "old"
+fn fixture_library() -> TempDir {
+    let root = fixture_root(&[("a.txt", "a"), ("b.txt", "b")]);
+    ResourceIndex::<Crc32>::build(root.path())
+        .store()
+        .unwrap();
+    write_tags(root.path(), json!({ ID_A: "old" }));
+    root
+}
+
+fn set_modified(path: &Path, modified: SystemTime) {
+    File::options()
+        .write(true)
+        .open(path)
+        .unwrap()
+        .set_modified(modified)
+        .unwrap();
+}
+
+/// Run `verify` with JSON output, returning the success and the findings
+fn verify(home: &Path, root: &Path, args: &[&str]) -> (bool, Vec<Value>) {
+    let output = ark_cli(home)
+        .args(["--format", "json", "verify"])
+        .arg(root)
+        .args(args)
+        .output()
+        .unwrap();
+    let findings: Value = serde_json::from_slice(&output.stdout).unwrap();
+    (
+        output.status.success(),
+        findings.as_array().unwrap().clone(),
+    )
+}
+
+#[test]
+fn clean_library_passes() {
+    let home = TempDir::new("ark-cli-home").unwrap();
+    let root = fixture_library();
+
+    let (success, findings) = verify(home.path(), root.path(), &["--full"]);
+    assert!(success);
+    assert!(findings.is_empty());
+}
+
+#[test]
+fn missing_file_is_dropped_with_fix() {
+    let home = TempDir::new("ark-cli-home").unwrap();
+    let root = fixture_library();
+    fs::remove_file(root.path().join("b.txt")).unwrap();
+
+    let (success, findings) = verify(home.path(), root.path(), &[]);
+    assert!(!success);
+    assert_eq!(findings.len(), 1);
+    assert_eq!(findings[0]["kind"], "missing");
+    assert_eq!(findings[0]["path"], "b.txt");
+
+    // Dropping entries needs a confirmation
+    let (success, findings) = verify(home.path(), root.path(), &["--fix"]);
+    assert!(!success);
+    assert_eq!(findings[0]["fixed"], false);
+
+    let (success, findings) =
+        verify(home.path(), root.path(), &["--fix", "--yes"]);
+    assert!(success);
+    assert_eq!(findings[0]["fixed"], true);
+
+    let (success, findings) = verify(home.path(), root.path(), &[]);
+    assert!(success);
+    assert!(findings.is_empty());
+}
+
+#[test]
+fn metadata_drift_is_fixed() {
+    let home = TempDir::new("ark-cli-home").unwrap();
+    let root = fixture_library();
+    set_modified(
+        &root.path().join("b.txt"),
+        SystemTime::now() + Duration::from_secs(3600),
+    );
+
+    let (success, findings) = verify(home.path(), root.path(), &[]);
+    assert!(!success);
+    assert_eq!(findings[0]["kind"], "metadata_drift");
+
+    let (success, _) = verify(home.path(), root.path(), &["--fix"]);
+    assert!(success);
+    let (success, findings) = verify(home.path(), root.path(), &[]);
+    assert!(success);
+    assert!(findings.is_empty());
+}
+
+#[test]
+fn id_mismatch_moves_tags() {
+    let home = TempDir::new("ark-cli-home").unwrap();
+    let root = fixture_library();
+    let path = root.path().join("a.txt");
+    let modified = fs::metadata(&path).unwrap().modified().unwrap();
+    fs::write(&path, "c").unwrap();
+    set_modified(&path, modified);
+
+    // Only a full verification reads content with an unchanged timestamp
+    let (success, _) = verify(home.path(), root.path(), &[]);
+    assert!(success);
+    let (success, findings) = verify(home.path(), root.path(), &["--full"]);
+    assert!(!success);
+    assert_eq!(findings[0]["kind"], "id_mismatch");
+    assert_eq!(findings[0]["id"], ID_A);
+    assert_eq!(findings[0]["actual_id"], ID_C);
+
+    let (success, _) = verify(home.path(), root.path(), &["--full", "--fix"]);
+    assert!(success);
+    let (success, _) = verify(home.path(), root.path(), &["--full"]);
+    assert!(success);
+
+    let tags = read_tags(root.path());
+    assert_eq!(tags, json!({ ID_C: { "added": { "old": 0 } } }));
+}
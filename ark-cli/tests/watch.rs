@@ -0,0 +1,61 @@
+use std::fs;
+use std::io::{BufRead, BufReader};
+use std::process::{Command, Stdio};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use serde_json::Value;
+use tempdir::TempDir;
+
+#[test]
+fn watch_prints_ndjson_events() {
+    let home = TempDir::new("ark-cli-home").unwrap();
+    let root = TempDir::new("ark-cli-root").unwrap();
+    fs::write(root.path().join("existing.txt"), "existing").unwrap();
+
+    let mut child = Command::new(assert_cmd::cargo::cargo_bin("ark-cli"))
+        .env("HOME", home.path())
+        .args(["--json", "watch"])
+        .arg(root.path())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    let (sender, receiver) = mpsc::channel();
+    let stdout = child.stdout.take().unwrap();
+    thread::spawn(move || {
+        for line in BufReader::new(stdout)
+            .lines()
+            .map_while(Result::ok)
+        {
+            if sender.send(line).is_err() {
+                break;
+            }
+        }
+    });
+
+    // Let the watcher build the index first
+    thread::sleep(Duration::from_millis(1000));
+    fs::write(root.path().join("new.txt"), "new file").unwrap();
+    fs::remove_file(root.path().join("existing.txt")).unwrap();
+
+    let mut events = Vec::new();
+    while events.len() < 2 {
+        match receiver.recv_timeout(Duration::from_secs(10)) {
+            Ok(line) => {
+                events.push(serde_json::from_str::<Value>(&line).unwrap())
+            }
+            Err(_) => break,
+        }
+    }
+    child.kill().unwrap();
+    child.wait().unwrap();
+
+    assert!(events
+        .iter()
+        .any(|e| e["event"] == "added" && e["path"] == "new.txt"));
+    assert!(events
+        .iter()
+        .any(|e| e["event"] == "removed" && e["path"] == "existing.txt"));
+}
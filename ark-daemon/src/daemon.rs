@@ -0,0 +1,148 @@
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Receiver;
+use std::sync::{Arc, MutexGuard, Weak};
+use std::time::Duration;
+
+use serde_json::{json, Value};
+use tokio::sync::broadcast;
+
+use data_error::Result;
+use fs_index::index::IndexUpdate;
+use fs_index::watch::IndexWatcher;
+use fs_index::ResourceIndex;
+
+use crate::rpc::{Notification, RpcError};
+use crate::ResourceId;
+
+/// Number of index updates kept for slow subscribers
+const UPDATES_CAPACITY: usize = 64;
+
+/// Time the index waits for more changes before it is stored
+const STORE_DEBOUNCE: Duration = Duration::from_secs(1);
+
+/// A root folder served by the daemon
+pub struct Root {
+    /// Canonical path of the root
+    pub path: PathBuf,
+    /// Keeps the index up to date with the files of the root
+    watcher: IndexWatcher<ResourceId>,
+}
+
+impl Root {
+    /// Current state of the index, updates wait until the guard is dropped
+    pub fn index(&self) -> MutexGuard<'_, ResourceIndex<ResourceId>> {
+        self.watcher.index()
+    }
+
+    /// Path relative to the root, as returned to clients
+    pub fn relative(&self, path: &Path) -> PathBuf {
+        path.strip_prefix(&self.path)
+            .map(Path::to_path_buf)
+            .unwrap_or_else(|_| path.to_path_buf())
+    }
+
+    pub fn entry(&self, id: &ResourceId, path: &Path) -> Value {
+        json!({
+            "id": id.to_string(),
+            "path": self.relative(path),
+        })
+    }
+}
+
+/// State shared by all connections
+pub struct Daemon {
+    roots: Vec<Arc<Root>>,
+    updates: broadcast::Sender<Arc<Notification>>,
+}
+
+impl Daemon {
+    /// Load (or build) the index of every root and watch the roots,
+    /// notifying subscribers about the changes
+    pub fn new<P: AsRef<Path>>(roots: &[P]) -> Result<Self> {
+        let (updates, _) = broadcast::channel(UPDATES_CAPACITY);
+        let roots = roots
+            .iter()
+            .map(|root| {
+                let path = root.as_ref().canonicalize()?;
+                log::info!("Loading index of {}", path.display());
+                let mut index = ResourceIndex::provide(&path)?;
+                index.set_auto_store(Some(STORE_DEBOUNCE));
+                let (watcher, root_updates) = index.watch()?;
+                let root = Arc::new(Root { path, watcher });
+                let notify = {
+                    let root = Arc::downgrade(&root);
+                    let updates = updates.clone();
+                    move || notify_updates(&root, root_updates, &updates)
+                };
+                std::thread::spawn(notify);
+                Ok(root)
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self { roots, updates })
+    }
+
+    /// Find the root a request refers to.
+    ///
+    /// The root may be omitted when only one root is served.
+    pub fn root(
+        &self,
+        root: Option<&str>,
+    ) -> std::result::Result<&Arc<Root>, RpcError> {
+        match root {
+            None if self.roots.len() == 1 => Ok(&self.roots[0]),
+            None => Err(RpcError::invalid_params(
+                "`root` is required when serving several roots",
+            )),
+            Some(root) => {
+                let path = Path::new(root).canonicalize().map_err(|_| {
+                    RpcError::not_found(format!("Unknown root {}", root))
+                })?;
+                self.roots
+                    .iter()
+                    .find(|served| served.path == path)
+                    .ok_or_else(|| {
+                        RpcError::not_found(format!("Unknown root {}", root))
+                    })
+            }
+        }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<Arc<Notification>> {
+        self.updates.subscribe()
+    }
+}
+
+/// Send an `index.update` notification for every update of the index
+/// of `root`, until the watcher of the root is dropped
+fn notify_updates(
+    root: &Weak<Root>,
+    root_updates: Receiver<IndexUpdate<ResourceId>>,
+    updates: &broadcast::Sender<Arc<Notification>>,
+) {
+    for update in root_updates {
+        let Some(root) = root.upgrade() else {
+            break;
+        };
+        let added: Vec<Value> = update
+            .added
+            .iter()
+            .map(|(path, id)| root.entry(id, AsRef::<Path>::as_ref(path)))
+            .collect();
+        let deleted: Vec<String> = update
+            .deleted
+            .iter()
+            .map(|id| id.to_string())
+            .collect();
+        let notification = Notification::new(
+            "index.update",
+            json!({
+                "root": root.path.display().to_string(),
+                "added": added,
+                "deleted": deleted,
+            }),
+        );
+        // Sending only fails when nobody is subscribed
+        let _ = updates.send(Arc::new(notification));
+    }
+}
@@ -0,0 +1,26 @@
+//! JSON-RPC 2.0 daemon serving index and storage queries of ARK roots.
+//!
+//! Messages are newline-delimited JSON objects exchanged over a Unix
+//! socket, or a named pipe on Windows. Supported methods:
+//!
+//! - `index.query` `{root?, prefix?}`: indexed resources
+//! - `index.getByPath` `{root?, path}`: resource by its relative path
+//! - `tags.of` `{root?, id}`: tags of a resource
+//! - `tags.find` `{root?, tag}`: resources having a tag
+//! - `properties.get` `{root?, id}`: properties of a resource
+//! - `properties.patch` `{root?, id, properties}`: merge properties
+//! - `subscribe` `{root?}`: receive `index.update` notifications
+//!
+//! `root` may be omitted when a single root is served.
+
+mod daemon;
+mod methods;
+pub mod rpc;
+mod server;
+mod tags;
+
+pub use daemon::{Daemon, Root};
+pub use server::serve;
+
+/// Type of the resource ids, [the one of the apps](dev_hash::Crc32)
+pub type ResourceId = dev_hash::Crc32;
@@ -0,0 +1,55 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use anyhow::Result;
+use clap::Parser;
+use home::home_dir;
+
+use ark_daemon::{serve, Daemon};
+use fs_atomic_versions::app_id;
+
+#[derive(Parser, Debug)]
+#[clap(name = "ark-daemon")]
+#[clap(about = "Serve index and storage queries over JSON-RPC")]
+struct Args {
+    #[clap(
+        short,
+        long,
+        help = "Socket path, or pipe name on Windows [default: ~/.ark/daemon.sock]"
+    )]
+    socket: Option<PathBuf>,
+    #[clap(required = true, help = "Root directories to serve")]
+    roots: Vec<PathBuf>,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    env_logger::init_from_env(
+        env_logger::Env::default().default_filter_or("info"),
+    );
+    let args = Args::parse();
+
+    let ark_dir = home_dir()
+        .ok_or_else(|| anyhow::anyhow!("Couldn't retrieve home directory"))?
+        .join(".ark");
+    std::fs::create_dir_all(&ark_dir)?;
+    app_id::load(&ark_dir)?;
+
+    let socket = match args.socket {
+        Some(socket) => socket,
+        #[cfg(windows)]
+        None => PathBuf::from(r"\\.\pipe\ark-daemon"),
+        #[cfg(not(windows))]
+        None => ark_dir.join("daemon.sock"),
+    };
+
+    let daemon = Arc::new(Daemon::new(&args.roots)?);
+    tokio::select! {
+        result = serve(daemon, &socket) => result?,
+        _ = tokio::signal::ctrl_c() => log::info!("Shutting down"),
+    }
+
+    #[cfg(unix)]
+    let _ = std::fs::remove_file(&socket);
+    Ok(())
+}
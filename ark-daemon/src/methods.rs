@@ -0,0 +1,211 @@
+//! Implementation of the JSON-RPC methods
+
+use std::path::Path;
+use std::str::FromStr;
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use data_error::ArklibError;
+use fs_properties::{load_raw_properties, store_properties};
+
+use crate::daemon::{Daemon, Root};
+use crate::rpc::{RpcError, METHOD_NOT_FOUND};
+use crate::tags::load_tags;
+use crate::ResourceId;
+
+type MethodResult = Result<Value, RpcError>;
+
+#[derive(Deserialize)]
+struct QueryParams {
+    root: Option<String>,
+    /// Only return resources under this relative path
+    prefix: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct PathParams {
+    root: Option<String>,
+    path: String,
+}
+
+#[derive(Deserialize)]
+struct IdParams {
+    root: Option<String>,
+    id: String,
+}
+
+#[derive(Deserialize)]
+struct TagParams {
+    root: Option<String>,
+    tag: String,
+}
+
+#[derive(Deserialize)]
+struct PatchParams {
+    root: Option<String>,
+    id: String,
+    properties: Value,
+}
+
+/// Dispatch a request to the method implementation.
+///
+/// `subscribe` only acknowledges the subscription here,
+/// forwarding of updates is handled by the connection.
+pub fn handle(daemon: &Daemon, method: &str, params: Value) -> MethodResult {
+    match method {
+        "index.query" => index_query(daemon, parse(params)?),
+        "index.getByPath" => index_get_by_path(daemon, parse(params)?),
+        "tags.of" => tags_of(daemon, parse(params)?),
+        "tags.find" => tags_find(daemon, parse(params)?),
+        "properties.get" => properties_get(daemon, parse(params)?),
+        "properties.patch" => properties_patch(daemon, parse(params)?),
+        "subscribe" => {
+            let params: SubscribeParams = parse(params)?;
+            if let Some(root) = params.root.as_deref() {
+                daemon.root(Some(root))?;
+            }
+            Ok(json!({ "subscribed": true }))
+        }
+        _ => Err(RpcError::new(
+            METHOD_NOT_FOUND,
+            format!("Unknown method {}", method),
+        )),
+    }
+}
+
+#[derive(Deserialize, Default)]
+pub struct SubscribeParams {
+    /// Only forward updates of this root
+    pub root: Option<String>,
+}
+
+pub fn parse<T: for<'de> Deserialize<'de>>(
+    params: Value,
+) -> Result<T, RpcError> {
+    // Methods without required params may be called without any
+    let params = if params.is_null() {
+        json!({})
+    } else {
+        params
+    };
+    Ok(serde_json::from_value(params)?)
+}
+
+fn parse_id(id: &str) -> Result<ResourceId, RpcError> {
+    ResourceId::from_str(id)
+        .map_err(|_| RpcError::invalid_params(format!("Invalid id {}", id)))
+}
+
+fn read_index<T>(
+    root: &Root,
+    f: impl FnOnce(&fs_index::ResourceIndex<ResourceId>) -> T,
+) -> Result<T, RpcError> {
+    Ok(f(&root.index()))
+}
+
+fn index_query(daemon: &Daemon, params: QueryParams) -> MethodResult {
+    let root = daemon.root(params.root.as_deref())?;
+    let prefix = params.prefix.unwrap_or_default();
+
+    read_index(root, |index| {
+        let mut entries: Vec<(&Path, &ResourceId)> = index
+            .path2id
+            .iter()
+            .map(|(path, entry)| (AsRef::<Path>::as_ref(path), &entry.id))
+            .filter(|(path, _)| root.relative(path).starts_with(&prefix))
+            .collect();
+        entries.sort();
+
+        Value::Array(
+            entries
+                .into_iter()
+                .map(|(path, id)| root.entry(id, path))
+                .collect(),
+        )
+    })
+}
+
+fn index_get_by_path(daemon: &Daemon, params: PathParams) -> MethodResult {
+    let root = daemon.root(params.root.as_deref())?;
+    let path = root.path.join(&params.path);
+
+    let entry = read_index(root, |index| {
+        index
+            .path2id
+            .iter()
+            .find(|(indexed, _)| AsRef::<Path>::as_ref(indexed) == path)
+            .map(|(indexed, entry)| {
+                root.entry(&entry.id, AsRef::<Path>::as_ref(indexed))
+            })
+    })?;
+
+    entry.ok_or_else(|| {
+        RpcError::not_found(format!("{} is not indexed", params.path))
+    })
+}
+
+fn tags_of(daemon: &Daemon, params: IdParams) -> MethodResult {
+    let root = daemon.root(params.root.as_deref())?;
+    let id = parse_id(&params.id)?;
+
+    let tags = load_tags(&root.path)?;
+    let tags = tags
+        .get(&id.to_string())
+        .cloned()
+        .unwrap_or_default();
+    Ok(json!(tags))
+}
+
+fn tags_find(daemon: &Daemon, params: TagParams) -> MethodResult {
+    let root = daemon.root(params.root.as_deref())?;
+
+    let tags = load_tags(&root.path)?;
+    let ids: Vec<ResourceId> = tags
+        .iter()
+        .filter(|(_, tags)| tags.contains(&params.tag))
+        .filter_map(|(id, _)| ResourceId::from_str(id).ok())
+        .collect();
+
+    read_index(root, |index| {
+        Value::Array(
+            ids.iter()
+                .map(|id| match index.id2path.get(id) {
+                    Some(path) => root.entry(id, AsRef::<Path>::as_ref(path)),
+                    // Tagged resources may be missing from the folder
+                    None => json!({ "id": id.to_string(), "path": null }),
+                })
+                .collect(),
+        )
+    })
+}
+
+fn properties_get(daemon: &Daemon, params: IdParams) -> MethodResult {
+    let root = daemon.root(params.root.as_deref())?;
+    let id = parse_id(&params.id)?;
+
+    let properties_dir = root
+        .path
+        .join(fs_storage::ARK_FOLDER)
+        .join(fs_properties::PROPERTIES_STORAGE_FOLDER)
+        .join(id.to_string());
+    if !properties_dir.exists() {
+        return Ok(Value::Null);
+    }
+
+    let raw = load_raw_properties(&root.path, id)?;
+    Ok(serde_json::from_slice(&raw).map_err(ArklibError::from)?)
+}
+
+/// Merge the given properties into the stored ones
+fn properties_patch(daemon: &Daemon, params: PatchParams) -> MethodResult {
+    let root = daemon.root(params.root.as_deref())?;
+    let id = parse_id(&params.id)?;
+    if !params.properties.is_object() {
+        return Err(RpcError::invalid_params("`properties` must be an object"));
+    }
+
+    store_properties(&root.path, id.clone(), &params.properties)?;
+    let raw = load_raw_properties(&root.path, id)?;
+    Ok(serde_json::from_slice(&raw).map_err(ArklibError::from)?)
+}
@@ -0,0 +1,194 @@
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+use serde_json::Value;
+use tokio::io::{
+    AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader,
+};
+use tokio::sync::{broadcast, mpsc};
+
+use crate::daemon::Daemon;
+use crate::methods::{handle, parse, SubscribeParams};
+use crate::rpc::{Request, Response, RpcError, INVALID_REQUEST, PARSE_ERROR};
+
+/// Serve the daemon API until an IO error occurs on the listener.
+///
+/// Every connection exchanges newline-delimited JSON-RPC messages.
+/// `socket` is a Unix socket path, or a named pipe name like
+/// `\\.\pipe\ark` on Windows.
+pub async fn serve(daemon: Arc<Daemon>, socket: &Path) -> io::Result<()> {
+    listen(daemon, socket).await
+}
+
+#[cfg(unix)]
+async fn listen(daemon: Arc<Daemon>, socket: &Path) -> io::Result<()> {
+    // A socket left by a previous run would make binding fail
+    if socket.exists() {
+        std::fs::remove_file(socket)?;
+    }
+    let listener = tokio::net::UnixListener::bind(socket)?;
+    log::info!("Listening on {}", socket.display());
+
+    loop {
+        let (stream, _) = listener.accept().await?;
+        tokio::spawn(connection(daemon.clone(), stream));
+    }
+}
+
+#[cfg(windows)]
+async fn listen(daemon: Arc<Daemon>, socket: &Path) -> io::Result<()> {
+    use tokio::net::windows::named_pipe::ServerOptions;
+
+    let mut server = ServerOptions::new()
+        .first_pipe_instance(true)
+        .create(socket)?;
+    log::info!("Listening on {}", socket.display());
+
+    loop {
+        server.connect().await?;
+        // Create the next instance before handing the connected one over,
+        // so that clients never see the pipe missing
+        let connected = server;
+        server = ServerOptions::new().create(socket)?;
+        tokio::spawn(connection(daemon.clone(), connected));
+    }
+}
+
+/// Handle requests of a single client.
+///
+/// Requests are processed concurrently, so responses may come out of order
+/// and must be matched by their id.
+async fn connection<S>(daemon: Arc<Daemon>, stream: S)
+where
+    S: AsyncRead + AsyncWrite + Send + 'static,
+{
+    let (reader, mut writer) = tokio::io::split(stream);
+    let (sender, mut receiver) = mpsc::unbounded_channel::<String>();
+
+    let writer_task = tokio::spawn(async move {
+        while let Some(mut message) = receiver.recv().await {
+            message.push('\n');
+            if writer
+                .write_all(message.as_bytes())
+                .await
+                .is_err()
+            {
+                break;
+            }
+        }
+    });
+
+    let mut lines = BufReader::new(reader).lines();
+    let mut subscriptions = Vec::new();
+    while let Ok(Some(line)) = lines.next_line().await {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let request = match parse_request(&line) {
+            Ok(request) => request,
+            Err(err) => {
+                let response = Response::new(Value::Null, Err(err));
+                send(&sender, &response);
+                continue;
+            }
+        };
+
+        if request.method == "subscribe" {
+            let params: SubscribeParams =
+                parse(request.params.clone()).unwrap_or_default();
+            subscriptions.push(tokio::spawn(forward_updates(
+                daemon.clone(),
+                params.root,
+                sender.clone(),
+            )));
+        }
+
+        let daemon = daemon.clone();
+        let sender = sender.clone();
+        tokio::spawn(async move {
+            let Request {
+                method, params, id, ..
+            } = request;
+            let result = tokio::task::spawn_blocking(move || {
+                handle(&daemon, &method, params)
+            })
+            .await
+            .unwrap_or_else(|err| {
+                Err(RpcError::new(
+                    crate::rpc::INTERNAL_ERROR,
+                    format!("Request panicked: {}", err),
+                ))
+            });
+
+            // Notifications from the client don't get a response
+            if let Some(id) = id {
+                send(&sender, &Response::new(id, result));
+            }
+        });
+    }
+
+    for subscription in subscriptions {
+        subscription.abort();
+    }
+    drop(sender);
+    let _ = writer_task.await;
+}
+
+fn parse_request(line: &str) -> Result<Request, RpcError> {
+    let request: Request = serde_json::from_str(line)
+        .map_err(|err| RpcError::new(PARSE_ERROR, err.to_string()))?;
+    if request.jsonrpc != "2.0" {
+        return Err(RpcError::new(
+            INVALID_REQUEST,
+            "Only JSON-RPC 2.0 is supported",
+        ));
+    }
+    Ok(request)
+}
+
+async fn forward_updates(
+    daemon: Arc<Daemon>,
+    root: Option<String>,
+    sender: mpsc::UnboundedSender<String>,
+) {
+    let root = root.and_then(|root| {
+        daemon
+            .root(Some(&root))
+            .ok()
+            .map(|root| root.path.display().to_string())
+    });
+
+    let mut updates = daemon.subscribe();
+    loop {
+        match updates.recv().await {
+            Ok(notification) => {
+                let matches = root
+                    .as_ref()
+                    .map_or(true, |root| notification.params["root"] == *root);
+                if matches && !send(&sender, notification.as_ref()) {
+                    break;
+                }
+            }
+            Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                log::warn!("Subscriber lagged, {} updates skipped", skipped);
+            }
+            Err(broadcast::error::RecvError::Closed) => break,
+        }
+    }
+}
+
+/// Returns `false` once the connection is closed
+fn send<T: serde::Serialize>(
+    sender: &mpsc::UnboundedSender<String>,
+    message: &T,
+) -> bool {
+    match serde_json::to_string(message) {
+        Ok(message) => sender.send(message).is_ok(),
+        Err(err) => {
+            log::error!("Failed to serialize a message: {}", err);
+            true
+        }
+    }
+}
@@ -0,0 +1,50 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+
+use data_error::Result;
+use fs_atomic_versions::atomic::AtomicFile;
+use fs_storage::base_storage::BaseStorage;
+use fs_storage::file_storage::FileStorage;
+use fs_storage::{ARK_FOLDER, TAG_STORAGE_FILE};
+
+pub type Tags = BTreeMap<String, BTreeSet<String>>;
+
+/// Load tags of all resources under `root`.
+///
+/// Tags are stored as `id:tag1,tag2` entries, either in an atomic
+/// folder (as written by `ark-cli`) or in a `FileStorage` file.
+pub fn load_tags(root: &Path) -> Result<Tags> {
+    let path = root.join(ARK_FOLDER).join(TAG_STORAGE_FILE);
+    let mut tags = Tags::new();
+
+    if path.is_file() {
+        let mut storage: FileStorage<String, String> =
+            FileStorage::new("tags".to_owned(), &path)?;
+        for (id, value) in storage.read_fs()? {
+            insert(&mut tags, id, value);
+        }
+    } else if path.is_dir() {
+        let latest = AtomicFile::new(&path)?.load()?;
+        if latest.version == 0 {
+            return Ok(tags);
+        }
+        for line in latest.read_to_string()?.lines() {
+            if let Some((id, value)) = line.split_once(':') {
+                insert(&mut tags, id, value);
+            }
+        }
+    }
+
+    Ok(tags)
+}
+
+fn insert(tags: &mut Tags, id: &str, value: &str) {
+    let entry = tags.entry(id.trim().to_owned()).or_default();
+    for tag in value
+        .split(',')
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+    {
+        entry.insert(tag.to_owned());
+    }
+}
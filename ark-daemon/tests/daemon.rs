@@ -0,0 +1,141 @@
+#![cfg(unix)]
+
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::UnixStream;
+use std::path::Path;
+use std::process::{Child, Command};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use data_resource::ResourceId;
+use dev_hash::Crc32;
+use serde_json::{json, Value};
+use tempdir::TempDir;
+
+struct Daemon(Child);
+
+impl Drop for Daemon {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+struct Client {
+    reader: BufReader<UnixStream>,
+    writer: UnixStream,
+    next_id: u64,
+}
+
+impl Client {
+    fn connect(socket: &Path) -> Self {
+        let started = Instant::now();
+        let stream = loop {
+            match UnixStream::connect(socket) {
+                Ok(stream) => break stream,
+                Err(_) if started.elapsed() < Duration::from_secs(30) => {
+                    thread::sleep(Duration::from_millis(100))
+                }
+                Err(err) => panic!("Daemon didn't start: {}", err),
+            }
+        };
+        stream
+            .set_read_timeout(Some(Duration::from_secs(10)))
+            .unwrap();
+        Self {
+            reader: BufReader::new(stream.try_clone().unwrap()),
+            writer: stream,
+            next_id: 0,
+        }
+    }
+
+    fn read(&mut self) -> Value {
+        let mut line = String::new();
+        self.reader.read_line(&mut line).unwrap();
+        serde_json::from_str(&line).unwrap()
+    }
+
+    fn call(&mut self, method: &str, params: Value) -> Value {
+        self.next_id += 1;
+        let request = json!({
+            "jsonrpc": "2.0",
+            "id": self.next_id,
+            "method": method,
+            "params": params,
+        });
+        writeln!(self.writer, "{}", request).unwrap();
+
+        let response = self.read();
+        assert_eq!(response["id"], self.next_id, "{}", response);
+        response
+    }
+}
+
+#[test]
+fn daemon_serves_all_methods() {
+    let home = TempDir::new("ark-daemon-home").unwrap();
+    let root = TempDir::new("ark-daemon-root").unwrap();
+    let socket = home.path().join("daemon.sock");
+
+    fs::write(root.path().join("lena.txt"), "lena").unwrap();
+    let id = Crc32::from_bytes(b"lena").unwrap().to_string();
+    let tags = root.path().join(".ark/user/tags");
+    fs::create_dir_all(&tags).unwrap();
+    fs::write(tags.join("tags_test.1"), format!("{}:photo,old\n", id)).unwrap();
+
+    let _daemon = Daemon(
+        Command::new(env!("CARGO_BIN_EXE_ark-daemon"))
+            .env("HOME", home.path())
+            .arg("--socket")
+            .arg(&socket)
+            .arg(root.path())
+            .spawn()
+            .unwrap(),
+    );
+    let mut client = Client::connect(&socket);
+
+    let response = client.call("index.query", json!({}));
+    assert_eq!(
+        response["result"],
+        json!([{ "id": id, "path": "lena.txt" }])
+    );
+
+    let response =
+        client.call("index.getByPath", json!({ "path": "lena.txt" }));
+    assert_eq!(response["result"]["id"], id);
+    let response = client.call("index.getByPath", json!({ "path": "nope" }));
+    assert_eq!(response["error"]["code"], -32001);
+
+    let response = client.call("tags.of", json!({ "id": id }));
+    assert_eq!(response["result"], json!(["old", "photo"]));
+    let response = client.call("tags.find", json!({ "tag": "photo" }));
+    assert_eq!(response["result"][0]["path"], "lena.txt");
+
+    let response = client.call("properties.get", json!({ "id": id }));
+    assert_eq!(response["result"], Value::Null);
+    client.call(
+        "properties.patch",
+        json!({ "id": id, "properties": { "title": "Lena" } }),
+    );
+    let response = client.call(
+        "properties.patch",
+        json!({ "id": id, "properties": { "author": "unknown" } }),
+    );
+    assert_eq!(
+        response["result"],
+        json!({ "title": "Lena", "author": "unknown" })
+    );
+    let response = client.call("properties.get", json!({ "id": id }));
+    assert_eq!(response["result"]["title"], "Lena");
+
+    let response = client.call("unknown", json!({}));
+    assert_eq!(response["error"]["code"], -32601);
+
+    let response = client.call("subscribe", json!({}));
+    assert_eq!(response["result"]["subscribed"], true);
+    fs::write(root.path().join("new.txt"), "new").unwrap();
+    let notification = client.read();
+    assert_eq!(notification["method"], "index.update");
+    assert_eq!(notification["params"]["added"][0]["path"], "new.txt");
+}
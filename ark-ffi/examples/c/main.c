@@ -0,0 +1,69 @@
+// Happy path of the ark-ffi C API.
+//
+// Usage: ./example <root>
+
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+#include "ark.h"
+
+#define CHECK(call)                                                        \
+    do {                                                                   \
+        int32_t code = (call);                                             \
+        if (code != ARK_OK) {                                              \
+            fprintf(stderr, "%s failed with %d: %s\n", #call, code,        \
+                    ark_last_error_message());                             \
+            return 1;                                                      \
+        }                                                                  \
+    } while (0)
+
+int main(int argc, char **argv) {
+    if (argc < 2) {
+        fprintf(stderr, "Usage: %s <root>\n", argv[0]);
+        return 1;
+    }
+    const char *root = argv[1];
+
+    char ark_dir[4096];
+    snprintf(ark_dir, sizeof(ark_dir), "%s/.ark", root);
+    CHECK(ark_init(ark_dir));
+
+    ArkIndex *index = NULL;
+    CHECK(ark_index_provide(root, &index));
+    printf("Indexed %lld resources\n", (long long)ark_index_size(index));
+
+    char *update = NULL;
+    CHECK(ark_index_update_all(index, &update));
+    printf("Update: %s\n", update);
+    ark_string_free(update);
+    CHECK(ark_index_store(index));
+    ark_index_free(index);
+
+    const char *id = "42";
+
+    ArkTags *tags = NULL;
+    CHECK(ark_tags_open(root, &tags));
+    CHECK(ark_tags_add(tags, id, "example"));
+    char *tags_json = NULL;
+    CHECK(ark_tags_get(tags, id, &tags_json));
+    printf("Tags of %s: %s\n", id, tags_json);
+    ark_string_free(tags_json);
+    ark_tags_free(tags);
+
+    ArkScores *scores = NULL;
+    CHECK(ark_scores_open(root, &scores));
+    CHECK(ark_score_set(scores, id, 3));
+    int32_t score = 0;
+    CHECK(ark_score_get(scores, id, &score));
+    printf("Score of %s: %d\n", id, score);
+    ark_scores_free(scores);
+
+    CHECK(ark_properties_patch(root, id, "{\"title\":\"Example\"}"));
+    char *properties = NULL;
+    CHECK(ark_properties_get(root, id, &properties));
+    printf("Properties of %s: %s\n", id, properties);
+    ark_string_free(properties);
+
+    return 0;
+}
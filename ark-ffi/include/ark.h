@@ -0,0 +1,126 @@
+#ifndef ARK_H
+#define ARK_H
+
+/* Generated with cbindgen, do not edit manually. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// The call succeeded
+#define ARK_OK 0
+
+// A pointer was null or a string wasn't valid UTF-8 or JSON
+#define ARK_INVALID_ARGUMENT -1
+
+// The library panicked, the handles used in the call must not be reused
+#define ARK_PANIC -2
+
+// Opaque handle to a resource index
+typedef struct ArkIndex ArkIndex;
+
+// Opaque handle to the scores storage of a root
+typedef struct ArkScores ArkScores;
+
+// Opaque handle to the tags storage of a root
+typedef struct ArkTags ArkTags;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Message of the last error which happened on the calling thread,
+// or null if the last call succeeded.
+//
+// The string is owned by the library and stays valid until the next
+// call on the same thread. It must not be freed.
+const char *ark_last_error_message(void);
+
+// Load the index of `root`, or build it if there is none yet.
+//
+// On success, `*out` receives a handle to be freed with `ark_index_free`.
+int32_t ark_index_provide(const char *root, ArkIndex **out);
+
+// Rescan the root folder.
+//
+// On success, `*out_json` receives the changes as
+// `{"added": [{"id", "path"}], "deleted": [id]}`, with paths relative
+// to the root. The string must be freed with `ark_string_free`.
+int32_t ark_index_update_all(ArkIndex *index, char **out_json);
+
+// Persist the index into the `.ark` folder of its root
+int32_t ark_index_store(ArkIndex *index);
+
+// Number of indexed resources, or -1 if `index` is null
+int64_t ark_index_size(const ArkIndex *index);
+
+// Free an index handle. Passing null is allowed and does nothing.
+void ark_index_free(ArkIndex *index);
+
+// Properties of a resource.
+//
+// On success, `*out_json` receives the properties as a JSON object, or
+// `null` if the resource has none. It must be freed with `ark_string_free`.
+int32_t ark_properties_get(const char *root, const char *id, char **out_json);
+
+// Merge a JSON object into the properties of a resource
+int32_t ark_properties_patch(const char *root, const char *id, const char *json);
+
+// Open the scores storage of `root`.
+//
+// On success, `*out` receives a handle to be freed with `ark_scores_free`.
+int32_t ark_scores_open(const char *root, ArkScores **out);
+
+// Score of a resource, resources without score have score 0
+int32_t ark_score_get(ArkScores *scores, const char *id, int32_t *out);
+
+// Set the score of a resource, the change is written to disk immediately.
+//
+// Setting score 0 removes the resource from the storage.
+int32_t ark_score_set(ArkScores *scores, const char *id, int32_t score);
+
+// Free a scores handle. Passing null is allowed and does nothing.
+void ark_scores_free(ArkScores *scores);
+
+// Open the tags storage of `root`.
+//
+// On success, `*out` receives a handle to be freed with `ark_tags_free`.
+int32_t ark_tags_open(const char *root, ArkTags **out);
+
+// Tag a resource, the change is written to disk immediately
+int32_t ark_tags_add(ArkTags *tags, const char *id, const char *tag);
+
+// Remove a tag from a resource, the change is written to disk immediately
+int32_t ark_tags_remove(ArkTags *tags, const char *id, const char *tag);
+
+// Tags of a resource.
+//
+// On success, `*out_json` receives a JSON array of tags,
+// to be freed with `ark_string_free`.
+int32_t ark_tags_get(ArkTags *tags, const char *id, char **out_json);
+
+// Resources having a tag.
+//
+// On success, `*out_json` receives a JSON array of ids,
+// to be freed with `ark_string_free`.
+int32_t ark_tags_find(ArkTags *tags, const char *tag, char **out_json);
+
+// Free a tags handle. Passing null is allowed and does nothing.
+void ark_tags_free(ArkTags *tags);
+
+// Free a string returned by the library.
+//
+// Passing null is allowed and does nothing.
+void ark_string_free(char *s);
+
+// Initialize the library, must be called once before any other function.
+//
+// `ark_dir` is the folder where the app id is kept, usually `~/.ark`.
+int32_t ark_init(const char *ark_dir);
+
+#ifdef __cplusplus
+} // extern "C"
+#endif // __cplusplus
+
+#endif // ARK_H
@@ -0,0 +1,99 @@
+use std::cell::RefCell;
+use std::ffi::{c_char, CString};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+use data_error::ArklibError;
+
+/// The call succeeded
+pub const ARK_OK: i32 = 0;
+/// A pointer was null or a string wasn't valid UTF-8 or JSON
+pub const ARK_INVALID_ARGUMENT: i32 = -1;
+/// The library panicked, the handles used in the call must not be reused
+pub const ARK_PANIC: i32 = -2;
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = RefCell::new(None);
+}
+
+pub(crate) enum FfiError {
+    Arklib(ArklibError),
+    InvalidArgument(String),
+}
+
+impl FfiError {
+    fn code(&self) -> i32 {
+        match self {
+            FfiError::Arklib(err) => err.code(),
+            FfiError::InvalidArgument(_) => ARK_INVALID_ARGUMENT,
+        }
+    }
+
+    fn message(&self) -> String {
+        match self {
+            FfiError::Arklib(err) => err.to_string(),
+            FfiError::InvalidArgument(message) => message.clone(),
+        }
+    }
+}
+
+impl From<ArklibError> for FfiError {
+    fn from(err: ArklibError) -> Self {
+        FfiError::Arklib(err)
+    }
+}
+
+impl From<std::io::Error> for FfiError {
+    fn from(err: std::io::Error) -> Self {
+        FfiError::Arklib(err.into())
+    }
+}
+
+impl From<serde_json::Error> for FfiError {
+    fn from(err: serde_json::Error) -> Self {
+        FfiError::Arklib(err.into())
+    }
+}
+
+pub(crate) type FfiResult<T> = std::result::Result<T, FfiError>;
+
+fn set_last_error(message: Option<String>) {
+    let message = message.map(|message| {
+        // Interior NUL bytes can't be represented in a C string
+        CString::new(message.replace('\0', " "))
+            .expect("NUL bytes have been replaced")
+    });
+    LAST_ERROR.with(|last| *last.borrow_mut() = message);
+}
+
+/// Run the body of an exported function, turning errors and panics
+/// into status codes and recording the error message.
+pub(crate) fn ffi_call(body: impl FnOnce() -> FfiResult<()>) -> i32 {
+    match catch_unwind(AssertUnwindSafe(body)) {
+        Ok(Ok(())) => {
+            set_last_error(None);
+            ARK_OK
+        }
+        Ok(Err(err)) => {
+            set_last_error(Some(err.message()));
+            err.code()
+        }
+        Err(_) => {
+            set_last_error(Some("Unexpected panic".to_owned()));
+            ARK_PANIC
+        }
+    }
+}
+
+/// Message of the last error which happened on the calling thread,
+/// or null if the last call succeeded.
+///
+/// The string is owned by the library and stays valid until the next
+/// call on the same thread. It must not be freed.
+#[no_mangle]
+pub extern "C" fn ark_last_error_message() -> *const c_char {
+    LAST_ERROR.with(|last| {
+        last.borrow()
+            .as_ref()
+            .map_or(std::ptr::null(), |message| message.as_ptr())
+    })
+}
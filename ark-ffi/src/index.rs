@@ -0,0 +1,114 @@
+use std::ffi::c_char;
+use std::path::{Path, PathBuf};
+
+use serde_json::{json, Value};
+
+use fs_index::ResourceIndex;
+
+use crate::error::ffi_call;
+use crate::util::{
+    free_handle, handle_arg, out_arg, path_arg, write_handle, write_json,
+};
+use crate::ResourceId;
+
+/// Opaque handle to a resource index
+pub struct ArkIndex {
+    root: PathBuf,
+    index: ResourceIndex<ResourceId>,
+}
+
+/// Load the index of `root`, or build it if there is none yet.
+///
+/// On success, `*out` receives a handle to be freed with `ark_index_free`.
+///
+/// # Safety
+/// `root` must be a NUL-terminated string and `out` a valid pointer
+#[no_mangle]
+pub unsafe extern "C" fn ark_index_provide(
+    root: *const c_char,
+    out: *mut *mut ArkIndex,
+) -> i32 {
+    ffi_call(|| {
+        let root = path_arg(root, "root")?;
+        out_arg(out, "out")?;
+
+        let root = root.canonicalize()?;
+        let index = ResourceIndex::provide(&root)?;
+        write_handle(ArkIndex { root, index }, out);
+        Ok(())
+    })
+}
+
+/// Rescan the root folder.
+///
+/// On success, `*out_json` receives the changes as
+/// `{"added": [{"id", "path"}], "deleted": [id]}`, with paths relative
+/// to the root. The string must be freed with `ark_string_free`.
+///
+/// # Safety
+/// `index` must be a live handle and `out_json` a valid pointer
+#[no_mangle]
+pub unsafe extern "C" fn ark_index_update_all(
+    index: *mut ArkIndex,
+    out_json: *mut *mut c_char,
+) -> i32 {
+    ffi_call(|| {
+        let handle = handle_arg(index, "index")?;
+        out_arg(out_json, "out_json")?;
+
+        let update = handle.index.update_all()?;
+        let mut added: Vec<Value> = update
+            .added
+            .iter()
+            .map(|(path, id)| {
+                let path: &Path = path.as_ref();
+                json!({
+                    "id": id.to_string(),
+                    "path": path.strip_prefix(&handle.root).unwrap_or(path),
+                })
+            })
+            .collect();
+        added.sort_by(|a, b| a["path"].as_str().cmp(&b["path"].as_str()));
+        let mut deleted: Vec<String> = update
+            .deleted
+            .iter()
+            .map(|id| id.to_string())
+            .collect();
+        deleted.sort();
+
+        write_json(&json!({ "added": added, "deleted": deleted }), out_json)
+    })
+}
+
+/// Persist the index into the `.ark` folder of its root
+///
+/// # Safety
+/// `index` must be a live handle
+#[no_mangle]
+pub unsafe extern "C" fn ark_index_store(index: *mut ArkIndex) -> i32 {
+    ffi_call(|| {
+        let handle = handle_arg(index, "index")?;
+        handle.index.store()?;
+        Ok(())
+    })
+}
+
+/// Number of indexed resources, or -1 if `index` is null
+///
+/// # Safety
+/// `index` must be null or a live handle
+#[no_mangle]
+pub unsafe extern "C" fn ark_index_size(index: *const ArkIndex) -> i64 {
+    index
+        .as_ref()
+        .map_or(-1, |handle| handle.index.size() as i64)
+}
+
+/// Free an index handle. Passing null is allowed and does nothing.
+///
+/// # Safety
+/// `index` must be null or a handle which wasn't freed yet
+#[no_mangle]
+pub unsafe extern "C" fn ark_index_free(index: *mut ArkIndex) {
+    free_handle(index)
+}
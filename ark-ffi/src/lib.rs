@@ -0,0 +1,211 @@
+//! C ABI for the ARK index and storages.
+//!
+//! # Conventions
+//!
+//! - Every fallible function returns `ARK_OK` (0) on success, otherwise
+//!   a negative code from this crate or a positive code from
+//!   `ArklibError::code`. `ark_last_error_message` describes the failure.
+//! - Handles (`ArkIndex`, `ArkTags`, `ArkScores`) are created by the
+//!   library and owned by the caller, who must release them with the
+//!   matching `ark_*_free` function exactly once.
+//! - Strings passed to the library are borrowed for the duration of the
+//!   call only.
+//! - Strings returned through `char **` out-parameters are owned by the
+//!   caller and must be released with `ark_string_free`. Out-parameters
+//!   are only written on success.
+//! - Handles are not thread-safe: a handle must not be used from several
+//!   threads at the same time.
+//!
+//! The C header is generated with `cbindgen`:
+//!
+//! ```text
+//! cbindgen --config cbindgen.toml --crate ark-ffi --output include/ark.h
+//! ```
+
+use std::ffi::c_char;
+
+use fs_atomic_versions::app_id;
+
+mod error;
+mod index;
+mod properties;
+mod scores;
+mod tags;
+mod util;
+
+pub use error::{
+    ark_last_error_message, ARK_INVALID_ARGUMENT, ARK_OK, ARK_PANIC,
+};
+pub use index::{
+    ark_index_free, ark_index_provide, ark_index_size, ark_index_store,
+    ark_index_update_all, ArkIndex,
+};
+pub use properties::{ark_properties_get, ark_properties_patch};
+pub use scores::{
+    ark_score_get, ark_score_set, ark_scores_free, ark_scores_open, ArkScores,
+};
+pub use tags::{
+    ark_tags_add, ark_tags_find, ark_tags_free, ark_tags_get, ark_tags_open,
+    ark_tags_remove, ArkTags, TagSet,
+};
+pub use util::ark_string_free;
+
+use error::ffi_call;
+use util::path_arg;
+
+/// Type of the resource ids, [the one of the apps](dev_hash::Crc32)
+pub type ResourceId = dev_hash::Crc32;
+
+/// Initialize the library, must be called once before any other function.
+///
+/// `ark_dir` is the folder where the app id is kept, usually `~/.ark`.
+///
+/// # Safety
+/// `ark_dir` must be a NUL-terminated string
+#[no_mangle]
+pub unsafe extern "C" fn ark_init(ark_dir: *const c_char) -> i32 {
+    ffi_call(|| {
+        let ark_dir = path_arg(ark_dir, "ark_dir")?;
+        std::fs::create_dir_all(&ark_dir)?;
+        app_id::load(ark_dir)?;
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::{CStr, CString};
+    use std::ptr;
+
+    use data_resource::ResourceId as _;
+    use serde_json::{json, Value};
+    use tempdir::TempDir;
+
+    use super::*;
+
+    fn c(s: &str) -> CString {
+        CString::new(s).unwrap()
+    }
+
+    /// Take ownership of a returned string, as a C caller would
+    unsafe fn take_json(s: *mut c_char) -> Value {
+        assert!(!s.is_null());
+        let value =
+            serde_json::from_str(CStr::from_ptr(s).to_str().unwrap()).unwrap();
+        ark_string_free(s);
+        value
+    }
+
+    fn setup() -> (TempDir, CString) {
+        let dir = TempDir::new("ark-ffi").unwrap();
+        let ark_dir = c(dir.path().join(".ark").to_str().unwrap());
+        unsafe {
+            assert_eq!(ark_init(ark_dir.as_ptr()), ARK_OK);
+        }
+        let root = c(dir.path().to_str().unwrap());
+        (dir, root)
+    }
+
+    #[test]
+    fn index_update_all_reports_changes() {
+        let (dir, root) = setup();
+        std::fs::write(dir.path().join("a.txt"), "a").unwrap();
+        unsafe {
+            let mut index = ptr::null_mut();
+            assert_eq!(ark_index_provide(root.as_ptr(), &mut index), ARK_OK);
+            assert_eq!(ark_index_size(index), 1);
+
+            std::fs::write(dir.path().join("b.txt"), "b").unwrap();
+            let mut json = ptr::null_mut();
+            assert_eq!(ark_index_update_all(index, &mut json), ARK_OK);
+            let id = dev_hash::Crc32::from_bytes(b"b")
+                .unwrap()
+                .to_string();
+            assert_eq!(
+                take_json(json),
+                json!({ "added": [{ "id": id, "path": "b.txt" }], "deleted": [] })
+            );
+
+            assert_eq!(ark_index_store(index), ARK_OK);
+            ark_index_free(index);
+        }
+    }
+
+    #[test]
+    fn tags_scores_and_properties_roundtrip() {
+        let (_dir, root) = setup();
+        let id = c("42");
+        unsafe {
+            let mut tags = ptr::null_mut();
+            assert_eq!(ark_tags_open(root.as_ptr(), &mut tags), ARK_OK);
+            assert_eq!(
+                ark_tags_add(tags, id.as_ptr(), c("a").as_ptr()),
+                ARK_OK
+            );
+            assert_eq!(
+                ark_tags_add(tags, id.as_ptr(), c("b").as_ptr()),
+                ARK_OK
+            );
+            assert_eq!(
+                ark_tags_remove(tags, id.as_ptr(), c("a").as_ptr()),
+                ARK_OK
+            );
+            let mut json = ptr::null_mut();
+            assert_eq!(ark_tags_get(tags, id.as_ptr(), &mut json), ARK_OK);
+            assert_eq!(take_json(json), json!(["b"]));
+            assert_eq!(ark_tags_find(tags, c("b").as_ptr(), &mut json), ARK_OK);
+            assert_eq!(take_json(json), json!(["42"]));
+            ark_tags_free(tags);
+
+            let mut scores = ptr::null_mut();
+            assert_eq!(ark_scores_open(root.as_ptr(), &mut scores), ARK_OK);
+            assert_eq!(ark_score_set(scores, id.as_ptr(), 5), ARK_OK);
+            let mut score = 0;
+            assert_eq!(ark_score_get(scores, id.as_ptr(), &mut score), ARK_OK);
+            assert_eq!(score, 5);
+            ark_scores_free(scores);
+
+            let patch = c(r#"{"title":"Lena"}"#);
+            assert_eq!(
+                ark_properties_patch(
+                    root.as_ptr(),
+                    id.as_ptr(),
+                    patch.as_ptr()
+                ),
+                ARK_OK
+            );
+            assert_eq!(
+                ark_properties_get(root.as_ptr(), id.as_ptr(), &mut json),
+                ARK_OK
+            );
+            assert_eq!(take_json(json), json!({ "title": "Lena" }));
+        }
+    }
+
+    #[test]
+    fn errors_set_code_and_message() {
+        let (_dir, root) = setup();
+        unsafe {
+            let mut json = ptr::null_mut();
+            let code = ark_properties_get(
+                root.as_ptr(),
+                c("not an id").as_ptr(),
+                &mut json,
+            );
+            assert_eq!(code, ARK_INVALID_ARGUMENT);
+            assert!(json.is_null());
+            let message = CStr::from_ptr(ark_last_error_message());
+            assert!(message.to_str().unwrap().contains("id"));
+
+            assert_eq!(
+                ark_tags_open(root.as_ptr(), ptr::null_mut()),
+                ARK_INVALID_ARGUMENT
+            );
+            // A successful call clears the message
+            let mut scores = ptr::null_mut();
+            assert_eq!(ark_scores_open(root.as_ptr(), &mut scores), ARK_OK);
+            assert!(ark_last_error_message().is_null());
+            ark_scores_free(scores);
+        }
+    }
+}
@@ -0,0 +1,72 @@
+use std::ffi::c_char;
+
+use serde_json::Value;
+
+use fs_properties::{
+    load_raw_properties, store_properties, PROPERTIES_STORAGE_FOLDER,
+};
+use fs_storage::ARK_FOLDER;
+
+use crate::error::{ffi_call, FfiError};
+use crate::util::{id_arg, out_arg, path_arg, str_arg, write_json};
+
+/// Properties of a resource.
+///
+/// On success, `*out_json` receives the properties as a JSON object, or
+/// `null` if the resource has none. It must be freed with `ark_string_free`.
+///
+/// # Safety
+/// `root` and `id` must be NUL-terminated strings
+/// and `out_json` a valid pointer
+#[no_mangle]
+pub unsafe extern "C" fn ark_properties_get(
+    root: *const c_char,
+    id: *const c_char,
+    out_json: *mut *mut c_char,
+) -> i32 {
+    ffi_call(|| {
+        let root = path_arg(root, "root")?;
+        let id = id_arg(id, "id")?;
+        out_arg(out_json, "out_json")?;
+
+        let storage = root
+            .join(ARK_FOLDER)
+            .join(PROPERTIES_STORAGE_FOLDER)
+            .join(id.to_string());
+        if !storage.exists() {
+            return write_json(&Value::Null, out_json);
+        }
+
+        let raw = load_raw_properties(&root, id)?;
+        let properties: Value = serde_json::from_slice(&raw)?;
+        write_json(&properties, out_json)
+    })
+}
+
+/// Merge a JSON object into the properties of a resource
+///
+/// # Safety
+/// `root`, `id` and `json` must be NUL-terminated strings
+#[no_mangle]
+pub unsafe extern "C" fn ark_properties_patch(
+    root: *const c_char,
+    id: *const c_char,
+    json: *const c_char,
+) -> i32 {
+    ffi_call(|| {
+        let root = path_arg(root, "root")?;
+        let id = id_arg(id, "id")?;
+        let properties: Value = serde_json::from_str(str_arg(json, "json")?)
+            .map_err(|_| {
+                FfiError::InvalidArgument("`json` is not valid JSON".to_owned())
+            })?;
+        if !properties.is_object() {
+            return Err(FfiError::InvalidArgument(
+                "`json` must be an object".to_owned(),
+            ));
+        }
+
+        store_properties(&root, id, &properties)?;
+        Ok(())
+    })
+}
@@ -0,0 +1,88 @@
+use std::ffi::c_char;
+
+use fs_scores::ScoreStorage;
+
+use crate::error::ffi_call;
+use crate::util::{
+    free_handle, handle_arg, id_arg, out_arg, path_arg, write_handle,
+};
+use crate::ResourceId;
+
+/// Opaque handle to the scores storage of a root
+pub struct ArkScores {
+    storage: ScoreStorage<ResourceId>,
+}
+
+/// Open the scores storage of `root`.
+///
+/// On success, `*out` receives a handle to be freed with `ark_scores_free`.
+///
+/// # Safety
+/// `root` must be a NUL-terminated string and `out` a valid pointer
+#[no_mangle]
+pub unsafe extern "C" fn ark_scores_open(
+    root: *const c_char,
+    out: *mut *mut ArkScores,
+) -> i32 {
+    ffi_call(|| {
+        let root = path_arg(root, "root")?;
+        out_arg(out, "out")?;
+
+        let storage = ScoreStorage::new(root)?;
+        write_handle(ArkScores { storage }, out);
+        Ok(())
+    })
+}
+
+/// Score of a resource, resources without score have score 0.
+/// Scores beyond the range of `i32` are clamped.
+///
+/// # Safety
+/// `scores` must be a live handle, `id` a NUL-terminated string
+/// and `out` a valid pointer
+#[no_mangle]
+pub unsafe extern "C" fn ark_score_get(
+    scores: *mut ArkScores,
+    id: *const c_char,
+    out: *mut i32,
+) -> i32 {
+    ffi_call(|| {
+        let handle = handle_arg(scores, "scores")?;
+        let id = id_arg(id, "id")?;
+        out_arg(out, "out")?;
+
+        let score = handle.storage.score(&id);
+        *out = score.clamp(i32::MIN.into(), i32::MAX.into()) as i32;
+        Ok(())
+    })
+}
+
+/// Set the score of a resource, the change is written to disk immediately.
+///
+/// Setting score 0 removes the resource from the storage.
+///
+/// # Safety
+/// `scores` must be a live handle and `id` a NUL-terminated string
+#[no_mangle]
+pub unsafe extern "C" fn ark_score_set(
+    scores: *mut ArkScores,
+    id: *const c_char,
+    score: i32,
+) -> i32 {
+    ffi_call(|| {
+        let handle = handle_arg(scores, "scores")?;
+        let id = id_arg(id, "id")?;
+
+        handle.storage.set_score(id, score.into())?;
+        Ok(())
+    })
+}
+
+/// Free a scores handle. Passing null is allowed and does nothing.
+///
+/// # Safety
+/// `scores` must be null or a handle which wasn't freed yet
+#[no_mangle]
+pub unsafe extern "C" fn ark_scores_free(scores: *mut ArkScores) {
+    free_handle(scores)
+}
@@ -0,0 +1,138 @@
+use std::ffi::c_char;
+
+pub use fs_storage::tag_set::TagSet;
+use fs_tags::TagStorage;
+
+use crate::error::ffi_call;
+use crate::util::{
+    free_handle, handle_arg, id_arg, out_arg, path_arg, str_arg, write_handle,
+    write_json,
+};
+use crate::ResourceId;
+
+/// Opaque handle to the tags storage of a root
+pub struct ArkTags {
+    storage: TagStorage<ResourceId>,
+}
+
+/// Open the tags storage of `root`.
+///
+/// On success, `*out` receives a handle to be freed with `ark_tags_free`.
+///
+/// # Safety
+/// `root` must be a NUL-terminated string and `out` a valid pointer
+#[no_mangle]
+pub unsafe extern "C" fn ark_tags_open(
+    root: *const c_char,
+    out: *mut *mut ArkTags,
+) -> i32 {
+    ffi_call(|| {
+        let root = path_arg(root, "root")?;
+        out_arg(out, "out")?;
+
+        let storage = TagStorage::new(root)?;
+        write_handle(ArkTags { storage }, out);
+        Ok(())
+    })
+}
+
+/// Tag a resource, the change is written to disk immediately
+///
+/// # Safety
+/// `tags` must be a live handle, `id` and `tag` NUL-terminated strings
+#[no_mangle]
+pub unsafe extern "C" fn ark_tags_add(
+    tags: *mut ArkTags,
+    id: *const c_char,
+    tag: *const c_char,
+) -> i32 {
+    ffi_call(|| {
+        let handle = handle_arg(tags, "tags")?;
+        let id = id_arg(id, "id")?;
+        let tag = str_arg(tag, "tag")?;
+
+        handle.storage.add_tag(id, tag)?;
+        Ok(())
+    })
+}
+
+/// Remove a tag from a resource, the change is written to disk immediately
+///
+/// # Safety
+/// `tags` must be a live handle, `id` and `tag` NUL-terminated strings
+#[no_mangle]
+pub unsafe extern "C" fn ark_tags_remove(
+    tags: *mut ArkTags,
+    id: *const c_char,
+    tag: *const c_char,
+) -> i32 {
+    ffi_call(|| {
+        let handle = handle_arg(tags, "tags")?;
+        let id = id_arg(id, "id")?;
+        let tag = str_arg(tag, "tag")?;
+
+        handle.storage.remove_tag(&id, tag)?;
+        Ok(())
+    })
+}
+
+/// Tags of a resource.
+///
+/// On success, `*out_json` receives a JSON array of tags,
+/// to be freed with `ark_string_free`.
+///
+/// # Safety
+/// `tags` must be a live handle, `id` a NUL-terminated string
+/// and `out_json` a valid pointer
+#[no_mangle]
+pub unsafe extern "C" fn ark_tags_get(
+    tags: *mut ArkTags,
+    id: *const c_char,
+    out_json: *mut *mut c_char,
+) -> i32 {
+    ffi_call(|| {
+        let handle = handle_arg(tags, "tags")?;
+        let id = id_arg(id, "id")?;
+        out_arg(out_json, "out_json")?;
+
+        write_json(&handle.storage.tags_of(&id), out_json)
+    })
+}
+
+/// Resources having a tag.
+///
+/// On success, `*out_json` receives a JSON array of ids,
+/// to be freed with `ark_string_free`.
+///
+/// # Safety
+/// `tags` must be a live handle, `tag` a NUL-terminated string
+/// and `out_json` a valid pointer
+#[no_mangle]
+pub unsafe extern "C" fn ark_tags_find(
+    tags: *mut ArkTags,
+    tag: *const c_char,
+    out_json: *mut *mut c_char,
+) -> i32 {
+    ffi_call(|| {
+        let handle = handle_arg(tags, "tags")?;
+        let tag = str_arg(tag, "tag")?;
+        out_arg(out_json, "out_json")?;
+
+        let ids: Vec<String> = handle
+            .storage
+            .resources_with_tag(tag)
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        write_json(&ids, out_json)
+    })
+}
+
+/// Free a tags handle. Passing null is allowed and does nothing.
+///
+/// # Safety
+/// `tags` must be null or a handle which wasn't freed yet
+#[no_mangle]
+pub unsafe extern "C" fn ark_tags_free(tags: *mut ArkTags) {
+    free_handle(tags)
+}
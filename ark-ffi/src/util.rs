@@ -0,0 +1,114 @@
+use std::ffi::{c_char, CStr, CString};
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use serde::Serialize;
+
+use crate::error::{FfiError, FfiResult};
+use crate::ResourceId;
+
+/// Borrow a string argument
+///
+/// # Safety
+/// `ptr` must be null or point to a NUL-terminated string
+pub(crate) unsafe fn str_arg<'a>(
+    ptr: *const c_char,
+    name: &str,
+) -> FfiResult<&'a str> {
+    if ptr.is_null() {
+        return Err(FfiError::InvalidArgument(format!("`{}` is null", name)));
+    }
+    CStr::from_ptr(ptr).to_str().map_err(|_| {
+        FfiError::InvalidArgument(format!("`{}` is not valid UTF-8", name))
+    })
+}
+
+/// # Safety
+/// Same as [`str_arg`]
+pub(crate) unsafe fn path_arg(
+    ptr: *const c_char,
+    name: &str,
+) -> FfiResult<PathBuf> {
+    str_arg(ptr, name).map(PathBuf::from)
+}
+
+/// # Safety
+/// Same as [`str_arg`]
+pub(crate) unsafe fn id_arg(
+    ptr: *const c_char,
+    name: &str,
+) -> FfiResult<ResourceId> {
+    let id = str_arg(ptr, name)?;
+    ResourceId::from_str(id).map_err(|_| {
+        FfiError::InvalidArgument(format!("`{}` is not a valid id", name))
+    })
+}
+
+/// Borrow a handle created by the library
+///
+/// # Safety
+/// `ptr` must be null or a live handle of type `T`
+pub(crate) unsafe fn handle_arg<'a, T>(
+    ptr: *mut T,
+    name: &str,
+) -> FfiResult<&'a mut T> {
+    ptr.as_mut()
+        .ok_or_else(|| FfiError::InvalidArgument(format!("`{}` is null", name)))
+}
+
+/// Check an out-pointer before doing any work
+pub(crate) fn out_arg<T>(ptr: *mut T, name: &str) -> FfiResult<()> {
+    if ptr.is_null() {
+        return Err(FfiError::InvalidArgument(format!("`{}` is null", name)));
+    }
+    Ok(())
+}
+
+/// Hand a value over to the caller as a JSON string,
+/// which must be freed with `ark_string_free`
+///
+/// # Safety
+/// `out` must be a valid, non-null pointer
+pub(crate) unsafe fn write_json<T: Serialize>(
+    value: &T,
+    out: *mut *mut c_char,
+) -> FfiResult<()> {
+    let json = serde_json::to_string(value)?;
+    let json = CString::new(json).map_err(|_| {
+        FfiError::InvalidArgument("Result contains a NUL byte".to_owned())
+    })?;
+    *out = json.into_raw();
+    Ok(())
+}
+
+/// Hand a handle over to the caller
+///
+/// # Safety
+/// `out` must be a valid, non-null pointer
+pub(crate) unsafe fn write_handle<T>(value: T, out: *mut *mut T) {
+    *out = Box::into_raw(Box::new(value));
+}
+
+/// Free a handle created by the library
+///
+/// # Safety
+/// `ptr` must be null or a handle of type `T` which wasn't freed yet
+pub(crate) unsafe fn free_handle<T>(ptr: *mut T) {
+    if !ptr.is_null() {
+        drop(Box::from_raw(ptr));
+    }
+}
+
+/// Free a string returned by the library.
+///
+/// Passing null is allowed and does nothing.
+///
+/// # Safety
+/// `s` must be null or a string returned by the library
+/// which wasn't freed yet
+#[no_mangle]
+pub unsafe extern "C" fn ark_string_free(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
@@ -0,0 +1,57 @@
+use pyo3::create_exception;
+use pyo3::exceptions::{PyException, PyValueError};
+use pyo3::prelude::*;
+
+use data_error::ArklibError;
+
+create_exception!(ark, ArkError, PyException, "Base class of ARK errors");
+create_exception!(ark, IoError, ArkError, "Filesystem access failed");
+create_exception!(ark, PathError, ArkError, "A path is malformed");
+create_exception!(ark, CollisionError, ArkError, "Resource ids collide");
+create_exception!(ark, ParseError, ArkError, "Data can't be parsed");
+create_exception!(ark, NetworkError, ArkError, "Network access failed");
+create_exception!(ark, StorageError, ArkError, "A storage is broken");
+create_exception!(ark, BulkError, ArkError, "Some items of a batch failed");
+
+/// Raise the exception matching the kind of `err`.
+///
+/// The message includes the path the error happened at, if known.
+pub(crate) fn ark_err(err: ArklibError) -> PyErr {
+    let message = err.to_string();
+
+    let mut kind = &err;
+    while let ArklibError::WithPath(_, inner) = kind {
+        kind = inner;
+    }
+    match kind {
+        ArklibError::Io(_) => IoError::new_err(message),
+        ArklibError::Path(_) => PathError::new_err(message),
+        ArklibError::Collision(_) => CollisionError::new_err(message),
+        ArklibError::Parse => ParseError::new_err(message),
+        ArklibError::Network => NetworkError::new_err(message),
+        ArklibError::Storage(_, _)
+        | ArklibError::UnsupportedVersion(_, _, _)
+        | ArklibError::Corrupted(_, _) => StorageError::new_err(message),
+        ArklibError::Bulk(_, _, _) => BulkError::new_err(message),
+        ArklibError::Other(_) | ArklibError::WithPath(_, _) => {
+            ArkError::new_err(message)
+        }
+    }
+}
+
+pub(crate) fn invalid_id(id: &str) -> PyErr {
+    PyValueError::new_err(format!("`{}` is not a valid id", id))
+}
+
+pub(crate) fn register(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    let py = m.py();
+    m.add("ArkError", py.get_type_bound::<ArkError>())?;
+    m.add("IoError", py.get_type_bound::<IoError>())?;
+    m.add("PathError", py.get_type_bound::<PathError>())?;
+    m.add("CollisionError", py.get_type_bound::<CollisionError>())?;
+    m.add("ParseError", py.get_type_bound::<ParseError>())?;
+    m.add("NetworkError", py.get_type_bound::<NetworkError>())?;
+    m.add("StorageError", py.get_type_bound::<StorageError>())?;
+    m.add("BulkError", py.get_type_bound::<BulkError>())?;
+    Ok(())
+}
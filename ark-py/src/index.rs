@@ -0,0 +1,153 @@
+use std::path::{Path, PathBuf};
+
+use canonical_path::CanonicalPathBuf;
+use pyo3::prelude::*;
+use serde_json::{json, Value};
+
+use crate::error::ark_err;
+use crate::{parse_id, to_py, ResourceId};
+
+type IndexResult = data_error::Result<fs_index::ResourceIndex<ResourceId>>;
+
+/// Index of the resources under a root folder.
+///
+/// Paths are reported relative to the root.
+#[pyclass(module = "ark")]
+pub struct ResourceIndex {
+    root: PathBuf,
+    index: fs_index::ResourceIndex<ResourceId>,
+}
+
+impl ResourceIndex {
+    fn new(
+        py: Python<'_>,
+        root: PathBuf,
+        open: impl FnOnce(PathBuf) -> IndexResult + Send,
+    ) -> PyResult<Self> {
+        let root = root
+            .canonicalize()
+            .map_err(|err| ark_err(err.into()))?;
+        // Hashing the files may take long, let other Python threads run
+        let index = py
+            .allow_threads(|| open(root.clone()))
+            .map_err(ark_err)?;
+        Ok(ResourceIndex { root, index })
+    }
+
+    fn relative(&self, path: &Path) -> String {
+        path.strip_prefix(&self.root)
+            .unwrap_or(path)
+            .to_string_lossy()
+            .into_owned()
+    }
+}
+
+#[pymethods]
+impl ResourceIndex {
+    /// Build the index of `root` from scratch
+    #[staticmethod]
+    fn build(py: Python<'_>, root: PathBuf) -> PyResult<Self> {
+        Self::new(py, root, |root| Ok(fs_index::ResourceIndex::build(root)))
+    }
+
+    /// Load the index stored in the `.ark` folder of `root`
+    #[staticmethod]
+    fn load(py: Python<'_>, root: PathBuf) -> PyResult<Self> {
+        Self::new(py, root, fs_index::ResourceIndex::load)
+    }
+
+    /// Load the index of `root` and update it,
+    /// or build it if there is none yet
+    #[staticmethod]
+    fn provide(py: Python<'_>, root: PathBuf) -> PyResult<Self> {
+        Self::new(py, root, fs_index::ResourceIndex::provide)
+    }
+
+    #[getter]
+    fn root(&self) -> String {
+        self.root.to_string_lossy().into_owned()
+    }
+
+    fn __len__(&self) -> usize {
+        self.index.size()
+    }
+
+    /// All resources as `{"id", "path"}` dicts, sorted by path
+    fn entries(&self, py: Python<'_>) -> PyResult<PyObject> {
+        let mut entries: Vec<(String, String)> = self
+            .index
+            .path2id
+            .iter()
+            .map(|(path, entry)| {
+                (self.relative(path.as_ref()), entry.id.to_string())
+            })
+            .collect();
+        entries.sort();
+        let entries: Vec<Value> = entries
+            .into_iter()
+            .map(|(path, id)| json!({ "id": id, "path": path }))
+            .collect();
+        to_py(py, &entries)
+    }
+
+    /// Path of a resource, or `None` if it isn't indexed
+    fn get_path(&self, id: &str) -> PyResult<Option<String>> {
+        let id = parse_id(id)?;
+        Ok(self
+            .index
+            .id2path
+            .get(&id)
+            .map(|path| self.relative(path.as_ref())))
+    }
+
+    /// Id of the resource at `path`, relative to the root or absolute
+    fn get_id(&self, path: PathBuf) -> Option<String> {
+        let path = CanonicalPathBuf::canonicalize(self.root.join(path)).ok()?;
+        self.index
+            .path2id
+            .get(&path)
+            .map(|entry| entry.id.to_string())
+    }
+
+    /// Rescan the root.
+    ///
+    /// Returns the changes as `{"added": [{"id", "path"}], "deleted": [id]}`.
+    fn update_all(&mut self, py: Python<'_>) -> PyResult<PyObject> {
+        let index = &mut self.index;
+        let update = py
+            .allow_threads(|| index.update_all())
+            .map_err(ark_err)?;
+
+        let mut added: Vec<(String, String)> = update
+            .added
+            .iter()
+            .map(|(path, id)| (self.relative(path.as_ref()), id.to_string()))
+            .collect();
+        added.sort();
+        let added: Vec<Value> = added
+            .into_iter()
+            .map(|(path, id)| json!({ "id": id, "path": path }))
+            .collect();
+        let mut deleted: Vec<String> = update
+            .deleted
+            .iter()
+            .map(|id| id.to_string())
+            .collect();
+        deleted.sort();
+
+        to_py(py, &json!({ "added": added, "deleted": deleted }))
+    }
+
+    /// Persist the index into the `.ark` folder of the root
+    fn store(&self) -> PyResult<()> {
+        self.index.store().map_err(ark_err)
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "ResourceIndex(root={:?}, size={})",
+            self.root(),
+            self.index.size()
+        )
+    }
+}
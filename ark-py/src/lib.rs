@@ -0,0 +1,109 @@
+//! Python bindings for the ARK index and storages, built with
+//! [maturin](https://www.maturin.rs):
+//!
+//! ```text
+//! maturin develop -m ark-py/Cargo.toml
+//! ```
+//!
+//! ```python
+//! import ark
+//!
+//! ark.initialize("/home/user/.ark")
+//! index = ark.ResourceIndex.provide("/home/user/Pictures")
+//! tags = ark.TagStorage("/home/user/Pictures")
+//! for entry in index.entries():
+//!     if entry["path"].endswith(".png"):
+//!         tags.add(entry["id"], "image")
+//! ```
+
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use pyo3::exceptions::{PyTypeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+use serde::Serialize;
+
+use data_resource::ResourceId as _;
+use dev_hash::{Blake3, Crc32};
+use fs_atomic_versions::app_id;
+
+mod error;
+mod index;
+mod properties;
+mod tags;
+
+use error::{ark_err, invalid_id};
+
+/// Type of the resource ids, [the one of the apps](dev_hash::Crc32)
+pub type ResourceId = dev_hash::Crc32;
+
+pub(crate) fn parse_id(id: &str) -> PyResult<ResourceId> {
+    ResourceId::from_str(id).map_err(|_| invalid_id(id))
+}
+
+/// Convert through JSON into dicts, lists and scalars
+pub(crate) fn to_py<T: Serialize>(
+    py: Python<'_>,
+    value: &T,
+) -> PyResult<PyObject> {
+    Ok(pythonize::pythonize(py, value)?)
+}
+
+/// Initialize the library, must be called once before anything else.
+///
+/// `ark_dir` is the folder where the app id is kept, usually `~/.ark`.
+#[pyfunction]
+fn initialize(ark_dir: PathBuf) -> PyResult<()> {
+    std::fs::create_dir_all(&ark_dir).map_err(|err| ark_err(err.into()))?;
+    app_id::load(ark_dir).map_err(ark_err)?;
+    Ok(())
+}
+
+/// Compute the id of a file, given its path, or of `bytes` content.
+///
+/// `kind` is either `"crc32"`, the kind used by the index, or `"blake3"`.
+#[pyfunction]
+#[pyo3(signature = (source, kind = "crc32"))]
+fn compute_id(
+    py: Python<'_>,
+    source: &Bound<'_, PyAny>,
+    kind: &str,
+) -> PyResult<String> {
+    fn id_of<Id: data_resource::ResourceId + Send>(
+        py: Python<'_>,
+        source: &Bound<'_, PyAny>,
+    ) -> PyResult<String> {
+        let id = if let Ok(bytes) = source.downcast::<PyBytes>() {
+            let bytes = bytes.as_bytes();
+            py.allow_threads(|| Id::from_bytes(bytes))
+        } else {
+            let path: PathBuf = source.extract().map_err(|_| {
+                PyTypeError::new_err("`source` must be a path or bytes")
+            })?;
+            py.allow_threads(|| Id::from_path(path))
+        };
+        Ok(id.map_err(ark_err)?.to_string())
+    }
+
+    match kind {
+        "crc32" => id_of::<Crc32>(py, source),
+        "blake3" => id_of::<Blake3>(py, source),
+        _ => Err(PyValueError::new_err(format!(
+            "Unknown id kind `{}`, expected \"crc32\" or \"blake3\"",
+            kind
+        ))),
+    }
+}
+
+#[pymodule]
+fn ark(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(initialize, m)?)?;
+    m.add_function(wrap_pyfunction!(compute_id, m)?)?;
+    m.add_function(wrap_pyfunction!(properties::get_properties, m)?)?;
+    m.add_function(wrap_pyfunction!(properties::patch_properties, m)?)?;
+    m.add_class::<index::ResourceIndex>()?;
+    m.add_class::<tags::TagStorage>()?;
+    error::register(m)?;
+    Ok(())
+}
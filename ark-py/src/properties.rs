@@ -0,0 +1,52 @@
+use std::path::PathBuf;
+
+use pyo3::exceptions::PyTypeError;
+use pyo3::prelude::*;
+use serde_json::Value;
+
+use fs_properties::{
+    load_raw_properties, store_properties, PROPERTIES_STORAGE_FOLDER,
+};
+use fs_storage::ARK_FOLDER;
+
+use crate::error::ark_err;
+use crate::{parse_id, to_py};
+
+/// Properties of a resource as a dict, or `None` if it has none
+#[pyfunction]
+pub fn get_properties(
+    py: Python<'_>,
+    root: PathBuf,
+    id: &str,
+) -> PyResult<PyObject> {
+    let id = parse_id(id)?;
+
+    let storage = root
+        .join(ARK_FOLDER)
+        .join(PROPERTIES_STORAGE_FOLDER)
+        .join(id.to_string());
+    if !storage.exists() {
+        return Ok(py.None());
+    }
+
+    let raw = load_raw_properties(&root, id).map_err(ark_err)?;
+    let properties: Value =
+        serde_json::from_slice(&raw).map_err(|err| ark_err(err.into()))?;
+    to_py(py, &properties)
+}
+
+/// Merge a dict into the properties of a resource
+#[pyfunction]
+pub fn patch_properties(
+    root: PathBuf,
+    id: &str,
+    properties: &Bound<'_, PyAny>,
+) -> PyResult<()> {
+    let id = parse_id(id)?;
+    let properties: Value = pythonize::depythonize_bound(properties.clone())?;
+    if !properties.is_object() {
+        return Err(PyTypeError::new_err("`properties` must be a dict"));
+    }
+
+    store_properties(root, id, &properties).map_err(ark_err)
+}
@@ -0,0 +1,79 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use pyo3::prelude::*;
+
+use fs_tags::TagStorage as Storage;
+
+use crate::error::ark_err;
+use crate::{parse_id, ResourceId};
+
+/// Tags storage of a root folder.
+///
+/// Changes are written to disk immediately.
+#[pyclass(module = "ark")]
+pub struct TagStorage {
+    storage: Storage<ResourceId>,
+}
+
+#[pymethods]
+impl TagStorage {
+    #[new]
+    fn new(root: PathBuf) -> PyResult<Self> {
+        let storage = Storage::new(root).map_err(ark_err)?;
+        Ok(TagStorage { storage })
+    }
+
+    /// Tags of a resource, sorted
+    fn get(&self, id: &str) -> PyResult<Vec<String>> {
+        let id = parse_id(id)?;
+        Ok(self.storage.tags_of(&id).into_iter().collect())
+    }
+
+    /// Add one or several tags to a resource
+    #[pyo3(signature = (id, *tags))]
+    fn add(&mut self, id: &str, tags: Vec<String>) -> PyResult<()> {
+        let id = parse_id(id)?;
+        for tag in &tags {
+            self.storage
+                .add_tag(id.clone(), tag)
+                .map_err(ark_err)?;
+        }
+        Ok(())
+    }
+
+    /// Remove one or several tags from a resource
+    #[pyo3(signature = (id, *tags))]
+    fn remove(&mut self, id: &str, tags: Vec<String>) -> PyResult<()> {
+        let id = parse_id(id)?;
+        for tag in &tags {
+            self.storage
+                .remove_tag(&id, tag)
+                .map_err(ark_err)?;
+        }
+        Ok(())
+    }
+
+    /// Ids of the resources having all of `tags`
+    #[pyo3(signature = (*tags))]
+    fn find(&self, tags: Vec<String>) -> Vec<String> {
+        self.storage
+            .entries()
+            .filter(|(_, set)| tags.iter().all(|tag| set.contains(tag)))
+            .map(|(id, _)| id.to_string())
+            .collect()
+    }
+
+    /// All tagged resources as a dict of id to sorted tags
+    fn to_dict(&self) -> BTreeMap<String, Vec<String>> {
+        self.storage
+            .entries()
+            .map(|(id, set)| (id.to_string(), set.iter().cloned().collect()))
+            .collect()
+    }
+
+    /// See [`fs_tags::TagStorage::sync`]
+    fn sync(&mut self) -> PyResult<()> {
+        self.storage.sync().map_err(ark_err)
+    }
+}
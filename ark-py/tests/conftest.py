@@ -0,0 +1,20 @@
+import pytest
+
+import ark
+
+
+@pytest.fixture(scope="session", autouse=True)
+def ark_home(tmp_path_factory):
+    home = tmp_path_factory.mktemp("ark-home")
+    ark.initialize(home)
+    return home
+
+
+@pytest.fixture
+def library(tmp_path):
+    """A small library: two distinct files and a duplicate in a subfolder"""
+    (tmp_path / "a.txt").write_text("a")
+    (tmp_path / "b.txt").write_text("b")
+    (tmp_path / "sub").mkdir()
+    (tmp_path / "sub" / "c.txt").write_text("a")
+    return tmp_path
@@ -0,0 +1,23 @@
+import pytest
+
+import ark
+
+
+def test_path_and_bytes_give_the_same_id(library):
+    assert ark.compute_id(library / "a.txt") == ark.compute_id(b"a")
+    assert ark.compute_id(str(library / "a.txt")) == ark.compute_id(b"a")
+
+
+def test_kinds():
+    assert ark.compute_id(b"abc") == "891568578"
+    assert (
+        ark.compute_id(b"abc", kind="blake3")
+        == "6437b3ac38465133ffb63b75273a8db548c558465d79db03fd359c6cd5bd9d85"
+    )
+    with pytest.raises(ValueError):
+        ark.compute_id(b"abc", kind="md5")
+
+
+def test_missing_file_raises_io_error(tmp_path):
+    with pytest.raises(ark.IoError):
+        ark.compute_id(tmp_path / "missing")
@@ -0,0 +1,53 @@
+import pytest
+
+import ark
+
+
+def test_build_lists_entries(library):
+    index = ark.ResourceIndex.build(library)
+
+    assert len(index) == 3
+    assert index.root == str(library.resolve())
+    entries = index.entries()
+    assert [entry["path"] for entry in entries] == ["a.txt", "b.txt", "sub/c.txt"]
+    assert entries[0]["id"] == ark.compute_id(library / "a.txt")
+
+
+def test_lookups(library):
+    index = ark.ResourceIndex.build(library)
+    id = index.get_id("b.txt")
+
+    assert id == ark.compute_id(b"b")
+    assert index.get_id(library / "b.txt") == id
+    assert index.get_path(id) == "b.txt"
+    assert index.get_id("missing.txt") is None
+    with pytest.raises(ValueError):
+        index.get_path("not an id")
+
+
+def test_update_all_reports_changes(library):
+    index = ark.ResourceIndex.build(library)
+    (library / "b.txt").unlink()
+    (library / "d.txt").write_text("d")
+
+    update = index.update_all()
+
+    assert update == {
+        "added": [{"id": ark.compute_id(b"d"), "path": "d.txt"}],
+        "deleted": [ark.compute_id(b"b")],
+    }
+
+
+def test_store_and_load(library):
+    index = ark.ResourceIndex.build(library)
+    index.store()
+
+    loaded = ark.ResourceIndex.load(library)
+    assert loaded.entries() == index.entries()
+    assert ark.ResourceIndex.provide(library).entries() == index.entries()
+
+
+def test_load_without_index_raises_io_error(library):
+    with pytest.raises(ark.IoError) as error:
+        ark.ResourceIndex.load(library)
+    assert isinstance(error.value, ark.ArkError)
@@ -0,0 +1,21 @@
+import pytest
+
+import ark
+
+
+def test_patch_merges_properties(library):
+    assert ark.get_properties(library, "1") is None
+
+    ark.patch_properties(library, "1", {"title": "Lena", "year": 1972})
+    ark.patch_properties(library, "1", {"author": "unknown"})
+
+    assert ark.get_properties(library, "1") == {
+        "title": "Lena",
+        "year": 1972,
+        "author": "unknown",
+    }
+
+
+def test_patch_requires_a_dict(library):
+    with pytest.raises(TypeError):
+        ark.patch_properties(library, "1", ["not", "a", "dict"])
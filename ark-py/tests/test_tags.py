@@ -0,0 +1,21 @@
+import ark
+
+
+def test_add_remove_find(library):
+    tags = ark.TagStorage(library)
+    tags.add("1", "red", "blue")
+    tags.add("2", "red")
+
+    assert tags.get("1") == ["blue", "red"]
+    assert sorted(tags.find("red")) == ["1", "2"]
+    assert tags.find("red", "blue") == ["1"]
+
+    tags.remove("1", "red", "blue")
+    assert tags.get("1") == []
+    assert tags.to_dict() == {"2": ["red"]}
+
+
+def test_changes_are_persisted(library):
+    ark.TagStorage(library).add("1", "red")
+
+    assert ark.TagStorage(library).get("1") == ["red"]
@@ -0,0 +1,104 @@
+use data_error::ArklibError;
+
+/// Errors surfaced to Kotlin and Swift.
+///
+/// Mirrors `ArklibError`, every variant carries the full message,
+/// including the path the error happened at, if known.
+#[derive(Debug, thiserror::Error, uniffi::Error)]
+pub enum ArkError {
+    #[error("{message}")]
+    Io { message: String },
+    #[error("{message}")]
+    Path { message: String },
+    #[error("{message}")]
+    Collision { message: String },
+    #[error("{message}")]
+    Parse { message: String },
+    #[error("{message}")]
+    Network { message: String },
+    #[error("{message}")]
+    Storage { label: String, message: String },
+    #[error("{message}")]
+    Bulk {
+        failed: u64,
+        total: u64,
+        message: String,
+    },
+    #[error("{message}")]
+    Other { message: String },
+    /// An argument passed by the caller is malformed, e.g. an invalid id
+    #[error("{message}")]
+    InvalidArgument { message: String },
+    /// The operation was cancelled through its `CancellationToken`
+    #[error("Operation cancelled")]
+    Cancelled,
+}
+
+impl From<ArklibError> for ArkError {
+    fn from(err: ArklibError) -> Self {
+        let message = err.to_string();
+
+        let mut kind = &err;
+        while let ArklibError::WithPath(_, inner) = kind {
+            kind = inner;
+        }
+        match kind {
+            ArklibError::Io(_) => ArkError::Io { message },
+            ArklibError::Path(_) => ArkError::Path { message },
+            ArklibError::Collision(_) => ArkError::Collision { message },
+            ArklibError::Parse => ArkError::Parse { message },
+            ArklibError::Network => ArkError::Network { message },
+            ArklibError::Storage(label, _)
+            | ArklibError::UnsupportedVersion(label, _, _)
+            | ArklibError::Corrupted(label, _) => ArkError::Storage {
+                label: label.clone(),
+                message,
+            },
+            ArklibError::Bulk(failed, total, _) => ArkError::Bulk {
+                failed: *failed as u64,
+                total: *total as u64,
+                message,
+            },
+            ArklibError::Other(_) | ArklibError::WithPath(_, _) => {
+                ArkError::Other { message }
+            }
+        }
+    }
+}
+
+impl From<std::io::Error> for ArkError {
+    fn from(err: std::io::Error) -> Self {
+        ArklibError::from(err).into()
+    }
+}
+
+impl From<serde_json::Error> for ArkError {
+    fn from(err: serde_json::Error) -> Self {
+        ArklibError::from(err).into()
+    }
+}
+
+pub type Result<T> = std::result::Result<T, ArkError>;
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    #[test]
+    fn annotated_error_keeps_kind_and_path() {
+        let err = ArklibError::WithPath(
+            PathBuf::from("/tmp/a"),
+            Box::new(ArklibError::Storage("tags".into(), "broken".into())),
+        );
+        match ArkError::from(err) {
+            ArkError::Storage { label, message } => {
+                assert_eq!(label, "tags");
+                assert!(message.contains("broken"));
+                assert!(message.contains("/tmp/a"));
+            }
+            other => panic!("Unexpected error: {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,198 @@
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use canonical_path::CanonicalPathBuf;
+
+use crate::error::{ArkError, Result};
+use crate::task::{background, CancellationToken};
+use crate::watch::{IndexListener, WatchHandle};
+use crate::{parse_id, ResourceId};
+
+/// Resource of the index, `path` is relative to the root
+#[derive(Debug, Clone, PartialEq, uniffi::Record)]
+pub struct IndexEntry {
+    pub id: String,
+    pub path: String,
+}
+
+/// Changes found by rescanning the root
+#[derive(Debug, Clone, PartialEq, uniffi::Record)]
+pub struct IndexUpdate {
+    pub added: Vec<IndexEntry>,
+    pub deleted: Vec<String>,
+}
+
+impl IndexUpdate {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.deleted.is_empty()
+    }
+}
+
+/// Index of the resources under a root folder
+#[derive(uniffi::Object)]
+pub struct ResourceIndex {
+    root: PathBuf,
+    // shared with the watcher of the root, if any
+    index: Arc<Mutex<fs_index::ResourceIndex<ResourceId>>>,
+}
+
+/// Load the index of `root`, or build it if there is none yet
+#[uniffi::export]
+pub async fn provide_index(root: String) -> Result<Arc<ResourceIndex>> {
+    background(move || {
+        let root = PathBuf::from(root).canonicalize()?;
+        let index = fs_index::ResourceIndex::provide(&root)?;
+        Ok(ResourceIndex::new(root, index))
+    })
+    .await
+}
+
+/// Build the index of `root` from scratch, ignoring the stored one.
+///
+/// Fails with `ArkError::Cancelled` if `token` is cancelled meanwhile.
+#[uniffi::export]
+pub async fn build_index(
+    root: String,
+    token: Arc<CancellationToken>,
+) -> Result<Arc<ResourceIndex>> {
+    background(move || {
+        let root = PathBuf::from(root).canonicalize()?;
+        let build =
+            fs_index::ResourceIndex::build_cancellable(&root, token.flag());
+        if !build.complete {
+            return Err(ArkError::Cancelled);
+        }
+        Ok(ResourceIndex::new(root, build.value))
+    })
+    .await
+}
+
+impl ResourceIndex {
+    fn new(
+        root: PathBuf,
+        index: fs_index::ResourceIndex<ResourceId>,
+    ) -> Arc<Self> {
+        Arc::new(ResourceIndex {
+            root,
+            index: Arc::new(Mutex::new(index)),
+        })
+    }
+
+    fn lock(&self) -> MutexGuard<'_, fs_index::ResourceIndex<ResourceId>> {
+        // The index stays consistent even if a thread panicked holding it
+        self.index
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn relative(&self, path: &Path) -> String {
+        path.strip_prefix(&self.root)
+            .unwrap_or(path)
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    pub(crate) fn shared(
+        &self,
+    ) -> Arc<Mutex<fs_index::ResourceIndex<ResourceId>>> {
+        self.index.clone()
+    }
+
+    /// Rescan the root, blocking the calling thread
+    pub(crate) fn update_blocking(&self) -> Result<IndexUpdate> {
+        let update = self.lock().update_all()?;
+        Ok(self.report(update))
+    }
+
+    /// Update of the index as reported to the apps
+    pub(crate) fn report(
+        &self,
+        update: fs_index::index::IndexUpdate<ResourceId>,
+    ) -> IndexUpdate {
+        let mut added: Vec<IndexEntry> = update
+            .added
+            .iter()
+            .map(|(path, id)| IndexEntry {
+                id: id.to_string(),
+                path: self.relative(path.as_ref()),
+            })
+            .collect();
+        added.sort_by(|a, b| a.path.cmp(&b.path));
+        let mut deleted: Vec<String> = update
+            .deleted
+            .iter()
+            .map(|id| id.to_string())
+            .collect();
+        deleted.sort();
+
+        IndexUpdate { added, deleted }
+    }
+}
+
+#[uniffi::export]
+impl ResourceIndex {
+    pub fn root(&self) -> String {
+        self.root.to_string_lossy().into_owned()
+    }
+
+    /// Number of indexed files, colliding files are counted separately
+    pub fn size(&self) -> u64 {
+        self.lock().size() as u64
+    }
+
+    pub fn entries(&self) -> Vec<IndexEntry> {
+        let mut entries: Vec<IndexEntry> = self
+            .lock()
+            .path2id
+            .iter()
+            .map(|(path, entry)| IndexEntry {
+                id: entry.id.to_string(),
+                path: self.relative(path.as_ref()),
+            })
+            .collect();
+        entries.sort_by(|a, b| a.path.cmp(&b.path));
+        entries
+    }
+
+    /// Path of a resource relative to the root, if it is indexed
+    pub fn path_of(&self, id: String) -> Result<Option<String>> {
+        let id = parse_id(&id)?;
+        Ok(self
+            .lock()
+            .id2path
+            .get(&id)
+            .map(|path| self.relative(path.as_ref())))
+    }
+
+    /// Id of the resource at `path`, relative to the root or absolute
+    pub fn id_of(&self, path: String) -> Option<String> {
+        let path = CanonicalPathBuf::canonicalize(self.root.join(path)).ok()?;
+        self.lock()
+            .path2id
+            .get(&path)
+            .map(|entry| entry.id.to_string())
+    }
+
+    /// Rescan the root and return what has changed
+    pub async fn update_all(self: Arc<Self>) -> Result<IndexUpdate> {
+        background(move || self.update_blocking()).await
+    }
+
+    /// Persist the index into the `.ark` folder of the root
+    pub fn store(&self) -> Result<()> {
+        self.lock().store()?;
+        Ok(())
+    }
+
+    /// Apply the changes of the files of the root to the index as the
+    /// filesystem reports them, reporting non-empty updates to `listener`
+    /// from a background thread.
+    ///
+    /// Watching stops when the returned handle is stopped or dropped.
+    pub fn watch(
+        self: Arc<Self>,
+        listener: Box<dyn IndexListener>,
+    ) -> Result<Arc<WatchHandle>> {
+        WatchHandle::spawn(self, listener)
+    }
+}
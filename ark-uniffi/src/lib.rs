@@ -0,0 +1,187 @@
+//! Kotlin and Swift bindings for the ARK index and storages,
+//! generated with [UniFFI](https://mozilla.github.io/uniffi-rs/).
+//!
+//! See the README of this crate for building the bindings
+//! for Android and iOS.
+
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use fs_atomic_versions::app_id;
+
+mod error;
+mod index;
+mod properties;
+mod scores;
+mod tags;
+mod task;
+mod watch;
+
+pub use error::ArkError;
+pub use index::{
+    build_index, provide_index, IndexEntry, IndexUpdate, ResourceIndex,
+};
+pub use properties::{get_properties, patch_properties};
+pub use scores::ScoreStorage;
+pub use tags::TagStorage;
+pub use task::CancellationToken;
+pub use watch::{IndexListener, WatchHandle};
+
+uniffi::setup_scaffolding!();
+
+/// Type of the resource ids, [the one of the apps](dev_hash::Crc32)
+pub type ResourceId = dev_hash::Crc32;
+
+/// Initialize the library, must be called once before anything else.
+///
+/// `ark_dir` is the folder where the app id is kept, e.g. the app's
+/// files directory on Android.
+#[uniffi::export]
+pub fn initialize(ark_dir: String) -> error::Result<()> {
+    let ark_dir = PathBuf::from(ark_dir);
+    std::fs::create_dir_all(&ark_dir)?;
+    app_id::load(ark_dir)?;
+    Ok(())
+}
+
+pub(crate) fn parse_id(id: &str) -> error::Result<ResourceId> {
+    ResourceId::from_str(id).map_err(|_| ArkError::InvalidArgument {
+        message: format!("`{}` is not a valid id", id),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc::{channel, Sender};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use data_resource::ResourceId as _;
+    use futures::executor::block_on;
+    use tempdir::TempDir;
+
+    use super::*;
+
+    fn setup() -> (TempDir, String) {
+        let dir = TempDir::new("ark-uniffi").unwrap();
+        initialize(
+            dir.path()
+                .join(".ark")
+                .to_string_lossy()
+                .into_owned(),
+        )
+        .unwrap();
+        let root = dir.path().to_string_lossy().into_owned();
+        (dir, root)
+    }
+
+    fn id_of(content: &[u8]) -> String {
+        ResourceId::from_bytes(content)
+            .unwrap()
+            .to_string()
+    }
+
+    struct Forward(Mutex<Sender<IndexUpdate>>);
+
+    impl IndexListener for Forward {
+        fn on_update(&self, update: IndexUpdate) {
+            self.0.lock().unwrap().send(update).unwrap();
+        }
+    }
+
+    #[test]
+    fn index_build_update_and_watch() {
+        let (dir, root) = setup();
+        std::fs::write(dir.path().join("a.txt"), "a").unwrap();
+
+        let index =
+            block_on(build_index(root.clone(), CancellationToken::new()))
+                .unwrap();
+        assert_eq!(index.size(), 1);
+        assert_eq!(index.id_of("a.txt".to_owned()), Some(id_of(b"a")));
+        assert_eq!(
+            index.path_of(id_of(b"a")).unwrap(),
+            Some("a.txt".to_owned())
+        );
+
+        std::fs::write(dir.path().join("b.txt"), "b").unwrap();
+        let update = block_on(index.clone().update_all()).unwrap();
+        assert_eq!(
+            update.added,
+            vec![IndexEntry {
+                id: id_of(b"b"),
+                path: "b.txt".to_owned()
+            }]
+        );
+        assert!(update.deleted.is_empty());
+
+        let (sender, receiver) = channel();
+        let handle = index
+            .clone()
+            .watch(Box::new(Forward(Mutex::new(sender))))
+            .unwrap();
+        std::fs::remove_file(dir.path().join("a.txt")).unwrap();
+        let update = receiver
+            .recv_timeout(Duration::from_secs(5))
+            .unwrap();
+        assert_eq!(update.deleted, vec![id_of(b"a")]);
+        handle.stop();
+
+        index.store().unwrap();
+        let provided = block_on(provide_index(root)).unwrap();
+        assert_eq!(provided.entries(), index.entries());
+    }
+
+    #[test]
+    fn cancelled_build_fails() {
+        let (dir, root) = setup();
+        std::fs::write(dir.path().join("a.txt"), "a").unwrap();
+
+        let token = CancellationToken::new();
+        token.cancel();
+        assert!(matches!(
+            block_on(build_index(root, Arc::clone(&token))),
+            Err(ArkError::Cancelled)
+        ));
+    }
+
+    #[test]
+    fn storages_and_properties_roundtrip() {
+        let (_dir, root) = setup();
+        let id = "42".to_owned();
+
+        let tags = TagStorage::new(root.clone()).unwrap();
+        tags.add(id.clone(), "a".to_owned()).unwrap();
+        tags.add(id.clone(), "b".to_owned()).unwrap();
+        tags.remove(id.clone(), "a".to_owned()).unwrap();
+        assert_eq!(tags.get(id.clone()).unwrap(), vec!["b"]);
+        assert_eq!(tags.find("b".to_owned()), vec![id.clone()]);
+
+        let scores = ScoreStorage::new(root.clone()).unwrap();
+        scores.set(id.clone(), 5).unwrap();
+        assert_eq!(
+            ScoreStorage::new(root.clone())
+                .unwrap()
+                .get(id.clone())
+                .unwrap(),
+            5
+        );
+
+        assert_eq!(get_properties(root.clone(), id.clone()).unwrap(), None);
+        patch_properties(
+            root.clone(),
+            id.clone(),
+            r#"{"title":"Lena"}"#.into(),
+        )
+        .unwrap();
+        assert_eq!(
+            get_properties(root.clone(), id).unwrap(),
+            Some(r#"{"title":"Lena"}"#.to_owned())
+        );
+
+        assert!(matches!(
+            tags.get("not an id".to_owned()),
+            Err(ArkError::InvalidArgument { .. })
+        ));
+    }
+}
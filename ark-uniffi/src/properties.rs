@@ -0,0 +1,49 @@
+use std::path::PathBuf;
+
+use serde_json::Value;
+
+use fs_properties::{
+    load_raw_properties, store_properties, PROPERTIES_STORAGE_FOLDER,
+};
+use fs_storage::ARK_FOLDER;
+
+use crate::error::{ArkError, Result};
+use crate::parse_id;
+
+/// Properties of a resource as a JSON object,
+/// or `null` if the resource has none
+#[uniffi::export]
+pub fn get_properties(root: String, id: String) -> Result<Option<String>> {
+    let root = PathBuf::from(root);
+    let id = parse_id(&id)?;
+
+    let storage = root
+        .join(ARK_FOLDER)
+        .join(PROPERTIES_STORAGE_FOLDER)
+        .join(id.to_string());
+    if !storage.exists() {
+        return Ok(None);
+    }
+
+    let raw = load_raw_properties(&root, id)?;
+    let properties: Value = serde_json::from_slice(&raw)?;
+    Ok(Some(properties.to_string()))
+}
+
+/// Merge a JSON object into the properties of a resource
+#[uniffi::export]
+pub fn patch_properties(root: String, id: String, json: String) -> Result<()> {
+    let id = parse_id(&id)?;
+    let properties: Value =
+        serde_json::from_str(&json).map_err(|_| ArkError::InvalidArgument {
+            message: "`json` is not valid JSON".to_owned(),
+        })?;
+    if !properties.is_object() {
+        return Err(ArkError::InvalidArgument {
+            message: "`json` must be an object".to_owned(),
+        });
+    }
+
+    store_properties(root, id, &properties)?;
+    Ok(())
+}
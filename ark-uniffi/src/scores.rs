@@ -0,0 +1,52 @@
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use fs_scores::ScoreStorage as Storage;
+
+use crate::error::Result;
+use crate::{parse_id, ResourceId};
+
+/// Scores storage of a root, changes are written to disk immediately
+#[derive(uniffi::Object)]
+pub struct ScoreStorage {
+    storage: Mutex<Storage<ResourceId>>,
+}
+
+impl ScoreStorage {
+    fn lock(&self) -> MutexGuard<'_, Storage<ResourceId>> {
+        self.storage
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[uniffi::export]
+impl ScoreStorage {
+    #[uniffi::constructor]
+    pub fn new(root: String) -> Result<Arc<Self>> {
+        let storage = Storage::new(root)?;
+        Ok(Arc::new(ScoreStorage {
+            storage: Mutex::new(storage),
+        }))
+    }
+
+    /// Score of a resource, resources without score have score 0.
+    /// Scores beyond the range of `i32` are clamped.
+    pub fn get(&self, id: String) -> Result<i32> {
+        let id = parse_id(&id)?;
+        let score = self.lock().score(&id);
+        Ok(score.clamp(i32::MIN.into(), i32::MAX.into()) as i32)
+    }
+
+    /// Set the score of a resource, score 0 removes it from the storage
+    pub fn set(&self, id: String, score: i32) -> Result<()> {
+        let id = parse_id(&id)?;
+        self.lock().set_score(id, score.into())?;
+        Ok(())
+    }
+
+    /// See [`fs_scores::ScoreStorage::sync`]
+    pub fn sync(&self) -> Result<()> {
+        self.lock().sync()?;
+        Ok(())
+    }
+}
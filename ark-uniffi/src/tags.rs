@@ -0,0 +1,94 @@
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use fs_storage::base_storage::BaseStorage;
+use fs_storage::file_storage::FileStorage;
+use fs_storage::tag_set::TagSetWithTombstones;
+use fs_storage::{now_millis, ARK_FOLDER, TAG_STORAGE_FILE};
+
+use crate::error::Result;
+use crate::parse_id;
+
+/// Tags storage of a root, changes are written to disk immediately
+#[derive(uniffi::Object)]
+pub struct TagStorage {
+    storage: Mutex<FileStorage<String, TagSetWithTombstones>>,
+}
+
+impl TagStorage {
+    fn lock(
+        &self,
+    ) -> MutexGuard<'_, FileStorage<String, TagSetWithTombstones>> {
+        self.storage
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[uniffi::export]
+impl TagStorage {
+    #[uniffi::constructor]
+    pub fn new(root: String) -> Result<Arc<Self>> {
+        let path = PathBuf::from(root)
+            .join(ARK_FOLDER)
+            .join(TAG_STORAGE_FILE);
+        let storage = FileStorage::new("tags".to_owned(), &path)?;
+        Ok(Arc::new(TagStorage {
+            storage: Mutex::new(storage),
+        }))
+    }
+
+    /// Tags of a resource, sorted
+    pub fn get(&self, id: String) -> Result<Vec<String>> {
+        let id = parse_id(&id)?.to_string();
+        Ok(self
+            .lock()
+            .as_ref()
+            .get(&id)
+            .map(|set| set.iter().map(str::to_owned).collect())
+            .unwrap_or_default())
+    }
+
+    pub fn add(&self, id: String, tag: String) -> Result<()> {
+        let id = parse_id(&id)?.to_string();
+        let mut storage = self.lock();
+        let mut set = storage
+            .as_ref()
+            .get(&id)
+            .cloned()
+            .unwrap_or_default();
+        set.insert(&tag, now_millis());
+        storage.set(id, set);
+        storage.write_fs()?;
+        Ok(())
+    }
+
+    pub fn remove(&self, id: String, tag: String) -> Result<()> {
+        let id = parse_id(&id)?.to_string();
+        let mut storage = self.lock();
+        let Some(mut set) = storage.as_ref().get(&id).cloned() else {
+            return Ok(());
+        };
+        // the removal is kept, so that syncing doesn't bring the tag back
+        set.remove(&tag, now_millis());
+        storage.set(id, set);
+        storage.write_fs()?;
+        Ok(())
+    }
+
+    /// Ids of the resources having `tag`
+    pub fn find(&self, tag: String) -> Vec<String> {
+        self.lock()
+            .as_ref()
+            .iter()
+            .filter(|(_, set)| set.contains(&tag))
+            .map(|(id, _)| id.clone())
+            .collect()
+    }
+
+    /// See [`BaseStorage::sync`]
+    pub fn sync(&self) -> Result<()> {
+        self.lock().sync()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,57 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+use futures::channel::oneshot;
+
+use crate::error::{ArkError, Result};
+
+/// Cancels a long-running operation, e.g. building an index.
+///
+/// The token can be cancelled from any thread, the operation notices it
+/// at its next checkpoint and fails with `ArkError::Cancelled`.
+#[derive(Debug, Default, uniffi::Object)]
+pub struct CancellationToken {
+    cancelled: AtomicBool,
+}
+
+#[uniffi::export]
+impl CancellationToken {
+    #[uniffi::constructor]
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self::default())
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+impl CancellationToken {
+    pub(crate) fn flag(&self) -> &AtomicBool {
+        &self.cancelled
+    }
+}
+
+/// Run blocking `work` on a dedicated thread.
+///
+/// Exported `async` functions use it so that the foreign caller's thread
+/// (e.g. a coroutine dispatcher or a Swift task) is released meanwhile.
+pub(crate) async fn background<T, F>(work: F) -> Result<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T> + Send + 'static,
+{
+    let (sender, receiver) = oneshot::channel();
+    thread::spawn(move || {
+        // The receiver is gone if the caller dropped the future
+        let _ = sender.send(work());
+    });
+    receiver.await.map_err(|_| ArkError::Other {
+        message: "Background task panicked".to_owned(),
+    })?
+}
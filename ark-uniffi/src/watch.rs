@@ -0,0 +1,83 @@
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+use fs_index::watch::IndexWatcher;
+
+use crate::error::Result;
+use crate::index::{IndexUpdate, ResourceIndex};
+use crate::ResourceId;
+
+/// Receives the changes found while watching an index.
+///
+/// Methods are called from a background thread owned by the library.
+#[uniffi::export(callback_interface)]
+pub trait IndexListener: Send + Sync {
+    fn on_update(&self, update: IndexUpdate);
+}
+
+/// Keeps a watcher running, see `ResourceIndex::watch`
+#[derive(uniffi::Object)]
+pub struct WatchHandle {
+    watcher: Mutex<Option<IndexWatcher<ResourceId>>>,
+    thread: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl WatchHandle {
+    pub(crate) fn spawn(
+        index: Arc<ResourceIndex>,
+        listener: Box<dyn IndexListener>,
+    ) -> Result<Arc<Self>> {
+        let (watcher, updates) = IndexWatcher::shared(index.shared())?;
+
+        // ends once the watcher is stopped
+        let thread = thread::spawn(move || {
+            for update in updates {
+                listener.on_update(index.report(update));
+            }
+        });
+
+        Ok(Arc::new(WatchHandle {
+            watcher: Mutex::new(Some(watcher)),
+            thread: Mutex::new(Some(thread)),
+        }))
+    }
+
+    fn stop_watcher(&self) {
+        let watcher = match self.watcher.lock() {
+            Ok(mut watcher) => watcher.take(),
+            Err(poisoned) => poisoned.into_inner().take(),
+        };
+        // waits for the update being applied, if any
+        drop(watcher);
+    }
+}
+
+#[uniffi::export]
+impl WatchHandle {
+    /// Stop watching and wait for the current update to be reported.
+    ///
+    /// No listener method is called after this returns, unless it is
+    /// called from the listener itself.
+    pub fn stop(&self) {
+        self.stop_watcher();
+        let thread = match self.thread.lock() {
+            Ok(mut thread) => thread.take(),
+            Err(poisoned) => poisoned.into_inner().take(),
+        };
+        if let Some(thread) = thread {
+            // Joining from the listener would wait for itself
+            if thread.thread().id() == thread::current().id() {
+                return;
+            }
+            if thread.join().is_err() {
+                log::error!("Watcher thread panicked");
+            }
+        }
+    }
+}
+
+impl Drop for WatchHandle {
+    fn drop(&mut self) {
+        self.stop_watcher();
+    }
+}
@@ -0,0 +1,36 @@
+import dev.arkbuilders.core.*
+import java.io.File
+import java.nio.file.Files
+import kotlinx.coroutines.runBlocking
+
+val root = Files.createTempDirectory("ark-uniffi").toFile()
+initialize(File(root, ".ark").path)
+File(root, "a.txt").writeText("a")
+
+runBlocking {
+    val index = buildIndex(root.path, CancellationToken())
+    assert(index.size() == 1uL)
+    val id = index.idOf("a.txt")!!
+
+    val tags = TagStorage(root.path)
+    tags.add(id, "kotlin")
+    assert(tags.get(id) == listOf("kotlin"))
+    assert(tags.find("kotlin") == listOf(id))
+
+    val scores = ScoreStorage(root.path)
+    scores.set(id, 3)
+    assert(scores.get(id) == 3)
+
+    patchProperties(root.path, id, """{"title":"a"}""")
+    assert(getProperties(root.path, id) == """{"title":"a"}""")
+
+    val token = CancellationToken()
+    token.cancel()
+    try {
+        buildIndex(root.path, token)
+        throw RuntimeException("Build should have been cancelled")
+    } catch (e: ArkException.Cancelled) {
+    }
+}
+
+root.deleteRecursively()
@@ -0,0 +1,40 @@
+import ArkCore
+import Foundation
+
+let root = FileManager.default.temporaryDirectory
+    .appendingPathComponent(UUID().uuidString)
+try! FileManager.default.createDirectory(
+    at: root, withIntermediateDirectories: true)
+try! initialize(arkDir: root.appendingPathComponent(".ark").path)
+try! "a".write(
+    to: root.appendingPathComponent("a.txt"), atomically: true,
+    encoding: .utf8)
+
+// Top-level code can't await, so wait for the task with a semaphore
+let done = DispatchSemaphore(value: 0)
+Task {
+    let index = try! await buildIndex(
+        root: root.path, token: CancellationToken())
+    assert(index.size() == 1)
+    let id = index.idOf(path: "a.txt")!
+
+    let tags = try! TagStorage(root: root.path)
+    try! tags.add(id: id, tag: "swift")
+    assert(try! tags.get(id: id) == ["swift"])
+
+    let scores = try! ScoreStorage(root: root.path)
+    try! scores.set(id: id, score: 3)
+    assert(try! scores.get(id: id) == 3)
+
+    let token = CancellationToken()
+    token.cancel()
+    do {
+        _ = try await buildIndex(root: root.path, token: token)
+        fatalError("Build should have been cancelled")
+    } catch ArkError.Cancelled {
+    }
+    done.signal()
+}
+done.wait()
+
+try! FileManager.default.removeItem(at: root)
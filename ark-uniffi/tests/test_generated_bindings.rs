@@ -0,0 +1,7 @@
+#![cfg(feature = "bindgen-tests")]
+
+// Generates the bindings, then compiles and runs each script against them
+uniffi::build_foreign_language_testcases!(
+    "tests/bindings/test_ark.kts",
+    "tests/bindings/test_ark.swift",
+);
@@ -0,0 +1,166 @@
+//! WebAssembly bindings for computing resource ids and merging JSON,
+//! for apps running in a browser or in Node.js.
+//!
+//! Nothing here touches the filesystem, resources are passed as bytes.
+//!
+//! ```text
+//! wasm-pack build ark-wasm --target web
+//! ```
+
+use std::str::FromStr;
+
+use serde::Deserialize;
+use serde_json::Value;
+use wasm_bindgen::prelude::*;
+
+use data_resource::ResourceId;
+use dev_hash::{Blake3, Crc32};
+
+#[wasm_bindgen(typescript_custom_section)]
+const TS_API: &str = r#"
+export type IdKind = "crc32" | "blake3";
+
+export interface MergeOptions {
+    /** Indent the resulting JSON */
+    pretty?: boolean;
+}
+
+export function computeId(bytes: Uint8Array, kind: IdKind): string;
+export function mergeJson(origin: string, update: string, options?: MergeOptions): string;
+export function applyPatch(target: string, patch: string, options?: MergeOptions): string;
+"#;
+
+/// Hash function used to compute the id
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum IdKind {
+    Crc32,
+    Blake3,
+}
+
+impl FromStr for IdKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "crc32" => Ok(IdKind::Crc32),
+            "blake3" => Ok(IdKind::Blake3),
+            _ => Err(format!("Unknown id kind `{}`", s)),
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct MergeOptions {
+    pretty: bool,
+}
+
+fn id_of(bytes: &[u8], kind: IdKind) -> Result<String, String> {
+    let id = match kind {
+        IdKind::Crc32 => Crc32::from_bytes(bytes).map(|id| id.to_string()),
+        IdKind::Blake3 => Blake3::from_bytes(bytes).map(|id| id.to_string()),
+    };
+    id.map_err(|err| err.to_string())
+}
+
+fn parse_json(json: &str, name: &str) -> Result<Value, String> {
+    serde_json::from_str(json)
+        .map_err(|err| format!("`{}` is not valid JSON: {}", name, err))
+}
+
+fn to_json(value: &Value, options: &MergeOptions) -> Result<String, String> {
+    let json = if options.pretty {
+        serde_json::to_string_pretty(value)
+    } else {
+        serde_json::to_string(value)
+    };
+    json.map_err(|err| err.to_string())
+}
+
+fn merge_options(options: JsValue) -> Result<MergeOptions, JsError> {
+    let options: Option<MergeOptions> = serde_wasm_bindgen::from_value(options)
+        .map_err(|err| JsError::new(&format!("Invalid options: {}", err)))?;
+    Ok(options.unwrap_or_default())
+}
+
+/// Compute the id of a resource from its content.
+///
+/// Ids are the same as the ones computed by the native libraries.
+#[wasm_bindgen(js_name = computeId, skip_typescript)]
+pub fn compute_id(bytes: &[u8], kind: &str) -> Result<String, JsError> {
+    let kind = IdKind::from_str(kind).map_err(|err| JsError::new(&err))?;
+    id_of(bytes, kind).map_err(|err| JsError::new(&err))
+}
+
+/// Merge two JSON documents the same way properties are merged on sync,
+/// see `data_json::merge`
+#[wasm_bindgen(js_name = mergeJson, skip_typescript)]
+pub fn merge_json(
+    origin: &str,
+    update: &str,
+    options: JsValue,
+) -> Result<String, JsError> {
+    let options = merge_options(options)?;
+    merge_str(origin, update, &options).map_err(|err| JsError::new(&err))
+}
+
+/// Apply a JSON Merge Patch to a document, see `data_json::apply_patch`
+#[wasm_bindgen(js_name = applyPatch, skip_typescript)]
+pub fn apply_patch(
+    target: &str,
+    patch: &str,
+    options: JsValue,
+) -> Result<String, JsError> {
+    let options = merge_options(options)?;
+    patch_str(target, patch, &options).map_err(|err| JsError::new(&err))
+}
+
+fn merge_str(
+    origin: &str,
+    update: &str,
+    options: &MergeOptions,
+) -> Result<String, String> {
+    let origin = parse_json(origin, "origin")?;
+    let update = parse_json(update, "update")?;
+    to_json(&data_json::merge(origin, update), options)
+}
+
+fn patch_str(
+    target: &str,
+    patch: &str,
+    options: &MergeOptions,
+) -> Result<String, String> {
+    let target = parse_json(target, "target")?;
+    let patch = parse_json(patch, "patch")?;
+    to_json(&data_json::apply_patch(target, patch), options)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ids_match_dev_hash() {
+        assert_eq!(
+            id_of(b"abc", IdKind::from_str("crc32").unwrap()).unwrap(),
+            "891568578"
+        );
+        assert!(IdKind::from_str("md5").is_err());
+    }
+
+    #[test]
+    fn merge_and_patch_strings() {
+        let options = MergeOptions::default();
+        assert_eq!(
+            merge_str(r#"{"a":1}"#, r#"{"b":2}"#, &options).unwrap(),
+            r#"{"a":1,"b":2}"#
+        );
+        assert_eq!(
+            patch_str(r#"{"a":1,"b":2}"#, r#"{"a":null}"#, &options).unwrap(),
+            r#"{"b":2}"#
+        );
+        assert!(merge_str("{", "{}", &options)
+            .unwrap_err()
+            .contains("`origin`"));
+    }
+}
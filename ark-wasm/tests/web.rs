@@ -0,0 +1,71 @@
+//! Run with `wasm-pack test --node ark-wasm`
+
+#![cfg(target_arch = "wasm32")]
+
+use serde::Deserialize;
+use wasm_bindgen::{JsError, JsValue};
+use wasm_bindgen_test::*;
+
+use ark_wasm::{apply_patch, compute_id, merge_json};
+
+// Shared with the native tests of `dev-hash`
+const VECTORS: &str = include_str!("../../test-assets/id-vectors.json");
+
+#[derive(Deserialize)]
+struct Vectors {
+    vectors: Vec<Vector>,
+}
+
+#[derive(Deserialize)]
+struct Vector {
+    name: String,
+    input: String,
+    crc32: String,
+    blake3: String,
+}
+
+fn ok<T>(result: Result<T, JsError>) -> T {
+    result.unwrap_or_else(|_| panic!("Unexpected error"))
+}
+
+#[wasm_bindgen_test]
+fn ids_match_golden_vectors() {
+    let vectors: Vectors = serde_json::from_str(VECTORS).unwrap();
+    for vector in vectors.vectors {
+        let input = hex::decode(&vector.input).unwrap();
+        assert_eq!(
+            ok(compute_id(&input, "crc32")),
+            vector.crc32,
+            "{}",
+            vector.name
+        );
+        assert_eq!(
+            ok(compute_id(&input, "blake3")),
+            vector.blake3,
+            "{}",
+            vector.name
+        );
+    }
+    assert!(compute_id(b"", "md5").is_err());
+}
+
+#[wasm_bindgen_test]
+fn merges_and_patches_json() {
+    assert_eq!(
+        ok(merge_json(
+            r#"{"a":"x"}"#,
+            r#"{"a":"y"}"#,
+            JsValue::UNDEFINED
+        )),
+        r#"{"a":["x","y"]}"#
+    );
+    assert_eq!(
+        ok(apply_patch(
+            r#"{"a":"x"}"#,
+            r#"{"a":"y"}"#,
+            JsValue::UNDEFINED
+        )),
+        r#"{"a":"y"}"#
+    );
+    assert!(merge_json("not json", "{}", JsValue::UNDEFINED).is_err());
+}
@@ -0,0 +1,217 @@
+use serde::ser::{SerializeStruct, Serializer};
+use serde::Serialize;
+use std::time::{Duration, Instant};
+
+use crate::{ArklibError, Result};
+
+/// Outcome of an operation applied to many items at once.
+///
+/// Bulk operations don't stop at the first failure: every item is attempted
+/// and the report keeps both the successful results and the errors, each
+/// tagged with the key of the item (resource id, path, etc.) it belongs to.
+#[derive(Debug)]
+pub struct BulkResult<T, K> {
+    pub succeeded: Vec<(K, T)>,
+    pub failed: Vec<(K, ArklibError)>,
+    /// Time spent performing the operation
+    pub elapsed: Duration,
+}
+
+impl<T, K> Default for BulkResult<T, K> {
+    fn default() -> Self {
+        Self {
+            succeeded: Vec::new(),
+            failed: Vec::new(),
+            elapsed: Duration::ZERO,
+        }
+    }
+}
+
+impl<T, K> BulkResult<T, K> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Run `operation` for every item, recording its outcome and measuring
+    /// the total time taken.
+    pub fn run<I, F>(items: I, mut operation: F) -> Self
+    where
+        I: IntoIterator<Item = K>,
+        F: FnMut(&K) -> Result<T>,
+    {
+        let start = Instant::now();
+        let mut report = Self::new();
+        for key in items {
+            let result = operation(&key);
+            report.record(key, result);
+        }
+        report.elapsed = start.elapsed();
+        report
+    }
+
+    /// Record the outcome of a single item
+    pub fn record(&mut self, key: K, result: Result<T>) {
+        match result {
+            Ok(value) => self.succeeded.push((key, value)),
+            Err(err) => self.failed.push((key, err)),
+        }
+    }
+
+    /// Total number of items processed
+    pub fn len(&self) -> usize {
+        self.succeeded.len() + self.failed.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn has_failures(&self) -> bool {
+        !self.failed.is_empty()
+    }
+
+    /// Share of items which were processed successfully.
+    ///
+    /// An empty report is considered fully successful.
+    pub fn ok_ratio(&self) -> f64 {
+        if self.is_empty() {
+            return 1.0;
+        }
+        self.succeeded.len() as f64 / self.len() as f64
+    }
+
+    /// Combine two reports, e.g. produced by different worker threads.
+    /// Elapsed times are summed up.
+    pub fn merge(mut self, other: Self) -> Self {
+        self.succeeded.extend(other.succeeded);
+        self.failed.extend(other.failed);
+        self.elapsed += other.elapsed;
+        self
+    }
+
+    /// Turn the report into a plain `Result`, failing if any item failed.
+    ///
+    /// The returned error carries the number of failures and the first
+    /// error encountered.
+    pub fn into_result(self) -> Result<Vec<(K, T)>> {
+        let total = self.len();
+        match self.failed.into_iter().next() {
+            None => Ok(self.succeeded),
+            Some((_, first)) => Err(ArklibError::Bulk(
+                total - self.succeeded.len(),
+                total,
+                Box::new(first),
+            )),
+        }
+    }
+}
+
+impl<T, K> FromIterator<(K, Result<T>)> for BulkResult<T, K> {
+    fn from_iter<I: IntoIterator<Item = (K, Result<T>)>>(iter: I) -> Self {
+        let start = Instant::now();
+        let mut report = Self::new();
+        for (key, result) in iter {
+            report.record(key, result);
+        }
+        report.elapsed = start.elapsed();
+        report
+    }
+}
+
+#[derive(Serialize)]
+struct FailedEntry<'a, K> {
+    key: &'a K,
+    error: String,
+}
+
+/// Errors are serialized using their `Display` representation,
+/// and elapsed time as milliseconds.
+impl<T: Serialize, K: Serialize> Serialize for BulkResult<T, K> {
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        let failed: Vec<FailedEntry<K>> = self
+            .failed
+            .iter()
+            .map(|(key, err)| FailedEntry {
+                key,
+                error: err.to_string(),
+            })
+            .collect();
+
+        let mut state = serializer.serialize_struct("BulkResult", 3)?;
+        state.serialize_field("succeeded", &self.succeeded)?;
+        state.serialize_field("failed", &failed)?;
+        state.serialize_field("elapsed_ms", &self.elapsed.as_millis())?;
+        state.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report(ok: &[u32], failed: &[u32]) -> BulkResult<u32, u32> {
+        ok.iter()
+            .map(|k| (*k, Ok(*k * 10)))
+            .chain(
+                failed
+                    .iter()
+                    .map(|k| (*k, Err(ArklibError::Parse))),
+            )
+            .collect()
+    }
+
+    #[test]
+    fn merge_combines_both_reports() {
+        let merged = report(&[1, 2], &[3]).merge(report(&[4], &[5, 6]));
+
+        assert_eq!(merged.len(), 6);
+        assert_eq!(
+            merged
+                .succeeded
+                .iter()
+                .map(|(k, _)| *k)
+                .collect::<Vec<_>>(),
+            vec![1, 2, 4]
+        );
+        assert_eq!(
+            merged
+                .failed
+                .iter()
+                .map(|(k, _)| *k)
+                .collect::<Vec<_>>(),
+            vec![3, 5, 6]
+        );
+        assert_eq!(merged.ok_ratio(), 0.5);
+    }
+
+    #[test]
+    fn into_result_fails_on_any_failure() {
+        let all_ok = report(&[1, 2, 3], &[]);
+        assert_eq!(all_ok.ok_ratio(), 1.0);
+        assert_eq!(all_ok.into_result().unwrap().len(), 3);
+
+        let one_failed = report(&[1, 2, 3], &[4]);
+        match one_failed.into_result() {
+            Err(ArklibError::Bulk(failed, total, _)) => {
+                assert_eq!(failed, 1);
+                assert_eq!(total, 4);
+            }
+            other => panic!("Unexpected result: {:?}", other),
+        }
+
+        let empty = report(&[], &[]);
+        assert_eq!(empty.ok_ratio(), 1.0);
+        assert!(empty.into_result().unwrap().is_empty());
+    }
+
+    #[test]
+    fn serializes_errors_as_messages() {
+        let json = serde_json::to_value(report(&[1], &[2])).unwrap();
+        assert_eq!(json["succeeded"], serde_json::json!([[1, 10]]));
+        assert_eq!(json["failed"][0]["key"], 2);
+        assert_eq!(json["failed"][0]["error"], "Parsing error");
+    }
+}
@@ -0,0 +1,82 @@
+use std::path::Path;
+
+use crate::{ArklibError, Result};
+
+/// Extension methods for attaching context to errors, so that the resulting
+/// [`ArklibError`] tells which file or storage the failure is about.
+pub trait ResultExt<T> {
+    /// Annotate the error with the path of the file or folder
+    /// the failed operation was performed on.
+    fn with_path<P: AsRef<Path>>(self, path: P) -> Result<T>;
+
+    /// Turn the error into [`ArklibError::Storage`] with the given label.
+    /// Errors which already are storage errors are left untouched.
+    fn with_label(self, label: &str) -> Result<T>;
+}
+
+impl<T, E: Into<ArklibError>> ResultExt<T> for std::result::Result<T, E> {
+    fn with_path<P: AsRef<Path>>(self, path: P) -> Result<T> {
+        self.map_err(|err| {
+            ArklibError::WithPath(
+                path.as_ref().to_path_buf(),
+                Box::new(err.into()),
+            )
+        })
+    }
+
+    fn with_label(self, label: &str) -> Result<T> {
+        self.map_err(|err| match err.into() {
+            err @ ArklibError::Storage(..) => err,
+            err => ArklibError::Storage(label.to_owned(), err.to_string()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io;
+
+    fn not_found() -> std::result::Result<(), io::Error> {
+        Err(io::Error::new(io::ErrorKind::NotFound, "No such file"))
+    }
+
+    #[test]
+    fn path_is_part_of_the_message() {
+        let err = not_found()
+            .with_path("/tmp/missing/storage")
+            .unwrap_err();
+
+        let message = err.to_string();
+        assert!(message.contains("No such file"), "{}", message);
+        assert!(message.contains("/tmp/missing/storage"), "{}", message);
+    }
+
+    #[test]
+    fn label_and_path_are_part_of_the_message() {
+        let err = not_found()
+            .with_path("/tmp/missing/storage")
+            .with_label("tags")
+            .unwrap_err();
+
+        assert!(
+            matches!(err, ArklibError::Storage(ref label, _) if label == "tags")
+        );
+        let message = err.to_string();
+        assert!(message.contains("tags"), "{}", message);
+        assert!(message.contains("/tmp/missing/storage"), "{}", message);
+    }
+
+    #[test]
+    fn storage_errors_keep_their_label() {
+        let err: Result<()> = Err(ArklibError::Storage(
+            "scores".to_owned(),
+            "Key not found".to_owned(),
+        ));
+        let err = err.with_label("tags").unwrap_err();
+
+        assert!(
+            matches!(err, ArklibError::Storage(ref label, _) if label == "scores")
+        );
+    }
+}
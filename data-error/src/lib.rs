@@ -1,6 +1,12 @@
-use std::{convert::Infallible, str::Utf8Error};
+use std::{convert::Infallible, path::PathBuf, str::Utf8Error};
 use thiserror::Error;
 
+mod bulk;
+mod context;
+
+pub use bulk::BulkResult;
+pub use context::ResultExt;
+
 pub type Result<T> = std::result::Result<T, ArklibError>;
 
 #[derive(Error, Debug)]
@@ -9,6 +15,9 @@ pub enum ArklibError {
     Io(#[from] std::io::Error),
     #[error("Path error: {0}")]
     Path(String),
+    /// Error annotated with the path it happened at
+    #[error("{} (path: {})", .1, .0.display())]
+    WithPath(PathBuf, Box<ArklibError>),
     #[error("There is some collision: {0}")]
     Collision(String),
     #[error("Parsing error")]
@@ -18,10 +27,50 @@ pub enum ArklibError {
     /// Storage error shows label and error message
     #[error("Storage error: {0} {1}")]
     Storage(String, String),
+    /// Bulk operation error shows number of failed items, total number
+    /// of items and the first error
+    #[error("{0} of {1} operations failed, first error: {2}")]
+    Bulk(usize, usize, Box<ArklibError>),
+    /// Storage written by a newer version shows label, version of the
+    /// storage and the latest supported version
+    #[error(
+        "Storage error: {0} has version {1}, but only versions up to {2} \
+         are supported, please update"
+    )]
+    UnsupportedVersion(String, i32, i32),
+    /// Storage file which can't be read as it was written shows label
+    /// and the reason
+    #[error(
+        "Storage error: {0} is corrupted, {1}. Restore it from a backup, \
+         or remove it to start from scratch"
+    )]
+    Corrupted(String, String),
     #[error(transparent)]
     Other(#[from] anyhow::Error),
 }
 
+impl ArklibError {
+    /// Stable numeric code of the error kind, used by foreign bindings.
+    ///
+    /// Codes are never reused or changed, new kinds get new codes.
+    /// Errors annotated with context report the code of the inner error.
+    pub fn code(&self) -> i32 {
+        match self {
+            ArklibError::Io(_) => 1,
+            ArklibError::Path(_) => 2,
+            ArklibError::WithPath(_, inner) => inner.code(),
+            ArklibError::Collision(_) => 3,
+            ArklibError::Parse => 4,
+            ArklibError::Network => 5,
+            ArklibError::Storage(_, _) => 6,
+            ArklibError::Bulk(_, _, _) => 7,
+            ArklibError::UnsupportedVersion(_, _, _) => 8,
+            ArklibError::Corrupted(_, _) => 9,
+            ArklibError::Other(_) => 99,
+        }
+    }
+}
+
 impl From<reqwest::Error> for ArklibError {
     fn from(_: reqwest::Error) -> Self {
         Self::Network
@@ -63,3 +112,18 @@ impl From<Infallible> for ArklibError {
         Self::Parse
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn code_of_annotated_error_is_inner_code() {
+        let err = ArklibError::WithPath(
+            PathBuf::from("/tmp"),
+            Box::new(ArklibError::Parse),
+        );
+        assert_eq!(err.code(), ArklibError::Parse.code());
+        assert_ne!(err.code(), 0);
+    }
+}
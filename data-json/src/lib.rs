@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+
 use serde_json::json;
 use serde_json::map::Entry;
 use serde_json::Map;
@@ -47,6 +49,116 @@ pub fn merge(origin: Value, new_data: Value) -> Value {
     }
 }
 
+/// Apply a JSON Merge Patch ([RFC 7396](https://www.rfc-editor.org/rfc/rfc7396))
+/// to `target`.
+///
+/// Unlike [`merge`], values of the patch replace the existing ones
+/// and `null` removes a key.
+pub fn apply_patch(target: Value, patch: Value) -> Value {
+    match patch {
+        Value::Object(patch) => {
+            let mut target = match target {
+                Value::Object(target) => target,
+                _ => Map::new(),
+            };
+            for (key, value) in patch.into_iter() {
+                if value.is_null() {
+                    target.remove(&key);
+                } else {
+                    let prev = target.remove(&key).unwrap_or(Value::Null);
+                    target.insert(key, apply_patch(prev, value));
+                }
+            }
+            Value::Object(target)
+        }
+        patch => patch,
+    }
+}
+
+/// How new values are combined with existing ones, see [`merge_with`]
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum MergeStrategy {
+    /// Combine the values with [`merge`]
+    #[default]
+    DeepMerge,
+    /// Keep the new value
+    Replace,
+    /// Append new arrays to existing ones, keeping duplicates,
+    /// merge objects key by key and keep new scalars
+    ArrayConcat,
+    /// Keep new arrays, merge objects key by key and keep new scalars
+    ArrayReplace,
+    /// Combine the values of the keys of objects with their own strategy,
+    /// or with `default` for keys which are not listed
+    PerKey {
+        keys: HashMap<String, MergeStrategy>,
+        default: Box<MergeStrategy>,
+    },
+}
+
+impl MergeStrategy {
+    /// Same strategy for every key, except the given ones
+    pub fn per_key<K: Into<String>>(
+        default: MergeStrategy,
+        keys: impl IntoIterator<Item = (K, MergeStrategy)>,
+    ) -> Self {
+        MergeStrategy::PerKey {
+            keys: keys
+                .into_iter()
+                .map(|(key, strategy)| (key.into(), strategy))
+                .collect(),
+            default: Box::new(default),
+        }
+    }
+}
+
+/// Combine `new_data` with `origin` according to `strategy`.
+///
+/// A `null` new value keeps the existing one, whatever the strategy.
+pub fn merge_with(
+    origin: Value,
+    new_data: Value,
+    strategy: &MergeStrategy,
+) -> Value {
+    match (strategy, origin, new_data) {
+        (_, origin, Value::Null) => origin,
+        (MergeStrategy::DeepMerge, origin, new_data) => merge(origin, new_data),
+        (MergeStrategy::Replace, _, new_data) => new_data,
+        (
+            MergeStrategy::ArrayConcat,
+            Value::Array(mut origin),
+            Value::Array(new_data),
+        ) => {
+            origin.extend(new_data);
+            Value::Array(origin)
+        }
+        (
+            MergeStrategy::PerKey { keys, default },
+            Value::Object(mut origin),
+            Value::Object(new_data),
+        ) => {
+            for (key, value) in new_data {
+                let strategy = keys.get(&key).unwrap_or(default);
+                let prev = origin.remove(&key).unwrap_or(Value::Null);
+                origin.insert(key, merge_with(prev, value, strategy));
+            }
+            Value::Object(origin)
+        }
+        (MergeStrategy::PerKey { default, .. }, origin, new_data) => {
+            merge_with(origin, new_data, default)
+        }
+        (strategy, Value::Object(mut origin), Value::Object(new_data)) => {
+            for (key, value) in new_data {
+                let prev = origin.remove(&key).unwrap_or(Value::Null);
+                origin.insert(key, merge_with(prev, value, strategy));
+            }
+            Value::Object(origin)
+        }
+        // arrays replaced or scalars
+        (_, _, new_data) => new_data,
+    }
+}
+
 fn merge_object(
     mut origin: Map<String, Value>,
     new_data: Map<String, Value>,
@@ -154,4 +266,42 @@ mod tests {
         let merged = merge(old, new);
         assert_eq!(merged, expected);
     }
+
+    #[rstest]
+    #[case(MergeStrategy::Replace, json ! ({"a": [1, 2], "b": 1}), json ! ({"a": []}), json ! ({"a": []}))]
+    #[case(MergeStrategy::ArrayConcat, json ! ({"a": [1, 2], "b": 1}), json ! ({"a": [2, 3], "b": 2}), json ! ({"a": [1, 2, 2, 3], "b": 2}))]
+    #[case(MergeStrategy::ArrayReplace, json ! ({"a": [1, 2], "b": {"c": 1}}), json ! ({"a": [], "b": {"d": 2}}), json ! ({"a": [], "b": {"c": 1, "d": 2}}))]
+    #[case(MergeStrategy::ArrayReplace, json ! ({"a": [1, 2]}), json ! ({"a": null}), json ! ({"a": [1, 2]}))]
+    #[case(
+        MergeStrategy::per_key(MergeStrategy::DeepMerge, [("title", MergeStrategy::Replace)]),
+        json ! ({"title": "old", "tags": ["a"]}),
+        json ! ({"title": "new", "tags": ["b"]}),
+        json ! ({"title": "new", "tags": ["a", "b"]})
+    )]
+    fn merging_with_strategy(
+        #[case] strategy: MergeStrategy,
+        #[case] old: Value,
+        #[case] new: Value,
+        #[case] expected: Value,
+    ) {
+        assert_eq!(merge_with(old, new, &strategy), expected);
+    }
+
+    #[rstest]
+    #[case(json ! ({"a": "b"}), json ! ({"a": "c"}), json ! ({"a": "c"}))]
+    #[case(json ! ({"a": "b"}), json ! ({"a": null}), json ! ({}))]
+    #[case(json ! ({"a": "b", "b": "c"}), json ! ({"a": null}), json ! ({"b": "c"}))]
+    #[case(json ! ({"a": ["b"]}), json ! ({"a": "c"}), json ! ({"a": "c"}))]
+    #[case(json ! ({"a": {"b": "c"}}), json ! ({"a": {"b": "d", "c": null}}), json ! ({"a": {"b": "d"}}))]
+    #[case(json ! (["a", "b"]), json ! (["c", "d"]), json ! (["c", "d"]))]
+    #[case(json ! ({"a": "foo"}), json ! ("bar"), json ! ("bar"))]
+    #[case(json ! ("string"), json ! ({"a": {"bb": {"ccc": null}}}), json ! ({"a": {"bb": {}}}))]
+    fn patching_as_expected(
+        #[case] target: Value,
+        #[case] patch: Value,
+        #[case] expected: Value,
+    ) {
+        let patched = apply_patch(target, patch);
+        assert_eq!(patched, expected);
+    }
 }
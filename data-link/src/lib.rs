@@ -1,10 +1,12 @@
 use data_error::Result;
 use data_resource::ResourceId;
 use fs_atomic_versions::atomic::AtomicFile;
+use fs_atomic_versions::durability::Durability;
 use fs_metadata::store_metadata;
 use fs_properties::load_raw_properties;
 use fs_properties::store_properties;
 use fs_properties::PROPERTIES_STORAGE_FOLDER;
+use fs_storage::cache_quota::CacheQuota;
 use fs_storage::{ARK_FOLDER, PREVIEWS_STORAGE_FOLDER};
 use reqwest::header::HeaderValue;
 use scraper::{Html, Selector};
@@ -122,11 +124,15 @@ impl<Id: ResourceId> Link<Id> {
             .join(ARK_FOLDER)
             .join(PREVIEWS_STORAGE_FOLDER)
             .join(id.to_string());
-        let file = AtomicFile::new(path)?;
+        // previews can be fetched again
+        let file = AtomicFile::new(path)?.with_durability(Durability::Fast);
         let tmp = file.make_temp()?;
         (&tmp).write_all(&image_data)?;
         let current_preview = file.load()?;
         file.compare_and_swap(&current_preview, tmp)?;
+        if let Err(err) = CacheQuota::previews(root).enforce() {
+            log::warn!("Failed to evict previews: {}", err);
+        }
         Ok(())
     }
 
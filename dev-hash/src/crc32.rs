@@ -14,6 +14,9 @@ use data_resource::ResourceId;
 /// Represents a resource identifier using the CRC32 algorithm.
 ///
 /// Uses [`crc32fast`] crate to compute the hash value.
+///
+/// This is the id used by the ARK apps: tools and bindings reading the
+/// storages written by the apps must use it too.
 #[derive(
     Debug, Clone, PartialEq, Eq, Ord, PartialOrd, Hash, Serialize, Deserialize,
 )]
@@ -77,4 +80,18 @@ mod tests {
             .expect("Failed to compute resource identifier");
         assert_eq!(id, Crc32(875183434));
     }
+
+    proptest::proptest! {
+        #[test]
+        fn from_str_never_panics(s in "\\PC*") {
+            let _ = Crc32::from_str(&s);
+        }
+
+        #[test]
+        fn from_str_roundtrip(n: u32) {
+            let id = Crc32(n);
+            let parsed = Crc32::from_str(&id.to_string());
+            proptest::prop_assert_eq!(parsed, Ok(id));
+        }
+    }
 }
@@ -3,3 +3,50 @@ mod crc32;
 
 pub use blake3::Blake3;
 pub use crc32::Crc32;
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use data_resource::ResourceId;
+
+    use super::*;
+
+    #[derive(Deserialize)]
+    struct Vectors {
+        vectors: Vec<Vector>,
+    }
+
+    #[derive(Deserialize)]
+    struct Vector {
+        name: String,
+        input: String,
+        crc32: String,
+        blake3: String,
+    }
+
+    /// The same vectors are checked by the `ark-wasm` test suite,
+    /// ids must not depend on the platform they are computed on
+    #[test]
+    fn golden_vectors() {
+        let vectors =
+            std::fs::read_to_string("../test-assets/id-vectors.json").unwrap();
+        let vectors: Vectors = serde_json::from_str(&vectors).unwrap();
+
+        for vector in vectors.vectors {
+            let input = hex::decode(&vector.input).unwrap();
+            assert_eq!(
+                Crc32::from_bytes(&input).unwrap().to_string(),
+                vector.crc32,
+                "{}",
+                vector.name
+            );
+            assert_eq!(
+                Blake3::from_bytes(&input).unwrap().to_string(),
+                vector.blake3,
+                "{}",
+                vector.name
+            );
+        }
+    }
+}
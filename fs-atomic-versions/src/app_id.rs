@@ -2,13 +2,13 @@ use std::{fs, path::Path};
 
 use anyhow::anyhow;
 
-use data_error::{ArklibError, Result};
+use data_error::{ArklibError, Result, ResultExt};
 
 use crate::{APP_ID_FILE, APP_ID_PATH};
 
 fn generate<P: AsRef<Path>>(app_id_path: P) -> Result<String> {
     let id = uuid::Uuid::new_v4().to_string();
-    fs::write(app_id_path, &id)?;
+    fs::write(&app_id_path, &id).with_path(&app_id_path)?;
     Ok(id)
 }
 
@@ -18,7 +18,7 @@ pub fn read() -> Result<String> {
     })?;
 
     if let Some(app_id_path) = &*app_id_path {
-        Ok(fs::read_to_string(app_id_path)?)
+        fs::read_to_string(app_id_path).with_path(app_id_path)
     } else {
         Err(ArklibError::Other(anyhow!("Device id path is not set")))
     }
@@ -28,7 +28,7 @@ pub fn load<P: AsRef<Path>>(root_path: P) -> Result<String> {
     let app_id_path = root_path.as_ref().join(APP_ID_FILE);
 
     let id = if app_id_path.exists() {
-        fs::read_to_string(&app_id_path)?
+        fs::read_to_string(&app_id_path).with_path(&app_id_path)?
     } else {
         generate(&app_id_path)?
     };
@@ -46,7 +46,7 @@ pub fn remove() -> Result<()> {
     })?;
 
     if let Some(app_id_path) = &*app_id_path {
-        fs::remove_file(app_id_path)?;
+        fs::remove_file(app_id_path).with_path(app_id_path)?;
     }
 
     Ok(())
@@ -4,7 +4,10 @@ use std::io::{Error, ErrorKind, Read, Result};
 use std::os::unix::fs::MetadataExt;
 use std::path::{Path, PathBuf};
 
+use data_error::ResultExt;
+
 use crate::app_id;
+use crate::durability::{finish_write, sync_dir, Durability};
 
 const MAX_VERSION_FILES: usize = 10;
 
@@ -95,6 +98,8 @@ impl ReadOnlyFile {
 pub struct AtomicFile {
     pub directory: PathBuf,
     pub prefix: String,
+    /// Durability of new versions, [`Durability::Sync`] by default
+    pub durability: Durability,
 }
 
 fn parse_version(filename: Option<&str>) -> Option<usize> {
@@ -104,22 +109,46 @@ fn parse_version(filename: Option<&str>) -> Option<usize> {
 
 impl AtomicFile {
     pub fn new(path: impl Into<PathBuf>) -> data_error::Result<Self> {
-        let directory = path.into();
         // This UID must be treated as confidential information.
         // Depending on network transport used to sync the files (if any),
         // it can leak to an unauthorized party.
         let app_id = app_id::read()?;
+        Self::with_app_id(path, &app_id)
+    }
 
-        std::fs::create_dir_all(&directory)?;
-        let filename: &str = match directory.file_name() {
-            Some(name) => name.to_str().unwrap(),
+    /// Same as [`AtomicFile::new`] with the id of this app given, to read
+    /// it once with [`app_id::read`] when opening many files
+    pub fn with_app_id(
+        path: impl Into<PathBuf>,
+        app_id: &str,
+    ) -> data_error::Result<Self> {
+        let directory = path.into();
+        std::fs::create_dir_all(&directory).with_path(&directory)?;
+        let filename: &str = match directory.file_name().map(|n| n.to_str()) {
+            Some(Some(name)) => name,
+            Some(None) => Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "`path` must be valid UTF-8",
+            ))
+            .with_path(&directory)?,
             None => Err(std::io::Error::new(
                 std::io::ErrorKind::InvalidInput,
                 "`path` must specify a directory name",
-            ))?,
+            ))
+            .with_path(&directory)?,
         };
         let prefix = format!("{}_{}.", filename, app_id);
-        Ok(Self { directory, prefix })
+        Ok(Self {
+            directory,
+            prefix,
+            durability: Durability::default(),
+        })
+    }
+
+    /// Same file, writing new versions with the given durability
+    pub fn with_durability(mut self, durability: Durability) -> Self {
+        self.durability = durability;
+        self
     }
 
     /// Return the latest version together with vector of the
@@ -162,6 +191,57 @@ impl AtomicFile {
         Ok((version, files))
     }
 
+    /// Return the files of every version which wasn't pruned yet,
+    /// oldest first. Multiple files can share a version,
+    /// see [`AtomicFile::latest_version`].
+    pub fn versions(&self) -> Result<Vec<ReadOnlyFile>> {
+        let mut files: Vec<ReadOnlyFile> = fs::read_dir(&self.directory)?
+            .flatten()
+            .filter_map(|entry| {
+                let version = parse_version(entry.file_name().to_str())?;
+                Some(ReadOnlyFile {
+                    version,
+                    path: entry.path(),
+                })
+            })
+            .collect();
+        files.sort_by(|a, b| {
+            a.version
+                .cmp(&b.version)
+                .then_with(|| a.path.cmp(&b.path))
+        });
+        Ok(files)
+    }
+
+    /// Return the file of the given version, preferring the one written by
+    /// this app if several devices wrote it.
+    ///
+    /// # Errors
+    /// `io::ErrorKind::NotFound` is returned if the version was never
+    /// written or was already pruned.
+    pub fn load_version(&self, version: usize) -> Result<ReadOnlyFile> {
+        let mut files: Vec<ReadOnlyFile> = self
+            .versions()?
+            .into_iter()
+            .filter(|file| file.version == version)
+            .collect();
+        let own = files.iter().position(|file| {
+            file.path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .map_or(false, |name| name.starts_with(&self.prefix))
+        });
+        match own {
+            Some(index) => Ok(files.swap_remove(index)),
+            None => files.pop().ok_or_else(|| {
+                Error::new(
+                    ErrorKind::NotFound,
+                    format!("Version {version} not found"),
+                )
+            }),
+        }
+    }
+
     pub fn path(&self, version: usize) -> PathBuf {
         self.directory
             .join(format!("{}{version}", self.prefix))
@@ -214,10 +294,10 @@ impl AtomicFile {
     pub fn compare_and_swap(
         &self,
         current: &ReadOnlyFile,
-        new: TmpFile,
+        mut new: TmpFile,
     ) -> Result<()> {
         let new_path = self.path(current.version + 1);
-        (new.file).sync_data()?;
+        finish_write(&mut new.file, self.durability)?;
         // Just to check if current.version is still the latest_version
         let (latest_version, _) = self.latest_version()?;
         if latest_version > current.version {
@@ -244,6 +324,9 @@ impl AtomicFile {
             #[cfg(not(target_os = "unix"))]
             Err(err)?;
         }
+        if self.durability == Durability::Sync {
+            sync_dir(&self.directory)?;
+        }
 
         let number_of_removed = self.prune_old_versions(latest_version);
         log::debug!("pruned {} old files", number_of_removed);
@@ -300,6 +383,54 @@ mod tests {
         assert_eq!(version_files, MAX_VERSION_FILES);
     }
 
+    #[test]
+    fn list_and_load_versions() {
+        initialize();
+        let dir = TempDir::new("versions").unwrap();
+        let file = AtomicFile::new(dir.path()).unwrap();
+        for i in 1..=3 {
+            let temp = file.make_temp().unwrap();
+            let current = file.load().unwrap();
+            (&temp)
+                .write_all(format!("Version {}", i).as_bytes())
+                .unwrap();
+            file.compare_and_swap(&current, temp).unwrap();
+        }
+
+        let versions: Vec<usize> = file
+            .versions()
+            .unwrap()
+            .iter()
+            .map(|file| file.version)
+            .collect();
+        assert_eq!(versions, vec![1, 2, 3]);
+        assert_eq!(
+            file.load_version(2)
+                .unwrap()
+                .read_to_string()
+                .unwrap(),
+            "Version 2"
+        );
+        let err = file.load_version(4).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn creation_error_contains_path() {
+        initialize();
+        let dir = TempDir::new("creation_error").unwrap();
+        let blocker = dir.path().join("blocker");
+        fs::write(&blocker, "").unwrap();
+
+        let err = AtomicFile::new(blocker.join("file")).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains(&blocker.display().to_string()),
+            "{}",
+            err
+        );
+    }
+
     #[test]
     fn multiple_version_files() {
         initialize();
@@ -336,6 +467,30 @@ mod tests {
         assert_eq!(content, content_local);
     }
 
+    #[test]
+    fn versions_written_with_any_durability() {
+        initialize();
+        let dir = TempDir::new("durability").unwrap();
+        let file = AtomicFile::new(dir.path()).unwrap();
+        assert_eq!(file.durability, Durability::Sync);
+
+        for durability in
+            [Durability::Fast, Durability::Flush, Durability::Sync]
+        {
+            let file = file.clone().with_durability(durability);
+            let temp = file.make_temp().unwrap();
+            let current = file.load().unwrap();
+            (&temp)
+                .write_all(format!("{:?}", durability).as_bytes())
+                .unwrap();
+            file.compare_and_swap(&current, temp).unwrap();
+            assert_eq!(
+                file.load().unwrap().read_to_string().unwrap(),
+                format!("{:?}", durability)
+            );
+        }
+    }
+
     #[rstest]
     #[case(3, &[1, 3], "case_1")]
     #[case(5, &[2, 4], "case_2")]
@@ -380,4 +535,18 @@ mod tests {
             format!("Version {} on {local_peer}", versions)
         );
     }
+
+    proptest::proptest! {
+        #[test]
+        fn parse_version_never_panics(name in "\\PC*") {
+            let _ = parse_version(Some(&name));
+        }
+
+        #[test]
+        fn parse_version_reads_suffix(prefix in "[a-z_]*", version: usize) {
+            let name = format!("{}.{}", prefix, version);
+            let parsed = parse_version(Some(&name));
+            proptest::prop_assert_eq!(parsed, Some(version));
+        }
+    }
 }
@@ -5,11 +5,25 @@ use std::io::{Read, Result, Write};
 
 pub use file::AtomicFile;
 
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(
+        name = "modify",
+        skip_all,
+        fields(
+            path = %atomic_file.directory.display(),
+            version = tracing::field::Empty,
+            retries = tracing::field::Empty,
+        )
+    )
+)]
 pub fn modify(
     atomic_file: &AtomicFile,
     mut operator: impl FnMut(&[u8]) -> Vec<u8>,
 ) -> Result<()> {
     let mut buf = vec![];
+    #[cfg(feature = "tracing")]
+    let mut retries = 0u64;
     loop {
         let latest = atomic_file.load()?;
         buf.clear();
@@ -21,9 +35,19 @@ pub fn modify(
         (&tmp).write_all(&data)?;
         (&tmp).flush()?;
         match atomic_file.compare_and_swap(&latest, tmp) {
-            Ok(()) => return Ok(()),
+            Ok(()) => {
+                #[cfg(feature = "tracing")]
+                tracing::Span::current()
+                    .record("version", latest.version + 1)
+                    .record("retries", retries);
+                return Ok(());
+            }
             Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => {
-                continue
+                #[cfg(feature = "tracing")]
+                {
+                    retries += 1;
+                }
+                continue;
             }
             Err(err) => return Err(err),
         }
@@ -34,24 +58,69 @@ pub fn modify_json<T: Serialize + DeserializeOwned>(
     atomic_file: &AtomicFile,
     mut operator: impl FnMut(&mut Option<T>),
 ) -> Result<()> {
+    try_modify_json(atomic_file, |value| {
+        operator(value);
+        Ok(())
+    })
+}
+
+/// Same as [`modify_json`], but the new version is not written
+/// if `operator` fails, returning its error instead
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(
+        name = "modify_json",
+        skip_all,
+        fields(
+            path = %atomic_file.directory.display(),
+            version = tracing::field::Empty,
+            retries = tracing::field::Empty,
+        )
+    )
+)]
+pub fn try_modify_json<T, E>(
+    atomic_file: &AtomicFile,
+    mut operator: impl FnMut(&mut Option<T>) -> std::result::Result<(), E>,
+) -> std::result::Result<(), E>
+where
+    T: Serialize + DeserializeOwned,
+    E: From<std::io::Error>,
+{
+    #[cfg(feature = "tracing")]
+    let mut retries = 0u64;
     loop {
         let latest = atomic_file.load()?;
         let mut val = None;
         if let Some(file) = latest.open()? {
-            val = Some(serde_json::from_reader(std::io::BufReader::new(file))?);
+            let reader = std::io::BufReader::new(file);
+            val = Some(
+                serde_json::from_reader(reader)
+                    .map_err(std::io::Error::from)?,
+            );
         }
-        operator(&mut val);
+        operator(&mut val)?;
         let tmp = atomic_file.make_temp()?;
         let mut writer = std::io::BufWriter::new(&tmp);
-        serde_json::to_writer(&mut writer, &val)?;
+        serde_json::to_writer(&mut writer, &val)
+            .map_err(std::io::Error::from)?;
         writer.flush()?;
         drop(writer);
         match atomic_file.compare_and_swap(&latest, tmp) {
-            Ok(()) => return Ok(()),
+            Ok(()) => {
+                #[cfg(feature = "tracing")]
+                tracing::Span::current()
+                    .record("version", latest.version + 1)
+                    .record("retries", retries);
+                return Ok(());
+            }
             Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => {
-                continue
+                #[cfg(feature = "tracing")]
+                {
+                    retries += 1;
+                }
+                continue;
             }
-            Err(err) => return Err(err),
+            Err(err) => return Err(err.into()),
         }
     }
 }
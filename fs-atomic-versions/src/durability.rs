@@ -0,0 +1,132 @@
+//! How far a write must go before it is reported as successful.
+
+use std::fs::File;
+use std::io::{Result, Write};
+use std::path::Path;
+
+/// Durability of a write, trading speed for safety on power loss.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Durability {
+    /// Leave the data to the buffers of the writer and the OS,
+    /// for data which can be regenerated like caches
+    Fast,
+    /// Flush the buffers of the writer to the OS,
+    /// the data survives a crash of the app but not of the system
+    Flush,
+    /// Wait until the data and the directory entry reach the disk,
+    /// for user data like tags and scores
+    #[default]
+    Sync,
+}
+
+/// Writer whose content can be synced to the disk
+pub trait DurableWrite: Write {
+    fn sync_all(&self) -> Result<()>;
+}
+
+impl DurableWrite for File {
+    fn sync_all(&self) -> Result<()> {
+        File::sync_all(self)
+    }
+}
+
+/// Complete a write with the given durability.
+///
+/// Durability of the directory entry is handled by [`sync_dir`].
+pub fn finish_write<W: DurableWrite + ?Sized>(
+    writer: &mut W,
+    durability: Durability,
+) -> Result<()> {
+    match durability {
+        Durability::Fast => Ok(()),
+        Durability::Flush => writer.flush(),
+        Durability::Sync => {
+            writer.flush()?;
+            writer.sync_all()
+        }
+    }
+}
+
+/// Persist the entries of the directory `dir`, so that a file
+/// created, renamed or linked into it survives a power loss
+pub fn sync_dir(dir: &Path) -> Result<()> {
+    #[cfg(unix)]
+    {
+        // parent of a relative file name
+        let dir = if dir.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            dir
+        };
+        File::open(dir)?.sync_all()
+    }
+    // directories can't be opened as files on Windows,
+    // and their entries are written through
+    #[cfg(not(unix))]
+    {
+        let _ = dir;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    #[derive(Default)]
+    struct MockWriter {
+        content: Vec<u8>,
+        flushed: usize,
+        // `sync_all` takes `&self` like `File::sync_all`
+        synced: Cell<usize>,
+    }
+
+    impl Write for MockWriter {
+        fn write(&mut self, buf: &[u8]) -> Result<usize> {
+            self.content.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> Result<()> {
+            self.flushed += 1;
+            Ok(())
+        }
+    }
+
+    impl DurableWrite for MockWriter {
+        fn sync_all(&self) -> Result<()> {
+            self.synced.set(self.synced.get() + 1);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn durability_levels() {
+        for (durability, flushed, synced) in [
+            (Durability::Fast, 0, 0),
+            (Durability::Flush, 1, 0),
+            (Durability::Sync, 1, 1),
+        ] {
+            let mut writer = MockWriter::default();
+            writer.write_all(b"data").unwrap();
+            finish_write(&mut writer, durability).unwrap();
+            assert_eq!(writer.content, b"data");
+            assert_eq!(
+                (writer.flushed, writer.synced.get()),
+                (flushed, synced),
+                "{:?}",
+                durability
+            );
+        }
+    }
+
+    #[test]
+    fn sync_dir_opens_the_directory() {
+        let dir = tempdir::TempDir::new("sync_dir").unwrap();
+        sync_dir(dir.path()).unwrap();
+        if cfg!(unix) {
+            assert!(sync_dir(&dir.path().join("missing")).is_err());
+        }
+    }
+}
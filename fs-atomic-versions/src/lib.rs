@@ -5,6 +5,7 @@ use std::sync::RwLock;
 
 pub mod app_id;
 pub mod atomic;
+pub mod durability;
 
 pub static INIT: Once = Once::new();
 
@@ -0,0 +1,251 @@
+use std::cmp::Ordering;
+use std::path::Path;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use data_error::{ArklibError, Result};
+use data_resource::ResourceId;
+use fs_storage::base_storage::BaseStorage;
+use fs_storage::file_storage::FileStorage;
+use fs_storage::monoid::Monoid;
+use fs_storage::{now_millis, ARK_FOLDER, FAVORITES_FILE};
+
+/// Smallest gap between two positions before they are renumbered
+const MIN_GAP: f64 = 1e-9;
+
+/// Position of a favorite in the list.
+///
+/// Positions are fractional, so that moving a favorite changes only its
+/// own position. When two devices move the same favorite, the latest
+/// move wins, and every device ends up with the same order.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Pin {
+    pub position: f64,
+    /// Milliseconds since the Unix epoch
+    pub timestamp: u64,
+}
+
+impl Pin {
+    /// Pin at `position`, set now
+    pub fn new(position: f64) -> Self {
+        Pin {
+            position,
+            timestamp: now_millis(),
+        }
+    }
+}
+
+impl FromStr for Pin {
+    type Err = serde_json::Error;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        serde_json::from_str(s)
+    }
+}
+
+// Pins set at the same time are ordered by their lowest position
+impl Monoid<Pin> for Pin {
+    fn neutral() -> Pin {
+        Pin {
+            position: 0.0,
+            timestamp: 0,
+        }
+    }
+
+    fn combine(a: &Pin, b: &Pin) -> Pin {
+        match a.timestamp.cmp(&b.timestamp) {
+            Ordering::Greater => *a,
+            Ordering::Less => *b,
+            Ordering::Equal if a.position <= b.position => *a,
+            Ordering::Equal => *b,
+        }
+    }
+}
+
+/// Ordered list of favorite resources of a root, kept in `.ark/favorites`.
+///
+/// Changes are written to disk immediately, changes made by other
+/// processes or devices are read with [`Favorites::sync`].
+pub struct Favorites<Id: ResourceId> {
+    storage: FileStorage<Id, Pin>,
+}
+
+impl<Id: ResourceId> Favorites<Id> {
+    /// Open the favorites of `root`, reading them if they exist
+    pub fn new<P: AsRef<Path>>(root: P) -> Result<Self> {
+        let path = root
+            .as_ref()
+            .join(ARK_FOLDER)
+            .join(FAVORITES_FILE);
+        let storage = FileStorage::new("favorites".to_owned(), &path)?;
+        Ok(Favorites { storage })
+    }
+
+    /// Favorites, in order
+    pub fn list(&self) -> Vec<Id> {
+        self.pins()
+            .into_iter()
+            .map(|(id, _)| id)
+            .collect()
+    }
+
+    pub fn is_pinned(&self, id: &Id) -> bool {
+        self.storage.contains_key(id)
+    }
+
+    /// Add a resource at the end of the favorites. Returns `false` if it
+    /// is pinned already.
+    pub fn pin(&mut self, id: Id) -> Result<bool> {
+        if self.is_pinned(&id) {
+            return Ok(false);
+        }
+        let last = self
+            .pins()
+            .last()
+            .map_or(0.0, |(_, position)| *position);
+        self.storage.set(id, Pin::new(last + 1.0));
+        self.storage.write_fs()?;
+        Ok(true)
+    }
+
+    /// Remove a resource from the favorites. Returns `false` if it
+    /// wasn't pinned.
+    pub fn unpin(&mut self, id: &Id) -> Result<bool> {
+        if !self.is_pinned(id) {
+            return Ok(false);
+        }
+        self.storage.remove(id)?;
+        self.storage.write_fs()?;
+        Ok(true)
+    }
+
+    /// Move a favorite to `index` of the list, or to its end if `index`
+    /// is past it
+    pub fn reorder(&mut self, id: &Id, index: usize) -> Result<()> {
+        if !self.is_pinned(id) {
+            return Err(ArklibError::Storage(
+                "favorites".to_owned(),
+                format!("{} is not pinned", id),
+            ));
+        }
+        let others: Vec<(Id, f64)> = self
+            .pins()
+            .into_iter()
+            .filter(|(other, _)| other != id)
+            .collect();
+        let index = index.min(others.len());
+        let before = index.checked_sub(1).map(|i| others[i].1);
+        let after = others.get(index).map(|(_, position)| *position);
+        let position = match (before, after) {
+            (Some(before), Some(after)) if after - before < MIN_GAP => {
+                // renumber all favorites, leaving room for the moved one
+                return self.renumber(others, index, id.clone());
+            }
+            (Some(before), Some(after)) => (before + after) / 2.0,
+            (Some(before), None) => before + 1.0,
+            (None, Some(after)) => after - 1.0,
+            (None, None) => 1.0,
+        };
+        self.storage.set(id.clone(), Pin::new(position));
+        self.storage.write_fs()
+    }
+
+    /// See [`BaseStorage::sync`]
+    pub fn sync(&mut self) -> Result<()> {
+        self.storage.sync()
+    }
+
+    /// Favorites with their positions, in order, ties in id order
+    fn pins(&self) -> Vec<(Id, f64)> {
+        let mut pins: Vec<(Id, f64)> = self
+            .storage
+            .as_ref()
+            .iter()
+            .map(|(id, pin)| (id.clone(), pin.position))
+            .collect();
+        pins.sort_by(|(_, a), (_, b)| a.total_cmp(b));
+        pins
+    }
+
+    /// Assign positions 1, 2, 3... to `others` with `id` inserted
+    /// at `index`
+    fn renumber(
+        &mut self,
+        mut others: Vec<(Id, f64)>,
+        index: usize,
+        id: Id,
+    ) -> Result<()> {
+        others.insert(index, (id, 0.0));
+        self.storage.transaction(|transaction| {
+            for (i, (id, _)) in others.into_iter().enumerate() {
+                transaction.set(id, Pin::new((i + 1) as f64));
+            }
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use dev_hash::Crc32;
+    use tempdir::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn test_pin_and_reorder() {
+        let dir = TempDir::new("arklib_test").unwrap();
+        let root = dir.path();
+        let (a, b, c) = (Crc32(1), Crc32(2), Crc32(3));
+
+        let mut favorites = Favorites::new(root).unwrap();
+        assert!(favorites.pin(c.clone()).unwrap());
+        assert!(favorites.pin(a.clone()).unwrap());
+        assert!(favorites.pin(b.clone()).unwrap());
+        assert!(!favorites.pin(a.clone()).unwrap());
+        assert_eq!(favorites.list(), [c.clone(), a.clone(), b.clone()]);
+
+        favorites.reorder(&b, 0).unwrap();
+        assert_eq!(favorites.list(), [b.clone(), c.clone(), a.clone()]);
+        favorites.reorder(&b, 1).unwrap();
+        assert_eq!(favorites.list(), [c.clone(), b.clone(), a.clone()]);
+        favorites.reorder(&c, 10).unwrap();
+        assert_eq!(favorites.list(), [b.clone(), a.clone(), c.clone()]);
+        assert!(favorites.reorder(&Crc32(4), 0).is_err());
+
+        assert!(favorites.unpin(&a).unwrap());
+        assert!(!favorites.unpin(&a).unwrap());
+        let favorites: Favorites<Crc32> = Favorites::new(root).unwrap();
+        assert_eq!(favorites.list(), [b, c]);
+    }
+
+    #[test]
+    fn test_renumber_when_positions_are_too_close() {
+        let dir = TempDir::new("arklib_test").unwrap();
+        let mut favorites = Favorites::new(dir.path()).unwrap();
+        for id in 0..3 {
+            favorites.pin(Crc32(id)).unwrap();
+        }
+        // keep moving the last favorite between the first two
+        for _ in 0..64 {
+            favorites.reorder(&Crc32(2), 1).unwrap();
+            favorites.reorder(&Crc32(1), 1).unwrap();
+        }
+        assert_eq!(favorites.list(), [Crc32(0), Crc32(1), Crc32(2)]);
+    }
+
+    #[test]
+    fn test_latest_move_wins() {
+        let early = Pin {
+            position: 1.0,
+            timestamp: 1,
+        };
+        let late = Pin {
+            position: 5.0,
+            timestamp: 2,
+        };
+        assert_eq!(Pin::combine(&early, &late), late);
+        assert_eq!(Pin::combine(&late, &early), late);
+    }
+}
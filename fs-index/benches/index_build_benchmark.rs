@@ -30,6 +30,19 @@ fn index_build_benchmark(c: &mut Criterion) {
             });
         },
     );
+
+    #[cfg(feature = "rayon")]
+    group.bench_with_input(
+        BenchmarkId::new("index_build_parallel", DIR_PATH),
+        &DIR_PATH,
+        |b, path| {
+            b.iter(|| {
+                let index: ResourceIndex<Crc32> =
+                    ResourceIndex::build_parallel(black_box(path), 0).unwrap();
+                collisions_size = index.collisions.len();
+            });
+        },
+    );
     group.finish();
 
     println!("Collisions: {}", collisions_size);
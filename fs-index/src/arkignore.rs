@@ -0,0 +1,102 @@
+//! Paths excluded from the index by the `.arkignore` file of the root.
+//!
+//! The file uses the gitignore syntax, e.g.
+//!
+//! ```text
+//! node_modules/
+//! *.tmp
+//! !keep.tmp
+//! ```
+
+use std::path::{Path, PathBuf};
+
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+
+pub const ARKIGNORE_FILE: &str = ".arkignore";
+
+/// Rules of the `.arkignore` file of a root,
+/// matching nothing if there is no such file
+pub(crate) struct IgnoreRules {
+    matcher: Gitignore,
+    root: PathBuf,
+    canonical_root: Option<PathBuf>,
+}
+
+impl IgnoreRules {
+    pub(crate) fn load(root: &Path) -> Self {
+        let file = root.join(ARKIGNORE_FILE);
+        let mut builder = GitignoreBuilder::new(root);
+        if file.is_file() {
+            // invalid lines are skipped, the other rules still apply
+            if let Some(err) = builder.add(&file) {
+                log::warn!("Invalid rule in {}: {}", file.display(), err);
+            }
+        }
+        let matcher = builder.build().unwrap_or_else(|err| {
+            log::warn!("Couldn't load {}: {}", file.display(), err);
+            Gitignore::empty()
+        });
+
+        IgnoreRules {
+            matcher,
+            root: root.to_owned(),
+            canonical_root: root.canonicalize().ok(),
+        }
+    }
+
+    /// Whether `path`, located under the root, or one of its parents
+    /// is excluded from the index
+    pub(crate) fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        if self.matcher.is_empty() {
+            return false;
+        }
+        let relative = path.strip_prefix(&self.root).ok().or_else(|| {
+            self.canonical_root
+                .as_ref()
+                .and_then(|root| path.strip_prefix(root).ok())
+        });
+        match relative {
+            Some(relative) if relative.as_os_str().is_empty() => false,
+            Some(relative) => self
+                .matcher
+                .matched_path_or_any_parents(relative, is_dir)
+                .is_ignore(),
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempdir::TempDir;
+
+    #[test]
+    fn gitignore_rules_are_applied() {
+        let dir = TempDir::new("arkignore").unwrap();
+        let root = dir.path();
+        fs::write(
+            root.join(ARKIGNORE_FILE),
+            "node_modules/\n*.tmp\n!keep.tmp\n/build\n",
+        )
+        .unwrap();
+        let rules = IgnoreRules::load(root);
+
+        assert!(rules.is_ignored(&root.join("node_modules"), true));
+        assert!(rules.is_ignored(&root.join("app/node_modules/a.js"), false));
+        assert!(rules.is_ignored(&root.join("notes.tmp"), false));
+        assert!(rules.is_ignored(&root.join("build"), true));
+        assert!(!rules.is_ignored(&root.join("keep.tmp"), false));
+        assert!(!rules.is_ignored(&root.join("src/build"), true));
+        assert!(!rules.is_ignored(&root.join("notes.txt"), false));
+        assert!(!rules.is_ignored(root, true));
+    }
+
+    #[test]
+    fn missing_file_ignores_nothing() {
+        let dir = TempDir::new("arkignore").unwrap();
+        let rules = IgnoreRules::load(dir.path());
+        assert!(!rules.is_ignored(&dir.path().join("a.tmp"), false));
+    }
+}
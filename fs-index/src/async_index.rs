@@ -0,0 +1,134 @@
+//! Index usable from async code without blocking the runtime.
+//!
+//! Walking and hashing run on the blocking thread pool of tokio,
+//! while lookups only wait for the lock of the index.
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::anyhow;
+use tokio::sync::{RwLock, RwLockReadGuard};
+use tokio::task::{spawn_blocking, JoinError};
+
+use data_error::{ArklibError, Result};
+use data_resource::ResourceId;
+
+use crate::index::{IndexUpdate, ResourceIndex};
+
+/// Shared [`ResourceIndex`] with async operations,
+/// clones refer to the same index
+pub struct AsyncIndex<Id: ResourceId> {
+    index: Arc<RwLock<ResourceIndex<Id>>>,
+}
+
+impl<Id: ResourceId> Clone for AsyncIndex<Id> {
+    fn clone(&self) -> Self {
+        AsyncIndex {
+            index: self.index.clone(),
+        }
+    }
+}
+
+impl<Id: ResourceId + Send + Sync + 'static> AsyncIndex<Id> {
+    pub fn new(index: ResourceIndex<Id>) -> Self {
+        AsyncIndex {
+            index: Arc::new(RwLock::new(index)),
+        }
+    }
+
+    /// See [`ResourceIndex::build`]
+    pub async fn build<P: AsRef<Path>>(root_path: P) -> Result<Self> {
+        let root_path: PathBuf = root_path.as_ref().to_owned();
+        let index = spawn_blocking(move || ResourceIndex::build(root_path))
+            .await
+            .map_err(join_error)?;
+        Ok(Self::new(index))
+    }
+
+    /// See [`ResourceIndex::provide`]
+    pub async fn provide<P: AsRef<Path>>(root_path: P) -> Result<Self> {
+        let root_path: PathBuf = root_path.as_ref().to_owned();
+        let index = spawn_blocking(move || ResourceIndex::provide(root_path))
+            .await
+            .map_err(join_error)??;
+        Ok(Self::new(index))
+    }
+
+    /// Current state of the index, updates wait until the guard is dropped
+    pub async fn read(&self) -> RwLockReadGuard<'_, ResourceIndex<Id>> {
+        self.index.read().await
+    }
+
+    /// See [`ResourceIndex::update_all`], lookups wait for the update
+    pub async fn update_all(&self) -> Result<IndexUpdate<Id>> {
+        let index = self.index.clone();
+        spawn_blocking(move || index.blocking_write().update_all())
+            .await
+            .map_err(join_error)?
+    }
+
+    /// See [`ResourceIndex::store`]
+    pub async fn store(&self) -> Result<()> {
+        let index = self.index.clone();
+        spawn_blocking(move || index.blocking_read().store())
+            .await
+            .map_err(join_error)?
+    }
+}
+
+#[cfg(feature = "watch")]
+impl<Id: ResourceId + Send + 'static> ResourceIndex<Id> {
+    /// Same as [`ResourceIndex::watch`], with the updates
+    /// sent to a tokio channel
+    pub fn watch_async(
+        self,
+    ) -> Result<(
+        crate::watch::IndexWatcher<Id>,
+        tokio::sync::mpsc::UnboundedReceiver<IndexUpdate<Id>>,
+    )> {
+        let (watcher, updates) = self.watch()?;
+        let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+        // ends when the watcher is stopped or the receiver is dropped
+        std::thread::spawn(move || {
+            while let Ok(update) = updates.recv() {
+                if tx.send(update).is_err() {
+                    break;
+                }
+            }
+        });
+        Ok((watcher, rx))
+    }
+}
+
+fn join_error(err: JoinError) -> ArklibError {
+    ArklibError::Other(anyhow!("Indexing task failed: {}", err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dev_hash::Crc32;
+    use std::fs;
+    use tempdir::TempDir;
+
+    #[tokio::test]
+    async fn async_index_builds_and_updates() {
+        let dir = TempDir::new("async_index").unwrap();
+        let root = dir.path();
+        fs::write(root.join("a.txt"), "a").unwrap();
+
+        let index = AsyncIndex::<Crc32>::build(root).await.unwrap();
+        assert_eq!(index.read().await.size(), 1);
+
+        fs::write(root.join("b.txt"), "b").unwrap();
+        // clones share the index
+        let shared = index.clone();
+        let update = shared.update_all().await.unwrap();
+        assert_eq!(update.added.len(), 1);
+        assert_eq!(index.read().await.size(), 2);
+
+        index.store().await.unwrap();
+        let provided = AsyncIndex::<Crc32>::provide(root).await.unwrap();
+        assert_eq!(*provided.read().await, *index.read().await);
+    }
+}
@@ -0,0 +1,246 @@
+//! Writing the index to `.ark/index` after it changes, see
+//! [`ResourceIndex::set_auto_store`](crate::ResourceIndex::set_auto_store).
+//!
+//! The index hands the content to write to a background thread after
+//! every change. The thread writes it right away if the index wasn't
+//! stored during the last debounce, otherwise once the debounce has
+//! elapsed, so the last changes are stored even if nothing changes
+//! afterwards.
+
+use std::path::PathBuf;
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use data_error::Result;
+
+use crate::{hash_cache, index};
+
+/// Content of the stored index and hash cache of a root
+pub(crate) struct Snapshot {
+    pub(crate) root: PathBuf,
+    pub(crate) index: Vec<u8>,
+    pub(crate) hashes: Vec<u8>,
+}
+
+impl Snapshot {
+    pub(crate) fn write(&self) -> Result<()> {
+        index::write_index_file(&self.root, &self.index)?;
+        hash_cache::write(&self.root, &self.hashes)
+    }
+}
+
+enum Message {
+    Changed(Snapshot),
+    Flush(Sender<Result<()>>),
+}
+
+/// Whether the index has changes to store and the thread storing them
+#[derive(Default, Debug)]
+pub(crate) struct AutoStore {
+    writer: Option<(Sender<Message>, JoinHandle<()>)>,
+    modified: bool,
+}
+
+// copies of the index must not write over the original
+impl Clone for AutoStore {
+    fn clone(&self) -> Self {
+        AutoStore::default()
+    }
+}
+
+// the last changes are written before the index is gone
+impl Drop for AutoStore {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+impl AutoStore {
+    pub(crate) fn set(&mut self, debounce: Option<Duration>) {
+        self.stop();
+        self.modified = false;
+        if let Some(debounce) = debounce {
+            let (sender, receiver) = mpsc::channel();
+            let thread = thread::spawn(move || {
+                let mut writer = Writer {
+                    debounce,
+                    pending: None,
+                    last_stored: None,
+                };
+                writer.run(receiver)
+            });
+            self.writer = Some((sender, thread));
+        }
+    }
+
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.writer.is_some()
+    }
+
+    pub(crate) fn changed(&mut self) {
+        self.modified = self.is_enabled();
+    }
+
+    /// Whether the index was modified since the last snapshot,
+    /// which is then expected to be stored
+    pub(crate) fn take_modified(&mut self) -> bool {
+        std::mem::take(&mut self.modified)
+    }
+
+    pub(crate) fn store(&mut self, snapshot: Snapshot) {
+        if let Some((sender, _)) = &self.writer {
+            // the thread only stops when the sender is dropped
+            let _ = sender.send(Message::Changed(snapshot));
+        }
+    }
+
+    /// Write the last snapshot now if it isn't stored yet
+    pub(crate) fn flush(&self) -> Result<()> {
+        let Some((sender, _)) = &self.writer else {
+            return Ok(());
+        };
+        let (done, result) = mpsc::channel();
+        let _ = sender.send(Message::Flush(done));
+        result.recv().unwrap_or(Ok(()))
+    }
+
+    fn stop(&mut self) {
+        if let Some((sender, thread)) = self.writer.take() {
+            drop(sender);
+            if thread.join().is_err() {
+                log::error!("The thread storing the index panicked");
+            }
+        }
+    }
+}
+
+struct Writer {
+    debounce: Duration,
+    pending: Option<Snapshot>,
+    last_stored: Option<Instant>,
+}
+
+impl Writer {
+    fn run(&mut self, receiver: mpsc::Receiver<Message>) {
+        loop {
+            let message = match (&self.pending, self.last_stored) {
+                (None, _) => receiver
+                    .recv()
+                    .map_err(|_| RecvTimeoutError::Disconnected),
+                (Some(_), None) => Err(RecvTimeoutError::Timeout),
+                (Some(_), Some(last)) => receiver
+                    .recv_timeout(self.debounce.saturating_sub(last.elapsed())),
+            };
+            match message {
+                Ok(Message::Changed(snapshot)) => self.pending = Some(snapshot),
+                Ok(Message::Flush(done)) => {
+                    let _ = done.send(self.write());
+                }
+                Err(RecvTimeoutError::Timeout) => {
+                    if let Err(err) = self.write() {
+                        log::error!("Failed to store the index: {}", err);
+                    }
+                }
+                Err(RecvTimeoutError::Disconnected) => {
+                    if let Err(err) = self.write() {
+                        log::error!("Failed to store the index: {}", err);
+                    }
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Write the pending snapshot, which is kept to be retried after
+    /// the debounce if writing fails
+    fn write(&mut self) -> Result<()> {
+        let Some(snapshot) = &self.pending else {
+            return Ok(());
+        };
+        let written = snapshot.write();
+        self.last_stored = Some(Instant::now());
+        if written.is_ok() {
+            self.pending = None;
+        }
+        written
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ResourceIndex;
+    use dev_hash::Crc32;
+    use std::fs;
+    use std::thread;
+    use std::time::{Duration, Instant};
+    use tempdir::TempDir;
+
+    fn stored(root: &std::path::Path) -> usize {
+        ResourceIndex::<Crc32>::load(root)
+            .map(|index| index.size())
+            .unwrap_or(0)
+    }
+
+    /// Wait for the index stored under `root` to have `size` entries
+    fn wait_stored(root: &std::path::Path, size: usize) -> bool {
+        let start = Instant::now();
+        while start.elapsed() < Duration::from_secs(5) {
+            if stored(root) == size {
+                return true;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        false
+    }
+
+    #[test]
+    fn changes_are_stored_after_debounce() {
+        let dir = TempDir::new("autostore").unwrap();
+        let root = dir.path();
+        fs::write(root.join("a.txt"), "a").unwrap();
+
+        let mut index = ResourceIndex::<Crc32>::build(root);
+        index.set_auto_store(Some(Duration::from_millis(200)));
+        index.update_all().unwrap();
+        // nothing changed yet
+        index.flush().unwrap();
+        assert_eq!(stored(root), 0);
+
+        fs::write(root.join("b.txt"), "b").unwrap();
+        index.update_all().unwrap();
+        assert!(wait_stored(root, 2));
+
+        // the last change is stored once the debounce has elapsed,
+        // without any further call
+        fs::write(root.join("c.txt"), "c").unwrap();
+        index.update_all().unwrap();
+        assert!(wait_stored(root, 3));
+    }
+
+    #[test]
+    fn pending_changes_are_flushed() {
+        let dir = TempDir::new("autostore").unwrap();
+        let root = dir.path();
+        fs::write(root.join("a.txt"), "a").unwrap();
+
+        let mut index = ResourceIndex::<Crc32>::build(root);
+        index.set_auto_store(Some(Duration::from_secs(3600)));
+        fs::write(root.join("b.txt"), "b").unwrap();
+        index.update_all().unwrap();
+        assert!(wait_stored(root, 2));
+
+        // the next changes wait for the debounce
+        fs::write(root.join("c.txt"), "c").unwrap();
+        index.update_all().unwrap();
+        assert_eq!(stored(root), 2);
+        index.flush().unwrap();
+        assert_eq!(stored(root), 3);
+
+        fs::write(root.join("d.txt"), "d").unwrap();
+        index.update_all().unwrap();
+        assert_eq!(stored(root), 3);
+        drop(index);
+        assert_eq!(stored(root), 4);
+    }
+}
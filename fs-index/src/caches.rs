@@ -0,0 +1,207 @@
+//! Removing the cached data of resources which are gone, see
+//! [`ResourceIndex::set_cache_invalidation`](crate::ResourceIndex::set_cache_invalidation).
+
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+
+use data_error::{Result, ResultExt};
+use data_resource::ResourceId;
+use fs_storage::{
+    ARK_FOLDER, METADATA_STORAGE_FOLDER, PREVIEWS_STORAGE_FOLDER,
+    TEXT_STORAGE_FOLDER, THUMBNAILS_STORAGE_FOLDER,
+};
+
+/// Folders of `.ark` holding data generated from the content of resources,
+/// with an entry named after the id of every resource
+pub const CACHE_FOLDERS: &[&str] = &[
+    PREVIEWS_STORAGE_FOLDER,
+    THUMBNAILS_STORAGE_FOLDER,
+    METADATA_STORAGE_FOLDER,
+    TEXT_STORAGE_FOLDER,
+];
+
+/// Ids which left the index since the caches were last invalidated,
+/// recorded only while invalidation is enabled
+#[derive(Debug)]
+pub(crate) struct CacheInvalidation<Id: ResourceId> {
+    enabled: bool,
+    removed: Vec<Id>,
+}
+
+impl<Id: ResourceId> Default for CacheInvalidation<Id> {
+    fn default() -> Self {
+        CacheInvalidation {
+            enabled: false,
+            removed: Vec::new(),
+        }
+    }
+}
+
+// copies of the index must not remove the caches of the original
+impl<Id: ResourceId> Clone for CacheInvalidation<Id> {
+    fn clone(&self) -> Self {
+        CacheInvalidation::default()
+    }
+}
+
+impl<Id: ResourceId> CacheInvalidation<Id> {
+    pub(crate) fn set(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        if !enabled {
+            self.removed.clear();
+        }
+    }
+
+    pub(crate) fn removed(&mut self, id: &Id) {
+        if self.enabled {
+            self.removed.push(id.clone());
+        }
+    }
+
+    /// Remove the cached data of the recorded ids
+    /// for which `indexed` is false
+    pub(crate) fn invalidate(
+        &mut self,
+        root: &Path,
+        indexed: impl Fn(&Id) -> bool,
+    ) {
+        let mut removed = std::mem::take(&mut self.removed);
+        removed.sort();
+        removed.dedup();
+        for id in removed.iter().filter(|id| !indexed(id)) {
+            if let Err(err) = invalidate(root, id) {
+                log::warn!(
+                    "Failed to invalidate the caches of {}: {}",
+                    id,
+                    err
+                );
+            }
+        }
+    }
+}
+
+/// Remove the cached data of the resource from every cache folder
+pub fn invalidate<Id: ResourceId>(root: &Path, id: &Id) -> Result<()> {
+    for folder in CACHE_FOLDERS {
+        let path = root
+            .join(ARK_FOLDER)
+            .join(folder)
+            .join(id.to_string());
+        remove_entry(&path)?;
+    }
+    Ok(())
+}
+
+/// Remove the entries of the cache folders for which `indexed` is false,
+/// returning their number. Entries not named after an id are left alone.
+pub(crate) fn collect_garbage<Id: ResourceId>(
+    root: &Path,
+    indexed: impl Fn(&Id) -> bool,
+) -> Result<usize> {
+    let mut removed = 0;
+    for folder in CACHE_FOLDERS {
+        let folder = root.join(ARK_FOLDER).join(folder);
+        let entries = match fs::read_dir(&folder) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == ErrorKind::NotFound => continue,
+            Err(err) => return Err(err).with_path(&folder),
+        };
+        for entry in entries {
+            let entry = entry.with_path(&folder)?;
+            let Some(Ok(id)) = entry.file_name().to_str().map(str::parse::<Id>)
+            else {
+                continue;
+            };
+            if !indexed(&id) {
+                remove_entry(&entry.path())?;
+                removed += 1;
+            }
+        }
+    }
+    Ok(removed)
+}
+
+fn remove_entry(path: &Path) -> Result<()> {
+    let result = if path.is_dir() {
+        fs::remove_dir_all(path)
+    } else {
+        fs::remove_file(path)
+    };
+    match result {
+        Err(err) if err.kind() != ErrorKind::NotFound => {
+            Err(err).with_path(path)
+        }
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ResourceIndex;
+    use dev_hash::Crc32;
+    use fs_storage::{
+        ARK_FOLDER, METADATA_STORAGE_FOLDER, PREVIEWS_STORAGE_FOLDER,
+        THUMBNAILS_STORAGE_FOLDER,
+    };
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use tempdir::TempDir;
+
+    fn cached(root: &Path, folder: &str, id: &Crc32) -> PathBuf {
+        let path = root
+            .join(ARK_FOLDER)
+            .join(folder)
+            .join(id.to_string());
+        fs::create_dir_all(&path).unwrap();
+        fs::write(path.join("data"), "cached").unwrap();
+        path
+    }
+
+    #[test]
+    fn caches_of_modified_and_removed_resources_are_invalidated() {
+        let dir = TempDir::new("caches").unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        fs::write(root.join("a.txt"), "a").unwrap();
+        fs::write(root.join("b.txt"), "b").unwrap();
+        fs::write(root.join("c.txt"), "c").unwrap();
+        fs::write(root.join("copy.txt"), "c").unwrap();
+        let id = |content: &str| Crc32::from_bytes(content.as_bytes()).unwrap();
+
+        let mut index = ResourceIndex::<Crc32>::build(&root);
+        index.set_cache_invalidation(true);
+        let preview = cached(&root, PREVIEWS_STORAGE_FOLDER, &id("a"));
+        let thumbnail = cached(&root, THUMBNAILS_STORAGE_FOLDER, &id("b"));
+        let metadata = cached(&root, METADATA_STORAGE_FOLDER, &id("c"));
+
+        fs::write(root.join("a.txt"), "modified").unwrap();
+        fs::remove_file(root.join("b.txt")).unwrap();
+        // the content is still indexed at another path
+        fs::remove_file(root.join("c.txt")).unwrap();
+        index.update_all().unwrap();
+
+        assert!(!preview.exists());
+        assert!(!thumbnail.exists());
+        assert!(metadata.exists());
+    }
+
+    #[test]
+    fn garbage_is_collected() {
+        let dir = TempDir::new("caches").unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        fs::write(root.join("a.txt"), "a").unwrap();
+        let id = |content: &str| Crc32::from_bytes(content.as_bytes()).unwrap();
+
+        let index = ResourceIndex::<Crc32>::build(&root);
+        let kept = cached(&root, PREVIEWS_STORAGE_FOLDER, &id("a"));
+        let stale = cached(&root, PREVIEWS_STORAGE_FOLDER, &id("gone"));
+        let unrelated = cached(&root, METADATA_STORAGE_FOLDER, &id("a"))
+            .with_file_name("not-an-id");
+        fs::create_dir_all(&unrelated).unwrap();
+
+        assert_eq!(index.collect_cache_garbage().unwrap(), 1);
+        assert!(kept.exists());
+        assert!(!stale.exists());
+        assert!(unrelated.exists());
+    }
+}
@@ -0,0 +1,169 @@
+//! Resources sharing an id.
+//!
+//! Ids like CRC32 are not cryptographic, so files with different content
+//! can end up with the same id. The index counts such paths in
+//! [`ResourceIndex::collisions`] and maps the id to only one of them,
+//! the functions below tell true duplicates from hash collisions.
+
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+
+use canonical_path::CanonicalPathBuf;
+
+use data_error::Result;
+use data_resource::ResourceId;
+
+use crate::ResourceIndex;
+
+/// Paths sharing an id, grouped by identical content
+#[derive(PartialEq, Clone, Debug)]
+pub struct Collision<Id: ResourceId> {
+    pub id: Id,
+    /// Paths with the same bytes, sorted
+    pub groups: Vec<Vec<CanonicalPathBuf>>,
+}
+
+impl<Id: ResourceId> Collision<Id> {
+    /// Whether all the paths are copies of the same content
+    pub fn is_duplicate(&self) -> bool {
+        self.groups.len() <= 1
+    }
+}
+
+impl<Id: ResourceId> ResourceIndex<Id> {
+    /// All paths indexed with `id`, sorted
+    pub fn paths_of(&self, id: &Id) -> Vec<&CanonicalPathBuf> {
+        if !self.collisions.contains_key(id) {
+            return self.id2path.get(id).into_iter().collect();
+        }
+        self.iter_entries()
+            .filter(|resource| &resource.id == id)
+            .map(|resource| &resource.path)
+            .collect()
+    }
+
+    /// Ids indexed by more than one path
+    pub fn colliding_ids(&self) -> impl Iterator<Item = &Id> + '_ {
+        self.collisions.keys()
+    }
+
+    /// Compare the bytes of the paths indexed with `id`
+    pub fn verify_collision(&self, id: &Id) -> Result<Collision<Id>> {
+        let mut groups: Vec<Vec<CanonicalPathBuf>> = Vec::new();
+        for path in self.paths_of(id) {
+            let mut group = None;
+            for (i, existing) in groups.iter().enumerate() {
+                if same_content(existing[0].as_path(), path.as_path())? {
+                    group = Some(i);
+                    break;
+                }
+            }
+            match group {
+                Some(i) => groups[i].push(path.clone()),
+                None => groups.push(vec![path.clone()]),
+            }
+        }
+
+        Ok(Collision {
+            id: id.clone(),
+            groups,
+        })
+    }
+
+    /// Verify every collision and let `resolve` pick the path
+    /// the id is mapped to in [`ResourceIndex::id2path`].
+    ///
+    /// Returning `None` or a path not sharing the id keeps the mapping.
+    pub fn resolve_collisions(
+        &mut self,
+        mut resolve: impl FnMut(&Collision<Id>) -> Option<CanonicalPathBuf>,
+    ) -> Result<()> {
+        let ids: Vec<Id> = self.colliding_ids().cloned().collect();
+        for id in ids {
+            let collision = self.verify_collision(&id)?;
+            if let Some(path) = resolve(&collision) {
+                if collision
+                    .groups
+                    .iter()
+                    .flatten()
+                    .any(|p| p == &path)
+                {
+                    self.id2path.insert(id, path);
+                } else {
+                    log::warn!("{} is not indexed with {}", path.display(), id);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn same_content(a: &Path, b: &Path) -> Result<bool> {
+    let (a, b) = (File::open(a)?, File::open(b)?);
+    if a.metadata()?.len() != b.metadata()?.len() {
+        return Ok(false);
+    }
+
+    let (mut a, mut b) = (BufReader::new(a), BufReader::new(b));
+    let mut buf_a = [0; 8192];
+    let mut buf_b = [0; 8192];
+    loop {
+        let read = a.read(&mut buf_a)?;
+        if read == 0 {
+            // sizes are equal, so both files ended
+            return Ok(true);
+        }
+        b.read_exact(&mut buf_b[..read])?;
+        if buf_a[..read] != buf_b[..read] {
+            return Ok(false);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dev_hash::Crc32;
+    use std::fs;
+    use tempdir::TempDir;
+
+    // "plumless" and "buckeroo" share the same CRC32
+    const COLLIDING: (&str, &str) = ("plumless", "buckeroo");
+
+    #[test]
+    fn duplicates_and_collisions_are_told_apart() {
+        let dir = TempDir::new("collisions").unwrap();
+        let root = dir.path();
+        fs::write(root.join("a.txt"), COLLIDING.0).unwrap();
+        fs::write(root.join("copy.txt"), COLLIDING.0).unwrap();
+        fs::write(root.join("other.txt"), COLLIDING.1).unwrap();
+        fs::write(root.join("single.txt"), "single").unwrap();
+
+        let mut index = ResourceIndex::<Crc32>::build(root);
+        let id = Crc32::from_bytes(COLLIDING.0.as_bytes()).unwrap();
+        assert_eq!(id, Crc32::from_bytes(COLLIDING.1.as_bytes()).unwrap());
+        assert_eq!(index.colliding_ids().collect::<Vec<_>>(), [&id]);
+        assert_eq!(index.paths_of(&id).len(), 3);
+
+        let collision = index.verify_collision(&id).unwrap();
+        assert!(!collision.is_duplicate());
+        assert_eq!(collision.groups.len(), 2);
+        assert_eq!(collision.groups[0].len(), 2);
+
+        let other = root.join("other.txt").canonicalize().unwrap();
+        index
+            .resolve_collisions(|collision| {
+                collision.groups.last()?.first().cloned()
+            })
+            .unwrap();
+        assert_eq!(index.id2path[&id].as_path(), other);
+
+        let single = Crc32::from_bytes(b"single").unwrap();
+        assert_eq!(index.paths_of(&single).len(), 1);
+        assert!(index
+            .verify_collision(&single)
+            .unwrap()
+            .is_duplicate());
+    }
+}
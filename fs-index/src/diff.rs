@@ -0,0 +1,174 @@
+//! Changes between two snapshots of an index.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use canonical_path::CanonicalPathBuf;
+use data_resource::ResourceId;
+
+use crate::ResourceIndex;
+
+/// Resources which differ between two snapshots of an index,
+/// sorted by path.
+///
+/// A file whose content changed is both removed with its old id
+/// and added with its new id.
+#[derive(PartialEq, Debug)]
+pub struct IndexDiff<Id: ResourceId> {
+    pub added: Vec<(Id, CanonicalPathBuf)>,
+    pub removed: Vec<(Id, CanonicalPathBuf)>,
+    /// Id with the old path and the new path
+    pub moved: Vec<(Id, CanonicalPathBuf, CanonicalPathBuf)>,
+}
+
+impl<Id: ResourceId> IndexDiff<Id> {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.removed.is_empty()
+            && self.moved.is_empty()
+    }
+}
+
+impl<Id: ResourceId> ResourceIndex<Id> {
+    /// Changes from this snapshot to `other`, e.g. from the index
+    /// stored when an app was closed to the current one.
+    ///
+    /// Resources are matched by id, so copies of a file are also
+    /// tracked: when a copy disappears from one path and appears at
+    /// another, it is reported as moved.
+    pub fn diff(&self, other: &ResourceIndex<Id>) -> IndexDiff<Id> {
+        let before = paths_by_id(self);
+        let after = paths_by_id(other);
+        let empty = BTreeSet::new();
+
+        let mut diff = IndexDiff {
+            added: Vec::new(),
+            removed: Vec::new(),
+            moved: Vec::new(),
+        };
+
+        let ids: BTreeSet<&Id> = before.keys().chain(after.keys()).collect();
+        for id in ids {
+            let old = before.get(id).unwrap_or(&empty);
+            let new = after.get(id).unwrap_or(&empty);
+            let mut gone = old.difference(new);
+            let mut appeared = new.difference(old);
+
+            loop {
+                match (gone.next(), appeared.next()) {
+                    (Some(from), Some(to)) => diff.moved.push((
+                        id.clone(),
+                        (*from).clone(),
+                        (*to).clone(),
+                    )),
+                    (Some(from), None) => {
+                        diff.removed.push((id.clone(), (*from).clone()))
+                    }
+                    (None, Some(to)) => {
+                        diff.added.push((id.clone(), (*to).clone()))
+                    }
+                    (None, None) => break,
+                }
+            }
+        }
+
+        diff.added.sort_by(|(_, a), (_, b)| a.cmp(b));
+        diff.removed.sort_by(|(_, a), (_, b)| a.cmp(b));
+        diff.moved
+            .sort_by(|(_, _, a), (_, _, b)| a.cmp(b));
+        diff
+    }
+}
+
+fn paths_by_id<Id: ResourceId>(
+    index: &ResourceIndex<Id>,
+) -> BTreeMap<&Id, BTreeSet<&CanonicalPathBuf>> {
+    let mut paths: BTreeMap<&Id, BTreeSet<&CanonicalPathBuf>> = BTreeMap::new();
+    for (path, entry) in &index.path2id {
+        paths.entry(&entry.id).or_default().insert(path);
+    }
+    paths
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dev_hash::Crc32;
+    use std::fs;
+    use std::path::Path;
+    use tempdir::TempDir;
+
+    fn names<'a>(
+        paths: impl Iterator<Item = &'a CanonicalPathBuf>,
+    ) -> Vec<&'a str> {
+        paths
+            .map(|path| path.file_name().unwrap().to_str().unwrap())
+            .collect()
+    }
+
+    fn id(content: &str) -> Crc32 {
+        Crc32::from_bytes(content.as_bytes()).unwrap()
+    }
+
+    fn write(root: &Path, name: &str, content: &str) {
+        fs::write(root.join(name), content).unwrap();
+    }
+
+    #[test]
+    fn diff_reports_added_removed_and_moved() {
+        let dir = TempDir::new("arklib_test").unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        write(&root, "a.txt", "a");
+        write(&root, "b.txt", "b");
+        write(&root, "c.txt", "c");
+        let before = ResourceIndex::<Crc32>::build(&root);
+        assert!(before.diff(&before).is_empty());
+
+        fs::rename(root.join("a.txt"), root.join("moved.txt")).unwrap();
+        fs::remove_file(root.join("b.txt")).unwrap();
+        write(&root, "c.txt", "modified");
+        write(&root, "d.txt", "d");
+        let after = ResourceIndex::<Crc32>::build(&root);
+
+        let diff = before.diff(&after);
+        assert_eq!(
+            names(diff.added.iter().map(|(_, path)| path)),
+            ["c.txt", "d.txt"]
+        );
+        assert_eq!(diff.added[0].0, id("modified"));
+        assert_eq!(
+            names(diff.removed.iter().map(|(_, path)| path)),
+            ["b.txt", "c.txt"]
+        );
+        assert_eq!(diff.removed[1].0, id("c"));
+        assert_eq!(diff.moved.len(), 1);
+        assert_eq!(diff.moved[0].0, id("a"));
+        assert_eq!(
+            names([&diff.moved[0].1, &diff.moved[0].2].into_iter()),
+            ["a.txt", "moved.txt"]
+        );
+
+        // the reverse diff undoes the changes
+        let reverse = after.diff(&before);
+        assert_eq!(reverse.added.len(), diff.removed.len());
+        assert_eq!(reverse.removed.len(), diff.added.len());
+        assert_eq!(reverse.moved[0].1, diff.moved[0].2);
+    }
+
+    #[test]
+    fn diff_tracks_copies() {
+        let dir = TempDir::new("arklib_test").unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        write(&root, "a.txt", "a");
+        write(&root, "copy.txt", "a");
+        let before = ResourceIndex::<Crc32>::build(&root);
+
+        fs::rename(root.join("copy.txt"), root.join("renamed.txt")).unwrap();
+        write(&root, "another.txt", "a");
+        let after = ResourceIndex::<Crc32>::build(&root);
+
+        let diff = before.diff(&after);
+        assert!(diff.removed.is_empty());
+        assert_eq!(diff.moved.len(), 1);
+        assert_eq!(names(diff.added.iter().map(|(_, path)| path)).len(), 1);
+    }
+}
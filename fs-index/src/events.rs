@@ -0,0 +1,201 @@
+//! Notifications of the changes applied to an index, see
+//! [`ResourceIndex::subscribe`](crate::ResourceIndex::subscribe).
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::mpsc::Sender;
+
+use canonical_path::CanonicalPathBuf;
+
+use data_resource::ResourceId;
+
+/// Change of a single resource
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub enum IndexEvent<Id: ResourceId> {
+    Added {
+        id: Id,
+        path: CanonicalPathBuf,
+    },
+    Removed {
+        id: Id,
+        path: CanonicalPathBuf,
+    },
+    Moved {
+        id: Id,
+        from: CanonicalPathBuf,
+        to: CanonicalPathBuf,
+    },
+    /// The content of the file changed
+    Modified {
+        path: CanonicalPathBuf,
+        old_id: Id,
+        new_id: Id,
+    },
+}
+
+impl<Id: ResourceId> IndexEvent<Id> {
+    /// Current path of the resource, or its last path if it was removed
+    pub fn path(&self) -> &CanonicalPathBuf {
+        match self {
+            IndexEvent::Added { path, .. }
+            | IndexEvent::Removed { path, .. }
+            | IndexEvent::Modified { path, .. }
+            | IndexEvent::Moved { to: path, .. } => path,
+        }
+    }
+}
+
+/// Paths removed from and inserted into an index since the last
+/// notification, recorded only while somebody listens
+#[derive(Clone, Debug)]
+pub(crate) struct Subscribers<Id: ResourceId> {
+    senders: Vec<Sender<IndexEvent<Id>>>,
+    removed: Vec<(CanonicalPathBuf, Id)>,
+    inserted: Vec<(CanonicalPathBuf, Id)>,
+}
+
+impl<Id: ResourceId> Default for Subscribers<Id> {
+    fn default() -> Self {
+        Subscribers {
+            senders: Vec::new(),
+            removed: Vec::new(),
+            inserted: Vec::new(),
+        }
+    }
+}
+
+impl<Id: ResourceId> Subscribers<Id> {
+    pub(crate) fn add(&mut self, sender: Sender<IndexEvent<Id>>) {
+        self.senders.push(sender);
+    }
+
+    pub(crate) fn removed(&mut self, path: &CanonicalPathBuf, id: &Id) {
+        if !self.senders.is_empty() {
+            self.removed.push((path.clone(), id.clone()));
+        }
+    }
+
+    pub(crate) fn inserted(&mut self, path: &CanonicalPathBuf, id: &Id) {
+        if !self.senders.is_empty() {
+            self.inserted.push((path.clone(), id.clone()));
+        }
+    }
+
+    /// Send the events of the recorded changes,
+    /// forgetting the subscribers which are gone
+    pub(crate) fn notify(&mut self) {
+        let removed = std::mem::take(&mut self.removed);
+        let inserted = std::mem::take(&mut self.inserted);
+        for event in events(removed, inserted) {
+            self.senders
+                .retain(|sender| sender.send(event.clone()).is_ok());
+        }
+    }
+}
+
+/// Pair removed and inserted paths into events, sorted by path
+fn events<Id: ResourceId>(
+    removed: Vec<(CanonicalPathBuf, Id)>,
+    inserted: Vec<(CanonicalPathBuf, Id)>,
+) -> Vec<IndexEvent<Id>> {
+    let mut removed: BTreeMap<CanonicalPathBuf, Id> =
+        removed.into_iter().collect();
+    let mut events = Vec::new();
+
+    let mut added = Vec::new();
+    for (path, new_id) in inserted {
+        match removed.remove(&path) {
+            Some(old_id) if old_id == new_id => {}
+            Some(old_id) => events.push(IndexEvent::Modified {
+                path,
+                old_id,
+                new_id,
+            }),
+            None => added.push((path, new_id)),
+        }
+    }
+    added.sort();
+
+    // a resource which disappeared from a path and appeared at another
+    let mut gone: HashMap<Id, Vec<CanonicalPathBuf>> = HashMap::new();
+    for (path, id) in removed.into_iter().rev() {
+        gone.entry(id).or_default().push(path);
+    }
+    for (path, id) in added {
+        match gone.get_mut(&id).and_then(Vec::pop) {
+            Some(from) => events.push(IndexEvent::Moved { id, from, to: path }),
+            None => events.push(IndexEvent::Added { id, path }),
+        }
+    }
+    for (id, paths) in gone {
+        for path in paths {
+            events.push(IndexEvent::Removed {
+                id: id.clone(),
+                path,
+            });
+        }
+    }
+
+    events.sort_by(|a, b| a.path().cmp(b.path()));
+    events
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ResourceIndex;
+    use dev_hash::Crc32;
+    use std::fs;
+    use tempdir::TempDir;
+
+    #[test]
+    fn updates_are_notified() {
+        let dir = TempDir::new("events").unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        fs::write(root.join("a.txt"), "a").unwrap();
+        fs::write(root.join("b.txt"), "b").unwrap();
+        fs::write(root.join("c.txt"), "c").unwrap();
+
+        let mut index = ResourceIndex::<Crc32>::build(&root);
+        let events = index.subscribe();
+        let dropped = index.subscribe();
+        drop(dropped);
+
+        fs::rename(root.join("a.txt"), root.join("moved.txt")).unwrap();
+        fs::remove_file(root.join("b.txt")).unwrap();
+        fs::write(root.join("c.txt"), "modified").unwrap();
+        fs::write(root.join("d.txt"), "d").unwrap();
+        index.update_all().unwrap();
+
+        let path = |name: &str| {
+            CanonicalPathBuf::canonicalize(root.join(name)).unwrap()
+        };
+        let id = |content: &str| Crc32::from_bytes(content.as_bytes()).unwrap();
+        let removed = root.join("b.txt");
+        let received: Vec<_> = events.try_iter().collect();
+        assert_eq!(received.len(), 4);
+        assert!(matches!(&received[0], IndexEvent::Removed { path, .. }
+            if path.as_path() == removed));
+        assert_eq!(
+            received[1],
+            IndexEvent::Modified {
+                path: path("c.txt"),
+                old_id: id("c"),
+                new_id: id("modified"),
+            }
+        );
+        assert_eq!(
+            received[2],
+            IndexEvent::Added {
+                id: id("d"),
+                path: path("d.txt"),
+            }
+        );
+        assert!(
+            matches!(&received[3], IndexEvent::Moved { id: moved, to, .. }
+            if moved == &id("a") && to == &path("moved.txt"))
+        );
+
+        index.forget_id(id("d")).unwrap();
+        assert_eq!(events.try_iter().count(), 1);
+    }
+}
@@ -0,0 +1,226 @@
+//! One logical index over several roots, e.g. the internal storage
+//! and the SD card of a phone.
+//!
+//! Every root keeps its own [`ResourceIndex`], stored in the `.ark`
+//! folder of that root, so a root can be used on its own or be
+//! disconnected without affecting the others.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use canonical_path::CanonicalPathBuf;
+
+use data_error::{ArklibError, Result};
+use data_resource::ResourceId;
+
+use crate::index::{IndexUpdate, IndexedResource, ResourceIndex};
+
+/// Indexes of several roots, none of them located inside another
+#[derive(Clone, Debug)]
+pub struct FederatedIndex<Id: ResourceId> {
+    // canonical root with its index, in the order of addition
+    roots: Vec<(PathBuf, ResourceIndex<Id>)>,
+}
+
+impl<Id: ResourceId> Default for FederatedIndex<Id> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<Id: ResourceId> FederatedIndex<Id> {
+    pub fn new() -> Self {
+        FederatedIndex { roots: Vec::new() }
+    }
+
+    /// Load or build the index of `root`, see [`ResourceIndex::provide`]
+    pub fn add_root<P: AsRef<Path>>(&mut self, root: P) -> Result<()> {
+        let canonical = self.check_new_root(root.as_ref())?;
+        let index = ResourceIndex::provide(root)?;
+        self.roots.push((canonical, index));
+        Ok(())
+    }
+
+    /// Add an index which has already been loaded
+    pub fn add_index(&mut self, index: ResourceIndex<Id>) -> Result<()> {
+        let canonical = self.check_new_root(index.root())?;
+        self.roots.push((canonical, index));
+        Ok(())
+    }
+
+    /// Stop using the root, e.g. when the SD card is removed
+    pub fn remove_root<P: AsRef<Path>>(
+        &mut self,
+        root: P,
+    ) -> Option<ResourceIndex<Id>> {
+        let root = canonicalize(root.as_ref());
+        let position = self
+            .roots
+            .iter()
+            .position(|(canonical, _)| canonical == &root)?;
+        Some(self.roots.remove(position).1)
+    }
+
+    pub fn roots(&self) -> impl Iterator<Item = &Path> + '_ {
+        self.roots.iter().map(|(_, index)| index.root())
+    }
+
+    pub fn indexes(&self) -> impl Iterator<Item = &ResourceIndex<Id>> + '_ {
+        self.roots.iter().map(|(_, index)| index)
+    }
+
+    /// Number of indexed paths over all roots
+    pub fn size(&self) -> usize {
+        self.indexes().map(ResourceIndex::size).sum()
+    }
+
+    /// Index of the root containing `path`
+    pub fn index_of<P: AsRef<Path>>(
+        &self,
+        path: P,
+    ) -> Option<&ResourceIndex<Id>> {
+        let path = canonicalize(path.as_ref());
+        self.roots
+            .iter()
+            .find(|(root, _)| path.starts_with(root))
+            .map(|(_, index)| index)
+    }
+
+    /// A path of the resource, from the first root containing it
+    pub fn get_path(&self, id: &Id) -> Option<&CanonicalPathBuf> {
+        self.indexes()
+            .find_map(|index| index.id2path.get(id))
+    }
+
+    /// Paths of the resource in every root containing it
+    pub fn get_paths<'a>(
+        &'a self,
+        id: &'a Id,
+    ) -> impl Iterator<Item = &'a CanonicalPathBuf> + 'a {
+        self.indexes()
+            .filter_map(move |index| index.id2path.get(id))
+    }
+
+    pub fn contains_id(&self, id: &Id) -> bool {
+        self.indexes().any(|index| index.contains_id(id))
+    }
+
+    /// Resource indexed by the absolute path `path`
+    pub fn get_resource_by_path<P: AsRef<Path>>(
+        &self,
+        path: P,
+    ) -> Option<&IndexedResource<Id>> {
+        self.index_of(path.as_ref())?
+            .get_resource_by_path(path.as_ref())
+    }
+
+    /// Update every root, the updates being keyed by root
+    pub fn update_all(&mut self) -> Result<HashMap<PathBuf, IndexUpdate<Id>>> {
+        let mut updates = HashMap::new();
+        for (_, index) in self.roots.iter_mut() {
+            let update = index.update_all()?;
+            updates.insert(index.root().to_owned(), update);
+        }
+        Ok(updates)
+    }
+
+    /// Store the index of every root in the `.ark` folder of that root
+    pub fn store(&self) -> Result<()> {
+        for index in self.indexes() {
+            index.store()?;
+        }
+        Ok(())
+    }
+
+    fn check_new_root(&self, root: &Path) -> Result<PathBuf> {
+        let canonical = std::fs::canonicalize(root)?;
+        for (existing, _) in &self.roots {
+            if canonical.starts_with(existing)
+                || existing.starts_with(&canonical)
+            {
+                return Err(ArklibError::Path(format!(
+                    "{} overlaps with the root {}",
+                    root.display(),
+                    existing.display()
+                )));
+            }
+        }
+        Ok(canonical)
+    }
+}
+
+// paths of deleted files can't be canonicalized
+fn canonicalize(path: &Path) -> PathBuf {
+    std::fs::canonicalize(path).unwrap_or_else(|_| path.to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dev_hash::Crc32;
+    use fs_storage::{ARK_FOLDER, INDEX_PATH};
+    use std::fs;
+    use tempdir::TempDir;
+
+    #[test]
+    fn lookups_span_every_root() {
+        let internal = TempDir::new("internal").unwrap();
+        let card = TempDir::new("card").unwrap();
+        fs::write(internal.path().join("a.txt"), "a").unwrap();
+        fs::write(card.path().join("b.txt"), "b").unwrap();
+        fs::write(card.path().join("copy.txt"), "a").unwrap();
+
+        let mut federated = FederatedIndex::<Crc32>::new();
+        federated.add_root(internal.path()).unwrap();
+        federated.add_root(card.path()).unwrap();
+        assert_eq!(federated.size(), 3);
+
+        let a = Crc32(3904355907);
+        let b = Crc32(1908338681);
+        assert!(federated
+            .get_path(&a)
+            .unwrap()
+            .starts_with(internal.path()));
+        assert_eq!(federated.get_paths(&a).count(), 2);
+        assert!(federated
+            .get_path(&b)
+            .unwrap()
+            .starts_with(card.path()));
+
+        let resource = federated
+            .get_resource_by_path(card.path().join("b.txt"))
+            .unwrap();
+        assert_eq!(resource.id, b);
+        assert!(federated
+            .get_resource_by_path(internal.path().join("b.txt"))
+            .is_none());
+
+        fs::write(card.path().join("c.txt"), "c").unwrap();
+        let updates = federated.update_all().unwrap();
+        assert!(updates[internal.path()].added.is_empty());
+        assert_eq!(updates[card.path()].added.len(), 1);
+
+        federated.store().unwrap();
+        for root in [internal.path(), card.path()] {
+            assert!(root.join(ARK_FOLDER).join(INDEX_PATH).exists());
+        }
+
+        let removed = federated.remove_root(card.path()).unwrap();
+        assert_eq!(removed.size(), 3);
+        assert!(!federated.contains_id(&b));
+    }
+
+    #[test]
+    fn overlapping_roots_are_rejected() {
+        let root = TempDir::new("root").unwrap();
+        fs::create_dir(root.path().join("nested")).unwrap();
+
+        let mut federated = FederatedIndex::<Crc32>::new();
+        federated.add_root(root.path()).unwrap();
+        assert!(federated.add_root(root.path()).is_err());
+        assert!(federated
+            .add_root(root.path().join("nested"))
+            .is_err());
+        assert_eq!(federated.roots().count(), 1);
+    }
+}
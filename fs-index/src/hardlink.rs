@@ -0,0 +1,112 @@
+//! Files reachable through several hard links, e.g. in backups made
+//! with `rsync --link-dest` or by deduplication tools.
+//!
+//! Every link is indexed as its own path, but the content is hashed
+//! through the first link only.
+
+use std::collections::HashMap;
+use std::fs::Metadata;
+
+use canonical_path::CanonicalPathBuf;
+use walkdir::DirEntry;
+
+use data_resource::ResourceId;
+
+use crate::hash_cache::HashCache;
+use crate::index::{scan_dir_entry, IndexEntry};
+
+/// Device and inode of a file having more than one link
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+struct Inode {
+    device: u64,
+    inode: u64,
+}
+
+impl Inode {
+    #[cfg(unix)]
+    fn of(metadata: &Metadata) -> Option<Self> {
+        use std::os::unix::fs::MetadataExt;
+
+        (metadata.nlink() > 1).then(|| Inode {
+            device: metadata.dev(),
+            inode: metadata.ino(),
+        })
+    }
+
+    // the file index of Windows is not exposed by stable std
+    #[cfg(not(unix))]
+    fn of(_metadata: &Metadata) -> Option<Self> {
+        None
+    }
+}
+
+/// Whether the file has other links, which are hashed only once
+pub(crate) fn is_linked(metadata: &Metadata) -> bool {
+    Inode::of(metadata).is_some()
+}
+
+/// Ids of the linked files scanned so far
+pub(crate) struct HardLinks<Id: ResourceId> {
+    ids: HashMap<Inode, Id>,
+}
+
+impl<Id: ResourceId> Default for HardLinks<Id> {
+    fn default() -> Self {
+        HardLinks {
+            ids: HashMap::new(),
+        }
+    }
+}
+
+impl<Id: ResourceId> HardLinks<Id> {
+    /// Same as `scan_dir_entry`, reusing the id of another link
+    /// to the same file if it has been scanned already
+    pub(crate) fn scan(
+        &mut self,
+        path: CanonicalPathBuf,
+        entry: DirEntry,
+        hashes: &mut HashCache<Id>,
+    ) -> Option<(CanonicalPathBuf, IndexEntry<Id>)> {
+        let metadata = entry.metadata().ok()?;
+        let inode = Inode::of(&metadata);
+        if let Some(id) = inode.and_then(|inode| self.ids.get(&inode)) {
+            log::trace!("[scan] {} is a link to {}", path.display(), id);
+            let modified = metadata.modified().ok()?;
+            let id = id.clone();
+            return Some((path, IndexEntry { modified, id }));
+        }
+
+        let scanned = scan_dir_entry(path, entry, Some(hashes))?;
+        if let Some(inode) = inode {
+            self.ids.insert(inode, scanned.1.id.clone());
+        }
+        Some(scanned)
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::ResourceIndex;
+    use dev_hash::Crc32;
+    use std::fs;
+    use tempdir::TempDir;
+
+    #[test]
+    fn links_share_the_id_of_their_file() {
+        let dir = TempDir::new("hardlink").unwrap();
+        let root = dir.path();
+        fs::write(root.join("a.txt"), "a").unwrap();
+        fs::create_dir(root.join("backup")).unwrap();
+        fs::hard_link(root.join("a.txt"), root.join("backup/a.txt")).unwrap();
+
+        let index = ResourceIndex::<Crc32>::build(root);
+        assert_eq!(index.len(), 2);
+        assert_eq!(index.collisions[&Crc32(3904355907)], 2);
+
+        let link = fs::metadata(root.join("backup/a.txt")).unwrap();
+        let file = fs::metadata(root.join("a.txt")).unwrap();
+        assert!(Inode::of(&link).is_some());
+        assert_eq!(Inode::of(&link), Inode::of(&file));
+    }
+}
@@ -0,0 +1,204 @@
+//! Ids computed for files, reused while their size and modification
+//! time stay the same.
+//!
+//! [`ResourceIndex::update_all`](crate::ResourceIndex::update_all) only
+//! hashes the files which are new or modified since the last scan, but
+//! building an index from scratch, e.g. when the stored index is
+//! missing or was written by another version, hashes every file.
+//! The cache is stored next to the index to avoid that.
+
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use data_error::{ArklibError, Result};
+use data_resource::ResourceId;
+use fs_storage::paths::simplified;
+use fs_storage::{ARK_FOLDER, HASH_CACHE_PATH};
+
+#[derive(Clone, Debug)]
+struct Hashed<Id> {
+    size: u64,
+    modified: SystemTime,
+    id: Id,
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct HashCache<Id: ResourceId> {
+    entries: HashMap<PathBuf, Hashed<Id>>,
+}
+
+impl<Id: ResourceId> Default for HashCache<Id> {
+    fn default() -> Self {
+        HashCache {
+            entries: HashMap::new(),
+        }
+    }
+}
+
+impl<Id: ResourceId> HashCache<Id> {
+    /// Cache stored under `root`, empty if there is none
+    pub(crate) fn load(root: &Path) -> Self {
+        let path = root.join(ARK_FOLDER).join(HASH_CACHE_PATH);
+        let mut cache = Self::default();
+        let Ok(file) = File::open(&path) else {
+            return cache;
+        };
+        let root = canonical_root(root);
+
+        for line in BufReader::new(file).lines() {
+            let parsed = line
+                .map_err(ArklibError::from)
+                .and_then(|line| parse_line(&root, &line));
+            match parsed {
+                Ok((path, hashed)) => {
+                    cache.entries.insert(path, hashed);
+                }
+                Err(err) => {
+                    // the cache can always be rebuilt
+                    log::warn!("Ignoring {}: {}", path.display(), err);
+                    return Self::default();
+                }
+            }
+        }
+        cache
+    }
+
+    /// Content of the stored cache with the entries of the paths
+    /// for which `keep` returns true
+    pub(crate) fn encode(
+        &self,
+        root: &Path,
+        keep: impl Fn(&Path) -> bool,
+    ) -> Result<Vec<u8>> {
+        let mut content = Vec::new();
+        let root = canonical_root(root);
+        for (path, hashed) in &self.entries {
+            if !keep(path) {
+                continue;
+            }
+            let Some(relative) =
+                pathdiff::diff_paths(simplified(path), simplified(&root))
+            else {
+                continue;
+            };
+            let modified = hashed
+                .modified
+                .duration_since(UNIX_EPOCH)
+                .map_err(|err| ArklibError::Other(err.into()))?
+                .as_nanos();
+            writeln!(
+                content,
+                "{} {} {} {}",
+                hashed.size,
+                modified,
+                hashed.id,
+                relative.display()
+            )?;
+        }
+        Ok(content)
+    }
+
+    /// Id of the file if it still has the size and modification time
+    /// it had when the id was computed
+    pub(crate) fn get(
+        &self,
+        path: &Path,
+        size: u64,
+        modified: SystemTime,
+    ) -> Option<&Id> {
+        self.entries
+            .get(path)
+            .filter(|hashed| hashed.size == size && hashed.modified == modified)
+            .map(|hashed| &hashed.id)
+    }
+
+    pub(crate) fn insert(
+        &mut self,
+        path: PathBuf,
+        size: u64,
+        modified: SystemTime,
+        id: Id,
+    ) {
+        self.entries
+            .insert(path, Hashed { size, modified, id });
+    }
+}
+
+// cached paths are canonical
+fn canonical_root(root: &Path) -> PathBuf {
+    fs::canonicalize(root).unwrap_or_else(|_| root.to_owned())
+}
+
+fn parse_line<Id: ResourceId>(
+    root: &Path,
+    line: &str,
+) -> Result<(PathBuf, Hashed<Id>)> {
+    let mut parts = line.splitn(4, ' ');
+    let mut next = || parts.next().ok_or(ArklibError::Parse);
+
+    let size = next()?.parse().map_err(|_| ArklibError::Parse)?;
+    let nanos: u128 = next()?.parse().map_err(|_| ArklibError::Parse)?;
+    let modified = UNIX_EPOCH
+        .checked_add(Duration::new(
+            (nanos / 1_000_000_000) as u64,
+            (nanos % 1_000_000_000) as u32,
+        ))
+        .ok_or(ArklibError::Parse)?;
+    let id = Id::from_str(next()?).map_err(|_| ArklibError::Parse)?;
+    let path = root.join(next()?);
+
+    Ok((path, Hashed { size, modified, id }))
+}
+
+/// Overwrite the cache stored under `root` with `content`
+pub(crate) fn write(root: &Path, content: &[u8]) -> Result<()> {
+    let path = root.join(ARK_FOLDER).join(HASH_CACHE_PATH);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, content)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dev_hash::Crc32;
+    use tempdir::TempDir;
+
+    #[test]
+    fn cache_round_trip() {
+        let dir = TempDir::new("hash_cache").unwrap();
+        let root = dir.path();
+        let modified = UNIX_EPOCH + Duration::new(1_700_000_000, 123_456_789);
+
+        let mut cache = HashCache::<Crc32>::default();
+        cache.insert(root.join("a b.txt"), 1, modified, Crc32(3904355907));
+        cache.insert(root.join("gone.txt"), 1, modified, Crc32(1908338681));
+        let content = cache
+            .encode(root, |path| !path.ends_with("gone.txt"))
+            .unwrap();
+        write(root, &content).unwrap();
+
+        let cache = HashCache::<Crc32>::load(root);
+        let path = root.join("a b.txt");
+        assert_eq!(cache.get(&path, 1, modified), Some(&Crc32(3904355907)));
+        assert_eq!(cache.get(&path, 2, modified), None);
+        assert_eq!(cache.get(&path, 1, UNIX_EPOCH), None);
+        assert_eq!(cache.get(&root.join("gone.txt"), 1, modified), None);
+    }
+
+    #[test]
+    fn invalid_cache_is_dropped() {
+        let dir = TempDir::new("hash_cache").unwrap();
+        let path = dir.path().join(ARK_FOLDER).join(HASH_CACHE_PATH);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, "1 2 3 a.txt\nnot a cache\n").unwrap();
+
+        let cache = HashCache::<Crc32>::load(dir.path());
+        assert!(cache.entries.is_empty());
+    }
+}
@@ -1,33 +1,93 @@
 use anyhow::anyhow;
 use canonical_path::{CanonicalPath, CanonicalPathBuf};
 use itertools::Itertools;
-use std::collections::{HashMap, HashSet};
-use std::fs::{self, File, Metadata};
-use std::io::{BufRead, BufReader, Write};
-use std::ops::Add;
-use std::path::{Path, PathBuf};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::ffi::OsStr;
+use std::fs::{self, Metadata};
+use std::io::{BufRead, Write};
+use std::ops::RangeBounds;
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver};
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
-use walkdir::{DirEntry, WalkDir};
+use walkdir::DirEntry;
 
 use log;
 
 use data_error::{ArklibError, Result};
 use data_resource::ResourceId;
+use fs_storage::paths::{extended_length, simplified};
 use fs_storage::{ARK_FOLDER, INDEX_PATH};
 
+use crate::arkignore::IgnoreRules;
+use crate::autostore::{AutoStore, Snapshot};
+use crate::caches::{self, CacheInvalidation};
+use crate::events::{IndexEvent, Subscribers};
+use crate::hardlink::HardLinks;
+use crate::hash_cache::HashCache;
+use crate::normalization::PathNormalization;
+use crate::progress::{ProgressHandler, ProgressTracker};
+use crate::symlink::{SymlinkFilter, SymlinkPolicy};
+
 #[derive(Eq, Ord, PartialEq, PartialOrd, Hash, Clone, Debug)]
+#[cfg_attr(
+    feature = "binary-index",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(bound = "Id: ResourceId")
+)]
 pub struct IndexEntry<Id: ResourceId> {
     pub modified: SystemTime,
     pub id: Id,
 }
 
-#[derive(PartialEq, Clone, Debug)]
+#[derive(Clone, Debug)]
 pub struct ResourceIndex<Id: ResourceId> {
     pub id2path: HashMap<Id, CanonicalPathBuf>,
     pub path2id: HashMap<CanonicalPathBuf, IndexEntry<Id>>,
 
     pub collisions: HashMap<Id, usize>,
     root: PathBuf,
+
+    // same entries as `path2id` keyed by `paths.key`,
+    // ordered for lookups by folder
+    by_path: BTreeMap<PathBuf, IndexedResource<Id>>,
+    symlinks: SymlinkPolicy,
+    paths: PathNormalization,
+    walk_threads: usize,
+    hashes: HashCache<Id>,
+    subscribers: Subscribers<Id>,
+    autostore: AutoStore,
+    caches: CacheInvalidation<Id>,
+}
+
+// `by_path` mirrors `path2id`, the cache, the subscribers, auto-storing
+// and cache invalidation don't change the content
+impl<Id: ResourceId> PartialEq for ResourceIndex<Id> {
+    fn eq(&self, other: &Self) -> bool {
+        self.id2path == other.id2path
+            && self.path2id == other.path2id
+            && self.collisions == other.collisions
+            && self.root == other.root
+            && self.symlinks == other.symlinks
+            && self.paths == other.paths
+    }
+}
+
+/// Indexed resource together with its path
+#[derive(PartialEq, Clone, Debug)]
+pub struct IndexedResource<Id: ResourceId> {
+    pub path: CanonicalPathBuf,
+    pub id: Id,
+    pub modified: SystemTime,
+}
+
+/// Direct content of a folder, see [`ResourceIndex::immediate_children`]
+#[derive(PartialEq, Debug)]
+pub struct Children<'a, Id: ResourceId> {
+    /// Names of the subfolders containing indexed resources, sorted
+    pub folders: Vec<&'a OsStr>,
+    /// Resources located directly in the folder, sorted by path
+    pub resources: Vec<&'a IndexedResource<Id>>,
 }
 
 #[derive(PartialEq, Debug)]
@@ -36,8 +96,24 @@ pub struct IndexUpdate<Id: ResourceId> {
     pub added: HashMap<CanonicalPathBuf, Id>,
 }
 
+/// Result of an operation which may have been cancelled,
+/// e.g. [`ResourceIndex::build_cancellable`]
+#[derive(PartialEq, Debug)]
+pub struct Partial<T> {
+    pub value: T,
+    /// Whether the operation ran to the end, otherwise the files
+    /// which hadn't been scanned yet are left out
+    pub complete: bool,
+}
+
 pub const RESOURCE_UPDATED_THRESHOLD: Duration = Duration::from_millis(1);
 
+/// Header of the binary format of the stored index,
+/// the text format starts with a timestamp
+const BINARY_INDEX_MAGIC: &[u8] = b"ARKINDEX";
+#[cfg(feature = "binary-index")]
+const BINARY_INDEX_VERSION: u32 = 1;
+
 pub type Paths = HashSet<CanonicalPathBuf>;
 
 impl<Id: ResourceId> ResourceIndex<Id> {
@@ -46,67 +122,505 @@ impl<Id: ResourceId> ResourceIndex<Id> {
         self.path2id.len()
     }
 
+    /// Number of indexed paths, same as [`ResourceIndex::size`]
+    pub fn len(&self) -> usize {
+        self.path2id.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.path2id.is_empty()
+    }
+
+    /// All resources sorted by path, without copying the index,
+    /// e.g. to show a page with `skip` and `take`
+    pub fn iter_entries(
+        &self,
+    ) -> impl ExactSizeIterator<Item = &IndexedResource<Id>> + '_ {
+        self.by_path.values()
+    }
+
+    /// Ids of the resources in arbitrary order,
+    /// every id appearing once even in presence of collisions
+    pub fn iter_ids(&self) -> impl ExactSizeIterator<Item = &Id> + '_ {
+        self.id2path.keys()
+    }
+
+    /// Paths of the resources, sorted
+    pub fn iter_paths(
+        &self,
+    ) -> impl ExactSizeIterator<Item = &CanonicalPathBuf> + '_ {
+        self.by_path
+            .values()
+            .map(|resource| &resource.path)
+    }
+
+    pub fn contains_id(&self, id: &Id) -> bool {
+        self.id2path.contains_key(id)
+    }
+
+    /// Whether `path` is indexed, relative paths are resolved
+    /// against the root of the index
+    pub fn contains_path<P: AsRef<Path>>(&self, path: P) -> bool {
+        self.by_path
+            .contains_key(&self.key(path.as_ref()))
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Receive an event for every resource added, removed, moved or
+    /// modified by the next updates of the index.
+    ///
+    /// Dropping the receiver unsubscribes.
+    pub fn subscribe(&mut self) -> Receiver<IndexEvent<Id>> {
+        let (sender, receiver) = mpsc::channel();
+        self.subscribers.add(sender);
+        receiver
+    }
+
+    /// Store the index in the background after every change, at most
+    /// once per `debounce`, or stop storing it automatically with `None`.
+    ///
+    /// Changes made sooner are stored once the debounce has elapsed,
+    /// by [`ResourceIndex::flush`] or when the index is dropped.
+    /// Clones of the index aren't stored automatically.
+    pub fn set_auto_store(&mut self, debounce: Option<Duration>) {
+        self.autostore.set(debounce);
+    }
+
+    /// Remove the previews, thumbnails, metadata and text of the resources
+    /// which are modified or removed by the next updates of the index,
+    /// unless their content is still indexed at another path.
+    pub fn set_cache_invalidation(&mut self, enabled: bool) {
+        self.caches.set(enabled);
+    }
+
+    /// Remove the entries of the [cache folders](caches::CACHE_FOLDERS)
+    /// whose resources are not indexed, returning their number
+    pub fn collect_cache_garbage(&self) -> Result<usize> {
+        caches::collect_garbage(&self.root, |id| self.id2path.contains_key(id))
+    }
+
+    /// Store the changes which are waiting for the debounce
+    /// of [`ResourceIndex::set_auto_store`]
+    pub fn flush(&mut self) -> Result<()> {
+        self.hand_over_changes();
+        self.autostore.flush()
+    }
+
+    pub fn path_normalization(&self) -> PathNormalization {
+        self.paths
+    }
+
+    /// Compare paths according to `paths` from now on, e.g. with
+    /// [`PathNormalization::native`] for an index shared across platforms.
+    ///
+    /// Paths which become spellings of the same path are only kept once.
+    pub fn set_path_normalization(&mut self, paths: PathNormalization) {
+        self.paths = paths;
+        let mut entries: Vec<(CanonicalPathBuf, IndexEntry<Id>)> = self
+            .path2id
+            .iter()
+            .map(|(path, entry)| (path.clone(), entry.clone()))
+            .collect();
+        entries.sort();
+
+        self.by_path.clear();
+        self.id2path.clear();
+        self.collisions.clear();
+        for (path, _) in entries.iter() {
+            self.remove_path(path.as_canonical_path());
+        }
+        for (path, entry) in entries {
+            self.insert_entry(path, entry);
+        }
+        self.changed();
+    }
+
+    /// Threads listing the folders during the next updates,
+    /// see [`ResourceIndex::build_with_walk_threads`]
+    #[cfg(feature = "parallel-walk")]
+    pub fn set_walk_threads(&mut self, threads: usize) {
+        self.walk_threads = threads;
+    }
+
+    pub fn symlink_policy(&self) -> SymlinkPolicy {
+        self.symlinks
+    }
+
+    /// Policy used by the next updates, e.g. after loading the index.
+    /// Resources indexed with the previous policy are kept until then.
+    pub fn set_symlink_policy(&mut self, symlinks: SymlinkPolicy) {
+        self.symlinks = symlinks;
+    }
+
+    /// Resource indexed by `path`, relative paths are resolved
+    /// against the root of the index
+    pub fn get_resource_by_path<P: AsRef<Path>>(
+        &self,
+        path: P,
+    ) -> Option<&IndexedResource<Id>> {
+        self.by_path.get(&self.key(path.as_ref()))
+    }
+
+    /// All resources located under the folder `prefix`, sorted by path.
+    ///
+    /// The prefix is resolved like in [`ResourceIndex::get_resource_by_path`]
+    /// and matched on whole path components, so `photos/2023` doesn't
+    /// match `photos/2023-backup`. The root prefix returns every resource.
+    pub fn resources_under<P: AsRef<Path>>(
+        &self,
+        prefix: P,
+    ) -> Vec<&IndexedResource<Id>> {
+        let prefix = self.key(prefix.as_ref());
+        self.by_path
+            .range(prefix.clone()..)
+            .take_while(|(path, _)| path.starts_with(&prefix))
+            .map(|(_, resource)| resource)
+            .collect()
+    }
+
+    /// Subfolders and resources located directly in the folder `prefix`,
+    /// e.g. to build a tree view of the index
+    pub fn immediate_children<P: AsRef<Path>>(
+        &self,
+        prefix: P,
+    ) -> Children<'_, Id> {
+        let prefix = self.key(prefix.as_ref());
+        let depth = prefix.components().count();
+        let mut children = Children {
+            folders: Vec::new(),
+            resources: Vec::new(),
+        };
+
+        // paths are ordered by components,
+        // so the content of every subfolder is contiguous
+        for (path, resource) in self.by_path.range(prefix.clone()..) {
+            let Ok(relative) = path.strip_prefix(&prefix) else {
+                break;
+            };
+            let mut components = relative.components();
+            match (components.next(), components.next()) {
+                (Some(Component::Normal(_)), Some(_)) => {
+                    // the key may be folded, names are taken from the path
+                    let Some(Component::Normal(folder)) =
+                        resource.path.components().nth(depth)
+                    else {
+                        continue;
+                    };
+                    if children.folders.last() != Some(&folder) {
+                        children.folders.push(folder);
+                    }
+                }
+                (Some(_), None) => children.resources.push(resource),
+                _ => {}
+            }
+        }
+
+        children
+    }
+
+    /// Resources whose file name has the extension `extension`,
+    /// compared case-insensitively, e.g. `"pdf"` matches `report.PDF`
+    pub fn resources_with_extension<'a>(
+        &'a self,
+        extension: &'a str,
+    ) -> impl Iterator<Item = &'a IndexedResource<Id>> + 'a {
+        let extension = extension.trim_start_matches('.');
+        self.by_path.values().filter(move |resource| {
+            resource
+                .path
+                .extension()
+                .and_then(OsStr::to_str)
+                .map_or(false, |ext| ext.eq_ignore_ascii_case(extension))
+        })
+    }
+
+    /// Resources modified at `since` or later
+    pub fn resources_modified_since(
+        &self,
+        since: SystemTime,
+    ) -> impl Iterator<Item = &IndexedResource<Id>> + '_ {
+        self.by_path
+            .values()
+            .filter(move |resource| resource.modified >= since)
+    }
+
+    /// Resources whose size in bytes is within `range`.
+    ///
+    /// Sizes are not part of the index, so they are read from the
+    /// filesystem as the iterator advances. Files which can't be
+    /// accessed anymore are skipped.
+    pub fn resources_in_size_range<R>(
+        &self,
+        range: R,
+    ) -> impl Iterator<Item = &IndexedResource<Id>> + '_
+    where
+        R: RangeBounds<u64> + 'static,
+    {
+        self.by_path.values().filter(move |resource| {
+            fs::metadata(&resource.path)
+                .map_or(false, |metadata| range.contains(&metadata.len()))
+        })
+    }
+
+    /// Key of `path` in `by_path`
+    fn key(&self, path: &Path) -> PathBuf {
+        self.paths.key(&self.normalize(path))
+    }
+
+    pub(crate) fn normalize(&self, path: &Path) -> PathBuf {
+        let path = self.root.join(path);
+        fs::canonicalize(&path).unwrap_or_else(|_| {
+            // the path doesn't exist anymore, so only the root is resolved
+            match (path.strip_prefix(&self.root), fs::canonicalize(&self.root))
+            {
+                (Ok(relative), Ok(root)) => root.join(relative),
+                _ => path,
+            }
+        })
+    }
+
     pub fn build<P: AsRef<Path>>(root_path: P) -> Self {
+        Self::build_with(root_path, None, SymlinkPolicy::default(), None, 1)
+            .value
+    }
+
+    /// Same as [`ResourceIndex::build`], reporting to `progress`
+    /// after every scanned file
+    pub fn build_with_progress<P: AsRef<Path>>(
+        root_path: P,
+        progress: &mut dyn ProgressHandler,
+    ) -> Self {
+        Self::build_with(
+            root_path,
+            Some(progress),
+            SymlinkPolicy::default(),
+            None,
+            1,
+        )
+        .value
+    }
+
+    /// Same as [`ResourceIndex::build`], handling symbolic links
+    /// according to `symlinks`, also during later updates
+    pub fn build_with_symlink_policy<P: AsRef<Path>>(
+        root_path: P,
+        symlinks: SymlinkPolicy,
+    ) -> Self {
+        Self::build_with(root_path, None, symlinks, None, 1).value
+    }
+
+    /// Same as [`ResourceIndex::build`], listing the folders on
+    /// `threads` threads, also during later updates.
+    ///
+    /// Listing folders can take longer than hashing on network
+    /// filesystems. The result is the same as with a single thread.
+    #[cfg(feature = "parallel-walk")]
+    pub fn build_with_walk_threads<P: AsRef<Path>>(
+        root_path: P,
+        threads: usize,
+    ) -> Self {
+        Self::build_with(
+            root_path,
+            None,
+            SymlinkPolicy::default(),
+            None,
+            threads,
+        )
+        .value
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "build",
+            skip_all,
+            fields(
+                root = %root_path.as_ref().display(),
+                entries = tracing::field::Empty,
+                elapsed_ms = tracing::field::Empty,
+            )
+        )
+    )]
+    fn build_with<P: AsRef<Path>>(
+        root_path: P,
+        progress: Option<&mut dyn ProgressHandler>,
+        symlinks: SymlinkPolicy,
+        cancelled: Option<&AtomicBool>,
+        walk_threads: usize,
+    ) -> Partial<Self> {
+        #[cfg(feature = "tracing")]
+        let start = std::time::Instant::now();
+
         log::info!("Building the index from scratch");
         let root_path: PathBuf = root_path.as_ref().to_owned();
 
-        let entries = discover_paths(&root_path);
-        let entries = scan_entries(entries);
+        let entries = discover_paths_under(
+            &root_path,
+            &root_path,
+            symlinks,
+            walk_threads,
+        );
+        let hashes = HashCache::load(&root_path);
+        let index = Self::build_from(
+            root_path,
+            entries,
+            hashes,
+            progress,
+            symlinks,
+            cancelled,
+            walk_threads,
+        );
 
-        let mut index = ResourceIndex {
-            id2path: HashMap::new(),
-            path2id: HashMap::new(),
-            collisions: HashMap::new(),
-            root: root_path,
-        };
+        #[cfg(feature = "tracing")]
+        tracing::Span::current()
+            .record("entries", index.value.path2id.len())
+            .record("elapsed_ms", start.elapsed().as_millis() as u64);
+        index
+    }
+
+    /// Index of the files `entries` found under `root_path`, scanning
+    /// them with the ids of `hashes` when they are still valid
+    fn build_from(
+        root_path: PathBuf,
+        entries: HashMap<CanonicalPathBuf, DirEntry>,
+        mut hashes: HashCache<Id>,
+        progress: Option<&mut dyn ProgressHandler>,
+        symlinks: SymlinkPolicy,
+        cancelled: Option<&AtomicBool>,
+        walk_threads: usize,
+    ) -> Partial<Self> {
+        let mut progress = ProgressTracker::new(progress, entries.values());
+        let (entries, skipped) =
+            scan_entries(entries, &mut progress, &mut hashes, cancelled);
+
+        let mut index = Self::empty(root_path);
+        index.symlinks = symlinks;
+        index.walk_threads = walk_threads;
+        index.hashes = hashes;
 
         for (path, entry) in entries {
             index.insert_entry(path, entry);
         }
 
-        log::info!("Index built");
-        index
+        if !skipped.is_empty() {
+            log::info!("Index build cancelled");
+        } else {
+            log::info!("Index built");
+        }
+        Partial {
+            value: index,
+            complete: skipped.is_empty(),
+        }
     }
 
-    pub fn load<P: AsRef<Path>>(root_path: P) -> Result<Self> {
+    /// Same as [`ResourceIndex::build`], but hashes the files on
+    /// `threads` threads, or one thread per CPU if `threads` is 0.
+    ///
+    /// The tree is walked before hashing starts, walking being
+    /// much cheaper than hashing. Only hashing is parallel, the files
+    /// are then indexed like in any build.
+    #[cfg(feature = "rayon")]
+    pub fn build_parallel<P: AsRef<Path>>(
+        root_path: P,
+        threads: usize,
+    ) -> Result<Self>
+    where
+        Id: Send,
+    {
+        log::info!("Building the index from scratch on {} threads", threads);
         let root_path: PathBuf = root_path.as_ref().to_owned();
 
-        let index_path: PathBuf = root_path.join(ARK_FOLDER).join(INDEX_PATH);
-        log::info!("Loading the index from file {}", index_path.display());
-        let file = File::open(&index_path)?;
-        let mut index = ResourceIndex {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()
+            .map_err(|err| ArklibError::Other(anyhow!(err)))?;
+
+        let symlinks = SymlinkPolicy::default();
+        let entries = discover_paths(&root_path, symlinks);
+        let mut hashes = HashCache::load(&root_path);
+        let report =
+            crate::parallel_hash::hash_missing(&pool, &entries, &mut hashes);
+        log::info!(
+            "Hashed {} files in {:?}",
+            report.succeeded.len(),
+            report.elapsed
+        );
+        for (path, err) in report.failed {
+            log::warn!("Couldn't hash {}: {}", path.display(), err);
+        }
+        Ok(Self::build_from(
+            root_path, entries, hashes, None, symlinks, None, 1,
+        )
+        .value)
+    }
+
+    /// Same as [`ResourceIndex::build`], but checks `cancelled` before
+    /// scanning every file and stops as soon as it is set.
+    ///
+    /// A cancelled build returns the files scanned so far,
+    /// [`ResourceIndex::update_all`] indexes the rest later.
+    pub fn build_cancellable<P: AsRef<Path>>(
+        root_path: P,
+        cancelled: &AtomicBool,
+    ) -> Partial<Self> {
+        Self::build_with(
+            root_path,
+            None,
+            SymlinkPolicy::default(),
+            Some(cancelled),
+            1,
+        )
+    }
+
+    /// Index of `root_path` without any resource
+    pub(crate) fn empty(root_path: PathBuf) -> Self {
+        ResourceIndex {
             id2path: HashMap::new(),
             path2id: HashMap::new(),
             collisions: HashMap::new(),
-            root: root_path.clone(),
-        };
-
-        // We should not return early in case of missing files
-        let lines = BufReader::new(file).lines();
-        for line in lines {
-            let line = line?;
+            root: root_path,
+            by_path: BTreeMap::new(),
+            symlinks: SymlinkPolicy::default(),
+            paths: PathNormalization::default(),
+            walk_threads: 1,
+            hashes: HashCache::default(),
+            subscribers: Subscribers::default(),
+            autostore: AutoStore::default(),
+            caches: CacheInvalidation::default(),
+        }
+    }
 
-            let mut parts = line.split(' ');
+    /// Cache of the ids of the files, for files hashed outside the index
+    pub(crate) fn hashes_mut(&mut self) -> &mut HashCache<Id> {
+        &mut self.hashes
+    }
 
-            let modified = {
-                let str = parts.next().ok_or(ArklibError::Parse)?;
-                UNIX_EPOCH.add(Duration::from_millis(
-                    str.parse().map_err(|_| ArklibError::Parse)?,
-                ))
-            };
+    pub fn load<P: AsRef<Path>>(root_path: P) -> Result<Self> {
+        let root_path: PathBuf = root_path.as_ref().to_owned();
+        let entries = read_stored_entries(&root_path)?;
+        let mut index = Self::from_stored_entries(root_path, entries);
+        index.hashes = HashCache::load(&index.root);
+        Ok(index)
+    }
 
-            let id = {
-                let str = parts.next().ok_or(ArklibError::Parse)?;
-                Id::from_str(str).map_err(|_| ArklibError::Parse)?
-            };
+    /// Index of the entries which still exist under `root_path`,
+    /// paths of the entries being relative to it
+    fn from_stored_entries(
+        root_path: PathBuf,
+        entries: Vec<(PathBuf, IndexEntry<Id>)>,
+    ) -> Self {
+        let mut index = Self::empty(root_path.clone());
 
-            let path: String =
-                itertools::Itertools::intersperse(parts, " ").collect();
-            let path: PathBuf = root_path.join(Path::new(&path));
+        // We should not return early in case of missing files
+        for (path, entry) in entries {
+            let path: PathBuf = root_path.join(path);
             match CanonicalPathBuf::canonicalize(&path) {
                 Ok(path) => {
-                    log::trace!("[load] {} -> {}", id, path.display());
-                    index.insert_entry(path, IndexEntry { modified, id });
+                    log::trace!("[load] {} -> {}", entry.id, path.display());
+                    index.insert_entry(path, entry);
                 }
                 Err(_) => {
                     log::warn!("File {} not found", path.display());
@@ -115,7 +629,25 @@ impl<Id: ResourceId> ResourceIndex<Id> {
             }
         }
 
-        Ok(index)
+        index
+    }
+
+    /// Entries of the index with paths relative to the root
+    pub(crate) fn stored_entries(
+        &self,
+    ) -> Result<Vec<(PathBuf, IndexEntry<Id>)>> {
+        let mut entries = Vec::with_capacity(self.path2id.len());
+        for (path, entry) in self.path2id.iter() {
+            // canonical paths may be verbatim on Windows, unlike the root
+            let path = pathdiff::diff_paths(
+                simplified(path.as_path()),
+                simplified(&self.root),
+            )
+            .ok_or(ArklibError::Path("Couldn't calculate path diff".into()))?;
+            entries.push((self.paths.stored(&path), entry.clone()));
+        }
+        entries.sort_by(|(_, a), (_, b)| a.cmp(b));
+        Ok(entries)
     }
 
     pub fn store(&self) -> Result<()> {
@@ -123,40 +655,7 @@ impl<Id: ResourceId> ResourceIndex<Id> {
 
         let start = SystemTime::now();
 
-        let index_path = self
-            .root
-            .to_owned()
-            .join(ARK_FOLDER)
-            .join(INDEX_PATH);
-
-        let ark_dir = index_path.parent().unwrap();
-        fs::create_dir_all(ark_dir)?;
-
-        let mut file = File::create(index_path)?;
-
-        let mut path2id: Vec<(&CanonicalPathBuf, &IndexEntry<Id>)> =
-            self.path2id.iter().collect();
-        path2id.sort_by_key(|(_, entry)| *entry);
-
-        for (path, entry) in path2id.iter() {
-            log::trace!("[store] {} by path {}", entry.id, path.display());
-
-            let timestamp = entry
-                .modified
-                .duration_since(UNIX_EPOCH)
-                .map_err(|_| {
-                    ArklibError::Other(anyhow!("Error using duration since"))
-                })?
-                .as_millis();
-
-            let path =
-                pathdiff::diff_paths(path.to_str().unwrap(), self.root.clone())
-                    .ok_or(ArklibError::Path(
-                        "Couldn't calculate path diff".into(),
-                    ))?;
-
-            writeln!(file, "{} {} {}", timestamp, entry.id, path.display())?;
-        }
+        self.snapshot()?.write()?;
 
         log::trace!(
             "Storing the index took {:?}",
@@ -167,6 +666,67 @@ impl<Id: ResourceId> ResourceIndex<Id> {
         Ok(())
     }
 
+    /// Drop the entries of files which don't exist anymore, recount the
+    /// collisions of the remaining paths and rewrite the stored index
+    /// with these entries only.
+    ///
+    /// Unlike [`ResourceIndex::update_all`], the root is not walked,
+    /// so new and modified files are left for the next update.
+    pub fn compact(&mut self) -> Result<IndexUpdate<Id>> {
+        let stale: Vec<CanonicalPathBuf> = self
+            .path2id
+            .keys()
+            .filter(|path| !path.is_file())
+            .cloned()
+            .collect();
+        for path in stale.iter() {
+            log::debug!("[compact] {} is gone", path.display());
+            self.remove_path(path.as_canonical_path());
+        }
+
+        let mut counts: HashMap<Id, usize> = HashMap::new();
+        for entry in self.path2id.values() {
+            *counts.entry(entry.id.clone()).or_default() += 1;
+        }
+        let deleted: HashSet<Id> = self
+            .id2path
+            .keys()
+            .filter(|id| !counts.contains_key(id))
+            .cloned()
+            .collect();
+
+        // every id must map to one of its own paths
+        self.id2path.retain(|id, path| {
+            self.path2id
+                .get(path)
+                .map_or(false, |entry| &entry.id == id)
+        });
+        for resource in self.by_path.values() {
+            self.id2path
+                .entry(resource.id.clone())
+                .or_insert_with(|| resource.path.clone());
+        }
+        self.collisions = counts
+            .into_iter()
+            .filter(|(_, count)| *count > 1)
+            .collect();
+
+        self.changed();
+        if self.autostore.is_enabled() {
+            // the thread storing the index must not write an older snapshot
+            // over this one
+            self.autostore.changed();
+            self.flush()?;
+        } else {
+            self.store()?;
+        }
+
+        Ok(IndexUpdate {
+            added: HashMap::new(),
+            deleted,
+        })
+    }
+
     pub fn provide<P: AsRef<Path>>(root_path: P) -> Result<Self> {
         match Self::load(&root_path) {
             Ok(mut index) => {
@@ -201,15 +761,143 @@ impl<Id: ResourceId> ResourceIndex<Id> {
     }
 
     pub fn update_all(&mut self) -> Result<IndexUpdate<Id>> {
+        self.update_all_with(None, None)
+            .map(|update| update.value)
+    }
+
+    /// Same as [`ResourceIndex::update_all`], reporting to `progress`
+    /// after every new or modified file is scanned
+    pub fn update_all_with_progress(
+        &mut self,
+        progress: &mut dyn ProgressHandler,
+    ) -> Result<IndexUpdate<Id>> {
+        self.update_all_with(Some(progress), None)
+            .map(|update| update.value)
+    }
+
+    /// Same as [`ResourceIndex::update_all`], but checks `cancelled`
+    /// before scanning every new or modified file.
+    ///
+    /// A cancelled update still applies the deletions and the files
+    /// scanned so far, the other files keep their previous state.
+    pub fn update_all_cancellable(
+        &mut self,
+        cancelled: &AtomicBool,
+    ) -> Result<Partial<IndexUpdate<Id>>> {
+        self.update_all_with(None, Some(cancelled))
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "update_all",
+            skip_all,
+            fields(
+                root = %self.root.display(),
+                entries = tracing::field::Empty,
+                added = tracing::field::Empty,
+                deleted = tracing::field::Empty,
+                elapsed_ms = tracing::field::Empty,
+            )
+        )
+    )]
+    fn update_all_with(
+        &mut self,
+        progress: Option<&mut dyn ProgressHandler>,
+        cancelled: Option<&AtomicBool>,
+    ) -> Result<Partial<IndexUpdate<Id>>> {
+        #[cfg(feature = "tracing")]
+        let start = std::time::Instant::now();
+
         log::debug!("Updating the index");
         log::trace!("[update] known paths: {:?}", self.path2id.keys());
 
-        let curr_entries = discover_paths(self.root.clone());
+        let curr_entries = discover_paths_under(
+            &self.root,
+            &self.root,
+            self.symlinks,
+            self.walk_threads,
+        );
+        let prev_paths: Paths = self.path2id.keys().cloned().collect();
+        let update =
+            self.apply_changes(prev_paths, curr_entries, progress, cancelled);
+        self.changed();
+
+        #[cfg(feature = "tracing")]
+        tracing::Span::current()
+            .record("entries", self.path2id.len())
+            .record("added", update.value.added.len())
+            .record("deleted", update.value.deleted.len())
+            .record("elapsed_ms", start.elapsed().as_millis() as u64);
+
+        Ok(update)
+    }
+
+    /// Same as [`ResourceIndex::update_all`], but only the folder or file
+    /// `path` is scanned, e.g. when the app knows that only `Downloads`
+    /// changed.
+    ///
+    /// Relative paths are resolved against the root of the index.
+    /// A path which doesn't exist anymore is treated as an empty folder.
+    pub fn update_subtree<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+    ) -> Result<IndexUpdate<Id>> {
+        let subtree = self.normalize(path.as_ref());
+        let root = fs::canonicalize(&self.root)?;
+        if !subtree.starts_with(&root) {
+            return Err(ArklibError::Path(format!(
+                "{} is outside of the index root",
+                subtree.display()
+            )));
+        }
+        log::debug!("Updating the index under {}", subtree.display());
+
+        // hidden and ignored folders are not part of the index
+        let excluded = subtree
+            .strip_prefix(&root)
+            .map(|relative| {
+                relative.components().any(|component| {
+                    matches!(component, Component::Normal(name)
+                        if name.to_string_lossy().starts_with('.'))
+                })
+            })
+            .unwrap_or(false)
+            || IgnoreRules::load(&root).is_ignored(&subtree, true);
+        let curr_entries = if subtree.exists() && !excluded {
+            let threads = if subtree.is_dir() {
+                self.walk_threads
+            } else {
+                1
+            };
+            discover_paths_under(&root, &subtree, self.symlinks, threads)
+        } else {
+            HashMap::new()
+        };
+        let prev_paths: Paths = self
+            .resources_under(&subtree)
+            .into_iter()
+            .map(|resource| resource.path.clone())
+            .collect();
+
+        let update = self.apply_changes(prev_paths, curr_entries, None, None);
+        self.changed();
+        Ok(update.value)
+    }
 
+    /// Bring the paths `prev_paths` of the index to the state
+    /// found on the disk, `curr_entries` being all the files
+    /// currently present in the same part of the tree
+    fn apply_changes(
+        &mut self,
+        prev_paths: Paths,
+        curr_entries: HashMap<CanonicalPathBuf, DirEntry>,
+        progress: Option<&mut dyn ProgressHandler>,
+        cancelled: Option<&AtomicBool>,
+    ) -> Partial<IndexUpdate<Id>> {
         //assuming that collections manipulation is
         // quicker than asking `path.exists()` for every path
         let curr_paths: Paths = curr_entries.keys().cloned().collect();
-        let prev_paths: Paths = self.path2id.keys().cloned().collect();
         let preserved_paths: Paths = curr_paths
             .intersection(&prev_paths)
             .cloned()
@@ -256,12 +944,14 @@ impl<Id: ResourceId> ResourceIndex<Id> {
                                 false
                             }
                             Ok(curr_modified) => {
-                                let elapsed = curr_modified
-                                    .duration_since(prev_modified)
-                                    .unwrap();
-
+                                // the timestamp can also move backwards,
+                                // e.g. when the file is restored from a backup
+                                let elapsed =
+                                    curr_modified.duration_since(prev_modified);
                                 let was_updated =
-                                    elapsed >= RESOURCE_UPDATED_THRESHOLD;
+                                    elapsed.as_ref().map_or(true, |elapsed| {
+                                        *elapsed >= RESOURCE_UPDATED_THRESHOLD
+                                    });
                                 if was_updated {
                                     log::trace!(
                                         "[update] modified {} by path {}
@@ -284,16 +974,38 @@ impl<Id: ResourceId> ResourceIndex<Id> {
             })
             .collect();
 
+        let updated: Paths = updated_paths.keys().cloned().collect();
+        let mut progress = ProgressTracker::new(
+            progress,
+            updated_paths
+                .values()
+                .chain(created_paths.values()),
+        );
+        let (updated_entries, skipped) = scan_entries(
+            updated_paths,
+            &mut progress,
+            &mut self.hashes,
+            cancelled,
+        );
+        log::debug!("Checking added paths");
+        let (created_entries, skipped_created) = scan_entries(
+            created_paths,
+            &mut progress,
+            &mut self.hashes,
+            cancelled,
+        );
+        let complete = skipped.is_empty() && skipped_created.is_empty();
+
         let mut deleted: HashSet<Id> = HashSet::new();
 
-        // treating both deleted and updated paths as deletions
+        // treating both deleted and updated paths as deletions,
+        // the updated paths which haven't been scanned keep their entries
         prev_paths
             .difference(&preserved_paths)
+            .chain(updated.difference(&skipped))
             .cloned()
-            .chain(updated_paths.keys().cloned())
             .for_each(|path| {
-                if let Some(entry) =
-                    self.path2id.remove(path.as_canonical_path())
+                if let Some(entry) = self.remove_path(path.as_canonical_path())
                 {
                     let k = self.collisions.remove(&entry.id).unwrap_or(1);
                     if k > 1 {
@@ -312,15 +1024,11 @@ impl<Id: ResourceId> ResourceIndex<Id> {
                 }
             });
 
-        let added: HashMap<CanonicalPathBuf, IndexEntry<Id>> =
-            scan_entries(updated_paths)
-                .into_iter()
-                .chain({
-                    log::debug!("Checking added paths");
-                    scan_entries(created_paths).into_iter()
-                })
-                .filter(|(_, entry)| !self.id2path.contains_key(&entry.id))
-                .collect();
+        let added: HashMap<CanonicalPathBuf, IndexEntry<Id>> = updated_entries
+            .into_iter()
+            .chain(created_entries)
+            .filter(|(_, entry)| !self.id2path.contains_key(&entry.id))
+            .collect();
 
         for (path, entry) in added.iter() {
             if deleted.contains(&entry.id) {
@@ -341,7 +1049,10 @@ impl<Id: ResourceId> ResourceIndex<Id> {
             .map(|(path, entry)| (path, entry.id))
             .collect();
 
-        Ok(IndexUpdate { deleted, added })
+        Partial {
+            value: IndexUpdate { deleted, added },
+            complete,
+        }
     }
 
     // the caller must ensure that:
@@ -359,6 +1070,12 @@ impl<Id: ResourceId> ResourceIndex<Id> {
             ));
         }
 
+        if IgnoreRules::load(&self.root).is_ignored(path.as_ref(), false) {
+            return Err(ArklibError::Path(
+                "The path is excluded by .arkignore".into(),
+            ));
+        }
+
         let path_buf = CanonicalPathBuf::canonicalize(path)?;
         let path = path_buf.as_canonical_path();
 
@@ -368,7 +1085,7 @@ impl<Id: ResourceId> ResourceIndex<Id> {
                     "Couldn't to retrieve file metadata".into(),
                 ));
             }
-            Ok(metadata) => match scan_entry(path, metadata) {
+            Ok(metadata) => match scan_entry(path, metadata, None) {
                 Err(_) => {
                     return Err(ArklibError::Path(
                         "The path points to a directory or empty file".into(),
@@ -385,7 +1102,8 @@ impl<Id: ResourceId> ResourceIndex<Id> {
                     added.insert(path_buf.clone(), id.clone());
 
                     self.id2path.insert(id, path_buf.clone());
-                    self.path2id.insert(path_buf, new_entry);
+                    self.insert_path(path_buf, new_entry);
+                    self.changed();
 
                     Ok(IndexUpdate {
                         added,
@@ -405,6 +1123,16 @@ impl<Id: ResourceId> ResourceIndex<Id> {
         &mut self,
         path: &dyn AsRef<Path>,
         old_id: Id,
+    ) -> Result<IndexUpdate<Id>> {
+        let update = self.replace_one(path, old_id);
+        self.changed();
+        update
+    }
+
+    fn replace_one(
+        &mut self,
+        path: &dyn AsRef<Path>,
+        old_id: Id,
     ) -> Result<IndexUpdate<Id>> {
         log::debug!("Updating a single entry in the index");
 
@@ -428,7 +1156,7 @@ impl<Id: ResourceId> ResourceIndex<Id> {
                 self.forget_path(path, old_id)
             }
             Ok(metadata) => {
-                match scan_entry(path, metadata) {
+                match scan_entry(path, metadata, None) {
                     Err(_) => {
                         // a directory or empty file exists by the path
                         self.forget_path(path, old_id)
@@ -480,19 +1208,20 @@ impl<Id: ResourceId> ResourceIndex<Id> {
     pub fn forget_id(&mut self, old_id: Id) -> Result<IndexUpdate<Id>> {
         let old_path = self
             .path2id
-            .drain()
+            .iter()
             .filter_map(|(k, v)| {
                 if v.id == old_id {
-                    Some(k)
+                    Some(k.clone())
                 } else {
                     None
                 }
             })
             .collect_vec();
         for p in old_path {
-            self.path2id.remove(&p);
+            self.remove_path(&p);
         }
         self.id2path.remove(&old_id);
+        self.changed();
         let mut deleted = HashSet::new();
         deleted.insert(old_id);
 
@@ -502,7 +1231,22 @@ impl<Id: ResourceId> ResourceIndex<Id> {
         })
     }
 
-    fn insert_entry(&mut self, path: CanonicalPathBuf, entry: IndexEntry<Id>) {
+    pub(crate) fn insert_entry(
+        &mut self,
+        path: CanonicalPathBuf,
+        entry: IndexEntry<Id>,
+    ) {
+        if let Some(indexed) = self.by_path.get(&self.paths.key(&path)) {
+            if indexed.path != path {
+                log::debug!(
+                    "{} is already indexed as {}",
+                    path.display(),
+                    indexed.path.display()
+                );
+                return;
+            }
+        }
+
         log::trace!("[add] {} by path {}", entry.id, path.display());
         let id = entry.clone().id;
 
@@ -516,15 +1260,74 @@ impl<Id: ResourceId> ResourceIndex<Id> {
             self.collisions.insert(id, 2);
         }
 
+        self.insert_path(path, entry);
+    }
+
+    // `path2id` must only be modified through `insert_path`
+    // and `remove_path`, to keep `by_path` in sync
+    fn insert_path(&mut self, path: CanonicalPathBuf, entry: IndexEntry<Id>) {
+        self.subscribers.inserted(&path, &entry.id);
+        self.autostore.changed();
+        self.by_path.insert(
+            self.paths.key(path.as_path()),
+            IndexedResource {
+                path: path.clone(),
+                id: entry.id.clone(),
+                modified: entry.modified,
+            },
+        );
         self.path2id.insert(path, entry);
     }
 
+    fn remove_path(&mut self, path: &CanonicalPath) -> Option<IndexEntry<Id>> {
+        self.by_path
+            .remove(&self.paths.key(path.as_path()));
+        let (path, entry) = self.path2id.remove_entry(path)?;
+        self.subscribers.removed(&path, &entry.id);
+        self.caches.removed(&entry.id);
+        self.autostore.changed();
+        Some(entry)
+    }
+
+    /// Notify the subscribers, invalidate the caches and hand the index
+    /// over to be stored, after the index has been modified
+    fn changed(&mut self) {
+        self.subscribers.notify();
+        let id2path = &self.id2path;
+        self.caches
+            .invalidate(&self.root, |id| id2path.contains_key(id));
+        self.hand_over_changes();
+    }
+
+    /// Hand the content of the index over to the thread storing it,
+    /// if the index is stored automatically and has changed
+    fn hand_over_changes(&mut self) {
+        if !self.autostore.take_modified() {
+            return;
+        }
+        match self.snapshot() {
+            Ok(snapshot) => self.autostore.store(snapshot),
+            Err(err) => log::error!("Failed to store the index: {}", err),
+        }
+    }
+
+    /// Content of the stored index and hash cache
+    fn snapshot(&self) -> Result<Snapshot> {
+        Ok(Snapshot {
+            root: self.root.clone(),
+            index: encode_stored_entries(&self.stored_entries()?)?,
+            hashes: self.hashes.encode(&self.root, |path| {
+                self.by_path.contains_key(&self.paths.key(path))
+            })?,
+        })
+    }
+
     fn forget_path(
         &mut self,
         path: &CanonicalPath,
         old_id: Id,
     ) -> Result<IndexUpdate<Id>> {
-        self.path2id.remove(path);
+        self.remove_path(path);
 
         if let Some(collisions) = self.collisions.get_mut(&old_id) {
             debug_assert!(
@@ -578,17 +1381,205 @@ impl<Id: ResourceId> ResourceIndex<Id> {
     }
 }
 
-fn discover_paths<P: AsRef<Path>>(
+/// Read the entries of the index stored in the `.ark` folder of `root`,
+/// including entries of files which don't exist anymore.
+///
+/// Both the text format and the binary format are read,
+/// the latter requires the `binary-index` feature.
+/// Paths are relative to the root.
+pub(crate) fn read_stored_entries<Id: ResourceId>(
+    root: &Path,
+) -> Result<Vec<(PathBuf, IndexEntry<Id>)>> {
+    let index_path: PathBuf = root.join(ARK_FOLDER).join(INDEX_PATH);
+    log::info!("Loading the index from file {}", index_path.display());
+    let content = fs::read(&index_path)?;
+
+    match content.strip_prefix(BINARY_INDEX_MAGIC) {
+        Some(binary) => read_binary_entries(binary),
+        None => read_text_entries(&content),
+    }
+}
+
+#[cfg(feature = "binary-index")]
+fn read_binary_entries<Id: ResourceId>(
+    content: &[u8],
+) -> Result<Vec<(PathBuf, IndexEntry<Id>)>> {
+    let (version, entries): (u32, Vec<(PathBuf, IndexEntry<Id>)>) =
+        bincode::deserialize(content).map_err(|_| ArklibError::Parse)?;
+    if version != BINARY_INDEX_VERSION {
+        return Err(ArklibError::Other(anyhow!(
+            "Unsupported index version {}",
+            version
+        )));
+    }
+    Ok(entries)
+}
+
+#[cfg(not(feature = "binary-index"))]
+fn read_binary_entries<Id: ResourceId>(
+    _content: &[u8],
+) -> Result<Vec<(PathBuf, IndexEntry<Id>)>> {
+    Err(ArklibError::Other(anyhow!(
+        "The index is stored in the binary format, \
+         which requires the `binary-index` feature"
+    )))
+}
+
+/// One line per entry: timestamp in milliseconds, id and path
+fn read_text_entries<Id: ResourceId>(
+    content: &[u8],
+) -> Result<Vec<(PathBuf, IndexEntry<Id>)>> {
+    let mut entries = Vec::new();
+    for line in content.lines() {
+        let line = line?;
+
+        let mut parts = line.split(' ');
+
+        let modified = {
+            let str = parts.next().ok_or(ArklibError::Parse)?;
+            UNIX_EPOCH
+                .checked_add(Duration::from_millis(
+                    str.parse().map_err(|_| ArklibError::Parse)?,
+                ))
+                .ok_or(ArklibError::Parse)?
+        };
+
+        let id = {
+            let str = parts.next().ok_or(ArklibError::Parse)?;
+            Id::from_str(str).map_err(|_| ArklibError::Parse)?
+        };
+
+        let path: String =
+            itertools::Itertools::intersperse(parts, " ").collect();
+        entries.push((PathBuf::from(path), IndexEntry { modified, id }));
+    }
+
+    Ok(entries)
+}
+
+/// Overwrite the index stored in the `.ark` folder of `root`,
+/// paths must be relative to the root.
+pub(crate) fn write_stored_entries<Id: ResourceId>(
+    root: &Path,
+    entries: &[(PathBuf, IndexEntry<Id>)],
+) -> Result<()> {
+    write_index_file(root, &encode_stored_entries(entries)?)
+}
+
+/// Content of the stored index with `entries`,
+/// paths must be relative to the root.
+///
+/// The binary format is written if the `binary-index` feature is enabled.
+pub(crate) fn encode_stored_entries<Id: ResourceId>(
+    entries: &[(PathBuf, IndexEntry<Id>)],
+) -> Result<Vec<u8>> {
+    let mut content = Vec::new();
+
+    #[cfg(feature = "binary-index")]
+    {
+        let encoded = bincode::serialize(&(BINARY_INDEX_VERSION, entries))
+            .map_err(|err| ArklibError::Other(anyhow!(err)))?;
+        content.write_all(BINARY_INDEX_MAGIC)?;
+        content.write_all(&encoded)?;
+    }
+
+    #[cfg(not(feature = "binary-index"))]
+    for (path, entry) in entries {
+        log::trace!("[store] {} by path {}", entry.id, path.display());
+
+        let timestamp = entry
+            .modified
+            .duration_since(UNIX_EPOCH)
+            .map_err(|_| {
+                ArklibError::Other(anyhow!("Error using duration since"))
+            })?
+            .as_millis();
+
+        writeln!(content, "{} {} {}", timestamp, entry.id, path.display())?;
+    }
+
+    Ok(content)
+}
+
+/// Overwrite the index stored in the `.ark` folder of `root` with `content`
+pub(crate) fn write_index_file(root: &Path, content: &[u8]) -> Result<()> {
+    let index_path = root.join(ARK_FOLDER).join(INDEX_PATH);
+
+    if let Some(ark_dir) = index_path.parent() {
+        fs::create_dir_all(ark_dir)?;
+    }
+    fs::write(index_path, content)?;
+
+    Ok(())
+}
+
+/// The index is serialized as its root and its entries relative to the
+/// root, entries of files which don't exist anymore are skipped when
+/// deserializing, as in [`ResourceIndex::load`]
+#[cfg(feature = "binary-index")]
+impl<Id: ResourceId> serde::Serialize for ResourceIndex<Id> {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        use serde::ser::Error;
+
+        let entries = self.stored_entries().map_err(S::Error::custom)?;
+        (&self.root, entries).serialize(serializer)
+    }
+}
+
+#[cfg(feature = "binary-index")]
+impl<'de, Id: ResourceId> serde::Deserialize<'de> for ResourceIndex<Id> {
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Self, D::Error> {
+        let (root, entries): (PathBuf, Vec<(PathBuf, IndexEntry<Id>)>) =
+            serde::Deserialize::deserialize(deserializer)?;
+        Ok(Self::from_stored_entries(root, entries))
+    }
+}
+
+pub(crate) fn discover_paths<P: AsRef<Path>>(
     root_path: P,
+    symlinks: SymlinkPolicy,
+) -> HashMap<CanonicalPathBuf, DirEntry> {
+    discover_paths_under(root_path.as_ref(), root_path.as_ref(), symlinks, 1)
+}
+
+/// Files of the folder `dir`, located under the root of the index,
+/// to which the `.arkignore` rules of the root apply
+fn discover_paths_under(
+    root_path: &Path,
+    dir: &Path,
+    symlinks: SymlinkPolicy,
+    threads: usize,
 ) -> HashMap<CanonicalPathBuf, DirEntry> {
-    log::debug!(
-        "Discovering all files under path {}",
-        root_path.as_ref().display()
-    );
+    log::debug!("Discovering all files under path {}", dir.display());
+    // deep folders exceed `MAX_PATH` on Windows
+    let root_path = &extended_length(root_path);
+    let dir = &extended_length(dir);
+
+    // ignored folders are not walked
+    let ignore = IgnoreRules::load(root_path);
+    let links = SymlinkFilter::new(symlinks, root_path);
+    let keep = |entry: &DirEntry| {
+        !is_hidden(entry)
+            && links.allows(entry)
+            && !ignore.is_ignored(entry.path(), entry.file_type().is_dir())
+    };
+
+    #[cfg(feature = "parallel-walk")]
+    if threads > 1 {
+        return crate::parallel_walk::walk(dir, symlinks, &keep, threads);
+    }
+    #[cfg(not(feature = "parallel-walk"))]
+    let _ = threads;
 
-    WalkDir::new(root_path)
+    symlinks
+        .walker(dir)
         .into_iter()
-        .filter_entry(|entry| !is_hidden(entry))
+        .filter_entry(keep)
         .filter_map(|result| match result {
             Ok(entry) => {
                 let path = entry.path();
@@ -608,6 +1599,10 @@ fn discover_paths<P: AsRef<Path>>(
                     None
                 }
             }
+            Err(msg) if msg.loop_ancestor().is_some() => {
+                log::debug!("Skipping symlink cycle: {}", msg);
+                None
+            }
             Err(msg) => {
                 log::error!("Error during walking: {}", msg);
                 None
@@ -616,9 +1611,10 @@ fn discover_paths<P: AsRef<Path>>(
         .collect()
 }
 
-fn scan_entry<Id>(
+pub(crate) fn scan_entry<Id>(
     path: &CanonicalPath,
     metadata: Metadata,
+    hashes: Option<&mut HashCache<Id>>,
 ) -> Result<IndexEntry<Id>>
 where
     Id: ResourceId,
@@ -635,38 +1631,79 @@ where
         ))?;
     }
 
-    let id = Id::from_path(path)?;
     let modified = metadata.modified()?;
+    let id = match hashes {
+        Some(hashes) => match hashes.get(path.as_path(), size, modified) {
+            Some(id) => id.clone(),
+            None => {
+                let id = Id::from_path(path)?;
+                hashes.insert(
+                    path.as_path().to_owned(),
+                    size,
+                    modified,
+                    id.clone(),
+                );
+                id
+            }
+        },
+        None => Id::from_path(path)?,
+    };
 
     Ok(IndexEntry { modified, id })
 }
 
+/// Scan the entries until `cancelled` is set,
+/// also returning the paths which haven't been scanned
 fn scan_entries<Id>(
     entries: HashMap<CanonicalPathBuf, DirEntry>,
-) -> HashMap<CanonicalPathBuf, IndexEntry<Id>>
+    progress: &mut ProgressTracker,
+    hashes: &mut HashCache<Id>,
+    cancelled: Option<&AtomicBool>,
+) -> (HashMap<CanonicalPathBuf, IndexEntry<Id>>, Paths)
 where
     Id: ResourceId,
 {
-    entries
-        .into_iter()
-        .filter_map(|(path_buf, entry)| {
-            let metadata = entry.metadata().ok()?;
-
-            let path = path_buf.as_canonical_path();
-            let result = scan_entry(path, metadata);
-            match result {
-                Err(msg) => {
-                    log::error!(
-                        "Couldn't retrieve metadata for {}:\n{}",
-                        path.display(),
-                        msg
-                    );
-                    None
-                }
-                Ok(entry) => Some((path_buf, entry)),
-            }
-        })
-        .collect()
+    let mut scanned = HashMap::new();
+    let mut skipped = Paths::new();
+    let mut links = HardLinks::default();
+    for (path_buf, entry) in entries {
+        if cancelled.map_or(false, |flag| flag.load(Ordering::Relaxed)) {
+            skipped.insert(path_buf);
+            continue;
+        }
+        let size = progress.size(&entry);
+        let path = path_buf.clone();
+        if let Some((path, entry)) = links.scan(path_buf, entry, hashes) {
+            scanned.insert(path, entry);
+        }
+        progress.scanned(path.as_path(), size);
+    }
+    (scanned, skipped)
+}
+
+pub(crate) fn scan_dir_entry<Id>(
+    path_buf: CanonicalPathBuf,
+    entry: DirEntry,
+    hashes: Option<&mut HashCache<Id>>,
+) -> Option<(CanonicalPathBuf, IndexEntry<Id>)>
+where
+    Id: ResourceId,
+{
+    let metadata = entry.metadata().ok()?;
+
+    let path = path_buf.as_canonical_path();
+    let result = scan_entry(path, metadata, hashes);
+    match result {
+        Err(msg) => {
+            log::error!(
+                "Couldn't retrieve metadata for {}:\n{}",
+                path.display(),
+                msg
+            );
+            None
+        }
+        Ok(entry) => Some((path_buf, entry)),
+    }
 }
 
 fn is_hidden(entry: &DirEntry) -> bool {
@@ -679,7 +1716,10 @@ fn is_hidden(entry: &DirEntry) -> bool {
 
 #[cfg(test)]
 mod tests {
-    use crate::index::{discover_paths, IndexEntry};
+    use crate::index::{discover_paths, IndexEntry, IndexedResource, Partial};
+    use crate::normalization::PathNormalization;
+    use crate::progress::Progress;
+    use crate::symlink::SymlinkPolicy;
     use crate::ResourceIndex;
     use canonical_path::CanonicalPathBuf;
     use dev_hash::Crc32;
@@ -690,8 +1730,11 @@ mod tests {
     #[cfg(target_family = "unix")]
     use std::os::unix::fs::PermissionsExt;
 
-    use std::path::PathBuf;
-    use std::time::SystemTime;
+    use std::collections::HashSet;
+    use std::ffi::OsStr;
+    use std::path::{Path, PathBuf};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::time::{Duration, SystemTime};
     use uuid::Uuid;
 
     const FILE_SIZE_1: u64 = 10;
@@ -785,6 +1828,168 @@ mod tests {
         })
     }
 
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn index_build_parallel_should_match_sequential_build() {
+        run_test_and_clean_up(|path| {
+            create_file_at(path.clone(), Some(FILE_SIZE_1), None);
+            create_file_at(path.clone(), Some(FILE_SIZE_1), None);
+            for i in 0..20 {
+                let dir = create_dir_at(path.clone());
+                create_file_at(dir, Some(i + 1), None);
+            }
+
+            let expected: ResourceIndex<Crc32> =
+                ResourceIndex::build(path.clone());
+            for threads in [0, 1, 4] {
+                let actual: ResourceIndex<Crc32> =
+                    ResourceIndex::build_parallel(path.clone(), threads)
+                        .unwrap();
+                assert_eq!(actual.path2id, expected.path2id);
+                assert_eq!(actual.collisions, expected.collisions);
+                assert_eq!(actual.size(), 22);
+                // the ids are cached like in any build
+                for (path, entry) in actual.path2id.iter() {
+                    let size = std::fs::metadata(path.as_path()).unwrap().len();
+                    let cached =
+                        actual
+                            .hashes
+                            .get(path.as_path(), size, entry.modified);
+                    assert_eq!(cached, Some(&entry.id));
+                }
+            }
+        })
+    }
+
+    #[test]
+    fn load_should_read_text_format() {
+        run_test_and_clean_up(|path| {
+            create_file_at(path.clone(), Some(FILE_SIZE_1), Some(FILE_NAME_1));
+            let expected: ResourceIndex<Crc32> =
+                ResourceIndex::build(path.clone());
+            let modified = expected.path2id.values().next().unwrap().modified;
+            let millis = modified
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_millis();
+
+            let ark_dir = path.join(fs_storage::ARK_FOLDER);
+            std::fs::create_dir_all(&ark_dir).unwrap();
+            std::fs::write(
+                ark_dir.join(fs_storage::INDEX_PATH),
+                format!("{} {} {}\n", millis, CRC32_1, FILE_NAME_1),
+            )
+            .unwrap();
+
+            let loaded: ResourceIndex<Crc32> =
+                ResourceIndex::load(path.clone()).unwrap();
+            assert_eq!(loaded.id2path, expected.id2path);
+
+            // Storing converts the index to the enabled format
+            loaded.store().unwrap();
+            let reloaded: ResourceIndex<Crc32> =
+                ResourceIndex::load(path.clone()).unwrap();
+            assert_eq!(reloaded.id2path, expected.id2path);
+        })
+    }
+
+    #[cfg(feature = "binary-index")]
+    #[test]
+    fn index_should_round_trip_in_binary_format() {
+        run_test_and_clean_up(|path| {
+            create_file_at(path.clone(), Some(FILE_SIZE_1), Some(FILE_NAME_1));
+            create_file_at(path.clone(), Some(FILE_SIZE_2), Some(FILE_NAME_2));
+            let index: ResourceIndex<Crc32> =
+                ResourceIndex::build(path.clone());
+
+            index.store().unwrap();
+            let content = std::fs::read(
+                path.join(fs_storage::ARK_FOLDER)
+                    .join(fs_storage::INDEX_PATH),
+            )
+            .unwrap();
+            assert!(content.starts_with(super::BINARY_INDEX_MAGIC));
+            let loaded: ResourceIndex<Crc32> =
+                ResourceIndex::load(path.clone()).unwrap();
+            assert_eq!(loaded, index);
+
+            let bytes = bincode::serialize(&index).unwrap();
+            let deserialized: ResourceIndex<Crc32> =
+                bincode::deserialize(&bytes).unwrap();
+            assert_eq!(deserialized, index);
+        })
+    }
+
+    #[test]
+    fn compact_should_drop_stale_entries() {
+        run_test_and_clean_up(|path| {
+            create_library(&path);
+            std::fs::write(path.join("copy.txt"), "notes").unwrap();
+            let mut index: ResourceIndex<Crc32> = ResourceIndex::build(&path);
+            index.store().unwrap();
+            assert_eq!(index.collisions.len(), 1);
+
+            std::fs::remove_file(path.join("notes.txt")).unwrap();
+            std::fs::remove_file(path.join("photos/2023/a.jpg")).unwrap();
+            let update = index.compact().unwrap();
+            assert_eq!(update.deleted, HashSet::from([Crc32(3904355907)]));
+            assert_eq!(index.len(), 4);
+            assert!(index.collisions.is_empty());
+            let notes = Crc32::from_bytes(b"notes").unwrap();
+            assert!(index.id2path[&notes].ends_with("copy.txt"));
+
+            let loaded: ResourceIndex<Crc32> =
+                ResourceIndex::load(&path).unwrap();
+            assert_eq!(loaded, index);
+        })
+    }
+
+    #[test]
+    fn index_build_cancellable_should_stop_when_cancelled() {
+        run_test_and_clean_up(|path| {
+            create_file_at(path.clone(), Some(FILE_SIZE_1), None);
+            create_file_at(path.clone(), Some(FILE_SIZE_2), None);
+
+            let cancelled = AtomicBool::new(false);
+            let actual: Partial<ResourceIndex<Crc32>> =
+                ResourceIndex::build_cancellable(path.clone(), &cancelled);
+            assert!(actual.complete);
+            assert_eq!(actual.value, ResourceIndex::build(path.clone()));
+
+            cancelled.store(true, Ordering::Relaxed);
+            let actual: Partial<ResourceIndex<Crc32>> =
+                ResourceIndex::build_cancellable(path.clone(), &cancelled);
+            assert!(!actual.complete);
+            assert_eq!(actual.value.size(), 0);
+        })
+    }
+
+    #[test]
+    fn update_all_cancellable_should_keep_unscanned_files() {
+        run_test_and_clean_up(|path| {
+            create_file_at(path.clone(), Some(FILE_SIZE_1), Some(FILE_NAME_1));
+            create_file_at(path.clone(), Some(FILE_SIZE_2), Some(FILE_NAME_2));
+            let mut index: ResourceIndex<Crc32> =
+                ResourceIndex::build(path.clone());
+
+            std::fs::remove_file(path.join(FILE_NAME_1)).unwrap();
+            create_file_at(path.clone(), Some(FILE_SIZE_1), Some(FILE_NAME_3));
+
+            let cancelled = AtomicBool::new(true);
+            let update = index.update_all_cancellable(&cancelled).unwrap();
+            assert!(!update.complete);
+            assert_eq!(update.value.deleted.len(), 1);
+            assert!(update.value.added.is_empty());
+            assert_eq!(index.size(), 1);
+
+            cancelled.store(false, Ordering::Relaxed);
+            let update = index.update_all_cancellable(&cancelled).unwrap();
+            assert!(update.complete);
+            assert_eq!(update.value.added.len(), 1);
+            assert_eq!(index.size(), 2);
+        })
+    }
+
     // resource index update
 
     #[test]
@@ -1031,6 +2236,38 @@ mod tests {
         })
     }
 
+    #[test]
+    fn should_not_index_ignored_paths() {
+        run_test_and_clean_up(|path| {
+            std::fs::write(
+                path.join(crate::ARKIGNORE_FILE),
+                "node_modules/\n*.tmp\n",
+            )
+            .unwrap();
+            let modules = path.join("node_modules");
+            std::fs::create_dir(&modules).unwrap();
+            create_file_at(modules, Some(FILE_SIZE_1), None);
+            create_file_at(path.clone(), Some(FILE_SIZE_1), Some("a.tmp"));
+            create_file_at(path.clone(), Some(FILE_SIZE_2), Some(FILE_NAME_1));
+
+            let mut index: ResourceIndex<Crc32> =
+                ResourceIndex::build(path.clone());
+            assert_eq!(index.size(), 1);
+            assert!(index.id2path.contains_key(&CRC32_2));
+
+            // incremental updates apply the rules too
+            create_file_at(path.clone(), Some(FILE_SIZE_1), Some("b.tmp"));
+            let update = index.update_all().unwrap();
+            assert!(update.added.is_empty());
+            assert!(index.index_new(&path.join("b.tmp")).is_err());
+
+            create_file_at(path.clone(), Some(FILE_SIZE_1), Some(FILE_NAME_2));
+            let update = index.update_all().unwrap();
+            assert_eq!(update.added.len(), 1);
+            assert_eq!(index.size(), 2);
+        })
+    }
+
     #[test]
     fn should_not_index_1_empty_directory() {
         run_test_and_clean_up(|path| {
@@ -1051,7 +2288,7 @@ mod tests {
         run_test_and_clean_up(|path| {
             let mut missing_path = path.clone();
             missing_path.push("missing/directory");
-            let actual = discover_paths(missing_path);
+            let actual = discover_paths(missing_path, SymlinkPolicy::default());
             assert_eq!(actual.len(), 0);
         })
     }
@@ -1091,6 +2328,370 @@ mod tests {
         assert!(new2 > new1);
     }
 
+    fn create_library(root: &Path) {
+        for (path, content) in [
+            ("notes.txt", "notes"),
+            ("photos/2023/a.jpg", "a"),
+            ("photos/2023/b.jpg", "b"),
+            ("photos/2023/trip/c.jpg", "c"),
+            ("photos/2023-backup/d.jpg", "d"),
+        ] {
+            let path = root.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        }
+    }
+
+    fn relative_paths(
+        root: &Path,
+        resources: &[&IndexedResource<Crc32>],
+    ) -> Vec<PathBuf> {
+        let root = root.canonicalize().unwrap();
+        resources
+            .iter()
+            .map(|resource| {
+                resource
+                    .path
+                    .as_path()
+                    .strip_prefix(&root)
+                    .unwrap()
+                    .to_owned()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn resources_under_should_match_whole_components() {
+        run_test_and_clean_up(|path| {
+            create_library(&path);
+            let index: ResourceIndex<Crc32> = ResourceIndex::build(&path);
+
+            let under = index.resources_under("photos/2023");
+            assert_eq!(
+                relative_paths(&path, &under),
+                [
+                    PathBuf::from("photos/2023/a.jpg"),
+                    PathBuf::from("photos/2023/b.jpg"),
+                    PathBuf::from("photos/2023/trip/c.jpg"),
+                ]
+            );
+
+            let under = index.resources_under(path.join("photos/2023/trip"));
+            assert_eq!(
+                relative_paths(&path, &under),
+                [PathBuf::from("photos/2023/trip/c.jpg")]
+            );
+
+            assert_eq!(index.resources_under("photos").len(), 4);
+            assert!(index.resources_under("photos/20").is_empty());
+            assert!(index.resources_under("missing").is_empty());
+        })
+    }
+
+    #[test]
+    fn resources_under_root_should_return_everything() {
+        run_test_and_clean_up(|path| {
+            create_library(&path);
+            let mut index: ResourceIndex<Crc32> = ResourceIndex::build(&path);
+
+            assert_eq!(index.resources_under("").len(), index.size());
+            assert_eq!(index.resources_under(&path).len(), 5);
+
+            std::fs::remove_file(path.join("photos/2023/b.jpg")).unwrap();
+            index.update_all().unwrap();
+            assert_eq!(index.resources_under(&path).len(), 4);
+            assert!(index
+                .get_resource_by_path("photos/2023/b.jpg")
+                .is_none());
+            let notes = index.get_resource_by_path("notes.txt").unwrap();
+            assert_eq!(index.id2path[&notes.id], notes.path);
+        })
+    }
+
+    #[test]
+    fn immediate_children_should_list_folders_and_resources() {
+        run_test_and_clean_up(|path| {
+            create_library(&path);
+            let index: ResourceIndex<Crc32> = ResourceIndex::build(&path);
+
+            let children = index.immediate_children("");
+            assert_eq!(children.folders, [OsStr::new("photos")]);
+            assert_eq!(
+                relative_paths(&path, &children.resources),
+                [PathBuf::from("notes.txt")]
+            );
+
+            let children = index.immediate_children("photos");
+            assert_eq!(
+                children.folders,
+                [OsStr::new("2023"), OsStr::new("2023-backup")]
+            );
+            assert!(children.resources.is_empty());
+
+            let children = index.immediate_children("photos/2023");
+            assert_eq!(children.folders, [OsStr::new("trip")]);
+            assert_eq!(children.resources.len(), 2);
+        })
+    }
+
+    #[test]
+    fn path_normalization_should_merge_spellings() {
+        run_test_and_clean_up(|path| {
+            std::fs::write(path.join("Notes.txt"), "notes").unwrap();
+            std::fs::write(path.join("notes.txt"), "notes").unwrap();
+            let mut index: ResourceIndex<Crc32> = ResourceIndex::build(&path);
+            assert_eq!(index.len(), 2);
+            assert!(!index.contains_path("NOTES.TXT"));
+
+            index.set_path_normalization(PathNormalization {
+                unicode: true,
+                case_fold: true,
+            });
+            assert_eq!(index.len(), 1);
+            assert_eq!(index.collisions.len(), 0);
+            let resource = index.get_resource_by_path("NOTES.TXT").unwrap();
+            assert!(resource.path.ends_with("Notes.txt"));
+
+            // the other spelling is still skipped by updates
+            let update = index.update_all().unwrap();
+            assert!(update.added.is_empty() && update.deleted.is_empty());
+            assert_eq!(index.len(), 1);
+        })
+    }
+
+    #[test]
+    fn iterators_should_borrow_the_entries() {
+        run_test_and_clean_up(|path| {
+            create_library(&path);
+            std::fs::write(path.join("copy.txt"), "notes").unwrap();
+            let index: ResourceIndex<Crc32> = ResourceIndex::build(&path);
+
+            assert_eq!(index.len(), 6);
+            assert!(!index.is_empty());
+            assert_eq!(index.iter_entries().len(), 6);
+            assert_eq!(index.iter_ids().len(), 5);
+
+            let paths: Vec<_> = index.iter_paths().collect();
+            assert!(paths.windows(2).all(|pair| pair[0] < pair[1]));
+            let page: Vec<_> = index.iter_entries().skip(2).take(2).collect();
+            assert_eq!(
+                relative_paths(&path, &page),
+                [
+                    PathBuf::from("photos/2023/a.jpg"),
+                    PathBuf::from("photos/2023/b.jpg"),
+                ]
+            );
+
+            assert!(index.contains_id(&Crc32(3904355907)));
+            assert!(!index.contains_id(&Crc32(0)));
+            assert!(index.contains_path("photos/2023/a.jpg"));
+            assert!(index.contains_path(path.join("copy.txt")));
+            assert!(!index.contains_path("photos/2023"));
+        })
+    }
+
+    #[test]
+    fn queries_should_filter_resources() {
+        run_test_and_clean_up(|path| {
+            create_library(&path);
+            std::fs::write(path.join("report.PDF"), "report").unwrap();
+            let index: ResourceIndex<Crc32> = ResourceIndex::build(&path);
+
+            let pdfs: Vec<_> = index.resources_with_extension("pdf").collect();
+            assert_eq!(
+                relative_paths(&path, &pdfs),
+                [PathBuf::from("report.PDF")]
+            );
+            assert_eq!(index.resources_with_extension(".jpg").count(), 4);
+            assert_eq!(index.resources_with_extension("png").count(), 0);
+
+            let large: Vec<_> = index.resources_in_size_range(2..).collect();
+            assert_eq!(
+                relative_paths(&path, &large),
+                [PathBuf::from("notes.txt"), PathBuf::from("report.PDF")]
+            );
+            assert_eq!(index.resources_in_size_range(..=1).count(), 4);
+            assert_eq!(index.resources_in_size_range(6..6).count(), 0);
+
+            let oldest = index
+                .resources_under("")
+                .iter()
+                .map(|resource| resource.modified)
+                .min()
+                .unwrap();
+            assert_eq!(
+                index.resources_modified_since(oldest).count(),
+                index.size()
+            );
+            let future = SystemTime::now() + Duration::from_secs(60);
+            assert_eq!(index.resources_modified_since(future).count(), 0);
+        })
+    }
+
+    #[test]
+    fn progress_should_be_reported_for_every_file() {
+        run_test_and_clean_up(|path| {
+            create_library(&path);
+            let mut reports = Vec::new();
+            let mut index: ResourceIndex<Crc32> =
+                ResourceIndex::build_with_progress(
+                    &path,
+                    &mut |progress: &Progress| {
+                        reports.push((
+                            progress.files_scanned,
+                            progress.files_total,
+                            progress.bytes_hashed,
+                            progress.bytes_total,
+                        ))
+                    },
+                );
+            assert_eq!(index.size(), 5);
+            assert_eq!(reports.len(), 5);
+            assert_eq!(reports.last(), Some(&(5, 5, 9, 9)));
+            assert!(reports.windows(2).all(|w| w[0].2 <= w[1].2));
+
+            std::fs::write(path.join("e.txt"), "eee").unwrap();
+            let mut paths = Vec::new();
+            index
+                .update_all_with_progress(&mut |progress: &Progress| {
+                    assert_eq!(progress.ratio(), 1.0);
+                    paths.push(progress.current_path.to_owned())
+                })
+                .unwrap();
+            assert_eq!(paths, [path.join("e.txt").canonicalize().unwrap()]);
+        })
+    }
+
+    #[test]
+    #[cfg(target_family = "unix")]
+    fn symlink_policy_should_decide_which_links_are_followed() {
+        use std::os::unix::fs::symlink;
+
+        run_test_and_clean_up(|path| {
+            let outside = std::env::temp_dir().join(Uuid::new_v4().to_string());
+            std::fs::create_dir(&outside).unwrap();
+            std::fs::write(outside.join("c.txt"), "c").unwrap();
+
+            std::fs::create_dir(path.join("inside")).unwrap();
+            std::fs::write(path.join("a.txt"), "a").unwrap();
+            std::fs::write(path.join("inside/b.txt"), "b").unwrap();
+            symlink(path.join("inside"), path.join("link_inside")).unwrap();
+            symlink(&outside, path.join("link_outside")).unwrap();
+            // cycle back to the root
+            symlink(&path, path.join("inside/loop")).unwrap();
+
+            let count = |symlinks| {
+                ResourceIndex::<Crc32>::build_with_symlink_policy(
+                    &path, symlinks,
+                )
+                .size()
+            };
+            assert_eq!(count(SymlinkPolicy::Ignore), 2);
+            assert_eq!(count(SymlinkPolicy::FollowWithinRoot), 2);
+            assert_eq!(count(SymlinkPolicy::FollowAll), 3);
+
+            let mut index = ResourceIndex::<Crc32>::build_with_symlink_policy(
+                &path,
+                SymlinkPolicy::FollowAll,
+            );
+            assert!(index.id2path.contains_key(&Crc32(112844655)));
+            index.set_symlink_policy(SymlinkPolicy::FollowWithinRoot);
+            let update = index.update_all().unwrap();
+            assert_eq!(update.deleted, HashSet::from([Crc32(112844655)]));
+
+            std::fs::remove_dir_all(outside).unwrap();
+        })
+    }
+
+    #[test]
+    fn build_should_reuse_ids_of_unchanged_files() {
+        run_test_and_clean_up(|path| {
+            let file = path.join("a.txt");
+            std::fs::write(&file, "a").unwrap();
+            ResourceIndex::<Crc32>::build(&path)
+                .store()
+                .unwrap();
+
+            // same size and modification time, so the file isn't hashed
+            let modified = std::fs::metadata(&file)
+                .unwrap()
+                .modified()
+                .unwrap();
+            std::fs::write(&file, "b").unwrap();
+            File::options()
+                .write(true)
+                .open(&file)
+                .unwrap()
+                .set_modified(modified)
+                .unwrap();
+            let index = ResourceIndex::<Crc32>::build(&path);
+            assert!(index.contains_id(&Crc32(3904355907)));
+
+            std::fs::write(&file, "bb").unwrap();
+            let index = ResourceIndex::<Crc32>::build(&path);
+            assert!(!index.contains_id(&Crc32(3904355907)));
+        })
+    }
+
+    #[test]
+    fn update_subtree_should_only_scan_the_folder() {
+        run_test_and_clean_up(|path| {
+            create_library(&path);
+            let mut index: ResourceIndex<Crc32> = ResourceIndex::build(&path);
+
+            std::fs::write(path.join("photos/2023/trip/e.jpg"), "e").unwrap();
+            std::fs::remove_file(path.join("photos/2023/a.jpg")).unwrap();
+            std::fs::write(path.join("outside.txt"), "outside").unwrap();
+
+            let update = index.update_subtree("photos/2023").unwrap();
+            assert_eq!(update.deleted, HashSet::from([Crc32(3904355907)]));
+            assert_eq!(update.added.len(), 1);
+            assert!(index
+                .get_resource_by_path("photos/2023/trip/e.jpg")
+                .is_some());
+            // changes outside of the folder are left for later
+            assert!(index
+                .get_resource_by_path("outside.txt")
+                .is_none());
+            assert_eq!(index.size(), 5);
+
+            std::fs::remove_dir_all(path.join("photos/2023-backup")).unwrap();
+            let update = index
+                .update_subtree("photos/2023-backup")
+                .unwrap();
+            assert_eq!(update.deleted.len(), 1);
+            assert_eq!(index.resources_under("photos").len(), 3);
+
+            let update = index.update_subtree("").unwrap();
+            assert_eq!(update.added.len(), 1);
+            assert_eq!(index.size(), 5);
+
+            assert!(index.update_subtree("/").is_err());
+        })
+    }
+
+    #[test]
+    fn update_subtree_should_scan_a_single_file() {
+        run_test_and_clean_up(|path| {
+            create_library(&path);
+            let mut index: ResourceIndex<Crc32> = ResourceIndex::build(&path);
+
+            std::fs::write(path.join("photos/new.txt"), "new").unwrap();
+            std::fs::write(path.join("photos/other.txt"), "other").unwrap();
+            let update = index.update_subtree("photos/new.txt").unwrap();
+            assert_eq!(update.added.len(), 1);
+            assert!(update.deleted.is_empty());
+            assert!(index
+                .get_resource_by_path("photos/other.txt")
+                .is_none());
+
+            std::fs::remove_file(path.join("photos/2023/a.jpg")).unwrap();
+            let update = index.update_subtree("photos/2023/a.jpg").unwrap();
+            assert_eq!(update.deleted, HashSet::from([Crc32(3904355907)]));
+            assert_eq!(index.resources_under("photos/2023").len(), 2);
+        })
+    }
+
     /// Test the performance of `ResourceIndex::build` on a specific directory.
     ///
     /// This test evaluates the performance of building a resource
@@ -0,0 +1,347 @@
+//! Index which can be used before every file has been hashed.
+//!
+//! Walking a folder is much cheaper than hashing its files, so
+//! [`LazyIndex::scan`] only walks the root and records the files found.
+//! Ids are computed when a file is looked up, in batches with
+//! [`LazyIndex::hash_pending`], or by a background thread with
+//! [`LazyIndex::hash_in_background`].
+//!
+//! Ids are taken from the hash cache stored under the root when the
+//! files haven't changed since they were hashed, and the ids computed
+//! are added to it.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+use std::time::SystemTime;
+
+use canonical_path::CanonicalPathBuf;
+
+use data_error::Result;
+use data_resource::ResourceId;
+
+use crate::hash_cache::HashCache;
+use crate::index::{discover_paths, scan_entry, IndexEntry, ResourceIndex};
+use crate::symlink::SymlinkPolicy;
+
+/// File to hash, with the size and modification time it has
+type ToHash = (CanonicalPathBuf, u64, SystemTime);
+
+/// File found by the walk, not hashed yet
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub struct PendingFile {
+    pub size: u64,
+    pub modified: SystemTime,
+}
+
+pub struct LazyIndex<Id: ResourceId> {
+    index: ResourceIndex<Id>,
+    // canonical paths
+    pending: BTreeMap<PathBuf, PendingFile>,
+}
+
+impl<Id: ResourceId> LazyIndex<Id> {
+    /// Walk `root_path` without hashing any file
+    pub fn scan<P: AsRef<Path>>(root_path: P) -> Self {
+        let root_path: PathBuf = root_path.as_ref().to_owned();
+        log::info!("Scanning {} without hashing", root_path.display());
+
+        let pending = discover_paths(&root_path, SymlinkPolicy::default())
+            .into_iter()
+            .filter_map(|(path, entry)| {
+                let metadata = entry.metadata().ok()?;
+                let pending = PendingFile {
+                    size: metadata.len(),
+                    modified: metadata.modified().ok()?,
+                };
+                // empty files are not indexed
+                (pending.size > 0)
+                    .then_some((path.as_path().to_owned(), pending))
+            })
+            .collect();
+
+        let hashes = HashCache::load(&root_path);
+        let mut index = ResourceIndex::empty(root_path);
+        *index.hashes_mut() = hashes;
+        LazyIndex { index, pending }
+    }
+
+    /// Resources hashed so far
+    pub fn index(&self) -> &ResourceIndex<Id> {
+        &self.index
+    }
+
+    /// Files which haven't been hashed yet, sorted by path
+    pub fn pending(&self) -> impl Iterator<Item = (&Path, &PendingFile)> + '_ {
+        self.pending
+            .iter()
+            .map(|(path, file)| (path.as_path(), file))
+    }
+
+    pub fn pending_count(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// Id of the file `path`, hashing it if it hasn't been yet.
+    ///
+    /// Returns `None` if the path wasn't found by the walk.
+    pub fn id_of<P: AsRef<Path>>(&mut self, path: P) -> Result<Option<Id>> {
+        let path = self.index.normalize(path.as_ref());
+        if let Some(resource) = self.index.get_resource_by_path(&path) {
+            return Ok(Some(resource.id.clone()));
+        }
+
+        if self.pending.remove(&path).is_none() {
+            return Ok(None);
+        }
+        self.hash(&path).map(Some)
+    }
+
+    /// Hash at most `limit` pending files, returning the number of files
+    /// still pending. Files which can't be hashed anymore are dropped.
+    pub fn hash_pending(&mut self, limit: usize) -> usize {
+        let batch = self.next_batch(limit);
+        self.record(hash_all(batch));
+        self.pending.len()
+    }
+
+    /// Hash every pending file
+    pub fn into_index(mut self) -> ResourceIndex<Id> {
+        self.hash_pending(usize::MAX);
+        self.index
+    }
+
+    fn hash(&mut self, path: &Path) -> Result<Id> {
+        let path = CanonicalPathBuf::canonicalize(path)?;
+        let metadata = fs::metadata(&path)?;
+        let hashes = Some(self.index.hashes_mut());
+        let entry = scan_entry(path.as_canonical_path(), metadata, hashes)?;
+        let id = entry.id.clone();
+        self.index.insert_entry(path, entry);
+        Ok(id)
+    }
+
+    /// Files to hash among the next `limit` pending files. Files whose id
+    /// is cached are indexed right away, and the other ones stay pending
+    /// until they are [recorded](Self::record).
+    fn next_batch(&mut self, limit: usize) -> Vec<ToHash> {
+        let paths: Vec<PathBuf> =
+            self.pending.keys().take(limit).cloned().collect();
+        let mut batch = Vec::new();
+        for path in paths {
+            let file = CanonicalPathBuf::canonicalize(&path).and_then(|file| {
+                let metadata = fs::metadata(&file)?;
+                Ok((file, metadata.len(), metadata.modified()?))
+            });
+            let (canonical, size, modified) = match file {
+                Ok(file) => file,
+                Err(err) => {
+                    log::warn!("Couldn't hash {}: {}", path.display(), err);
+                    self.pending.remove(&path);
+                    continue;
+                }
+            };
+            // empty files are not indexed
+            if size == 0 {
+                self.pending.remove(&path);
+                continue;
+            }
+            let cached = self
+                .index
+                .hashes_mut()
+                .get(&path, size, modified)
+                .cloned();
+            match cached {
+                Some(id) => {
+                    self.pending.remove(&path);
+                    self.index
+                        .insert_entry(canonical, IndexEntry { modified, id });
+                }
+                None => batch.push((canonical, size, modified)),
+            }
+        }
+        batch
+    }
+
+    /// Index the files hashed, unless they were hashed meanwhile
+    fn record(&mut self, hashed: Vec<(ToHash, Result<Id>)>) {
+        for ((path, size, modified), id) in hashed {
+            if self.pending.remove(path.as_path()).is_none() {
+                continue;
+            }
+            match id {
+                Ok(id) => {
+                    self.index.hashes_mut().insert(
+                        path.as_path().to_owned(),
+                        size,
+                        modified,
+                        id.clone(),
+                    );
+                    self.index
+                        .insert_entry(path, IndexEntry { modified, id });
+                }
+                Err(err) => {
+                    log::warn!("Couldn't hash {}: {}", path.display(), err)
+                }
+            }
+        }
+    }
+}
+
+impl<Id: ResourceId + Send + 'static> LazyIndex<Id> {
+    /// Hash the pending files of `index` on a background thread, `batch`
+    /// files at a time, until none is pending.
+    ///
+    /// The index is only locked to pick the next files and to record
+    /// their ids, not while hashing them, so that it can be used
+    /// meanwhile. Files looked up with [`LazyIndex::id_of`] are hashed
+    /// right away as usual.
+    pub fn hash_in_background(
+        index: &Arc<Mutex<Self>>,
+        batch: usize,
+    ) -> BackgroundHashing {
+        let index = Arc::clone(index);
+        let stop = Arc::new(AtomicBool::new(false));
+        let stopped = Arc::clone(&stop);
+        let thread = thread::spawn(move || {
+            while !stopped.load(Ordering::Relaxed) {
+                let next = lock(&index).next_batch(batch.max(1));
+                if next.is_empty() && lock(&index).is_complete() {
+                    break;
+                }
+                let hashed = hash_all(next);
+                lock(&index).record(hashed);
+            }
+        });
+        BackgroundHashing {
+            stop,
+            thread: Some(thread),
+        }
+    }
+}
+
+/// Thread hashing the pending files of a [`LazyIndex`], see
+/// [`LazyIndex::hash_in_background`].
+///
+/// Dropping it stops the thread once the files being hashed are
+/// recorded.
+pub struct BackgroundHashing {
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl BackgroundHashing {
+    /// Whether every file was hashed, or the thread stopped otherwise
+    pub fn is_finished(&self) -> bool {
+        self.thread
+            .as_ref()
+            .map_or(true, |thread| thread.is_finished())
+    }
+
+    /// Wait for every pending file to be hashed
+    pub fn join(mut self) {
+        self.wait();
+    }
+
+    fn wait(&mut self) {
+        if let Some(thread) = self.thread.take() {
+            if thread.join().is_err() {
+                log::error!("The thread hashing the index panicked");
+            }
+        }
+    }
+}
+
+impl Drop for BackgroundHashing {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        self.wait();
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+fn hash_all<Id: ResourceId>(batch: Vec<ToHash>) -> Vec<(ToHash, Result<Id>)> {
+    batch
+        .into_iter()
+        .map(|file| {
+            let id = Id::from_path(file.0.as_path());
+            (file, id)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dev_hash::Crc32;
+    use tempdir::TempDir;
+
+    #[test]
+    fn files_are_hashed_on_demand() {
+        let dir = TempDir::new("lazy").unwrap();
+        let root = dir.path();
+        fs::write(root.join("a.txt"), "a").unwrap();
+        fs::write(root.join("b.txt"), "b").unwrap();
+        fs::write(root.join("c.txt"), "c").unwrap();
+        fs::write(root.join("empty.txt"), "").unwrap();
+
+        let mut lazy = LazyIndex::<Crc32>::scan(root);
+        assert_eq!(lazy.pending_count(), 3);
+        assert_eq!(lazy.index().size(), 0);
+        assert!(lazy.pending().all(|(_, file)| file.size == 1));
+
+        let id = lazy.id_of("b.txt").unwrap();
+        assert_eq!(id, Some(Crc32(1908338681)));
+        assert_eq!(lazy.pending_count(), 2);
+        assert_eq!(lazy.id_of(root.join("b.txt")).unwrap(), id);
+        assert_eq!(lazy.id_of("missing.txt").unwrap(), None);
+
+        assert_eq!(lazy.hash_pending(1), 1);
+        assert_eq!(lazy.index().size(), 2);
+
+        let index = lazy.into_index();
+        assert_eq!(index, ResourceIndex::build(root));
+    }
+
+    #[test]
+    fn files_are_hashed_in_background() {
+        let dir = TempDir::new("lazy").unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        for name in ["a.txt", "b.txt", "c.txt"] {
+            fs::write(root.join(name), name).unwrap();
+        }
+
+        // the id cached for `a.txt` is reused
+        let path = root.join("a.txt");
+        let modified = fs::metadata(&path).unwrap().modified().unwrap();
+        let mut cache = HashCache::default();
+        cache.insert(path, 5, modified, Crc32(7));
+        let content = cache.encode(&root, |_| true).unwrap();
+        crate::hash_cache::write(&root, &content).unwrap();
+
+        let lazy = Arc::new(Mutex::new(LazyIndex::<Crc32>::scan(&root)));
+        LazyIndex::hash_in_background(&lazy, 2).join();
+        let lazy = Arc::try_unwrap(lazy)
+            .unwrap()
+            .into_inner()
+            .unwrap();
+        assert!(lazy.is_complete());
+
+        let index = lazy.into_index();
+        assert_eq!(index.size(), 3);
+        assert!(index.id2path.contains_key(&Crc32(7)));
+        assert_eq!(*index, ResourceIndex::build(&root));
+    }
+}
@@ -1,3 +1,36 @@
+mod arkignore;
+#[cfg(feature = "tokio")]
+pub mod async_index;
+mod autostore;
+pub mod caches;
+pub mod collisions;
+pub mod diff;
+pub mod events;
+pub mod federated;
+mod hardlink;
+mod hash_cache;
 pub mod index;
+pub mod lazy;
+mod normalization;
+#[cfg(feature = "rayon")]
+mod parallel_hash;
+#[cfg(feature = "parallel-walk")]
+mod parallel_walk;
+pub mod progress;
+mod symlink;
+pub mod verify;
+#[cfg(feature = "watch")]
+pub mod watch;
 
-pub use index::ResourceIndex;
+pub use arkignore::ARKIGNORE_FILE;
+#[cfg(feature = "tokio")]
+pub use async_index::AsyncIndex;
+pub use collisions::Collision;
+pub use diff::IndexDiff;
+pub use events::IndexEvent;
+pub use federated::FederatedIndex;
+pub use index::{IndexedResource, Partial, ResourceIndex};
+pub use lazy::LazyIndex;
+pub use normalization::PathNormalization;
+pub use progress::{Progress, ProgressHandler};
+pub use symlink::SymlinkPolicy;
@@ -0,0 +1,88 @@
+//! Spellings of a path which refer to the same file.
+//!
+//! macOS stores file names decomposed (NFD) while most other systems
+//! keep them composed (NFC), and case-insensitive filesystems accept
+//! a name in any case. The index compares paths after normalizing them,
+//! so a file isn't indexed twice under different spellings.
+
+use std::path::{Path, PathBuf};
+
+use unicode_normalization::UnicodeNormalization;
+
+/// How the index compares paths, see
+/// [`ResourceIndex::set_path_normalization`](crate::ResourceIndex::set_path_normalization)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PathNormalization {
+    /// Compare paths in Unicode NFC and store them that way
+    pub unicode: bool,
+    /// Compare paths ignoring their case
+    pub case_fold: bool,
+}
+
+impl PathNormalization {
+    /// Normalization matching the usual filesystem of the platform,
+    /// APFS on macOS and NTFS on Windows
+    pub fn native() -> Self {
+        PathNormalization {
+            unicode: cfg!(target_os = "macos"),
+            case_fold: cfg!(any(target_os = "macos", windows)),
+        }
+    }
+
+    /// Spelling of `path` written to the stored index
+    pub fn stored(&self, path: &Path) -> PathBuf {
+        if !self.unicode {
+            return path.to_owned();
+        }
+        map_str(path, |path| path.nfc().collect())
+    }
+
+    /// Spelling shared by every path referring to the same file as `path`
+    pub fn key(&self, path: &Path) -> PathBuf {
+        let path = self.stored(path);
+        if !self.case_fold {
+            return path;
+        }
+        map_str(&path, str::to_lowercase)
+    }
+}
+
+// paths which aren't valid UTF-8 are kept as they are
+fn map_str(path: &Path, f: impl FnOnce(&str) -> String) -> PathBuf {
+    match path.to_str() {
+        Some(path) => PathBuf::from(f(path)),
+        None => path.to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spellings_share_a_key() {
+        let composed = Path::new("photos/Caf\u{e9}.jpg");
+        let decomposed = Path::new("photos/Cafe\u{301}.jpg");
+        let upper = Path::new("PHOTOS/CAF\u{c9}.JPG");
+
+        let none = PathNormalization::default();
+        assert_ne!(none.key(composed), none.key(decomposed));
+        assert_eq!(none.stored(decomposed), decomposed);
+
+        let unicode = PathNormalization {
+            unicode: true,
+            case_fold: false,
+        };
+        assert_eq!(unicode.key(composed), unicode.key(decomposed));
+        assert_eq!(unicode.stored(decomposed), composed);
+        assert_ne!(unicode.key(composed), unicode.key(upper));
+
+        let both = PathNormalization {
+            unicode: true,
+            case_fold: true,
+        };
+        assert_eq!(both.key(upper), both.key(decomposed));
+        // the case is kept in the stored index
+        assert_eq!(both.stored(upper), upper);
+    }
+}
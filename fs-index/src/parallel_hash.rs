@@ -0,0 +1,76 @@
+//! Hashing the files of a build on several threads, see
+//! [`ResourceIndex::build_parallel`](crate::ResourceIndex::build_parallel).
+//!
+//! The ids are only put in the hash cache, from which the files are then
+//! scanned one by one like in any build, so that links, progress and
+//! cancellation are handled the same way.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::{Instant, SystemTime};
+
+use canonical_path::CanonicalPathBuf;
+use rayon::prelude::*;
+use rayon::ThreadPool;
+use walkdir::DirEntry;
+
+use data_error::{BulkResult, Result};
+use data_resource::ResourceId;
+
+use crate::hardlink;
+use crate::hash_cache::HashCache;
+
+/// Hash the files of `entries` which are missing from `hashes` on the
+/// threads of `pool`. Files with several links are left to the scan,
+/// which hashes them once.
+///
+/// The report holds the id of every hashed file and the error of every
+/// file which couldn't be hashed, the scan still retries these.
+pub(crate) fn hash_missing<Id>(
+    pool: &ThreadPool,
+    entries: &HashMap<CanonicalPathBuf, DirEntry>,
+    hashes: &mut HashCache<Id>,
+) -> BulkResult<Id, PathBuf>
+where
+    Id: ResourceId + Send,
+{
+    let start = Instant::now();
+    let missing: Vec<(&CanonicalPathBuf, u64, SystemTime)> = entries
+        .iter()
+        .filter_map(|(path, entry)| {
+            let metadata = entry.metadata().ok()?;
+            let modified = metadata.modified().ok()?;
+            let size = metadata.len();
+            let hashed = hashes
+                .get(path.as_path(), size, modified)
+                .is_some();
+            let skipped = !metadata.is_file()
+                || size == 0
+                || hashed
+                || hardlink::is_linked(&metadata);
+            (!skipped).then_some((path, size, modified))
+        })
+        .collect();
+
+    let hashed: Vec<(&CanonicalPathBuf, u64, SystemTime, Result<Id>)> = pool
+        .install(|| {
+            missing
+                .into_par_iter()
+                .map(|(path, size, modified)| {
+                    let id = Id::from_path(path.as_path());
+                    (path, size, modified, id)
+                })
+                .collect()
+        });
+
+    let mut report = BulkResult::new();
+    for (path, size, modified, id) in hashed {
+        let path = path.as_path().to_owned();
+        if let Ok(id) = &id {
+            hashes.insert(path.clone(), size, modified, id.clone());
+        }
+        report.record(path, id);
+    }
+    report.elapsed = start.elapsed();
+    report
+}
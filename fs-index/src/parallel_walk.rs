@@ -0,0 +1,158 @@
+//! Walking the root on several threads, for filesystems where listing
+//! a folder is slow, e.g. network shares.
+//!
+//! Every folder is listed by its own task. Files are collected by
+//! canonical path, so the result doesn't depend on the scheduling and
+//! is the same as the one of a single-threaded walk.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use canonical_path::CanonicalPathBuf;
+use walkdir::DirEntry;
+
+use crate::symlink::SymlinkPolicy;
+
+type Files = Mutex<HashMap<CanonicalPathBuf, DirEntry>>;
+type Keep<'a> = &'a (dyn Fn(&DirEntry) -> bool + Sync);
+
+/// Files found under `dir`, folders and files for which `keep`
+/// returns false being skipped
+pub(crate) fn walk(
+    dir: &Path,
+    symlinks: SymlinkPolicy,
+    keep: Keep,
+    threads: usize,
+) -> HashMap<CanonicalPathBuf, DirEntry> {
+    let pool = match rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build()
+    {
+        Ok(pool) => pool,
+        Err(err) => {
+            log::error!("Couldn't start the walking threads: {}", err);
+            return HashMap::new();
+        }
+    };
+
+    let files = Mutex::new(HashMap::new());
+    let ancestors: Vec<PathBuf> = dir.canonicalize().into_iter().collect();
+    pool.scope(|scope| {
+        list(scope, dir.to_owned(), ancestors, symlinks, keep, &files)
+    });
+    files
+        .into_inner()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// List the folder `dir`, spawning a task for every subfolder.
+///
+/// `ancestors` are the canonical paths of `dir` and its parents,
+/// walkdir only detects cycles of links within a single walk.
+fn list<'s>(
+    scope: &rayon::Scope<'s>,
+    dir: PathBuf,
+    ancestors: Vec<PathBuf>,
+    symlinks: SymlinkPolicy,
+    keep: Keep<'s>,
+    files: &'s Files,
+) {
+    let mut found = Vec::new();
+    for result in symlinks.walker(&dir).min_depth(1).max_depth(1) {
+        let entry = match result {
+            Ok(entry) => entry,
+            Err(msg) => {
+                log::error!("Error during walking: {}", msg);
+                continue;
+            }
+        };
+        if !keep(&entry) {
+            continue;
+        }
+
+        let path = entry.path();
+        if !entry.file_type().is_dir() {
+            match CanonicalPathBuf::canonicalize(path) {
+                Ok(canonical_path) => found.push((canonical_path, entry)),
+                Err(msg) => {
+                    log::warn!(
+                        "Couldn't canonicalize {}:\n{}",
+                        path.display(),
+                        msg
+                    );
+                }
+            }
+            continue;
+        }
+
+        let canonical = match (entry.path_is_symlink(), ancestors.last()) {
+            (false, Some(parent)) => parent.join(entry.file_name()),
+            _ => match path.canonicalize() {
+                Ok(canonical) => canonical,
+                Err(_) => continue,
+            },
+        };
+        if ancestors.contains(&canonical) {
+            log::debug!("Skipping symlink cycle: {}", path.display());
+            continue;
+        }
+        let mut ancestors = ancestors.clone();
+        ancestors.push(canonical);
+        let path = path.to_owned();
+        scope.spawn(move |scope| {
+            list(scope, path, ancestors, symlinks, keep, files)
+        });
+    }
+
+    files
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .extend(found);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::discover_paths;
+    use crate::ResourceIndex;
+    use dev_hash::Crc32;
+    use std::collections::BTreeSet;
+    use std::fs;
+    use tempdir::TempDir;
+
+    #[test]
+    fn parallel_walk_finds_the_same_files() {
+        let dir = TempDir::new("parallel_walk").unwrap();
+        let root = dir.path();
+        for i in 0..20 {
+            let folder = root.join(format!("{}/{}", i % 4, i));
+            fs::create_dir_all(&folder).unwrap();
+            fs::write(folder.join("file.txt"), i.to_string()).unwrap();
+        }
+        fs::create_dir(root.join(".hidden")).unwrap();
+        fs::write(root.join(".hidden/file.txt"), "hidden").unwrap();
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(root, root.join("0/cycle")).unwrap();
+
+        let keep = |entry: &DirEntry| {
+            !entry
+                .file_name()
+                .to_string_lossy()
+                .starts_with('.')
+        };
+        let parallel: BTreeSet<_> =
+            walk(root, SymlinkPolicy::default(), &keep, 4)
+                .into_keys()
+                .collect();
+        let sequential: BTreeSet<_> =
+            discover_paths(root, SymlinkPolicy::default())
+                .into_keys()
+                .collect();
+        assert_eq!(parallel.len(), 20);
+        assert_eq!(parallel, sequential);
+
+        let index = ResourceIndex::<Crc32>::build_with_walk_threads(root, 4);
+        assert_eq!(index, ResourceIndex::build(root));
+    }
+}
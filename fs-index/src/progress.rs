@@ -0,0 +1,99 @@
+//! Progress of [`ResourceIndex::build_with_progress`] and
+//! [`ResourceIndex::update_all_with_progress`].
+//!
+//! [`ResourceIndex::build_with_progress`]: crate::ResourceIndex::build_with_progress
+//! [`ResourceIndex::update_all_with_progress`]: crate::ResourceIndex::update_all_with_progress
+
+use std::path::Path;
+
+use walkdir::DirEntry;
+
+/// State of the scan, reported after every file
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct Progress<'a> {
+    pub files_scanned: usize,
+    /// Files to scan in total, known once the tree has been walked
+    pub files_total: usize,
+    pub bytes_hashed: u64,
+    pub bytes_total: u64,
+    /// File which has just been scanned
+    pub current_path: &'a Path,
+}
+
+impl Progress<'_> {
+    /// Share of the bytes which have been hashed, from 0 to 1
+    pub fn ratio(&self) -> f64 {
+        if self.bytes_total == 0 {
+            return 1.0;
+        }
+        self.bytes_hashed as f64 / self.bytes_total as f64
+    }
+}
+
+/// Receiver of the progress, e.g. a progress bar.
+///
+/// Any `FnMut(&Progress)` closure is a handler.
+pub trait ProgressHandler {
+    fn on_progress(&mut self, progress: &Progress);
+}
+
+impl<F: FnMut(&Progress)> ProgressHandler for F {
+    fn on_progress(&mut self, progress: &Progress) {
+        self(progress)
+    }
+}
+
+/// Counts the scanned files for an optional handler
+pub(crate) struct ProgressTracker<'h> {
+    handler: Option<&'h mut dyn ProgressHandler>,
+    files_scanned: usize,
+    files_total: usize,
+    bytes_hashed: u64,
+    bytes_total: u64,
+}
+
+impl<'h> ProgressTracker<'h> {
+    pub(crate) fn new<'e>(
+        handler: Option<&'h mut dyn ProgressHandler>,
+        entries: impl Iterator<Item = &'e DirEntry>,
+    ) -> Self {
+        let mut tracker = ProgressTracker {
+            handler,
+            files_scanned: 0,
+            files_total: 0,
+            bytes_hashed: 0,
+            bytes_total: 0,
+        };
+        // sizes are only needed to report them
+        if tracker.handler.is_some() {
+            for entry in entries {
+                tracker.files_total += 1;
+                tracker.bytes_total += tracker.size(entry);
+            }
+        }
+        tracker
+    }
+
+    pub(crate) fn size(&self, entry: &DirEntry) -> u64 {
+        match self.handler {
+            Some(_) => entry
+                .metadata()
+                .map_or(0, |metadata| metadata.len()),
+            None => 0,
+        }
+    }
+
+    pub(crate) fn scanned(&mut self, path: &Path, size: u64) {
+        if let Some(handler) = self.handler.as_mut() {
+            self.files_scanned += 1;
+            self.bytes_hashed += size;
+            handler.on_progress(&Progress {
+                files_scanned: self.files_scanned,
+                files_total: self.files_total,
+                bytes_hashed: self.bytes_hashed,
+                bytes_total: self.bytes_total,
+                current_path: path,
+            });
+        }
+    }
+}
@@ -0,0 +1,62 @@
+//! Handling of symbolic links found while walking the root.
+
+use std::path::{Path, PathBuf};
+
+use walkdir::{DirEntry, WalkDir};
+
+/// What the index does with symbolic links.
+///
+/// Resources are always indexed by their canonical path, so a file
+/// reachable through a link and through its real path is indexed once.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SymlinkPolicy {
+    /// Skip links to files and folders
+    Ignore,
+    /// Follow links whose target is located under the root
+    /// and skip the others
+    #[default]
+    FollowWithinRoot,
+    /// Follow every link, indexing files located outside of the root.
+    /// Links to one of their own parent folders are skipped.
+    FollowAll,
+}
+
+impl SymlinkPolicy {
+    pub(crate) fn walker(self, dir: &Path) -> WalkDir {
+        // cycles are detected by `walkdir` when following links
+        WalkDir::new(dir).follow_links(self != SymlinkPolicy::Ignore)
+    }
+}
+
+/// Decides which links of a walk are kept
+pub(crate) struct SymlinkFilter {
+    policy: SymlinkPolicy,
+    canonical_root: Option<PathBuf>,
+}
+
+impl SymlinkFilter {
+    pub(crate) fn new(policy: SymlinkPolicy, root: &Path) -> Self {
+        SymlinkFilter {
+            policy,
+            canonical_root: root.canonicalize().ok(),
+        }
+    }
+
+    /// Whether `entry` is kept, folders which are not kept aren't walked
+    pub(crate) fn allows(&self, entry: &DirEntry) -> bool {
+        // the root itself may be a link
+        if !entry.path_is_symlink() || entry.depth() == 0 {
+            return true;
+        }
+        match self.policy {
+            SymlinkPolicy::Ignore => false,
+            SymlinkPolicy::FollowAll => true,
+            SymlinkPolicy::FollowWithinRoot => {
+                match (&self.canonical_root, entry.path().canonicalize()) {
+                    (Some(root), Ok(target)) => target.starts_with(root),
+                    _ => false,
+                }
+            }
+        }
+    }
+}
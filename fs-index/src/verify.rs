@@ -0,0 +1,382 @@
+//! Integrity checks of a stored index against the filesystem.
+//!
+//! Unlike [`ResourceIndex::load`](crate::ResourceIndex::load), which
+//! silently skips files that don't exist anymore, verification reads the
+//! stored index as is and reports every entry which doesn't match the
+//! files under the root. Nothing is modified until [`repair`] is called.
+
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use data_error::{Result, ResultExt};
+use data_resource::ResourceId;
+
+use crate::index::{
+    read_stored_entries, write_stored_entries, IndexEntry, ResourceIndex,
+    RESOURCE_UPDATED_THRESHOLD,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VerifyMode {
+    /// Check that indexed files exist and re-hash the files
+    /// whose modification time changed
+    Quick,
+    /// Re-hash every indexed file
+    Full,
+    /// Same as `Quick`, and also re-hash the given fraction
+    /// (between 0 and 1) of the other files
+    Sample(f64),
+}
+
+/// Entry of the stored index which doesn't match the filesystem.
+///
+/// Paths are relative to the root.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Finding<Id: ResourceId> {
+    /// The indexed file doesn't exist anymore
+    Missing { path: PathBuf, id: Id },
+    /// The file was touched but its content still matches the id
+    MetadataDrift {
+        path: PathBuf,
+        id: Id,
+        stored: SystemTime,
+        actual: SystemTime,
+    },
+    /// The content of the file doesn't match the indexed id
+    IdMismatch {
+        path: PathBuf,
+        stored: Id,
+        actual: Id,
+        modified: SystemTime,
+    },
+}
+
+impl<Id: ResourceId> Finding<Id> {
+    /// Stable name of the kind of the finding
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Finding::Missing { .. } => "missing",
+            Finding::MetadataDrift { .. } => "metadata_drift",
+            Finding::IdMismatch { .. } => "id_mismatch",
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        match self {
+            Finding::Missing { path, .. }
+            | Finding::MetadataDrift { path, .. }
+            | Finding::IdMismatch { path, .. } => path,
+        }
+    }
+
+    /// Id of the resource as stored in the index
+    pub fn stored_id(&self) -> &Id {
+        match self {
+            Finding::Missing { id, .. }
+            | Finding::MetadataDrift { id, .. }
+            | Finding::IdMismatch { stored: id, .. } => id,
+        }
+    }
+}
+
+/// Outcome of [`ResourceIndex::verify`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct VerifyReport<Id: ResourceId> {
+    /// Number of entries checked
+    pub checked: usize,
+    /// Number of files re-hashed
+    pub hashed: usize,
+    /// Entries which don't match the filesystem, sorted by path
+    pub findings: Vec<Finding<Id>>,
+}
+
+impl<Id: ResourceId> VerifyReport<Id> {
+    pub fn is_healthy(&self) -> bool {
+        self.findings.is_empty()
+    }
+}
+
+impl<Id: ResourceId> ResourceIndex<Id> {
+    /// Compare the entries of the index with the files under the root,
+    /// e.g. to detect bit rot or truncated files.
+    ///
+    /// The index isn't modified, the findings can be fixed with
+    /// [`ResourceIndex::update_all`].
+    pub fn verify(&self, mode: VerifyMode) -> Result<VerifyReport<Id>> {
+        verify_entries(self.root(), self.stored_entries()?, mode)
+    }
+}
+
+/// Compare the index stored in the `.ark` folder of `root`
+/// with the files under the root.
+///
+/// Findings are sorted by path.
+pub fn verify<Id: ResourceId, P: AsRef<Path>>(
+    root: P,
+    mode: VerifyMode,
+) -> Result<Vec<Finding<Id>>> {
+    let root = root.as_ref();
+    let entries = read_stored_entries::<Id>(root)?;
+    Ok(verify_entries(root, entries, mode)?.findings)
+}
+
+fn verify_entries<Id: ResourceId>(
+    root: &Path,
+    mut entries: Vec<(PathBuf, IndexEntry<Id>)>,
+    mode: VerifyMode,
+) -> Result<VerifyReport<Id>> {
+    entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    let sampled = sample(entries.len(), mode);
+    let checked = entries.len();
+    let mut hashed = 0;
+
+    let mut findings = Vec::new();
+    for (i, (path, entry)) in entries.into_iter().enumerate() {
+        let full_path = root.join(&path);
+        let metadata = match fs::metadata(&full_path) {
+            Ok(metadata) if metadata.is_file() => metadata,
+            Ok(_) => {
+                findings.push(Finding::Missing { path, id: entry.id });
+                continue;
+            }
+            Err(err) if err.kind() == ErrorKind::NotFound => {
+                findings.push(Finding::Missing { path, id: entry.id });
+                continue;
+            }
+            Err(err) => return Err(err).with_path(&full_path),
+        };
+
+        // the stored timestamp is truncated to milliseconds
+        let modified = metadata.modified().with_path(&full_path)?;
+        let drifted = modified
+            .duration_since(entry.modified)
+            .map_or(true, |elapsed| elapsed >= RESOURCE_UPDATED_THRESHOLD);
+
+        if !drifted && !sampled[i] {
+            continue;
+        }
+
+        log::trace!("[verify] hashing {}", full_path.display());
+        let actual = Id::from_path(&full_path)?;
+        hashed += 1;
+        if actual != entry.id {
+            findings.push(Finding::IdMismatch {
+                path,
+                stored: entry.id,
+                actual,
+                modified,
+            });
+        } else if drifted {
+            findings.push(Finding::MetadataDrift {
+                path,
+                id: entry.id,
+                stored: entry.modified,
+                actual: modified,
+            });
+        }
+    }
+
+    Ok(VerifyReport {
+        checked,
+        hashed,
+        findings,
+    })
+}
+
+/// Which of `count` entries, sorted by path, must be re-hashed.
+///
+/// Sampled entries are spread evenly, so repeated runs check the same files.
+fn sample(count: usize, mode: VerifyMode) -> Vec<bool> {
+    match mode {
+        VerifyMode::Quick => vec![false; count],
+        VerifyMode::Full => vec![true; count],
+        VerifyMode::Sample(fraction) => {
+            let mut sampled = vec![false; count];
+            let fraction = fraction.clamp(0.0, 1.0);
+            let picked = (count as f64 * fraction).ceil() as usize;
+            for k in 0..picked {
+                sampled[k * count / picked] = true;
+            }
+            sampled
+        }
+    }
+}
+
+/// Apply the safe repairs of `findings` to the index stored in the
+/// `.ark` folder of `root`: entries of missing files are dropped,
+/// timestamps and ids are replaced by the actual ones.
+///
+/// Findings which don't match the stored index anymore are skipped.
+/// Returns the number of repaired entries.
+pub fn repair<Id: ResourceId, P: AsRef<Path>>(
+    root: P,
+    findings: &[Finding<Id>],
+) -> Result<usize> {
+    let root = root.as_ref();
+    let mut entries = read_stored_entries::<Id>(root)?;
+
+    let mut repaired = 0;
+    for finding in findings {
+        let Some(i) = entries.iter().position(|(path, entry)| {
+            path == finding.path() && &entry.id == finding.stored_id()
+        }) else {
+            log::warn!(
+                "[repair] {} is not indexed as {}",
+                finding.path().display(),
+                finding.stored_id()
+            );
+            continue;
+        };
+
+        match finding {
+            Finding::Missing { .. } => {
+                entries.remove(i);
+            }
+            Finding::MetadataDrift { actual, .. } => {
+                entries[i].1.modified = *actual;
+            }
+            Finding::IdMismatch {
+                actual, modified, ..
+            } => {
+                entries[i].1.id = actual.clone();
+                entries[i].1.modified = *modified;
+            }
+        }
+        repaired += 1;
+    }
+
+    if repaired > 0 {
+        entries.sort_by(|(_, a), (_, b)| a.cmp(b));
+        write_stored_entries(root, &entries)?;
+    }
+
+    Ok(repaired)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ResourceIndex;
+    use dev_hash::Crc32;
+    use std::fs::File;
+    use std::time::Duration;
+    use uuid::Uuid;
+
+    fn library() -> PathBuf {
+        let root = std::env::temp_dir().join(Uuid::new_v4().to_string());
+        fs::create_dir(&root).unwrap();
+        fs::write(root.join("a.txt"), "a").unwrap();
+        fs::write(root.join("b.txt"), "b").unwrap();
+        ResourceIndex::<Crc32>::build(&root)
+            .store()
+            .unwrap();
+        root
+    }
+
+    fn set_modified(path: &Path, modified: SystemTime) {
+        File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+    }
+
+    #[test]
+    fn quick_verification_finds_missing_and_drifted_files() {
+        let root = library();
+        assert!(verify::<Crc32, _>(&root, VerifyMode::Quick)
+            .unwrap()
+            .is_empty());
+
+        fs::remove_file(root.join("a.txt")).unwrap();
+        set_modified(
+            &root.join("b.txt"),
+            SystemTime::now() + Duration::from_secs(60),
+        );
+
+        let findings = verify::<Crc32, _>(&root, VerifyMode::Quick).unwrap();
+        let kinds: Vec<_> = findings.iter().map(Finding::kind).collect();
+        assert_eq!(kinds, ["missing", "metadata_drift"]);
+
+        assert_eq!(repair(&root, &findings).unwrap(), 2);
+        assert!(verify::<Crc32, _>(&root, VerifyMode::Quick)
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            ResourceIndex::<Crc32>::load(&root)
+                .unwrap()
+                .size(),
+            1
+        );
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn full_verification_finds_changed_content() {
+        let root = library();
+        let path = root.join("a.txt");
+        let modified = fs::metadata(&path).unwrap().modified().unwrap();
+        fs::write(&path, "c").unwrap();
+        set_modified(&path, modified);
+
+        assert!(verify::<Crc32, _>(&root, VerifyMode::Quick)
+            .unwrap()
+            .is_empty());
+        let findings = verify::<Crc32, _>(&root, VerifyMode::Full).unwrap();
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].kind(), "id_mismatch");
+        assert_eq!(findings[0].path(), Path::new("a.txt"));
+
+        repair(&root, &findings).unwrap();
+        let index = ResourceIndex::<Crc32>::load(&root).unwrap();
+        assert!(index
+            .id2path
+            .contains_key(&Crc32::from_path(&path).unwrap()));
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn in_memory_index_is_verified() {
+        let root = library();
+        let index = ResourceIndex::<Crc32>::load(&root).unwrap();
+        let report = index.verify(VerifyMode::Full).unwrap();
+        assert!(report.is_healthy());
+        assert_eq!((report.checked, report.hashed), (2, 2));
+
+        // truncated to the same size and time, only found by hashing
+        let path = root.join("b.txt");
+        let modified = fs::metadata(&path).unwrap().modified().unwrap();
+        fs::write(&path, "x").unwrap();
+        set_modified(&path, modified);
+        fs::remove_file(root.join("a.txt")).unwrap();
+
+        let report = index.verify(VerifyMode::Quick).unwrap();
+        assert_eq!(report.hashed, 0);
+        assert_eq!(report.findings.len(), 1);
+        let report = index.verify(VerifyMode::Sample(1.0)).unwrap();
+        let kinds: Vec<_> = report
+            .findings
+            .iter()
+            .map(Finding::kind)
+            .collect();
+        assert_eq!(kinds, ["missing", "id_mismatch"]);
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn sampling_spreads_over_entries() {
+        assert_eq!(
+            sample(4, VerifyMode::Sample(0.5)),
+            [true, false, true, false]
+        );
+        assert_eq!(sample(3, VerifyMode::Sample(0.0)), [false; 3]);
+        assert_eq!(sample(3, VerifyMode::Sample(0.01)), [true, false, false]);
+    }
+}
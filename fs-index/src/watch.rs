@@ -0,0 +1,322 @@
+//! Live updates of a [`ResourceIndex`] from filesystem events.
+//!
+//! Events are delivered by the `notify` crate. They are batched for
+//! [`DEBOUNCE`] and the files and folders of every batch are scanned
+//! with [`ResourceIndex::update_subtree`], so bursts of writes only
+//! scan each file once. The whole root is scanned again with
+//! [`ResourceIndex::update_all`] if events were lost.
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::path::{Component, Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use anyhow::anyhow;
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+use data_error::{ArklibError, Result};
+use data_resource::ResourceId;
+
+use crate::index::{IndexUpdate, ResourceIndex};
+
+/// Time to wait for more events before updating the index
+pub const DEBOUNCE: Duration = Duration::from_millis(100);
+
+/// Keeps a [`ResourceIndex`] up to date until it is stopped or dropped.
+pub struct IndexWatcher<Id: ResourceId> {
+    index: Arc<Mutex<ResourceIndex<Id>>>,
+    watcher: Option<RecommendedWatcher>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl<Id: ResourceId + Send + 'static> ResourceIndex<Id> {
+    /// Watch the root of the index, applying the changes of the files
+    /// to the index without calling [`ResourceIndex::update_all`].
+    ///
+    /// Every non-empty update is sent to the returned receiver.
+    /// The index keeps being updated if the receiver is dropped.
+    pub fn watch(
+        self,
+    ) -> Result<(IndexWatcher<Id>, Receiver<IndexUpdate<Id>>)> {
+        IndexWatcher::shared(Arc::new(Mutex::new(self)))
+    }
+}
+
+impl<Id: ResourceId + Send + 'static> IndexWatcher<Id> {
+    /// Same as [`ResourceIndex::watch`] for an index which other threads
+    /// keep using while it is watched
+    pub fn shared(
+        index: Arc<Mutex<ResourceIndex<Id>>>,
+    ) -> Result<(Self, Receiver<IndexUpdate<Id>>)> {
+        // events are reported with canonical paths
+        let root = {
+            let index = index
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+            index
+                .root()
+                .canonicalize()
+                .unwrap_or_else(|_| index.root().to_owned())
+        };
+
+        let (events_tx, events_rx) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(events_tx)
+            .map_err(|err| ArklibError::Other(anyhow!(err)))?;
+        watcher
+            .watch(&root, RecursiveMode::Recursive)
+            .map_err(|err| ArklibError::Other(anyhow!(err)))?;
+
+        let (updates_tx, updates_rx) = mpsc::channel();
+        let thread = {
+            let index = index.clone();
+            std::thread::spawn(move || {
+                apply_events(&root, &index, events_rx, updates_tx)
+            })
+        };
+
+        log::info!("Watching {}", root.display());
+        Ok((
+            IndexWatcher {
+                index,
+                watcher: Some(watcher),
+                thread: Some(thread),
+            },
+            updates_rx,
+        ))
+    }
+}
+
+impl<Id: ResourceId> IndexWatcher<Id> {
+    /// Current state of the index, updates wait until the guard is dropped
+    pub fn index(&self) -> MutexGuard<'_, ResourceIndex<Id>> {
+        self.index
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Stop watching and return the index, or a copy of it if it is
+    /// still shared, see [`IndexWatcher::shared`]
+    pub fn stop(self) -> ResourceIndex<Id> {
+        let index = self.index.clone();
+        // joins the thread, which holds another reference
+        drop(self);
+        match Arc::try_unwrap(index) {
+            Ok(index) => index
+                .into_inner()
+                .unwrap_or_else(|poisoned| poisoned.into_inner()),
+            Err(index) => index
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .clone(),
+        }
+    }
+
+    fn shutdown(&mut self) {
+        // dropping the watcher disconnects the events channel,
+        // which ends the thread
+        self.watcher.take();
+        if let Some(thread) = self.thread.take() {
+            if thread.join().is_err() {
+                log::error!("Index watcher thread panicked");
+            }
+        }
+    }
+}
+
+impl<Id: ResourceId> Drop for IndexWatcher<Id> {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+fn apply_events<Id: ResourceId>(
+    root: &Path,
+    index: &Mutex<ResourceIndex<Id>>,
+    events: Receiver<notify::Result<Event>>,
+    updates: Sender<IndexUpdate<Id>>,
+) {
+    // blocks until the next change, ends when the watcher is dropped
+    while let Ok(event) = events.recv() {
+        let mut changes = Changes::default();
+        changes.add(root, event);
+        loop {
+            match events.recv_timeout(DEBOUNCE) {
+                Ok(event) => changes.add(root, event),
+                Err(RecvTimeoutError::Timeout) => break,
+                Err(RecvTimeoutError::Disconnected) => return,
+            }
+        }
+        if !changes.lost && changes.paths.is_empty() {
+            continue;
+        }
+
+        let update = changes.apply(
+            &mut index
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner()),
+        );
+        match update {
+            Ok(update) => {
+                if update.added.is_empty() && update.deleted.is_empty() {
+                    continue;
+                }
+                log::debug!(
+                    "[watch] {} added, {} deleted",
+                    update.added.len(),
+                    update.deleted.len()
+                );
+                // nobody listens anymore, but the index is still kept
+                let _ = updates.send(update);
+            }
+            Err(err) => log::error!("Failed to update index: {}", err),
+        }
+    }
+}
+
+/// Paths changed during a batch of events
+#[derive(Default)]
+struct Changes {
+    paths: BTreeSet<PathBuf>,
+    /// Whether events may have been lost
+    lost: bool,
+}
+
+impl Changes {
+    /// Changes of hidden files, including the `.ark` folder,
+    /// are not indexed
+    fn add(&mut self, root: &Path, event: notify::Result<Event>) {
+        let event = match event {
+            Ok(event) => event,
+            Err(err) => {
+                log::warn!("[watch] {}", err);
+                self.lost = true;
+                return;
+            }
+        };
+        if event.need_rescan() {
+            self.lost = true;
+        }
+        if matches!(event.kind, EventKind::Access(_)) {
+            return;
+        }
+        for path in event.paths {
+            let Ok(relative) = path.strip_prefix(root) else {
+                continue;
+            };
+            let hidden = relative.components().any(|component| {
+                matches!(component, Component::Normal(name)
+                    if name.to_string_lossy().starts_with('.'))
+            });
+            if !hidden {
+                self.paths.insert(path);
+            }
+        }
+    }
+
+    /// Scan the changed paths, once each even if they are nested
+    fn apply<Id: ResourceId>(
+        self,
+        index: &mut ResourceIndex<Id>,
+    ) -> Result<IndexUpdate<Id>> {
+        if self.lost {
+            return index.update_all();
+        }
+
+        let mut merged = IndexUpdate {
+            deleted: HashSet::new(),
+            added: HashMap::new(),
+        };
+        // paths are sorted by components,
+        // so the content of a folder follows the folder
+        let mut scanned: Option<PathBuf> = None;
+        for path in self.paths {
+            if scanned
+                .as_ref()
+                .is_some_and(|folder| path.starts_with(folder))
+            {
+                continue;
+            }
+            let update = index.update_subtree(&path)?;
+            merged.deleted.extend(update.deleted);
+            merged.added.extend(update.added);
+            scanned = Some(path);
+        }
+        // resources moved from one path to another are still indexed
+        merged
+            .deleted
+            .retain(|id| !index.id2path.contains_key(id));
+        Ok(merged)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dev_hash::Crc32;
+    use std::fs;
+    use std::time::Instant;
+    use uuid::Uuid;
+
+    const TIMEOUT: Duration = Duration::from_secs(10);
+
+    /// Wait for updates until `done` returns true for the index
+    fn wait_for(
+        watcher: &IndexWatcher<Crc32>,
+        updates: &Receiver<IndexUpdate<Crc32>>,
+        done: impl Fn(&ResourceIndex<Crc32>) -> bool,
+    ) {
+        let start = Instant::now();
+        while !done(&watcher.index()) {
+            assert!(start.elapsed() < TIMEOUT, "Index was not updated");
+            let _ = updates.recv_timeout(DEBOUNCE);
+        }
+    }
+
+    #[test]
+    fn watched_index_follows_files() {
+        let root = std::env::temp_dir().join(Uuid::new_v4().to_string());
+        fs::create_dir(&root).unwrap();
+        let root = root.canonicalize().unwrap();
+        fs::write(root.join("a.txt"), "a").unwrap();
+
+        let index = ResourceIndex::<Crc32>::build(&root);
+        let (watcher, updates) = index.watch().unwrap();
+
+        fs::create_dir(root.join("folder")).unwrap();
+        fs::write(root.join("folder/b.txt"), "b").unwrap();
+        let update = updates.recv_timeout(TIMEOUT).unwrap();
+        assert!(update
+            .added
+            .values()
+            .any(|id| id == &Crc32::from_bytes(b"b").unwrap()));
+        wait_for(&watcher, &updates, |index| index.size() == 2);
+
+        fs::write(root.join("a.txt"), "c").unwrap();
+        wait_for(&watcher, &updates, |index| {
+            index
+                .id2path
+                .contains_key(&Crc32::from_bytes(b"c").unwrap())
+        });
+
+        // only the paths of the events are scanned
+        fs::rename(root.join("a.txt"), root.join("moved.txt")).unwrap();
+        wait_for(&watcher, &updates, |index| {
+            index.get_resource_by_path("moved.txt").is_some()
+        });
+        assert_eq!(watcher.index().size(), 2);
+
+        fs::remove_dir_all(root.join("folder")).unwrap();
+        wait_for(&watcher, &updates, |index| index.size() == 1);
+
+        // hidden files are ignored
+        fs::write(root.join(".hidden"), "d").unwrap();
+        std::thread::sleep(DEBOUNCE * 3);
+        assert!(updates.try_recv().is_err());
+
+        let index = watcher.stop();
+        assert_eq!(index.size(), 1);
+        fs::remove_dir_all(root).unwrap();
+    }
+}
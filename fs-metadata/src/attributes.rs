@@ -0,0 +1,37 @@
+//! Basic attributes of files, available for every resource.
+
+use std::fs;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde_json::{json, Value};
+
+use data_error::{Result, ResultExt};
+
+use crate::extractor::{extension, MetadataExtractor};
+
+pub(crate) struct Attributes;
+
+impl MetadataExtractor for Attributes {
+    fn name(&self) -> &str {
+        "file"
+    }
+
+    fn extract(&self, path: &Path) -> Result<Option<Value>> {
+        let metadata = fs::metadata(path).with_path(path)?;
+        Ok(Some(json!({
+            "size": metadata.len(),
+            "extension": extension(path),
+            "readonly": metadata.permissions().readonly(),
+            "modified": metadata.modified().ok().and_then(millis),
+            "created": metadata.created().ok().and_then(millis),
+        })))
+    }
+}
+
+/// Milliseconds since the Unix epoch
+fn millis(time: SystemTime) -> Option<u64> {
+    time.duration_since(UNIX_EPOCH)
+        .ok()
+        .map(|elapsed| elapsed.as_millis() as u64)
+}
@@ -0,0 +1,42 @@
+//! ID3 tags of audio files, e.g. the artist or the album.
+
+use std::path::Path;
+
+use id3::{ErrorKind, Tag, TagLike};
+use serde_json::{json, Value};
+
+use data_error::{Result, ResultExt};
+
+use crate::extractor::{error, MetadataExtractor};
+
+pub(crate) const EXTENSIONS: &[&str] = &["mp3", "aiff", "aif", "wav"];
+
+pub(crate) struct Id3;
+
+impl MetadataExtractor for Id3 {
+    fn name(&self) -> &str {
+        "id3"
+    }
+
+    fn extract(&self, path: &Path) -> Result<Option<Value>> {
+        let tag = match Tag::read_from_path(path) {
+            Ok(tag) => tag,
+            Err(err) if matches!(err.kind, ErrorKind::NoTag) => {
+                return Ok(None)
+            }
+            Err(err) => return Err(error(self.name(), err)).with_path(path),
+        };
+        Ok(Some(json!({
+            "title": tag.title(),
+            "artist": tag.artist(),
+            "album": tag.album(),
+            "album_artist": tag.album_artist(),
+            "genre": tag.genre_parsed(),
+            "year": tag.year(),
+            "track": tag.track(),
+            "disc": tag.disc(),
+            // in seconds
+            "duration": tag.duration(),
+        })))
+    }
+}
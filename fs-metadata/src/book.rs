@@ -0,0 +1,46 @@
+//! Metadata of EPUB books, e.g. the title or the authors,
+//! and their cover as preview.
+
+use std::path::Path;
+
+use epub::doc::EpubDoc;
+use serde_json::{json, Value};
+
+use data_error::{Result, ResultExt};
+
+use crate::extractor::{error, MetadataExtractor};
+
+pub(crate) const EXTENSIONS: &[&str] = &["epub"];
+
+pub(crate) struct Epub;
+
+impl MetadataExtractor for Epub {
+    fn name(&self) -> &str {
+        "epub"
+    }
+
+    fn extract(&self, path: &Path) -> Result<Option<Value>> {
+        let book = EpubDoc::new(path)
+            .map_err(|err| error(self.name(), err))
+            .with_path(path)?;
+        let authors: Vec<String> = book
+            .metadata
+            .get("creator")
+            .cloned()
+            .unwrap_or_default();
+        Ok(Some(json!({
+            "title": book.mdata("title"),
+            "authors": authors,
+            "language": book.mdata("language"),
+            "publisher": book.mdata("publisher"),
+            "date": book.mdata("date"),
+        })))
+    }
+
+    fn preview(&self, path: &Path) -> Result<Option<Vec<u8>>> {
+        let mut book = EpubDoc::new(path)
+            .map_err(|err| error(self.name(), err))
+            .with_path(path)?;
+        Ok(book.get_cover().map(|(cover, _mime)| cover))
+    }
+}
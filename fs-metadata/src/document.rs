@@ -0,0 +1,72 @@
+//! Document information of PDF files, e.g. the title or the author.
+
+use std::path::Path;
+
+use lopdf::{Dictionary, Document, Object};
+use serde_json::{Map, Value};
+
+use data_error::{Result, ResultExt};
+
+use crate::extractor::{error, MetadataExtractor};
+
+pub(crate) const EXTENSIONS: &[&str] = &["pdf"];
+
+/// Entries of the document information dictionary, and their keys
+const ENTRIES: &[(&[u8], &str)] = &[
+    (b"Title", "title"),
+    (b"Author", "author"),
+    (b"Subject", "subject"),
+    (b"Keywords", "keywords"),
+    (b"Creator", "creator"),
+    (b"Producer", "producer"),
+    (b"CreationDate", "created"),
+    (b"ModDate", "modified"),
+];
+
+pub(crate) struct PdfInfo;
+
+impl MetadataExtractor for PdfInfo {
+    fn name(&self) -> &str {
+        "pdf"
+    }
+
+    fn extract(&self, path: &Path) -> Result<Option<Value>> {
+        let document = Document::load(path)
+            .map_err(|err| error(self.name(), err))
+            .with_path(path)?;
+
+        let mut info = Map::new();
+        info.insert("pages".to_owned(), document.get_pages().len().into());
+        if let Some(dictionary) = info_dictionary(&document) {
+            for (entry, key) in ENTRIES {
+                if let Ok(Object::String(bytes, _)) = dictionary.get(entry) {
+                    info.insert((*key).to_owned(), decode_text(bytes).into());
+                }
+            }
+        }
+        Ok(Some(Value::Object(info)))
+    }
+}
+
+fn info_dictionary(document: &Document) -> Option<&Dictionary> {
+    match document.trailer.get(b"Info").ok()? {
+        Object::Reference(id) => document.get_dictionary(*id).ok(),
+        Object::Dictionary(dictionary) => Some(dictionary),
+        _ => None,
+    }
+}
+
+/// Text strings are either UTF-16BE with a byte order mark,
+/// or in PDFDocEncoding which mostly matches Latin-1
+fn decode_text(bytes: &[u8]) -> String {
+    match bytes.strip_prefix(&[0xFE, 0xFF]) {
+        Some(utf16) => {
+            let units: Vec<u16> = utf16
+                .chunks_exact(2)
+                .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+                .collect();
+            String::from_utf16_lossy(&units)
+        }
+        None => bytes.iter().map(|&byte| byte as char).collect(),
+    }
+}
@@ -0,0 +1,219 @@
+//! Extractors of metadata from the content of resources.
+//!
+//! Every extractor produces a JSON object of its own, stored under its
+//! name in the metadata of the resource, e.g. `exif` or `id3`.
+//!
+//! Extractors are [registered](register) for the resources they support,
+//! designated by extension or MIME type. Applications can register their
+//! own extractors, e.g. for DICOM or CAD files, next to the built-in ones.
+
+use std::fmt::Display;
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+
+use lazy_static::lazy_static;
+use serde_json::Value;
+
+use data_error::{ArklibError, Result};
+
+use crate::attributes::Attributes;
+
+/// Source of metadata for some kinds of resources
+pub trait MetadataExtractor: Send + Sync {
+    /// Key of the extracted metadata, also identifying the extractor
+    fn name(&self) -> &str;
+
+    /// Metadata of the resource at `path`, `None` if it has none
+    fn extract(&self, path: &Path) -> Result<Option<Value>>;
+
+    /// Image standing for the resource at `path`, e.g. the cover of
+    /// a book, stored into the previews. `None` by default.
+    fn preview(&self, _path: &Path) -> Result<Option<Vec<u8>>> {
+        Ok(None)
+    }
+}
+
+/// Resources an extractor is run on
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Supported {
+    /// Every resource
+    All,
+    /// Resources with the extension, compared case-insensitively
+    Extension(String),
+    /// Resources whose MIME type, guessed from the extension, matches.
+    /// A subtype of `*` matches the whole type, e.g. `image/*`.
+    Mime(String),
+}
+
+impl Supported {
+    pub fn extension(extension: &str) -> Self {
+        Supported::Extension(extension.to_lowercase())
+    }
+
+    pub fn mime(mime: &str) -> Self {
+        Supported::Mime(mime.to_lowercase())
+    }
+
+    fn matches(&self, path: &Path) -> bool {
+        match self {
+            Supported::All => true,
+            Supported::Extension(supported) => {
+                extension(path).as_ref() == Some(supported)
+            }
+            Supported::Mime(supported) => mime_guess::from_path(path)
+                .iter()
+                .any(|mime| match supported.strip_suffix("/*") {
+                    Some(type_) => mime.type_() == type_,
+                    None => mime.essence_str() == supported,
+                }),
+        }
+    }
+}
+
+type Registration = (Supported, Arc<dyn MetadataExtractor>);
+
+lazy_static! {
+    static ref REGISTRY: RwLock<Vec<Registration>> = RwLock::new(built_in());
+}
+
+fn built_in() -> Vec<Registration> {
+    let mut registry: Vec<Registration> =
+        vec![(Supported::All, Arc::new(Attributes))];
+    #[cfg(feature = "exif")]
+    {
+        let exif: Arc<dyn MetadataExtractor> = Arc::new(crate::photo::Exif);
+        for extension in crate::photo::EXTENSIONS {
+            registry.push((Supported::extension(extension), exif.clone()));
+        }
+    }
+    #[cfg(feature = "id3")]
+    {
+        let id3: Arc<dyn MetadataExtractor> = Arc::new(crate::audio::Id3);
+        for extension in crate::audio::EXTENSIONS {
+            registry.push((Supported::extension(extension), id3.clone()));
+        }
+    }
+    #[cfg(feature = "pdf")]
+    {
+        let pdf: Arc<dyn MetadataExtractor> =
+            Arc::new(crate::document::PdfInfo);
+        for extension in crate::document::EXTENSIONS {
+            registry.push((Supported::extension(extension), pdf.clone()));
+        }
+    }
+    #[cfg(feature = "epub")]
+    {
+        let epub: Arc<dyn MetadataExtractor> = Arc::new(crate::book::Epub);
+        for extension in crate::book::EXTENSIONS {
+            registry.push((Supported::extension(extension), epub.clone()));
+        }
+    }
+    registry
+}
+
+/// Run `extractor` on the `supported` resources. An extractor can be
+/// registered several times, e.g. for several extensions.
+///
+/// For a given resource, an extractor replaces the extractors with the
+/// same name registered before, including the built-in ones.
+pub fn register<E: MetadataExtractor + 'static>(
+    supported: Supported,
+    extractor: E,
+) {
+    registry_mut().push((supported, Arc::new(extractor)));
+}
+
+/// Stop running the extractors with the given name,
+/// returning whether any was registered
+pub fn unregister(name: &str) -> bool {
+    let mut registry = registry_mut();
+    let before = registry.len();
+    registry.retain(|(_, extractor)| extractor.name() != name);
+    registry.len() != before
+}
+
+/// Extractors to run on the resource at `path`
+pub fn extractors_for(path: &Path) -> Vec<Arc<dyn MetadataExtractor>> {
+    let registry = REGISTRY
+        .read()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    let mut extractors: Vec<Arc<dyn MetadataExtractor>> = Vec::new();
+    // the latest registration of a name wins
+    for (supported, extractor) in registry.iter().rev() {
+        if supported.matches(path)
+            && extractors
+                .iter()
+                .all(|chosen| chosen.name() != extractor.name())
+        {
+            extractors.push(extractor.clone());
+        }
+    }
+    extractors.reverse();
+    extractors
+}
+
+fn registry_mut() -> std::sync::RwLockWriteGuard<'static, Vec<Registration>> {
+    REGISTRY
+        .write()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Lowercase extension of `path`, if any
+pub(crate) fn extension(path: &Path) -> Option<String> {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .map(str::to_lowercase)
+}
+
+pub(crate) fn error(extractor: &str, err: impl Display) -> ArklibError {
+    ArklibError::Storage(extractor.to_owned(), err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    struct Dicom;
+
+    impl MetadataExtractor for Dicom {
+        fn name(&self) -> &str {
+            "dicom"
+        }
+
+        fn extract(&self, _path: &Path) -> Result<Option<Value>> {
+            Ok(Some(json!({ "modality": "CT" })))
+        }
+    }
+
+    fn names(path: &str) -> Vec<String> {
+        extractors_for(Path::new(path))
+            .iter()
+            .map(|extractor| extractor.name().to_owned())
+            .collect()
+    }
+
+    #[test]
+    fn test_register_by_extension_and_mime() {
+        assert_eq!(names("scan.dcm"), ["file"]);
+
+        register(Supported::extension("DCM"), Dicom);
+        register(Supported::mime("application/dicom"), Dicom);
+        assert_eq!(names("scan.dcm"), ["file", "dicom"]);
+        assert_eq!(names("SCAN.DCM"), ["file", "dicom"]);
+        assert!(!names("notes.txt").contains(&"dicom".to_owned()));
+
+        assert!(unregister("dicom"));
+        assert!(!unregister("dicom"));
+        assert_eq!(names("scan.dcm"), ["file"]);
+    }
+
+    #[test]
+    fn test_mime_wildcard() {
+        let supported = Supported::mime("text/*");
+        assert!(supported.matches(Path::new("notes.txt")));
+        assert!(supported.matches(Path::new("page.html")));
+        assert!(!supported.matches(Path::new("photo.jpg")));
+    }
+}
@@ -1,14 +1,31 @@
-use data_error::Result;
+use data_error::{Result, ResultExt};
 use fs_atomic_versions::atomic::{modify_json, AtomicFile};
+use fs_atomic_versions::durability::Durability;
 use serde::{de::DeserializeOwned, Serialize};
+use serde_json::{Map, Value};
 use std::fmt::Debug;
-use std::io::Read;
-use std::path::Path;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
 
 use data_resource::ResourceId;
-use fs_storage::ARK_FOLDER;
+use fs_storage::cache_quota::CacheQuota;
+use fs_storage::{ARK_FOLDER, PREVIEWS_STORAGE_FOLDER};
 
-pub const METADATA_STORAGE_FOLDER: &str = "cache/metadata";
+mod attributes;
+#[cfg(feature = "id3")]
+mod audio;
+#[cfg(feature = "epub")]
+mod book;
+#[cfg(feature = "pdf")]
+mod document;
+pub mod extractor;
+#[cfg(feature = "exif")]
+mod photo;
+pub mod text;
+
+pub use extractor::{MetadataExtractor, Supported};
+
+pub use fs_storage::METADATA_STORAGE_FOLDER;
 
 pub fn store_metadata<
     S: Serialize + DeserializeOwned + Clone + Debug,
@@ -19,12 +36,14 @@ pub fn store_metadata<
     id: Id,
     metadata: &S,
 ) -> Result<()> {
+    // metadata can be extracted again
     let file = AtomicFile::new(
         root.as_ref()
             .join(ARK_FOLDER)
             .join(METADATA_STORAGE_FOLDER)
             .join(id.to_string()),
-    )?;
+    )?
+    .with_durability(Durability::Fast);
     modify_json(&file, |current_meta: &mut Option<S>| {
         let new_meta = metadata.clone();
         match current_meta {
@@ -41,6 +60,124 @@ pub fn store_metadata<
     Ok(())
 }
 
+/// Run the [extractors registered](extractor::register) for the resource
+/// at `path`, and store their metadata under their names, e.g. `file`,
+/// `exif`, `id3`, `pdf` or `epub`. The first preview provided by the
+/// extractors, e.g. the cover of a book, is stored into the previews.
+///
+/// Other metadata of the resource, e.g. stored with [`store_metadata`],
+/// is kept. An extractor which fails is skipped with a warning, so that
+/// a damaged tag doesn't prevent extracting the rest.
+pub fn extract_metadata<P: AsRef<Path>, Id: ResourceId>(
+    root: P,
+    path: &Path,
+    id: Id,
+) -> Result<Value> {
+    let mut extracted = Map::new();
+    let mut preview = None;
+    for extractor in extractor::extractors_for(path) {
+        if preview.is_none() {
+            match extractor.preview(path) {
+                Ok(image) => preview = image,
+                Err(err) => log::warn!(
+                    "Failed to extract {} preview of {}: {}",
+                    extractor.name(),
+                    path.display(),
+                    err
+                ),
+            }
+        }
+        match extractor.extract(path) {
+            Ok(Some(metadata)) => {
+                extracted.insert(extractor.name().to_owned(), metadata);
+            }
+            Ok(None) => {}
+            Err(err) => log::warn!(
+                "Failed to extract {} metadata of {}: {}",
+                extractor.name(),
+                path.display(),
+                err
+            ),
+        }
+    }
+
+    if let Some(preview) = preview {
+        store_preview(&root, id.clone(), &preview)?;
+    }
+
+    let file = AtomicFile::new(metadata_path(root, id))?
+        .with_durability(Durability::Fast);
+    modify_json(&file, |current_meta: &mut Option<Value>| {
+        let mut metadata = match current_meta.take() {
+            Some(Value::Object(metadata)) => metadata,
+            _ => Map::new(),
+        };
+        metadata.extend(extracted.clone());
+        *current_meta = Some(Value::Object(metadata));
+    })
+    .with_path(&file.directory)?;
+    Ok(Value::Object(extracted))
+}
+
+/// Store `image` as the preview of the resource, evicting other
+/// previews if their [cap](CacheQuota) is exceeded
+fn store_preview<P: AsRef<Path>, Id: ResourceId>(
+    root: P,
+    id: Id,
+    image: &[u8],
+) -> Result<()> {
+    let file = AtomicFile::new(
+        root.as_ref()
+            .join(ARK_FOLDER)
+            .join(PREVIEWS_STORAGE_FOLDER)
+            .join(id.to_string()),
+    )?
+    .with_durability(Durability::Fast);
+    let tmp = file.make_temp().with_path(&file.directory)?;
+    (&tmp)
+        .write_all(image)
+        .with_path(&file.directory)?;
+    let current = file.load().with_path(&file.directory)?;
+    file.compare_and_swap(&current, tmp)
+        .with_path(&file.directory)?;
+    if let Err(err) = CacheQuota::previews(root).enforce() {
+        log::warn!("Failed to evict previews: {}", err);
+    }
+    Ok(())
+}
+
+/// Metadata of the resource, parsed
+pub fn load_metadata<P: AsRef<Path>, Id: ResourceId>(
+    root: P,
+    id: Id,
+) -> Result<Value> {
+    let path = metadata_path(&root, id.clone());
+    let bytes = load_raw_metadata(root, id).with_path(&path)?;
+    serde_json::from_slice(&bytes).with_path(&path)
+}
+
+/// Remove the metadata of the resource, e.g. when its content changed.
+/// Metadata is a cache, so all versions are removed at once.
+pub fn invalidate_metadata<P: AsRef<Path>, Id: ResourceId>(
+    root: P,
+    id: Id,
+) -> Result<()> {
+    let path = metadata_path(root, id);
+    match std::fs::remove_dir_all(&path) {
+        Err(err) if err.kind() != std::io::ErrorKind::NotFound => {
+            Err(err).with_path(&path)
+        }
+        _ => Ok(()),
+    }
+}
+
+fn metadata_path<P: AsRef<Path>, Id: ResourceId>(root: P, id: Id) -> PathBuf {
+    root.as_ref()
+        .join(ARK_FOLDER)
+        .join(METADATA_STORAGE_FOLDER)
+        .join(id.to_string())
+}
+
 /// The file must exist if this method is called
 #[allow(dead_code)]
 pub fn load_raw_metadata<P: AsRef<Path>, Id: ResourceId>(
@@ -98,4 +235,73 @@ mod tests {
         let prop2: TestMetadata = serde_json::from_slice(&bytes).unwrap();
         assert_eq!(meta, prop2);
     }
+
+    #[test]
+    fn test_extract_and_invalidate() {
+        initialize();
+
+        let dir = TempDir::new("arklib_test").unwrap();
+        let root = dir.path();
+        let id = Crc32(0x342a3d4a);
+
+        let path = root.join("notes.TXT");
+        std::fs::write(&path, "Some notes").unwrap();
+
+        let mut link = TestMetadata::new();
+        link.insert("title".to_string(), "Notes".to_string());
+        store_metadata(root, id.clone(), &link).unwrap();
+
+        let extracted = extract_metadata(root, &path, id.clone()).unwrap();
+        assert_eq!(extracted["file"]["size"], 10);
+        assert_eq!(extracted["file"]["extension"], "txt");
+
+        let metadata = load_metadata(root, id.clone()).unwrap();
+        assert_eq!(metadata["file"], extracted["file"]);
+        assert_eq!(metadata["title"], "Notes");
+
+        invalidate_metadata(root, id.clone()).unwrap();
+        assert!(load_metadata(root, id.clone()).is_err());
+        // nothing left to invalidate
+        invalidate_metadata(root, id).unwrap();
+    }
+
+    struct Cover;
+
+    impl MetadataExtractor for Cover {
+        fn name(&self) -> &str {
+            "cover"
+        }
+
+        fn extract(&self, _path: &Path) -> Result<Option<Value>> {
+            Ok(None)
+        }
+
+        fn preview(&self, _path: &Path) -> Result<Option<Vec<u8>>> {
+            Ok(Some(b"cover image".to_vec()))
+        }
+    }
+
+    #[test]
+    fn test_extracted_preview_is_stored() {
+        initialize();
+        extractor::register(Supported::extension("withcover"), Cover);
+
+        let dir = TempDir::new("arklib_test").unwrap();
+        let root = dir.path();
+        let id = Crc32(0x342a3d4a);
+        let path = root.join("book.withcover");
+        std::fs::write(&path, "A book").unwrap();
+
+        let extracted = extract_metadata(root, &path, id.clone()).unwrap();
+        assert!(extracted.get("cover").is_none());
+
+        let previews = AtomicFile::new(
+            root.join(ARK_FOLDER)
+                .join(PREVIEWS_STORAGE_FOLDER)
+                .join(id.to_string()),
+        )
+        .unwrap();
+        let preview = previews.load().unwrap().read_content().unwrap();
+        assert_eq!(preview, b"cover image");
+    }
 }
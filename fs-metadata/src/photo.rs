@@ -0,0 +1,46 @@
+//! EXIF metadata of images, e.g. the camera or the time of the shot.
+
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+use serde_json::{Map, Value};
+
+use data_error::{Result, ResultExt};
+
+use crate::extractor::{error, MetadataExtractor};
+
+pub(crate) const EXTENSIONS: &[&str] = &[
+    "jpg", "jpeg", "tif", "tiff", "heic", "heif", "avif", "png", "webp",
+];
+
+pub(crate) struct Exif;
+
+impl MetadataExtractor for Exif {
+    fn name(&self) -> &str {
+        "exif"
+    }
+
+    fn extract(&self, path: &Path) -> Result<Option<Value>> {
+        let file = File::open(path).with_path(path)?;
+        let exif = match exif::Reader::new()
+            .read_from_container(&mut BufReader::new(file))
+        {
+            Ok(exif) => exif,
+            Err(exif::Error::NotFound(_)) => return Ok(None),
+            Err(err) => return Err(error(self.name(), err)).with_path(path),
+        };
+
+        // fields of the thumbnail are left out,
+        // values are normalized to their display form with units
+        let fields: Map<String, Value> = exif
+            .fields()
+            .filter(|field| field.ifd_num == exif::In::PRIMARY)
+            .map(|field| {
+                let value = field.display_value().with_unit(&exif).to_string();
+                (field.tag.to_string(), Value::String(value))
+            })
+            .collect();
+        Ok((!fields.is_empty()).then_some(Value::Object(fields)))
+    }
+}
@@ -0,0 +1,117 @@
+//! Text of resources under `.ark/cache/text/<id>`, e.g. recognized in
+//! screenshots and scans, so that search tools can index it.
+//!
+//! Optical character recognition relies on Tesseract and is only
+//! available with the `ocr` feature.
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use data_error::{Result, ResultExt};
+use data_resource::ResourceId;
+use fs_atomic_versions::atomic::AtomicFile;
+use fs_atomic_versions::durability::Durability;
+use fs_storage::{ARK_FOLDER, TEXT_STORAGE_FOLDER};
+
+/// Extensions of the images text is recognized in
+#[cfg(feature = "ocr")]
+const EXTENSIONS: &[&str] =
+    &["png", "jpg", "jpeg", "tif", "tiff", "bmp", "gif", "webp"];
+
+/// Store `text` as the text of the resource, replacing the previous one.
+/// The text is a cache, so it is written without syncing it to disk.
+pub fn store_text<P: AsRef<Path>, Id: ResourceId>(
+    root: P,
+    id: Id,
+    text: &str,
+) -> Result<()> {
+    let file =
+        AtomicFile::new(text_path(root, id))?.with_durability(Durability::Fast);
+    let tmp = file.make_temp().with_path(&file.directory)?;
+    (&tmp)
+        .write_all(text.as_bytes())
+        .with_path(&file.directory)?;
+    let current = file.load().with_path(&file.directory)?;
+    file.compare_and_swap(&current, tmp)
+        .with_path(&file.directory)
+}
+
+/// The stored text of the resource, if any
+pub fn load_text<P: AsRef<Path>, Id: ResourceId>(
+    root: P,
+    id: Id,
+) -> Result<Option<String>> {
+    let path = text_path(root, id);
+    if !path.exists() {
+        return Ok(None);
+    }
+    let file = AtomicFile::new(&path)?;
+    let latest = file.load().with_path(&path)?;
+    match latest.open().with_path(&latest.path)? {
+        Some(_) => Ok(Some(latest.read_to_string().with_path(&latest.path)?)),
+        None => Ok(None),
+    }
+}
+
+/// Recognize the text of the image at `path` in the given Tesseract
+/// `language`, e.g. `eng`, and store it as the text of the resource.
+///
+/// Returns `None` without running Tesseract if the resource is not an
+/// image, as told by its extension.
+#[cfg(feature = "ocr")]
+pub fn recognize_text<P: AsRef<Path>, Id: ResourceId>(
+    root: P,
+    path: &Path,
+    id: Id,
+    language: &str,
+) -> Result<Option<String>> {
+    let supported = crate::extractor::extension(path)
+        .map_or(false, |extension| EXTENSIONS.contains(&extension.as_str()));
+    if !supported {
+        return Ok(None);
+    }
+    let image = path.to_str().ok_or_else(|| {
+        data_error::ArklibError::Path(format!(
+            "{} is not valid UTF-8",
+            path.display()
+        ))
+    })?;
+    let text = tesseract::ocr(image, language)
+        .map_err(|err| crate::extractor::error("ocr", err))
+        .with_path(path)?;
+    store_text(root, id, &text)?;
+    Ok(Some(text))
+}
+
+fn text_path<P: AsRef<Path>, Id: ResourceId>(root: P, id: Id) -> PathBuf {
+    root.as_ref()
+        .join(ARK_FOLDER)
+        .join(TEXT_STORAGE_FOLDER)
+        .join(id.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use dev_hash::Crc32;
+    use fs_atomic_versions::initialize;
+    use tempdir::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn test_store_and_load_text() {
+        initialize();
+
+        let dir = TempDir::new("arklib_test").unwrap();
+        let root = dir.path();
+        let id = Crc32(0x342a3d4a);
+
+        assert_eq!(load_text(root, id.clone()).unwrap(), None);
+        store_text(root, id.clone(), "Recognized text").unwrap();
+        store_text(root, id.clone(), "Recognized again").unwrap();
+        assert_eq!(
+            load_text(root, id).unwrap().as_deref(),
+            Some("Recognized again")
+        );
+    }
+}
@@ -0,0 +1,85 @@
+//! Properties usable from async code without blocking the runtime.
+//!
+//! The disk IO runs on the blocking thread pool of tokio.
+
+use std::path::Path;
+
+use serde::Serialize;
+use serde_json::Value;
+use tokio::task::spawn_blocking;
+
+use data_error::{ArklibError, Result};
+use data_resource::ResourceId;
+
+use crate::{load_raw_properties, store_properties};
+
+/// Same as [`store_properties`]
+pub async fn store_properties_async<S, P, Id>(
+    root: P,
+    id: Id,
+    properties: &S,
+) -> Result<()>
+where
+    S: Serialize,
+    P: AsRef<Path>,
+    Id: ResourceId + Send + 'static,
+{
+    let root = root.as_ref().to_path_buf();
+    let value = serde_json::to_value(properties)?;
+    spawn_blocking(move || store_properties::<Value, _, _>(root, id, &value))
+        .await
+        .map_err(join_error)?
+}
+
+/// Same as [`load_raw_properties`]
+pub async fn load_raw_properties_async<P, Id>(
+    root: P,
+    id: Id,
+) -> Result<Vec<u8>>
+where
+    P: AsRef<Path>,
+    Id: ResourceId + Send + 'static,
+{
+    let root = root.as_ref().to_path_buf();
+    spawn_blocking(move || load_raw_properties(root, id))
+        .await
+        .map_err(join_error)?
+}
+
+fn join_error(err: tokio::task::JoinError) -> ArklibError {
+    ArklibError::Storage(
+        "properties".to_owned(),
+        format!("Properties task failed: {}", err),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use dev_hash::Crc32;
+    use fs_atomic_versions::initialize;
+    use tempdir::TempDir;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_store_and_load_async() {
+        initialize();
+
+        let dir = TempDir::new("arklib_test").unwrap();
+        let root = dir.path();
+        let id = Crc32(0x342a3d4a);
+
+        let mut prop = HashMap::new();
+        prop.insert("abc".to_string(), "def".to_string());
+        store_properties_async(root, id.clone(), &prop)
+            .await
+            .unwrap();
+
+        let bytes = load_raw_properties_async(root, id).await.unwrap();
+        let prop2: HashMap<String, String> =
+            serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(prop, prop2);
+    }
+}
@@ -0,0 +1,217 @@
+//! Inverted index of the properties of every resource of a root,
+//! to find resources by the value of a property without reading
+//! the properties of every resource.
+
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+
+use serde_json::Value;
+
+use data_error::{Result, ResultExt};
+use data_resource::ResourceId;
+
+use crate::{is_not_found, iter_properties, load_from, properties_folder};
+
+/// Resources by property and value.
+///
+/// Properties are designated by JSON pointers, e.g. `/author` or
+/// `/camera/model`. Every value of an array is indexed under the pointer
+/// of the array, so a resource with `"tags": ["sea", "sky"]` is found
+/// by both `/tags = "sea"` and `/tags = "sky"`. Objects and arrays are
+/// not indexed as a whole.
+///
+/// The index is not notified of changes: call [`PropertiesIndex::update`]
+/// after storing or removing the properties of a resource.
+#[derive(Debug, Clone)]
+pub struct PropertiesIndex<Id: ResourceId> {
+    folder: PathBuf,
+    /// Pointer, then serialized value, to resources
+    resources: HashMap<String, HashMap<String, BTreeSet<Id>>>,
+    /// Pairs of pointer and serialized value indexed for every resource
+    indexed: HashMap<Id, Vec<(String, String)>>,
+}
+
+impl<Id: ResourceId> PropertiesIndex<Id> {
+    /// Index the properties of every resource of `root`.
+    ///
+    /// Properties which can't be read are skipped with a warning,
+    /// see [`iter_properties`].
+    pub fn build<P: AsRef<Path>>(root: P) -> Result<Self> {
+        let mut index = PropertiesIndex {
+            folder: properties_folder(&root),
+            resources: HashMap::new(),
+            indexed: HashMap::new(),
+        };
+        for properties in iter_properties(root)? {
+            match properties {
+                Ok((id, properties)) => index.insert(id, &properties),
+                Err(err) => log::warn!("Skipping properties: {}", err),
+            }
+        }
+        Ok(index)
+    }
+
+    /// Resources whose property at `pointer` is, or contains, `value`
+    pub fn find(&self, pointer: &str, value: &Value) -> Vec<Id> {
+        self.resources
+            .get(pointer)
+            .and_then(|values| values.get(&value.to_string()))
+            .map(|ids| ids.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Pointers of the properties of all resources
+    pub fn pointers(&self) -> impl Iterator<Item = &str> {
+        self.resources.keys().map(String::as_str)
+    }
+
+    /// Number of indexed resources
+    pub fn len(&self) -> usize {
+        self.indexed.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.indexed.is_empty()
+    }
+
+    /// Read the properties of the resource again, replacing its entries.
+    /// A resource without properties is removed from the index.
+    pub fn update(&mut self, id: Id) -> Result<()> {
+        self.remove(&id);
+        let path = self.folder.join(id.to_string());
+        if !path.exists() {
+            return Ok(());
+        }
+        let bytes = match load_from(&path) {
+            Ok(bytes) => bytes,
+            // no version yet, or removed
+            Err(err) if is_not_found(&err) => return Ok(()),
+            Err(err) => return Err(err),
+        };
+        let properties: Value =
+            serde_json::from_slice(&bytes).with_path(&path)?;
+        self.insert(id, &properties);
+        Ok(())
+    }
+
+    fn insert(&mut self, id: Id, properties: &Value) {
+        let mut pairs = Vec::new();
+        flatten(properties, &mut String::new(), &mut pairs);
+        pairs.sort();
+        pairs.dedup();
+        for (pointer, value) in &pairs {
+            self.resources
+                .entry(pointer.clone())
+                .or_default()
+                .entry(value.clone())
+                .or_default()
+                .insert(id.clone());
+        }
+        if !pairs.is_empty() {
+            self.indexed.insert(id, pairs);
+        }
+    }
+
+    /// Remove the entries of the resource
+    pub fn remove(&mut self, id: &Id) {
+        let Some(pairs) = self.indexed.remove(id) else {
+            return;
+        };
+        for (pointer, value) in pairs {
+            let Some(values) = self.resources.get_mut(&pointer) else {
+                continue;
+            };
+            if let Some(ids) = values.get_mut(&value) {
+                ids.remove(id);
+                if ids.is_empty() {
+                    values.remove(&value);
+                }
+            }
+            if values.is_empty() {
+                self.resources.remove(&pointer);
+            }
+        }
+    }
+}
+
+/// Collect the pointers and serialized values of the scalars of `value`
+fn flatten(
+    value: &Value,
+    pointer: &mut String,
+    pairs: &mut Vec<(String, String)>,
+) {
+    match value {
+        Value::Null => {}
+        Value::Object(object) => {
+            for (key, value) in object {
+                let len = pointer.len();
+                pointer.push('/');
+                // escaping as defined by RFC 6901
+                pointer.push_str(&key.replace('~', "~0").replace('/', "~1"));
+                flatten(value, pointer, pairs);
+                pointer.truncate(len);
+            }
+        }
+        Value::Array(values) => {
+            for value in values {
+                if !value.is_array() {
+                    flatten(value, pointer, pairs);
+                }
+            }
+        }
+        scalar => {
+            if !pointer.is_empty() {
+                pairs.push((pointer.clone(), scalar.to_string()));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use dev_hash::Crc32;
+    use fs_atomic_versions::initialize;
+    use serde_json::json;
+    use tempdir::TempDir;
+
+    use super::*;
+    use crate::{remove_properties, store_properties};
+
+    #[test]
+    fn test_find_by_property() {
+        initialize();
+
+        let dir = TempDir::new("arklib_test").unwrap();
+        let root = dir.path();
+        let (a, b, c) = (Crc32(1), Crc32(2), Crc32(3));
+        store_properties(
+            root,
+            a.clone(),
+            &json!({ "author": "X", "tags": ["sea", "sky"] }),
+        )
+        .unwrap();
+        store_properties(
+            root,
+            b.clone(),
+            &json!({ "author": "Y", "camera": { "model": "X100" } }),
+        )
+        .unwrap();
+        store_properties(root, c.clone(), &json!({ "author": "X" })).unwrap();
+
+        let mut index = PropertiesIndex::build(root).unwrap();
+        assert_eq!(index.len(), 3);
+        assert_eq!(index.find("/author", &json!("X")), [a.clone(), c.clone()]);
+        assert_eq!(index.find("/tags", &json!("sky")), [a.clone()]);
+        assert_eq!(index.find("/camera/model", &json!("X100")), [b.clone()]);
+        assert!(index.find("/author", &json!("Z")).is_empty());
+
+        store_properties(root, b.clone(), &json!({ "author": "X" })).unwrap();
+        remove_properties(root, c.clone()).unwrap();
+        index.update(b.clone()).unwrap();
+        index.update(c).unwrap();
+        assert_eq!(index.len(), 2);
+        // merged with the previous value
+        assert_eq!(index.find("/author", &json!("X")), [a, b.clone()]);
+        assert_eq!(index.find("/author", &json!("Y")), [b]);
+    }
+}
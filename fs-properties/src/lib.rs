@@ -1,15 +1,34 @@
 use serde::{de::DeserializeOwned, Serialize};
 use serde_json::Value;
+use std::any::type_name;
+use std::collections::HashSet;
+use std::ffi::OsString;
 use std::fmt::Debug;
-use std::io::Read;
-use std::path::Path;
+use std::fs;
+use std::io::{ErrorKind, Read};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
 
-use data_error::Result;
-use data_json::merge;
+use data_error::{ArklibError, BulkResult, Result, ResultExt};
+use data_json::merge_with;
+pub use data_json::MergeStrategy;
 use data_resource::ResourceId;
-use fs_atomic_versions::atomic::{modify_json, AtomicFile};
+use fs_atomic_versions::app_id;
+use fs_atomic_versions::atomic::{
+    modify, modify_json, try_modify_json, AtomicFile,
+};
+use fs_atomic_versions::durability::sync_dir;
 use fs_storage::ARK_FOLDER;
 
+#[cfg(feature = "tokio")]
+pub mod async_properties;
+pub mod index;
+pub mod schema;
+
+#[cfg(feature = "tokio")]
+pub use async_properties::{load_raw_properties_async, store_properties_async};
+pub use index::PropertiesIndex;
+
 pub const PROPERTIES_STORAGE_FOLDER: &str = "user/properties";
 
 pub fn store_properties<
@@ -21,25 +40,183 @@ pub fn store_properties<
     id: Id,
     properties: &S,
 ) -> Result<()> {
-    let file = AtomicFile::new(
-        root.as_ref()
-            .join(ARK_FOLDER)
-            .join(PROPERTIES_STORAGE_FOLDER)
-            .join(id.to_string()),
-    )?;
+    store_properties_with(root, id, properties, &MergeStrategy::DeepMerge)
+}
+
+/// Store the properties of the resource, combining them with the existing
+/// ones according to `strategy`, e.g. [`MergeStrategy::Replace`] to
+/// overwrite values instead of collecting them into arrays
+pub fn store_properties_with<S: Serialize, P: AsRef<Path>, Id: ResourceId>(
+    root: P,
+    id: Id,
+    properties: &S,
+    strategy: &MergeStrategy,
+) -> Result<()> {
+    let file = AtomicFile::new(properties_path(root, id))?;
+    let new_value = serde_json::to_value(properties)?;
+    merge_into(&file, new_value, strategy)
+}
+
+/// Store the properties of many resources, as [`store_properties`] does
+/// for each of them. Every resource is attempted, failures are reported
+/// along with their id.
+///
+/// The id of this app is read and the properties folder is created once
+/// for the whole batch. Every new version is synced like with
+/// [`store_properties`], while the properties folder, which gets a new
+/// entry for every resource without properties yet, is synced once at
+/// the end. If that fails, these resources are reported as failed.
+pub fn store_properties_batch<S, P, Id, I>(
+    root: P,
+    properties: I,
+) -> BulkResult<(), Id>
+where
+    S: Serialize,
+    P: AsRef<Path>,
+    Id: ResourceId,
+    I: IntoIterator<Item = (Id, S)>,
+{
+    let start = Instant::now();
+    let folder = properties_folder(root);
+    let mut report = BulkResult::new();
+    let app_id = app_id::read().and_then(|app_id| {
+        fs::create_dir_all(&folder).with_path(&folder)?;
+        Ok(app_id)
+    });
+
+    let mut created = HashSet::new();
+    for (id, value) in properties {
+        let directory = folder.join(id.to_string());
+        let is_new = !directory.exists();
+        let result = serde_json::to_value(&value)
+            .map_err(Into::into)
+            .and_then(|new_value| {
+                let app_id = shared(&app_id)?;
+                let file = AtomicFile::with_app_id(&directory, app_id)?;
+                merge_into(&file, new_value, &MergeStrategy::DeepMerge)
+            });
+        if is_new && result.is_ok() {
+            created.insert(id.clone());
+        }
+        report.record(id, result);
+    }
+
+    if !created.is_empty() {
+        if let Err(err) = sync_dir(&folder) {
+            let message =
+                format!("failed to sync {}: {}", folder.display(), err);
+            let (unsynced, synced) = std::mem::take(&mut report.succeeded)
+                .into_iter()
+                .partition(|(id, _)| created.contains(id));
+            report.succeeded = synced;
+            for (id, _) in unsynced {
+                let err = ArklibError::Storage(
+                    "properties".to_owned(),
+                    message.clone(),
+                );
+                report.record(id, Err(err));
+            }
+        }
+    }
+    report.elapsed = start.elapsed();
+    report
+}
+
+/// Load the raw properties of many resources, as [`load_raw_properties`]
+/// does for each of them. Resources without properties are reported
+/// as failed.
+///
+/// The id of this app is read and the properties folder is listed once
+/// for the whole batch.
+pub fn load_properties_batch<P, Id, I>(
+    root: P,
+    ids: I,
+) -> BulkResult<Vec<u8>, Id>
+where
+    P: AsRef<Path>,
+    Id: ResourceId,
+    I: IntoIterator<Item = Id>,
+{
+    let folder = properties_folder(root);
+    let stored: Result<(String, HashSet<OsString>)> =
+        app_id::read().and_then(|app_id| {
+            let names = match fs::read_dir(&folder) {
+                Ok(entries) => entries
+                    .flatten()
+                    .map(|entry| entry.file_name())
+                    .collect(),
+                Err(err) if err.kind() == ErrorKind::NotFound => HashSet::new(),
+                Err(err) => return Err(err).with_path(&folder),
+            };
+            Ok((app_id, names))
+        });
+    BulkResult::run(ids, |id| {
+        let (app_id, names) = shared(&stored)?;
+        let storage = folder.join(id.to_string());
+        if !names.contains(storage.file_name().unwrap_or_default()) {
+            return Err(not_found(&storage));
+        }
+        load_file(&AtomicFile::with_app_id(&storage, app_id)?, &storage)
+    })
+}
+
+/// Outcome of the setup of a batch, failing every item if it failed
+fn shared<T>(setup: &Result<T>) -> Result<&T> {
+    setup.as_ref().map_err(|err| {
+        ArklibError::Storage("properties".to_owned(), err.to_string())
+    })
+}
+
+/// Merge `new_value` into the properties, not writing them if
+/// the merged properties are invalid for the registered schemas
+fn merge_into(
+    file: &AtomicFile,
+    new_value: Value,
+    strategy: &MergeStrategy,
+) -> Result<()> {
+    try_modify_json(file, |current_data: &mut Option<Value>| -> Result<()> {
+        let new_value = new_value.clone();
+        let merged = match current_data.take() {
+            Some(old_value) => merge_with(old_value, new_value, strategy),
+            None => new_value,
+        };
+        schema::validate_registered(&merged)?;
+        *current_data = Some(merged);
+        Ok(())
+    })
+    .with_path(&file.directory)
+}
+
+/// Remove the given keys from the properties of the resource,
+/// writing a new version. Missing keys are ignored.
+pub fn remove_property_keys<P: AsRef<Path>, Id: ResourceId>(
+    root: P,
+    id: Id,
+    keys: &[&str],
+) -> Result<()> {
+    let file = AtomicFile::new(properties_path(root, id))?;
     modify_json(&file, |current_data: &mut Option<Value>| {
-        let new_value = serde_json::to_value(properties).unwrap();
-        match current_data {
-            Some(old_data) => {
-                // Should not failed unless serialize failed which should never
-                // happen
-                let old_value = serde_json::to_value(old_data).unwrap();
-                *current_data = Some(merge(old_value, new_value));
+        if let Some(Value::Object(properties)) = current_data {
+            for key in keys {
+                properties.remove(*key);
             }
-            None => *current_data = Some(new_value),
         }
-    })?;
-    Ok(())
+    })
+    .with_path(&file.directory)
+}
+
+/// Remove all properties of the resource. A `null` version is written,
+/// so the removal wins over older versions synced from other devices,
+/// and the properties are reported missing afterwards.
+pub fn remove_properties<P: AsRef<Path>, Id: ResourceId>(
+    root: P,
+    id: Id,
+) -> Result<()> {
+    let file = AtomicFile::new(properties_path(root, id))?;
+    modify_json(&file, |current_data: &mut Option<Value>| {
+        *current_data = None;
+    })
+    .with_path(&file.directory)
 }
 
 /// The file must exist if this method is called
@@ -47,25 +224,161 @@ pub fn load_raw_properties<P: AsRef<Path>, Id: ResourceId>(
     root: P,
     id: Id,
 ) -> Result<Vec<u8>> {
-    let storage = root
-        .as_ref()
-        .join(ARK_FOLDER)
-        .join(PROPERTIES_STORAGE_FOLDER)
-        .join(id.to_string());
-    let file = AtomicFile::new(storage)?;
-    let read_file = file.load()?;
-    if let Some(mut real_file) = read_file.open()? {
-        let mut content = vec![];
-        real_file.read_to_end(&mut content)?;
-        Ok(content)
+    load_from(&properties_path(root, id))
+}
+
+/// Load the properties of the resource as `T`, after validating them
+/// with the [registered schemas](schema::register)
+pub fn load_properties<T: DeserializeOwned, P: AsRef<Path>, Id: ResourceId>(
+    root: P,
+    id: Id,
+) -> Result<T> {
+    let storage = properties_path(root, id);
+    let bytes = load_from(&storage)?;
+    let value: Value = serde_json::from_slice(&bytes).with_path(&storage)?;
+    schema::validate_registered(&value).with_path(&storage)?;
+    T::deserialize(value)
+        .map_err(|err| {
+            ArklibError::Storage(
+                "properties".to_owned(),
+                format!("can't be read as {}: {}", type_name::<T>(), err),
+            )
+        })
+        .with_path(&storage)
+}
+
+/// Versions of the properties of the resource which can still be loaded,
+/// oldest first. Only the most recent versions are kept.
+pub fn list_property_versions<P: AsRef<Path>, Id: ResourceId>(
+    root: P,
+    id: Id,
+) -> Result<Vec<usize>> {
+    let storage = properties_path(root, id);
+    let file = AtomicFile::new(&storage)?;
+    let mut versions: Vec<usize> = file
+        .versions()
+        .with_path(&storage)?
+        .iter()
+        .map(|file| file.version)
+        .collect();
+    versions.dedup();
+    Ok(versions)
+}
+
+/// Raw properties of the resource as they were at `version`
+pub fn load_properties_at<P: AsRef<Path>, Id: ResourceId>(
+    root: P,
+    id: Id,
+    version: usize,
+) -> Result<Vec<u8>> {
+    let storage = properties_path(root, id);
+    let file = AtomicFile::new(&storage)?;
+    let read_file = file.load_version(version).with_path(&storage)?;
+    read_file
+        .read_content()
+        .with_path(&read_file.path)
+}
+
+/// Undo the changes made to the properties of the resource since `version`,
+/// by writing its content as a new version. The history is kept, so the
+/// rollback can be undone as well.
+pub fn rollback<P: AsRef<Path>, Id: ResourceId>(
+    root: P,
+    id: Id,
+    version: usize,
+) -> Result<()> {
+    let storage = properties_path(root, id);
+    let file = AtomicFile::new(&storage)?;
+    let read_file = file.load_version(version).with_path(&storage)?;
+    let content = read_file
+        .read_content()
+        .with_path(&read_file.path)?;
+    modify(&file, |_| content.clone()).with_path(&storage)
+}
+
+/// Properties of every resource of the root, read lazily.
+///
+/// Resources whose properties were removed are skipped, as well as entries
+/// of the properties folder which are not named after a resource id.
+pub fn iter_properties<P: AsRef<Path>, Id: ResourceId>(
+    root: P,
+) -> Result<impl Iterator<Item = Result<(Id, Value)>>> {
+    let folder = properties_folder(root);
+    let entries = if folder.exists() {
+        Some(fs::read_dir(&folder).with_path(&folder)?)
     } else {
-        Err(std::io::Error::new(
-            std::io::ErrorKind::NotFound,
-            "File not found",
-        ))?
+        None
+    };
+    Ok(entries
+        .into_iter()
+        .flatten()
+        .filter_map(move |entry| {
+            let entry = match entry.with_path(&folder) {
+                Ok(entry) => entry,
+                Err(err) => return Some(Err(err)),
+            };
+            let path = entry.path();
+            let Some(Ok(id)) = entry.file_name().to_str().map(str::parse::<Id>)
+            else {
+                log::warn!("Skipping {}: not a resource id", path.display());
+                return None;
+            };
+            let bytes = match load_from(&path) {
+                Ok(bytes) => bytes,
+                // no version yet, or removed
+                Err(err) if is_not_found(&err) => return None,
+                Err(err) => return Some(Err(err)),
+            };
+            Some(
+                serde_json::from_slice(&bytes)
+                    .with_path(&path)
+                    .map(|properties| (id, properties)),
+            )
+        }))
+}
+
+fn is_not_found(err: &ArklibError) -> bool {
+    match err {
+        ArklibError::WithPath(_, inner) => is_not_found(inner),
+        ArklibError::Io(err) => err.kind() == std::io::ErrorKind::NotFound,
+        _ => false,
     }
 }
 
+fn load_from(storage: &Path) -> Result<Vec<u8>> {
+    load_file(&AtomicFile::new(storage)?, storage)
+}
+
+fn load_file(file: &AtomicFile, storage: &Path) -> Result<Vec<u8>> {
+    let read_file = file.load().with_path(storage)?;
+    if let Some(mut real_file) = read_file.open().with_path(&read_file.path)? {
+        let mut content = vec![];
+        real_file
+            .read_to_end(&mut content)
+            .with_path(&read_file.path)?;
+        // written by `remove_properties`
+        if !matches!(serde_json::from_slice(&content), Ok(Value::Null)) {
+            return Ok(content);
+        }
+    }
+    Err(not_found(storage))
+}
+
+fn not_found(storage: &Path) -> ArklibError {
+    let err = std::io::Error::new(ErrorKind::NotFound, "File not found");
+    ArklibError::WithPath(storage.to_path_buf(), Box::new(err.into()))
+}
+
+fn properties_folder<P: AsRef<Path>>(root: P) -> PathBuf {
+    root.as_ref()
+        .join(ARK_FOLDER)
+        .join(PROPERTIES_STORAGE_FOLDER)
+}
+
+fn properties_path<P: AsRef<Path>, Id: ResourceId>(root: P, id: Id) -> PathBuf {
+    properties_folder(root).join(id.to_string())
+}
+
 #[cfg(test)]
 mod tests {
     use fs_atomic_versions::initialize;
@@ -98,4 +411,236 @@ mod tests {
         let prop2: TestProperties = serde_json::from_slice(&bytes).unwrap();
         assert_eq!(prop, prop2);
     }
+
+    #[test]
+    fn test_load_missing_reports_path() {
+        initialize();
+
+        let dir = TempDir::new("arklib_test").unwrap();
+        let root = dir.path();
+        let id = Crc32(0x342a3d4a);
+
+        let err = load_raw_properties(root, id.clone()).unwrap_err();
+        let expected = root
+            .join(ARK_FOLDER)
+            .join(PROPERTIES_STORAGE_FOLDER)
+            .join(id.to_string());
+        assert!(
+            err.to_string()
+                .contains(&expected.display().to_string()),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_remove_keys_and_properties() {
+        initialize();
+
+        let dir = TempDir::new("arklib_test").unwrap();
+        let root = dir.path();
+        let id = Crc32(0x342a3d4a);
+
+        let mut prop = TestProperties::new();
+        prop.insert("abc".to_string(), "def".to_string());
+        prop.insert("xyz".to_string(), "123".to_string());
+        store_properties(root, id.clone(), &prop).unwrap();
+
+        remove_property_keys(root, id.clone(), &["abc", "missing"]).unwrap();
+        let bytes = load_raw_properties(root, id.clone()).unwrap();
+        let prop2: TestProperties = serde_json::from_slice(&bytes).unwrap();
+        prop.remove("abc");
+        assert_eq!(prop, prop2);
+
+        remove_properties(root, id.clone()).unwrap();
+        assert!(load_raw_properties(root, id.clone()).is_err());
+
+        // properties stored after the removal don't bring back old keys
+        let mut fresh = TestProperties::new();
+        fresh.insert("new".to_string(), "value".to_string());
+        store_properties(root, id.clone(), &fresh).unwrap();
+        let bytes = load_raw_properties(root, id).unwrap();
+        let prop3: TestProperties = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(fresh, prop3);
+    }
+
+    #[test]
+    fn test_store_and_load_batch() {
+        initialize();
+
+        let dir = TempDir::new("arklib_test").unwrap();
+        let root = dir.path();
+
+        let batch: Vec<(Crc32, TestProperties)> = (0..10)
+            .map(|n| {
+                let mut prop = TestProperties::new();
+                prop.insert("n".to_string(), n.to_string());
+                (Crc32(n), prop)
+            })
+            .collect();
+        let stored = store_properties_batch(root, batch.clone());
+        assert!(!stored.has_failures());
+        assert_eq!(stored.len(), 10);
+
+        let ids = batch
+            .iter()
+            .map(|(id, _)| id.clone())
+            .chain([Crc32(42)]);
+        let loaded = load_properties_batch(root, ids);
+        assert_eq!(loaded.succeeded.len(), 10);
+        assert_eq!(loaded.failed.len(), 1);
+        assert_eq!(loaded.failed[0].0, Crc32(42));
+        // loading doesn't create folders for missing resources
+        assert!(!properties_path(root, Crc32(42)).exists());
+        for ((id, bytes), (expected_id, expected)) in
+            loaded.succeeded.iter().zip(&batch)
+        {
+            let prop: TestProperties = serde_json::from_slice(bytes).unwrap();
+            assert_eq!(id, expected_id);
+            assert_eq!(&prop, expected);
+        }
+    }
+
+    struct Rated;
+
+    impl schema::Schema for Rated {
+        const NAME: &'static str = "Rated";
+
+        fn validate(value: &Value) -> Result<()> {
+            schema::get_field::<u8>(Self::NAME, value, "/rating").map(|_| ())
+        }
+    }
+
+    #[derive(Debug, PartialEq, serde::Deserialize)]
+    struct RatedProperties {
+        rated: RatedValue,
+    }
+
+    #[derive(Debug, PartialEq, serde::Deserialize)]
+    struct RatedValue {
+        rating: u8,
+    }
+
+    #[test]
+    fn test_typed_properties() {
+        initialize();
+        schema::register::<Rated>("rated");
+
+        let dir = TempDir::new("arklib_test").unwrap();
+        let root = dir.path();
+        let id = Crc32(0x342a3d4a);
+
+        let invalid = serde_json::json!({ "rated": { "rating": "high" } });
+        assert!(store_properties(root, id.clone(), &invalid).is_err());
+        assert!(load_raw_properties(root, id.clone()).is_err());
+
+        let valid = serde_json::json!({ "rated": { "rating": 5 } });
+        store_properties(root, id.clone(), &valid).unwrap();
+        let typed: RatedProperties = load_properties(root, id.clone()).unwrap();
+        assert_eq!(typed.rated, RatedValue { rating: 5 });
+
+        // merging would collect both ratings into an array
+        let other = serde_json::json!({ "rated": { "rating": 4 } });
+        assert!(store_properties(root, id.clone(), &other).is_err());
+        let typed: RatedProperties = load_properties(root, id.clone()).unwrap();
+        assert_eq!(typed.rated, RatedValue { rating: 5 });
+
+        let err =
+            load_properties::<TestProperties, _, _>(root, id).unwrap_err();
+        assert!(err.to_string().contains("HashMap"), "{}", err);
+    }
+
+    #[test]
+    fn test_versions_and_rollback() {
+        initialize();
+
+        let dir = TempDir::new("arklib_test").unwrap();
+        let root = dir.path();
+        let id = Crc32(0x342a3d4a);
+
+        let mut prop = TestProperties::new();
+        prop.insert("abc".to_string(), "def".to_string());
+        store_properties(root, id.clone(), &prop).unwrap();
+        let mut edit = TestProperties::new();
+        edit.insert("abc".to_string(), "oops".to_string());
+        store_properties(root, id.clone(), &edit).unwrap();
+
+        assert_eq!(list_property_versions(root, id.clone()).unwrap(), [1, 2]);
+        let bytes = load_properties_at(root, id.clone(), 1).unwrap();
+        let first: TestProperties = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(first, prop);
+        assert!(load_properties_at(root, id.clone(), 3).is_err());
+
+        rollback(root, id.clone(), 1).unwrap();
+        assert_eq!(
+            list_property_versions(root, id.clone()).unwrap(),
+            [1, 2, 3]
+        );
+        let bytes = load_raw_properties(root, id).unwrap();
+        let current: TestProperties = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(current, prop);
+    }
+
+    #[test]
+    fn test_store_with_strategy() {
+        initialize();
+
+        let dir = TempDir::new("arklib_test").unwrap();
+        let root = dir.path();
+        let id = Crc32(0x342a3d4a);
+
+        let old = serde_json::json!({ "title": "old", "tags": ["a", "b"] });
+        store_properties(root, id.clone(), &old).unwrap();
+        store_properties(
+            root,
+            id.clone(),
+            &serde_json::json!({ "title": "new" }),
+        )
+        .unwrap();
+        let bytes = load_raw_properties(root, id.clone()).unwrap();
+        let merged: Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(merged["title"], serde_json::json!(["old", "new"]));
+
+        let strategy = MergeStrategy::per_key(
+            MergeStrategy::ArrayReplace,
+            [("title", MergeStrategy::Replace)],
+        );
+        let new = serde_json::json!({ "title": "newer", "tags": [] });
+        store_properties_with(root, id.clone(), &new, &strategy).unwrap();
+        let bytes = load_raw_properties(root, id).unwrap();
+        let replaced: Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(replaced, new);
+    }
+
+    #[test]
+    fn test_iter_properties() {
+        initialize();
+
+        let dir = TempDir::new("arklib_test").unwrap();
+        let root = dir.path();
+
+        let mut prop = TestProperties::new();
+        prop.insert("abc".to_string(), "def".to_string());
+        for n in 0..3 {
+            store_properties(root, Crc32(n), &prop).unwrap();
+        }
+        remove_properties(root, Crc32(1)).unwrap();
+
+        let mut all: Vec<(Crc32, Value)> = iter_properties(root)
+            .unwrap()
+            .collect::<Result<_>>()
+            .unwrap();
+        all.sort_by(|a, b| a.0.cmp(&b.0));
+        let ids: Vec<Crc32> = all.iter().map(|(id, _)| id.clone()).collect();
+        assert_eq!(ids, [Crc32(0), Crc32(2)]);
+        assert_eq!(all[0].1, serde_json::to_value(&prop).unwrap());
+
+        let empty = TempDir::new("arklib_test").unwrap();
+        assert_eq!(
+            iter_properties::<_, Crc32>(empty.path())
+                .unwrap()
+                .count(),
+            0
+        );
+    }
 }
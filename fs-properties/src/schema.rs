@@ -0,0 +1,352 @@
+//! Typed access to properties, see [`properties_schema`](crate::properties_schema).
+//!
+//! Schemas can be [registered](register) for a namespace, i.e. a top-level
+//! key of the properties, so that values are validated when they are
+//! stored or loaded.
+
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+use lazy_static::lazy_static;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Map;
+pub use serde_json::Value;
+
+use data_error::ArklibError;
+pub use data_error::Result;
+
+type Validator = fn(&Value) -> Result<()>;
+
+lazy_static! {
+    static ref REGISTRY: RwLock<HashMap<String, Validator>> =
+        RwLock::new(HashMap::new());
+}
+
+/// Properties with a known shape
+pub trait Schema {
+    /// Name of the schema, used in errors
+    const NAME: &'static str;
+
+    /// Check that the fields of the schema present in `value`
+    /// have the expected types. Missing fields are valid.
+    fn validate(value: &Value) -> Result<()>;
+}
+
+fn error(schema: &str, pointer: &str, message: impl ToString) -> ArklibError {
+    ArklibError::Storage(
+        schema.to_owned(),
+        format!("{}: {}", pointer, message.to_string()),
+    )
+}
+
+/// Validate the properties under `namespace` with the schema `S`, replacing
+/// the schema registered before, if any. The empty namespace stands for
+/// the whole properties.
+pub fn register<S: Schema>(namespace: &str) {
+    REGISTRY
+        .write()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .insert(namespace.to_owned(), S::validate);
+}
+
+/// Stop validating the properties under `namespace`,
+/// returning whether a schema was registered
+pub fn unregister(namespace: &str) -> bool {
+    REGISTRY
+        .write()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .remove(namespace)
+        .is_some()
+}
+
+/// Validate `properties` with the schemas of the namespaces it contains
+pub fn validate_registered(properties: &Value) -> Result<()> {
+    let registry = REGISTRY
+        .read()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    for (namespace, validate) in registry.iter() {
+        let value = if namespace.is_empty() {
+            Some(properties)
+        } else {
+            properties.get(namespace)
+        };
+        match value {
+            None | Some(Value::Null) => {}
+            Some(value) => validate(value).map_err(|err| {
+                ArklibError::Storage(
+                    namespace.clone(),
+                    format!("invalid properties: {}", err),
+                )
+            })?,
+        }
+    }
+    Ok(())
+}
+
+/// Value at the JSON pointer `pointer`, `None` if it is absent or `null`
+pub fn get_field<T: DeserializeOwned>(
+    schema: &str,
+    properties: &Value,
+    pointer: &str,
+) -> Result<Option<T>> {
+    match properties.pointer(pointer) {
+        None | Some(Value::Null) => Ok(None),
+        Some(value) => T::deserialize(value)
+            .map(Some)
+            .map_err(|err| error(schema, pointer, err)),
+    }
+}
+
+/// Replace the value at the JSON pointer `pointer`,
+/// creating the missing parent objects
+pub fn set_field<T: Serialize>(
+    schema: &str,
+    properties: &mut Value,
+    pointer: &str,
+    value: &T,
+) -> Result<()> {
+    let value = serde_json::to_value(value)
+        .map_err(|err| error(schema, pointer, err))?;
+
+    let mut current = properties;
+    for token in pointer.split('/').skip(1) {
+        // escaping as defined by RFC 6901
+        let key = token.replace("~1", "/").replace("~0", "~");
+        if current.is_null() {
+            *current = Value::Object(Map::new());
+        }
+        let Value::Object(object) = current else {
+            return Err(error(schema, pointer, "parent is not an object"));
+        };
+        current = object.entry(key).or_insert(Value::Null);
+    }
+    *current = value;
+    Ok(())
+}
+
+/// Declare a typed wrapper around the properties of a resource.
+///
+/// Every field is given a getter, a setter, its type and its location
+/// as a JSON pointer. The wrapper keeps the whole JSON document, so fields
+/// which are not part of the schema are written back untouched.
+///
+/// ```
+/// use fs_properties::properties_schema;
+///
+/// properties_schema! {
+///     /// Properties of a photo
+///     pub struct Photo {
+///         get_title, set_title: String = "/title",
+///         get_rating, set_rating: f64 = "/rating",
+///         get_model, set_model: String = "/camera/model",
+///     }
+/// }
+///
+/// let mut photo = Photo::new();
+/// photo.set_title("Sunset".to_owned()).unwrap();
+/// assert_eq!(photo.get_title().unwrap().as_deref(), Some("Sunset"));
+/// assert_eq!(photo.get_rating().unwrap(), None);
+/// ```
+#[macro_export]
+macro_rules! properties_schema {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident {
+            $(
+                $get:ident, $set:ident: $ty:ty = $pointer:literal
+            ),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, PartialEq)]
+        $vis struct $name($crate::schema::Value);
+
+        impl $name {
+            /// Properties without any field
+            pub fn new() -> Self {
+                Self($crate::schema::Value::Object(Default::default()))
+            }
+
+            /// Wrap the properties, checking the types of the fields
+            pub fn from_value(
+                value: $crate::schema::Value,
+            ) -> $crate::schema::Result<Self> {
+                <Self as $crate::schema::Schema>::validate(&value)?;
+                Ok(Self(value))
+            }
+
+            /// The whole document, including fields
+            /// which are not part of the schema
+            pub fn as_value(&self) -> &$crate::schema::Value {
+                &self.0
+            }
+
+            pub fn into_value(self) -> $crate::schema::Value {
+                self.0
+            }
+
+            $(
+                pub fn $get(&self) -> $crate::schema::Result<Option<$ty>> {
+                    $crate::schema::get_field(
+                        stringify!($name),
+                        &self.0,
+                        $pointer,
+                    )
+                }
+
+                pub fn $set(
+                    &mut self,
+                    value: $ty,
+                ) -> $crate::schema::Result<()> {
+                    $crate::schema::set_field(
+                        stringify!($name),
+                        &mut self.0,
+                        $pointer,
+                        &value,
+                    )
+                }
+            )*
+        }
+
+        impl Default for $name {
+            fn default() -> Self {
+                Self::new()
+            }
+        }
+
+        impl $crate::schema::Schema for $name {
+            const NAME: &'static str = stringify!($name);
+
+            fn validate(
+                value: &$crate::schema::Value,
+            ) -> $crate::schema::Result<()> {
+                $(
+                    $crate::schema::get_field::<$ty>(
+                        stringify!($name),
+                        value,
+                        $pointer,
+                    )?;
+                )*
+                Ok(())
+            }
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+    use serde_json::json;
+
+    use super::*;
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct Location {
+        lat: f64,
+        lon: f64,
+    }
+
+    properties_schema! {
+        struct Photo {
+            get_title, set_title: String = "/title",
+            get_rating, set_rating: f64 = "/rating",
+            get_tags, set_tags: Vec<String> = "/tags",
+            get_location, set_location: Location = "/location",
+            get_model, set_model: String = "/camera/model",
+        }
+    }
+
+    #[test]
+    fn get_and_set_round_trip() {
+        let mut photo = Photo::new();
+        photo.set_title("Sunset".to_owned()).unwrap();
+        photo.set_rating(4.5).unwrap();
+        photo
+            .set_tags(vec!["sea".to_owned(), "sky".to_owned()])
+            .unwrap();
+        let location = Location {
+            lat: 43.3,
+            lon: 5.4,
+        };
+        photo.set_location(location.clone()).unwrap();
+        photo.set_model("X100".to_owned()).unwrap();
+
+        assert_eq!(
+            photo.as_value(),
+            &json!({
+                "title": "Sunset",
+                "rating": 4.5,
+                "tags": ["sea", "sky"],
+                "location": { "lat": 43.3, "lon": 5.4 },
+                "camera": { "model": "X100" },
+            })
+        );
+
+        let photo = Photo::from_value(photo.into_value()).unwrap();
+        assert_eq!(photo.get_title().unwrap().as_deref(), Some("Sunset"));
+        assert_eq!(photo.get_rating().unwrap(), Some(4.5));
+        assert_eq!(photo.get_tags().unwrap().unwrap().len(), 2);
+        assert_eq!(photo.get_location().unwrap(), Some(location));
+        assert_eq!(photo.get_model().unwrap().as_deref(), Some("X100"));
+    }
+
+    #[test]
+    fn unknown_fields_are_preserved() {
+        let mut photo = Photo::from_value(json!({
+            "title": "Sunset",
+            "album": { "name": "Holidays" },
+            "camera": { "lens": "23mm" },
+        }))
+        .unwrap();
+        assert_eq!(photo.get_rating().unwrap(), None);
+
+        photo.set_title("Sunrise".to_owned()).unwrap();
+        photo.set_model("X100".to_owned()).unwrap();
+        assert_eq!(
+            photo.into_value(),
+            json!({
+                "title": "Sunrise",
+                "album": { "name": "Holidays" },
+                "camera": { "lens": "23mm", "model": "X100" },
+            })
+        );
+    }
+
+    #[test]
+    fn registered_namespaces_are_validated() {
+        register::<Photo>("registered_photo");
+
+        validate_registered(&json!({
+            "registered_photo": { "rating": 4.5 },
+            "other": { "rating": "high" },
+        }))
+        .unwrap();
+        let err = validate_registered(&json!({
+            "registered_photo": { "rating": "high" },
+        }))
+        .unwrap_err();
+        assert!(err.to_string().contains("registered_photo"), "{}", err);
+        assert!(err.to_string().contains("/rating"), "{}", err);
+
+        assert!(unregister("registered_photo"));
+        validate_registered(&json!({
+            "registered_photo": { "rating": "high" },
+        }))
+        .unwrap();
+    }
+
+    #[test]
+    fn type_mismatch_is_an_error() {
+        let invalid = json!({ "title": "Sunset", "rating": "high" });
+        let err = Photo::validate(&invalid).unwrap_err();
+        assert!(err.to_string().contains("/rating"), "{}", err);
+        assert!(Photo::from_value(invalid).is_err());
+
+        assert!(Photo::validate(&json!({ "tags": [1, 2] })).is_err());
+        assert!(Photo::validate(&json!({ "location": { "lat": 1 } })).is_err());
+
+        // The parent of a field must be an object
+        let mut photo = Photo::from_value(json!({ "camera": "X100" })).unwrap();
+        assert!(photo.set_model("X100".to_owned()).is_err());
+    }
+}
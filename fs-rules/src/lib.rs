@@ -0,0 +1,223 @@
+use std::path::{Path, PathBuf};
+
+use serde_json::{Map, Value};
+
+use data_error::{BulkResult, Result};
+use data_resource::ResourceId;
+use fs_index::IndexEvent;
+use fs_storage::base_storage::BaseStorage;
+use fs_storage::file_storage::FileStorage;
+use fs_storage::{now_millis, ARK_FOLDER, RULES_STORAGE_FILE};
+use fs_tags::TagStorage;
+
+mod rule;
+
+pub use rule::{Condition, Rule};
+
+/// Rules tagging new resources of a root, kept in `.ark/user/rules`
+/// under their names, e.g. "everything under `Camera/` gets `photo`".
+///
+/// Rules are applied to the resources the index reports as added, see
+/// [`RuleStorage::apply_to_events`], and never to existing resources.
+///
+/// Changes are written to disk immediately. Changes made by other
+/// processes or devices are read with [`RuleStorage::sync`].
+pub struct RuleStorage {
+    root: PathBuf,
+    storage: FileStorage<String, Rule>,
+}
+
+impl RuleStorage {
+    /// Open the rules of `root`, reading them if they exist
+    pub fn new<P: AsRef<Path>>(root: P) -> Result<Self> {
+        let root = root.as_ref().to_path_buf();
+        let path = root.join(ARK_FOLDER).join(RULES_STORAGE_FILE);
+        let storage = FileStorage::new("rules".to_owned(), &path)?;
+        Ok(RuleStorage { root, storage })
+    }
+
+    /// Rules with their names, in name order
+    pub fn rules(&self) -> impl Iterator<Item = (&String, &Rule)> {
+        self.storage.as_ref().iter()
+    }
+
+    pub fn rule(&self, name: &str) -> Option<&Rule> {
+        self.storage.get(&name.to_owned())
+    }
+
+    /// Add a rule, or replace the rule with the same name
+    pub fn set_rule(&mut self, name: &str, mut rule: Rule) -> Result<()> {
+        rule.updated_at = now_millis();
+        self.storage.set(name.to_owned(), rule);
+        self.storage.write_fs()
+    }
+
+    /// Returns `false` if there was no rule with this name
+    pub fn remove_rule(&mut self, name: &str) -> Result<bool> {
+        let name = name.to_owned();
+        if !self.storage.contains_key(&name) {
+            return Ok(false);
+        }
+        self.storage.remove(&name)?;
+        self.storage.write_fs()?;
+        Ok(true)
+    }
+
+    /// Names of the rules matching the resource at `path`, absolute
+    /// or relative to the root
+    pub fn matching(&self, path: &Path, metadata: Option<&Value>) -> Vec<&str> {
+        let path = path.strip_prefix(&self.root).unwrap_or(path);
+        self.rules()
+            .filter(|(_, rule)| rule.condition.matches(path, metadata))
+            .map(|(name, _)| name.as_str())
+            .collect()
+    }
+
+    /// Apply the rules to the resources reported as added by the index,
+    /// other events being ignored. See [`RuleStorage::apply`].
+    pub fn apply_to_events<Id: ResourceId>(
+        &self,
+        tags: &mut TagStorage<Id>,
+        events: &[IndexEvent<Id>],
+    ) -> BulkResult<Vec<String>, Id> {
+        let added = events.iter().filter_map(|event| match event {
+            IndexEvent::Added { id, path } => {
+                Some((id.clone(), path.as_path()))
+            }
+            _ => None,
+        });
+        self.apply(tags, added)
+    }
+
+    /// Add the tags and properties of the matching rules to every resource.
+    /// Returns the names of the rules applied to each resource matching
+    /// at least one rule.
+    ///
+    /// Metadata conditions see the metadata extracted so far, so metadata
+    /// should be extracted before the rules are applied.
+    pub fn apply<'a, Id, I>(
+        &self,
+        tags: &mut TagStorage<Id>,
+        resources: I,
+    ) -> BulkResult<Vec<String>, Id>
+    where
+        Id: ResourceId,
+        I: IntoIterator<Item = (Id, &'a Path)>,
+    {
+        let uses_metadata = self
+            .rules()
+            .any(|(_, rule)| rule.condition.uses_metadata());
+        let mut report = BulkResult::new();
+        for (id, path) in resources {
+            let metadata = if uses_metadata {
+                fs_metadata::load_metadata(&self.root, id.clone()).ok()
+            } else {
+                None
+            };
+            let matching = self.matching(path, metadata.as_ref());
+            if matching.is_empty() {
+                continue;
+            }
+            let applied = self.apply_rules(tags, id.clone(), &matching);
+            report.record(id, applied);
+        }
+        report
+    }
+
+    fn apply_rules<Id: ResourceId>(
+        &self,
+        tags: &mut TagStorage<Id>,
+        id: Id,
+        names: &[&str],
+    ) -> Result<Vec<String>> {
+        let mut properties = Map::new();
+        for rule in names.iter().filter_map(|name| self.rule(name)) {
+            for tag in &rule.tags {
+                tags.add_tag(id.clone(), tag)?;
+            }
+            // rules later in name order take precedence
+            properties.extend(rule.properties.clone());
+        }
+        if !properties.is_empty() {
+            fs_properties::store_properties(
+                &self.root,
+                id,
+                &Value::Object(properties),
+            )?;
+        }
+        Ok(names
+            .iter()
+            .map(|name| name.to_string())
+            .collect())
+    }
+
+    /// See [`BaseStorage::sync`]
+    pub fn sync(&mut self) -> Result<()> {
+        self.storage.sync()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use dev_hash::Crc32;
+    use fs_index::ResourceIndex;
+    use serde_json::json;
+    use tempdir::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn test_apply_to_new_resources() {
+        let dir = TempDir::new("arklib_test").unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        std::fs::create_dir(root.join("Camera")).unwrap();
+        std::fs::write(root.join("notes.txt"), "notes").unwrap();
+
+        let mut rules = RuleStorage::new(&root).unwrap();
+        let camera = Condition::Under("Camera".to_owned());
+        rules
+            .set_rule("camera", Rule::tagging(camera, ["photo"]))
+            .unwrap();
+        let mut rule = Rule::tagging(
+            Condition::Mime("image/*".to_owned()),
+            ["image", "inbox"],
+        );
+        rule.properties
+            .insert("source".to_owned(), json!("rules"));
+        rules.set_rule("images", rule).unwrap();
+        assert_eq!(rules.rules().count(), 2);
+
+        let mut index = ResourceIndex::<Crc32>::build(&root);
+        let events = index.subscribe();
+        std::fs::write(root.join("Camera").join("a.jpg"), "a").unwrap();
+        std::fs::write(root.join("b.png"), "b").unwrap();
+        index.update_all().unwrap();
+        let events: Vec<IndexEvent<Crc32>> = events.try_iter().collect();
+
+        let mut tags = TagStorage::new(&root).unwrap();
+        let report = rules.apply_to_events(&mut tags, &events);
+        assert_eq!(report.len(), 2);
+        assert!(!report.has_failures());
+
+        let a = Crc32::from_bytes(b"a").unwrap();
+        let b = Crc32::from_bytes(b"b").unwrap();
+        let expected: BTreeSet<String> = ["image", "inbox", "photo"]
+            .map(String::from)
+            .into();
+        assert_eq!(tags.tags_of(&a), expected);
+        assert_eq!(tags.tags_of(&b).len(), 2);
+        // existing resources are left alone
+        let notes = Crc32::from_bytes(b"notes").unwrap();
+        assert!(tags.tags_of(&notes).is_empty());
+
+        let properties = fs_properties::load_raw_properties(&root, b).unwrap();
+        let properties: Value = serde_json::from_slice(&properties).unwrap();
+        assert_eq!(properties["source"], "rules");
+
+        assert!(rules.remove_rule("camera").unwrap());
+        let rules = RuleStorage::new(&root).unwrap();
+        assert_eq!(rules.matching(Path::new("Camera/c.jpg"), None), ["images"]);
+    }
+}
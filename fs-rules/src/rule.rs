@@ -0,0 +1,175 @@
+use std::cmp::Ordering;
+use std::collections::BTreeSet;
+use std::path::Path;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use fs_storage::monoid::Monoid;
+
+/// Condition on a new resource, e.g. `{"under": "Camera"}` in JSON
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Condition {
+    /// Resources in the folder, relative to the root
+    Under(String),
+    /// Resources with the extension, compared case-insensitively
+    Extension(String),
+    /// Resources whose MIME type, guessed from the extension, matches.
+    /// A subtype of `*` matches the whole type, e.g. `image/*`.
+    Mime(String),
+    /// Resources whose metadata has a field, designated by a JSON
+    /// pointer like `/exif/Model`, with the value if one is given
+    Metadata {
+        pointer: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        equals: Option<Value>,
+    },
+    /// Resources matching all the conditions
+    All(Vec<Condition>),
+    /// Resources matching any of the conditions, none if there are none
+    Any(Vec<Condition>),
+    Not(Box<Condition>),
+}
+
+impl Condition {
+    /// Whether the resource at `path`, relative to the root, matches.
+    /// Metadata conditions don't match resources without metadata.
+    pub fn matches(&self, path: &Path, metadata: Option<&Value>) -> bool {
+        match self {
+            Condition::Under(folder) => path.starts_with(folder),
+            Condition::Extension(extension) => path
+                .extension()
+                .and_then(|found| found.to_str())
+                .is_some_and(|found| found.eq_ignore_ascii_case(extension)),
+            Condition::Mime(mime) => {
+                let mime = mime.to_lowercase();
+                mime_guess::from_path(path).iter().any(|guessed| {
+                    match mime.strip_suffix("/*") {
+                        Some(type_) => guessed.type_() == type_,
+                        None => guessed.essence_str() == mime,
+                    }
+                })
+            }
+            Condition::Metadata { pointer, equals } => metadata
+                .and_then(|metadata| metadata.pointer(pointer))
+                .is_some_and(|found| {
+                    equals
+                        .as_ref()
+                        .map_or(true, |value| found == value)
+                }),
+            Condition::All(conditions) => conditions
+                .iter()
+                .all(|condition| condition.matches(path, metadata)),
+            Condition::Any(conditions) => conditions
+                .iter()
+                .any(|condition| condition.matches(path, metadata)),
+            Condition::Not(condition) => !condition.matches(path, metadata),
+        }
+    }
+
+    /// Whether matching needs the metadata of resources
+    pub(crate) fn uses_metadata(&self) -> bool {
+        match self {
+            Condition::Metadata { .. } => true,
+            Condition::All(conditions) | Condition::Any(conditions) => {
+                conditions.iter().any(Condition::uses_metadata)
+            }
+            Condition::Not(condition) => condition.uses_metadata(),
+            _ => false,
+        }
+    }
+}
+
+/// Tags and properties applied to the new resources matching
+/// a condition
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Rule {
+    pub condition: Condition,
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub tags: BTreeSet<String>,
+    /// Merged into the properties of the resources
+    #[serde(default, skip_serializing_if = "Map::is_empty")]
+    pub properties: Map<String, Value>,
+    /// When the rule was last changed, in milliseconds since the epoch
+    #[serde(default)]
+    pub updated_at: u64,
+}
+
+impl Rule {
+    /// Rule tagging the resources matching `condition`
+    pub fn tagging<I, S>(condition: Condition, tags: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Rule {
+            condition,
+            tags: tags.into_iter().map(Into::into).collect(),
+            properties: Map::new(),
+            updated_at: 0,
+        }
+    }
+}
+
+impl FromStr for Rule {
+    type Err = serde_json::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        serde_json::from_str(s)
+    }
+}
+
+// The latest change wins
+impl Monoid<Rule> for Rule {
+    fn neutral() -> Rule {
+        Rule::tagging(Condition::Any(vec![]), Vec::<String>::new())
+    }
+
+    fn combine(a: &Rule, b: &Rule) -> Rule {
+        match a.updated_at.cmp(&b.updated_at) {
+            Ordering::Less => b.clone(),
+            _ => a.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_conditions() {
+        let photo = Path::new("Camera/2024/IMG_1.JPG");
+        let metadata = json!({"exif": {"Model": "Pixel 7"}});
+
+        assert!(Condition::Under("Camera".to_owned()).matches(photo, None));
+        assert!(!Condition::Under("Cam".to_owned()).matches(photo, None));
+        assert!(Condition::Extension("jpg".to_owned()).matches(photo, None));
+        assert!(Condition::Mime("image/*".to_owned()).matches(photo, None));
+        assert!(!Condition::Mime("image/png".to_owned()).matches(photo, None));
+
+        let model: Condition = serde_json::from_value(json!({
+            "metadata": {"pointer": "/exif/Model", "equals": "Pixel 7"}
+        }))
+        .unwrap();
+        assert!(model.matches(photo, Some(&metadata)));
+        assert!(!model.matches(photo, None));
+
+        let rule: Rule = r#"{
+            "condition": {
+                "all": [{"under": "Camera"}, {"not": {"mime": "video/*"}}]
+            },
+            "tags": ["photo"]
+        }"#
+        .parse()
+        .unwrap();
+        assert!(rule.condition.matches(photo, None));
+        assert!(!rule.condition.uses_metadata());
+        assert!(model.uses_metadata());
+        assert!(!Rule::neutral().condition.matches(photo, None));
+    }
+}
@@ -0,0 +1,54 @@
+use std::time::Duration;
+
+use crate::Score;
+
+/// Exponential decay of scores, so that resources scored long ago cool
+/// off without being rescored: a score loses half of its weight every
+/// `half_life` after it was set.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Decay {
+    pub half_life: Duration,
+}
+
+impl Decay {
+    pub fn new(half_life: Duration) -> Self {
+        Decay { half_life }
+    }
+
+    /// Weight of `score` at `now`, in milliseconds since the epoch.
+    ///
+    /// Scores set in the future don't decay, nor do legacy scores, which
+    /// have no timestamp. A zero half-life disables decay.
+    pub fn apply(&self, score: &Score, now: u64) -> f64 {
+        let half_life = self.half_life.as_millis() as f64;
+        if score.timestamp == 0 || half_life == 0.0 {
+            return score.value as f64;
+        }
+        let elapsed = now.saturating_sub(score.timestamp) as f64;
+        score.value as f64 * 0.5f64.powf(elapsed / half_life)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_half_life() {
+        let decay = Decay::new(Duration::from_secs(10));
+        let score = Score {
+            value: 8,
+            timestamp: 1_000,
+        };
+        assert_eq!(decay.apply(&score, 1_000), 8.0);
+        assert_eq!(decay.apply(&score, 11_000), 4.0);
+        assert_eq!(decay.apply(&score, 31_000), 1.0);
+        assert_eq!(decay.apply(&score, 0), 8.0);
+
+        let legacy = Score {
+            value: 8,
+            timestamp: 0,
+        };
+        assert_eq!(decay.apply(&legacy, 31_000), 8.0);
+    }
+}
@@ -0,0 +1,372 @@
+use std::ops::RangeInclusive;
+use std::path::Path;
+
+use data_error::{ArklibError, Result};
+use data_resource::ResourceId;
+use fs_index::ResourceIndex;
+use fs_storage::base_storage::BaseStorage;
+use fs_storage::file_storage::FileStorage;
+use fs_storage::{ARK_FOLDER, SCORE_STORAGE_FILE};
+
+mod decay;
+mod score;
+
+pub use decay::Decay;
+pub use score::Score;
+
+/// Order of resources sorted by score, ties being in id order
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortOrder {
+    /// Lowest scores first
+    Ascending,
+    /// Highest scores first
+    Descending,
+}
+
+/// Scores of the resources of a root, kept in `.ark/user/scores`.
+///
+/// Resources without score have score 0. Changes are written to disk
+/// immediately, changes made by other processes or devices are read
+/// with [`ScoreStorage::sync`].
+///
+/// With a [`Decay`], [effective scores](ScoreStorage::effective_score)
+/// weigh recent scores more than old ones. Stored scores are unaffected.
+pub struct ScoreStorage<Id: ResourceId> {
+    storage: FileStorage<Id, Score>,
+    decay: Option<Decay>,
+}
+
+impl<Id: ResourceId> ScoreStorage<Id> {
+    /// Open the scores storage of `root`, reading it if it exists
+    pub fn new<P: AsRef<Path>>(root: P) -> Result<Self> {
+        let path = root
+            .as_ref()
+            .join(ARK_FOLDER)
+            .join(SCORE_STORAGE_FILE);
+        let storage = FileStorage::new("scores".to_owned(), &path)?;
+        Ok(ScoreStorage {
+            storage,
+            decay: None,
+        })
+    }
+
+    /// Make effective scores decay with time
+    pub fn with_decay(mut self, decay: Decay) -> Self {
+        self.decay = Some(decay);
+        self
+    }
+
+    pub fn decay(&self) -> Option<Decay> {
+        self.decay
+    }
+
+    /// Score of a resource
+    pub fn score(&self, id: &Id) -> i64 {
+        self.storage
+            .get(id)
+            .map_or(0, |score| score.value)
+    }
+
+    /// Score of a resource at `now`, in milliseconds since the epoch,
+    /// decayed since it was set. Without decay, this is its score.
+    pub fn effective_score(&self, id: &Id, now: u64) -> f64 {
+        match (self.storage.get(id), self.decay) {
+            (Some(score), Some(decay)) => decay.apply(score, now),
+            (Some(score), None) => score.value as f64,
+            (None, _) => 0.0,
+        }
+    }
+
+    /// `n` resources with the highest effective scores at `now`,
+    /// highest first, e.g. to show the resources being "hot"
+    pub fn top_n_effective(&self, n: usize, now: u64) -> Vec<(Id, f64)> {
+        let mut entries: Vec<(Id, f64)> = self
+            .storage
+            .keys()
+            .map(|id| (id.clone(), self.effective_score(id, now)))
+            .collect();
+        entries.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+        entries.truncate(n);
+        entries
+    }
+
+    /// Set the score of a resource, score 0 removes it from the storage
+    pub fn set_score(&mut self, id: Id, score: i64) -> Result<()> {
+        if score == 0 {
+            if !self.storage.contains_key(&id) {
+                return Ok(());
+            }
+            self.storage.remove(&id)?;
+        } else {
+            self.storage.set(id, Score::new(score));
+        }
+        self.storage.write_fs()
+    }
+
+    /// Replace every score with `f(score)` at once. Resources whose new
+    /// score is 0 are removed from the storage. Returns the number of
+    /// resources whose score changed.
+    ///
+    /// Scores keep the time they were set at, so that transforming them
+    /// doesn't affect how much they have [decayed](Decay).
+    ///
+    /// Nothing is changed if the storage can't be written.
+    pub fn apply<F: Fn(i64) -> i64>(&mut self, f: F) -> Result<usize> {
+        let changed: Vec<(Id, Score)> = self
+            .storage
+            .as_ref()
+            .iter()
+            .filter_map(|(id, score)| {
+                let value = f(score.value);
+                (value != score.value)
+                    .then(|| (id.clone(), Score { value, ..*score }))
+            })
+            .collect();
+        if changed.is_empty() {
+            return Ok(0);
+        }
+        self.storage.transaction(|transaction| {
+            for (id, score) in &changed {
+                if score.value == 0 {
+                    transaction.remove(id.clone())?;
+                } else {
+                    transaction.set(id.clone(), *score);
+                }
+            }
+            Ok(changed.len())
+        })
+    }
+
+    /// Map scores linearly onto `range`, the lowest score becoming its
+    /// start and the highest its end, e.g. to bring scores from years
+    /// of use back to a common scale. Equal scores are all mapped to
+    /// the middle of the range. See [`ScoreStorage::apply`].
+    pub fn rescale_scores(
+        &mut self,
+        range: RangeInclusive<i64>,
+    ) -> Result<usize> {
+        let (start, end) = (*range.start() as i128, *range.end() as i128);
+        if start > end {
+            return Err(ArklibError::Storage(
+                "scores".to_owned(),
+                format!("can't rescale to empty range {:?}", range),
+            ));
+        }
+        let values = || self.storage.as_ref().values().map(|s| s.value);
+        let (Some(min), Some(max)) = (values().min(), values().max()) else {
+            return Ok(0);
+        };
+        let (min, max) = (min as i128, max as i128);
+        self.apply(|score| {
+            if min == max {
+                return ((start + end) / 2) as i64;
+            }
+            // rounded to the nearest integer, all terms being positive
+            let offset = (score as i128 - min) * (end - start);
+            let span = max - min;
+            (start + (2 * offset + span) / (2 * span)) as i64
+        })
+    }
+
+    /// Resources of `index` without score, sorted, e.g. to triage
+    /// new resources
+    pub fn unscored_resources(&self, index: &ResourceIndex<Id>) -> Vec<Id> {
+        let mut unscored: Vec<Id> = index
+            .id2path
+            .keys()
+            .filter(|id| !self.storage.contains_key(id))
+            .cloned()
+            .collect();
+        unscored.sort();
+        unscored
+    }
+
+    /// Scored resources with their scores, in id order
+    pub fn entries(&self) -> impl Iterator<Item = (&Id, i64)> {
+        self.storage
+            .as_ref()
+            .iter()
+            .map(|(id, score)| (id, score.value))
+    }
+
+    /// `n` resources with the highest scores, highest first
+    pub fn top_n(&self, n: usize) -> Vec<(Id, i64)> {
+        let mut entries = self.sorted(SortOrder::Descending);
+        entries.truncate(n);
+        entries
+    }
+
+    /// Scored resources sorted by score
+    pub fn sorted_ids(&self, order: SortOrder) -> Vec<Id> {
+        self.sorted(order)
+            .into_iter()
+            .map(|(id, _)| id)
+            .collect()
+    }
+
+    fn sorted(&self, order: SortOrder) -> Vec<(Id, i64)> {
+        let mut entries: Vec<(Id, i64)> = self
+            .entries()
+            .map(|(id, score)| (id.clone(), score))
+            .collect();
+        // entries are in id order already, and sorting is stable
+        match order {
+            SortOrder::Ascending => entries.sort_by_key(|(_, score)| *score),
+            SortOrder::Descending => entries.sort_by(|(_, a), (_, b)| b.cmp(a)),
+        }
+        entries
+    }
+
+    /// See [`BaseStorage::sync`]
+    pub fn sync(&mut self) -> Result<()> {
+        self.storage.sync()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use dev_hash::Crc32;
+    use tempdir::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn test_set_and_sort_scores() {
+        let dir = TempDir::new("arklib_test").unwrap();
+        let root = dir.path();
+
+        let mut storage = ScoreStorage::new(root).unwrap();
+        for (id, score) in [(1, 5), (2, 15), (3, -1), (4, 5)] {
+            storage.set_score(Crc32(id), score).unwrap();
+        }
+        storage.set_score(Crc32(5), 0).unwrap();
+        assert_eq!(storage.score(&Crc32(2)), 15);
+        assert_eq!(storage.score(&Crc32(5)), 0);
+
+        assert_eq!(
+            storage.top_n(3),
+            [(Crc32(2), 15), (Crc32(1), 5), (Crc32(4), 5)]
+        );
+        assert_eq!(
+            storage.sorted_ids(SortOrder::Ascending),
+            [Crc32(3), Crc32(1), Crc32(4), Crc32(2)]
+        );
+
+        storage.set_score(Crc32(2), 0).unwrap();
+        let storage: ScoreStorage<Crc32> = ScoreStorage::new(root).unwrap();
+        assert_eq!(storage.entries().count(), 3);
+        assert_eq!(storage.top_n(1), [(Crc32(1), 5)]);
+    }
+
+    #[test]
+    fn test_apply_and_rescale() {
+        let dir = TempDir::new("arklib_test").unwrap();
+        let root = dir.path();
+
+        let mut storage = ScoreStorage::new(root).unwrap();
+        for (id, score) in [(1, 10), (2, 40), (3, 100)] {
+            storage.set_score(Crc32(id), score).unwrap();
+        }
+        assert_eq!(storage.rescale_scores(1..=10).unwrap(), 3);
+        assert_eq!(
+            storage.top_n(3),
+            [(Crc32(3), 10), (Crc32(2), 4), (Crc32(1), 1)]
+        );
+        assert!(storage.rescale_scores(5..=1).is_err());
+
+        assert_eq!(storage.apply(|score| score - 1).unwrap(), 3);
+        let storage: ScoreStorage<Crc32> = ScoreStorage::new(root).unwrap();
+        // score 0 is removed
+        assert_eq!(storage.top_n(3), [(Crc32(3), 9), (Crc32(2), 3)]);
+    }
+
+    #[test]
+    fn test_rescale_keeps_decay() {
+        let dir = TempDir::new("arklib_test").unwrap();
+        let root = dir.path();
+
+        let day = Duration::from_secs(24 * 60 * 60);
+        let mut storage = ScoreStorage::new(root)
+            .unwrap()
+            .with_decay(Decay::new(day));
+        storage.set_score(Crc32(1), 40).unwrap();
+        let now = storage.storage.get(&Crc32(1)).unwrap().timestamp;
+        // the second score was set two weeks ago
+        let two_weeks_ago = now - 14 * day.as_millis() as u64;
+        storage.storage.set(
+            Crc32(2),
+            Score {
+                value: 100,
+                timestamp: two_weeks_ago,
+            },
+        );
+        storage.storage.write_fs().unwrap();
+
+        assert_eq!(storage.rescale_scores(1..=10).unwrap(), 2);
+        assert_eq!(storage.score(&Crc32(2)), 10);
+        let old = storage.storage.get(&Crc32(2)).unwrap();
+        assert_eq!(old.timestamp, two_weeks_ago);
+        // the rescaled old score is still cold
+        assert!(storage.effective_score(&Crc32(2), now) < 0.01);
+        let top: Vec<Crc32> = storage
+            .top_n_effective(2, now)
+            .into_iter()
+            .map(|(id, _)| id)
+            .collect();
+        assert_eq!(top, [Crc32(1), Crc32(2)]);
+    }
+
+    #[test]
+    fn test_effective_scores() {
+        let dir = TempDir::new("arklib_test").unwrap();
+        let root = dir.path();
+
+        let mut storage = ScoreStorage::new(root).unwrap();
+        storage.set_score(Crc32(1), 10).unwrap();
+        storage.set_score(Crc32(2), 4).unwrap();
+        let now = storage.storage.get(&Crc32(2)).unwrap().timestamp;
+        assert_eq!(storage.effective_score(&Crc32(1), now + 60_000), 10.0);
+
+        let day = Duration::from_secs(24 * 60 * 60);
+        let mut storage = storage.with_decay(Decay::new(day));
+        // the first score was set two weeks ago
+        let two_weeks_ago = now - 14 * day.as_millis() as u64;
+        storage.storage.set(
+            Crc32(1),
+            Score {
+                value: 10,
+                timestamp: two_weeks_ago,
+            },
+        );
+        assert!(storage.effective_score(&Crc32(1), now) < 0.01);
+        assert_eq!(storage.effective_score(&Crc32(3), now), 0.0);
+        let top: Vec<Crc32> = storage
+            .top_n_effective(2, now)
+            .into_iter()
+            .map(|(id, _)| id)
+            .collect();
+        assert_eq!(top, [Crc32(2), Crc32(1)]);
+        // stored scores are unaffected
+        assert_eq!(storage.top_n(1), [(Crc32(1), 10)]);
+    }
+
+    #[test]
+    fn test_unscored_resources() {
+        let dir = TempDir::new("arklib_test").unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        std::fs::write(root.join("a.txt"), "a").unwrap();
+        std::fs::write(root.join("b.txt"), "b").unwrap();
+        let index = ResourceIndex::<Crc32>::build(&root);
+        let a = Crc32::from_bytes(b"a").unwrap();
+        let b = Crc32::from_bytes(b"b").unwrap();
+
+        let mut storage = ScoreStorage::new(&root).unwrap();
+        assert_eq!(storage.unscored_resources(&index).len(), 2);
+        storage.set_score(a, 3).unwrap();
+        // not indexed
+        storage.set_score(Crc32(1), 3).unwrap();
+        assert_eq!(storage.unscored_resources(&index), [b]);
+    }
+}
@@ -0,0 +1,32 @@
+use fs_storage::monoid::LastWriteWins;
+
+/// Score of a resource, as kept in the scores storage.
+///
+/// Concurrent edits are resolved by keeping the latest one, and the
+/// highest score if they have the same timestamp. Legacy storages keep
+/// plain integers, which are read with timestamp 0, so that they are
+/// merged by keeping the highest score as before.
+pub type Score = LastWriteWins<i64>;
+
+#[cfg(test)]
+mod tests {
+    use fs_storage::monoid::Monoid;
+
+    use super::*;
+
+    #[test]
+    fn test_legacy_and_latest_scores() {
+        let legacy: Score = "5".parse().unwrap();
+        assert_eq!(legacy.timestamp, 0);
+        assert_eq!(Score::combine(&legacy, &"7".parse().unwrap()).value, 7);
+
+        let latest = Score {
+            value: -1,
+            timestamp: 10,
+        };
+        let json = serde_json::to_string(&latest).unwrap();
+        assert_eq!(json.parse::<Score>().unwrap(), latest);
+        assert_eq!(Score::combine(&legacy, &latest), latest);
+        assert_eq!(Score::combine(&latest, &legacy), latest);
+    }
+}
@@ -0,0 +1,412 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use data_error::{Result, ResultExt};
+use data_resource::ResourceId;
+use fs_atomic_versions::app_id;
+use fs_atomic_versions::atomic::{modify_json, AtomicFile};
+use fs_storage::{now_millis, ARK_FOLDER};
+
+pub use fs_storage::STATS_FOLDER;
+
+/// Folder of `.ark/stats` with the stats recorded by every device,
+/// in `<device>/<id>`
+const DEVICES_FOLDER: &str = "devices";
+
+/// Device of the stats recorded before stats were kept per device,
+/// in `.ark/stats/<id>`
+pub const LEGACY_DEVICE: &str = "legacy";
+
+/// Number of the latest opens kept with their time by every device
+pub const RECENT_OPENS: usize = 100;
+
+/// Usage statistics of a resource on a device, kept as JSON in the
+/// atomic file `.ark/stats/devices/<device>/<id>`.
+///
+/// Every device only writes its own stats, so that syncing the root
+/// between devices doesn't lose any, and stats of all devices are
+/// [aggregated](ResourceStats::aggregate) when read.
+///
+/// Only the time of the latest [`RECENT_OPENS`] opens is kept, so that
+/// the stats of a resource opened often stay small. Stats recorded as
+/// a list of all the opens are read as such.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(from = "Repr")]
+pub struct ResourceStats {
+    count: u64,
+    /// In milliseconds since the epoch
+    #[serde(skip_serializing_if = "Option::is_none")]
+    last_opened: Option<u64>,
+    /// In milliseconds since the epoch, oldest first
+    recent: Vec<u64>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Repr {
+    Counted {
+        count: u64,
+        #[serde(default)]
+        last_opened: Option<u64>,
+        #[serde(default)]
+        recent: Vec<u64>,
+    },
+    Legacy {
+        #[serde(default)]
+        opens: Vec<u64>,
+    },
+}
+
+impl From<Repr> for ResourceStats {
+    fn from(repr: Repr) -> Self {
+        match repr {
+            Repr::Counted {
+                count,
+                last_opened,
+                recent,
+            } => ResourceStats {
+                count,
+                last_opened,
+                recent,
+            },
+            Repr::Legacy { mut opens } => {
+                opens.sort_unstable();
+                let mut stats = ResourceStats {
+                    count: opens.len() as u64,
+                    last_opened: opens.last().copied(),
+                    recent: opens,
+                };
+                stats.truncate_recent();
+                stats
+            }
+        }
+    }
+}
+
+impl ResourceStats {
+    /// Number of times the resource was opened
+    pub fn open_count(&self) -> u64 {
+        self.count
+    }
+
+    /// Last time the resource was opened, in milliseconds since the epoch
+    pub fn last_opened(&self) -> Option<u64> {
+        self.last_opened
+    }
+
+    /// Times of the latest opens, in milliseconds since the epoch,
+    /// oldest first
+    pub fn recent_opens(&self) -> &[u64] {
+        &self.recent
+    }
+
+    fn record(&mut self, timestamp: u64) {
+        self.count += 1;
+        self.last_opened = self.last_opened.max(Some(timestamp));
+        let at = self
+            .recent
+            .partition_point(|opened| *opened <= timestamp);
+        self.recent.insert(at, timestamp);
+        self.truncate_recent();
+    }
+
+    fn truncate_recent(&mut self) {
+        let excess = self.recent.len().saturating_sub(RECENT_OPENS);
+        self.recent.drain(..excess);
+    }
+
+    /// Stats of several devices put together: open counts add up, and
+    /// the resource was last opened on the device which opened it last.
+    /// The recent opens of every device are kept, sorted.
+    pub fn aggregate<'a, I>(stats: I) -> ResourceStats
+    where
+        I: IntoIterator<Item = &'a ResourceStats>,
+    {
+        let mut aggregated = ResourceStats::default();
+        for stats in stats {
+            aggregated.count += stats.count;
+            aggregated.last_opened =
+                aggregated.last_opened.max(stats.last_opened);
+            aggregated.recent.extend_from_slice(&stats.recent);
+        }
+        aggregated.recent.sort_unstable();
+        aggregated
+    }
+}
+
+/// Record that the resource was opened now, on this device
+pub fn record_open<P: AsRef<Path>, Id: ResourceId>(
+    root: P,
+    id: Id,
+) -> Result<ResourceStats> {
+    record_open_at(root, id, now_millis())
+}
+
+/// Record that the resource was opened at `timestamp`, in milliseconds
+/// since the epoch, on this device. Returns the stats of this device.
+pub fn record_open_at<P: AsRef<Path>, Id: ResourceId>(
+    root: P,
+    id: Id,
+    timestamp: u64,
+) -> Result<ResourceStats> {
+    let device = app_id::read()?;
+    let path = devices_folder(root)
+        .join(device)
+        .join(id.to_string());
+    let file = AtomicFile::new(path)?;
+    let mut recorded = ResourceStats::default();
+    modify_json(&file, |stats: &mut Option<ResourceStats>| {
+        let stats = stats.get_or_insert_with(ResourceStats::default);
+        stats.record(timestamp);
+        recorded = stats.clone();
+    })
+    .with_path(&file.directory)?;
+    Ok(recorded)
+}
+
+/// Stats of a resource on all devices, `None` if it was never opened
+pub fn load_stats<P: AsRef<Path>, Id: ResourceId>(
+    root: P,
+    id: &Id,
+) -> Result<Option<ResourceStats>> {
+    let devices = load_device_stats(root, id)?;
+    if devices.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(ResourceStats::aggregate(devices.values())))
+}
+
+/// Stats of a resource by device, stats recorded before stats were kept
+/// per device being under [`LEGACY_DEVICE`]
+pub fn load_device_stats<P: AsRef<Path>, Id: ResourceId>(
+    root: P,
+    id: &Id,
+) -> Result<BTreeMap<String, ResourceStats>> {
+    let folder = root.as_ref().join(ARK_FOLDER).join(STATS_FOLDER);
+    let mut stats = BTreeMap::new();
+    if let Some(legacy) = load_from(&folder.join(id.to_string()))? {
+        stats.insert(LEGACY_DEVICE.to_owned(), legacy);
+    }
+    let devices = folder.join(DEVICES_FOLDER);
+    for device in devices_of(&devices)? {
+        let path = devices.join(&device).join(id.to_string());
+        if let Some(device_stats) = load_from(&path)? {
+            stats.insert(device, device_stats);
+        }
+    }
+    Ok(stats)
+}
+
+/// Stats of all the resources of `root` on all devices, sorted by id,
+/// empty if none were recorded
+pub fn load_all_stats<P: AsRef<Path>, Id: ResourceId>(
+    root: P,
+) -> Result<Vec<(Id, ResourceStats)>> {
+    let folder = root.as_ref().join(ARK_FOLDER).join(STATS_FOLDER);
+    let mut stats: BTreeMap<Id, Vec<ResourceStats>> = BTreeMap::new();
+    load_folder(&folder, &mut stats)?;
+    let devices = folder.join(DEVICES_FOLDER);
+    for device in devices_of(&devices)? {
+        load_folder(&devices.join(device), &mut stats)?;
+    }
+    Ok(stats
+        .into_iter()
+        .map(|(id, stats)| (id, ResourceStats::aggregate(&stats)))
+        .collect())
+}
+
+/// `n` resources opened most recently, latest first
+pub fn recently_used<P: AsRef<Path>, Id: ResourceId>(
+    root: P,
+    n: usize,
+) -> Result<Vec<(Id, ResourceStats)>> {
+    let mut stats = load_all_stats(root)?;
+    stats.retain(|(_, stats)| stats.last_opened().is_some());
+    stats.sort_by(|(_, a), (_, b)| b.last_opened().cmp(&a.last_opened()));
+    stats.truncate(n);
+    Ok(stats)
+}
+
+/// `n` resources opened most often, the most recently opened first
+/// among those opened as often
+pub fn most_used<P: AsRef<Path>, Id: ResourceId>(
+    root: P,
+    n: usize,
+) -> Result<Vec<(Id, ResourceStats)>> {
+    let mut stats = load_all_stats(root)?;
+    stats.retain(|(_, stats)| stats.open_count() > 0);
+    stats.sort_by(|(_, a), (_, b)| {
+        b.open_count()
+            .cmp(&a.open_count())
+            .then(b.last_opened().cmp(&a.last_opened()))
+    });
+    stats.truncate(n);
+    Ok(stats)
+}
+
+fn devices_folder<P: AsRef<Path>>(root: P) -> PathBuf {
+    root.as_ref()
+        .join(ARK_FOLDER)
+        .join(STATS_FOLDER)
+        .join(DEVICES_FOLDER)
+}
+
+/// Devices which recorded stats in `folder`
+fn devices_of(folder: &Path) -> Result<Vec<String>> {
+    if !folder.is_dir() {
+        return Ok(vec![]);
+    }
+    let mut devices = Vec::new();
+    for entry in std::fs::read_dir(folder).with_path(folder)? {
+        let entry = entry.with_path(folder)?;
+        match entry.file_name().into_string() {
+            Ok(device) => devices.push(device),
+            Err(_) => {
+                log::warn!("Skipping {} in stats", entry.path().display())
+            }
+        }
+    }
+    Ok(devices)
+}
+
+/// Add the stats of the resources in `folder` to `stats`
+fn load_folder<Id: ResourceId>(
+    folder: &Path,
+    stats: &mut BTreeMap<Id, Vec<ResourceStats>>,
+) -> Result<()> {
+    if !folder.is_dir() {
+        return Ok(());
+    }
+    for entry in std::fs::read_dir(folder).with_path(folder)? {
+        let entry = entry.with_path(folder)?;
+        let name = entry.file_name();
+        if name == DEVICES_FOLDER {
+            continue;
+        }
+        let Some(id) = name
+            .to_str()
+            .and_then(|name| Id::from_str(name).ok())
+        else {
+            log::warn!("Skipping {} in stats", entry.path().display());
+            continue;
+        };
+        if let Some(resource) = load_from(&entry.path())? {
+            stats.entry(id).or_default().push(resource);
+        }
+    }
+    Ok(())
+}
+
+fn load_from(path: &Path) -> Result<Option<ResourceStats>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let file = AtomicFile::new(path)?;
+    let Some(content) = file.load().with_path(path)?.open()? else {
+        return Ok(None);
+    };
+    Ok(Some(serde_json::from_reader(content).with_path(path)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use dev_hash::Crc32;
+    use fs_atomic_versions::initialize;
+    use tempdir::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn test_record_and_query() {
+        initialize();
+
+        let dir = TempDir::new("arklib_test").unwrap();
+        let root = dir.path();
+        let (a, b, c) = (Crc32(1), Crc32(2), Crc32(3));
+
+        assert!(load_stats(root, &a).unwrap().is_none());
+        assert!(recently_used::<_, Crc32>(root, 10)
+            .unwrap()
+            .is_empty());
+
+        record_open_at(root, a.clone(), 10).unwrap();
+        record_open_at(root, a.clone(), 20).unwrap();
+        record_open_at(root, b.clone(), 30).unwrap();
+        let stats = record_open(root, c.clone()).unwrap();
+        assert_eq!(stats.open_count(), 1);
+
+        let stats = load_stats(root, &a).unwrap().unwrap();
+        assert_eq!(stats.recent_opens(), [10, 20]);
+        assert_eq!(stats.last_opened(), Some(20));
+
+        let recent: Vec<Crc32> = recently_used(root, 2)
+            .unwrap()
+            .into_iter()
+            .map(|(id, _)| id)
+            .collect();
+        assert_eq!(recent, [c.clone(), b.clone()]);
+        let most: Vec<Crc32> = most_used(root, 3)
+            .unwrap()
+            .into_iter()
+            .map(|(id, _)| id)
+            .collect();
+        assert_eq!(most, [a, c, b]);
+    }
+
+    #[test]
+    fn test_aggregate_devices() {
+        initialize();
+
+        let dir = TempDir::new("arklib_test").unwrap();
+        let root = dir.path();
+        let id = Crc32(1);
+        record_open_at(root, id.clone(), 30).unwrap();
+
+        // written by a phone syncing the same root
+        let stats = root.join(ARK_FOLDER).join(STATS_FOLDER);
+        let phone = stats.join(DEVICES_FOLDER).join("phone");
+        let legacy = stats.join(id.to_string());
+        for (path, opens) in
+            [(phone.join(id.to_string()), [10, 50]), (legacy, [5, 6])]
+        {
+            let file = AtomicFile::new(path).unwrap();
+            modify_json(&file, |stats: &mut Option<serde_json::Value>| {
+                *stats = Some(serde_json::json!({ "opens": opens }))
+            })
+            .unwrap();
+        }
+
+        let devices = load_device_stats(root, &id).unwrap();
+        assert_eq!(devices.len(), 3);
+        assert_eq!(devices["phone"].recent_opens(), [10, 50]);
+        assert_eq!(devices[LEGACY_DEVICE].open_count(), 2);
+
+        let stats = load_stats(root, &id).unwrap().unwrap();
+        assert_eq!(stats.open_count(), 5);
+        assert_eq!(stats.last_opened(), Some(50));
+        assert_eq!(stats.recent_opens(), [5, 6, 10, 30, 50]);
+        assert_eq!(load_all_stats(root).unwrap(), [(id, stats)]);
+    }
+
+    #[test]
+    fn test_recent_opens_are_capped() {
+        initialize();
+
+        let dir = TempDir::new("arklib_test").unwrap();
+        let root = dir.path();
+        let id = Crc32(1);
+        let total = RECENT_OPENS as u64 + 10;
+        for timestamp in (1..=total).rev() {
+            record_open_at(root, id.clone(), timestamp).unwrap();
+        }
+
+        let stats = load_stats(root, &id).unwrap().unwrap();
+        assert_eq!(stats.open_count(), total);
+        assert_eq!(stats.last_opened(), Some(total));
+        assert_eq!(stats.recent_opens().len(), RECENT_OPENS);
+        assert_eq!(stats.recent_opens()[0], 11);
+    }
+}
@@ -0,0 +1,54 @@
+use criterion::{
+    black_box, criterion_group, criterion_main, BenchmarkId, Criterion,
+};
+use fs_storage::base_storage::BaseStorage;
+use fs_storage::file_storage::FileStorage;
+use tempdir::TempDir;
+
+const ENTRIES: usize = 100_000;
+
+fn value_index_benchmark(c: &mut Criterion) {
+    let temp_dir =
+        TempDir::new("tmp").expect("Failed to create temporary directory");
+    let storage_path = temp_dir.path().join("scores");
+
+    let mut storage: FileStorage<String, i32> =
+        FileStorage::new("scores".to_string(), &storage_path).unwrap();
+    for i in 0..ENTRIES {
+        storage.set(format!("resource{}", i), (i % 100) as i32);
+    }
+
+    let mut group = c.benchmark_group("find_by_value");
+
+    group.bench_with_input(
+        BenchmarkId::new("scan", ENTRIES),
+        &storage,
+        |b, storage| {
+            b.iter(|| {
+                storage
+                    .as_ref()
+                    .iter()
+                    .filter(|(_, score)| **score == black_box(42))
+                    .count()
+            });
+        },
+    );
+
+    storage.enable_value_index(|score| Some(score.to_string()));
+    group.bench_with_input(
+        BenchmarkId::new("value_index", ENTRIES),
+        &storage,
+        |b, storage| {
+            b.iter(|| storage.find_by_value(black_box("42")).len());
+        },
+    );
+
+    group.finish();
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default();
+    targets = value_index_benchmark
+}
+criterion_main!(benches);
@@ -0,0 +1,132 @@
+//! Storages usable from async code without blocking the runtime.
+//!
+//! The disk IO runs on the blocking thread pool of tokio,
+//! the in-memory mapping is still updated with [`BaseStorage`].
+
+use std::collections::BTreeMap;
+use std::future::Future;
+
+use tokio::task::{spawn_blocking, JoinError};
+
+use crate::base_storage::BaseStorage;
+use crate::file_storage::{file_modified, write_full, FileStorage};
+use crate::lock;
+use crate::monoid::Monoid;
+use data_error::{ArklibError, Result};
+
+/// [`BaseStorage`] with async variants of the methods accessing the disk
+pub trait AsyncBaseStorage<K, V>: BaseStorage<K, V> {
+    /// Same as [`BaseStorage::read_fs`]
+    fn read_fs_async(
+        &mut self,
+    ) -> impl Future<Output = Result<&BTreeMap<K, V>>> + Send;
+
+    /// Same as [`BaseStorage::write_fs`]
+    ///
+    /// A [`FileStorage`] with a journal is written entirely.
+    fn write_fs_async(&mut self) -> impl Future<Output = Result<()>> + Send;
+}
+
+impl<K, V> AsyncBaseStorage<K, V> for FileStorage<K, V>
+where
+    K: Ord
+        + Clone
+        + serde::Serialize
+        + serde::de::DeserializeOwned
+        + std::str::FromStr
+        + Send
+        + Sync
+        + 'static,
+    V: Clone
+        + serde::Serialize
+        + serde::de::DeserializeOwned
+        + std::str::FromStr
+        + Monoid<V>
+        + Send
+        + Sync
+        + 'static,
+{
+    async fn read_fs_async(&mut self) -> Result<&BTreeMap<K, V>> {
+        let label = self.label().to_owned();
+        let path = self.path().to_owned();
+        let key = self.key().cloned();
+        let lock_wait = self.lock_wait();
+        let (data, journaled, modified) = spawn_blocking(move || {
+            let _lock = lock_wait
+                .map(|timeout| lock::acquire(&label, &path, false, timeout))
+                .transpose()?;
+            let (data, journaled) =
+                Self::load_data(&label, &path, key.as_ref())?;
+            let modified = file_modified(&label, &path)?;
+            Ok::<_, ArklibError>((data, journaled, modified))
+        })
+        .await
+        .map_err(join_error(self.label()))??;
+        Ok(self.loaded(data, journaled, modified))
+    }
+
+    async fn write_fs_async(&mut self) -> Result<()> {
+        let label = self.label().to_owned();
+        let path = self.path().to_owned();
+        let durability = self.durability();
+        // serialized here so that the entries are not cloned
+        let (content, journaled) = self.full_content()?;
+        let lock_wait = self.lock_wait();
+        let written = spawn_blocking(move || {
+            let _lock = lock_wait
+                .map(|timeout| lock::acquire(&label, &path, true, timeout))
+                .transpose()?;
+            write_full(&label, &path, &content, durability, journaled, false)
+        })
+        .await;
+        let written = match written {
+            Ok(written) => written,
+            Err(err) => Err(join_error(self.label())(err)),
+        };
+        self.written_entirely(written, journaled)
+    }
+}
+
+fn join_error(label: &str) -> impl Fn(JoinError) -> ArklibError + '_ {
+    move |err| {
+        ArklibError::Storage(
+            label.to_owned(),
+            format!("Storage task failed: {}", err),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+
+    use super::AsyncBaseStorage;
+    use crate::{
+        base_storage::{BaseStorage, SyncStatus},
+        file_storage::FileStorage,
+    };
+
+    #[tokio::test]
+    async fn test_file_storage_async_write_read() {
+        let temp_dir =
+            TempDir::new("tmp").expect("Failed to create temporary directory");
+        let storage_path = temp_dir.path().join("storage.json");
+
+        let mut storage =
+            FileStorage::new("TestStorage".to_string(), &storage_path).unwrap();
+        storage.set("key1".to_string(), 1);
+        storage.set("key2".to_string(), 2);
+        storage.write_fs_async().await.unwrap();
+        assert_eq!(storage.sync_status().unwrap(), SyncStatus::InSync);
+
+        let mut reopened: FileStorage<String, i32> =
+            FileStorage::new("Reopened".to_string(), &storage_path).unwrap();
+        assert_eq!(reopened.as_ref(), storage.as_ref());
+
+        storage.remove(&"key1".to_string()).unwrap();
+        storage.write_fs_async().await.unwrap();
+        let entries = reopened.read_fs_async().await.unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries.get("key2"), Some(&2));
+    }
+}
@@ -1,5 +1,8 @@
 use data_error::Result;
-use std::collections::BTreeMap;
+use std::borrow::Borrow;
+use std::collections::{BTreeMap, BTreeSet};
+use std::ops::{Bound, RangeBounds};
+use std::path::Path;
 
 #[cfg(feature = "jni-bindings")]
 use jnix::{FromJava, IntoJava};
@@ -38,19 +41,89 @@ impl std::fmt::Display for SyncStatus {
 ///
 /// The trait also includes a method to merge values from another key-value mapping.
 ///
+/// Entries are read with the accessors of the trait, e.g. [`BaseStorage::get`].
+/// They are implemented on top of `AsRef<BTreeMap>` by default, and can be
+/// overridden by storages which don't need the whole mapping for them.
+///
 /// Note: The trait does not write to storage by default. It is up to the implementor to decide when to read or write to storage
 /// based on `SyncStatus`. This is to allow for trading off between performance and consistency.
 pub trait BaseStorage<K, V>: AsRef<BTreeMap<K, V>> {
+    /// Value of an entry in the internal mapping.
+    fn get(&self, id: &K) -> Option<&V>
+    where
+        K: Ord,
+    {
+        self.as_ref().get(id)
+    }
+
+    /// Whether the internal mapping has an entry for the key.
+    fn contains_key(&self, id: &K) -> bool
+    where
+        K: Ord,
+    {
+        self.get(id).is_some()
+    }
+
+    /// Number of entries in the internal mapping.
+    fn len(&self) -> usize {
+        self.as_ref().len()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Keys of the internal mapping, in order.
+    fn keys(&self) -> impl Iterator<Item = &K> {
+        self.as_ref().keys()
+    }
+
+    /// Entries of the internal mapping whose keys are in `range`, in order.
+    fn range<R>(&self, range: R) -> impl Iterator<Item = (&K, &V)>
+    where
+        K: Ord,
+        R: RangeBounds<K>,
+    {
+        self.as_ref().range(range)
+    }
+
+    /// Keys of the internal mapping starting with `prefix`, in order,
+    /// e.g. to autocomplete tags.
+    fn keys_with_prefix<'a>(
+        &'a self,
+        prefix: &'a str,
+    ) -> impl Iterator<Item = &'a K>
+    where
+        K: Ord + Borrow<str> + 'a,
+        V: 'a,
+    {
+        self.as_ref()
+            .range::<str, _>((Bound::Included(prefix), Bound::Unbounded))
+            .map(|(key, _)| key)
+            .take_while(move |key| {
+                Borrow::<str>::borrow(*key).starts_with(prefix)
+            })
+    }
+
     /// Create or update an entry in the internal mapping.
     fn set(&mut self, id: K, value: V);
 
     /// Remove an entry from the internal mapping.
     fn remove(&mut self, id: &K) -> Result<()>;
 
+    /// Keys set or removed since the mapping was last read or written,
+    /// e.g. to sync only these entries with another replica.
+    ///
+    /// Storages which support it persist only these entries on write.
+    fn dirty_keys(&self) -> &BTreeSet<K>;
+
     /// Get [`SyncStatus`] of the storage
     fn sync_status(&self) -> Result<SyncStatus>;
 
-    /// Sync the in-memory storage with the storage on disk
+    /// Sync the in-memory storage with the storage on disk, depending
+    /// on its [`SyncStatus`]: changes made on disk by other processes or
+    /// devices are read, changes made in memory are written, and both
+    /// are merged when they diverge.
     fn sync(&mut self) -> Result<()>;
 
     /// Scan and load the key-value mapping
@@ -66,4 +139,15 @@ pub trait BaseStorage<K, V>: AsRef<BTreeMap<K, V>> {
 
     /// Merge values from another key-value mapping.
     fn merge_from(&mut self, other: impl AsRef<BTreeMap<K, V>>) -> Result<()>;
+
+    /// Copy the persisted state of the storage into a snapshot folder at
+    /// `dest`, along with its label and version, e.g. before a migration.
+    ///
+    /// Changes which are not written yet are not part of the snapshot.
+    /// See [`crate::snapshot`] for the layout of the folder.
+    fn snapshot(&self, dest: &Path) -> Result<()>;
+
+    /// Replace the persisted state with the snapshot at `src`
+    /// and read it, discarding the changes which are not written yet.
+    fn restore(&mut self, src: &Path) -> Result<()>;
 }
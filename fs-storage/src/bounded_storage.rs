@@ -0,0 +1,234 @@
+use std::collections::BTreeMap;
+use std::marker::PhantomData;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::base_storage::BaseStorage;
+use crate::monoid::Monoid;
+use data_error::Result;
+
+/// Value of a [`BoundedStorage`] along with the last time it was used,
+/// as a counter shared by all the entries of the storage
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Recent<V> {
+    pub value: V,
+    pub used: u64,
+}
+
+impl<V: serde::de::DeserializeOwned> FromStr for Recent<V> {
+    type Err = serde_json::Error;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        serde_json::from_str(s)
+    }
+}
+
+// The most recently used value wins
+impl<V: Monoid<V> + Clone> Monoid<Recent<V>> for Recent<V> {
+    fn neutral() -> Recent<V> {
+        Recent {
+            value: V::neutral(),
+            used: 0,
+        }
+    }
+
+    fn combine(a: &Recent<V>, b: &Recent<V>) -> Recent<V> {
+        match a.used.cmp(&b.used) {
+            std::cmp::Ordering::Greater => a.clone(),
+            std::cmp::Ordering::Less => b.clone(),
+            std::cmp::Ordering::Equal => Recent {
+                value: V::combine(&a.value, &b.value),
+                used: a.used,
+            },
+        }
+    }
+}
+
+/// Wrapper of a storage of cached data, e.g. previews or thumbnails
+/// bookkeeping, which evicts the least recently used entries once their
+/// size exceeds a quota.
+///
+/// The size of an entry is approximated by the length of its JSON
+/// serialization. Getting or setting an entry marks it as used.
+pub struct BoundedStorage<S, K, V> {
+    storage: S,
+    /// Maximum size of the entries, in bytes
+    quota: usize,
+    sizes: BTreeMap<K, usize>,
+    size: usize,
+    /// Last value of the `used` counter
+    clock: u64,
+    _entries: PhantomData<V>,
+}
+
+impl<S, K, V> BoundedStorage<S, K, V>
+where
+    S: BaseStorage<K, Recent<V>>,
+    K: Ord + Clone + Serialize,
+    V: Clone + Serialize,
+{
+    pub fn new(storage: S, quota: usize) -> Self {
+        let mut storage = BoundedStorage {
+            storage,
+            quota,
+            sizes: BTreeMap::new(),
+            size: 0,
+            clock: 0,
+            _entries: PhantomData,
+        };
+        storage.measure();
+        storage.evict();
+        storage
+    }
+
+    /// Value of `key`, marking it as used
+    pub fn get(&mut self, key: &K) -> Option<&V> {
+        let value = self.storage.get(key)?.value.clone();
+        self.put(key.clone(), value);
+        self.storage.get(key).map(|entry| &entry.value)
+    }
+
+    /// Set the value of `key`, evicting other entries
+    /// if the quota is exceeded
+    pub fn set(&mut self, key: K, value: V) {
+        self.put(key, value);
+        self.evict();
+    }
+
+    pub fn remove(&mut self, key: &K) -> Result<()> {
+        self.storage.remove(key)?;
+        if let Some(size) = self.sizes.remove(key) {
+            self.size -= size;
+        }
+        Ok(())
+    }
+
+    /// Approximate size of the entries, in bytes
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn quota(&self) -> usize {
+        self.quota
+    }
+
+    /// Remove the least recently used entries until the size of the
+    /// entries fits the quota, returning how many were removed
+    pub fn evict(&mut self) -> usize {
+        if self.size <= self.quota {
+            return 0;
+        }
+        let mut by_use: Vec<(u64, K)> = self
+            .storage
+            .as_ref()
+            .iter()
+            .map(|(key, entry)| (entry.used, key.clone()))
+            .collect();
+        by_use.sort();
+
+        let mut evicted = 0;
+        for (_, key) in by_use {
+            if self.size <= self.quota {
+                break;
+            }
+            // the keys were just found in the storage
+            let _ = self.remove(&key);
+            evicted += 1;
+        }
+        log::debug!("{} entries evicted from a bounded storage", evicted);
+        evicted
+    }
+
+    /// See [`BaseStorage::read_fs`], entries are evicted
+    /// if the quota is exceeded
+    pub fn read_fs(&mut self) -> Result<&BTreeMap<K, Recent<V>>> {
+        self.storage.read_fs()?;
+        self.measure();
+        self.evict();
+        Ok(self.storage.as_ref())
+    }
+
+    /// See [`BaseStorage::write_fs`]
+    pub fn write_fs(&mut self) -> Result<()> {
+        self.evict();
+        self.storage.write_fs()
+    }
+
+    pub fn inner(&self) -> &S {
+        &self.storage
+    }
+
+    pub fn into_inner(self) -> S {
+        self.storage
+    }
+
+    /// Set the value of `key` as the most recently used one
+    fn put(&mut self, key: K, value: V) {
+        self.clock += 1;
+        let entry = Recent {
+            value,
+            used: self.clock,
+        };
+        let size = entry_size(&key, &entry);
+        if let Some(previous) = self.sizes.insert(key.clone(), size) {
+            self.size -= previous;
+        }
+        self.size += size;
+        self.storage.set(key, entry);
+    }
+
+    fn measure(&mut self) {
+        self.sizes = self
+            .storage
+            .as_ref()
+            .iter()
+            .map(|(key, entry)| (key.clone(), entry_size(key, entry)))
+            .collect();
+        self.size = self.sizes.values().sum();
+        self.clock = self
+            .storage
+            .as_ref()
+            .values()
+            .map(|entry| entry.used)
+            .max()
+            .unwrap_or(0);
+    }
+}
+
+fn entry_size<K: Serialize, V: Serialize>(key: &K, entry: &Recent<V>) -> usize {
+    serde_json::to_vec(&(key, entry))
+        .map(|json| json.len())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BoundedStorage, Recent};
+    use crate::{base_storage::BaseStorage, memory_storage::MemoryStorage};
+
+    #[test]
+    fn test_bounded_storage_evicts_least_recently_used() {
+        let storage: MemoryStorage<String, Recent<i32>> =
+            MemoryStorage::new("Cache".to_string());
+        let mut cache = BoundedStorage::new(storage, 100);
+        cache.set("a".to_string(), 1);
+        let entry_size = cache.size();
+        let capacity = 100 / entry_size;
+        assert!(capacity >= 2);
+
+        for i in 1..capacity {
+            cache.set(format!("{}", i), 1);
+        }
+        assert!(cache.size() <= cache.quota());
+        assert_eq!(cache.inner().len(), capacity);
+
+        // "a" is used, so "1" is the least recently used entry
+        assert_eq!(cache.get(&"a".to_string()), Some(&1));
+        cache.set("b".to_string(), 2);
+        assert!(cache.size() <= cache.quota());
+        assert!(cache.inner().contains_key(&"a".to_string()));
+        assert!(!cache.inner().contains_key(&"1".to_string()));
+        assert!(cache.inner().contains_key(&"b".to_string()));
+    }
+}
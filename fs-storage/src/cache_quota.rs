@@ -0,0 +1,241 @@
+//! Size cap of the cache folders of `.ark`, e.g. previews, whose
+//! least recently used entries are evicted once the cap is exceeded.
+//!
+//! Every entry of a cache folder is a folder named after a resource id,
+//! holding the versions of its data. An entry is used when its data is
+//! written or [touched](CacheQuota::touch), the cap is kept along with
+//! the entries in [`QUOTA_FILE`].
+
+use std::fs::{self, File};
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{ARK_FOLDER, PREVIEWS_STORAGE_FOLDER};
+use data_error::{Result, ResultExt};
+
+/// Name of the file holding the cap of a cache folder
+pub const QUOTA_FILE: &str = ".quota";
+
+/// Space taken by a cache folder
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CacheUsage {
+    pub entries: usize,
+    pub bytes: u64,
+    /// Cap of the folder, in bytes
+    pub cap: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Quota {
+    cap: Option<u64>,
+}
+
+/// Size-bounded cache folder
+#[derive(Debug, Clone)]
+pub struct CacheQuota {
+    folder: PathBuf,
+}
+
+/// Entry of a cache folder, with its size and last use
+struct Entry {
+    path: PathBuf,
+    bytes: u64,
+    used: SystemTime,
+}
+
+impl CacheQuota {
+    /// Cache folder of `.ark`, e.g. [`PREVIEWS_STORAGE_FOLDER`]
+    pub fn new<P: AsRef<Path>>(root: P, folder: &str) -> Self {
+        CacheQuota {
+            folder: root.as_ref().join(ARK_FOLDER).join(folder),
+        }
+    }
+
+    /// Previews of the resources
+    pub fn previews<P: AsRef<Path>>(root: P) -> Self {
+        Self::new(root, PREVIEWS_STORAGE_FOLDER)
+    }
+
+    /// Cap of the folder in bytes, `None` if it can grow unbounded
+    pub fn cap(&self) -> Result<Option<u64>> {
+        let path = self.folder.join(QUOTA_FILE);
+        match fs::read(&path) {
+            Ok(content) => {
+                let quota: Quota =
+                    serde_json::from_slice(&content).with_path(&path)?;
+                Ok(quota.cap)
+            }
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err).with_path(&path),
+        }
+    }
+
+    /// Change the cap of the folder, evicting entries if it is exceeded.
+    /// Returns the number of evicted entries.
+    pub fn set_cap(&self, cap: Option<u64>) -> Result<usize> {
+        fs::create_dir_all(&self.folder).with_path(&self.folder)?;
+        let path = self.folder.join(QUOTA_FILE);
+        let content = serde_json::to_vec(&Quota { cap }).with_path(&path)?;
+        fs::write(&path, content).with_path(&path)?;
+        self.enforce()
+    }
+
+    /// Current number of entries, their size and the cap
+    pub fn usage(&self) -> Result<CacheUsage> {
+        let entries = self.entries()?;
+        Ok(CacheUsage {
+            entries: entries.len(),
+            bytes: entries.iter().map(|entry| entry.bytes).sum(),
+            cap: self.cap()?,
+        })
+    }
+
+    /// Mark the entry of the resource as used, e.g. when it is read
+    pub fn touch(&self, id: &str) -> Result<()> {
+        let path = self.folder.join(id);
+        let Some(latest) = latest_file(&path)? else {
+            return Ok(());
+        };
+        File::options()
+            .write(true)
+            .open(&latest)
+            .and_then(|file| file.set_modified(SystemTime::now()))
+            .with_path(&latest)
+    }
+
+    /// Evict the least recently used entries until the cap is respected,
+    /// returning their number
+    pub fn enforce(&self) -> Result<usize> {
+        let Some(cap) = self.cap()? else {
+            return Ok(0);
+        };
+        let mut entries = self.entries()?;
+        let mut bytes: u64 = entries.iter().map(|entry| entry.bytes).sum();
+        entries.sort_by_key(|entry| entry.used);
+
+        let mut evicted = 0;
+        for entry in entries {
+            if bytes <= cap {
+                break;
+            }
+            fs::remove_dir_all(&entry.path).with_path(&entry.path)?;
+            bytes -= entry.bytes;
+            evicted += 1;
+        }
+        if evicted > 0 {
+            log::debug!(
+                "{} entries evicted from {}",
+                evicted,
+                self.folder.display()
+            );
+        }
+        Ok(evicted)
+    }
+
+    fn entries(&self) -> Result<Vec<Entry>> {
+        let read_dir = match fs::read_dir(&self.folder) {
+            Ok(read_dir) => read_dir,
+            Err(err) if err.kind() == ErrorKind::NotFound => {
+                return Ok(Vec::new())
+            }
+            Err(err) => return Err(err).with_path(&self.folder),
+        };
+        let mut entries = Vec::new();
+        for entry in read_dir {
+            let path = entry.with_path(&self.folder)?.path();
+            if !path.is_dir() {
+                continue;
+            }
+            let mut bytes = 0;
+            let mut used = SystemTime::UNIX_EPOCH;
+            for file in fs::read_dir(&path).with_path(&path)? {
+                let metadata = file
+                    .and_then(|file| file.metadata())
+                    .with_path(&path)?;
+                bytes += metadata.len();
+                if let Ok(modified) = metadata.modified() {
+                    used = used.max(modified);
+                }
+            }
+            entries.push(Entry { path, bytes, used });
+        }
+        Ok(entries)
+    }
+}
+
+/// Most recently modified file of the folder of an entry
+fn latest_file(path: &Path) -> Result<Option<PathBuf>> {
+    let read_dir = match fs::read_dir(path) {
+        Ok(read_dir) => read_dir,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err).with_path(path),
+    };
+    let mut latest: Option<(SystemTime, PathBuf)> = None;
+    for file in read_dir {
+        let file = file.with_path(path)?;
+        let modified = file
+            .metadata()
+            .and_then(|metadata| metadata.modified())
+            .with_path(file.path())?;
+        if latest
+            .as_ref()
+            .map_or(true, |(latest, _)| modified >= *latest)
+        {
+            latest = Some((modified, file.path()));
+        }
+    }
+    Ok(latest.map(|(_, path)| path))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::time::{Duration, SystemTime};
+
+    use tempdir::TempDir;
+
+    use super::*;
+
+    fn write_entry(quota: &CacheQuota, id: &str, bytes: usize, age: u64) {
+        let path = quota.folder.join(id);
+        fs::create_dir_all(&path).unwrap();
+        let file = path.join("preview.1");
+        fs::write(&file, vec![0u8; bytes]).unwrap();
+        File::options()
+            .write(true)
+            .open(&file)
+            .unwrap()
+            .set_modified(SystemTime::now() - Duration::from_secs(age))
+            .unwrap();
+    }
+
+    #[test]
+    fn test_least_recently_used_entries_are_evicted() {
+        let dir = TempDir::new("cache_quota").unwrap();
+        let quota = CacheQuota::previews(dir.path());
+        assert_eq!(quota.usage().unwrap(), CacheUsage::default());
+
+        write_entry(&quota, "old", 100, 300);
+        write_entry(&quota, "used", 100, 200);
+        write_entry(&quota, "new", 100, 100);
+        quota.touch("used").unwrap();
+        assert_eq!(quota.usage().unwrap().bytes, 300);
+
+        assert_eq!(quota.set_cap(Some(250)).unwrap(), 1);
+        let usage = quota.usage().unwrap();
+        assert_eq!(usage.entries, 2);
+        assert_eq!(usage.cap, Some(250));
+        assert!(!quota.folder.join("old").exists());
+        assert!(quota.folder.join("used").exists());
+
+        assert_eq!(quota.set_cap(Some(100)).unwrap(), 1);
+        assert!(quota.folder.join("used").exists());
+
+        write_entry(&quota, "newer", 100, 0);
+        assert_eq!(quota.set_cap(None).unwrap(), 0);
+        assert_eq!(quota.usage().unwrap().entries, 2);
+    }
+}
@@ -0,0 +1,169 @@
+//! Checksum footer of storage files, to tell a corrupted file from
+//! a file which is merely invalid.
+//!
+//! The footer holds the CRC32 of every byte preceding it, along with the
+//! number of entries written. In JSON files, it is the last field of the
+//! document, so other readers ignore it. In CBOR files, it follows the
+//! document. Files without a footer, e.g. written by older versions,
+//! are read without verification.
+
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::file_storage::StorageFormat;
+use data_error::{ArklibError, Result};
+
+/// Start of the footer of JSON files, as written by `serde_json`
+const JSON_MARKER: &[u8] = b",\n  \"checksum\": ";
+
+/// Start of the footer of CBOR files
+#[cfg(feature = "binary-format")]
+const CBOR_MARKER: &[u8] = b"ARK-CHECKSUM";
+
+/// Length of the footer of CBOR files
+#[cfg(feature = "binary-format")]
+const CBOR_FOOTER_LEN: usize = CBOR_MARKER.len() + 4 + 8;
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+struct Footer {
+    crc32: u32,
+    entries: usize,
+}
+
+/// Append the footer to `content`, a document with `entries` entries
+pub(crate) fn append(
+    format: StorageFormat,
+    mut content: Vec<u8>,
+    entries: usize,
+) -> Vec<u8> {
+    match format {
+        StorageFormat::Json => {
+            // pretty-printed documents end with "\n}"
+            if !content.ends_with(b"\n}") {
+                return content;
+            }
+            content.truncate(content.len() - 2);
+            let footer = Footer {
+                crc32: crc32fast::hash(&content),
+                entries,
+            };
+            content.extend_from_slice(JSON_MARKER);
+            // serializing two integers can't fail
+            let _ = serde_json::to_writer(&mut content, &footer);
+            content.extend_from_slice(b"\n}");
+        }
+        #[cfg(feature = "binary-format")]
+        StorageFormat::Cbor => {
+            let crc32 = crc32fast::hash(&content);
+            content.extend_from_slice(CBOR_MARKER);
+            content.extend_from_slice(&crc32.to_be_bytes());
+            content.extend_from_slice(&(entries as u64).to_be_bytes());
+        }
+    }
+    content
+}
+
+/// Verify the footer of `content`, if any, returning the document
+/// to parse along with the number of entries it must have
+pub(crate) fn verify<'a>(
+    label: &str,
+    path: &Path,
+    format: StorageFormat,
+    content: &'a [u8],
+) -> Result<(&'a [u8], Option<usize>)> {
+    let (document, checked, footer) = match split(format, content) {
+        Some(split) => split,
+        None => return Ok((content, None)),
+    };
+    if crc32fast::hash(checked) != footer.crc32 {
+        return Err(corrupted(
+            label,
+            path,
+            "its checksum doesn't match (if it was edited by hand, \
+             remove the checksum to accept the changes)",
+        ));
+    }
+    Ok((document, Some(footer.entries)))
+}
+
+/// Error of a storage file which can't be read as it was written
+pub(crate) fn corrupted(label: &str, path: &Path, reason: &str) -> ArklibError {
+    ArklibError::WithPath(
+        path.to_owned(),
+        Box::new(ArklibError::Corrupted(label.to_owned(), reason.to_owned())),
+    )
+}
+
+/// Split `content` into the document to parse,
+/// the bytes covered by the checksum and the footer
+fn split(
+    format: StorageFormat,
+    content: &[u8],
+) -> Option<(&[u8], &[u8], Footer)> {
+    match format {
+        StorageFormat::Json => {
+            let end = content
+                .iter()
+                .rposition(|byte| !byte.is_ascii_whitespace())?;
+            if content[end] != b'}' {
+                return None;
+            }
+            let start = content
+                .windows(JSON_MARKER.len())
+                .rposition(|window| window == JSON_MARKER)?;
+            let footer = serde_json::from_slice(
+                &content[start + JSON_MARKER.len()..end],
+            )
+            .ok()?;
+            Some((content, &content[..start], footer))
+        }
+        #[cfg(feature = "binary-format")]
+        StorageFormat::Cbor => {
+            let start = content.len().checked_sub(CBOR_FOOTER_LEN)?;
+            let footer = &content[start..];
+            if !footer.starts_with(CBOR_MARKER) {
+                return None;
+            }
+            let footer = &footer[CBOR_MARKER.len()..];
+            let crc32 = u32::from_be_bytes(footer[..4].try_into().ok()?);
+            let entries = u64::from_be_bytes(footer[4..].try_into().ok()?);
+            let footer = Footer {
+                crc32,
+                entries: entries as usize,
+            };
+            Some((&content[..start], &content[..start], footer))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_footer_roundtrip() {
+        let document = serde_json::to_vec_pretty(
+            &serde_json::json!({ "version": 3, "entries": { "a": 1 } }),
+        )
+        .unwrap();
+        let content = append(StorageFormat::Json, document, 1);
+        let path = Path::new("storage");
+
+        let (parsed, entries) =
+            verify("Test", path, StorageFormat::Json, &content).unwrap();
+        assert_eq!(entries, Some(1));
+        let value: serde_json::Value = serde_json::from_slice(parsed).unwrap();
+        assert_eq!(value["checksum"]["entries"], 1);
+
+        let flipped = String::from_utf8(content)
+            .unwrap()
+            .replace("\"a\": 1", "\"a\": 2");
+        let err = verify("Test", path, StorageFormat::Json, flipped.as_bytes())
+            .unwrap_err();
+        assert_eq!(
+            err.code(),
+            ArklibError::Corrupted(String::new(), String::new()).code()
+        );
+    }
+}
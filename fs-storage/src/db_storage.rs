@@ -0,0 +1,429 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Display;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use rusqlite::{params, Connection, OptionalExtension};
+
+use crate::base_storage::{BaseStorage, SyncStatus};
+use crate::monoid::Monoid;
+use crate::snapshot;
+use data_error::{ArklibError, Result, ResultExt};
+
+/*
+Note on `DbStorage` Versioning:
+
+The version of the schema is stamped in the `user_version` of the database.
+Version 3 is the first one, values are stored as JSON like in `FileStorage`.
+*/
+const STORAGE_VERSION: i32 = 3;
+
+/// Represents a storage system that persists data to an SQLite database.
+///
+/// Writing only updates the rows of the entries set or removed since the
+/// last write, so large storages, e.g. the tags of a vault with hundreds
+/// of thousands of resources, don't have to be rewritten entirely.
+pub struct DbStorage<K, V>
+where
+    K: Ord,
+{
+    /// Label for logging
+    label: String,
+    /// Path to the database file
+    path: PathBuf,
+    connection: Connection,
+    entries: BTreeMap<K, V>,
+    /// Keys set or removed since the last write
+    changed: BTreeSet<K>,
+    /// `data_version` of the database as of the last read or write,
+    /// it changes when another connection commits
+    data_version: i64,
+}
+
+impl<K, V> DbStorage<K, V>
+where
+    K: Ord + Clone + Display + FromStr,
+    V: Clone + serde::Serialize + serde::de::DeserializeOwned + Monoid<V>,
+{
+    /// Open the database at `path` with a diagnostic label,
+    /// creating it if it doesn't exist yet
+    pub fn new(label: String, path: &Path) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .with_path(parent)
+                .with_label(&label)?;
+        }
+        let connection = Connection::open(path).map_err(db_error(&label))?;
+
+        let version: i32 = connection
+            .query_row("PRAGMA user_version", [], |row| row.get(0))
+            .map_err(db_error(&label))?;
+        match version {
+            0 => connection
+                .execute_batch(&format!(
+                    "CREATE TABLE IF NOT EXISTS entries (
+                        key TEXT PRIMARY KEY NOT NULL,
+                        value TEXT NOT NULL
+                    );
+                    PRAGMA user_version = {};",
+                    STORAGE_VERSION
+                ))
+                .map_err(db_error(&label))?,
+            STORAGE_VERSION => {}
+            _ => {
+                return Err(ArklibError::Storage(
+                    label,
+                    format!(
+                        "Storage version mismatch: expected {}, got {}",
+                        STORAGE_VERSION, version
+                    ),
+                ))
+            }
+        }
+
+        let mut storage = Self {
+            label,
+            path: PathBuf::from(path),
+            connection,
+            entries: BTreeMap::new(),
+            changed: BTreeSet::new(),
+            data_version: 0,
+        };
+        storage.read_fs()?;
+        Ok(storage)
+    }
+
+    /// Value of `key` as currently stored in the database,
+    /// without loading the other entries
+    pub fn get_stored(&self, key: &K) -> Result<Option<V>> {
+        let json: Option<String> = self
+            .connection
+            .query_row(
+                "SELECT value FROM entries WHERE key = ?1",
+                params![key.to_string()],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(db_error(&self.label))?;
+        json.map(|json| self.parse_value(&json))
+            .transpose()
+    }
+
+    fn current_data_version(&self) -> Result<i64> {
+        self.connection
+            .query_row("PRAGMA data_version", [], |row| row.get(0))
+            .map_err(db_error(&self.label))
+    }
+
+    fn load_db_data(&self) -> Result<BTreeMap<K, V>> {
+        let mut statement = self
+            .connection
+            .prepare("SELECT key, value FROM entries")
+            .map_err(db_error(&self.label))?;
+        let rows = statement
+            .query_map([], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+            })
+            .map_err(db_error(&self.label))?;
+
+        let mut entries = BTreeMap::new();
+        for row in rows {
+            let (key, value) = row.map_err(db_error(&self.label))?;
+            let key = K::from_str(&key).map_err(|_| {
+                ArklibError::Storage(
+                    self.label.clone(),
+                    format!("Invalid key: {}", key),
+                )
+            })?;
+            entries.insert(key, self.parse_value(&value)?);
+        }
+        Ok(entries)
+    }
+
+    fn parse_value(&self, json: &str) -> Result<V> {
+        serde_json::from_str(json).map_err(|err| {
+            ArklibError::Storage(
+                self.label.clone(),
+                format!("{} (path: {})", err, self.path.display()),
+            )
+        })
+    }
+}
+
+impl<K, V> BaseStorage<K, V> for DbStorage<K, V>
+where
+    K: Ord + Clone + Display + FromStr,
+    V: Clone + serde::Serialize + serde::de::DeserializeOwned + Monoid<V>,
+{
+    /// Set a key-value pair in the internal mapping
+    fn set(&mut self, key: K, value: V) {
+        self.changed.insert(key.clone());
+        self.entries.insert(key, value);
+    }
+
+    /// Remove an entry from the internal mapping given a key
+    fn remove(&mut self, id: &K) -> Result<()> {
+        self.entries.remove(id).ok_or_else(|| {
+            ArklibError::Storage(self.label.clone(), "Key not found".to_owned())
+        })?;
+        self.changed.insert(id.clone());
+        Ok(())
+    }
+
+    /// Keys set or removed since the last read or write
+    fn dirty_keys(&self) -> &BTreeSet<K> {
+        &self.changed
+    }
+
+    /// Compare the pending changes of the in-memory storage and the
+    /// commits of other connections since the last read or write
+    /// to determine if either of the two requires syncing.
+    fn sync_status(&self) -> Result<SyncStatus> {
+        let status = match (
+            !self.changed.is_empty(),
+            self.current_data_version()? != self.data_version,
+        ) {
+            (true, true) => SyncStatus::Diverge,
+            (true, false) => SyncStatus::StorageStale,
+            (false, true) => SyncStatus::MappingStale,
+            (false, false) => SyncStatus::InSync,
+        };
+
+        log::info!("{} sync status is {}", self.label, status);
+        Ok(status)
+    }
+
+    /// Sync the in-memory storage with the storage on disk
+    fn sync(&mut self) -> Result<()> {
+        match self.sync_status()? {
+            SyncStatus::InSync => Ok(()),
+            SyncStatus::MappingStale => self.read_fs().map(|_| ()),
+            SyncStatus::StorageStale => self.write_fs(),
+            SyncStatus::Diverge => {
+                let data = self.load_db_data()?;
+                for (key, value) in data {
+                    let value = match self.entries.get(&key) {
+                        Some(existing) => V::combine(existing, &value),
+                        None => value,
+                    };
+                    self.set(key, value);
+                }
+                self.write_fs()
+            }
+        }
+    }
+
+    /// Read all the entries from the database
+    fn read_fs(&mut self) -> Result<&BTreeMap<K, V>> {
+        self.entries = self.load_db_data()?;
+        self.changed.clear();
+        self.data_version = self.current_data_version()?;
+        Ok(&self.entries)
+    }
+
+    /// Write the entries set or removed since the last write
+    /// in a single transaction
+    fn write_fs(&mut self) -> Result<()> {
+        let transaction = self
+            .connection
+            .transaction()
+            .map_err(db_error(&self.label))?;
+        for key in self.changed.iter() {
+            let result = match self.entries.get(key) {
+                Some(value) => {
+                    let json = serde_json::to_string(value)
+                        .with_path(&self.path)
+                        .with_label(&self.label)?;
+                    transaction.execute(
+                        "INSERT OR REPLACE INTO entries (key, value)
+                         VALUES (?1, ?2)",
+                        params![key.to_string(), json],
+                    )
+                }
+                None => transaction.execute(
+                    "DELETE FROM entries WHERE key = ?1",
+                    params![key.to_string()],
+                ),
+            };
+            result.map_err(db_error(&self.label))?;
+        }
+        transaction
+            .commit()
+            .map_err(db_error(&self.label))?;
+
+        log::info!(
+            "{} {} entries have been written",
+            self.label,
+            self.changed.len()
+        );
+        self.changed.clear();
+        self.data_version = self.current_data_version()?;
+        Ok(())
+    }
+
+    /// Erase the database from disk
+    fn erase(&self) -> Result<()> {
+        fs::remove_file(&self.path)
+            .with_path(&self.path)
+            .with_label(&self.label)?;
+        // journal files left by SQLite, if any
+        for suffix in ["-wal", "-shm", "-journal"] {
+            let mut path = self.path.clone().into_os_string();
+            path.push(suffix);
+            let _ = fs::remove_file(path);
+        }
+        Ok(())
+    }
+
+    /// Merge the data from another storage instance into this storage instance
+    fn merge_from(&mut self, other: impl AsRef<BTreeMap<K, V>>) -> Result<()>
+    where
+        V: Monoid<V>,
+    {
+        let other_entries = other.as_ref();
+        for (key, value) in other_entries {
+            if let Some(existing_value) = self.entries.get(key) {
+                let resolved_value = V::combine(existing_value, value);
+                self.set(key.clone(), resolved_value);
+            } else {
+                self.set(key.clone(), value.clone())
+            }
+        }
+        Ok(())
+    }
+
+    /// Copy the database into a snapshot, consistently
+    /// even if it is written by another connection
+    fn snapshot(&self, dest: &Path) -> Result<()> {
+        snapshot::create(&self.label, dest, STORAGE_VERSION)?;
+        let data = snapshot::data_path(dest);
+        // `VACUUM INTO` refuses to replace a file
+        if data.exists() {
+            fs::remove_file(&data)
+                .with_path(&data)
+                .with_label(&self.label)?;
+        }
+        self.connection
+            .execute("VACUUM INTO ?1", params![data.to_string_lossy()])
+            .map_err(db_error(&self.label))?;
+        Ok(())
+    }
+
+    /// Replace the entries of the database with the entries of
+    /// a snapshot, in a single transaction
+    fn restore(&mut self, src: &Path) -> Result<()> {
+        snapshot::read_manifest(&self.label, src, STORAGE_VERSION)?;
+        let data = snapshot::data_path(src);
+        if !data.is_file() {
+            return Err(ArklibError::Storage(
+                self.label.clone(),
+                format!("Nothing to restore at {}", data.display()),
+            ));
+        }
+        self.connection
+            .execute(
+                "ATTACH DATABASE ?1 AS snapshot",
+                params![data.to_string_lossy()],
+            )
+            .map_err(db_error(&self.label))?;
+        let restored = self.connection.execute_batch(
+            "BEGIN;
+             DELETE FROM entries;
+             INSERT INTO entries (key, value)
+                 SELECT key, value FROM snapshot.entries;
+             COMMIT;",
+        );
+        if restored.is_err() {
+            let _ = self.connection.execute_batch("ROLLBACK;");
+        }
+        let detached = self
+            .connection
+            .execute_batch("DETACH DATABASE snapshot;");
+        restored.map_err(db_error(&self.label))?;
+        detached.map_err(db_error(&self.label))?;
+
+        self.read_fs()?;
+        log::info!("{} restored from {}", self.label, src.display());
+        Ok(())
+    }
+}
+
+impl<K, V> AsRef<BTreeMap<K, V>> for DbStorage<K, V>
+where
+    K: Ord,
+{
+    fn as_ref(&self) -> &BTreeMap<K, V> {
+        &self.entries
+    }
+}
+
+fn db_error(label: &str) -> impl Fn(rusqlite::Error) -> ArklibError + '_ {
+    move |err| ArklibError::Storage(label.to_owned(), err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+
+    use crate::{
+        base_storage::{BaseStorage, SyncStatus},
+        db_storage::DbStorage,
+    };
+
+    #[test]
+    fn test_db_storage_write_read() {
+        let temp_dir =
+            TempDir::new("tmp").expect("Failed to create temporary directory");
+        let storage_path = temp_dir.path().join("storage.db");
+
+        let mut storage =
+            DbStorage::new("TestStorage".to_string(), &storage_path).unwrap();
+        storage.set("key1".to_string(), 1);
+        storage.set("key2".to_string(), 2);
+        assert_eq!(storage.get_stored(&"key1".to_string()).unwrap(), None);
+        storage.write_fs().unwrap();
+        assert_eq!(storage.get_stored(&"key1".to_string()).unwrap(), Some(1));
+
+        storage.remove(&"key1".to_string()).unwrap();
+        storage.write_fs().unwrap();
+
+        let reopened: DbStorage<String, i32> =
+            DbStorage::new("TestStorage".to_string(), &storage_path).unwrap();
+        assert_eq!(reopened.as_ref(), storage.as_ref());
+        assert_eq!(reopened.as_ref().len(), 1);
+
+        storage.erase().unwrap();
+        assert!(!storage_path.exists());
+    }
+
+    #[test]
+    fn test_db_storage_sync_status() {
+        let temp_dir =
+            TempDir::new("tmp").expect("Failed to create temporary directory");
+        let storage_path = temp_dir.path().join("storage.db");
+
+        let mut storage =
+            DbStorage::new("TestStorage".to_string(), &storage_path).unwrap();
+        assert_eq!(storage.sync_status().unwrap(), SyncStatus::InSync);
+        storage.set("key1".to_string(), 1);
+        assert!(storage.dirty_keys().contains("key1"));
+        assert_eq!(storage.sync_status().unwrap(), SyncStatus::StorageStale);
+        storage.write_fs().unwrap();
+        assert_eq!(storage.sync_status().unwrap(), SyncStatus::InSync);
+
+        // External data manipulation
+        let mut mirror: DbStorage<String, i32> =
+            DbStorage::new("MirrorStorage".to_string(), &storage_path).unwrap();
+        mirror.set("key2".to_string(), 2);
+        mirror.write_fs().unwrap();
+        assert_eq!(storage.sync_status().unwrap(), SyncStatus::MappingStale);
+
+        storage.set("key1".to_string(), 5);
+        assert_eq!(storage.sync_status().unwrap(), SyncStatus::Diverge);
+        storage.sync().unwrap();
+        assert_eq!(storage.sync_status().unwrap(), SyncStatus::InSync);
+        assert_eq!(storage.as_ref().get("key1"), Some(&5));
+        assert_eq!(storage.as_ref().get("key2"), Some(&2));
+    }
+}
@@ -0,0 +1,283 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Display;
+use std::path::Path;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::base_storage::{BaseStorage, SyncStatus};
+use crate::file_storage::FileStorage;
+use data_error::{ArklibError, Result};
+
+/// Storage of schema-less JSON values, e.g. user-defined fields
+/// whose type is not known at compile time.
+///
+/// The storage is a [`FileStorage`] of [`Value`], so it reads and writes
+/// the same version 3 files as any other file storage. Values are merged
+/// with [`data_json::merge`].
+///
+/// Typed views of the values are available through
+/// [`DynamicStorage::get_as`] and [`DynamicStorage::set_from`].
+pub struct DynamicStorage<K>
+where
+    K: Ord,
+{
+    label: String,
+    storage: FileStorage<K, Value>,
+}
+
+impl<K> DynamicStorage<K>
+where
+    K: Ord
+        + Clone
+        + Display
+        + serde::Serialize
+        + serde::de::DeserializeOwned
+        + std::str::FromStr,
+{
+    /// Create a new dynamic storage with a diagnostic label and file path,
+    /// see [`FileStorage::new`]
+    pub fn new(label: String, path: &Path) -> Result<Self> {
+        Ok(Self {
+            storage: FileStorage::new(label.clone(), path)?,
+            label,
+        })
+    }
+
+    /// Value of `key` deserialized as `T`
+    ///
+    /// Returns `None` if the key is absent and an error
+    /// if the value doesn't have the shape of `T`.
+    pub fn get_as<T: DeserializeOwned>(&self, key: &K) -> Result<Option<T>> {
+        let Some(value) = self.storage.get(key) else {
+            return Ok(None);
+        };
+        T::deserialize(value).map(Some).map_err(|err| {
+            ArklibError::Storage(
+                self.label.clone(),
+                format!(
+                    "Value of {} doesn't have the expected shape: {}",
+                    key, err
+                ),
+            )
+        })
+    }
+
+    /// Serialize `value` and set it as the value of `key`
+    pub fn set_from<T: Serialize>(&mut self, key: K, value: &T) -> Result<()> {
+        let value = serde_json::to_value(value).map_err(|err| {
+            ArklibError::Storage(
+                self.label.clone(),
+                format!("Value of {} can't be serialized: {}", key, err),
+            )
+        })?;
+        self.storage.set(key, value);
+        Ok(())
+    }
+}
+
+impl<K> BaseStorage<K, Value> for DynamicStorage<K>
+where
+    K: Ord
+        + Clone
+        + serde::Serialize
+        + serde::de::DeserializeOwned
+        + std::str::FromStr,
+{
+    fn set(&mut self, key: K, value: Value) {
+        self.storage.set(key, value)
+    }
+
+    fn remove(&mut self, key: &K) -> Result<()> {
+        self.storage.remove(key)
+    }
+
+    fn dirty_keys(&self) -> &BTreeSet<K> {
+        self.storage.dirty_keys()
+    }
+
+    fn sync_status(&self) -> Result<SyncStatus> {
+        self.storage.sync_status()
+    }
+
+    fn sync(&mut self) -> Result<()> {
+        self.storage.sync()
+    }
+
+    fn read_fs(&mut self) -> Result<&BTreeMap<K, Value>> {
+        self.storage.read_fs()
+    }
+
+    fn write_fs(&mut self) -> Result<()> {
+        self.storage.write_fs()
+    }
+
+    fn erase(&self) -> Result<()> {
+        self.storage.erase()
+    }
+
+    fn merge_from(
+        &mut self,
+        other: impl AsRef<BTreeMap<K, Value>>,
+    ) -> Result<()> {
+        self.storage.merge_from(other)
+    }
+
+    fn snapshot(&self, dest: &Path) -> Result<()> {
+        self.storage.snapshot(dest)
+    }
+
+    fn restore(&mut self, src: &Path) -> Result<()> {
+        self.storage.restore(src)
+    }
+}
+
+impl<K> AsRef<BTreeMap<K, Value>> for DynamicStorage<K>
+where
+    K: Ord,
+{
+    fn as_ref(&self) -> &BTreeMap<K, Value> {
+        self.storage.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+    use serde_json::{json, Value};
+    use tempdir::TempDir;
+
+    use crate::{
+        base_storage::BaseStorage, dynamic_storage::DynamicStorage,
+        file_storage::FileStorage,
+    };
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Rating {
+        stars: u8,
+        comment: String,
+    }
+
+    #[test]
+    fn test_dynamic_storage_mixed_values() {
+        let temp_dir =
+            TempDir::new("tmp").expect("Failed to create temporary directory");
+        let storage_path = temp_dir.path().join("custom_fields");
+
+        let mut storage: DynamicStorage<String> =
+            DynamicStorage::new("CustomFields".to_string(), &storage_path)
+                .unwrap();
+        storage.set("number".to_string(), json!(42));
+        storage.set("text".to_string(), json!("forty-two"));
+        storage.set("list".to_string(), json!([4, 2]));
+        storage.set("object".to_string(), json!({ "answer": 42 }));
+        storage.write_fs().unwrap();
+
+        // The file is a regular version 3 storage
+        let typed: FileStorage<String, Value> =
+            FileStorage::new("Typed".to_string(), &storage_path).unwrap();
+        assert_eq!(typed.as_ref(), storage.as_ref());
+
+        let reopened: DynamicStorage<String> =
+            DynamicStorage::new("CustomFields".to_string(), &storage_path)
+                .unwrap();
+        assert_eq!(reopened.as_ref().len(), 4);
+        assert_eq!(reopened.as_ref()["list"], json!([4, 2]));
+    }
+
+    #[test]
+    fn test_dynamic_storage_typed_accessors() {
+        let temp_dir =
+            TempDir::new("tmp").expect("Failed to create temporary directory");
+        let storage_path = temp_dir.path().join("custom_fields");
+
+        let mut storage: DynamicStorage<String> =
+            DynamicStorage::new("CustomFields".to_string(), &storage_path)
+                .unwrap();
+        let rating = Rating {
+            stars: 4,
+            comment: "good".to_string(),
+        };
+        storage
+            .set_from("rating".to_string(), &rating)
+            .unwrap();
+        storage.set("count".to_string(), json!(3));
+
+        assert_eq!(
+            storage
+                .get_as::<Rating>(&"rating".to_string())
+                .unwrap(),
+            Some(rating)
+        );
+        assert_eq!(
+            storage
+                .get_as::<u32>(&"count".to_string())
+                .unwrap(),
+            Some(3)
+        );
+        assert_eq!(
+            storage
+                .get_as::<u32>(&"missing".to_string())
+                .unwrap(),
+            None
+        );
+
+        let err = storage
+            .get_as::<Rating>(&"count".to_string())
+            .unwrap_err();
+        assert!(err.to_string().contains("count"), "{}", err);
+        assert!(storage
+            .get_as::<String>(&"rating".to_string())
+            .is_err());
+    }
+
+    #[test]
+    fn test_dynamic_storage_merge() {
+        let temp_dir =
+            TempDir::new("tmp").expect("Failed to create temporary directory");
+
+        let mut storage_1: DynamicStorage<String> = DynamicStorage::new(
+            "Storage1".to_string(),
+            &temp_dir.path().join("storage1"),
+        )
+        .unwrap();
+        let mut storage_2: DynamicStorage<String> = DynamicStorage::new(
+            "Storage2".to_string(),
+            &temp_dir.path().join("storage2"),
+        )
+        .unwrap();
+
+        storage_1.set(
+            "key1".to_string(),
+            json!({ "name": "a", "tags": ["x"], "nested": { "b": 1 } }),
+        );
+        storage_1.set("key2".to_string(), json!("only in 1"));
+        storage_2.set(
+            "key1".to_string(),
+            json!({ "name": "a", "tags": ["y"], "size": 2 }),
+        );
+        storage_2.set("key3".to_string(), json!("only in 2"));
+
+        storage_1.merge_from(&storage_2).unwrap();
+        assert_eq!(
+            storage_1.as_ref()["key1"],
+            json!({
+                "name": "a",
+                "tags": ["x", "y"],
+                "nested": { "b": 1 },
+                "size": 2,
+            })
+        );
+        assert_eq!(storage_1.as_ref()["key2"], json!("only in 1"));
+        assert_eq!(storage_1.as_ref()["key3"], json!("only in 2"));
+
+        // Nested objects under the same key are kept side by side
+        storage_2.set("key1".to_string(), json!({ "nested": { "c": 2 } }));
+        storage_1.merge_from(&storage_2).unwrap();
+        assert_eq!(
+            storage_1.as_ref()["key1"]["nested"],
+            json!([{ "b": 1 }, { "c": 2 }])
+        );
+    }
+}
@@ -0,0 +1,106 @@
+//! Encryption at rest of [`FileStorage`](crate::file_storage::FileStorage)
+//! files, behind the `encryption` feature.
+//!
+//! An encrypted file starts with a magic line, followed by the random nonce
+//! and the content encrypted with ChaCha20-Poly1305, in whichever format
+//! the storage is written.
+
+#[cfg(feature = "encryption")]
+use data_error::{ArklibError, Result};
+
+const MAGIC: &[u8] = b"ARK-ENCRYPTED-1\n";
+#[cfg(feature = "encryption")]
+const NONCE_LEN: usize = 12;
+
+/// 256-bit key of an encrypted storage, see
+/// [`FileStorage::new_encrypted`](crate::file_storage::FileStorage::new_encrypted)
+#[derive(Clone)]
+#[cfg_attr(not(feature = "encryption"), allow(dead_code))]
+pub struct EncryptionKey([u8; 32]);
+
+impl EncryptionKey {
+    #[cfg(feature = "encryption")]
+    pub fn new(bytes: [u8; 32]) -> Self {
+        EncryptionKey(bytes)
+    }
+}
+
+impl std::fmt::Debug for EncryptionKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("EncryptionKey(..)")
+    }
+}
+
+pub(crate) fn is_encrypted(content: &[u8]) -> bool {
+    content.starts_with(MAGIC)
+}
+
+#[cfg(feature = "encryption")]
+pub(crate) fn encrypt(
+    label: &str,
+    key: &EncryptionKey,
+    plaintext: &[u8],
+) -> Result<Vec<u8>> {
+    use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+    use chacha20poly1305::ChaCha20Poly1305;
+
+    let cipher = ChaCha20Poly1305::new(&key.0.into());
+    let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(&nonce, plaintext)
+        .map_err(|_| error(label, "Failed to encrypt the storage"))?;
+
+    let mut content =
+        Vec::with_capacity(MAGIC.len() + NONCE_LEN + ciphertext.len());
+    content.extend_from_slice(MAGIC);
+    content.extend_from_slice(&nonce);
+    content.extend_from_slice(&ciphertext);
+    Ok(content)
+}
+
+#[cfg(feature = "encryption")]
+pub(crate) fn decrypt(
+    label: &str,
+    key: &EncryptionKey,
+    content: &[u8],
+) -> Result<Vec<u8>> {
+    use chacha20poly1305::aead::{Aead, KeyInit};
+    use chacha20poly1305::{ChaCha20Poly1305, Nonce};
+
+    let content = &content[MAGIC.len()..];
+    if content.len() < NONCE_LEN {
+        return Err(error(label, "Encrypted storage is truncated"));
+    }
+    let (nonce, ciphertext) = content.split_at(NONCE_LEN);
+    let cipher = ChaCha20Poly1305::new(&key.0.into());
+    cipher
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| {
+            error(label, "Failed to decrypt the storage, is the key right?")
+        })
+}
+
+#[cfg(feature = "encryption")]
+fn error(label: &str, message: &str) -> ArklibError {
+    ArklibError::Storage(label.to_owned(), message.to_owned())
+}
+
+#[cfg(all(test, feature = "encryption"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encrypted_content_roundtrip() {
+        let key = EncryptionKey::new([7; 32]);
+        let content = encrypt("Test", &key, b"{\"version\":3}").unwrap();
+        assert!(is_encrypted(&content));
+        assert_eq!(
+            decrypt("Test", &key, &content).unwrap(),
+            b"{\"version\":3}"
+        );
+
+        let wrong = EncryptionKey::new([8; 32]);
+        assert!(decrypt("Test", &wrong, &content).is_err());
+        assert!(decrypt("Test", &key, &content[..MAGIC.len() + 4]).is_err());
+    }
+}
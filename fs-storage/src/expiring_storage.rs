@@ -0,0 +1,193 @@
+use std::collections::BTreeMap;
+use std::marker::PhantomData;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::base_storage::BaseStorage;
+use crate::monoid::Monoid;
+use data_error::Result;
+
+/// Value of an [`ExpiringStorage`] along with its insertion time
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Expiring<V> {
+    pub value: V,
+    /// Seconds since the Unix epoch
+    pub inserted: u64,
+}
+
+impl<V> Expiring<V> {
+    pub fn new(value: V) -> Self {
+        Expiring {
+            value,
+            inserted: now(),
+        }
+    }
+
+    pub fn is_expired(&self, ttl: Duration) -> bool {
+        self.inserted.saturating_add(ttl.as_secs()) <= now()
+    }
+}
+
+impl<V: serde::de::DeserializeOwned> FromStr for Expiring<V> {
+    type Err = serde_json::Error;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        serde_json::from_str(s)
+    }
+}
+
+// The most recent value wins
+impl<V: Monoid<V> + Clone> Monoid<Expiring<V>> for Expiring<V> {
+    fn neutral() -> Expiring<V> {
+        Expiring {
+            value: V::neutral(),
+            inserted: 0,
+        }
+    }
+
+    fn combine(a: &Expiring<V>, b: &Expiring<V>) -> Expiring<V> {
+        match a.inserted.cmp(&b.inserted) {
+            std::cmp::Ordering::Greater => a.clone(),
+            std::cmp::Ordering::Less => b.clone(),
+            std::cmp::Ordering::Equal => Expiring {
+                value: V::combine(&a.value, &b.value),
+                inserted: a.inserted,
+            },
+        }
+    }
+}
+
+/// Wrapper of a storage of cached data, e.g. metadata or previews
+/// bookkeeping, whose entries expire `ttl` after they were set.
+///
+/// Expired entries are hidden by [`ExpiringStorage::get`], and evicted
+/// from the underlying storage when it is read or written.
+pub struct ExpiringStorage<S, K, V> {
+    storage: S,
+    ttl: Duration,
+    _entries: PhantomData<(K, V)>,
+}
+
+impl<S, K, V> ExpiringStorage<S, K, V>
+where
+    S: BaseStorage<K, Expiring<V>>,
+    K: Ord + Clone,
+{
+    pub fn new(storage: S, ttl: Duration) -> Self {
+        let mut storage = ExpiringStorage {
+            storage,
+            ttl,
+            _entries: PhantomData,
+        };
+        storage.evict_expired();
+        storage
+    }
+
+    /// Value of `key`, unless it is expired
+    pub fn get(&self, key: &K) -> Option<&V> {
+        self.storage
+            .get(key)
+            .filter(|entry| !entry.is_expired(self.ttl))
+            .map(|entry| &entry.value)
+    }
+
+    /// Set the value of `key`, expiring `ttl` from now
+    pub fn set(&mut self, key: K, value: V) {
+        self.storage.set(key, Expiring::new(value));
+    }
+
+    pub fn remove(&mut self, key: &K) -> Result<()> {
+        self.storage.remove(key)
+    }
+
+    /// Remove the expired entries, returning how many were removed
+    pub fn evict_expired(&mut self) -> usize {
+        let expired: Vec<K> = self
+            .storage
+            .as_ref()
+            .iter()
+            .filter(|(_, entry)| entry.is_expired(self.ttl))
+            .map(|(key, _)| key.clone())
+            .collect();
+        for key in expired.iter() {
+            // the keys were just found in the storage
+            let _ = self.storage.remove(key);
+        }
+        expired.len()
+    }
+
+    /// See [`BaseStorage::read_fs`], expired entries are evicted
+    pub fn read_fs(&mut self) -> Result<&BTreeMap<K, Expiring<V>>> {
+        self.storage.read_fs()?;
+        self.evict_expired();
+        Ok(self.storage.as_ref())
+    }
+
+    /// See [`BaseStorage::write_fs`], expired entries are evicted first
+    pub fn write_fs(&mut self) -> Result<()> {
+        self.evict_expired();
+        self.storage.write_fs()
+    }
+
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    pub fn inner(&self) -> &S {
+        &self.storage
+    }
+
+    pub fn into_inner(self) -> S {
+        self.storage
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tempdir::TempDir;
+
+    use super::{Expiring, ExpiringStorage};
+    use crate::{base_storage::BaseStorage, file_storage::FileStorage};
+
+    #[test]
+    fn test_expiring_storage_evicts_expired_entries() {
+        let temp_dir =
+            TempDir::new("tmp").expect("Failed to create temporary directory");
+        let storage_path = temp_dir.path().join("cache");
+
+        let mut file_storage: FileStorage<String, Expiring<i32>> =
+            FileStorage::new("Cache".to_string(), &storage_path).unwrap();
+        file_storage.set(
+            "old".to_string(),
+            Expiring {
+                value: 1,
+                inserted: 0,
+            },
+        );
+        file_storage.write_fs().unwrap();
+
+        let mut cache =
+            ExpiringStorage::new(file_storage, Duration::from_secs(3600));
+        cache.set("new".to_string(), 2);
+        assert_eq!(cache.get(&"old".to_string()), None);
+        assert_eq!(cache.get(&"new".to_string()), Some(&2));
+        cache.write_fs().unwrap();
+
+        let reopened: FileStorage<String, Expiring<i32>> =
+            FileStorage::new("Cache".to_string(), &storage_path).unwrap();
+        assert_eq!(reopened.len(), 1);
+        assert!(reopened.contains_key(&"new".to_string()));
+        assert_eq!(cache.evict_expired(), 0);
+    }
+}
@@ -1,16 +1,27 @@
 use serde::{Deserialize, Serialize};
 use std::fs::{self, File};
 use std::io::Write;
-use std::time::SystemTime;
+#[cfg(feature = "tracing")]
+use std::time::Instant;
+use std::time::{Duration, SystemTime};
 use std::{
-    collections::BTreeMap,
+    collections::{BTreeMap, BTreeSet},
     path::{Path, PathBuf},
 };
 
 use crate::base_storage::{BaseStorage, SyncStatus};
+use crate::checksum;
+use crate::encryption::{self, EncryptionKey};
+use crate::journal;
+use crate::lock::{self, Lock};
+use crate::merge::{self, SyncReport};
+use crate::migration;
 use crate::monoid::Monoid;
-use crate::utils::read_version_2_fs;
-use data_error::{ArklibError, Result};
+use crate::snapshot;
+use crate::utils::{now_millis, read_version_2_fs};
+use data_error::{ArklibError, Result, ResultExt};
+pub use fs_atomic_versions::durability::Durability;
+use fs_atomic_versions::durability::{finish_write, sync_dir};
 
 /*
 Note on `FileStorage` Versioning:
@@ -21,8 +32,24 @@ In version 2, `FileStorage` stored data in a plaintext format.
 Starting from version 3, data is stored in JSON format.
 
 For backward compatibility, we provide a helper function `read_version_2_fs` to read version 2 format.
+Later versions are upgraded by the migrations registered in `migration::MIGRATIONS`.
+Older files are written back in the current version by `read_fs`, keeping a
+backup, and files of newer versions are rejected.
+
+Version 3 storages can also be written in the CBOR format, behind the
+`binary-format` feature. The version header is kept, and the format is
+detected when reading.
+
+The optional `generation` of version 3 is only written by storages with a
+journal, see `FileStorage::with_journal`. Other readers can ignore it.
+
+The optional `tombstones` of version 3 record the time keys were removed,
+see `FileStorage::tombstones`. Other readers can ignore them.
+
+Storages are written with a checksum footer, see the `checksum` module.
+It is the last field of JSON documents, so other readers can ignore it too.
 */
-const STORAGE_VERSION: i32 = 3;
+pub(crate) const STORAGE_VERSION: i32 = 3;
 
 /// Represents a file storage system that persists data to disk.
 pub struct FileStorage<K, V>
@@ -40,6 +67,155 @@ where
     /// `modified` only when data is written or read from disk.
     written_to_disk: SystemTime,
     data: FileStorageData<K, V>,
+    /// Entries as they were last read or written, the base of the
+    /// three-way merge of [`FileStorage::sync_with_report`]
+    base: BTreeMap<K, V>,
+    /// How far `write_fs` goes before returning
+    durability: Durability,
+    /// Format of the file written by `write_fs`
+    format: StorageFormat,
+    /// Key encrypting the file, see [`FileStorage::new_encrypted`]
+    key: Option<EncryptionKey>,
+    /// Keys by projected value, see [`FileStorage::enable_value_index`]
+    value_index: ValueIndex<K, V>,
+    /// Keys set or removed since the last read or write
+    changed: BTreeSet<K>,
+    /// Number of changes in the journal, see [`FileStorage::with_journal`]
+    journal_len: usize,
+    compact_after: Option<usize>,
+    /// How long to wait for other processes, see [`FileStorage::with_locking`]
+    lock_timeout: Option<Duration>,
+    /// Lock taken by [`FileStorage::try_lock_exclusive`]
+    held: Option<Lock>,
+}
+
+/// Serialization format of a [`FileStorage`] file
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum StorageFormat {
+    /// Pretty-printed JSON, readable and editable by hand
+    #[default]
+    Json,
+    /// CBOR, smaller and faster to parse for big storages
+    #[cfg(feature = "binary-format")]
+    Cbor,
+}
+
+impl StorageFormat {
+    /// Format of the content of a storage file
+    fn detect(content: &[u8]) -> Option<Self> {
+        // JSON documents start with `{`, possibly after whitespace,
+        // while CBOR maps start with a byte of major type 5
+        match content.first() {
+            #[cfg(feature = "binary-format")]
+            Some(0xa0..=0xbf) => Some(StorageFormat::Cbor),
+            #[cfg(not(feature = "binary-format"))]
+            Some(0xa0..=0xbf) => None,
+            _ => Some(StorageFormat::Json),
+        }
+    }
+
+    /// Parse the content of a storage file of this format,
+    /// telling malformed content from content of another type
+    fn decode<T: serde::de::DeserializeOwned>(
+        self,
+        label: &str,
+        path: &Path,
+        content: &[u8],
+    ) -> Result<T> {
+        let invalid = |err: &dyn std::fmt::Display| {
+            ArklibError::Storage(
+                label.to_owned(),
+                format!("{} (path: {})", err, path.display()),
+            )
+        };
+        match self {
+            StorageFormat::Json => {
+                serde_json::from_slice(content).map_err(|err| {
+                    if err.is_eof() {
+                        checksum::corrupted(label, path, "it is truncated")
+                    } else if err.is_syntax() {
+                        checksum::corrupted(label, path, &err.to_string())
+                    } else {
+                        invalid(&err)
+                    }
+                })
+            }
+            #[cfg(feature = "binary-format")]
+            StorageFormat::Cbor => {
+                ciborium::from_reader(content).map_err(|err| match err {
+                    ciborium::de::Error::Io(_) => {
+                        checksum::corrupted(label, path, "it is truncated")
+                    }
+                    ciborium::de::Error::Syntax(offset) => checksum::corrupted(
+                        label,
+                        path,
+                        &format!("it is malformed at byte {}", offset),
+                    ),
+                    err => invalid(&err),
+                })
+            }
+        }
+    }
+}
+
+/// Fields of a storage file read before its version is known
+#[derive(Deserialize)]
+struct StorageHeader {
+    version: i32,
+}
+
+type Projection<V> = Box<dyn Fn(&V) -> Option<String> + Send + Sync>;
+
+/// In-memory reverse index of a [`FileStorage`],
+/// empty until a projection is set
+struct ValueIndex<K, V> {
+    projection: Option<Projection<V>>,
+    keys: BTreeMap<String, BTreeSet<K>>,
+    // returned for values without keys
+    empty: BTreeSet<K>,
+}
+
+impl<K: Ord + Clone, V> ValueIndex<K, V> {
+    fn new() -> Self {
+        Self {
+            projection: None,
+            keys: BTreeMap::new(),
+            empty: BTreeSet::new(),
+        }
+    }
+
+    fn project(&self, value: &V) -> Option<String> {
+        self.projection
+            .as_ref()
+            .and_then(|projection| projection(value))
+    }
+
+    fn insert(&mut self, key: &K, value: &V) {
+        if let Some(projected) = self.project(value) {
+            self.keys
+                .entry(projected)
+                .or_default()
+                .insert(key.clone());
+        }
+    }
+
+    fn remove(&mut self, key: &K, value: &V) {
+        if let Some(projected) = self.project(value) {
+            if let Some(keys) = self.keys.get_mut(&projected) {
+                keys.remove(key);
+                if keys.is_empty() {
+                    self.keys.remove(&projected);
+                }
+            }
+        }
+    }
+
+    fn rebuild(&mut self, entries: &BTreeMap<K, V>) {
+        self.keys.clear();
+        for (key, value) in entries {
+            self.insert(key, value);
+        }
+    }
 }
 
 /// A struct that represents the data stored in a [`FileStorage`] instance.
@@ -52,7 +228,18 @@ where
     K: Ord,
 {
     version: i32,
+    /// Incremented by every full write of a storage with a journal
+    #[serde(default, skip_serializing_if = "is_zero")]
+    generation: u64,
     entries: BTreeMap<K, V>,
+    /// Time of the removal of the keys removed since they were last
+    /// set, in milliseconds since the Unix epoch
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    tombstones: BTreeMap<K, u64>,
+}
+
+fn is_zero(generation: &u64) -> bool {
+    *generation == 0
 }
 
 impl<K, V> AsRef<BTreeMap<K, V>> for FileStorageData<K, V>
@@ -83,6 +270,28 @@ where
     /// Note: if the file storage already exists, the data will be read from the file
     /// without overwriting it.
     pub fn new(label: String, path: &Path) -> Result<Self> {
+        Self::open(label, path, None)
+    }
+
+    /// Create a new file storage encrypted with `key`, see [`FileStorage::new`]
+    ///
+    /// A file which is not encrypted yet is read as is and encrypted by
+    /// the next write. Encrypted storages are always written entirely,
+    /// their changes are not appended to a journal.
+    #[cfg(feature = "encryption")]
+    pub fn new_encrypted(
+        label: String,
+        path: &Path,
+        key: EncryptionKey,
+    ) -> Result<Self> {
+        Self::open(label, path, Some(key))
+    }
+
+    fn open(
+        label: String,
+        path: &Path,
+        key: Option<EncryptionKey>,
+    ) -> Result<Self> {
         let time = SystemTime::now();
         let mut storage = Self {
             label,
@@ -91,8 +300,20 @@ where
             written_to_disk: time,
             data: FileStorageData {
                 version: STORAGE_VERSION,
+                generation: 0,
                 entries: BTreeMap::new(),
+                tombstones: BTreeMap::new(),
             },
+            base: BTreeMap::new(),
+            durability: Durability::default(),
+            format: StorageFormat::default(),
+            key,
+            value_index: ValueIndex::new(),
+            changed: BTreeSet::new(),
+            journal_len: 0,
+            compact_after: None,
+            lock_timeout: None,
+            held: None,
         };
 
         if Path::exists(path) {
@@ -102,34 +323,369 @@ where
         Ok(storage)
     }
 
+    /// Write the storage with the given durability.
+    ///
+    /// Storages of user data keep the default [`Durability::Sync`],
+    /// storages which can be regenerated, like caches, can use
+    /// [`Durability::Fast`].
+    pub fn with_durability(mut self, durability: Durability) -> Self {
+        self.durability = durability;
+        self
+    }
+
+    pub fn durability(&self) -> Durability {
+        self.durability
+    }
+
+    /// Write the storage in the given format.
+    ///
+    /// Files in any format are read, only the next writes are affected.
+    pub fn with_format(mut self, format: StorageFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    pub fn format(&self) -> StorageFormat {
+        self.format
+    }
+
+    /// Lock the storage file while reading or writing it, so that
+    /// other processes locking it as well don't interleave their writes.
+    ///
+    /// The locks are advisory, waiting at most `timeout` for other
+    /// processes to release the file before the operation fails.
+    pub fn with_locking(mut self, timeout: Duration) -> Self {
+        self.lock_timeout = Some(timeout);
+        self
+    }
+
+    /// Hold an exclusive lock of the storage file until [`FileStorage::unlock`]
+    /// is called or the storage is dropped, e.g. for a long editing session.
+    ///
+    /// Returns `false` if another process holds a lock of the file.
+    /// The storage can still be read and written while the lock is held.
+    pub fn try_lock_exclusive(&mut self) -> Result<bool> {
+        if self.held.is_none() {
+            self.held = lock::try_acquire(&self.label, &self.path, true)?;
+        }
+        Ok(self.held.is_some())
+    }
+
+    /// Release the lock taken by [`FileStorage::try_lock_exclusive`]
+    pub fn unlock(&mut self) {
+        self.held = None;
+    }
+
+    /// Lock the file for a single operation, unless locking is disabled
+    /// or the lock is already held
+    fn lock(&self, exclusive: bool) -> Result<Option<Lock>> {
+        match self.lock_wait() {
+            Some(timeout) => {
+                lock::acquire(&self.label, &self.path, exclusive, timeout)
+                    .map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    pub(crate) fn lock_wait(&self) -> Option<Duration> {
+        match self.held {
+            Some(_) => None,
+            None => self.lock_timeout,
+        }
+    }
+
+    /// Keys removed since they were last set, along with the time of
+    /// their removal in milliseconds since the Unix epoch.
+    ///
+    /// Tombstones are written with the entries, so that removals are not
+    /// undone by merging the entries of another device which still has
+    /// them, see [`BaseStorage::merge_from`] and
+    /// [`FileStorage::merge_tombstones`].
+    pub fn tombstones(&self) -> &BTreeMap<K, u64> {
+        &self.data.tombstones
+    }
+
+    /// Apply the removals of another storage, e.g. the same storage on
+    /// another device, before merging its entries.
+    ///
+    /// Values are not timestamped, so a key removed by the other storage
+    /// is removed even if it was set again here meanwhile, unless it was
+    /// removed here later.
+    pub fn merge_tombstones(
+        &mut self,
+        tombstones: &BTreeMap<K, u64>,
+    ) -> Result<()> {
+        for (key, removed) in tombstones {
+            match self.data.tombstones.get(key) {
+                Some(local) if local >= removed => continue,
+                _ => {}
+            }
+            if self.data.entries.contains_key(key) {
+                self.remove(key)?;
+            }
+            self.data.tombstones.insert(key.clone(), *removed);
+        }
+        Ok(())
+    }
+
+    /// Forget the tombstones of the keys removed before `before`,
+    /// in milliseconds since the Unix epoch, returning how many
+    /// were forgotten
+    ///
+    /// Removals older than the last merge with every other device
+    /// don't need their tombstones anymore.
+    pub fn prune_tombstones(&mut self, before: u64) -> usize {
+        let count = self.data.tombstones.len();
+        self.data
+            .tombstones
+            .retain(|_, removed| *removed >= before);
+        let pruned = count - self.data.tombstones.len();
+        if pruned > 0 {
+            self.modified = SystemTime::now();
+        }
+        pruned
+    }
+
+    /// Sync the in-memory storage with the storage on disk, see
+    /// [`BaseStorage::sync`], reporting how the keys changed on both
+    /// sides were resolved.
+    ///
+    /// If both sides changed, they are merged with the entries as they
+    /// were last read or written, so that a key removed on one side
+    /// isn't brought back by the other, see [`merge::merge_three_way`].
+    /// The report is empty if only one side changed.
+    pub fn sync_with_report(&mut self) -> Result<SyncReport<K>> {
+        match self.sync_status()? {
+            SyncStatus::InSync => {}
+            SyncStatus::MappingStale => {
+                self.read_fs()?;
+            }
+            SyncStatus::StorageStale => self.write_fs()?,
+            SyncStatus::Diverge => {
+                let (remote, journaled) = {
+                    let _lock = self.lock(false)?;
+                    self.load_fs_data()?
+                };
+                let modified = file_modified(&self.label, &self.path)?;
+                let (merged, report) = merge::merge_three_way(
+                    &self.base,
+                    &self.data.entries,
+                    &remote.entries,
+                );
+
+                // start over from the file, so that only the keys
+                // which differ from it are written
+                let tombstones = std::mem::take(&mut self.data.tombstones);
+                self.loaded(remote, journaled, modified);
+                self.merge_tombstones(&tombstones)?;
+                if self.data.version != STORAGE_VERSION {
+                    self.migrate()?;
+                }
+                let removed: Vec<K> = self
+                    .data
+                    .entries
+                    .keys()
+                    .filter(|key| !merged.contains_key(key))
+                    .cloned()
+                    .collect();
+                for key in removed.iter() {
+                    self.remove(key)?;
+                }
+                for (key, value) in merged {
+                    if !merge::same(self.data.entries.get(&key), Some(&value)) {
+                        self.set(key, value);
+                    }
+                }
+                let entries = &self.data.entries;
+                self.data
+                    .tombstones
+                    .retain(|key, _| !entries.contains_key(key));
+                self.write_fs()?;
+
+                log::info!(
+                    "{} merged {} keys from disk, {} conflicts",
+                    self.label,
+                    report.taken.len(),
+                    report.combined.len() + report.kept.len()
+                );
+                return Ok(report);
+            }
+        }
+        Ok(SyncReport::default())
+    }
+
+    /// Append the changes to a journal next to the storage file
+    /// instead of rewriting the whole file on every `write_fs`.
+    ///
+    /// The journal is compacted into the file once it would exceed
+    /// `compact_after` changes. A journal is replayed when the storage
+    /// is read, whether this mode is enabled or not.
+    pub fn with_journal(mut self, compact_after: usize) -> Self {
+        self.compact_after = Some(compact_after);
+        self
+    }
+
+    /// Maintain an in-memory index of the keys by projected value,
+    /// e.g. `|tags| tags.contains("favorite").then(|| "favorite".into())`,
+    /// so that [`FileStorage::find_by_value`] doesn't scan the storage.
+    ///
+    /// Values projected to `None` are not indexed.
+    /// The index is rebuilt from the current entries and kept up to date
+    /// by every mutation, the file format is not affected.
+    pub fn enable_value_index(
+        &mut self,
+        projection: impl Fn(&V) -> Option<String> + Send + Sync + 'static,
+    ) {
+        self.value_index.projection = Some(Box::new(projection));
+        self.value_index.rebuild(&self.data.entries);
+    }
+
+    /// Keys whose value is projected to `projected`
+    ///
+    /// Always empty if the value index is not enabled.
+    pub fn find_by_value(&self, projected: &str) -> &BTreeSet<K> {
+        if self.value_index.projection.is_none() {
+            log::warn!("{} has no value index", self.label);
+        }
+        self.value_index
+            .keys
+            .get(projected)
+            .unwrap_or(&self.value_index.empty)
+    }
+
+    /// Stage changes in a [`Transaction`] and write them to disk at once.
+    ///
+    /// The file is replaced by renaming a temporary file over it,
+    /// so a crash leaves either the previous or the new content.
+    /// Nothing is applied if `stage` fails or the file can't be written.
+    /// Other unsaved changes of the storage are written as well.
+    pub fn transaction<T>(
+        &mut self,
+        stage: impl FnOnce(&mut Transaction<K, V>) -> Result<T>,
+    ) -> Result<T> {
+        let mut transaction = Transaction {
+            label: &self.label,
+            entries: &self.data.entries,
+            staged: BTreeMap::new(),
+        };
+        let result = stage(&mut transaction)?;
+        let staged = transaction.staged;
+        let _lock = self.lock(true)?;
+
+        let mut previous = Vec::with_capacity(staged.len());
+        for (key, value) in staged {
+            let (old, tombstone) = match value {
+                Some(value) => (
+                    self.data.entries.insert(key.clone(), value),
+                    self.data.tombstones.remove(&key),
+                ),
+                None => (
+                    self.data.entries.remove(&key),
+                    self.data
+                        .tombstones
+                        .insert(key.clone(), now_millis()),
+                ),
+            };
+            previous.push((key, old, tombstone));
+        }
+
+        match self.write_entirely(true) {
+            Ok(()) => {
+                for (key, old, _) in previous.iter() {
+                    if let Some(old) = old {
+                        self.value_index.remove(key, old);
+                    }
+                    if let Some(new) = self.data.entries.get(key) {
+                        self.value_index.insert(key, new);
+                    }
+                }
+                Ok(result)
+            }
+            Err(err) => {
+                for (key, old, tombstone) in previous {
+                    match tombstone {
+                        Some(tombstone) => self
+                            .data
+                            .tombstones
+                            .insert(key.clone(), tombstone),
+                        None => self.data.tombstones.remove(&key),
+                    };
+                    match old {
+                        Some(old) => self.data.entries.insert(key, old),
+                        None => self.data.entries.remove(&key),
+                    };
+                }
+                Err(err)
+            }
+        }
+    }
+
     /// Load mapping from file
-    fn load_fs_data(&self) -> Result<FileStorageData<K, V>> {
-        if !self.path.exists() {
+    fn load_fs_data(&self) -> Result<(FileStorageData<K, V>, usize)> {
+        Self::load_data(&self.label, &self.path, self.key.as_ref())
+    }
+
+    /// Load mapping from the file at `path` and its journal, also returns
+    /// the number of changes replayed from the journal.
+    /// The label is used for errors
+    pub(crate) fn load_data(
+        label: &str,
+        path: &Path,
+        key: Option<&EncryptionKey>,
+    ) -> Result<(FileStorageData<K, V>, usize)> {
+        if !path.exists() {
             return Err(ArklibError::Storage(
-                self.label.clone(),
-                "File does not exist".to_owned(),
+                label.to_owned(),
+                format!("File does not exist: {}", path.display()),
             ));
         }
 
         // First check if the file starts with "version: 2"
-        let file_content = std::fs::read_to_string(&self.path)?;
-        if file_content.starts_with("version: 2") {
+        let mut file_content =
+            fs::read(path).with_path(path).with_label(label)?;
+        if encryption::is_encrypted(&file_content) {
+            file_content = match key {
+                #[cfg(feature = "encryption")]
+                Some(key) => encryption::decrypt(label, key, &file_content)?,
+                _ => {
+                    return Err(ArklibError::Storage(
+                        label.to_owned(),
+                        format!(
+                            "Storage is encrypted, but no key was given \
+                             (path: {})",
+                            path.display()
+                        ),
+                    ))
+                }
+            };
+        }
+        if file_content.starts_with(b"version: 2") {
             // Attempt to parse the file using the legacy version 2 storage format of FileStorage.
-            match read_version_2_fs(&self.path) {
+            match read_version_2_fs(path) {
                 Ok(data) => {
                     log::info!(
                         "Version 2 storage format detected for {}",
-                        self.label
+                        label
                     );
-                    let data = FileStorageData {
+                    let mut data = FileStorageData {
                         version: 2,
+                        generation: 0,
                         entries: data,
+                        tombstones: BTreeMap::new(),
                     };
-                    return Ok(data);
+                    let journaled = journal::replay(
+                        label,
+                        path,
+                        0,
+                        &mut data.entries,
+                        &mut data.tombstones,
+                    )?;
+                    return Ok((data, journaled));
                 }
                 Err(_) => {
                     return Err(ArklibError::Storage(
-                        self.label.clone(),
+                        label.to_owned(),
                         "Storage seems to be version 2, but failed to parse"
                             .to_owned(),
                     ));
@@ -137,23 +693,251 @@ where
             };
         }
 
-        let file = fs::File::open(&self.path)?;
-        let data: FileStorageData<K, V> = serde_json::from_reader(file)
-            .map_err(|err| {
-                ArklibError::Storage(self.label.clone(), err.to_string())
-            })?;
-        let version = data.version;
-        if version != STORAGE_VERSION {
-            return Err(ArklibError::Storage(
-                self.label.clone(),
-                format!(
-                    "Storage version mismatch: expected {}, got {}",
-                    STORAGE_VERSION, version
-                ),
-            ));
+        let invalid = |err: &dyn std::fmt::Display| {
+            ArklibError::Storage(
+                label.to_owned(),
+                format!("{} (path: {})", err, path.display()),
+            )
+        };
+        let format = match StorageFormat::detect(&file_content) {
+            Some(format) => format,
+            None => {
+                return Err(invalid(
+                    &"Storage seems to be binary, \
+                      but the binary-format feature is disabled",
+                ))
+            }
+        };
+        let (document, written_entries) =
+            checksum::verify(label, path, format, &file_content)?;
+        let version = format
+            .decode::<StorageHeader>(label, path, document)?
+            .version;
+        if version > STORAGE_VERSION {
+            return Err(ArklibError::UnsupportedVersion(
+                label.to_owned(),
+                version,
+                STORAGE_VERSION,
+            ))
+            .with_path(path);
+        }
+        let mut data: FileStorageData<K, V> = if version == STORAGE_VERSION {
+            format.decode(label, path, document)?
+        } else {
+            let old: serde_json::Value =
+                format.decode(label, path, document)?;
+            let upgraded = migration::upgrade(
+                label,
+                old,
+                version,
+                STORAGE_VERSION,
+                migration::MIGRATIONS,
+            )?;
+            let mut data: FileStorageData<K, V> =
+                serde_json::from_value(upgraded)
+                    .map_err(|err| invalid(&err))?;
+            // the file is migrated by `read_fs`
+            data.version = version;
+            data
+        };
+        if let Some(written_entries) = written_entries {
+            if written_entries != data.entries.len() {
+                return Err(checksum::corrupted(
+                    label,
+                    path,
+                    &format!(
+                        "{} entries were written, but {} were read",
+                        written_entries,
+                        data.entries.len()
+                    ),
+                ));
+            }
+        }
+
+        let journaled = journal::replay(
+            label,
+            path,
+            data.generation,
+            &mut data.entries,
+            &mut data.tombstones,
+        )?;
+        Ok((data, journaled))
+    }
+
+    #[cfg(any(feature = "tokio", feature = "watch"))]
+    pub(crate) fn label(&self) -> &str {
+        &self.label
+    }
+
+    #[cfg(any(feature = "tokio", feature = "watch"))]
+    pub(crate) fn path(&self) -> &Path {
+        &self.path
+    }
+
+    #[cfg(feature = "tokio")]
+    pub(crate) fn key(&self) -> Option<&EncryptionKey> {
+        self.key.as_ref()
+    }
+
+    /// Replace the mapping with data read from disk,
+    /// `modified` being the timestamp of the file
+    pub(crate) fn loaded(
+        &mut self,
+        data: FileStorageData<K, V>,
+        journaled: usize,
+        modified: SystemTime,
+    ) -> &BTreeMap<K, V> {
+        self.changed.clear();
+        self.journal_len = journaled;
+        self.modified = modified;
+        self.written_to_disk = modified;
+        self.data = data;
+        self.base = self.data.entries.clone();
+        self.value_index.rebuild(&self.data.entries);
+        &self.data.entries
+    }
+
+    /// Content of the file as written by `write_fs`
+    pub(crate) fn serialized(&self) -> Result<Vec<u8>> {
+        let content = self.encoded()?;
+        #[cfg(feature = "encryption")]
+        if let Some(key) = &self.key {
+            return encryption::encrypt(&self.label, key, &content);
+        }
+        Ok(content)
+    }
+
+    /// The mapping in the format of the storage
+    fn encoded(&self) -> Result<Vec<u8>> {
+        let content = match self.format {
+            StorageFormat::Json => serde_json::to_vec_pretty(&self.data)
+                .with_path(&self.path)
+                .with_label(&self.label)?,
+            #[cfg(feature = "binary-format")]
+            StorageFormat::Cbor => {
+                let mut content = Vec::new();
+                ciborium::into_writer(&self.data, &mut content).map_err(
+                    |err| {
+                        ArklibError::Storage(
+                            self.label.clone(),
+                            format!("{} (path: {})", err, self.path.display()),
+                        )
+                    },
+                )?;
+                content
+            }
+        };
+        Ok(checksum::append(
+            self.format,
+            content,
+            self.data.entries.len(),
+        ))
+    }
+
+    /// Content of the file for a write of the whole mapping, along with
+    /// whether a journal has to be removed once it is written
+    pub(crate) fn full_content(&mut self) -> Result<(Vec<u8>, bool)> {
+        let journaled = self.compact_after.is_some()
+            || journal::journal_path(&self.path).exists();
+        if journaled {
+            // a journal left behind must not be replayed over the new file
+            self.data.generation += 1;
+        }
+        let content = self.serialized();
+        if content.is_err() && journaled {
+            self.data.generation -= 1;
+        }
+        Ok((content?, journaled))
+    }
+
+    /// Apply the outcome of writing the content from `full_content`
+    pub(crate) fn written_entirely(
+        &mut self,
+        written: Result<SystemTime>,
+        journaled: bool,
+    ) -> Result<()> {
+        match written {
+            Ok(timestamp) => {
+                self.journal_len = 0;
+                self.written(timestamp);
+                Ok(())
+            }
+            Err(err) => {
+                if journaled {
+                    self.data.generation -= 1;
+                }
+                Err(err)
+            }
+        }
+    }
+
+    /// Write the whole mapping, replacing the file
+    /// by a temporary one if `atomic` is set
+    fn write_entirely(&mut self, atomic: bool) -> Result<()> {
+        let (content, journaled) = self.full_content()?;
+        let written = write_full(
+            &self.label,
+            &self.path,
+            &content,
+            self.durability,
+            journaled,
+            atomic,
+        );
+        self.written_entirely(written, journaled)
+    }
+
+    /// Write a storage read from a file of an older version
+    /// in the current version, keeping a backup of the file
+    fn migrate(&mut self) -> Result<()> {
+        let _lock = self.lock(true)?;
+        let version = self.data.version;
+        migration::backup(&self.label, &self.path, version)?;
+        self.data.version = STORAGE_VERSION;
+        if let Err(err) = self.write_entirely(true) {
+            self.data.version = version;
+            return Err(err);
         }
+        log::info!(
+            "{} migrated from version {} to {}",
+            self.label,
+            version,
+            STORAGE_VERSION
+        );
+        Ok(())
+    }
 
-        Ok(data)
+    /// Append the changed entries to the journal
+    fn write_journal(&mut self) -> Result<()> {
+        let timestamp = journal::append(
+            &self.label,
+            &self.path,
+            self.data.generation,
+            self.journal_len == 0,
+            self.changed.iter().map(|key| {
+                (
+                    key,
+                    self.data.entries.get(key),
+                    self.data.tombstones.get(key).copied(),
+                )
+            }),
+            self.durability,
+        )?;
+        self.journal_len += self.changed.len();
+        self.written(timestamp);
+        Ok(())
+    }
+
+    /// Mark the mapping as written to disk at `timestamp`
+    pub(crate) fn written(&mut self, timestamp: SystemTime) {
+        self.changed.clear();
+        self.base = self.data.entries.clone();
+        self.modified = timestamp;
+        self.written_to_disk = timestamp;
+        log::info!(
+            "{} {} entries have been written",
+            self.label,
+            self.data.entries.len()
+        );
     }
 }
 
@@ -172,24 +956,41 @@ where
 {
     /// Set a key-value pair in the internal mapping
     fn set(&mut self, key: K, value: V) {
+        if let Some(previous) = self.data.entries.get(&key) {
+            self.value_index.remove(&key, previous);
+        }
+        self.value_index.insert(&key, &value);
+        self.changed.insert(key.clone());
+        self.data.tombstones.remove(&key);
         self.data.entries.insert(key, value);
         self.modified = std::time::SystemTime::now();
     }
 
-    /// Remove an entry from the internal mapping given a key
+    /// Remove an entry from the internal mapping given a key,
+    /// leaving a tombstone, see [`FileStorage::tombstones`]
     fn remove(&mut self, id: &K) -> Result<()> {
-        self.data.entries.remove(id).ok_or_else(|| {
+        let value = self.data.entries.remove(id).ok_or_else(|| {
             ArklibError::Storage(self.label.clone(), "Key not found".to_owned())
         })?;
+        self.value_index.remove(id, &value);
+        self.changed.insert(id.clone());
+        self.data
+            .tombstones
+            .insert(id.clone(), now_millis());
         self.modified = std::time::SystemTime::now();
         Ok(())
     }
 
+    /// Keys set or removed since the last read or write
+    fn dirty_keys(&self) -> &BTreeSet<K> {
+        &self.changed
+    }
+
     /// Compare the timestamp of the storage file
     /// with the timestamp of the in-memory storage and the last written
     /// to time to determine if either of the two requires syncing.
     fn sync_status(&self) -> Result<SyncStatus> {
-        let file_updated = fs::metadata(&self.path)?.modified()?;
+        let file_updated = file_modified(&self.label, &self.path)?;
 
         // Determine the synchronization status based on the modification times
         // Conditions:
@@ -221,72 +1022,110 @@ where
             SyncStatus::InSync => Ok(()),
             SyncStatus::MappingStale => self.read_fs().map(|_| ()),
             SyncStatus::StorageStale => self.write_fs().map(|_| ()),
-            SyncStatus::Diverge => {
-                let data = self.load_fs_data()?;
-                self.merge_from(&data)?;
-                self.write_fs()?;
-                Ok(())
-            }
+            SyncStatus::Diverge => self.sync_with_report().map(|_| ()),
         }
     }
 
     /// Read the data from file
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "read_fs",
+            skip_all,
+            fields(
+                label = %self.label,
+                path = %self.path.display(),
+                entries = tracing::field::Empty,
+                elapsed_ms = tracing::field::Empty,
+            )
+        )
+    )]
     fn read_fs(&mut self) -> Result<&BTreeMap<K, V>> {
-        let data = self.load_fs_data()?;
+        #[cfg(feature = "tracing")]
+        let start = Instant::now();
 
-        // Update file storage with loaded data
-        self.modified = fs::metadata(&self.path)?.modified()?;
-        self.written_to_disk = self.modified;
-        self.data = data;
+        let lock = self.lock(false)?;
+        let (data, journaled) = self.load_fs_data()?;
+        let modified = file_modified(&self.label, &self.path)?;
+        self.loaded(data, journaled, modified);
+        drop(lock);
+
+        if self.data.version != STORAGE_VERSION {
+            self.migrate()?;
+        }
+
+        #[cfg(feature = "tracing")]
+        tracing::Span::current()
+            .record("entries", self.data.entries.len())
+            .record("elapsed_ms", start.elapsed().as_millis() as u64);
 
         Ok(&self.data.entries)
     }
 
-    /// Write the data to file
+    /// Write the data to file, or append the changes to the journal
+    /// if it is enabled
     ///
     /// Update the modified timestamp in file metadata to avoid OS timing issues
     /// https://github.com/ARK-Builders/ark-rust/pull/63#issuecomment-2163882227
-    fn write_fs(&mut self) -> Result<()> {
-        let parent_dir = self.path.parent().ok_or_else(|| {
-            ArklibError::Storage(
-                self.label.clone(),
-                "Failed to get parent directory".to_owned(),
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "write_fs",
+            skip_all,
+            fields(
+                label = %self.label,
+                path = %self.path.display(),
+                entries = self.data.entries.len(),
+                durability = ?self.durability,
+                elapsed_ms = tracing::field::Empty,
             )
-        })?;
-        fs::create_dir_all(parent_dir)?;
-        let mut file = File::create(&self.path)?;
-        file.write_all(serde_json::to_string_pretty(&self.data)?.as_bytes())?;
-        file.flush()?;
+        )
+    )]
+    fn write_fs(&mut self) -> Result<()> {
+        #[cfg(feature = "tracing")]
+        let start = Instant::now();
 
-        let new_timestamp = SystemTime::now();
-        file.set_modified(new_timestamp)?;
-        file.sync_all()?;
+        let _lock = self.lock(true)?;
+        let journal = match self.compact_after {
+            Some(compact_after) if self.key.is_none() => {
+                self.path.exists()
+                    && self.journal_len + self.changed.len() <= compact_after
+            }
+            _ => false,
+        };
+        if journal {
+            self.write_journal()?;
+        } else {
+            self.write_entirely(false)?;
+        }
 
-        self.modified = new_timestamp;
-        self.written_to_disk = new_timestamp;
+        #[cfg(feature = "tracing")]
+        tracing::Span::current()
+            .record("elapsed_ms", start.elapsed().as_millis() as u64);
 
-        log::info!(
-            "{} {} entries have been written",
-            self.label,
-            self.data.entries.len()
-        );
         Ok(())
     }
 
     /// Erase the file from disk
     fn erase(&self) -> Result<()> {
-        fs::remove_file(&self.path).map_err(|err| {
-            ArklibError::Storage(self.label.clone(), err.to_string())
-        })
+        fs::remove_file(&self.path)
+            .with_path(&self.path)
+            .with_label(&self.label)
     }
 
     /// Merge the data from another storage instance into this storage instance
+    ///
+    /// Keys removed from this storage are not brought back,
+    /// see [`FileStorage::tombstones`].
     fn merge_from(&mut self, other: impl AsRef<BTreeMap<K, V>>) -> Result<()>
     where
         V: Monoid<V>,
     {
         let other_entries = other.as_ref();
         for (key, value) in other_entries {
+            if self.data.tombstones.contains_key(key) {
+                continue;
+            }
             if let Some(existing_value) = self.data.entries.get(key) {
                 let resolved_value = V::combine(existing_value, value);
                 self.set(key.clone(), resolved_value);
@@ -297,6 +1136,28 @@ where
         self.modified = std::time::SystemTime::now();
         Ok(())
     }
+
+    /// Copy the file and its journal into a snapshot
+    fn snapshot(&self, dest: &Path) -> Result<()> {
+        let _lock = self.lock(false)?;
+        snapshot::create(&self.label, dest, self.data.version)?;
+        snapshot::copy_file(&self.label, &self.path, &snapshot::data_path(dest))
+    }
+
+    fn restore(&mut self, src: &Path) -> Result<()> {
+        snapshot::read_manifest(&self.label, src, STORAGE_VERSION)?;
+        {
+            let _lock = self.lock(true)?;
+            snapshot::copy_file(
+                &self.label,
+                &snapshot::data_path(src),
+                &self.path,
+            )?;
+        }
+        self.read_fs()?;
+        log::info!("{} restored from {}", self.label, src.display());
+        Ok(())
+    }
 }
 
 impl<K, V> AsRef<BTreeMap<K, V>> for FileStorage<K, V>
@@ -308,15 +1169,165 @@ where
     }
 }
 
+/// Changes staged by [`FileStorage::transaction`]
+pub struct Transaction<'a, K, V> {
+    label: &'a str,
+    entries: &'a BTreeMap<K, V>,
+    /// `None` for the removed keys
+    staged: BTreeMap<K, Option<V>>,
+}
+
+impl<K: Ord, V> Transaction<'_, K, V> {
+    /// Value of `key`, including the staged changes
+    pub fn get(&self, key: &K) -> Option<&V> {
+        match self.staged.get(key) {
+            Some(staged) => staged.as_ref(),
+            None => self.entries.get(key),
+        }
+    }
+
+    pub fn set(&mut self, key: K, value: V) {
+        self.staged.insert(key, Some(value));
+    }
+
+    pub fn remove(&mut self, key: K) -> Result<()> {
+        if self.get(&key).is_none() {
+            return Err(ArklibError::Storage(
+                self.label.to_owned(),
+                "Key not found".to_owned(),
+            ));
+        }
+        self.staged.insert(key, None);
+        Ok(())
+    }
+}
+
+/// Last modification time of the file at `path` or of its journal
+pub(crate) fn file_modified(label: &str, path: &Path) -> Result<SystemTime> {
+    let modified = fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .with_path(path)
+        .with_label(label)?;
+    Ok(match journal::modified(path) {
+        Some(journal) => modified.max(journal),
+        None => modified,
+    })
+}
+
+/// Write the whole mapping serialized in `content` to the file at `path`,
+/// removing its journal if `journaled` is set
+pub(crate) fn write_full(
+    label: &str,
+    path: &Path,
+    content: &[u8],
+    durability: Durability,
+    journaled: bool,
+    atomic: bool,
+) -> Result<SystemTime> {
+    let timestamp = if atomic {
+        replace_file(label, path, content, durability)?
+    } else {
+        write_file(label, path, content, durability)?
+    };
+    if journaled {
+        // a journal of an older generation is ignored anyway
+        if let Err(err) = journal::remove(label, path) {
+            log::warn!("{} failed to remove its journal: {}", label, err);
+        }
+    }
+    Ok(timestamp)
+}
+
+/// Write `content` to the file at `path`, creating its parent folders,
+/// and return the modification time set on the file
+///
+/// The modified timestamp is set explicitly to avoid OS timing issues
+/// https://github.com/ARK-Builders/ark-rust/pull/63#issuecomment-2163882227
+pub(crate) fn write_file(
+    label: &str,
+    path: &Path,
+    content: &[u8],
+    durability: Durability,
+) -> Result<SystemTime> {
+    let parent_dir = path.parent().ok_or_else(|| {
+        ArklibError::Storage(
+            label.to_owned(),
+            "Failed to get parent directory".to_owned(),
+        )
+    })?;
+    fs::create_dir_all(parent_dir)
+        .with_path(parent_dir)
+        .with_label(label)?;
+    let created = !path.exists();
+    let mut file = File::create(path)
+        .with_path(path)
+        .with_label(label)?;
+    file.write_all(content)
+        .with_path(path)
+        .with_label(label)?;
+
+    let timestamp = SystemTime::now();
+    file.set_modified(timestamp)
+        .and_then(|_| finish_write(&mut file, durability))
+        .with_path(path)
+        .with_label(label)?;
+    // a new file is lost on power loss until its directory is synced
+    if created && durability == Durability::Sync {
+        sync_dir(parent_dir)
+            .with_path(parent_dir)
+            .with_label(label)?;
+    }
+    Ok(timestamp)
+}
+
+/// Same as [`write_file`], but the content is written to a temporary file
+/// which is then renamed over `path`
+pub(crate) fn replace_file(
+    label: &str,
+    path: &Path,
+    content: &[u8],
+    durability: Durability,
+) -> Result<SystemTime> {
+    let file_name = path.file_name().ok_or_else(|| {
+        ArklibError::Storage(
+            label.to_owned(),
+            format!("Invalid storage path: {}", path.display()),
+        )
+    })?;
+    let mut temp_name = std::ffi::OsString::from(".");
+    temp_name.push(file_name);
+    temp_name.push(".tmp");
+    let temp_path = path.with_file_name(temp_name);
+
+    let timestamp = write_file(label, &temp_path, content, durability)?;
+    fs::rename(&temp_path, path)
+        .with_path(path)
+        .with_label(label)?;
+    if durability == Durability::Sync {
+        if let Some(parent_dir) = path.parent() {
+            sync_dir(parent_dir)
+                .with_path(parent_dir)
+                .with_label(label)?;
+        }
+    }
+    Ok(timestamp)
+}
+
 #[cfg(test)]
 mod tests {
-    use std::{collections::BTreeMap, fs};
+    use std::{
+        collections::{BTreeMap, BTreeSet},
+        fs,
+        time::Duration,
+    };
     use tempdir::TempDir;
 
     use crate::{
         base_storage::{BaseStorage, SyncStatus},
-        file_storage::FileStorage,
+        file_storage::{Durability, FileStorage, STORAGE_VERSION},
+        migration, snapshot,
     };
+    use data_error::ArklibError;
 
     #[test]
     fn test_file_storage_write_read() {
@@ -331,9 +1342,11 @@ mod tests {
         file_storage.set("key2".to_string(), "value2".to_string());
 
         assert!(file_storage.remove(&"key1".to_string()).is_ok());
+        assert_eq!(file_storage.dirty_keys().len(), 2);
         file_storage
             .write_fs()
             .expect("Failed to write data to disk");
+        assert!(file_storage.dirty_keys().is_empty());
         let data_read: &BTreeMap<_, _> = file_storage
             .read_fs()
             .expect("Failed to read data from disk");
@@ -433,6 +1446,120 @@ mod tests {
         assert_eq!(mirror_storage.sync_status().unwrap(), SyncStatus::InSync);
     }
 
+    #[test]
+    fn test_file_storage_three_way_sync() {
+        let temp_dir =
+            TempDir::new("tmp").expect("Failed to create temporary directory");
+        let storage_path = temp_dir.path().join("teststorage.txt");
+
+        let mut file_storage =
+            FileStorage::new("TestStorage".to_string(), &storage_path).unwrap();
+        file_storage.set("key1".to_string(), 1);
+        file_storage.set("key2".to_string(), 2);
+        file_storage.write_fs().unwrap();
+
+        let mut mirror_storage =
+            FileStorage::new("MirrorTestStorage".to_string(), &storage_path)
+                .unwrap();
+        mirror_storage.set("key3".to_string(), 3);
+        mirror_storage.set("key2".to_string(), 5);
+        mirror_storage.write_fs().unwrap();
+
+        // the removal is not undone by the other side
+        file_storage.remove(&"key1".to_string()).unwrap();
+        file_storage.set("key2".to_string(), 4);
+        assert_eq!(file_storage.sync_status().unwrap(), SyncStatus::Diverge);
+        let report = file_storage.sync_with_report().unwrap();
+        assert!(report.taken.contains("key3"));
+        assert!(report.combined.contains("key2"));
+        assert!(report.kept.is_empty());
+
+        assert_eq!(file_storage.sync_status().unwrap(), SyncStatus::InSync);
+        assert!(!file_storage.contains_key(&"key1".to_string()));
+        assert_eq!(file_storage.get(&"key2".to_string()), Some(&5));
+        assert_eq!(file_storage.get(&"key3".to_string()), Some(&3));
+        mirror_storage.sync().unwrap();
+        assert_eq!(mirror_storage.as_ref(), file_storage.as_ref());
+    }
+
+    #[test]
+    fn test_file_storage_tombstones() {
+        let temp_dir =
+            TempDir::new("tmp").expect("Failed to create temporary directory");
+        let laptop_path = temp_dir.path().join("laptop");
+        let phone_path = temp_dir.path().join("phone");
+
+        let mut laptop =
+            FileStorage::new("Laptop".to_string(), &laptop_path).unwrap();
+        laptop.set("key1".to_string(), 1);
+        laptop.set("key2".to_string(), 2);
+        laptop.write_fs().unwrap();
+        let mut phone =
+            FileStorage::new("Phone".to_string(), &phone_path).unwrap();
+        phone.merge_from(&laptop).unwrap();
+        phone.write_fs().unwrap();
+
+        // the removal is kept by the laptop, and spread to the phone
+        laptop.remove(&"key1".to_string()).unwrap();
+        laptop.write_fs().unwrap();
+        let mut laptop =
+            FileStorage::new("Laptop".to_string(), &laptop_path).unwrap();
+        assert!(laptop.tombstones().contains_key("key1"));
+        laptop.merge_from(&phone).unwrap();
+        assert!(!laptop.contains_key(&"key1".to_string()));
+
+        phone
+            .merge_tombstones(laptop.tombstones())
+            .unwrap();
+        assert!(!phone.contains_key(&"key1".to_string()));
+        assert_eq!(phone.tombstones(), laptop.tombstones());
+
+        // setting the key again revives it
+        laptop.set("key1".to_string(), 3);
+        assert!(laptop.tombstones().is_empty());
+        assert_eq!(laptop.prune_tombstones(u64::MAX), 0);
+        assert_eq!(phone.prune_tombstones(u64::MAX), 1);
+    }
+
+    #[test]
+    fn test_file_storage_errors_contain_path() {
+        let temp_dir =
+            TempDir::new("tmp").expect("Failed to create temporary directory");
+        let storage_path = temp_dir.path().join("missing_storage.txt");
+
+        // The storage has never been written to disk
+        let file_storage: FileStorage<String, String> =
+            FileStorage::new("TestStorage".to_string(), &storage_path).unwrap();
+        let err = file_storage.sync_status().unwrap_err();
+        assert!(
+            err.to_string()
+                .contains(&storage_path.display().to_string()),
+            "{}",
+            err
+        );
+        let err = file_storage.erase().unwrap_err();
+        assert!(
+            err.to_string()
+                .contains(&storage_path.display().to_string()),
+            "{}",
+            err
+        );
+
+        // The parent of the storage is a file
+        let blocker = temp_dir.path().join("blocker");
+        fs::write(&blocker, "").unwrap();
+        let mut file_storage: FileStorage<String, String> =
+            FileStorage::new("TestStorage".to_string(), &blocker.join("inner"))
+                .unwrap();
+        let err = file_storage.write_fs().unwrap_err();
+        assert!(
+            err.to_string()
+                .contains(&blocker.display().to_string()),
+            "{}",
+            err
+        );
+    }
+
     #[test]
     fn test_monoid_combine() {
         let temp_dir =
@@ -461,4 +1588,521 @@ mod tests {
         assert_eq!(file_storage_1.as_ref().get("key2"), Some(&6));
         assert_eq!(file_storage_1.as_ref().get("key3"), Some(&9));
     }
+
+    #[test]
+    fn test_file_storage_durability() {
+        let temp_dir =
+            TempDir::new("tmp").expect("Failed to create temporary directory");
+
+        for durability in
+            [Durability::Fast, Durability::Flush, Durability::Sync]
+        {
+            let storage_path = temp_dir
+                .path()
+                .join(format!("{:?}.txt", durability));
+            let mut file_storage =
+                FileStorage::new("TestStorage".to_string(), &storage_path)
+                    .unwrap()
+                    .with_durability(durability);
+            assert_eq!(file_storage.durability(), durability);
+
+            file_storage.set("key1".to_string(), "value1".to_string());
+            file_storage.write_fs().unwrap();
+            let mirror_storage: FileStorage<String, String> =
+                FileStorage::new("MirrorStorage".to_string(), &storage_path)
+                    .unwrap();
+            assert_eq!(mirror_storage.durability(), Durability::Sync);
+            assert_eq!(mirror_storage.as_ref(), file_storage.as_ref());
+        }
+    }
+
+    #[test]
+    fn test_value_index_consistency() {
+        let temp_dir =
+            TempDir::new("tmp").expect("Failed to create temporary directory");
+        let storage_path = temp_dir.path().join("teststorage.txt");
+        let keys = |keys: &[&str]| -> BTreeSet<String> {
+            keys.iter().map(|key| key.to_string()).collect()
+        };
+
+        let mut file_storage =
+            FileStorage::new("TestStorage".to_string(), &storage_path).unwrap();
+        file_storage.set("key1".to_string(), 1);
+        file_storage.set("key2".to_string(), 2);
+
+        // Existing entries are indexed, negative values are not
+        file_storage.enable_value_index(|value: &i32| {
+            (*value >= 0).then(|| (value % 2).to_string())
+        });
+        assert_eq!(file_storage.find_by_value("1"), &keys(&["key1"]));
+        assert_eq!(file_storage.find_by_value("0"), &keys(&["key2"]));
+
+        file_storage.set("key3".to_string(), 3);
+        file_storage.set("key2".to_string(), 5);
+        file_storage.set("key1".to_string(), -1);
+        assert_eq!(file_storage.find_by_value("1"), &keys(&["key2", "key3"]));
+        assert!(file_storage.find_by_value("0").is_empty());
+
+        file_storage.remove(&"key3".to_string()).unwrap();
+        assert_eq!(file_storage.find_by_value("1"), &keys(&["key2"]));
+        file_storage.write_fs().unwrap();
+
+        // `merge_from` keeps the greatest score
+        let mut other =
+            FileStorage::new("OtherStorage".to_string(), &storage_path)
+                .unwrap();
+        other.set("key1".to_string(), 4);
+        other.set("key2".to_string(), 8);
+        other.set("key4".to_string(), 7);
+        file_storage.merge_from(&other).unwrap();
+        assert_eq!(file_storage.find_by_value("0"), &keys(&["key1", "key2"]));
+        assert_eq!(file_storage.find_by_value("1"), &keys(&["key4"]));
+
+        // Reloading drops the unsaved changes from the index
+        file_storage.read_fs().unwrap();
+        assert_eq!(file_storage.find_by_value("1"), &keys(&["key2"]));
+        assert!(file_storage.find_by_value("0").is_empty());
+    }
+
+    #[test]
+    fn test_file_storage_transaction() {
+        let temp_dir =
+            TempDir::new("tmp").expect("Failed to create temporary directory");
+        let storage_path = temp_dir.path().join("teststorage.txt");
+
+        let mut file_storage =
+            FileStorage::new("TestStorage".to_string(), &storage_path).unwrap();
+        file_storage.set("key1".to_string(), 1);
+        file_storage.write_fs().unwrap();
+
+        let previous = file_storage
+            .transaction(|tx| {
+                let previous = tx.get(&"key1".to_string()).copied();
+                tx.set("key2".to_string(), 2);
+                tx.set("key3".to_string(), 3);
+                tx.remove("key1".to_string())?;
+                Ok(previous)
+            })
+            .unwrap();
+        assert_eq!(previous, Some(1));
+        assert_eq!(file_storage.sync_status().unwrap(), SyncStatus::InSync);
+        let reloaded: FileStorage<String, i32> =
+            FileStorage::new("Reloaded".to_string(), &storage_path).unwrap();
+        assert_eq!(reloaded.as_ref(), file_storage.as_ref());
+        assert_eq!(file_storage.as_ref().len(), 2);
+
+        // A failing transaction changes nothing
+        let failed = file_storage.transaction(|tx| {
+            tx.set("key4".to_string(), 4);
+            tx.remove("key1".to_string())
+        });
+        assert!(failed.is_err());
+        assert_eq!(reloaded.as_ref(), file_storage.as_ref());
+        let files = fs::read_dir(temp_dir.path()).unwrap().count();
+        assert_eq!(files, 1);
+    }
+
+    #[test]
+    fn test_file_storage_journal() {
+        let temp_dir =
+            TempDir::new("tmp").expect("Failed to create temporary directory");
+        let storage_path = temp_dir.path().join("teststorage.txt");
+        let journal_path = temp_dir.path().join("teststorage.txt.journal");
+        let reopen = || -> FileStorage<String, i32> {
+            FileStorage::new("Reopened".to_string(), &storage_path).unwrap()
+        };
+
+        let mut file_storage =
+            FileStorage::new("TestStorage".to_string(), &storage_path)
+                .unwrap()
+                .with_journal(2);
+        file_storage.set("key1".to_string(), 1);
+        file_storage.write_fs().unwrap();
+        assert!(!journal_path.exists());
+
+        // Changes are appended to the journal and replayed when reading
+        file_storage.set("key2".to_string(), 2);
+        file_storage.write_fs().unwrap();
+        assert!(journal_path.exists());
+        assert!(!fs::read_to_string(&storage_path)
+            .unwrap()
+            .contains("key2"));
+        let mut reopened = reopen();
+        assert_eq!(reopened.as_ref(), file_storage.as_ref());
+
+        file_storage.remove(&"key1".to_string()).unwrap();
+        file_storage.write_fs().unwrap();
+        assert_eq!(reopened.sync_status().unwrap(), SyncStatus::MappingStale);
+        reopened.sync().unwrap();
+        assert_eq!(reopened.as_ref(), file_storage.as_ref());
+        let journal = fs::read(&journal_path).unwrap();
+
+        // The journal is compacted once it would exceed 2 changes
+        file_storage.set("key3".to_string(), 3);
+        file_storage.write_fs().unwrap();
+        assert!(!journal_path.exists());
+        assert_eq!(reopen().as_ref(), file_storage.as_ref());
+
+        // A journal left behind by an interrupted compaction is ignored
+        fs::write(&journal_path, journal).unwrap();
+        assert_eq!(reopen().as_ref(), file_storage.as_ref());
+    }
+
+    #[test]
+    fn test_file_storage_journal_cut_short() {
+        let temp_dir =
+            TempDir::new("tmp").expect("Failed to create temporary directory");
+        let storage_path = temp_dir.path().join("teststorage.txt");
+        let journal_path = temp_dir.path().join("teststorage.txt.journal");
+        let open = || -> FileStorage<String, i32> {
+            FileStorage::new("TestStorage".to_string(), &storage_path)
+                .unwrap()
+                .with_journal(10)
+        };
+
+        let mut file_storage = open();
+        file_storage.set("key1".to_string(), 1);
+        file_storage.write_fs().unwrap();
+        file_storage.set("key2".to_string(), 2);
+        file_storage.write_fs().unwrap();
+        file_storage.set("key3".to_string(), 3);
+        file_storage.write_fs().unwrap();
+
+        // A crash cuts the last change in the middle of its line
+        let journal = fs::read(&journal_path).unwrap();
+        fs::write(&journal_path, &journal[..journal.len() - 5]).unwrap();
+
+        let mut reopened = open();
+        assert_eq!(reopened.get(&"key2".to_string()), Some(&2));
+        assert_eq!(reopened.get(&"key3".to_string()), None);
+
+        // The next change isn't appended to the truncated one
+        reopened.set("key4".to_string(), 4);
+        reopened.write_fs().unwrap();
+        let reopened = open();
+        assert_eq!(reopened.get(&"key2".to_string()), Some(&2));
+        assert_eq!(reopened.get(&"key4".to_string()), Some(&4));
+        assert_eq!(reopened.len(), 3);
+    }
+
+    #[cfg(feature = "binary-format")]
+    #[test]
+    fn test_file_storage_binary_format() {
+        use crate::file_storage::StorageFormat;
+
+        let temp_dir =
+            TempDir::new("tmp").expect("Failed to create temporary directory");
+        let json_path = temp_dir.path().join("storage.json");
+        let cbor_path = temp_dir.path().join("storage.cbor");
+
+        let mut json =
+            FileStorage::new("JsonStorage".to_string(), &json_path).unwrap();
+        let mut cbor = FileStorage::new("CborStorage".to_string(), &cbor_path)
+            .unwrap()
+            .with_format(StorageFormat::Cbor);
+        for i in 0..100 {
+            json.set(format!("key{}", i), i);
+            cbor.set(format!("key{}", i), i);
+        }
+        json.write_fs().unwrap();
+        cbor.write_fs().unwrap();
+        let json_size = fs::metadata(&json_path).unwrap().len();
+        let cbor_size = fs::metadata(&cbor_path).unwrap().len();
+        assert!(cbor_size < json_size);
+
+        // The format is detected when reading
+        let reopened: FileStorage<String, i32> =
+            FileStorage::new("Reopened".to_string(), &cbor_path).unwrap();
+        assert_eq!(reopened.format(), StorageFormat::Json);
+        assert_eq!(reopened.as_ref(), json.as_ref());
+    }
+
+    #[cfg(feature = "encryption")]
+    #[test]
+    fn test_file_storage_encryption() {
+        use crate::encryption::EncryptionKey;
+
+        let temp_dir =
+            TempDir::new("tmp").expect("Failed to create temporary directory");
+        let storage_path = temp_dir.path().join("teststorage.txt");
+        let key = EncryptionKey::new([42; 32]);
+
+        let mut file_storage = FileStorage::new_encrypted(
+            "TestStorage".to_string(),
+            &storage_path,
+            key.clone(),
+        )
+        .unwrap();
+        file_storage.set("secret".to_string(), "value".to_string());
+        file_storage.write_fs().unwrap();
+        let content = fs::read(&storage_path).unwrap();
+        assert!(!String::from_utf8_lossy(&content).contains("secret"));
+
+        // The key is needed to read the storage
+        assert!(FileStorage::<String, String>::new(
+            "NoKey".to_string(),
+            &storage_path
+        )
+        .is_err());
+        let reopened = FileStorage::<String, String>::new_encrypted(
+            "Reopened".to_string(),
+            &storage_path,
+            key,
+        )
+        .unwrap();
+        assert_eq!(reopened.as_ref(), file_storage.as_ref());
+    }
+
+    #[test]
+    fn test_file_storage_locking() {
+        let temp_dir =
+            TempDir::new("tmp").expect("Failed to create temporary directory");
+        let storage_path = temp_dir.path().join("teststorage.txt");
+        let timeout = Duration::from_millis(50);
+
+        let mut editor =
+            FileStorage::new("Editor".to_string(), &storage_path).unwrap();
+        let mut other = FileStorage::new("Other".to_string(), &storage_path)
+            .unwrap()
+            .with_locking(timeout);
+        assert!(editor.try_lock_exclusive().unwrap());
+        assert!(!other.try_lock_exclusive().unwrap());
+
+        // The editor keeps writing while the other storage waits
+        editor.set("key1".to_string(), 1);
+        editor.write_fs().unwrap();
+        other.set("key2".to_string(), 2);
+        assert!(other.write_fs().is_err());
+
+        editor.unlock();
+        other.write_fs().unwrap();
+        assert_eq!(editor.sync_status().unwrap(), SyncStatus::MappingStale);
+    }
+
+    #[test]
+    fn test_file_storage_migration() {
+        let temp_dir =
+            TempDir::new("tmp").expect("Failed to create temporary directory");
+        let storage_path = temp_dir.path().join("teststorage.txt");
+        let legacy = "version: 2\nkey1:1\nkey2:2\n";
+        fs::write(&storage_path, legacy).unwrap();
+
+        let file_storage: FileStorage<String, i32> =
+            FileStorage::new("TestStorage".to_string(), &storage_path).unwrap();
+        assert_eq!(file_storage.get(&"key2".to_string()), Some(&2));
+        let backup = migration::backup_path(&storage_path, 2);
+        assert_eq!(fs::read_to_string(backup).unwrap(), legacy);
+        let migrated = fs::read_to_string(&storage_path).unwrap();
+        assert!(migrated.contains(&format!("\"version\": {}", STORAGE_VERSION)));
+
+        // files of newer versions are left alone
+        let newer = format!(
+            "{{\"version\": {}, \"entries\": {{}}}}",
+            STORAGE_VERSION + 1
+        );
+        fs::write(&storage_path, &newer).unwrap();
+        let err = FileStorage::<String, i32>::new(
+            "TestStorage".to_string(),
+            &storage_path,
+        )
+        .err()
+        .unwrap();
+        assert_eq!(
+            err.code(),
+            ArklibError::UnsupportedVersion(String::new(), 0, 0).code()
+        );
+        assert_eq!(fs::read_to_string(&storage_path).unwrap(), newer);
+    }
+
+    #[test]
+    fn test_file_storage_snapshot_restore() {
+        let temp_dir =
+            TempDir::new("tmp").expect("Failed to create temporary directory");
+        let storage_path = temp_dir.path().join("teststorage.txt");
+        let snapshot_path = temp_dir.path().join("snapshot");
+
+        let mut file_storage =
+            FileStorage::new("TestStorage".to_string(), &storage_path).unwrap();
+        file_storage.set("key1".to_string(), 1);
+        file_storage.write_fs().unwrap();
+        file_storage.snapshot(&snapshot_path).unwrap();
+
+        let manifest = snapshot::read_manifest(
+            "TestStorage",
+            &snapshot_path,
+            STORAGE_VERSION,
+        )
+        .unwrap();
+        assert_eq!(manifest.label, "TestStorage");
+        assert_eq!(manifest.version, STORAGE_VERSION);
+
+        file_storage.set("key1".to_string(), 2);
+        file_storage.set("key2".to_string(), 3);
+        file_storage.write_fs().unwrap();
+        file_storage.set("key3".to_string(), 4);
+
+        file_storage.restore(&snapshot_path).unwrap();
+        assert_eq!(file_storage.len(), 1);
+        assert_eq!(file_storage.get(&"key1".to_string()), Some(&1));
+        assert!(file_storage.dirty_keys().is_empty());
+        let reopened: FileStorage<String, i32> =
+            FileStorage::new("Reopened".to_string(), &storage_path).unwrap();
+        assert_eq!(reopened.as_ref(), file_storage.as_ref());
+    }
+
+    #[test]
+    fn test_file_storage_corruption() {
+        let temp_dir =
+            TempDir::new("tmp").expect("Failed to create temporary directory");
+        let storage_path = temp_dir.path().join("teststorage.txt");
+        let corrupted =
+            ArklibError::Corrupted(String::new(), String::new()).code();
+
+        let mut file_storage =
+            FileStorage::new("TestStorage".to_string(), &storage_path).unwrap();
+        file_storage.set("key1".to_string(), "value1".to_string());
+        file_storage.set("key2".to_string(), "value2".to_string());
+        file_storage.write_fs().unwrap();
+        let content = fs::read_to_string(&storage_path).unwrap();
+
+        fs::write(&storage_path, &content[..content.len() / 2]).unwrap();
+        let err = file_storage.read_fs().unwrap_err();
+        assert_eq!(err.code(), corrupted);
+
+        fs::write(&storage_path, content.replace("value2", "value3")).unwrap();
+        let err = file_storage.read_fs().unwrap_err();
+        assert_eq!(err.code(), corrupted);
+
+        // files written without a checksum are still read
+        fs::write(
+            &storage_path,
+            r#"{"version": 3, "entries": {"key1": "value1"}}"#,
+        )
+        .unwrap();
+        assert_eq!(file_storage.read_fs().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_value_index_disabled() {
+        let temp_dir =
+            TempDir::new("tmp").expect("Failed to create temporary directory");
+        let storage_path = temp_dir.path().join("teststorage.txt");
+
+        let mut file_storage =
+            FileStorage::new("TestStorage".to_string(), &storage_path).unwrap();
+        file_storage.set("key1".to_string(), "value1".to_string());
+        assert!(file_storage.find_by_value("value1").is_empty());
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn test_tracing_spans_emitted() {
+        use std::collections::HashMap;
+        use std::sync::{Arc, Mutex};
+        use tracing::field::{Field, Visit};
+        use tracing::span::{Attributes, Id, Record};
+        use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+
+        type Fields = HashMap<String, String>;
+
+        /// Spans in order of creation and the index of the latest span
+        /// using a given id
+        #[derive(Default)]
+        struct Spans {
+            spans: Vec<(String, Fields)>,
+            ids: HashMap<u64, usize>,
+        }
+
+        struct FieldVisitor<'a>(&'a mut Fields);
+
+        impl Visit for FieldVisitor<'_> {
+            fn record_debug(
+                &mut self,
+                field: &Field,
+                value: &dyn std::fmt::Debug,
+            ) {
+                self.0
+                    .insert(field.name().to_owned(), format!("{:?}", value));
+            }
+        }
+
+        struct Collector(Arc<Mutex<Spans>>);
+
+        impl<S: tracing::Subscriber> Layer<S> for Collector {
+            fn on_new_span(
+                &self,
+                attrs: &Attributes<'_>,
+                id: &Id,
+                _ctx: Context<'_, S>,
+            ) {
+                let mut fields = Fields::new();
+                attrs.record(&mut FieldVisitor(&mut fields));
+
+                let mut spans = self.0.lock().unwrap();
+                let index = spans.spans.len();
+                spans
+                    .spans
+                    .push((attrs.metadata().name().to_owned(), fields));
+                spans.ids.insert(id.into_u64(), index);
+            }
+
+            fn on_record(
+                &self,
+                id: &Id,
+                values: &Record<'_>,
+                _ctx: Context<'_, S>,
+            ) {
+                let mut spans = self.0.lock().unwrap();
+                if let Some(index) = spans.ids.get(&id.into_u64()).copied() {
+                    values.record(&mut FieldVisitor(&mut spans.spans[index].1));
+                }
+            }
+        }
+
+        let temp_dir =
+            TempDir::new("tmp").expect("Failed to create temporary directory");
+        let storage_path = temp_dir.path().join("teststorage.txt");
+
+        let collected = Arc::new(Mutex::new(Spans::default()));
+        let subscriber =
+            tracing_subscriber::registry().with(Collector(collected.clone()));
+        tracing::subscriber::with_default(subscriber, || {
+            let mut file_storage =
+                FileStorage::new("TestStorage".to_string(), &storage_path)
+                    .unwrap();
+            file_storage.set("key1".to_string(), "value1".to_string());
+            file_storage.write_fs().unwrap();
+            file_storage.read_fs().unwrap();
+        });
+
+        let collected = collected.lock().unwrap();
+        for name in ["write_fs", "read_fs"] {
+            let (_, fields) = collected
+                .spans
+                .iter()
+                .find(|(span, _)| span == name)
+                .unwrap_or_else(|| panic!("Span {} was not emitted", name));
+
+            assert_eq!(fields["label"], "TestStorage");
+            assert_eq!(fields["path"], storage_path.display().to_string());
+            assert_eq!(fields["entries"], "1");
+            assert!(fields.contains_key("elapsed_ms"));
+        }
+    }
+
+    proptest::proptest! {
+        /// Loading a corrupted storage file must fail gracefully
+        #[test]
+        fn test_file_storage_arbitrary_content(content: Vec<u8>) {
+            let temp_dir = TempDir::new("tmp")
+                .expect("Failed to create temporary directory");
+            let storage_path = temp_dir.path().join("teststorage.txt");
+            fs::write(&storage_path, content).unwrap();
+
+            let _ = FileStorage::<String, String>::new(
+                "TestStorage".to_string(),
+                &storage_path,
+            );
+        }
+    }
 }
@@ -0,0 +1,604 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Display;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::SystemTime;
+
+use crate::base_storage::{BaseStorage, SyncStatus};
+use crate::monoid::Monoid;
+use crate::snapshot;
+use data_error::{ArklibError, Result, ResultExt};
+pub use fs_atomic_versions::durability::Durability;
+use fs_atomic_versions::durability::{finish_write, sync_dir};
+
+/*
+Note on `FolderStorage` Versioning:
+
+Every file of a `FolderStorage` is stamped with the version of its format,
+so that files written by different versions can live in the same folder.
+Version 3 is the first one, matching the JSON format of `FileStorage`.
+*/
+const STORAGE_VERSION: i32 = 3;
+
+/// Represents a storage system that persists every entry to its own file
+/// in a folder, the file being named after the key. Characters which
+/// can't appear in file names are percent-encoded.
+///
+/// Writing a single entry doesn't rewrite the others, which suits large
+/// values and values edited from several devices, like properties.
+/// Otherwise it behaves like [`FileStorage`](crate::file_storage::FileStorage).
+pub struct FolderStorage<K, V>
+where
+    K: Ord,
+{
+    /// Label for logging
+    label: String,
+    /// Path to the folder where the entries are persisted
+    path: PathBuf,
+    /// Last modified time of internal mapping. This becomes equal to
+    /// `written_to_disk` only when data is written or read from disk.
+    modified: SystemTime,
+    /// Last time the data was written to disk. This becomes equal to
+    /// `modified` only when data is written or read from disk.
+    written_to_disk: SystemTime,
+    entries: BTreeMap<K, V>,
+    /// Timestamps of the files as of the last read or write
+    on_disk: BTreeMap<K, SystemTime>,
+    /// Keys set or removed since the last write
+    changed: BTreeSet<K>,
+    /// How far `write_fs` goes before returning
+    durability: Durability,
+}
+
+/// Content of the file of a single entry
+#[derive(Serialize, Deserialize)]
+struct FolderStorageEntry<V> {
+    version: i32,
+    value: V,
+}
+
+impl<K, V> FolderStorage<K, V>
+where
+    K: Ord
+        + Clone
+        + Display
+        + serde::Serialize
+        + serde::de::DeserializeOwned
+        + FromStr,
+    V: Clone
+        + serde::Serialize
+        + serde::de::DeserializeOwned
+        + FromStr
+        + Monoid<V>,
+{
+    /// Create a new folder storage with a diagnostic label and folder path
+    /// The storage will be initialized using the disk data, if the folder
+    /// exists
+    pub fn new(label: String, path: &Path) -> Result<Self> {
+        let time = SystemTime::now();
+        let mut storage = Self {
+            label,
+            path: PathBuf::from(path),
+            modified: time,
+            written_to_disk: time,
+            entries: BTreeMap::new(),
+            on_disk: BTreeMap::new(),
+            changed: BTreeSet::new(),
+            durability: Durability::default(),
+        };
+
+        if Path::exists(path) {
+            storage.read_fs()?;
+        }
+
+        Ok(storage)
+    }
+
+    /// Write the storage with the given durability,
+    /// see [`FileStorage::with_durability`](crate::file_storage::FileStorage::with_durability)
+    pub fn with_durability(mut self, durability: Durability) -> Self {
+        self.durability = durability;
+        self
+    }
+
+    pub fn durability(&self) -> Durability {
+        self.durability
+    }
+
+    fn entry_path(&self, key: &K) -> PathBuf {
+        self.path.join(file_name(&key.to_string()))
+    }
+
+    /// Keys of the files in the folder with their modification time,
+    /// files whose name isn't a key are skipped
+    fn list_fs(&self) -> Result<BTreeMap<K, SystemTime>> {
+        let mut listed = BTreeMap::new();
+        if !self.path.exists() {
+            return Ok(listed);
+        }
+
+        let dir = fs::read_dir(&self.path)
+            .with_path(&self.path)
+            .with_label(&self.label)?;
+        for entry in dir {
+            let entry = entry
+                .with_path(&self.path)
+                .with_label(&self.label)?;
+            let name = entry.file_name();
+            // temporary files of interrupted writes
+            if name.to_string_lossy().starts_with('.') {
+                continue;
+            }
+            let Some(key) = name
+                .to_str()
+                .and_then(decode_file_name)
+                .and_then(|name| K::from_str(&name).ok())
+            else {
+                log::warn!("{} skips {}", self.label, entry.path().display());
+                continue;
+            };
+            let modified = entry
+                .metadata()
+                .and_then(|metadata| metadata.modified())
+                .with_path(entry.path())
+                .with_label(&self.label)?;
+            listed.insert(key, modified);
+        }
+        Ok(listed)
+    }
+
+    /// Load the entries of the files listed in `listed`
+    fn load_fs_data(
+        &self,
+        listed: &BTreeMap<K, SystemTime>,
+    ) -> Result<BTreeMap<K, V>> {
+        let mut entries = BTreeMap::new();
+        for key in listed.keys() {
+            let path = self.entry_path(key);
+            let file = File::open(&path)
+                .with_path(&path)
+                .with_label(&self.label)?;
+            let entry: FolderStorageEntry<V> = serde_json::from_reader(file)
+                .map_err(|err| {
+                    ArklibError::Storage(
+                        self.label.clone(),
+                        format!("{} (path: {})", err, path.display()),
+                    )
+                })?;
+            if entry.version != STORAGE_VERSION {
+                return Err(ArklibError::Storage(
+                    self.label.clone(),
+                    format!(
+                        "Storage version mismatch: expected {}, got {} \
+                         (path: {})",
+                        STORAGE_VERSION,
+                        entry.version,
+                        path.display()
+                    ),
+                ));
+            }
+            entries.insert(key.clone(), entry.value);
+        }
+        Ok(entries)
+    }
+
+    /// Write the entry of `key` to a temporary file, which then replaces
+    /// the file of the entry, so that the entry is never half-written
+    fn write_entry(&self, key: &K, value: &V) -> Result<()> {
+        let name = file_name(&key.to_string());
+        if name.is_empty() {
+            return Err(ArklibError::Storage(
+                self.label.clone(),
+                "Empty keys can't be stored in a folder".to_owned(),
+            ));
+        }
+        let path = self.path.join(&name);
+        let temp = self.path.join(format!(".{}.tmp", name));
+        let entry = FolderStorageEntry {
+            version: STORAGE_VERSION,
+            value,
+        };
+        let content = serde_json::to_string_pretty(&entry)
+            .with_path(&path)
+            .with_label(&self.label)?;
+        let mut file = File::create(&temp)
+            .with_path(&temp)
+            .with_label(&self.label)?;
+        let written = file
+            .write_all(content.as_bytes())
+            .and_then(|_| file.set_modified(SystemTime::now()))
+            .and_then(|_| finish_write(&mut file, self.durability))
+            .and_then(|_| fs::rename(&temp, &path));
+        if written.is_err() {
+            let _ = fs::remove_file(&temp);
+        }
+        written.with_path(&path).with_label(&self.label)
+    }
+}
+
+impl<K, V> BaseStorage<K, V> for FolderStorage<K, V>
+where
+    K: Ord
+        + Clone
+        + Display
+        + serde::Serialize
+        + serde::de::DeserializeOwned
+        + FromStr,
+    V: Clone
+        + serde::Serialize
+        + serde::de::DeserializeOwned
+        + FromStr
+        + Monoid<V>,
+{
+    /// Set a key-value pair in the internal mapping
+    fn set(&mut self, key: K, value: V) {
+        self.changed.insert(key.clone());
+        self.entries.insert(key, value);
+        self.modified = SystemTime::now();
+    }
+
+    /// Remove an entry from the internal mapping given a key
+    fn remove(&mut self, id: &K) -> Result<()> {
+        self.entries.remove(id).ok_or_else(|| {
+            ArklibError::Storage(self.label.clone(), "Key not found".to_owned())
+        })?;
+        self.changed.insert(id.clone());
+        self.modified = SystemTime::now();
+        Ok(())
+    }
+
+    /// Keys set or removed since the last read or write
+    fn dirty_keys(&self) -> &BTreeSet<K> {
+        &self.changed
+    }
+
+    /// Compare the files of the folder with the ones found by the last
+    /// read or write, and the timestamp of the in-memory storage with
+    /// the last write, to determine if either of the two requires syncing.
+    fn sync_status(&self) -> Result<SyncStatus> {
+        let folder_updated = self.list_fs()? != self.on_disk;
+
+        let status =
+            match (self.modified > self.written_to_disk, folder_updated) {
+                (true, true) => SyncStatus::Diverge,
+                (true, false) => SyncStatus::StorageStale,
+                (false, true) => SyncStatus::MappingStale,
+                (false, false) => SyncStatus::InSync,
+            };
+
+        log::info!("{} sync status is {}", self.label, status);
+        Ok(status)
+    }
+
+    /// Sync the in-memory storage with the storage on disk
+    fn sync(&mut self) -> Result<()> {
+        match self.sync_status()? {
+            SyncStatus::InSync => Ok(()),
+            SyncStatus::MappingStale => self.read_fs().map(|_| ()),
+            SyncStatus::StorageStale => self.write_fs(),
+            SyncStatus::Diverge => {
+                let listed = self.list_fs()?;
+                // files removed by somebody else since the last read
+                // or write, unless the entry was set here since then
+                let removed: Vec<K> = self
+                    .on_disk
+                    .keys()
+                    .filter(|key| {
+                        !listed.contains_key(key) && !self.changed.contains(key)
+                    })
+                    .cloned()
+                    .collect();
+                for key in removed {
+                    self.entries.remove(&key);
+                }
+
+                let data = self.load_fs_data(&listed)?;
+                for (key, value) in data {
+                    let updated = self.on_disk.get(&key) != listed.get(&key);
+                    if !updated {
+                        continue;
+                    }
+                    if !self.changed.contains(&key) {
+                        self.entries.insert(key, value);
+                        continue;
+                    }
+                    // entries removed here stay removed
+                    if let Some(existing) = self.entries.get(&key) {
+                        let value = V::combine(existing, &value);
+                        self.set(key, value);
+                    }
+                }
+                self.write_fs()
+            }
+        }
+    }
+
+    /// Read the entries from the folder
+    fn read_fs(&mut self) -> Result<&BTreeMap<K, V>> {
+        let listed = self.list_fs()?;
+        self.entries = self.load_fs_data(&listed)?;
+        self.on_disk = listed;
+        self.changed.clear();
+        self.modified = SystemTime::now();
+        self.written_to_disk = self.modified;
+
+        Ok(&self.entries)
+    }
+
+    /// Write the entries set or removed since the last write,
+    /// the files of the other entries are left untouched
+    fn write_fs(&mut self) -> Result<()> {
+        fs::create_dir_all(&self.path)
+            .with_path(&self.path)
+            .with_label(&self.label)?;
+
+        let mut listing_changed = false;
+        for key in std::mem::take(&mut self.changed) {
+            match self.entries.get(&key) {
+                Some(value) => {
+                    self.write_entry(&key, value)?;
+                    listing_changed = true;
+                }
+                None => {
+                    let path = self.entry_path(&key);
+                    if path.is_file() {
+                        fs::remove_file(&path)
+                            .with_path(&path)
+                            .with_label(&self.label)?;
+                        listing_changed = true;
+                    }
+                }
+            }
+        }
+        // renamed and removed files are lost on power loss
+        // until the folder is synced
+        if listing_changed && self.durability == Durability::Sync {
+            sync_dir(&self.path)
+                .with_path(&self.path)
+                .with_label(&self.label)?;
+        }
+
+        self.on_disk = self.list_fs()?;
+        self.modified = SystemTime::now();
+        self.written_to_disk = self.modified;
+
+        log::info!(
+            "{} {} entries have been written",
+            self.label,
+            self.entries.len()
+        );
+        Ok(())
+    }
+
+    /// Erase the folder from disk
+    fn erase(&self) -> Result<()> {
+        fs::remove_dir_all(&self.path)
+            .with_path(&self.path)
+            .with_label(&self.label)
+    }
+
+    /// Merge the data from another storage instance into this storage instance
+    fn merge_from(&mut self, other: impl AsRef<BTreeMap<K, V>>) -> Result<()>
+    where
+        V: Monoid<V>,
+    {
+        let other_entries = other.as_ref();
+        for (key, value) in other_entries {
+            if let Some(existing_value) = self.entries.get(key) {
+                let resolved_value = V::combine(existing_value, value);
+                self.set(key.clone(), resolved_value);
+            } else {
+                self.set(key.clone(), value.clone())
+            }
+        }
+        self.modified = SystemTime::now();
+        Ok(())
+    }
+
+    /// Copy the files of the entries into a snapshot
+    fn snapshot(&self, dest: &Path) -> Result<()> {
+        snapshot::create(&self.label, dest, STORAGE_VERSION)?;
+        snapshot::copy_folder(
+            &self.label,
+            &self.path,
+            &snapshot::data_path(dest),
+        )
+    }
+
+    fn restore(&mut self, src: &Path) -> Result<()> {
+        snapshot::read_manifest(&self.label, src, STORAGE_VERSION)?;
+        snapshot::copy_folder(
+            &self.label,
+            &snapshot::data_path(src),
+            &self.path,
+        )?;
+        self.read_fs()?;
+        log::info!("{} restored from {}", self.label, src.display());
+        Ok(())
+    }
+}
+
+impl<K, V> AsRef<BTreeMap<K, V>> for FolderStorage<K, V>
+where
+    K: Ord,
+{
+    fn as_ref(&self) -> &BTreeMap<K, V> {
+        &self.entries
+    }
+}
+
+/// Name of the file of the entry with the key `key`.
+///
+/// Path separators, `%`, characters which Windows doesn't allow in file
+/// names and control characters are percent-encoded, like a leading
+/// dot, so that every key stays a single file of the folder and names
+/// starting with a dot are left for temporary files.
+fn file_name(key: &str) -> String {
+    let mut name = String::with_capacity(key.len());
+    for (index, c) in key.char_indices() {
+        let escaped = matches!(
+            c,
+            '/' | '\\' | '%' | ':' | '*' | '?' | '"' | '<' | '>' | '|'
+        ) || c.is_control()
+            || (index == 0 && c == '.');
+        if escaped {
+            let mut buf = [0; 4];
+            for byte in c.encode_utf8(&mut buf).bytes() {
+                name.push_str(&format!("%{:02X}", byte));
+            }
+        } else {
+            name.push(c);
+        }
+    }
+    name
+}
+
+/// Key encoded by [`file_name`]
+fn decode_file_name(name: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(name.len());
+    let mut rest = name.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        if byte == b'%' {
+            let hex = std::str::from_utf8(tail.get(..2)?).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+            rest = &tail[2..];
+        } else {
+            bytes.push(byte);
+            rest = tail;
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::fs;
+    use tempdir::TempDir;
+
+    use crate::{
+        base_storage::{BaseStorage, SyncStatus},
+        folder_storage::FolderStorage,
+    };
+
+    #[test]
+    fn test_folder_storage_write_read() {
+        let temp_dir =
+            TempDir::new("tmp").expect("Failed to create temporary directory");
+        let storage_path = temp_dir.path().join("storage");
+
+        let mut storage =
+            FolderStorage::new("TestStorage".to_string(), &storage_path)
+                .unwrap();
+        storage.set("key1".to_string(), "value1".to_string());
+        storage.set("key2".to_string(), "value2".to_string());
+        storage.write_fs().unwrap();
+        assert!(storage_path.join("key1").exists());
+
+        storage.remove(&"key1".to_string()).unwrap();
+        storage.write_fs().unwrap();
+        assert!(!storage_path.join("key1").exists());
+
+        let data_read: &BTreeMap<_, _> = storage.read_fs().unwrap();
+        assert_eq!(data_read.len(), 1);
+        assert_eq!(data_read.get("key2").map(|v| v.as_str()), Some("value2"));
+
+        storage.erase().unwrap();
+        assert!(!storage_path.exists());
+    }
+
+    #[test]
+    fn test_folder_storage_sync_status() {
+        let temp_dir =
+            TempDir::new("tmp").expect("Failed to create temporary directory");
+        let storage_path = temp_dir.path().join("storage");
+
+        let mut storage =
+            FolderStorage::new("TestStorage".to_string(), &storage_path)
+                .unwrap();
+        storage.set("key1".to_string(), 1);
+        assert_eq!(storage.sync_status().unwrap(), SyncStatus::StorageStale);
+        storage.write_fs().unwrap();
+        assert_eq!(storage.sync_status().unwrap(), SyncStatus::InSync);
+
+        // External data manipulation
+        let mut mirror: FolderStorage<String, i32> =
+            FolderStorage::new("MirrorStorage".to_string(), &storage_path)
+                .unwrap();
+        assert_eq!(mirror.as_ref(), storage.as_ref());
+        mirror.set("key2".to_string(), 2);
+        mirror.write_fs().unwrap();
+        assert_eq!(storage.sync_status().unwrap(), SyncStatus::MappingStale);
+
+        // both sides changed, the values are merged
+        storage.set("key1".to_string(), 5);
+        assert_eq!(storage.sync_status().unwrap(), SyncStatus::Diverge);
+        storage.sync().unwrap();
+        assert_eq!(storage.sync_status().unwrap(), SyncStatus::InSync);
+        assert_eq!(storage.as_ref().get("key1"), Some(&5));
+        assert_eq!(storage.as_ref().get("key2"), Some(&2));
+
+        let content = fs::read_to_string(storage_path.join("key1")).unwrap();
+        assert!(content.contains("\"version\": 3"));
+    }
+
+    #[test]
+    fn test_folder_storage_escapes_keys() {
+        let temp_dir =
+            TempDir::new("tmp").expect("Failed to create temporary directory");
+        let storage_path = temp_dir.path().join("storage");
+
+        let mut storage =
+            FolderStorage::new("TestStorage".to_string(), &storage_path)
+                .unwrap();
+        let keys = ["../outside", "a/b", "..", ".hidden", "100%"];
+        for key in keys {
+            storage.set(key.to_string(), key.to_string());
+        }
+        storage.write_fs().unwrap();
+        assert!(!temp_dir.path().join("outside").exists());
+
+        // every key is a single file of the folder
+        let names: Vec<String> = fs::read_dir(&storage_path)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        assert_eq!(names.len(), keys.len());
+        assert!(names.iter().all(|name| !name.starts_with('.')));
+
+        let mirror: FolderStorage<String, String> =
+            FolderStorage::new("MirrorStorage".to_string(), &storage_path)
+                .unwrap();
+        assert_eq!(mirror.as_ref(), storage.as_ref());
+    }
+
+    #[test]
+    fn test_folder_storage_keeps_remote_removals() {
+        let temp_dir =
+            TempDir::new("tmp").expect("Failed to create temporary directory");
+        let storage_path = temp_dir.path().join("storage");
+
+        let mut storage =
+            FolderStorage::new("TestStorage".to_string(), &storage_path)
+                .unwrap();
+        storage.set("key1".to_string(), 1);
+        storage.set("key2".to_string(), 2);
+        storage.write_fs().unwrap();
+
+        let mut mirror: FolderStorage<String, i32> =
+            FolderStorage::new("MirrorStorage".to_string(), &storage_path)
+                .unwrap();
+        mirror.remove(&"key1".to_string()).unwrap();
+        mirror.write_fs().unwrap();
+
+        storage.set("key3".to_string(), 3);
+        assert_eq!(storage.sync_status().unwrap(), SyncStatus::Diverge);
+        storage.sync().unwrap();
+        assert_eq!(storage.as_ref().get("key1"), None);
+        assert!(!storage_path.join("key1").exists());
+        assert_eq!(storage.as_ref().len(), 2);
+    }
+}
@@ -0,0 +1,217 @@
+//! Journal of the changes of a [`FileStorage`](crate::file_storage::FileStorage)
+//! appended since its file was last written entirely.
+//!
+//! The journal is stored next to the storage file, with the `.journal`
+//! extension appended. Its first line holds the generation of the storage
+//! file it applies to, every other line is a single change. A journal
+//! of another generation is left over from an interrupted compaction
+//! and ignored, as the storage file already contains its changes.
+
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
+
+use data_error::{ArklibError, Result, ResultExt};
+use fs_atomic_versions::durability::{finish_write, Durability};
+
+#[derive(Serialize, Deserialize)]
+struct Header {
+    generation: u64,
+}
+
+/// A key set to a value, or removed if there is no value
+#[derive(Serialize, Deserialize)]
+struct Change<K, V> {
+    key: K,
+    value: Option<V>,
+    /// Time of the removal, in milliseconds since the Unix epoch
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    removed: Option<u64>,
+}
+
+pub(crate) fn journal_path(path: &Path) -> PathBuf {
+    let mut journal = OsString::from(path.as_os_str());
+    journal.push(".journal");
+    PathBuf::from(journal)
+}
+
+/// Apply the changes of the journal of the storage file at `path`
+/// to `entries` and their `tombstones`, returning how many changes
+/// were applied
+pub(crate) fn replay<K, V>(
+    label: &str,
+    path: &Path,
+    generation: u64,
+    entries: &mut BTreeMap<K, V>,
+    tombstones: &mut BTreeMap<K, u64>,
+) -> Result<usize>
+where
+    K: Ord + serde::de::DeserializeOwned,
+    V: serde::de::DeserializeOwned,
+{
+    let journal = journal_path(path);
+    let content = match fs::read_to_string(&journal) {
+        Ok(content) => content,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(0),
+        Err(err) => return Err(err).with_path(&journal).with_label(label),
+    };
+    let invalid = |err: serde_json::Error| {
+        ArklibError::Storage(
+            label.to_owned(),
+            format!("{} (path: {})", err, journal.display()),
+        )
+    };
+
+    // the last change may have been cut short by a crash, it is dropped
+    // by the next append
+    let complete = &content[..complete_len(content.as_bytes())];
+    if complete.len() < content.len() {
+        log::warn!("{} ignores a truncated change", label);
+    }
+
+    let mut lines = complete.lines();
+    let Some(header) = lines.next() else {
+        return Ok(0);
+    };
+    match serde_json::from_str::<Header>(header) {
+        Ok(header) if header.generation == generation => {}
+        _ => {
+            log::warn!("{} ignores a stale journal", label);
+            return Ok(0);
+        }
+    }
+
+    let mut applied = 0;
+    for line in lines {
+        let change: Change<K, V> =
+            serde_json::from_str(line).map_err(invalid)?;
+        match change.value {
+            Some(value) => {
+                tombstones.remove(&change.key);
+                entries.insert(change.key, value);
+            }
+            None => {
+                entries.remove(&change.key);
+                if let Some(removed) = change.removed {
+                    tombstones.insert(change.key, removed);
+                }
+            }
+        }
+        applied += 1;
+    }
+    Ok(applied)
+}
+
+/// Append `changes` to the journal of the storage file at `path`,
+/// along with the time of the removals,
+/// starting a new journal if `start` is set,
+/// and return the modification time set on the journal
+pub(crate) fn append<'a, K, V>(
+    label: &str,
+    path: &Path,
+    generation: u64,
+    start: bool,
+    changes: impl Iterator<Item = (&'a K, Option<&'a V>, Option<u64>)>,
+    durability: Durability,
+) -> Result<SystemTime>
+where
+    K: serde::Serialize + 'a,
+    V: serde::Serialize + 'a,
+{
+    let journal = journal_path(path);
+    let mut content = String::new();
+    if start {
+        let header = serde_json::to_string(&Header { generation })
+            .with_path(&journal)
+            .with_label(label)?;
+        content.push_str(&header);
+        content.push('\n');
+    }
+    for (key, value, removed) in changes {
+        let change = serde_json::to_string(&Change {
+            key,
+            value,
+            removed,
+        })
+        .with_path(&journal)
+        .with_label(label)?;
+        content.push_str(&change);
+        content.push('\n');
+    }
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .read(true)
+        .write(true)
+        .truncate(start)
+        .open(&journal)
+        .with_path(&journal)
+        .with_label(label)?;
+    if !start {
+        drop_truncated_tail(&mut file)
+            .with_path(&journal)
+            .with_label(label)?;
+    }
+    file.write_all(content.as_bytes())
+        .with_path(&journal)
+        .with_label(label)?;
+
+    let timestamp = SystemTime::now();
+    file.set_modified(timestamp)
+        .and_then(|_| finish_write(&mut file, durability))
+        .with_path(&journal)
+        .with_label(label)?;
+    Ok(timestamp)
+}
+
+/// Length of the complete lines at the beginning of `content`
+fn complete_len(content: &[u8]) -> usize {
+    content
+        .iter()
+        .rposition(|byte| *byte == b'\n')
+        .map_or(0, |newline| newline + 1)
+}
+
+/// Remove a change cut short by a crash at the end of the journal,
+/// which the next change would be appended to otherwise, and move
+/// to the end of the journal
+fn drop_truncated_tail(file: &mut File) -> io::Result<()> {
+    let len = file.seek(SeekFrom::End(0))?;
+    if len == 0 {
+        return Ok(());
+    }
+    let mut last = [0u8];
+    file.seek(SeekFrom::End(-1))?;
+    file.read_exact(&mut last)?;
+    if last[0] != b'\n' {
+        let mut content = Vec::new();
+        file.seek(SeekFrom::Start(0))?;
+        file.read_to_end(&mut content)?;
+        file.set_len(complete_len(&content) as u64)?;
+    }
+    file.seek(SeekFrom::End(0))?;
+    Ok(())
+}
+
+/// Remove the journal of the storage file at `path`, if any
+pub(crate) fn remove(label: &str, path: &Path) -> Result<()> {
+    let journal = journal_path(path);
+    match fs::remove_file(&journal) {
+        Err(err) if err.kind() != ErrorKind::NotFound => {
+            Err(err).with_path(&journal).with_label(label)
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Modification time of the journal of the storage file at `path`, if any
+pub(crate) fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(journal_path(path))
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
@@ -1,9 +1,32 @@
+#[cfg(feature = "tokio")]
+pub mod async_storage;
 pub mod base_storage;
+pub mod bounded_storage;
+pub mod cache_quota;
+mod checksum;
+#[cfg(feature = "sqlite")]
+pub mod db_storage;
+pub mod dynamic_storage;
+pub mod encryption;
+pub mod expiring_storage;
 pub mod file_storage;
+pub mod folder_storage;
 #[cfg(feature = "jni-bindings")]
 pub mod jni;
+mod journal;
+mod lock;
+pub mod memory_storage;
+pub mod merge;
+pub mod migration;
 pub mod monoid;
+pub mod paths;
+pub mod snapshot;
+pub mod tag_set;
 mod utils;
+#[cfg(feature = "watch")]
+pub mod watch;
+
+pub use utils::now_millis;
 pub const ARK_FOLDER: &str = ".ark";
 
 // Should not be lost if possible
@@ -12,9 +35,15 @@ pub const FAVORITES_FILE: &str = "favorites";
 
 // User-defined data
 pub const TAG_STORAGE_FILE: &str = "user/tags";
+pub const TAG_ALIASES_STORAGE_FILE: &str = "user/tag_aliases";
+pub const TAG_METADATA_STORAGE_FILE: &str = "user/tag_metadata";
 pub const SCORE_STORAGE_FILE: &str = "user/scores";
+pub const RULES_STORAGE_FILE: &str = "user/rules";
 
 // Generated data
 pub const INDEX_PATH: &str = "index";
+pub const HASH_CACHE_PATH: &str = "cache/hashes";
 pub const PREVIEWS_STORAGE_FOLDER: &str = "cache/previews";
+pub const METADATA_STORAGE_FOLDER: &str = "cache/metadata";
+pub const TEXT_STORAGE_FOLDER: &str = "cache/text";
 pub const THUMBNAILS_STORAGE_FOLDER: &str = "cache/thumbnails";
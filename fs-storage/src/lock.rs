@@ -0,0 +1,95 @@
+//! Advisory locks of storage files shared between processes.
+//!
+//! The lock is taken on a separate file next to the storage file, with
+//! the `.lock` extension appended, since the storage file itself may be
+//! replaced while it is written.
+
+use std::ffi::OsString;
+use std::fs::{self, File, OpenOptions};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use fs2::FileExt;
+
+use data_error::{ArklibError, Result, ResultExt};
+
+const RETRY_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Lock released when dropped
+pub(crate) struct Lock {
+    file: File,
+}
+
+impl Drop for Lock {
+    fn drop(&mut self) {
+        let _ = self.file.unlock();
+    }
+}
+
+pub(crate) fn lock_path(path: &Path) -> PathBuf {
+    let mut lock = OsString::from(path.as_os_str());
+    lock.push(".lock");
+    PathBuf::from(lock)
+}
+
+/// Lock the storage file at `path`, waiting at most `timeout`
+/// for other processes to release it
+pub(crate) fn acquire(
+    label: &str,
+    path: &Path,
+    exclusive: bool,
+    timeout: Duration,
+) -> Result<Lock> {
+    let start = Instant::now();
+    loop {
+        if let Some(lock) = try_acquire(label, path, exclusive)? {
+            return Ok(lock);
+        }
+        if start.elapsed() >= timeout {
+            return Err(ArklibError::Storage(
+                label.to_owned(),
+                format!(
+                    "Storage is locked by another process (path: {})",
+                    path.display()
+                ),
+            ));
+        }
+        thread::sleep(RETRY_INTERVAL);
+    }
+}
+
+/// Lock the storage file at `path` if no other process holds a lock
+/// which conflicts
+pub(crate) fn try_acquire(
+    label: &str,
+    path: &Path,
+    exclusive: bool,
+) -> Result<Option<Lock>> {
+    let lock_path = lock_path(path);
+    if let Some(parent) = lock_path.parent() {
+        fs::create_dir_all(parent)
+            .with_path(parent)
+            .with_label(label)?;
+    }
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&lock_path)
+        .with_path(&lock_path)
+        .with_label(label)?;
+
+    let locked = if exclusive {
+        file.try_lock_exclusive()
+    } else {
+        file.try_lock_shared()
+    };
+    match locked {
+        Ok(()) => Ok(Some(Lock { file })),
+        Err(err) if err.kind() == fs2::lock_contended_error().kind() => {
+            Ok(None)
+        }
+        Err(err) => Err(err).with_path(&lock_path).with_label(label),
+    }
+}
@@ -0,0 +1,238 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+
+use crate::base_storage::{BaseStorage, SyncStatus};
+use crate::file_storage::{FileStorage, STORAGE_VERSION};
+use crate::monoid::Monoid;
+use crate::snapshot;
+use data_error::{ArklibError, Result};
+
+/// Represents a storage which is never written to disk,
+/// e.g. for tests, dry runs or ephemeral vaults.
+///
+/// Reading and writing do nothing, the storage is always in sync.
+/// [`MemoryStorage::persist`] turns it into a [`FileStorage`]
+/// once the data has to be kept.
+pub struct MemoryStorage<K, V>
+where
+    K: Ord,
+{
+    /// Label for logging
+    label: String,
+    entries: BTreeMap<K, V>,
+    /// Keys set or removed since the last `write_fs`
+    changed: BTreeSet<K>,
+}
+
+impl<K, V> MemoryStorage<K, V>
+where
+    K: Ord
+        + Clone
+        + serde::Serialize
+        + serde::de::DeserializeOwned
+        + std::str::FromStr,
+    V: Clone
+        + serde::Serialize
+        + serde::de::DeserializeOwned
+        + std::str::FromStr
+        + Monoid<V>,
+{
+    /// Create an empty storage with a diagnostic label
+    pub fn new(label: String) -> Self {
+        Self {
+            label,
+            entries: BTreeMap::new(),
+            changed: BTreeSet::new(),
+        }
+    }
+
+    /// Write the entries to a [`FileStorage`] at `path`, replacing the
+    /// entries it may already contain, and return that storage
+    pub fn persist(&self, path: &Path) -> Result<FileStorage<K, V>> {
+        let mut storage = FileStorage::new(self.label.clone(), path)?;
+        let stale: Vec<K> = storage
+            .as_ref()
+            .keys()
+            .filter(|key| !self.entries.contains_key(key))
+            .cloned()
+            .collect();
+        for key in stale.iter() {
+            storage.remove(key)?;
+        }
+        for (key, value) in self.entries.iter() {
+            storage.set(key.clone(), value.clone());
+        }
+        storage.write_fs()?;
+
+        log::info!(
+            "{} {} entries have been written to {}",
+            self.label,
+            self.entries.len(),
+            path.display()
+        );
+        Ok(storage)
+    }
+}
+
+impl<K, V> BaseStorage<K, V> for MemoryStorage<K, V>
+where
+    K: Ord
+        + Clone
+        + serde::Serialize
+        + serde::de::DeserializeOwned
+        + std::str::FromStr,
+    V: Clone
+        + serde::Serialize
+        + serde::de::DeserializeOwned
+        + std::str::FromStr
+        + Monoid<V>,
+{
+    /// Set a key-value pair in the internal mapping
+    fn set(&mut self, key: K, value: V) {
+        self.changed.insert(key.clone());
+        self.entries.insert(key, value);
+    }
+
+    /// Remove an entry from the internal mapping given a key
+    fn remove(&mut self, id: &K) -> Result<()> {
+        self.entries.remove(id).ok_or_else(|| {
+            ArklibError::Storage(self.label.clone(), "Key not found".to_owned())
+        })?;
+        self.changed.insert(id.clone());
+        Ok(())
+    }
+
+    /// Keys set or removed since the last `write_fs`,
+    /// i.e. what a dry run would have written
+    fn dirty_keys(&self) -> &BTreeSet<K> {
+        &self.changed
+    }
+
+    /// There is nothing to sync with
+    fn sync_status(&self) -> Result<SyncStatus> {
+        Ok(SyncStatus::InSync)
+    }
+
+    fn sync(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    /// The entries are only kept in memory
+    fn read_fs(&mut self) -> Result<&BTreeMap<K, V>> {
+        Ok(&self.entries)
+    }
+
+    /// The entries are only kept in memory
+    fn write_fs(&mut self) -> Result<()> {
+        self.changed.clear();
+        Ok(())
+    }
+
+    /// Nothing is stored on disk
+    fn erase(&self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Merge the data from another storage instance into this storage instance
+    fn merge_from(&mut self, other: impl AsRef<BTreeMap<K, V>>) -> Result<()>
+    where
+        V: Monoid<V>,
+    {
+        for (key, value) in other.as_ref() {
+            let value = match self.entries.get(key) {
+                Some(existing) => V::combine(existing, value),
+                None => value.clone(),
+            };
+            self.set(key.clone(), value);
+        }
+        Ok(())
+    }
+
+    /// Write the entries into a snapshot, in the format of a [`FileStorage`]
+    fn snapshot(&self, dest: &Path) -> Result<()> {
+        snapshot::create(&self.label, dest, STORAGE_VERSION)?;
+        self.persist(&snapshot::data_path(dest))?;
+        Ok(())
+    }
+
+    /// Replace the entries with the entries of a snapshot
+    /// of any storage in the format of a [`FileStorage`]
+    fn restore(&mut self, src: &Path) -> Result<()> {
+        snapshot::read_manifest(&self.label, src, STORAGE_VERSION)?;
+        let restored: FileStorage<K, V> =
+            FileStorage::new(self.label.clone(), &snapshot::data_path(src))?;
+        self.entries = restored.as_ref().clone();
+        self.changed.clear();
+        Ok(())
+    }
+}
+
+impl<K, V> AsRef<BTreeMap<K, V>> for MemoryStorage<K, V>
+where
+    K: Ord,
+{
+    fn as_ref(&self) -> &BTreeMap<K, V> {
+        &self.entries
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+
+    use crate::{
+        base_storage::{BaseStorage, SyncStatus},
+        file_storage::FileStorage,
+        memory_storage::MemoryStorage,
+    };
+
+    #[test]
+    fn test_memory_storage_persist() {
+        let temp_dir =
+            TempDir::new("tmp").expect("Failed to create temporary directory");
+        let storage_path = temp_dir.path().join("storage.json");
+
+        let mut existing =
+            FileStorage::new("Existing".to_string(), &storage_path).unwrap();
+        existing.set("stale".to_string(), 1);
+        existing.write_fs().unwrap();
+
+        let mut storage = MemoryStorage::new("TestStorage".to_string());
+        storage.set("key1".to_string(), 2);
+        storage.set("key2".to_string(), 3);
+        storage.merge_from(&existing).unwrap();
+        storage.remove(&"stale".to_string()).unwrap();
+        assert_eq!(storage.dirty_keys().len(), 3);
+        assert_eq!(storage.len(), 2);
+        assert_eq!(storage.get(&"key1".to_string()), Some(&2));
+        assert!(!storage.contains_key(&"stale".to_string()));
+        assert!(storage.keys().eq(["key1", "key2"].iter()));
+        storage.write_fs().unwrap();
+        assert!(storage.dirty_keys().is_empty());
+        assert_eq!(storage.sync_status().unwrap(), SyncStatus::InSync);
+
+        let promoted = storage.persist(&storage_path).unwrap();
+        assert_eq!(promoted.as_ref(), storage.as_ref());
+        let reloaded: FileStorage<String, i32> =
+            FileStorage::new("Reloaded".to_string(), &storage_path).unwrap();
+        assert_eq!(reloaded.as_ref(), storage.as_ref());
+    }
+
+    #[test]
+    fn test_memory_storage_range_queries() {
+        let mut storage = MemoryStorage::new("TestStorage".to_string());
+        for tag in ["art", "article", "artist", "bird", "arc"] {
+            storage.set(tag.to_string(), 1);
+        }
+
+        let completions: Vec<_> = storage.keys_with_prefix("art").collect();
+        assert_eq!(completions, ["art", "article", "artist"]);
+        assert_eq!(storage.keys_with_prefix("z").count(), 0);
+
+        let range: Vec<_> = storage
+            .range("arc".to_string().."artist".to_string())
+            .map(|(key, _)| key.as_str())
+            .collect();
+        assert_eq!(range, ["arc", "art", "article"]);
+    }
+}
@@ -0,0 +1,143 @@
+//! Three-way merge of key-value mappings which diverged from a common
+//! base, e.g. the mapping of a storage and its file when both changed
+//! since they were last synced.
+//!
+//! Unlike [`BaseStorage::merge_from`](crate::base_storage::BaseStorage::merge_from),
+//! the base tells a key removed on one side from a key added on the other.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::Serialize;
+
+use crate::monoid::Monoid;
+
+/// How the keys which changed on either side were resolved
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyncReport<K: Ord> {
+    /// Keys set or removed only on the other side, taken from it
+    pub taken: BTreeSet<K>,
+    /// Keys set to different values on both sides, whose values
+    /// were combined with [`Monoid::combine`]
+    pub combined: BTreeSet<K>,
+    /// Keys removed on one side but set on the other, whose value was kept
+    pub kept: BTreeSet<K>,
+}
+
+impl<K: Ord> Default for SyncReport<K> {
+    fn default() -> Self {
+        SyncReport {
+            taken: BTreeSet::new(),
+            combined: BTreeSet::new(),
+            kept: BTreeSet::new(),
+        }
+    }
+}
+
+impl<K: Ord> SyncReport<K> {
+    /// Whether both sides changed some keys differently
+    pub fn has_conflicts(&self) -> bool {
+        !self.combined.is_empty() || !self.kept.is_empty()
+    }
+}
+
+/// Merge the `local` and `remote` changes of `base`.
+///
+/// A key changed on one side only gets the value of that side, including
+/// removals. A key changed on both sides gets the combination of both
+/// values, or the value of the side which didn't remove it.
+pub fn merge_three_way<K, V>(
+    base: &BTreeMap<K, V>,
+    local: &BTreeMap<K, V>,
+    remote: &BTreeMap<K, V>,
+) -> (BTreeMap<K, V>, SyncReport<K>)
+where
+    K: Ord + Clone,
+    V: Clone + Serialize + Monoid<V>,
+{
+    let mut merged = BTreeMap::new();
+    let mut report = SyncReport::default();
+
+    let keys: BTreeSet<&K> = base
+        .keys()
+        .chain(local.keys())
+        .chain(remote.keys())
+        .collect();
+    for key in keys {
+        let (base_value, local_value, remote_value) =
+            (base.get(key), local.get(key), remote.get(key));
+        let value = if same(local_value, remote_value) {
+            local_value
+        } else if same(local_value, base_value) {
+            report.taken.insert(key.clone());
+            remote_value
+        } else if same(remote_value, base_value) {
+            local_value
+        } else {
+            match (local_value, remote_value) {
+                (Some(local_value), Some(remote_value)) => {
+                    report.combined.insert(key.clone());
+                    merged.insert(
+                        key.clone(),
+                        V::combine(local_value, remote_value),
+                    );
+                    continue;
+                }
+                (Some(value), None) | (None, Some(value)) => {
+                    report.kept.insert(key.clone());
+                    Some(value)
+                }
+                // both sides removed the key
+                (None, None) => None,
+            }
+        };
+        if let Some(value) = value {
+            merged.insert(key.clone(), value.clone());
+        }
+    }
+    (merged, report)
+}
+
+/// Values are compared by their serialization,
+/// so they don't have to implement `PartialEq`
+pub(crate) fn same<V: Serialize>(a: Option<&V>, b: Option<&V>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => {
+            match (serde_json::to_vec(a), serde_json::to_vec(b)) {
+                (Ok(a), Ok(b)) => a == b,
+                _ => false,
+            }
+        }
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::merge_three_way;
+
+    fn mapping(entries: &[(&str, i32)]) -> BTreeMap<String, i32> {
+        entries
+            .iter()
+            .map(|(key, value)| (key.to_string(), *value))
+            .collect()
+    }
+
+    #[test]
+    fn test_three_way_merge_keeps_removals() {
+        let base = mapping(&[("a", 1), ("b", 2), ("c", 3), ("d", 4)]);
+        // removed "a", changed "c" and "d", added "e"
+        let local = mapping(&[("b", 2), ("c", 5), ("d", 6), ("e", 7)]);
+        // removed "b" and "d", changed "c", added "f"
+        let remote = mapping(&[("a", 1), ("c", 8), ("f", 9)]);
+
+        let (merged, report) = merge_three_way(&base, &local, &remote);
+        assert_eq!(merged, mapping(&[("c", 8), ("d", 6), ("e", 7), ("f", 9)]));
+        assert!(report.taken.iter().eq(["b", "f"].iter()));
+        assert!(report.combined.iter().eq(["c"].iter()));
+        assert!(report.kept.iter().eq(["d"].iter()));
+        assert!(report.has_conflicts());
+    }
+}
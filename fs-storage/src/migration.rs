@@ -0,0 +1,153 @@
+//! Upgrades of storage files written by older versions of `FileStorage`.
+//!
+//! Every bump of the storage version ships a [`Migration`] from the
+//! previous version, registered in [`MIGRATIONS`]. Older files are upgraded
+//! one version at a time when they are read, then written back in the
+//! current version, the original file being kept next to it as a backup.
+//!
+//! Version 2 predates this registry, it is read by a dedicated parser
+//! and migrated in place the same way.
+
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde_json::Value;
+
+use data_error::{ArklibError, Result, ResultExt};
+
+/// Upgrade of the data of a storage from `from_version`
+/// to the next version
+pub trait Migration: Send + Sync {
+    /// Version of the data accepted by [`Migration::migrate`]
+    fn from_version(&self) -> i32;
+
+    /// Convert the whole content of a storage file, including its
+    /// `version` field, which is set by [`upgrade`] afterwards
+    fn migrate(&self, data: Value) -> Result<Value>;
+}
+
+/// Migrations shipped with the current storage version,
+/// at most one per version
+pub static MIGRATIONS: &[&dyn Migration] = &[];
+
+/// Upgrade `data` of version `from` to version `to`
+/// by chaining `migrations`
+pub fn upgrade(
+    label: &str,
+    mut data: Value,
+    from: i32,
+    to: i32,
+    migrations: &[&dyn Migration],
+) -> Result<Value> {
+    for version in from..to {
+        let migration = migrations
+            .iter()
+            .find(|migration| migration.from_version() == version)
+            .ok_or_else(|| {
+                ArklibError::Storage(
+                    label.to_owned(),
+                    format!("No migration from storage version {}", version),
+                )
+            })?;
+        data = migration.migrate(data)?;
+        match data.as_object_mut() {
+            Some(fields) => {
+                fields.insert("version".to_owned(), Value::from(version + 1));
+            }
+            None => {
+                return Err(ArklibError::Storage(
+                    label.to_owned(),
+                    format!(
+                        "Migration from storage version {} \
+                         didn't return an object",
+                        version
+                    ),
+                ))
+            }
+        }
+        log::info!(
+            "{} migrated from version {} to {}",
+            label,
+            version,
+            version + 1
+        );
+    }
+    Ok(data)
+}
+
+/// Path of the copy of a storage file of `version` kept by a migration
+pub fn backup_path(path: &Path, version: i32) -> PathBuf {
+    let mut backup = OsString::from(path.as_os_str());
+    backup.push(format!(".v{}.bak", version));
+    PathBuf::from(backup)
+}
+
+/// Copy the storage file at `path` before it is migrated from `version`
+pub(crate) fn backup(label: &str, path: &Path, version: i32) -> Result<()> {
+    let backup = backup_path(path, version);
+    fs::copy(path, &backup)
+        .with_path(&backup)
+        .with_label(label)?;
+    log::info!("{} backed up to {}", label, backup.display());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    /// Renames `entries` to `items`
+    struct Rename;
+
+    impl Migration for Rename {
+        fn from_version(&self) -> i32 {
+            3
+        }
+
+        fn migrate(&self, mut data: Value) -> Result<Value> {
+            if let Some(fields) = data.as_object_mut() {
+                let entries = fields.remove("entries").unwrap_or_default();
+                fields.insert("items".to_owned(), entries);
+            }
+            Ok(data)
+        }
+    }
+
+    /// Wraps every item into an object
+    struct Wrap;
+
+    impl Migration for Wrap {
+        fn from_version(&self) -> i32 {
+            4
+        }
+
+        fn migrate(&self, mut data: Value) -> Result<Value> {
+            if let Some(items) = data["items"].as_object_mut() {
+                for item in items.values_mut() {
+                    *item = json!({ "value": item.take() });
+                }
+            }
+            Ok(data)
+        }
+    }
+
+    #[test]
+    fn test_upgrade_chains_migrations() {
+        let data = json!({ "version": 3, "entries": { "a": 1 } });
+        let upgraded = upgrade("Test", data, 3, 5, &[&Wrap, &Rename]).unwrap();
+        assert_eq!(
+            upgraded,
+            json!({ "version": 5, "items": { "a": { "value": 1 } } })
+        );
+    }
+
+    #[test]
+    fn test_upgrade_without_migration_fails() {
+        let data = json!({ "version": 3, "entries": {} });
+        assert!(upgrade("Test", data.clone(), 3, 5, &[&Rename]).is_err());
+        assert_eq!(upgrade("Test", data.clone(), 3, 3, &[]).unwrap(), data);
+    }
+}
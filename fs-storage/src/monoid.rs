@@ -4,6 +4,15 @@
 // CRDTs can be considered later when we need to add structures that require
 // more powerful combine semantics.
 
+use std::cmp::Ordering;
+use std::collections::BTreeSet;
+use std::str::FromStr;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::now_millis;
+
 // Trait defining a Monoid, which represents a mathematical structure with an identity element and an associative binary operation.
 pub trait Monoid<V> {
     // Returns the neutral element of the monoid.
@@ -35,6 +44,16 @@ impl Monoid<i32> for i32 {
     }
 }
 
+impl Monoid<i64> for i64 {
+    fn neutral() -> i64 {
+        0
+    }
+
+    fn combine(a: &i64, b: &i64) -> i64 {
+        *a.max(b)
+    }
+}
+
 impl Monoid<String> for String {
     fn neutral() -> String {
         String::new()
@@ -46,3 +65,262 @@ impl Monoid<String> for String {
         result
     }
 }
+
+// Schema-less values are merged with `data_json::merge`,
+// `null` being its neutral element
+impl Monoid<serde_json::Value> for serde_json::Value {
+    fn neutral() -> serde_json::Value {
+        serde_json::Value::Null
+    }
+
+    fn combine(
+        a: &serde_json::Value,
+        b: &serde_json::Value,
+    ) -> serde_json::Value {
+        data_json::merge(a.clone(), b.clone())
+    }
+}
+
+// Ready-made monoids for common values. They are serialized as their
+// inner value, except `LastWriteWins` which keeps its timestamp, and
+// parsed from JSON.
+
+/// Register whose most recently written value wins,
+/// e.g. the title or the description of a resource.
+///
+/// Plain values, e.g. written before they were timestamped,
+/// are read as written at time 0.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize,
+)]
+#[serde(from = "LastWriteWinsRepr<V>")]
+pub struct LastWriteWins<V> {
+    pub value: V,
+    /// Milliseconds since the Unix epoch
+    pub timestamp: u64,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum LastWriteWinsRepr<V> {
+    Written { value: V, timestamp: u64 },
+    Plain(V),
+}
+
+impl<V> From<LastWriteWinsRepr<V>> for LastWriteWins<V> {
+    fn from(repr: LastWriteWinsRepr<V>) -> Self {
+        match repr {
+            LastWriteWinsRepr::Written { value, timestamp } => {
+                LastWriteWins { value, timestamp }
+            }
+            LastWriteWinsRepr::Plain(value) => LastWriteWins {
+                value,
+                timestamp: 0,
+            },
+        }
+    }
+}
+
+impl<V> LastWriteWins<V> {
+    /// Value written now
+    pub fn new(value: V) -> Self {
+        LastWriteWins {
+            value,
+            timestamp: now_millis(),
+        }
+    }
+}
+
+// Values written at the same time are combined
+impl<V: Monoid<V> + Clone> Monoid<LastWriteWins<V>> for LastWriteWins<V> {
+    fn neutral() -> LastWriteWins<V> {
+        LastWriteWins {
+            value: V::neutral(),
+            timestamp: 0,
+        }
+    }
+
+    fn combine(a: &LastWriteWins<V>, b: &LastWriteWins<V>) -> LastWriteWins<V> {
+        match a.timestamp.cmp(&b.timestamp) {
+            Ordering::Greater => a.clone(),
+            Ordering::Less => b.clone(),
+            Ordering::Equal => LastWriteWins {
+                value: V::combine(&a.value, &b.value),
+                timestamp: a.timestamp,
+            },
+        }
+    }
+}
+
+/// Set whose values are merged into their union, e.g. collections
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Union<T: Ord>(pub BTreeSet<T>);
+
+impl<T: Ord> Default for Union<T> {
+    fn default() -> Self {
+        Union(BTreeSet::new())
+    }
+}
+
+impl<T: Ord + Clone> Monoid<Union<T>> for Union<T> {
+    fn neutral() -> Union<T> {
+        Union::default()
+    }
+
+    fn combine(a: &Union<T>, b: &Union<T>) -> Union<T> {
+        Union(a.0.union(&b.0).cloned().collect())
+    }
+}
+
+/// Greatest of the values, e.g. the highest score
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Max<T>(pub T);
+
+/// Least of the values, e.g. the first time a resource was seen
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Min<T>(pub T);
+
+/// Sum of the values, saturating at the bounds of the type,
+/// e.g. the number of times a resource was opened
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Sum<T>(pub T);
+
+macro_rules! numeric_monoids {
+    ($($number:ty),*) => {
+        $(
+            impl Monoid<Max<$number>> for Max<$number> {
+                fn neutral() -> Max<$number> {
+                    Max(<$number>::MIN)
+                }
+
+                fn combine(a: &Max<$number>, b: &Max<$number>) -> Max<$number> {
+                    Max(a.0.max(b.0))
+                }
+            }
+
+            impl Monoid<Min<$number>> for Min<$number> {
+                fn neutral() -> Min<$number> {
+                    Min(<$number>::MAX)
+                }
+
+                fn combine(a: &Min<$number>, b: &Min<$number>) -> Min<$number> {
+                    Min(a.0.min(b.0))
+                }
+            }
+
+            impl Monoid<Sum<$number>> for Sum<$number> {
+                fn neutral() -> Sum<$number> {
+                    Sum(0)
+                }
+
+                fn combine(a: &Sum<$number>, b: &Sum<$number>) -> Sum<$number> {
+                    Sum(a.0.saturating_add(b.0))
+                }
+            }
+        )*
+    };
+}
+
+numeric_monoids!(i8, i16, i32, i64, u8, u16, u32, u64, usize);
+
+/// Counter which never exceeds `LIMIT`, e.g. a rating
+/// incremented by several devices
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize,
+)]
+#[serde(transparent)]
+pub struct Capped<const LIMIT: u64>(pub u64);
+
+impl<const LIMIT: u64> Capped<LIMIT> {
+    pub fn new(count: u64) -> Self {
+        Capped(count.min(LIMIT))
+    }
+}
+
+impl<const LIMIT: u64> Monoid<Capped<LIMIT>> for Capped<LIMIT> {
+    fn neutral() -> Capped<LIMIT> {
+        Capped(0)
+    }
+
+    fn combine(a: &Capped<LIMIT>, b: &Capped<LIMIT>) -> Capped<LIMIT> {
+        Capped::new(a.0.saturating_add(b.0))
+    }
+}
+
+macro_rules! from_json {
+    ($($monoid:ident [$($bound:tt)*]),*) => {
+        $(
+            impl<T: $($bound)*> FromStr for $monoid<T> {
+                type Err = serde_json::Error;
+
+                fn from_str(s: &str) -> Result<Self, Self::Err> {
+                    serde_json::from_str(s)
+                }
+            }
+        )*
+    };
+}
+
+from_json!(
+    LastWriteWins[DeserializeOwned],
+    Union[Ord + DeserializeOwned],
+    Max[DeserializeOwned],
+    Min[DeserializeOwned],
+    Sum[DeserializeOwned]
+);
+
+impl<const LIMIT: u64> FromStr for Capped<LIMIT> {
+    type Err = serde_json::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        serde_json::from_str(s).map(|Capped(count)| Capped::new(count))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_last_write_wins() {
+        let old = LastWriteWins {
+            value: "old".to_string(),
+            timestamp: 1,
+        };
+        let new = LastWriteWins::new("new".to_string());
+        assert_eq!(LastWriteWins::combine(&old, &new), new);
+        assert_eq!(LastWriteWins::combine(&new, &old), new);
+        assert_eq!(
+            LastWriteWins::combine(&old, &LastWriteWins::neutral()),
+            old
+        );
+    }
+
+    #[test]
+    fn test_numeric_monoids() {
+        let values = [3, -1, 7];
+        assert_eq!(Max::combine_all(values.map(Max)), Max(7));
+        assert_eq!(Min::combine_all(values.map(Min)), Min(-1));
+        assert_eq!(Sum::combine_all(values.map(Sum)), Sum(9));
+        assert_eq!(Sum::combine(&Sum(u8::MAX), &Sum(1)), Sum(u8::MAX));
+    }
+
+    #[test]
+    fn test_union_and_capped() {
+        let a: Union<i32> = "[1, 2]".parse().unwrap();
+        let b: Union<i32> = "[2, 3]".parse().unwrap();
+        assert!(Union::combine(&a, &b)
+            .0
+            .iter()
+            .eq([1, 2, 3].iter()));
+
+        let count: Capped<5> = "7".parse().unwrap();
+        assert_eq!(count, Capped(5));
+        assert_eq!(Capped::<5>::combine(&Capped(2), &Capped(2)), Capped(4));
+        assert_eq!(Capped::<5>::combine(&Capped(4), &Capped(4)), Capped(5));
+    }
+}
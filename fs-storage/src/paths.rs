@@ -0,0 +1,102 @@
+//! Windows paths longer than `MAX_PATH` and paths of network shares.
+//!
+//! Prefixing a path with `\\?\` lifts the limit of 260 characters, UNC
+//! paths like `\\server\share` being written `\\?\UNC\server\share`.
+//! `std::fs::canonicalize` returns such paths, so a root spelled
+//! `C:\vault` has to be compared with `\\?\C:\vault\file`.
+//! Other platforms have a single spelling, the functions below keep
+//! their paths unchanged.
+
+use std::path::{Path, PathBuf};
+
+const VERBATIM: &str = r"\\?\";
+const VERBATIM_UNC: &str = r"\\?\UNC\";
+const DEVICE: &str = r"\\.\";
+
+/// Spelling of an absolute `path` which isn't limited to `MAX_PATH`
+pub fn extended_length(path: &Path) -> PathBuf {
+    if !cfg!(windows) {
+        return path.to_owned();
+    }
+    match path.to_str().and_then(to_extended) {
+        Some(extended) => PathBuf::from(extended),
+        None => path.to_owned(),
+    }
+}
+
+/// Spelling of `path` without the `\\?\` prefix, e.g. to display it
+/// or to compare it with a path given by the user
+pub fn simplified(path: &Path) -> PathBuf {
+    if !cfg!(windows) {
+        return path.to_owned();
+    }
+    match path.to_str().and_then(to_simplified) {
+        Some(simplified) => PathBuf::from(simplified),
+        None => path.to_owned(),
+    }
+}
+
+fn to_extended(path: &str) -> Option<String> {
+    if path.starts_with(VERBATIM) || path.starts_with(DEVICE) {
+        return None;
+    }
+    // verbatim paths are not normalized by Windows
+    let path = path.replace('/', r"\");
+    if let Some(share) = path.strip_prefix(r"\\") {
+        return Some(format!("{}{}", VERBATIM_UNC, share));
+    }
+    let mut chars = path.chars();
+    match (chars.next(), chars.next(), chars.next()) {
+        (Some(drive), Some(':'), Some('\\')) if drive.is_ascii_alphabetic() => {
+            Some(format!("{}{}", VERBATIM, path))
+        }
+        // relative paths can't be verbatim
+        _ => None,
+    }
+}
+
+fn to_simplified(path: &str) -> Option<String> {
+    if let Some(share) = path.strip_prefix(VERBATIM_UNC) {
+        return Some(format!(r"\\{}", share));
+    }
+    let rest = path.strip_prefix(VERBATIM)?;
+    let mut chars = rest.chars();
+    match (chars.next(), chars.next()) {
+        (Some(drive), Some(':')) if drive.is_ascii_alphabetic() => {
+            Some(rest.to_owned())
+        }
+        // e.g. volume GUIDs only exist in verbatim form
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn drive_paths_round_trip() {
+        let extended = to_extended(r"C:\vault/photos").unwrap();
+        assert_eq!(extended, r"\\?\C:\vault\photos");
+        assert_eq!(to_simplified(&extended).unwrap(), r"C:\vault\photos");
+        assert_eq!(to_extended(&extended), None);
+        assert_eq!(to_extended(r"vault\photos"), None);
+    }
+
+    #[test]
+    fn unc_paths_round_trip() {
+        let extended = to_extended(r"\\server\share\vault").unwrap();
+        assert_eq!(extended, r"\\?\UNC\server\share\vault");
+        assert_eq!(to_simplified(&extended).unwrap(), r"\\server\share\vault");
+        assert_eq!(to_extended(r"\\.\pipe\name"), None);
+        assert_eq!(to_simplified(r"\\?\Volume{1234}\vault"), None);
+    }
+
+    #[test]
+    #[cfg(not(windows))]
+    fn other_platforms_are_unchanged() {
+        let path = Path::new("/home/user/vault");
+        assert_eq!(extended_length(path), path);
+        assert_eq!(simplified(path), path);
+    }
+}
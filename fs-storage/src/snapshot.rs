@@ -0,0 +1,154 @@
+//! Snapshots of the persisted state of storages, see
+//! [`BaseStorage::snapshot`](crate::base_storage::BaseStorage::snapshot).
+//!
+//! A snapshot is a folder with a [`SNAPSHOT_MANIFEST`] describing the
+//! storage, and a copy of its file, or folder, named [`SNAPSHOT_DATA`].
+//! The copy is left in the format of the storage, so a snapshot of a
+//! [`FileStorage`](crate::file_storage::FileStorage) can be opened as is.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::journal::journal_path;
+use data_error::{ArklibError, Result, ResultExt};
+
+/// Name of the manifest of a snapshot
+pub const SNAPSHOT_MANIFEST: &str = "snapshot.json";
+/// Name of the copy of the persisted state in a snapshot
+pub const SNAPSHOT_DATA: &str = "data";
+
+/// Describes the storage a snapshot was taken of
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SnapshotManifest {
+    /// Label of the storage
+    pub label: String,
+    /// Version of the storage format
+    pub version: i32,
+    /// Creation time, in seconds since UNIX epoch
+    pub created: u64,
+}
+
+/// Path of the copy of the persisted state in the snapshot at `path`
+pub fn data_path(path: &Path) -> PathBuf {
+    path.join(SNAPSHOT_DATA)
+}
+
+/// Read the manifest of the snapshot at `path`, checking that
+/// its version is supported
+pub fn read_manifest(
+    label: &str,
+    path: &Path,
+    supported: i32,
+) -> Result<SnapshotManifest> {
+    let manifest_path = path.join(SNAPSHOT_MANIFEST);
+    let content = fs::read(&manifest_path)
+        .with_path(&manifest_path)
+        .with_label(label)?;
+    let manifest: SnapshotManifest = serde_json::from_slice(&content)
+        .with_path(&manifest_path)
+        .with_label(label)?;
+    if manifest.version > supported {
+        return Err(ArklibError::UnsupportedVersion(
+            manifest.label,
+            manifest.version,
+            supported,
+        ))
+        .with_path(path);
+    }
+    if manifest.label != label {
+        log::warn!(
+            "{} restores a snapshot of {} from {}",
+            label,
+            manifest.label,
+            path.display()
+        );
+    }
+    Ok(manifest)
+}
+
+/// Create the folder of a snapshot at `path` and write its manifest
+pub(crate) fn create(label: &str, path: &Path, version: i32) -> Result<()> {
+    fs::create_dir_all(path)
+        .with_path(path)
+        .with_label(label)?;
+    let manifest = SnapshotManifest {
+        label: label.to_owned(),
+        version,
+        created: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_secs())
+            .unwrap_or(0),
+    };
+    let manifest_path = path.join(SNAPSHOT_MANIFEST);
+    let content = serde_json::to_vec_pretty(&manifest)
+        .with_path(&manifest_path)
+        .with_label(label)?;
+    fs::write(&manifest_path, content)
+        .with_path(&manifest_path)
+        .with_label(label)
+}
+
+/// Copy a storage file and its journal, if any, replacing the destination
+pub(crate) fn copy_file(label: &str, from: &Path, to: &Path) -> Result<()> {
+    if !from.is_file() {
+        return Err(ArklibError::Storage(
+            label.to_owned(),
+            format!("Nothing to copy at {}", from.display()),
+        ));
+    }
+    if let Some(parent) = to.parent() {
+        fs::create_dir_all(parent)
+            .with_path(parent)
+            .with_label(label)?;
+    }
+    fs::copy(from, to)
+        .with_path(to)
+        .with_label(label)?;
+
+    let (from_journal, to_journal) = (journal_path(from), journal_path(to));
+    if from_journal.exists() {
+        fs::copy(&from_journal, &to_journal)
+            .with_path(&to_journal)
+            .with_label(label)?;
+    } else if to_journal.exists() {
+        fs::remove_file(&to_journal)
+            .with_path(&to_journal)
+            .with_label(label)?;
+    }
+    Ok(())
+}
+
+/// Copy the files of a storage folder, replacing the destination
+pub(crate) fn copy_folder(label: &str, from: &Path, to: &Path) -> Result<()> {
+    if !from.is_dir() {
+        return Err(ArklibError::Storage(
+            label.to_owned(),
+            format!("Nothing to copy at {}", from.display()),
+        ));
+    }
+    if to.exists() {
+        fs::remove_dir_all(to)
+            .with_path(to)
+            .with_label(label)?;
+    }
+    fs::create_dir_all(to)
+        .with_path(to)
+        .with_label(label)?;
+    for entry in fs::read_dir(from)
+        .with_path(from)
+        .with_label(label)?
+    {
+        let entry = entry.with_path(from).with_label(label)?;
+        let path = entry.path();
+        if path.is_file() {
+            let target = to.join(entry.file_name());
+            fs::copy(&path, &target)
+                .with_path(&target)
+                .with_label(label)?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,267 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::convert::Infallible;
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::monoid::Monoid;
+
+/// Tags of a single resource, as kept in the tags storage.
+///
+/// Version 2 storages keep them as a comma-separated list.
+/// Merging two sets results in their union.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct TagSet(pub BTreeSet<String>);
+
+impl FromStr for TagSet {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(TagSet(
+            s.split(',')
+                .map(str::trim)
+                .filter(|tag| !tag.is_empty())
+                .map(str::to_owned)
+                .collect(),
+        ))
+    }
+}
+
+impl fmt::Display for TagSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let tags: Vec<&str> = self.0.iter().map(String::as_str).collect();
+        write!(f, "{}", tags.join(","))
+    }
+}
+
+impl Monoid<TagSet> for TagSet {
+    fn neutral() -> TagSet {
+        TagSet::default()
+    }
+
+    fn combine(a: &TagSet, b: &TagSet) -> TagSet {
+        TagSet(a.0.union(&b.0).cloned().collect())
+    }
+}
+
+/// Tags of a single resource, remembering when each tag was added and
+/// removed, so that merging never brings back a removed tag.
+///
+/// A tag is present if it was added after it was last removed, removals
+/// winning over additions made at the same time. Merging two sets keeps
+/// the latest addition and the latest removal of every tag: adding a tag
+/// again after removing it works, but a device that didn't see the
+/// removal can't undo it.
+///
+/// Plain lists of tags, as [`TagSet`] serializes them, and comma-separated
+/// strings of tags are read as tags added at time 0.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(from = "TombstonesRepr")]
+pub struct TagSetWithTombstones {
+    /// Tag to the time it was last added, in milliseconds since the epoch
+    added: BTreeMap<String, u64>,
+    /// Tag to the time it was last removed, in milliseconds since the epoch
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    removed: BTreeMap<String, u64>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum TombstonesRepr {
+    List(BTreeSet<String>),
+    Plain(String),
+    Tracked {
+        added: BTreeMap<String, u64>,
+        #[serde(default)]
+        removed: BTreeMap<String, u64>,
+    },
+}
+
+impl From<TombstonesRepr> for TagSetWithTombstones {
+    fn from(repr: TombstonesRepr) -> Self {
+        match repr {
+            TombstonesRepr::List(tags) => TagSet(tags).into(),
+            TombstonesRepr::Plain(tags) => {
+                tags.parse::<TagSet>().unwrap_or_default().into()
+            }
+            TombstonesRepr::Tracked { added, removed } => {
+                TagSetWithTombstones { added, removed }
+            }
+        }
+    }
+}
+
+impl From<TagSet> for TagSetWithTombstones {
+    fn from(tags: TagSet) -> Self {
+        TagSetWithTombstones {
+            added: tags.0.into_iter().map(|tag| (tag, 0)).collect(),
+            removed: BTreeMap::new(),
+        }
+    }
+}
+
+impl TagSetWithTombstones {
+    /// Add `tag` at `timestamp`. Returns `false` if the tag was present
+    /// already, or was removed later.
+    pub fn insert(&mut self, tag: &str, timestamp: u64) -> bool {
+        let was_present = self.contains(tag);
+        let added = self.added.entry(tag.to_owned()).or_default();
+        *added = (*added).max(timestamp);
+        !was_present && self.contains(tag)
+    }
+
+    /// Remove `tag` at `timestamp`. Returns `false` if the tag wasn't
+    /// present, or was added later.
+    pub fn remove(&mut self, tag: &str, timestamp: u64) -> bool {
+        let was_present = self.contains(tag);
+        let removed = self.removed.entry(tag.to_owned()).or_default();
+        *removed = (*removed).max(timestamp);
+        was_present && !self.contains(tag)
+    }
+
+    pub fn contains(&self, tag: &str) -> bool {
+        self.added.get(tag).is_some_and(|added| {
+            self.removed
+                .get(tag)
+                .map_or(true, |removed| added > removed)
+        })
+    }
+
+    /// Present tags, sorted
+    pub fn iter(&self) -> impl Iterator<Item = &str> {
+        self.added
+            .keys()
+            .filter(|tag| self.contains(tag))
+            .map(String::as_str)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.iter().next().is_none()
+    }
+
+    /// Present tags, without the history of the set
+    pub fn tags(&self) -> TagSet {
+        TagSet(self.iter().map(str::to_owned).collect())
+    }
+
+    /// Forget the tags removed before `timestamp`, e.g. once every device
+    /// synced past it. Returns the number of tombstones dropped.
+    pub fn prune_tombstones(&mut self, timestamp: u64) -> usize {
+        let pruned: Vec<String> = self
+            .removed
+            .iter()
+            .filter(|(tag, removed)| {
+                **removed < timestamp && !self.contains(tag)
+            })
+            .map(|(tag, _)| tag.clone())
+            .collect();
+        for tag in &pruned {
+            self.removed.remove(tag);
+            self.added.remove(tag);
+        }
+        pruned.len()
+    }
+}
+
+impl FromStr for TagSetWithTombstones {
+    type Err = serde_json::Error;
+
+    /// JSON, or a comma-separated list of tags added at time 0
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.trim_start().starts_with('{') {
+            return serde_json::from_str(s);
+        }
+        let tags: TagSet = s.parse().unwrap_or_default();
+        Ok(tags.into())
+    }
+}
+
+impl Monoid<TagSetWithTombstones> for TagSetWithTombstones {
+    fn neutral() -> TagSetWithTombstones {
+        TagSetWithTombstones::default()
+    }
+
+    fn combine(
+        a: &TagSetWithTombstones,
+        b: &TagSetWithTombstones,
+    ) -> TagSetWithTombstones {
+        fn latest(
+            a: &BTreeMap<String, u64>,
+            b: &BTreeMap<String, u64>,
+        ) -> BTreeMap<String, u64> {
+            let mut merged = a.clone();
+            for (tag, timestamp) in b {
+                let entry = merged.entry(tag.clone()).or_default();
+                *entry = (*entry).max(*timestamp);
+            }
+            merged
+        }
+        TagSetWithTombstones {
+            added: latest(&a.added, &b.added),
+            removed: latest(&a.removed, &b.removed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_and_displays_comma_separated_list() {
+        let tags: TagSet = " b, a,,a ".parse().unwrap();
+        assert_eq!(tags.0.len(), 2);
+        assert_eq!(tags.to_string(), "a,b");
+    }
+
+    #[test]
+    fn combine_is_union() {
+        let a: TagSet = "a,b".parse().unwrap();
+        let b: TagSet = "b,c".parse().unwrap();
+        assert_eq!(TagSet::combine(&a, &b).to_string(), "a,b,c");
+        assert_eq!(TagSet::combine(&a, &TagSet::neutral()), a);
+    }
+
+    #[test]
+    fn combine_keeps_removals() {
+        let mut laptop =
+            TagSetWithTombstones::from("sea,sky".parse::<TagSet>().unwrap());
+        let mut phone = laptop.clone();
+        assert!(laptop.remove("sky", 10));
+        assert!(phone.insert("sun", 20));
+        assert!(!phone.insert("sea", 5));
+
+        let merged = TagSetWithTombstones::combine(&laptop, &phone);
+        assert_eq!(merged.tags().to_string(), "sea,sun");
+        assert_eq!(TagSetWithTombstones::combine(&phone, &laptop), merged);
+
+        // adding the tag again after its removal brings it back
+        let mut again = merged.clone();
+        assert!(again.insert("sky", 30));
+        let merged = TagSetWithTombstones::combine(&merged, &again);
+        assert_eq!(merged.tags().to_string(), "sea,sky,sun");
+
+        // removals win over additions made at the same time
+        assert!(again.remove("sun", 20));
+        assert!(!again.contains("sun"));
+        assert_eq!(again.prune_tombstones(25), 1);
+    }
+
+    #[test]
+    fn reads_plain_tag_lists() {
+        let plain: TagSetWithTombstones =
+            serde_json::from_str(r#"["a","b"]"#).unwrap();
+        assert_eq!(plain.tags().to_string(), "a,b");
+        assert_eq!("a, b".parse::<TagSetWithTombstones>().unwrap(), plain);
+        let string: TagSetWithTombstones =
+            serde_json::from_str(r#""a, b""#).unwrap();
+        assert_eq!(string, plain);
+
+        let mut tracked = plain.clone();
+        tracked.remove("a", 1);
+        let json = serde_json::to_string(&tracked).unwrap();
+        assert_eq!(json.parse::<TagSetWithTombstones>().unwrap(), tracked);
+    }
+}
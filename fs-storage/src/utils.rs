@@ -1,6 +1,7 @@
 use data_error::Result;
 use std::collections::BTreeMap;
 use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 /// Parses version 2 `FileStorage` format and returns the data as a BTreeMap
 ///
@@ -34,15 +35,19 @@ where
     // Parse the file content into a BTreeMap
     let mut data = BTreeMap::new();
     for line in file_content.lines().skip(1) {
+        if line.trim().is_empty() {
+            continue;
+        }
+
         let mut parts = line.split(':');
         let key = parts
             .next()
-            .unwrap()
+            .ok_or(data_error::ArklibError::Parse)?
             .parse()
             .map_err(|_| data_error::ArklibError::Parse)?;
         let value = parts
             .next()
-            .unwrap()
+            .ok_or(data_error::ArklibError::Parse)?
             .parse()
             .map_err(|_| data_error::ArklibError::Parse)?;
 
@@ -79,4 +84,29 @@ key3:3
         assert_eq!(data.get("key2"), Some(&2));
         assert_eq!(data.get("key3"), Some(&3));
     }
+
+    proptest::proptest! {
+        /// Malformed legacy files must be rejected with an error, not a panic
+        #[test]
+        fn test_read_legacy_fs_arbitrary(body: Vec<u8>) {
+            let temp_dir = TempDir::new("ark-rust").unwrap();
+            let file_path = temp_dir.path().join("test_read_legacy_fs");
+            let mut file = std::fs::File::create(&file_path).unwrap();
+            file.write_all(b"version: 2\n").unwrap();
+            file.write_all(&body).unwrap();
+
+            let _ = read_version_2_fs::<String, i32>(&file_path);
+        }
+    }
+}
+
+/// Current time in milliseconds since the Unix epoch, the timestamp
+/// of the values merged by time, e.g. [`LastWriteWins`]
+///
+/// [`LastWriteWins`]: crate::monoid::LastWriteWins
+pub fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis() as u64)
+        .unwrap_or(0)
 }
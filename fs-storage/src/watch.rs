@@ -0,0 +1,262 @@
+//! Reloading of a [`FileStorage`] when its file is modified by another
+//! process, behind the `watch` feature.
+//!
+//! Events of the folder of the file are delivered by the `notify` crate.
+//! They are batched for [`DEBOUNCE`], then the storage is synced
+//! according to its [`SyncStatus`].
+
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+use crate::base_storage::{BaseStorage, SyncStatus};
+use crate::file_storage::FileStorage;
+use crate::journal::journal_path;
+use crate::monoid::Monoid;
+use data_error::{ArklibError, Result, ResultExt};
+
+/// Time to wait for more events before syncing the storage
+pub const DEBOUNCE: Duration = Duration::from_millis(100);
+
+/// Keeps a [`FileStorage`] in sync with its file until it is stopped
+/// or dropped.
+pub struct StorageWatcher<K: Ord, V> {
+    storage: Arc<Mutex<FileStorage<K, V>>>,
+    watcher: Option<RecommendedWatcher>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl<K, V> FileStorage<K, V>
+where
+    K: Ord
+        + Clone
+        + serde::Serialize
+        + serde::de::DeserializeOwned
+        + std::str::FromStr
+        + Send
+        + 'static,
+    V: Clone
+        + serde::Serialize
+        + serde::de::DeserializeOwned
+        + std::str::FromStr
+        + Monoid<V>
+        + Send
+        + 'static,
+{
+    /// Watch the file of the storage, reading it when it is modified
+    /// by somebody else, or merging it with the unsaved changes and
+    /// writing the result if both sides changed.
+    ///
+    /// The status of the storage before every such sync is sent to the
+    /// returned receiver, i.e. [`SyncStatus::MappingStale`] or
+    /// [`SyncStatus::Diverge`]. The storage keeps being synced if the
+    /// receiver is dropped.
+    pub fn watch(self) -> Result<(StorageWatcher<K, V>, Receiver<SyncStatus>)> {
+        let path = self.path().to_owned();
+        let folder = path
+            .parent()
+            .ok_or_else(|| {
+                ArklibError::Storage(
+                    self.label().to_owned(),
+                    "Failed to get parent directory".to_owned(),
+                )
+            })?
+            .to_owned();
+        std::fs::create_dir_all(&folder)
+            .with_path(&folder)
+            .with_label(self.label())?;
+        // events are reported with canonical paths
+        let folder = folder.canonicalize().unwrap_or(folder);
+        let files = match path.file_name() {
+            Some(name) => {
+                let file = folder.join(name);
+                vec![journal_path(&file), file]
+            }
+            None => vec![path],
+        };
+        let label = self.label().to_owned();
+        let storage = Arc::new(Mutex::new(self));
+
+        let watch_error = |err: notify::Error| {
+            ArklibError::Storage(label.clone(), err.to_string())
+        };
+        let (events_tx, events_rx) = mpsc::channel();
+        let mut watcher =
+            notify::recommended_watcher(events_tx).map_err(watch_error)?;
+        watcher
+            .watch(&folder, RecursiveMode::NonRecursive)
+            .map_err(watch_error)?;
+
+        let (synced_tx, synced_rx) = mpsc::channel();
+        let thread = {
+            let storage = storage.clone();
+            std::thread::spawn(move || {
+                sync_on_events(&files, &storage, events_rx, synced_tx)
+            })
+        };
+
+        log::info!("{} is watching {}", label, folder.display());
+        Ok((
+            StorageWatcher {
+                storage,
+                watcher: Some(watcher),
+                thread: Some(thread),
+            },
+            synced_rx,
+        ))
+    }
+}
+
+impl<K: Ord, V> StorageWatcher<K, V> {
+    /// Current state of the storage, syncs wait until the guard is dropped
+    pub fn storage(&self) -> MutexGuard<'_, FileStorage<K, V>> {
+        self.storage
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Stop watching and return the storage
+    pub fn stop(mut self) -> FileStorage<K, V> {
+        self.shutdown();
+        let storage = self.storage.clone();
+        drop(self);
+        match Arc::try_unwrap(storage) {
+            Ok(storage) => storage
+                .into_inner()
+                .unwrap_or_else(|poisoned| poisoned.into_inner()),
+            Err(_) => unreachable!("the watcher thread has ended"),
+        }
+    }
+
+    fn shutdown(&mut self) {
+        // dropping the watcher disconnects the events channel,
+        // which ends the thread
+        self.watcher.take();
+        if let Some(thread) = self.thread.take() {
+            if thread.join().is_err() {
+                log::error!("Storage watcher thread panicked");
+            }
+        }
+    }
+}
+
+impl<K: Ord, V> Drop for StorageWatcher<K, V> {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+fn sync_on_events<K, V>(
+    files: &[PathBuf],
+    storage: &Mutex<FileStorage<K, V>>,
+    events: Receiver<notify::Result<Event>>,
+    synced: Sender<SyncStatus>,
+) where
+    K: Ord
+        + Clone
+        + serde::Serialize
+        + serde::de::DeserializeOwned
+        + std::str::FromStr,
+    V: Clone
+        + serde::Serialize
+        + serde::de::DeserializeOwned
+        + std::str::FromStr
+        + Monoid<V>,
+{
+    // blocks until the next change, ends when the watcher is dropped
+    while let Ok(event) = events.recv() {
+        let mut relevant = is_relevant(files, event);
+        loop {
+            match events.recv_timeout(DEBOUNCE) {
+                Ok(event) => relevant |= is_relevant(files, event),
+                Err(RecvTimeoutError::Timeout) => break,
+                Err(RecvTimeoutError::Disconnected) => return,
+            }
+        }
+        if !relevant {
+            continue;
+        }
+
+        let mut storage = storage
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if !storage.path().exists() {
+            continue;
+        }
+        let status = match storage.sync_status() {
+            // own writes and changes not written yet are left alone
+            Ok(SyncStatus::InSync | SyncStatus::StorageStale) => continue,
+            Ok(status) => status,
+            Err(err) => {
+                log::error!("Failed to check the storage: {}", err);
+                continue;
+            }
+        };
+        match storage.sync() {
+            // nobody listens anymore, but the storage is still synced
+            Ok(()) => {
+                let _ = synced.send(status);
+            }
+            Err(err) => log::error!("Failed to sync the storage: {}", err),
+        }
+    }
+}
+
+/// Only changes of the storage file and its journal are relevant
+fn is_relevant(files: &[PathBuf], event: notify::Result<Event>) -> bool {
+    let event = match event {
+        Ok(event) => event,
+        Err(err) => {
+            // events may have been lost
+            log::warn!("[watch] {}", err);
+            return true;
+        }
+    };
+    if matches!(event.kind, EventKind::Access(_)) {
+        return false;
+    }
+    event
+        .paths
+        .iter()
+        .any(|path| files.contains(path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+    use tempdir::TempDir;
+
+    const TIMEOUT: Duration = Duration::from_secs(10);
+
+    #[test]
+    fn watched_storage_follows_its_file() {
+        let temp_dir =
+            TempDir::new("tmp").expect("Failed to create temporary directory");
+        let storage_path = temp_dir.path().join("teststorage.txt");
+
+        let mut storage =
+            FileStorage::new("TestStorage".to_string(), &storage_path).unwrap();
+        storage.set("key1".to_string(), 1);
+        storage.write_fs().unwrap();
+        let (watcher, synced) = storage.watch().unwrap();
+
+        let mut other: FileStorage<String, i32> =
+            FileStorage::new("Other".to_string(), &storage_path).unwrap();
+        other.set("key2".to_string(), 2);
+        other.write_fs().unwrap();
+
+        let start = Instant::now();
+        while watcher.storage().as_ref().len() != 2 {
+            assert!(start.elapsed() < TIMEOUT, "Storage was not reloaded");
+            let _ = synced.recv_timeout(DEBOUNCE);
+        }
+
+        let storage = watcher.stop();
+        assert_eq!(storage.as_ref(), other.as_ref());
+    }
+}
@@ -0,0 +1,122 @@
+use std::collections::{BTreeSet, HashMap};
+
+/// Number of resources having both tags of every pair of tags
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Cooccurrences {
+    /// Symmetric, tag to the tags applied together with it
+    pairs: HashMap<String, HashMap<String, usize>>,
+}
+
+impl Cooccurrences {
+    pub(crate) fn build<'a, I>(sets: I) -> Self
+    where
+        I: IntoIterator<Item = &'a BTreeSet<String>>,
+    {
+        let mut cooccurrences = Cooccurrences::default();
+        for tags in sets {
+            cooccurrences.update(&BTreeSet::new(), tags);
+        }
+        cooccurrences
+    }
+
+    /// Account for the tags of a resource changing from `old` to `new`
+    pub(crate) fn update(
+        &mut self,
+        old: &BTreeSet<String>,
+        new: &BTreeSet<String>,
+    ) {
+        for (a, b) in pairs(old) {
+            for (a, b) in [(a, b), (b, a)] {
+                let Some(counts) = self.pairs.get_mut(a) else {
+                    continue;
+                };
+                if let Some(count) = counts.get_mut(b) {
+                    *count -= 1;
+                    if *count == 0 {
+                        counts.remove(b);
+                    }
+                }
+                if counts.is_empty() {
+                    self.pairs.remove(a);
+                }
+            }
+        }
+        for (a, b) in pairs(new) {
+            for (a, b) in [(a, b), (b, a)] {
+                *self
+                    .pairs
+                    .entry(a.clone())
+                    .or_default()
+                    .entry(b.clone())
+                    .or_default() += 1;
+            }
+        }
+    }
+
+    /// Tags applied together with `tag`, with the number of resources
+    pub(crate) fn of(&self, tag: &str) -> Option<&HashMap<String, usize>> {
+        self.pairs.get(tag)
+    }
+
+    /// Tags applied together with `tags`, not among them, scored by the
+    /// number of resources they share with each of `tags`
+    pub(crate) fn suggest(
+        &self,
+        tags: &BTreeSet<String>,
+    ) -> Vec<(String, usize)> {
+        let mut scores: HashMap<&String, usize> = HashMap::new();
+        for counts in tags.iter().filter_map(|tag| self.pairs.get(tag)) {
+            for (tag, count) in counts {
+                if !tags.contains(tag) {
+                    *scores.entry(tag).or_default() += count;
+                }
+            }
+        }
+        let mut suggestions: Vec<(String, usize)> = scores
+            .into_iter()
+            .map(|(tag, score)| (tag.clone(), score))
+            .collect();
+        suggestions.sort_by(|(a, x), (b, y)| y.cmp(x).then_with(|| a.cmp(b)));
+        suggestions
+    }
+}
+
+fn pairs(tags: &BTreeSet<String>) -> impl Iterator<Item = (&String, &String)> {
+    tags.iter()
+        .enumerate()
+        .flat_map(move |(i, a)| tags.iter().skip(i + 1).map(move |b| (a, b)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set(tags: &[&str]) -> BTreeSet<String> {
+        tags.iter().map(|tag| tag.to_string()).collect()
+    }
+
+    #[test]
+    fn test_update_and_suggest() {
+        let mut cooccurrences = Cooccurrences::build(&[
+            set(&["sea", "sky", "summer"]),
+            set(&["sea", "sky"]),
+            set(&["sea", "boat"]),
+        ]);
+        assert_eq!(
+            cooccurrences.suggest(&set(&["sea"])),
+            [
+                ("sky".to_owned(), 2),
+                ("boat".to_owned(), 1),
+                ("summer".to_owned(), 1)
+            ]
+        );
+        assert_eq!(
+            cooccurrences.suggest(&set(&["sky", "summer"])),
+            [("sea".to_owned(), 3)]
+        );
+
+        cooccurrences.update(&set(&["sea", "boat"]), &set(&["sea"]));
+        assert!(cooccurrences.of("boat").is_none());
+        assert_eq!(cooccurrences.of("sea").unwrap().len(), 2);
+    }
+}
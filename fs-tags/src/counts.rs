@@ -0,0 +1,90 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Number of resources having every tag
+#[derive(Debug, Clone, Default)]
+pub(crate) struct TagCounts {
+    counts: BTreeMap<String, usize>,
+}
+
+impl TagCounts {
+    pub(crate) fn build<'a, I>(sets: I) -> Self
+    where
+        I: IntoIterator<Item = &'a BTreeSet<String>>,
+    {
+        let mut counts = TagCounts::default();
+        for tags in sets {
+            counts.update(&BTreeSet::new(), tags);
+        }
+        counts
+    }
+
+    /// Account for the tags of a resource changing from `old` to `new`
+    pub(crate) fn update(
+        &mut self,
+        old: &BTreeSet<String>,
+        new: &BTreeSet<String>,
+    ) {
+        for tag in old.difference(new) {
+            if let Some(count) = self.counts.get_mut(tag) {
+                *count -= 1;
+                if *count == 0 {
+                    self.counts.remove(tag);
+                }
+            }
+        }
+        for tag in new.difference(old) {
+            *self.counts.entry(tag.clone()).or_default() += 1;
+        }
+    }
+
+    pub(crate) fn all(&self) -> &BTreeMap<String, usize> {
+        &self.counts
+    }
+
+    /// Tags starting with `prefix`, the most used first, then in
+    /// alphabetical order
+    pub(crate) fn starting_with(&self, prefix: &str) -> Vec<(String, usize)> {
+        let mut tags: Vec<(String, usize)> = self
+            .counts
+            .range(prefix.to_owned()..)
+            .take_while(|(tag, _)| tag.starts_with(prefix))
+            .map(|(tag, count)| (tag.clone(), *count))
+            .collect();
+        // stable, so ties stay in alphabetical order
+        tags.sort_by(|(_, a), (_, b)| b.cmp(a));
+        tags
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set(tags: &[&str]) -> BTreeSet<String> {
+        tags.iter().map(|tag| tag.to_string()).collect()
+    }
+
+    #[test]
+    fn test_update_and_complete() {
+        let mut counts = TagCounts::build(&[
+            set(&["sea", "sky", "summer"]),
+            set(&["sea", "sky"]),
+            set(&["sea", "boat"]),
+        ]);
+        assert_eq!(counts.all()["sea"], 3);
+        assert_eq!(
+            counts.starting_with("s"),
+            [
+                ("sea".to_owned(), 3),
+                ("sky".to_owned(), 2),
+                ("summer".to_owned(), 1)
+            ]
+        );
+
+        counts.update(&set(&["sea", "boat"]), &set(&["sea", "sun"]));
+        assert!(!counts.all().contains_key("boat"));
+        assert_eq!(counts.all()["sea"], 3);
+        assert_eq!(counts.starting_with("su").len(), 2);
+        assert!(counts.starting_with("x").is_empty());
+    }
+}
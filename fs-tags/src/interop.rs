@@ -0,0 +1,513 @@
+//! Exchange of tags with other taggers, so that users coming from them
+//! keep their tags: `.tags` sidecar files, XMP keywords and, with the
+//! `tmsu` feature, TMSU databases.
+
+use std::fs::{self, File};
+use std::io::Read;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use data_error::{BulkResult, Result, ResultExt};
+use data_resource::ResourceId;
+use fs_index::ResourceIndex;
+use fs_storage::base_storage::BaseStorage;
+
+use crate::{error, TagStorage, TAG_SEPARATOR};
+
+/// How much of a resource is searched for an embedded XMP packet
+const XMP_SCAN_LIMIT: u64 = 1 << 20;
+
+/// Separator of hierarchical keywords in `lr:hierarchicalSubject`
+const XMP_HIERARCHY_SEPARATOR: char = '|';
+
+/// Format of the files kept next to resources to hold their tags
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sidecar {
+    /// `photo.jpg.tags`, with one tag per line. Blank lines and lines
+    /// starting with `#` are ignored.
+    Tags,
+    /// `photo.jpg.xmp` or `photo.xmp`, tags being its keywords
+    /// (`dc:subject`, and `lr:hierarchicalSubject` written by Lightroom).
+    /// Keywords embedded in resources without sidecar are imported too.
+    Xmp,
+}
+
+impl Sidecar {
+    fn extension(self) -> &'static str {
+        match self {
+            Sidecar::Tags => "tags",
+            Sidecar::Xmp => "xmp",
+        }
+    }
+
+    /// Whether `path` is a sidecar of this format, rather than a resource
+    fn is_sidecar(self, path: &Path) -> bool {
+        path.extension()
+            .is_some_and(|extension| extension == self.extension())
+    }
+}
+
+impl<Id: ResourceId> TagStorage<Id> {
+    /// Add the tags found in the sidecars of the resources of `index`.
+    /// Returns the number of tags added to each resource with a sidecar,
+    /// resources without sidecar are skipped.
+    ///
+    /// The imported tags are written at once, the error is about writing
+    /// them.
+    pub fn import_sidecars(
+        &mut self,
+        index: &ResourceIndex<Id>,
+        format: Sidecar,
+    ) -> Result<BulkResult<usize, PathBuf>> {
+        let mut result = BulkResult::new();
+        for resource in index.iter_entries() {
+            let path = resource.path.as_path();
+            if format.is_sidecar(path) {
+                continue;
+            }
+            let tags = match format {
+                Sidecar::Tags => read_tags_sidecar(path),
+                Sidecar::Xmp => read_xmp(path),
+            };
+            let imported = match tags {
+                Ok(None) => continue,
+                Ok(Some(tags)) => self.import(resource.id.clone(), &tags),
+                Err(err) => Err(err),
+            };
+            result.record(path.to_path_buf(), imported);
+        }
+        self.write_staged()?;
+        Ok(result)
+    }
+
+    /// Write the tags of the tagged resources of `index` to their
+    /// sidecars, so that other taggers can read them.
+    ///
+    /// `.tags` sidecars are overwritten. In existing XMP sidecars, only
+    /// the keywords are replaced, and sidecars without `dc:subject` are
+    /// reported as failures rather than being rewritten.
+    pub fn export_sidecars(
+        &self,
+        index: &ResourceIndex<Id>,
+        format: Sidecar,
+    ) -> BulkResult<(), PathBuf> {
+        let mut result = BulkResult::new();
+        for resource in index.iter_entries() {
+            let path = resource.path.as_path();
+            let Some(tags) = self.storage.get(&resource.id) else {
+                continue;
+            };
+            if tags.is_empty() || format.is_sidecar(path) {
+                continue;
+            }
+            let tags: Vec<&str> = tags.iter().collect();
+            let written = match format {
+                Sidecar::Tags => write_tags_sidecar(path, &tags),
+                Sidecar::Xmp => write_xmp_sidecar(path, &tags),
+            };
+            result.record(path.to_path_buf(), written);
+        }
+        result
+    }
+
+    /// Add tags of another tagger to a resource, all of them or none
+    /// if one can't be stored
+    fn import(&mut self, id: Id, tags: &[String]) -> Result<usize> {
+        let tags = tags
+            .iter()
+            .map(|tag| self.resolve(tag))
+            .collect::<Result<Vec<String>>>()?;
+        self.stage_tags(id, tags)
+    }
+}
+
+/// `path` with `extension` appended, e.g. `photo.jpg.xmp`
+fn with_appended_extension(path: &Path, extension: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".");
+    name.push(extension);
+    PathBuf::from(name)
+}
+
+fn read_tags_sidecar(path: &Path) -> Result<Option<Vec<String>>> {
+    let sidecar = with_appended_extension(path, Sidecar::Tags.extension());
+    if !sidecar.is_file() {
+        return Ok(None);
+    }
+    let content = fs::read_to_string(&sidecar).with_path(&sidecar)?;
+    let tags = content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_owned)
+        .collect();
+    Ok(Some(tags))
+}
+
+fn write_tags_sidecar(path: &Path, tags: &[&str]) -> Result<()> {
+    let sidecar = with_appended_extension(path, Sidecar::Tags.extension());
+    let mut content = String::new();
+    for tag in tags {
+        content.push_str(tag);
+        content.push('\n');
+    }
+    fs::write(&sidecar, content).with_path(&sidecar)
+}
+
+/// Existing XMP sidecar of a resource, `photo.jpg.xmp` being preferred
+/// over `photo.xmp`
+fn xmp_sidecar(path: &Path) -> Option<PathBuf> {
+    [
+        with_appended_extension(path, Sidecar::Xmp.extension()),
+        path.with_extension(Sidecar::Xmp.extension()),
+    ]
+    .into_iter()
+    .find(|sidecar| sidecar.is_file())
+}
+
+fn read_xmp(path: &Path) -> Result<Option<Vec<String>>> {
+    let packet = match xmp_sidecar(path) {
+        Some(sidecar) => fs::read_to_string(&sidecar).with_path(&sidecar)?,
+        None => match embedded_xmp(path)? {
+            Some(packet) => packet,
+            None => return Ok(None),
+        },
+    };
+    let keywords = xmp_keywords(&packet);
+    Ok((!keywords.is_empty()).then_some(keywords))
+}
+
+/// XMP packet embedded at the beginning of a resource, as in most
+/// images, PDFs and videos
+fn embedded_xmp(path: &Path) -> Result<Option<String>> {
+    let mut content = Vec::new();
+    File::open(path)
+        .and_then(|file| {
+            file.take(XMP_SCAN_LIMIT)
+                .read_to_end(&mut content)
+        })
+        .with_path(path)?;
+    let start = find(&content, b"<x:xmpmeta");
+    let end = find(&content, b"</x:xmpmeta>");
+    Ok(match (start, end) {
+        (Some(start), Some(end)) if start < end => {
+            Some(String::from_utf8_lossy(&content[start..end]).into_owned())
+        }
+        _ => None,
+    })
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+/// Keywords of an XMP packet, hierarchical keywords of Lightroom
+/// becoming hierarchical tags
+fn xmp_keywords(packet: &str) -> Vec<String> {
+    let mut keywords = Vec::new();
+    if let Some(bag) = element_content(packet, "dc:subject") {
+        keywords.extend(list_items(&packet[bag]));
+    }
+    if let Some(bag) = element_content(packet, "lr:hierarchicalSubject") {
+        keywords.extend(
+            list_items(&packet[bag])
+                .into_iter()
+                .map(|keyword| {
+                    keyword
+                        .split(XMP_HIERARCHY_SEPARATOR)
+                        .collect::<Vec<_>>()
+                        .join(&TAG_SEPARATOR.to_string())
+                }),
+        );
+    }
+    keywords
+}
+
+fn write_xmp_sidecar(path: &Path, tags: &[&str]) -> Result<()> {
+    let Some(sidecar) = xmp_sidecar(path) else {
+        let sidecar = with_appended_extension(path, Sidecar::Xmp.extension());
+        let packet = XMP_TEMPLATE.replace("{keywords}", &xmp_bag(tags));
+        return fs::write(&sidecar, packet).with_path(&sidecar);
+    };
+
+    let mut packet = fs::read_to_string(&sidecar).with_path(&sidecar)?;
+    let Some(bag) = element_content(&packet, "dc:subject") else {
+        return Err(error(format!(
+            "no keywords to replace in {}",
+            sidecar.display()
+        )));
+    };
+    packet.replace_range(bag, &xmp_bag(tags));
+    // or Lightroom would bring removed keywords back
+    if let Some(bag) = element_content(&packet, "lr:hierarchicalSubject") {
+        let hierarchical: Vec<String> = tags
+            .iter()
+            .map(|tag| {
+                tag.replace(TAG_SEPARATOR, &XMP_HIERARCHY_SEPARATOR.to_string())
+            })
+            .collect();
+        let hierarchical: Vec<&str> =
+            hierarchical.iter().map(String::as_str).collect();
+        packet.replace_range(bag, &xmp_bag(&hierarchical));
+    }
+    fs::write(&sidecar, packet).with_path(&sidecar)
+}
+
+const XMP_TEMPLATE: &str = r#"<?xpacket begin="" id="W5M0MpCehiHzreSzNTczkc9d"?>
+<x:xmpmeta xmlns:x="adobe:ns:meta/">
+ <rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+  <rdf:Description rdf:about=""
+    xmlns:dc="http://purl.org/dc/elements/1.1/">
+   <dc:subject>{keywords}</dc:subject>
+  </rdf:Description>
+ </rdf:RDF>
+</x:xmpmeta>
+<?xpacket end="w"?>
+"#;
+
+fn xmp_bag(items: &[&str]) -> String {
+    let mut bag = String::from("\n    <rdf:Bag>\n");
+    for item in items {
+        bag.push_str(&format!("     <rdf:li>{}</rdf:li>\n", escape(item)));
+    }
+    bag.push_str("    </rdf:Bag>\n   ");
+    bag
+}
+
+/// Range of the content of the first element `name` of `xml`,
+/// `None` if there is none or it is empty
+fn element_content(xml: &str, name: &str) -> Option<Range<usize>> {
+    let open = format!("<{}", name);
+    let close = format!("</{}>", name);
+    let mut from = 0;
+    while let Some(found) = xml[from..].find(&open) {
+        let start = from + found + open.len();
+        let rest = &xml[start..];
+        // and not another element whose name starts with `name`
+        if rest.starts_with(|c: char| c == '>' || c.is_whitespace()) {
+            let tag_end = start + rest.find('>')?;
+            if xml[..tag_end].ends_with('/') {
+                return None;
+            }
+            let end = tag_end + xml[tag_end..].find(&close)?;
+            return Some(tag_end + 1..end);
+        }
+        from = start;
+    }
+    None
+}
+
+/// Non-empty items of an RDF bag or sequence
+fn list_items(list: &str) -> Vec<String> {
+    let mut items = Vec::new();
+    let mut rest = list;
+    while let Some(item) = element_content(rest, "rdf:li") {
+        let text = unescape(rest[item.clone()].trim());
+        if !text.is_empty() {
+            items.push(text);
+        }
+        rest = &rest[item.end..];
+    }
+    items
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+#[cfg(feature = "tmsu")]
+mod tmsu {
+    use std::collections::BTreeMap;
+    use std::path::{Path, PathBuf};
+
+    use rusqlite::{Connection, OpenFlags};
+
+    use data_error::{ArklibError, BulkResult, Result};
+    use data_resource::ResourceId;
+    use fs_index::ResourceIndex;
+
+    use crate::{error, TagStorage, TAG_SEPARATOR};
+
+    const TAGGINGS: &str = "SELECT file.directory, file.name, tag.name, \
+        value.name FROM file_tag \
+        JOIN file ON file.id = file_tag.file_id \
+        JOIN tag ON tag.id = file_tag.tag_id \
+        LEFT JOIN value ON value.id = file_tag.value_id";
+
+    impl<Id: ResourceId> TagStorage<Id> {
+        /// Add the tags of a TMSU database, e.g. `<root>/.tmsu/db`, to the
+        /// resources of `index`. Tags with a value, like `year=2017`,
+        /// become hierarchical tags, `year/2017`. Returns the number of
+        /// tags added to each file of the database, files which are not
+        /// in `index` being failures.
+        ///
+        /// The imported tags are written at once, the error is about
+        /// reading the database or writing the tags.
+        pub fn import_tmsu<P: AsRef<Path>>(
+            &mut self,
+            db: P,
+            index: &ResourceIndex<Id>,
+        ) -> Result<BulkResult<usize, PathBuf>> {
+            let db = db.as_ref();
+            let connection = Connection::open_with_flags(
+                db,
+                OpenFlags::SQLITE_OPEN_READ_ONLY,
+            )
+            .map_err(tmsu_error)?;
+            let mut statement =
+                connection.prepare(TAGGINGS).map_err(tmsu_error)?;
+            let rows = statement
+                .query_map([], |row| {
+                    Ok((
+                        row.get::<_, String>(0)?,
+                        row.get::<_, String>(1)?,
+                        row.get::<_, String>(2)?,
+                        row.get::<_, Option<String>>(3)?,
+                    ))
+                })
+                .map_err(tmsu_error)?;
+
+            // databases in `<root>/.tmsu/db` keep paths relative to `<root>`
+            let base = db
+                .parent()
+                .and_then(Path::parent)
+                .unwrap_or(Path::new(""));
+            let mut files: BTreeMap<PathBuf, Vec<String>> = BTreeMap::new();
+            for row in rows {
+                let (directory, name, tag, value) = row.map_err(tmsu_error)?;
+                let tag = match value {
+                    Some(value) if !value.is_empty() => {
+                        format!("{}{}{}", tag, TAG_SEPARATOR, value)
+                    }
+                    _ => tag,
+                };
+                files
+                    .entry(base.join(directory).join(name))
+                    .or_default()
+                    .push(tag);
+            }
+
+            let mut result = BulkResult::new();
+            for (path, tags) in files {
+                let imported = match index.get_resource_by_path(&path) {
+                    Some(resource) => self.import(resource.id.clone(), &tags),
+                    None => Err(ArklibError::Path(format!(
+                        "{} is not indexed",
+                        path.display()
+                    ))),
+                };
+                result.record(path, imported);
+            }
+            self.write_staged()?;
+            Ok(result)
+        }
+    }
+
+    fn tmsu_error(err: rusqlite::Error) -> ArklibError {
+        error(format!("TMSU database: {}", err))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use dev_hash::Crc32;
+    use tempdir::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn test_tags_sidecars() {
+        let dir = TempDir::new("arklib_test").unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        std::fs::write(root.join("a.txt"), "a").unwrap();
+        std::fs::write(root.join("b.txt"), "b").unwrap();
+        std::fs::write(root.join("b.txt.tags"), "# from TMSU\nsea\n\n sky \n")
+            .unwrap();
+        let index = ResourceIndex::<Crc32>::build(&root);
+        let a = Crc32::from_bytes(b"a").unwrap();
+        let b = Crc32::from_bytes(b"b").unwrap();
+
+        let mut storage = TagStorage::new(&root).unwrap();
+        let imported = storage
+            .import_sidecars(&index, Sidecar::Tags)
+            .unwrap();
+        assert_eq!(imported.succeeded, [(root.join("b.txt"), 2)]);
+        assert_eq!(
+            storage.tags_of(&b),
+            BTreeSet::from(["sea", "sky"].map(String::from))
+        );
+
+        storage.add_tag(a, "place/beach").unwrap();
+        let exported = storage.export_sidecars(&index, Sidecar::Tags);
+        assert_eq!(exported.len(), 2);
+        assert!(!exported.has_failures());
+        let content = std::fs::read_to_string(root.join("a.txt.tags")).unwrap();
+        assert_eq!(content, "place/beach\n");
+    }
+
+    #[test]
+    fn test_xmp_sidecars() {
+        let packet = r#"<x:xmpmeta><rdf:RDF><rdf:Description>
+            <dc:subjects>not this one</dc:subjects>
+            <dc:subject><rdf:Bag>
+              <rdf:li>Paris</rdf:li><rdf:li>Tom &amp; Jerry</rdf:li>
+            </rdf:Bag></dc:subject>
+            <lr:hierarchicalSubject><rdf:Bag>
+              <rdf:li>Places|France|Paris</rdf:li>
+            </rdf:Bag></lr:hierarchicalSubject>
+            </rdf:Description></rdf:RDF></x:xmpmeta>"#;
+        assert_eq!(
+            xmp_keywords(packet),
+            ["Paris", "Tom & Jerry", "Places/France/Paris"]
+        );
+
+        let dir = TempDir::new("arklib_test").unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        std::fs::write(root.join("a.jpg"), "a").unwrap();
+        std::fs::write(root.join("a.xmp"), packet).unwrap();
+        std::fs::write(root.join("b.jpg"), "b").unwrap();
+        let index = ResourceIndex::<Crc32>::build(&root);
+        let a = Crc32::from_bytes(b"a").unwrap();
+        let b = Crc32::from_bytes(b"b").unwrap();
+
+        let mut storage = TagStorage::new(&root).unwrap();
+        let imported = storage
+            .import_sidecars(&index, Sidecar::Xmp)
+            .unwrap();
+        assert_eq!(imported.succeeded, [(root.join("a.jpg"), 3)]);
+        storage.remove_tag(&a, "Paris").unwrap();
+        storage.add_tag(b, "sea").unwrap();
+        let exported = storage.export_sidecars(&index, Sidecar::Xmp);
+        assert!(!exported.has_failures());
+
+        // the sidecar is updated in place, a new one is created for b
+        let updated = std::fs::read_to_string(root.join("a.xmp")).unwrap();
+        assert!(updated.contains("<dc:subjects>not this one</dc:subjects>"));
+        assert_eq!(
+            xmp_keywords(&updated),
+            [
+                "Places/France/Paris",
+                "Tom & Jerry",
+                "Places/France/Paris",
+                "Tom & Jerry",
+            ]
+        );
+        let created = std::fs::read_to_string(root.join("b.jpg.xmp")).unwrap();
+        assert_eq!(xmp_keywords(&created), ["sea"]);
+    }
+}
@@ -0,0 +1,902 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+
+use data_error::{ArklibError, Result};
+use data_resource::ResourceId;
+use fs_index::ResourceIndex;
+use fs_storage::base_storage::BaseStorage;
+use fs_storage::file_storage::FileStorage;
+use fs_storage::monoid::LastWriteWins;
+use fs_storage::tag_set::TagSetWithTombstones;
+use fs_storage::{
+    now_millis, ARK_FOLDER, TAG_ALIASES_STORAGE_FILE,
+    TAG_METADATA_STORAGE_FILE, TAG_STORAGE_FILE,
+};
+
+mod cooccurrence;
+mod counts;
+pub mod interop;
+mod metadata;
+mod query;
+
+pub use metadata::TagMetadata;
+pub use query::TagQuery;
+
+use cooccurrence::Cooccurrences;
+use counts::TagCounts;
+
+/// Separator of the segments of hierarchical tags
+pub const TAG_SEPARATOR: char = '/';
+
+/// Tags of the resources of a root, kept in `.ark/user/tags`.
+///
+/// Tags are hierarchical: `project/ark/design` is a child of `project/ark`,
+/// itself a child of `project`. The hierarchy is kept in the tags
+/// themselves, so parents exist as long as one of their descendants does.
+///
+/// Aliases, kept in `.ark/user/tag_aliases`, make old names of renamed
+/// tags keep resolving: every tag passed to the storage is
+/// [resolved](TagStorage::resolve) first.
+///
+/// [Metadata of tags](TagMetadata), e.g. their colors, is kept in
+/// `.ark/user/tag_metadata` and follows renamed tags.
+///
+/// Changes are written to disk immediately. Changes made by other
+/// processes or devices are read with [`TagStorage::sync`]. Removed tags
+/// are remembered, see [`TagSetWithTombstones`], so that syncing with a
+/// device which still has them doesn't bring them back.
+pub struct TagStorage<Id: ResourceId> {
+    storage: FileStorage<Id, TagSetWithTombstones>,
+    /// Alias to the tag it stands for
+    aliases: FileStorage<String, LastWriteWins<String>>,
+    metadata: FileStorage<String, TagMetadata>,
+    cooccurrences: Cooccurrences,
+    counts: TagCounts,
+    /// Time of the last change, in milliseconds since the epoch
+    clock: u64,
+}
+
+impl<Id: ResourceId> TagStorage<Id> {
+    /// Open the tags storage of `root`, reading it if it exists
+    pub fn new<P: AsRef<Path>>(root: P) -> Result<Self> {
+        let ark = root.as_ref().join(ARK_FOLDER);
+        let storage =
+            FileStorage::new("tags".to_owned(), &ark.join(TAG_STORAGE_FILE))?;
+        let aliases = FileStorage::new(
+            "tag aliases".to_owned(),
+            &ark.join(TAG_ALIASES_STORAGE_FILE),
+        )?;
+        let metadata = FileStorage::new(
+            "tag metadata".to_owned(),
+            &ark.join(TAG_METADATA_STORAGE_FILE),
+        )?;
+        let sets = present_sets(&storage);
+        let cooccurrences = Cooccurrences::build(&sets);
+        let counts = TagCounts::build(&sets);
+        Ok(TagStorage {
+            storage,
+            aliases,
+            metadata,
+            cooccurrences,
+            counts,
+            clock: 0,
+        })
+    }
+
+    /// Tag which `tag` stands for, with its segments trimmed.
+    ///
+    /// The longest aliased ancestor of the tag is replaced, e.g.
+    /// `project/ark` resolves to `work/ark` once `project` is renamed
+    /// to `work`. Tags without alias resolve to themselves.
+    pub fn resolve(&self, tag: &str) -> Result<String> {
+        let tag = validate(tag)?;
+        let mut end = tag.len();
+        loop {
+            if let Some(target) = self.aliases.get(&tag[..end].to_owned()) {
+                return Ok(format!("{}{}", target.value, &tag[end..]));
+            }
+            match tag[..end].rfind(TAG_SEPARATOR) {
+                Some(separator) => end = separator,
+                None => return Ok(tag),
+            }
+        }
+    }
+
+    /// Tag a resource. Returns `false` if it already had the tag.
+    ///
+    /// Tags are trimmed, and must be non-empty and contain no comma.
+    /// Segments of hierarchical tags are trimmed too, and must be non-empty.
+    pub fn add_tag(&mut self, id: Id, tag: &str) -> Result<bool> {
+        let tag = self.resolve(tag)?;
+        if self.stage_tags(id, [tag])? == 0 {
+            return Ok(false);
+        }
+        self.write_staged()?;
+        Ok(true)
+    }
+
+    /// Remove a tag from a resource. Returns `false` if it didn't have
+    /// the tag.
+    pub fn remove_tag(&mut self, id: &Id, tag: &str) -> Result<bool> {
+        let Some(old) = self.storage.get(id).cloned() else {
+            return Ok(false);
+        };
+        let Ok(tag) = self.resolve(tag) else {
+            return Ok(false);
+        };
+        let mut tags = old.clone();
+        if !tags.remove(&tag, self.tick()) {
+            return Ok(false);
+        }
+        self.set_tags(id.clone(), &old, tags)?;
+        self.storage.write_fs()?;
+        Ok(true)
+    }
+
+    /// Tags of a resource, sorted
+    pub fn tags_of(&self, id: &Id) -> BTreeSet<String> {
+        self.storage
+            .get(id)
+            .map(|tags| tags.tags().0)
+            .unwrap_or_default()
+    }
+
+    /// Tags to suggest for a resource having `tags`, best first: tags
+    /// applied together with `tags` on more resources come first.
+    /// At most `limit` tags are suggested.
+    pub fn suggest_tags<'a, I>(&self, tags: I, limit: usize) -> Vec<String>
+    where
+        I: IntoIterator<Item = &'a str>,
+    {
+        let tags = tags
+            .into_iter()
+            .filter_map(|tag| self.resolve(tag).ok())
+            .collect();
+        self.cooccurrences
+            .suggest(&tags)
+            .into_iter()
+            .take(limit)
+            .map(|(tag, _)| tag)
+            .collect()
+    }
+
+    /// Tags applied together with `tag`, with the number of resources
+    /// having both
+    pub fn cooccurring_tags(&self, tag: &str) -> BTreeMap<String, usize> {
+        self.resolve(tag)
+            .ok()
+            .and_then(|tag| self.cooccurrences.of(&tag))
+            .map(|counts| {
+                counts
+                    .iter()
+                    .map(|(tag, count)| (tag.clone(), *count))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Resources of `index` without any tag, sorted, e.g. to triage
+    /// new resources
+    pub fn untagged_resources(&self, index: &ResourceIndex<Id>) -> Vec<Id> {
+        let mut untagged: Vec<Id> = index
+            .id2path
+            .keys()
+            .filter(|id| {
+                self.storage
+                    .get(id)
+                    .map_or(true, |t| t.is_empty())
+            })
+            .cloned()
+            .collect();
+        untagged.sort();
+        untagged
+    }
+
+    /// Resources having `tag`, sorted
+    pub fn resources_with_tag(&self, tag: &str) -> Vec<Id> {
+        let Ok(tag) = self.resolve(tag) else {
+            return Vec::new();
+        };
+        self.storage
+            .as_ref()
+            .iter()
+            .filter(|(_, tags)| tags.contains(&tag))
+            .map(|(id, _)| id.clone())
+            .collect()
+    }
+
+    /// Resources having `tag` or one of its descendants, sorted, e.g.
+    /// `project` finds resources tagged `project/ark/design`
+    pub fn resources_with_tag_or_descendants(&self, tag: &str) -> Vec<Id> {
+        let Ok(tag) = self.resolve(tag) else {
+            return Vec::new();
+        };
+        self.storage
+            .as_ref()
+            .iter()
+            .filter(|(_, tags)| tags.iter().any(|t| is_within(t, &tag)))
+            .map(|(id, _)| id.clone())
+            .collect()
+    }
+
+    /// Resources matching `query`, sorted. Tags of the query are
+    /// [resolved](TagStorage::resolve) first.
+    ///
+    /// Only tagged resources are known to the storage, so `NOT archived`
+    /// doesn't find resources without any tag.
+    pub fn query(&self, query: &TagQuery) -> Vec<Id> {
+        let query = query.map_tags(&|tag| {
+            self.resolve(tag)
+                .unwrap_or_else(|_| tag.to_owned())
+        });
+        self.entries()
+            .filter(|(_, tags)| query.matches(tags))
+            .map(|(id, _)| id.clone())
+            .collect()
+    }
+
+    /// Direct children of `parent`, sorted, including those which are
+    /// only parents of other tags. An empty `parent` lists top-level tags.
+    pub fn children(&self, parent: &str) -> BTreeSet<String> {
+        let parent = match parent.trim() {
+            "" => String::new(),
+            parent => match self.resolve(parent) {
+                Ok(parent) => format!("{}{}", parent, TAG_SEPARATOR),
+                Err(_) => return BTreeSet::new(),
+            },
+        };
+        self.tags()
+            .iter()
+            .filter_map(|tag| tag.strip_prefix(&parent))
+            .map(|rest| {
+                let child = rest.split(TAG_SEPARATOR).next().unwrap_or(rest);
+                format!("{}{}", parent, child)
+            })
+            .collect()
+    }
+
+    /// Move `tag` and its descendants under `new_parent`, or to the top
+    /// level if it is empty, e.g. `project/ark` re-parented under
+    /// `archive` becomes `archive/ark`. Returns the number of resources
+    /// whose tags changed.
+    pub fn reparent(&mut self, tag: &str, new_parent: &str) -> Result<usize> {
+        let tag = self.resolve(tag)?;
+        let name = tag.rsplit(TAG_SEPARATOR).next().unwrap_or(&tag);
+        let moved = match new_parent.trim() {
+            "" => name.to_owned(),
+            new_parent => format!(
+                "{}{}{}",
+                self.resolve(new_parent)?,
+                TAG_SEPARATOR,
+                name
+            ),
+        };
+        self.move_subtree(&tag, &moved)
+    }
+
+    /// Rename `old` and its descendants to `new` on every resource,
+    /// and record `old` as an alias of `new`. Returns the number of
+    /// resources whose tags changed.
+    ///
+    /// Aliases standing for the renamed tags are updated, and aliases
+    /// named like the new tags are removed, as they are real tags now.
+    pub fn rename_tag(&mut self, old: &str, new: &str) -> Result<usize> {
+        let old = self.resolve(old)?;
+        let new = self.resolve(new)?;
+        if old == new {
+            return Ok(0);
+        }
+        let count = self.move_subtree(&old, &new)?;
+
+        let shadowed: Vec<String> = self
+            .aliases
+            .keys()
+            .filter(|alias| is_within(alias, &new))
+            .cloned()
+            .collect();
+        for alias in &shadowed {
+            self.aliases.remove(alias)?;
+        }
+        self.aliases.set(old, LastWriteWins::new(new));
+        self.aliases.write_fs()?;
+        Ok(count)
+    }
+
+    /// Define `alias` as another name of `target`. The alias must not
+    /// be used as a tag, see [`TagStorage::rename_tag`] to merge tags.
+    pub fn add_alias(&mut self, alias: &str, target: &str) -> Result<()> {
+        let alias = validate(alias)?;
+        let target = self.resolve(target)?;
+        if is_within(&target, &alias) {
+            return Err(error(format!(
+                "`{}` can't be an alias of `{}`",
+                alias, target
+            )));
+        }
+        if !self
+            .resources_with_tag_or_descendants(&alias)
+            .is_empty()
+        {
+            return Err(error(format!("`{}` is already a tag", alias)));
+        }
+        self.aliases
+            .set(alias, LastWriteWins::new(target));
+        self.aliases.write_fs()
+    }
+
+    /// Remove an alias. Returns `false` if there was no such alias.
+    pub fn remove_alias(&mut self, alias: &str) -> Result<bool> {
+        let Ok(alias) = validate(alias) else {
+            return Ok(false);
+        };
+        if !self.aliases.contains_key(&alias) {
+            return Ok(false);
+        }
+        self.aliases.remove(&alias)?;
+        self.aliases.write_fs()?;
+        Ok(true)
+    }
+
+    /// Metadata of a tag, `None` if it was never described nor used
+    pub fn tag_metadata(&self, tag: &str) -> Option<TagMetadata> {
+        let tag = self.resolve(tag).ok()?;
+        self.metadata.get(&tag).cloned()
+    }
+
+    /// Replace the color, icon and description of a tag. Its creation
+    /// time is kept, or set now if it has none.
+    pub fn set_tag_metadata(
+        &mut self,
+        tag: &str,
+        metadata: TagMetadata,
+    ) -> Result<()> {
+        let tag = self.resolve(tag)?;
+        let now = now_millis();
+        let created_at = self
+            .metadata
+            .get(&tag)
+            .map(|current| current.created_at)
+            .filter(|created_at| *created_at != 0)
+            .unwrap_or(now);
+        let metadata = TagMetadata {
+            created_at,
+            updated_at: now,
+            ..metadata
+        };
+        self.metadata.set(tag, metadata);
+        self.metadata.write_fs()
+    }
+
+    /// Aliases with the tags they stand for
+    pub fn aliases(&self) -> BTreeMap<String, String> {
+        self.aliases
+            .as_ref()
+            .iter()
+            .map(|(alias, target)| (alias.clone(), target.value.clone()))
+            .collect()
+    }
+
+    /// Replace `from` and its descendants with `to` on every resource
+    /// and in the targets of aliases
+    fn move_subtree(&mut self, from: &str, to: &str) -> Result<usize> {
+        if is_within(to, from) {
+            return Err(error(format!("can't move `{}` under itself", from)));
+        }
+        let moved = |tag: &str| {
+            is_within(tag, from)
+                .then(|| format!("{}{}", to, &tag[from.len()..]))
+        };
+        let count = self.rewrite(moved)?;
+
+        let retargeted: Vec<(String, String)> = self
+            .aliases
+            .as_ref()
+            .iter()
+            .filter_map(|(alias, target)| {
+                moved(&target.value).map(|target| (alias.clone(), target))
+            })
+            .collect();
+        if !retargeted.is_empty() {
+            for (alias, target) in retargeted {
+                self.aliases
+                    .set(alias, LastWriteWins::new(target));
+            }
+            self.aliases.write_fs()?;
+        }
+
+        let described: Vec<(String, String, TagMetadata)> = self
+            .metadata
+            .as_ref()
+            .iter()
+            .filter_map(|(tag, metadata)| {
+                moved(tag).map(|new| (tag.clone(), new, metadata.clone()))
+            })
+            .collect();
+        if !described.is_empty() {
+            for (old, new, metadata) in described {
+                // merged with the metadata the new tag may have already
+                let metadata = match self.metadata.get(&new) {
+                    Some(current) => TagMetadata::combine(current, &metadata),
+                    None => metadata,
+                };
+                self.metadata.remove(&old)?;
+                self.metadata.set(new, metadata);
+            }
+            self.metadata.write_fs()?;
+        }
+        Ok(count)
+    }
+
+    /// Replace the tags of every resource for which `f` returns a new
+    /// tag, writing the storage once. Returns the number of resources
+    /// whose tags changed.
+    fn rewrite<F>(&mut self, f: F) -> Result<usize>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        let changed: Vec<(Id, TagSetWithTombstones)> = self
+            .storage
+            .as_ref()
+            .iter()
+            .filter(|(_, tags)| tags.iter().any(|tag| f(tag).is_some()))
+            .map(|(id, old)| (id.clone(), old.clone()))
+            .collect();
+        if changed.is_empty() {
+            return Ok(0);
+        }
+        let count = changed.len();
+        // the new tags are added after the old ones are removed,
+        // in case they are named alike
+        let (removed_at, added_at) = (self.tick(), self.tick());
+        for (id, old) in changed {
+            let mut tags = old.clone();
+            for tag in old.iter() {
+                if let Some(new) = f(tag) {
+                    tags.remove(tag, removed_at);
+                    tags.insert(&new, added_at);
+                }
+            }
+            self.set_tags(id, &old, tags)?;
+        }
+        self.storage.write_fs()?;
+        Ok(count)
+    }
+
+    /// Add resolved tags to a resource in memory, creating the metadata
+    /// of new tags. Returns the number of tags the resource didn't have.
+    fn stage_tags<I>(&mut self, id: Id, tags: I) -> Result<usize>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        let old = self.storage.get(&id).cloned().unwrap_or_default();
+        let mut new = old.clone();
+        let mut added = 0;
+        let now = self.tick();
+        for tag in tags {
+            if !new.insert(&tag, now) {
+                continue;
+            }
+            added += 1;
+            if !self.metadata.contains_key(&tag) {
+                let created_at = now_millis();
+                let metadata = TagMetadata {
+                    created_at,
+                    updated_at: created_at,
+                    ..Default::default()
+                };
+                self.metadata.set(tag, metadata);
+            }
+        }
+        if added > 0 {
+            self.set_tags(id, &old, new)?;
+        }
+        Ok(added)
+    }
+
+    /// Write the changes made by [`TagStorage::stage_tags`]
+    fn write_staged(&mut self) -> Result<()> {
+        self.storage.write_fs()?;
+        if !self.metadata.dirty_keys().is_empty() {
+            self.metadata.write_fs()?;
+        }
+        Ok(())
+    }
+
+    /// Replace the tags of a resource in memory. Resources left without
+    /// tags are kept, with their removed tags.
+    fn set_tags(
+        &mut self,
+        id: Id,
+        old: &TagSetWithTombstones,
+        tags: TagSetWithTombstones,
+    ) -> Result<()> {
+        let (old, new) = (old.tags().0, tags.tags().0);
+        self.cooccurrences.update(&old, &new);
+        self.counts.update(&old, &new);
+        self.storage.set(id, tags);
+        Ok(())
+    }
+
+    /// Timestamp of a change, later than the previous changes even if
+    /// they were made in the same millisecond
+    fn tick(&mut self) -> u64 {
+        self.clock = now_millis().max(self.clock + 1);
+        self.clock
+    }
+
+    /// Tags of all resources, sorted
+    pub fn tags(&self) -> BTreeSet<String> {
+        self.counts.all().keys().cloned().collect()
+    }
+
+    /// Tags of all resources with the number of resources having them,
+    /// e.g. for a tag cloud. Counts are kept up to date as tags are
+    /// added and removed, so this is cheap even with many resources.
+    pub fn tag_counts(&self) -> &BTreeMap<String, usize> {
+        self.counts.all()
+    }
+
+    /// Number of resources having `tag`, not counting its descendants
+    pub fn tag_count(&self, tag: &str) -> usize {
+        self.resolve(tag)
+            .ok()
+            .and_then(|tag| self.counts.all().get(&tag).copied())
+            .unwrap_or(0)
+    }
+
+    /// Tags starting with `prefix`, compared case-sensitively, the most
+    /// used first, e.g. to complete a tag being typed. At most `limit`
+    /// tags are returned.
+    pub fn complete_tag(&self, prefix: &str, limit: usize) -> Vec<String> {
+        self.counts
+            .starting_with(prefix.trim_start())
+            .into_iter()
+            .take(limit)
+            .map(|(tag, _)| tag)
+            .collect()
+    }
+
+    /// Tagged resources with their tags
+    pub fn entries(&self) -> impl Iterator<Item = (&Id, BTreeSet<String>)> {
+        self.storage
+            .as_ref()
+            .iter()
+            .filter(|(_, tags)| !tags.is_empty())
+            .map(|(id, tags)| (id, tags.tags().0))
+    }
+
+    /// Sync the tags, aliases and metadata of tags as
+    /// [`BaseStorage::sync`] does
+    pub fn sync(&mut self) -> Result<()> {
+        self.storage.sync()?;
+        let sets = present_sets(&self.storage);
+        self.cooccurrences = Cooccurrences::build(&sets);
+        self.counts = TagCounts::build(&sets);
+        self.aliases.sync()?;
+        self.metadata.sync()
+    }
+
+    /// Merge the tags, aliases and metadata of tags of another root,
+    /// e.g. a copy of this root on another device. Tags removed on
+    /// either side stay removed.
+    pub fn merge_from(&mut self, other: &TagStorage<Id>) -> Result<()> {
+        self.storage.merge_from(&other.storage)?;
+        self.storage.write_fs()?;
+        let sets = present_sets(&self.storage);
+        self.cooccurrences = Cooccurrences::build(&sets);
+        self.counts = TagCounts::build(&sets);
+        self.aliases.merge_from(&other.aliases)?;
+        self.aliases.write_fs()?;
+        self.metadata.merge_from(&other.metadata)?;
+        self.metadata.write_fs()
+    }
+}
+
+/// Present tags of every resource
+fn present_sets<Id: ResourceId>(
+    storage: &FileStorage<Id, TagSetWithTombstones>,
+) -> Vec<BTreeSet<String>> {
+    storage
+        .as_ref()
+        .values()
+        .map(|tags| tags.tags().0)
+        .collect()
+}
+
+/// Tag with its segments trimmed, if it can be stored in a tag set
+fn validate(tag: &str) -> Result<String> {
+    let segments: Vec<&str> = tag.split(TAG_SEPARATOR).map(str::trim).collect();
+    if tag.contains(',') || segments.iter().any(|s| s.is_empty()) {
+        return Err(error(format!("invalid tag `{}`", tag.trim())));
+    }
+    Ok(segments.join(&TAG_SEPARATOR.to_string()))
+}
+
+fn error(message: String) -> ArklibError {
+    ArklibError::Storage("tags".to_owned(), message)
+}
+
+/// Whether `tag` is `ancestor` or one of its descendants
+fn is_within(tag: &str, ancestor: &str) -> bool {
+    tag.strip_prefix(ancestor)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with(TAG_SEPARATOR))
+}
+
+#[cfg(test)]
+mod tests {
+    use dev_hash::Crc32;
+    use tempdir::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn test_add_and_remove_tags() {
+        let dir = TempDir::new("arklib_test").unwrap();
+        let root = dir.path();
+        let (a, b) = (Crc32(1), Crc32(2));
+
+        let mut storage = TagStorage::new(root).unwrap();
+        assert!(storage.add_tag(a.clone(), "sea").unwrap());
+        assert!(storage.add_tag(a.clone(), " sky ").unwrap());
+        assert!(!storage.add_tag(a.clone(), "sea").unwrap());
+        assert!(storage.add_tag(b.clone(), "sea").unwrap());
+        assert!(storage.add_tag(b.clone(), "a,b").is_err());
+        assert!(storage.add_tag(b.clone(), " ").is_err());
+        assert!(storage.add_tag(b.clone(), "a//b").is_err());
+
+        assert_eq!(
+            storage
+                .tags_of(&a)
+                .into_iter()
+                .collect::<Vec<_>>(),
+            ["sea", "sky"]
+        );
+        assert_eq!(storage.resources_with_tag("sea"), [a.clone(), b.clone()]);
+
+        assert!(storage.remove_tag(&b, "sea").unwrap());
+        assert!(!storage.remove_tag(&b, "sea").unwrap());
+        assert!(storage.tags_of(&b).is_empty());
+        assert_eq!(storage.entries().count(), 1);
+
+        // written to disk immediately
+        let storage: TagStorage<Crc32> = TagStorage::new(root).unwrap();
+        assert_eq!(storage.resources_with_tag("sea"), [a.clone()]);
+        assert_eq!(storage.resources_with_tag("sky"), [a]);
+        assert_eq!(storage.tags().len(), 2);
+    }
+
+    #[test]
+    fn test_hierarchical_tags() {
+        let dir = TempDir::new("arklib_test").unwrap();
+        let root = dir.path();
+        let (a, b, c) = (Crc32(1), Crc32(2), Crc32(3));
+
+        let mut storage = TagStorage::new(root).unwrap();
+        storage
+            .add_tag(a.clone(), "project / ark/design")
+            .unwrap();
+        storage.add_tag(b.clone(), "project/ark").unwrap();
+        storage.add_tag(b.clone(), "projects").unwrap();
+        storage.add_tag(c.clone(), "archive").unwrap();
+
+        assert_eq!(storage.resources_with_tag("project"), []);
+        assert_eq!(
+            storage.resources_with_tag_or_descendants("project"),
+            [a.clone(), b.clone()]
+        );
+        let top: Vec<_> = storage.children("").into_iter().collect();
+        assert_eq!(top, ["archive", "project", "projects"]);
+        let children: Vec<_> =
+            storage.children("project").into_iter().collect();
+        assert_eq!(children, ["project/ark"]);
+
+        assert!(storage
+            .reparent("project/ark", "project/ark/design")
+            .is_err());
+        assert_eq!(
+            storage
+                .reparent("project/ark", "archive")
+                .unwrap(),
+            2
+        );
+        assert_eq!(storage.resources_with_tag("archive/ark/design"), [a]);
+        assert_eq!(
+            storage.resources_with_tag_or_descendants("archive"),
+            [Crc32(1), b.clone(), c]
+        );
+        assert!(storage.children("project").is_empty());
+
+        assert_eq!(storage.reparent("archive/ark", "").unwrap(), 2);
+        assert_eq!(storage.resources_with_tag("ark"), [b]);
+    }
+
+    #[test]
+    fn test_rename_and_aliases() {
+        let dir = TempDir::new("arklib_test").unwrap();
+        let root = dir.path();
+        let (a, b) = (Crc32(1), Crc32(2));
+
+        let mut storage = TagStorage::new(root).unwrap();
+        storage.add_tag(a.clone(), "project/ark").unwrap();
+        storage.add_tag(b.clone(), "project").unwrap();
+        storage.add_tag(b.clone(), "work").unwrap();
+
+        assert_eq!(storage.rename_tag("project", "work").unwrap(), 2);
+        assert!(storage.tags_of(&a).contains("work/ark"));
+        assert_eq!(storage.tags_of(&b).len(), 1);
+        // old names keep resolving
+        assert_eq!(storage.resolve("project/ark").unwrap(), "work/ark");
+        assert_eq!(storage.resources_with_tag("project/ark"), [a.clone()]);
+        storage.add_tag(a.clone(), "project/new").unwrap();
+        assert!(storage.tags_of(&a).contains("work/new"));
+
+        assert!(storage.add_alias("work", "misc").is_err());
+        storage.add_alias("job", "project").unwrap();
+        assert_eq!(storage.resolve("job").unwrap(), "work");
+
+        // aliases follow the renamed tags
+        storage
+            .rename_tag("work", "archive/work")
+            .unwrap();
+        let mut storage: TagStorage<Crc32> = TagStorage::new(root).unwrap();
+        assert_eq!(storage.aliases()["project"], "archive/work");
+        assert_eq!(storage.aliases()["job"], "archive/work");
+        assert_eq!(storage.resources_with_tag("job"), [b]);
+        assert!(storage.rename_tag("a", "a/b").is_err());
+    }
+
+    #[test]
+    fn test_query() {
+        let dir = TempDir::new("arklib_test").unwrap();
+        let root = dir.path();
+        let (a, b, c) = (Crc32(1), Crc32(2), Crc32(3));
+
+        let mut storage = TagStorage::new(root).unwrap();
+        for tag in ["job", "urgent"] {
+            storage.add_tag(a.clone(), tag).unwrap();
+            storage.add_tag(b.clone(), tag).unwrap();
+        }
+        storage.add_tag(b.clone(), "archived").unwrap();
+        storage.add_tag(c.clone(), "urgent").unwrap();
+        storage.rename_tag("job", "work").unwrap();
+
+        let query = "(job AND urgent) AND NOT archived"
+            .parse()
+            .unwrap();
+        assert_eq!(storage.query(&query), [a.clone()]);
+        let query = "work OR urgent".parse().unwrap();
+        assert_eq!(storage.query(&query), [a, b, c]);
+    }
+
+    #[test]
+    fn test_suggest_tags() {
+        let dir = TempDir::new("arklib_test").unwrap();
+        let root = dir.path();
+
+        let mut storage = TagStorage::new(root).unwrap();
+        for (id, tags) in [(1, "sea,sky"), (2, "sea,sky,boat"), (3, "sea")] {
+            for tag in tags.split(',') {
+                storage.add_tag(Crc32(id), tag).unwrap();
+            }
+        }
+        assert_eq!(storage.suggest_tags(["sea"], 5), ["sky", "boat"]);
+        assert_eq!(storage.suggest_tags(["sea"], 1), ["sky"]);
+
+        storage.remove_tag(&Crc32(2), "boat").unwrap();
+        storage.rename_tag("sky", "clouds").unwrap();
+        let storage: TagStorage<Crc32> = TagStorage::new(root).unwrap();
+        assert_eq!(storage.suggest_tags(["sky"], 5), ["sea"]);
+        assert_eq!(storage.cooccurring_tags("sea")["clouds"], 2);
+    }
+
+    #[test]
+    fn test_tag_metadata() {
+        let dir = TempDir::new("arklib_test").unwrap();
+        let root = dir.path();
+
+        let mut storage = TagStorage::new(root).unwrap();
+        assert!(storage.tag_metadata("sea").is_none());
+        storage.add_tag(Crc32(1), "sea/blue").unwrap();
+        let created_at = storage
+            .tag_metadata("sea/blue")
+            .unwrap()
+            .created_at;
+        assert_ne!(created_at, 0);
+
+        let metadata = TagMetadata {
+            color: Some("#0000ff".to_owned()),
+            icon: Some("🌊".to_owned()),
+            ..Default::default()
+        };
+        storage
+            .set_tag_metadata("sea/blue", metadata)
+            .unwrap();
+        storage.rename_tag("sea", "ocean").unwrap();
+
+        let storage: TagStorage<Crc32> = TagStorage::new(root).unwrap();
+        assert!(storage.tag_metadata("sea/blue").is_some());
+        let metadata = storage.tag_metadata("ocean/blue").unwrap();
+        assert_eq!(metadata.color.as_deref(), Some("#0000ff"));
+        assert_eq!(metadata.created_at, created_at);
+        assert!(metadata.description.is_none());
+    }
+
+    #[test]
+    fn test_merge_keeps_removed_tags() {
+        let laptop_dir = TempDir::new("arklib_test").unwrap();
+        let phone_dir = TempDir::new("arklib_test").unwrap();
+        let a = Crc32(1);
+
+        let mut laptop = TagStorage::new(laptop_dir.path()).unwrap();
+        for tag in ["sea", "sky"] {
+            laptop.add_tag(a.clone(), tag).unwrap();
+        }
+        let mut phone = TagStorage::new(phone_dir.path()).unwrap();
+        phone.merge_from(&laptop).unwrap();
+        assert_eq!(phone.tags_of(&a), laptop.tags_of(&a));
+
+        // edited on both devices before they sync again
+        assert!(laptop.remove_tag(&a, "sky").unwrap());
+        assert!(laptop.remove_tag(&a, "sea").unwrap());
+        assert!(phone.add_tag(a.clone(), "sun").unwrap());
+        assert_eq!(laptop.entries().count(), 0);
+
+        laptop.merge_from(&phone).unwrap();
+        phone.merge_from(&laptop).unwrap();
+        let expected: BTreeSet<String> = ["sun".to_owned()].into();
+        assert_eq!(laptop.tags_of(&a), expected);
+        assert_eq!(phone.tags_of(&a), expected);
+        assert_eq!(phone.tag_count("sky"), 0);
+
+        // written to disk
+        let laptop: TagStorage<Crc32> =
+            TagStorage::new(laptop_dir.path()).unwrap();
+        assert_eq!(laptop.tags_of(&a), expected);
+        assert_eq!(laptop.resources_with_tag("sea"), []);
+    }
+
+    #[test]
+    fn test_tag_counts() {
+        let dir = TempDir::new("arklib_test").unwrap();
+        let root = dir.path();
+
+        let mut storage = TagStorage::new(root).unwrap();
+        for (id, tag) in [(1, "sea"), (2, "sea"), (2, "sky"), (3, "summer")] {
+            storage.add_tag(Crc32(id), tag).unwrap();
+        }
+        assert_eq!(storage.tag_count("sea"), 2);
+        assert_eq!(storage.complete_tag("s", 2), ["sea", "sky"]);
+
+        storage.remove_tag(&Crc32(1), "sea").unwrap();
+        storage.rename_tag("summer", "sun").unwrap();
+        let counts: Vec<(&str, usize)> = storage
+            .tag_counts()
+            .iter()
+            .map(|(tag, count)| (tag.as_str(), *count))
+            .collect();
+        assert_eq!(counts, [("sea", 1), ("sky", 1), ("sun", 1)]);
+        // counts follow aliases
+        assert_eq!(storage.tag_count("summer"), 1);
+
+        let storage: TagStorage<Crc32> = TagStorage::new(root).unwrap();
+        assert_eq!(storage.tag_counts().len(), 3);
+    }
+
+    #[test]
+    fn test_untagged_resources() {
+        let dir = TempDir::new("arklib_test").unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        std::fs::write(root.join("a.txt"), "a").unwrap();
+        std::fs::write(root.join("b.txt"), "b").unwrap();
+        let index = ResourceIndex::<Crc32>::build(&root);
+        let a = Crc32::from_bytes(b"a").unwrap();
+        let b = Crc32::from_bytes(b"b").unwrap();
+
+        let mut storage = TagStorage::new(&root).unwrap();
+        assert_eq!(storage.untagged_resources(&index).len(), 2);
+        storage.add_tag(a, "sea").unwrap();
+        // not indexed
+        storage.add_tag(Crc32(1), "sea").unwrap();
+        assert_eq!(storage.untagged_resources(&index), [b]);
+    }
+}
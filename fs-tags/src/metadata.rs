@@ -0,0 +1,80 @@
+use std::cmp::Ordering;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use fs_storage::monoid::Monoid;
+
+/// How a tag is rendered, shared by all devices
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TagMetadata {
+    /// e.g. `#ff8800`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+    /// Emoji or name of an icon
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub icon: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// When the tag was first used, in milliseconds since the epoch
+    #[serde(default)]
+    pub created_at: u64,
+    /// When the metadata was last changed, in milliseconds since the epoch
+    #[serde(default)]
+    pub updated_at: u64,
+}
+
+impl FromStr for TagMetadata {
+    type Err = serde_json::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        serde_json::from_str(s)
+    }
+}
+
+// The latest change wins, keeping the earliest creation time
+impl Monoid<TagMetadata> for TagMetadata {
+    fn neutral() -> TagMetadata {
+        TagMetadata::default()
+    }
+
+    fn combine(a: &TagMetadata, b: &TagMetadata) -> TagMetadata {
+        let mut latest = match a.updated_at.cmp(&b.updated_at) {
+            Ordering::Less => b.clone(),
+            _ => a.clone(),
+        };
+        latest.created_at = match (a.created_at, b.created_at) {
+            (0, created) | (created, 0) => created,
+            (a, b) => a.min(b),
+        };
+        latest
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_latest_change_wins() {
+        let red = TagMetadata {
+            color: Some("red".to_owned()),
+            created_at: 5,
+            updated_at: 10,
+            ..Default::default()
+        };
+        let blue = TagMetadata {
+            color: Some("blue".to_owned()),
+            created_at: 7,
+            updated_at: 20,
+            ..Default::default()
+        };
+        let merged = TagMetadata::combine(&red, &blue);
+        assert_eq!(merged.color.as_deref(), Some("blue"));
+        assert_eq!(merged.created_at, 5);
+        assert_eq!(TagMetadata::combine(&blue, &red), merged);
+
+        let json = serde_json::to_string(&merged).unwrap();
+        assert_eq!(json.parse::<TagMetadata>().unwrap(), merged);
+    }
+}
@@ -0,0 +1,209 @@
+//! Boolean queries on the tags of resources, e.g.
+//! `(work AND urgent) AND NOT archived`.
+
+use std::collections::BTreeSet;
+use std::iter::Peekable;
+use std::str::FromStr;
+use std::vec::IntoIter;
+
+use data_error::{ArklibError, Result};
+
+use crate::error;
+
+/// Boolean expression on tags, parsed from text.
+///
+/// `AND`, `OR` and `NOT` are case-insensitive, `NOT` binding tighter than
+/// `AND`, itself binding tighter than `OR`. Tags containing spaces or
+/// named like an operator are quoted, e.g. `"to do" OR "not"`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TagQuery {
+    Tag(String),
+    Not(Box<TagQuery>),
+    And(Box<TagQuery>, Box<TagQuery>),
+    Or(Box<TagQuery>, Box<TagQuery>),
+}
+
+impl TagQuery {
+    /// Whether a resource with `tags` matches the query
+    pub fn matches(&self, tags: &BTreeSet<String>) -> bool {
+        match self {
+            TagQuery::Tag(tag) => tags.contains(tag),
+            TagQuery::Not(query) => !query.matches(tags),
+            TagQuery::And(a, b) => a.matches(tags) && b.matches(tags),
+            TagQuery::Or(a, b) => a.matches(tags) || b.matches(tags),
+        }
+    }
+
+    /// Same query with every tag replaced by `f`
+    pub(crate) fn map_tags<F: Fn(&str) -> String>(&self, f: &F) -> TagQuery {
+        match self {
+            TagQuery::Tag(tag) => TagQuery::Tag(f(tag)),
+            TagQuery::Not(query) => TagQuery::Not(Box::new(query.map_tags(f))),
+            TagQuery::And(a, b) => {
+                TagQuery::And(Box::new(a.map_tags(f)), Box::new(b.map_tags(f)))
+            }
+            TagQuery::Or(a, b) => {
+                TagQuery::Or(Box::new(a.map_tags(f)), Box::new(b.map_tags(f)))
+            }
+        }
+    }
+}
+
+impl FromStr for TagQuery {
+    type Err = ArklibError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut tokens = tokenize(s)?.into_iter().peekable();
+        let query = parse_or(&mut tokens)?;
+        match tokens.next() {
+            None => Ok(query),
+            Some(token) => Err(unexpected(&token)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Open,
+    Close,
+    And,
+    Or,
+    Not,
+    Tag(String),
+}
+
+type Tokens = Peekable<IntoIter<Token>>;
+
+fn tokenize(s: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = s.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '(' => {
+                chars.next();
+                tokens.push(Token::Open);
+            }
+            ')' => {
+                chars.next();
+                tokens.push(Token::Close);
+            }
+            '"' => {
+                chars.next();
+                let tag: String =
+                    chars.by_ref().take_while(|&c| c != '"').collect();
+                tokens.push(Token::Tag(tag));
+            }
+            _ => {
+                let mut word = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || "()\"".contains(c) {
+                        break;
+                    }
+                    word.push(c);
+                    chars.next();
+                }
+                tokens.push(match word.to_uppercase().as_str() {
+                    "AND" => Token::And,
+                    "OR" => Token::Or,
+                    "NOT" => Token::Not,
+                    _ => Token::Tag(word),
+                });
+            }
+        }
+    }
+    if s.matches('"').count() % 2 != 0 {
+        return Err(error("invalid query, unclosed quote".to_owned()));
+    }
+    Ok(tokens)
+}
+
+fn parse_or(tokens: &mut Tokens) -> Result<TagQuery> {
+    let mut query = parse_and(tokens)?;
+    while tokens.next_if_eq(&Token::Or).is_some() {
+        query = TagQuery::Or(Box::new(query), Box::new(parse_and(tokens)?));
+    }
+    Ok(query)
+}
+
+fn parse_and(tokens: &mut Tokens) -> Result<TagQuery> {
+    let mut query = parse_not(tokens)?;
+    while tokens.next_if_eq(&Token::And).is_some() {
+        query = TagQuery::And(Box::new(query), Box::new(parse_not(tokens)?));
+    }
+    Ok(query)
+}
+
+fn parse_not(tokens: &mut Tokens) -> Result<TagQuery> {
+    match tokens.next() {
+        Some(Token::Not) => Ok(TagQuery::Not(Box::new(parse_not(tokens)?))),
+        Some(Token::Open) => {
+            let query = parse_or(tokens)?;
+            match tokens.next() {
+                Some(Token::Close) => Ok(query),
+                Some(token) => Err(unexpected(&token)),
+                None => Err(error("invalid query, unclosed `(`".to_owned())),
+            }
+        }
+        Some(Token::Tag(tag)) => Ok(TagQuery::Tag(tag)),
+        Some(token) => Err(unexpected(&token)),
+        None => Err(error("invalid query, unexpected end".to_owned())),
+    }
+}
+
+fn unexpected(token: &Token) -> ArklibError {
+    let token = match token {
+        Token::Open => "(",
+        Token::Close => ")",
+        Token::And => "AND",
+        Token::Or => "OR",
+        Token::Not => "NOT",
+        Token::Tag(tag) => tag,
+    };
+    error(format!("invalid query, unexpected `{}`", token))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tag(tag: &str) -> Box<TagQuery> {
+        Box::new(TagQuery::Tag(tag.to_owned()))
+    }
+
+    #[test]
+    fn test_parse_precedence() {
+        let query: TagQuery = "(work and urgent) OR NOT archived AND \"to do\""
+            .parse()
+            .unwrap();
+        assert_eq!(
+            query,
+            TagQuery::Or(
+                Box::new(TagQuery::And(tag("work"), tag("urgent"))),
+                Box::new(TagQuery::And(
+                    Box::new(TagQuery::Not(tag("archived"))),
+                    tag("to do")
+                ))
+            )
+        );
+
+        for invalid in ["", "work AND", "(work", "work)", "a b", "\"a"] {
+            assert!(invalid.parse::<TagQuery>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_matches() {
+        let query: TagQuery = "(work AND urgent) AND NOT archived"
+            .parse()
+            .unwrap();
+        let tags = |tags: &[&str]| -> BTreeSet<String> {
+            tags.iter().map(|t| t.to_string()).collect()
+        };
+        assert!(query.matches(&tags(&["work", "urgent"])));
+        assert!(!query.matches(&tags(&["work", "urgent", "archived"])));
+        assert!(!query.matches(&tags(&["work"])));
+    }
+}
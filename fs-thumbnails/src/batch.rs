@@ -0,0 +1,159 @@
+//! Generating the thumbnails of every image of an index,
+//! e.g. to warm up the cache on the first run.
+
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Instant;
+
+use image::ImageFormat;
+
+use data_error::{BulkResult, Result};
+use data_resource::ResourceId;
+use fs_index::ResourceIndex;
+
+use crate::{generate_thumbnail, thumbnail_path, ThumbnailConfig};
+
+/// How [`generate_all`] runs
+#[derive(Debug, Clone, Default)]
+pub struct GenerateOptions {
+    pub config: ThumbnailConfig,
+    /// Number of workers, one per CPU if 0
+    pub threads: usize,
+    /// Generate the thumbnails which are already stored as well
+    pub force: bool,
+}
+
+/// State of the generation, reported after every image
+#[derive(Debug)]
+pub struct GenerateProgress<'a> {
+    pub done: usize,
+    /// Images to generate a thumbnail of, in total
+    pub total: usize,
+    pub failed: usize,
+    /// Image which has just been processed
+    pub current_path: &'a Path,
+}
+
+/// Generate the missing thumbnails of the images of `index` on a pool
+/// of workers, calling `progress` after every image.
+///
+/// Resources which are not images, as told by their extension, are
+/// skipped. Every image is attempted and the failures are reported
+/// along with their id.
+pub fn generate_all<Id, F>(
+    index: &ResourceIndex<Id>,
+    options: &GenerateOptions,
+    mut progress: F,
+) -> BulkResult<(), Id>
+where
+    Id: ResourceId + Send + Sync,
+    F: FnMut(&GenerateProgress),
+{
+    let start = Instant::now();
+    let root = index.root();
+    let images: Vec<(Id, PathBuf)> = index
+        .id2path
+        .iter()
+        .filter(|(_, path)| ImageFormat::from_path(path.as_path()).is_ok())
+        .filter(|(id, _)| {
+            options.force || !thumbnail_path(root, (*id).clone()).exists()
+        })
+        .map(|(id, path)| (id.clone(), path.as_path().to_owned()))
+        .collect();
+    let total = images.len();
+    let threads = match options.threads {
+        0 => thread::available_parallelism().map_or(1, usize::from),
+        threads => threads,
+    }
+    .min(total.max(1));
+    log::info!("Generating {} thumbnails on {} threads", total, threads);
+
+    let mut report = BulkResult::new();
+    let next = AtomicUsize::new(0);
+    let (sender, receiver) = mpsc::channel::<(usize, Result<()>)>();
+    thread::scope(|scope| {
+        for _ in 0..threads {
+            let sender = sender.clone();
+            let (images, next) = (&images, &next);
+            scope.spawn(move || loop {
+                let position = next.fetch_add(1, Ordering::Relaxed);
+                let Some((id, path)) = images.get(position) else {
+                    break;
+                };
+                let result =
+                    generate_thumbnail(root, path, id.clone(), &options.config)
+                        .map(|_| ());
+                if sender.send((position, result)).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(sender);
+
+        for (position, result) in receiver {
+            let (id, path) = &images[position];
+            report.record(id.clone(), result);
+            progress(&GenerateProgress {
+                done: report.len(),
+                total,
+                failed: report.failed.len(),
+                current_path: path,
+            });
+        }
+    });
+    report.elapsed = start.elapsed();
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use dev_hash::Crc32;
+    use fs_atomic_versions::initialize;
+    use image::RgbImage;
+    use tempdir::TempDir;
+
+    use super::*;
+    use crate::load_thumbnail;
+
+    #[test]
+    fn test_generate_all() {
+        initialize();
+
+        let dir = TempDir::new("arklib_test").unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        for (name, color) in [("a.png", 10), ("b.png", 20), ("c.jpg", 30)] {
+            RgbImage::from_pixel(8, 8, image::Rgb([color, 0, 0]))
+                .save(root.join(name))
+                .unwrap();
+        }
+        fs::write(root.join("notes.txt"), "not an image").unwrap();
+        fs::write(root.join("broken.png"), "not an image either").unwrap();
+
+        let index = ResourceIndex::<Crc32>::build(&root);
+        let options = GenerateOptions {
+            threads: 2,
+            ..Default::default()
+        };
+        let mut reported = Vec::new();
+        let report = generate_all(&index, &options, |progress| {
+            reported.push((progress.done, progress.total));
+        });
+        assert_eq!(report.succeeded.len(), 3);
+        assert_eq!(report.failed.len(), 1);
+        assert_eq!(reported.last(), Some(&(4, 4)));
+        for (id, _) in &report.succeeded {
+            assert!(load_thumbnail(&root, id.clone())
+                .unwrap()
+                .is_some());
+        }
+
+        // only the broken image is attempted again
+        let report = generate_all(&index, &options, |_| {});
+        assert_eq!(report.len(), 1);
+        assert!(report.has_failures());
+    }
+}
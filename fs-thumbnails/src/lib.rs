@@ -0,0 +1,231 @@
+mod batch;
+
+pub use batch::{generate_all, GenerateOptions, GenerateProgress};
+
+use std::io::{Cursor, Write};
+use std::path::{Path, PathBuf};
+
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::webp::WebPEncoder;
+use image::{DynamicImage, ImageError};
+
+use data_error::{ArklibError, Result, ResultExt};
+use data_resource::ResourceId;
+use fs_atomic_versions::atomic::AtomicFile;
+use fs_atomic_versions::durability::Durability;
+use fs_storage::{ARK_FOLDER, THUMBNAILS_STORAGE_FOLDER};
+
+/// Encoding of thumbnails
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThumbnailFormat {
+    /// Lossy, `quality` ranging from 1 to 100
+    Jpeg { quality: u8 },
+    /// Lossless, keeping transparency
+    WebP,
+}
+
+/// How thumbnails are generated
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ThumbnailConfig {
+    /// Maximum width, the aspect ratio of the image is kept
+    pub width: u32,
+    /// Maximum height, the aspect ratio of the image is kept
+    pub height: u32,
+    pub format: ThumbnailFormat,
+}
+
+impl Default for ThumbnailConfig {
+    fn default() -> Self {
+        ThumbnailConfig {
+            width: 256,
+            height: 256,
+            format: ThumbnailFormat::Jpeg { quality: 85 },
+        }
+    }
+}
+
+/// Generate the thumbnail of the image at `path` and store it
+/// under `.ark/cache/thumbnails/<id>`, replacing the previous one
+pub fn generate_thumbnail<P: AsRef<Path>, Id: ResourceId>(
+    root: P,
+    path: &Path,
+    id: Id,
+    config: &ThumbnailConfig,
+) -> Result<Vec<u8>> {
+    let image = image::open(path)
+        .map_err(image_error)
+        .with_path(path)?;
+    let thumbnail =
+        encode(&image.thumbnail(config.width, config.height), config.format)
+            .with_path(path)?;
+
+    // thumbnails can be generated again
+    let file = AtomicFile::new(thumbnail_path(root, id))?
+        .with_durability(Durability::Fast);
+    let tmp = file.make_temp().with_path(&file.directory)?;
+    (&tmp)
+        .write_all(&thumbnail)
+        .with_path(&file.directory)?;
+    let current = file.load().with_path(&file.directory)?;
+    file.compare_and_swap(&current, tmp)
+        .with_path(&file.directory)?;
+    Ok(thumbnail)
+}
+
+/// The stored thumbnail of the resource, if any
+pub fn load_thumbnail<P: AsRef<Path>, Id: ResourceId>(
+    root: P,
+    id: Id,
+) -> Result<Option<Vec<u8>>> {
+    let path = thumbnail_path(root, id);
+    if !path.exists() {
+        return Ok(None);
+    }
+    let file = AtomicFile::new(&path)?;
+    let latest = file.load().with_path(&path)?;
+    match latest.open().with_path(&latest.path)? {
+        Some(_) => Ok(Some(latest.read_content().with_path(&latest.path)?)),
+        None => Ok(None),
+    }
+}
+
+/// The stored thumbnail of the resource, generated from the image at `path`
+/// if there is none.
+///
+/// A stored thumbnail is returned as is, even if it was generated with
+/// another config: call [`remove_thumbnail`] first to regenerate it.
+pub fn get_or_generate_thumbnail<P: AsRef<Path>, Id: ResourceId>(
+    root: P,
+    path: &Path,
+    id: Id,
+    config: &ThumbnailConfig,
+) -> Result<Vec<u8>> {
+    match load_thumbnail(&root, id.clone())? {
+        Some(thumbnail) => Ok(thumbnail),
+        None => generate_thumbnail(root, path, id, config),
+    }
+}
+
+/// Remove the stored thumbnail of the resource, e.g. when its content
+/// changed. Thumbnails are a cache, so all versions are removed at once.
+pub fn remove_thumbnail<P: AsRef<Path>, Id: ResourceId>(
+    root: P,
+    id: Id,
+) -> Result<()> {
+    let path = thumbnail_path(root, id);
+    match std::fs::remove_dir_all(&path) {
+        Err(err) if err.kind() != std::io::ErrorKind::NotFound => {
+            Err(err).with_path(&path)
+        }
+        _ => Ok(()),
+    }
+}
+
+fn thumbnail_path<P: AsRef<Path>, Id: ResourceId>(root: P, id: Id) -> PathBuf {
+    root.as_ref()
+        .join(ARK_FOLDER)
+        .join(THUMBNAILS_STORAGE_FOLDER)
+        .join(id.to_string())
+}
+
+fn encode(image: &DynamicImage, format: ThumbnailFormat) -> Result<Vec<u8>> {
+    let mut buffer = Cursor::new(Vec::new());
+    match format {
+        ThumbnailFormat::Jpeg { quality } => {
+            // JPEG has no alpha channel
+            DynamicImage::ImageRgb8(image.to_rgb8()).write_with_encoder(
+                JpegEncoder::new_with_quality(
+                    &mut buffer,
+                    quality.clamp(1, 100),
+                ),
+            )
+        }
+        ThumbnailFormat::WebP => DynamicImage::ImageRgba8(image.to_rgba8())
+            .write_with_encoder(WebPEncoder::new_lossless(&mut buffer)),
+    }
+    .map_err(image_error)?;
+    Ok(buffer.into_inner())
+}
+
+fn image_error(err: ImageError) -> ArklibError {
+    ArklibError::Storage("thumbnails".to_owned(), err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use fs_atomic_versions::initialize;
+    use image::{ImageFormat, RgbImage};
+    use tempdir::TempDir;
+
+    use dev_hash::Crc32;
+
+    use super::*;
+
+    fn source_image(root: &Path) -> PathBuf {
+        let path = root.join("image.png");
+        RgbImage::from_pixel(64, 32, image::Rgb([200, 100, 50]))
+            .save(&path)
+            .unwrap();
+        path
+    }
+
+    #[test]
+    fn test_get_or_generate() {
+        initialize();
+
+        let dir = TempDir::new("arklib_test").unwrap();
+        let root = dir.path();
+        let path = source_image(root);
+        let id = Crc32(0x342a3d4a);
+        let config = ThumbnailConfig {
+            width: 16,
+            height: 16,
+            ..Default::default()
+        };
+
+        assert_eq!(load_thumbnail(root, id.clone()).unwrap(), None);
+        let thumbnail =
+            get_or_generate_thumbnail(root, &path, id.clone(), &config)
+                .unwrap();
+        assert_eq!(image::guess_format(&thumbnail).unwrap(), ImageFormat::Jpeg);
+        let decoded = image::load_from_memory(&thumbnail).unwrap();
+        assert_eq!((decoded.width(), decoded.height()), (16, 8));
+
+        // the stored thumbnail is returned, whatever the config
+        std::fs::remove_file(&path).unwrap();
+        let cached = get_or_generate_thumbnail(
+            root,
+            &path,
+            id.clone(),
+            &ThumbnailConfig::default(),
+        )
+        .unwrap();
+        assert_eq!(cached, thumbnail);
+
+        remove_thumbnail(root, id.clone()).unwrap();
+        assert_eq!(load_thumbnail(root, id).unwrap(), None);
+    }
+
+    #[test]
+    fn test_webp_format() {
+        initialize();
+
+        let dir = TempDir::new("arklib_test").unwrap();
+        let root = dir.path();
+        let path = source_image(root);
+        let config = ThumbnailConfig {
+            width: 32,
+            height: 8,
+            format: ThumbnailFormat::WebP,
+        };
+
+        let thumbnail =
+            generate_thumbnail(root, &path, Crc32(1), &config).unwrap();
+        assert_eq!(image::guess_format(&thumbnail).unwrap(), ImageFormat::WebP);
+        let decoded = image::load_from_memory(&thumbnail).unwrap();
+        assert_eq!((decoded.width(), decoded.height()), (16, 8));
+
+        let missing = root.join("missing.png");
+        assert!(generate_thumbnail(root, &missing, Crc32(2), &config).is_err());
+    }
+}
@@ -0,0 +1,14 @@
+#![no_main]
+
+use fs_storage::file_storage::FileStorage;
+use libfuzzer_sys::fuzz_target;
+use tempdir::TempDir;
+
+// Covers both the legacy version 2 format and the JSON format
+fuzz_target!(|data: &[u8]| {
+    let temp_dir = TempDir::new("fuzz").unwrap();
+    let path = temp_dir.path().join("storage");
+    std::fs::write(&path, data).unwrap();
+
+    let _ = FileStorage::<String, String>::new("fuzz".to_string(), &path);
+});
@@ -0,0 +1,11 @@
+#![no_main]
+
+use std::str::FromStr;
+
+use dev_hash::{Blake3, Crc32};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &str| {
+    let _ = Crc32::from_str(data);
+    let _ = Blake3::from_str(data);
+});